@@ -0,0 +1,27 @@
+#!/bin/bash
+set -e
+
+make
+
+rm -rf occlum_exec_allowlist && mkdir occlum_exec_allowlist && cd occlum_exec_allowlist
+occlum init
+cp ../hello_world image/bin
+# A copy with one more byte at the end, which still runs but has another hash
+cp ../hello_world image/bin/hello_world_denied
+echo >> image/bin/hello_world_denied
+
+# Only hello_world and ld.so are allowed to be executed
+allowlist="$(sha256sum image/bin/hello_world image/lib/ld-musl-x86_64.so.1 | \
+    awk '{print $1}' | jq -R . | jq -s .)"
+new_json="$(jq --argjson allowlist "${allowlist}" \
+    '.process.exec_allowlist = $allowlist' Occlum.json)" && \
+echo "${new_json}" > Occlum.json
+
+occlum build
+echo -e "\nBuild done. Running the allowed and the denied binaries ..."
+occlum run /bin/hello_world
+if occlum run /bin/hello_world_denied; then
+    echo "Error: the binary not in the exec allowlist is executed"
+    exit 1
+fi
+echo "The binary not in the exec allowlist is denied as expected"
@@ -0,0 +1,66 @@
+#!/bin/bash
+set -e
+
+make
+
+rm -rf occlum_mount_conf && mkdir occlum_mount_conf && cd occlum_mount_conf
+occlum init
+cp ../hello_world image/bin
+mkdir -p image/etc host_data app/bin
+touch image/etc/host_data.hashes image/etc/models.hashes image/etc/app_bin.sha256
+
+# The mount points of all the types and options beyond the default ones
+mounts='[{
+    "target": "/share",
+    "type": "9p",
+    "source": "./9p.sock",
+    "options": { "aname": "/srv/share" }
+}, {
+    "target": "/models",
+    "type": "httpfs",
+    "options": {
+        "url": "https://10.0.0.1:8443/models",
+        "page_hashes": "/etc/models.hashes"
+    }
+}, {
+    "target": "/data",
+    "type": "blockfs",
+    "source": "./blockfs",
+    "options": { "size": "64MB" }
+}, {
+    "target": "/host_data",
+    "type": "hostfs",
+    "source": "./host_data",
+    "options": { "page_hashes": "/etc/host_data.hashes" }
+}, {
+    "target": "/app",
+    "type": "sefs",
+    "source": "./app",
+    "options": {
+        "integrity_only_paths": ["bin"],
+        "file_hashes": "/etc/app_bin.sha256"
+    }
+}]'
+new_json="$(jq --argjson mounts "${mounts}" '.mount += $mounts' Occlum.json)" && \
+echo "${new_json}" > Occlum.json
+
+occlum build
+echo -e "\nBuild done. Checking the mount points in the config of the enclave ..."
+# The root is generated from the image, and a SEFS without a source is put
+# under run/mount, while the other mount points are kept as they are
+expected="$(jq -S '.mount[1:] | map(
+    if .type == "sefs" and .source == null
+    then .source = "./run/mount" + .target
+    else . end)' Occlum.json)"
+actual="$(jq -S '.mount[1:]' build/Occlum.json)"
+if [ "${expected}" != "${actual}" ]; then
+    echo "Error: the mount points are not the ones in Occlum.json"
+    diff <(echo "${expected}") <(echo "${actual}") || true
+    exit 1
+fi
+root_layers="$(jq -c '[.mount[0].options.layers[].source]' build/Occlum.json)"
+if [ "${root_layers}" != '["./build/mount/__ROOT","./run/mount/__ROOT"]' ]; then
+    echo "Error: the root is not generated from the image"
+    exit 1
+fi
+echo "The mount points are passed to the enclave as expected"
@@ -0,0 +1,86 @@
+// Map a file on HostFS whose pages are verified against the trusted page hashes,
+// and check the content of every page, except the tampered one given by the
+// arguments, which must fail to be read.
+//
+// Usage: mmap_window <path> [tampered <page index> | truncated]
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+
+#define PAGE_SIZE 4096
+
+int main(int argc, const char *argv[]) {
+    if (argc != 2 && !(argc == 4 && strcmp(argv[2], "tampered") == 0) &&
+            !(argc == 3 && strcmp(argv[2], "truncated") == 0)) {
+        printf("Usage: %s <path> [tampered <page index> | truncated]\n", argv[0]);
+        return -1;
+    }
+    const char *path = argv[1];
+    int is_truncated = argc == 3;
+    long tampered_page = argc == 4 ? atol(argv[3]) : -1;
+
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        printf("ERROR: failed to open %s\n", path);
+        return -1;
+    }
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0) {
+        printf("ERROR: failed to stat %s\n", path);
+        return -1;
+    }
+    // The size of a truncated file is not trusted; map the size it used to have
+    size_t size = is_truncated ? 4 * PAGE_SIZE : stat_buf.st_size;
+    char *addr = mmap(NULL, size, PROT_READ, MAP_PRIVATE, fd, 0);
+    if (is_truncated) {
+        if (addr != MAP_FAILED || errno != EIO) {
+            printf("ERROR: the truncated file is mapped without EIO\n");
+            return -1;
+        }
+        printf("The truncated file fails to be mapped with EIO as expected\n");
+        return 0;
+    }
+    if (addr == MAP_FAILED) {
+        printf("ERROR: failed to mmap %s\n", path);
+        return -1;
+    }
+
+    int null_fd = open("/dev/null", O_WRONLY);
+    if (null_fd < 0) {
+        printf("ERROR: failed to open /dev/null\n");
+        return -1;
+    }
+    static char buf[PAGE_SIZE];
+    size_t num_pages = (size + PAGE_SIZE - 1) / PAGE_SIZE;
+    for (size_t page_i = 0; page_i < num_pages; page_i++) {
+        size_t offset = page_i * PAGE_SIZE;
+        size_t len = size - offset < PAGE_SIZE ? size - offset : PAGE_SIZE;
+        if ((long)page_i == tampered_page) {
+            // The page is populated by the system call, which fails the verification
+            if (write(null_fd, addr + offset, len) >= 0 || errno != EIO) {
+                printf("ERROR: the tampered page %zu is read without EIO\n", page_i);
+                return -1;
+            }
+            continue;
+        }
+        // The page is populated on the first access by the user
+        if (pread(fd, buf, len, offset) != len || memcmp(addr + offset, buf, len) != 0) {
+            printf("ERROR: the content of page %zu is wrong\n", page_i);
+            return -1;
+        }
+        if (write(null_fd, addr + offset, len) != len) {
+            printf("ERROR: failed to write page %zu\n", page_i);
+            return -1;
+        }
+    }
+    munmap(addr, size);
+    close(null_fd);
+    close(fd);
+    printf("All the %zu pages of %s are checked\n", num_pages, path);
+    return 0;
+}
@@ -0,0 +1,35 @@
+#!/bin/bash
+set -e
+
+make
+
+rm -rf occlum_instance && mkdir occlum_instance && cd occlum_instance
+occlum init
+cp ../mmap_window image/bin
+
+# The files on the host, whose trusted page hashes are kept in the image
+mkdir host_data
+head -c $((4096 * 4 + 100)) /dev/urandom > host_data/data
+cp host_data/data host_data/tampered
+cp host_data/data host_data/truncated
+mkdir -p image/etc
+for file in data tampered truncated; do
+    hashes="$(split -b 4096 --filter=sha256sum host_data/${file} | awk '{print $1}')"
+    echo "${file} $(stat -c %s host_data/${file}) ${hashes//$'\n'/ }" >> image/etc/host_data.hashes
+done
+# Modify one byte of page 2, and drop the last two pages
+printf '\xff' | dd of=host_data/tampered bs=1 seek=$((4096 * 2 + 10)) conv=notrunc
+truncate -s $((4096 * 2)) host_data/truncated
+
+new_json="$(jq '.mount += [{
+    "target": "/host_data",
+    "type": "hostfs",
+    "source": "./host_data",
+    "options": { "page_hashes": "/etc/host_data.hashes" } }]' Occlum.json)" && \
+echo "${new_json}" > Occlum.json
+
+occlum build
+echo -e "\nBuild done. Mapping the intact, tampered and truncated files ..."
+occlum run /bin/mmap_window /host_data/data
+occlum run /bin/mmap_window /host_data/tampered tampered 2
+occlum run /bin/mmap_window /host_data/truncated truncated
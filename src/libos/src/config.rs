@@ -1,11 +1,14 @@
 use super::*;
 use crate::std::untrusted::path::PathEx;
+use crate::syscall::SyscallNum;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Component, Path, PathBuf};
 use std::sgxfs::SgxFile;
+use std::time::Duration;
 
 lazy_static! {
     pub static ref LIBOS_CONFIG: Config = {
@@ -83,6 +86,33 @@ fn parse_mac(mac_str: &str) -> Result<sgx_aes_gcm_128bit_tag_t> {
     Ok(mac)
 }
 
+fn parse_sha256_hash(hash_str: &str) -> Result<sgx_sha256_hash_t> {
+    let mut hash: sgx_sha256_hash_t = Default::default();
+    if hash_str.len() != hash.len() * 2 || !hash_str.is_ascii() {
+        return_errno!(
+            EINVAL,
+            "The length or format of SHA-256 hash string is invalid"
+        );
+    }
+    for (byte_i, byte) in hash.iter_mut().enumerate() {
+        *byte =
+            u8::from_str_radix(&hash_str[byte_i * 2..byte_i * 2 + 2], 16).map_err(|e| errno!(e))?;
+    }
+    Ok(hash)
+}
+
+fn parse_syscall_name(name: &str) -> Result<SyscallNum> {
+    let num = SyscallNum::from_libc_name(name)
+        .ok_or_else(|| errno!(EINVAL, "unknown system call in the syscall denylist"))?;
+    // The system calls through which the LibOS itself returns to the user
+    match num {
+        SyscallNum::RtSigreturn | SyscallNum::HandleException | SyscallNum::HandleInterrupt => {
+            return_errno!(EINVAL, "the system call cannot be denied")
+        }
+        _ => Ok(num),
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub resource_limits: ConfigResourceLimits,
@@ -90,6 +120,11 @@ pub struct Config {
     pub env: ConfigEnv,
     pub entry_points: Vec<PathBuf>,
     pub mount: Vec<ConfigMount>,
+    pub network: ConfigNetwork,
+    pub devices: ConfigDevices,
+    pub watchdog: ConfigWatchdog,
+    pub time: ConfigTime,
+    pub sync: ConfigSync,
 }
 
 #[derive(Debug)]
@@ -102,6 +137,12 @@ pub struct ConfigProcess {
     pub default_stack_size: usize,
     pub default_heap_size: usize,
     pub default_mmap_size: usize,
+    pub mem_pressure_threshold: usize,
+    /// The SHA-256 hashes of the files that are allowed to be executed, if the
+    /// allowlist is enabled
+    pub exec_allowlist: Option<HashSet<sgx_sha256_hash_t>>,
+    /// The system calls that fail with EPERM
+    pub syscall_denylist: Vec<SyscallNum>,
 }
 
 #[derive(Debug)]
@@ -110,29 +151,240 @@ pub struct ConfigEnv {
     pub untrusted: HashSet<String>,
 }
 
+#[derive(Debug)]
+pub struct ConfigNetwork {
+    /// The names of the network interfaces that sockets can be bound to by SO_BINDTODEVICE
+    pub bind_devices: HashSet<String>,
+    /// The rate limits of the data sent by sockets
+    pub egress_limits: Vec<ConfigEgressLimit>,
+    /// The in-enclave TCP/IP stack, which serves the TCP sockets if enabled
+    pub stack: Option<ConfigNetStack>,
+    /// The TLS termination of the host sockets, which is done in the enclave
+    pub tls: Option<ConfigNetTls>,
+    /// The abstract unix sockets that are bridged to the D-Bus sockets of the host
+    pub dbus_bridges: Vec<ConfigDbusBridge>,
+    /// The buffering of the small sends and receives of the host sockets
+    pub buffering: Option<ConfigNetBuffering>,
+    /// The asynchronous OCalls of the sends and receives of the host sockets
+    pub async_io: Option<ConfigNetAsyncIo>,
+    /// The address families of the sockets that can be created, or all of them if None
+    pub address_families: Option<HashSet<libc::c_int>>,
+    /// The rules of the addresses that the host sockets can talk to
+    pub firewall: Option<ConfigFirewall>,
+    /// The paths that the ends of socketpairs can be bound to, which are relayed to
+    /// the unix sockets of the host
+    pub unix_relays: Vec<ConfigUnixRelay>,
+    /// The name resolution by the resolver of the host, which is disabled if None
+    pub host_dns: Option<ConfigHostDns>,
+    /// The TCP keepalive enabled on the host sockets, which detects the dead peers
+    /// of the idle connections
+    pub keepalive: Option<ConfigNetKeepalive>,
+}
+
+#[derive(Debug)]
+pub struct ConfigNetKeepalive {
+    /// The idle time of a connection before the first probe is sent
+    pub idle_secs: u32,
+    /// The interval between the probes
+    pub interval_secs: u32,
+    /// The number of the unanswered probes before the peer is considered dead
+    pub count: u32,
+}
+
+#[derive(Debug)]
+pub struct ConfigHostDns {
+    /// The max number of the addresses returned by a resolution
+    pub max_results: usize,
+    /// The addresses that the critical hostnames must resolve to, where the names
+    /// are in lowercase without the trailing dot
+    pub pinned: HashMap<String, Vec<IpAddr>>,
+}
+
+#[derive(Debug)]
+pub struct ConfigFirewall {
+    /// The action of the addresses that match none of the rules
+    pub default: ConfigFirewallAction,
+    /// The rules, where the first one that matches an address decides its action
+    pub rules: Vec<ConfigFirewallRule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFirewallAction {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFirewallDirection {
+    /// The addresses that are bound to, and the peers of the accepted connections
+    Inbound,
+    /// The addresses that are connected or sent to
+    Outbound,
+}
+
+/// A rule of the firewall, whose conditions that are None match any address
+#[derive(Debug)]
+pub struct ConfigFirewallRule {
+    pub action: ConfigFirewallAction,
+    pub direction: Option<ConfigFirewallDirection>,
+    pub family: Option<libc::c_int>,
+    pub cidr: Option<(IpAddr, u8)>,
+    pub ports: Option<(u16, u16)>,
+}
+
+#[derive(Debug)]
+pub struct ConfigNetStack {
+    /// The name of the tap device on the host, which carries the Ethernet frames
+    pub tap: String,
+    pub mac: [u8; 6],
+    pub addr: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Option<Ipv4Addr>,
+}
+
+#[derive(Debug)]
+pub struct ConfigNetBuffering {
+    /// The size of the sends staged before they are sent, which is 0 if the sends
+    /// are not staged
+    pub send_flush_size: usize,
+    /// The max size of the data prefetched by a receive, which is 0 if the receives
+    /// do not prefetch
+    pub recv_prefetch_size: usize,
+}
+
+#[derive(Debug)]
+pub struct ConfigNetAsyncIo {
+    /// The number of the host threads that serve the requests
+    pub num_threads: usize,
+}
+
+#[derive(Debug)]
+pub struct ConfigNetTls {
+    pub server: Option<ConfigTlsServer>,
+    pub client: Option<ConfigTlsClient>,
+}
+
+#[derive(Debug)]
+pub struct ConfigTlsServer {
+    /// The local ports of the TCP sockets whose accepted connections are TLS
+    pub ports: HashSet<u16>,
+    /// The paths of the PEM files of the certificate chain and the private key,
+    /// which are in the trusted root file system
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct ConfigTlsClient {
+    /// The remote ports of the TCP sockets whose connections are TLS
+    pub ports: HashSet<u16>,
+    /// The path of the PEM file of the trusted CAs in the trusted root file system
+    pub ca: PathBuf,
+    /// The name that the certificates of the servers are verified against
+    pub server_name: String,
+}
+
+#[derive(Debug)]
+pub struct ConfigDbusBridge {
+    /// The abstract name with a leading '@', which matches the names of the prefix
+    /// if it ends with '*'
+    pub name: String,
+    /// The path of the unix socket of the host
+    pub host_path: String,
+    /// The interfaces of the method calls and signals that can be sent to the host
+    pub interfaces: HashSet<String>,
+}
+
+#[derive(Debug)]
+pub struct ConfigUnixRelay {
+    /// The path given to bind in the enclave
+    pub path: String,
+    /// The path of the unix socket of the host, which is listened on by the relay
+    pub host_path: String,
+}
+
+#[derive(Debug)]
+pub struct ConfigEgressLimit {
+    pub target: ConfigEgressTarget,
+    /// The rate in bytes per second
+    pub rate: usize,
+    /// The max bytes that can be sent in a burst
+    pub burst: usize,
+}
+
+/// The data limited by an egress limit
+#[derive(Debug)]
+pub enum ConfigEgressTarget {
+    /// The data sent to the addresses in the CIDR block
+    Cidr { addr: IpAddr, prefix_len: u8 },
+    /// The data sent by the processes of the executable
+    Process(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct ConfigDevices {
+    /// The hardware devices that are emulated, instead of being denied, when opened
+    pub emulations: HashMap<PathBuf, ConfigDeviceEmulation>,
+}
+
+/// The safe emulation of a hardware device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigDeviceEmulation {
+    /// Behave like /dev/null
+    Null,
+    /// Behave like /dev/zero
+    Zero,
+}
+
+#[derive(Debug)]
+pub struct ConfigWatchdog {
+    /// The syscalls blocked in the host longer than the threshold are reported. None
+    /// means the watchdog is disabled.
+    pub ocall_threshold: Option<Duration>,
+    /// Whether the reported syscalls fail with EIO
+    pub fail_stuck_ocalls: bool,
+}
+
+#[derive(Debug)]
+pub struct ConfigTime {
+    /// The max adjustment of the realtime clock of the host since the LibOS reads it
+    /// first, beyond which the clock is clamped. None means no limit.
+    pub max_host_clock_adjustment: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct ConfigSync {
+    /// The interval of syncing the dirty data of the file systems. None means the
+    /// data is synced only when the applications ask to.
+    pub interval: Option<Duration>,
+}
+
 #[derive(Debug)]
 pub struct ConfigMount {
-    pub type_: ConfigMountFsType,
+    /// The type of the file system, which is checked when it is mounted
+    pub type_: String,
     pub target: PathBuf,
     pub source: Option<PathBuf>,
     pub options: ConfigMountOptions,
 }
 
-#[derive(Debug, PartialEq)]
-#[allow(non_camel_case_types)]
-pub enum ConfigMountFsType {
-    TYPE_SEFS,
-    TYPE_HOSTFS,
-    TYPE_RAMFS,
-    TYPE_UNIONFS,
-}
-
 #[derive(Debug)]
 pub struct ConfigMountOptions {
     pub integrity_only: bool,
     pub mac: Option<sgx_aes_gcm_128bit_tag_t>,
     pub layers: Option<Vec<ConfigMount>>,
     pub temporary: bool,
+    pub page_hashes: Option<PathBuf>,
+    /// The name of the tree to attach on the 9P server
+    pub aname: Option<String>,
+    /// The URL of the share served over HTTPS
+    pub url: Option<String>,
+    /// The size of the block device to create
+    pub size: Option<usize>,
+    /// The paths of a writable SEFS that are integrity-only, relative to the target
+    pub integrity_only_paths: Vec<PathBuf>,
+    /// The manifest of the hashes of the files at the integrity-only paths
+    pub file_hashes: Option<PathBuf>,
 }
 
 impl Config {
@@ -158,12 +410,22 @@ impl Config {
             }
             mount
         };
+        let network = ConfigNetwork::from_input(&input.network)?;
+        let devices = ConfigDevices::from_input(&input.devices)?;
+        let watchdog = ConfigWatchdog::from_input(&input.watchdog)?;
+        let time = ConfigTime::from_input(&input.time)?;
+        let sync = ConfigSync::from_input(&input.sync)?;
         Ok(Config {
             resource_limits,
             process,
             env,
             entry_points,
             mount,
+            network,
+            devices,
+            watchdog,
+            time,
+            sync,
         })
     }
 }
@@ -180,10 +442,28 @@ impl ConfigProcess {
         let default_stack_size = parse_memory_size(&input.default_stack_size)?;
         let default_heap_size = parse_memory_size(&input.default_heap_size)?;
         let default_mmap_size = parse_memory_size(&input.default_mmap_size)?;
+        let mem_pressure_threshold = parse_memory_size(&input.mem_pressure_threshold)?;
+        let exec_allowlist = match &input.exec_allowlist {
+            Some(hashes) => Some(
+                hashes
+                    .iter()
+                    .map(|hash| parse_sha256_hash(hash))
+                    .collect::<Result<HashSet<sgx_sha256_hash_t>>>()?,
+            ),
+            None => None,
+        };
+        let syscall_denylist = input
+            .syscall_denylist
+            .iter()
+            .map(|name| parse_syscall_name(name))
+            .collect::<Result<Vec<SyscallNum>>>()?;
         Ok(ConfigProcess {
             default_stack_size,
             default_heap_size,
             default_mmap_size,
+            mem_pressure_threshold,
+            exec_allowlist,
+            syscall_denylist,
         })
     }
 }
@@ -197,19 +477,503 @@ impl ConfigEnv {
     }
 }
 
-impl ConfigMount {
-    fn from_input(input: &InputConfigMount) -> Result<ConfigMount> {
-        const ALL_FS_TYPES: [&str; 4] = ["sefs", "hostfs", "ramfs", "unionfs"];
-
-        let type_ = match input.type_.as_str() {
-            "sefs" => ConfigMountFsType::TYPE_SEFS,
-            "hostfs" => ConfigMountFsType::TYPE_HOSTFS,
-            "ramfs" => ConfigMountFsType::TYPE_RAMFS,
-            "unionfs" => ConfigMountFsType::TYPE_UNIONFS,
-            _ => {
-                return_errno!(EINVAL, "Unsupported file system type");
+impl ConfigNetwork {
+    fn from_input(input: &InputConfigNetwork) -> Result<ConfigNetwork> {
+        for name in &input.bind_devices {
+            if !is_valid_ifname(name) {
+                return_errno!(EINVAL, "invalid name of network interface");
             }
+        }
+        let egress_limits = input
+            .egress_limits
+            .iter()
+            .map(ConfigEgressLimit::from_input)
+            .collect::<Result<Vec<_>>>()?;
+        let stack = match &input.stack {
+            Some(stack) => Some(ConfigNetStack::from_input(stack)?),
+            None => None,
+        };
+        let tls = match &input.tls {
+            Some(tls) => Some(ConfigNetTls::from_input(tls)?),
+            None => None,
+        };
+        let dbus_bridges = input
+            .dbus_bridges
+            .iter()
+            .map(ConfigDbusBridge::from_input)
+            .collect::<Result<Vec<_>>>()?;
+        let buffering = match &input.buffering {
+            Some(buffering) => Some(ConfigNetBuffering::from_input(buffering)?),
+            None => None,
         };
+        let async_io = match &input.async_io {
+            Some(async_io) => Some(ConfigNetAsyncIo::from_input(async_io)?),
+            None => None,
+        };
+        let address_families = match &input.address_families {
+            Some(families) => Some(
+                families
+                    .iter()
+                    .map(|family| parse_address_family(family))
+                    .collect::<Result<HashSet<_>>>()?,
+            ),
+            None => None,
+        };
+        let firewall = match &input.firewall {
+            Some(firewall) => Some(ConfigFirewall::from_input(firewall)?),
+            None => None,
+        };
+        let unix_relays = input
+            .unix_relays
+            .iter()
+            .map(ConfigUnixRelay::from_input)
+            .collect::<Result<Vec<_>>>()?;
+        let host_dns = match &input.host_dns {
+            Some(host_dns) => Some(ConfigHostDns::from_input(host_dns)?),
+            None => None,
+        };
+        let keepalive = match &input.keepalive {
+            Some(keepalive) => Some(ConfigNetKeepalive::from_input(keepalive)?),
+            None => None,
+        };
+        Ok(ConfigNetwork {
+            bind_devices: input.bind_devices.clone(),
+            egress_limits,
+            stack,
+            tls,
+            dbus_bridges,
+            buffering,
+            async_io,
+            address_families,
+            firewall,
+            unix_relays,
+            host_dns,
+            keepalive,
+        })
+    }
+}
+
+impl ConfigNetKeepalive {
+    fn from_input(input: &InputConfigNetKeepalive) -> Result<ConfigNetKeepalive> {
+        // The same ranges as TCP_KEEPIDLE, TCP_KEEPINTVL and TCP_KEEPCNT of Linux
+        const MAX_SECS: u32 = 32767;
+        const MAX_COUNT: u32 = 127;
+        if input.idle_secs == 0 || input.idle_secs > MAX_SECS {
+            return_errno!(EINVAL, "invalid idle time of keepalive");
+        }
+        if input.interval_secs == 0 || input.interval_secs > MAX_SECS {
+            return_errno!(EINVAL, "invalid interval of keepalive");
+        }
+        if input.count == 0 || input.count > MAX_COUNT {
+            return_errno!(EINVAL, "invalid count of keepalive");
+        }
+        Ok(ConfigNetKeepalive {
+            idle_secs: input.idle_secs,
+            interval_secs: input.interval_secs,
+            count: input.count,
+        })
+    }
+}
+
+impl ConfigHostDns {
+    fn from_input(input: &InputConfigHostDns) -> Result<ConfigHostDns> {
+        if input.max_results == 0 {
+            return_errno!(EINVAL, "the max results of host DNS must not be zero");
+        }
+        let mut pinned = HashMap::new();
+        for (name, addrs) in &input.pinned {
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            if name.is_empty() || addrs.is_empty() {
+                return_errno!(EINVAL, "invalid pinned name of host DNS");
+            }
+            let addrs = addrs
+                .iter()
+                .map(|addr| {
+                    addr.parse::<IpAddr>()
+                        .map_err(|_| errno!(EINVAL, "invalid pinned address of host DNS"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            pinned.insert(name, addrs);
+        }
+        Ok(ConfigHostDns {
+            max_results: input.max_results,
+            pinned,
+        })
+    }
+}
+
+impl ConfigFirewall {
+    fn from_input(input: &InputConfigFirewall) -> Result<ConfigFirewall> {
+        let default = parse_firewall_action(&input.default)?;
+        let rules = input
+            .rules
+            .iter()
+            .map(ConfigFirewallRule::from_input)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ConfigFirewall { default, rules })
+    }
+}
+
+impl ConfigFirewallRule {
+    fn from_input(input: &InputConfigFirewallRule) -> Result<ConfigFirewallRule> {
+        let action = parse_firewall_action(&input.action)?;
+        let direction = match input.direction.as_deref() {
+            Some("inbound") => Some(ConfigFirewallDirection::Inbound),
+            Some("outbound") => Some(ConfigFirewallDirection::Outbound),
+            None => None,
+            Some(_) => return_errno!(EINVAL, "invalid direction of firewall rule"),
+        };
+        let family = match input.family.as_deref() {
+            Some(family) => match parse_address_family(family)? {
+                family @ libc::AF_INET | family @ libc::AF_INET6 => Some(family),
+                _ => return_errno!(EINVAL, "the family of firewall rule must be inet or inet6"),
+            },
+            None => None,
+        };
+        let cidr = match &input.cidr {
+            Some(cidr) => Some(parse_cidr(cidr)?),
+            None => None,
+        };
+        let ports = match &input.ports {
+            Some(ports) => Some(parse_port_range(ports)?),
+            None => None,
+        };
+        Ok(ConfigFirewallRule {
+            action,
+            direction,
+            family,
+            cidr,
+            ports,
+        })
+    }
+}
+
+fn parse_firewall_action(action: &str) -> Result<ConfigFirewallAction> {
+    match action {
+        "allow" => Ok(ConfigFirewallAction::Allow),
+        "deny" => Ok(ConfigFirewallAction::Deny),
+        _ => return_errno!(EINVAL, "invalid action of firewall"),
+    }
+}
+
+/// Parse a port (e.g., "443") or an inclusive range of ports (e.g., "8000-8999")
+fn parse_port_range(ports: &str) -> Result<(u16, u16)> {
+    let parse_port = |port: &str| {
+        port.trim()
+            .parse::<u16>()
+            .map_err(|_| errno!(EINVAL, "invalid port of firewall rule"))
+    };
+    let range = match ports.find('-') {
+        Some(idx) => (parse_port(&ports[..idx])?, parse_port(&ports[idx + 1..])?),
+        None => {
+            let port = parse_port(ports)?;
+            (port, port)
+        }
+    };
+    if range.0 > range.1 {
+        return_errno!(EINVAL, "invalid range of ports of firewall rule");
+    }
+    Ok(range)
+}
+
+impl ConfigNetStack {
+    fn from_input(input: &InputConfigNetStack) -> Result<ConfigNetStack> {
+        if !is_valid_ifname(&input.tap) {
+            return_errno!(EINVAL, "invalid name of tap device");
+        }
+        let mac = match &input.mac {
+            Some(mac) => parse_ether_addr(mac)?,
+            // A locally administered address
+            None => [0x02, 0, 0, 0, 0, 0x01],
+        };
+        let (addr, prefix_len) = match parse_cidr(&input.addr)? {
+            (IpAddr::V4(addr), prefix_len) => (addr, prefix_len),
+            _ => return_errno!(EINVAL, "the address of the stack must be IPv4"),
+        };
+        let gateway = match &input.gateway {
+            Some(gateway) => Some(
+                gateway
+                    .parse::<Ipv4Addr>()
+                    .map_err(|_| errno!(EINVAL, "invalid gateway of the stack"))?,
+            ),
+            None => None,
+        };
+        Ok(ConfigNetStack {
+            tap: input.tap.clone(),
+            mac,
+            addr,
+            prefix_len,
+            gateway,
+        })
+    }
+}
+
+impl ConfigNetBuffering {
+    fn from_input(input: &InputConfigNetBuffering) -> Result<ConfigNetBuffering> {
+        // The buffers are allocated in the untrusted memory for each socket
+        const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+
+        let send_flush_size = parse_memory_size(&input.send_flush_size)?;
+        let recv_prefetch_size = parse_memory_size(&input.recv_prefetch_size)?;
+        if send_flush_size > MAX_BUFFER_SIZE || recv_prefetch_size > MAX_BUFFER_SIZE {
+            return_errno!(EINVAL, "the buffer of sockets is too large");
+        }
+        Ok(ConfigNetBuffering {
+            send_flush_size,
+            recv_prefetch_size,
+        })
+    }
+}
+
+impl ConfigNetAsyncIo {
+    fn from_input(input: &InputConfigNetAsyncIo) -> Result<ConfigNetAsyncIo> {
+        // Each thread spins on the queue for a while before it sleeps
+        const MAX_THREADS: usize = 16;
+
+        if input.threads == 0 || input.threads > MAX_THREADS {
+            return_errno!(EINVAL, "invalid number of the threads of async I/O");
+        }
+        Ok(ConfigNetAsyncIo {
+            num_threads: input.threads,
+        })
+    }
+}
+
+impl ConfigNetTls {
+    fn from_input(input: &InputConfigNetTls) -> Result<ConfigNetTls> {
+        let server = match &input.server {
+            Some(server) => Some(ConfigTlsServer {
+                ports: parse_tls_ports(&server.ports)?,
+                cert: parse_tls_file(&server.cert)?,
+                key: parse_tls_file(&server.key)?,
+            }),
+            None => None,
+        };
+        let client = match &input.client {
+            Some(client) => {
+                if client.server_name.is_empty() {
+                    return_errno!(EINVAL, "the server name of TLS is empty");
+                }
+                Some(ConfigTlsClient {
+                    ports: parse_tls_ports(&client.ports)?,
+                    ca: parse_tls_file(&client.ca)?,
+                    server_name: client.server_name.clone(),
+                })
+            }
+            None => None,
+        };
+        Ok(ConfigNetTls { server, client })
+    }
+}
+
+fn parse_tls_ports(ports: &HashSet<u16>) -> Result<HashSet<u16>> {
+    if ports.is_empty() || ports.contains(&0) {
+        return_errno!(EINVAL, "invalid ports of TLS");
+    }
+    Ok(ports.clone())
+}
+
+fn parse_tls_file(path: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return_errno!(EINVAL, "the path of a TLS file must be absolute");
+    }
+    Ok(path)
+}
+
+impl ConfigDbusBridge {
+    fn from_input(input: &InputConfigDbusBridge) -> Result<ConfigDbusBridge> {
+        let name = &input.name;
+        let wildcard_pos = name.find('*');
+        if !name.starts_with('@')
+            || name.len() == 1
+            || wildcard_pos.map_or(false, |pos| pos != name.len() - 1)
+        {
+            return_errno!(EINVAL, "invalid abstract name of D-Bus bridge");
+        }
+        // The path must fit in sun_path of sockaddr_un with the trailing NUL
+        const UNIX_PATH_MAX: usize = 108;
+        if !input.host_path.starts_with('/') || input.host_path.len() >= UNIX_PATH_MAX {
+            return_errno!(EINVAL, "invalid host path of D-Bus bridge");
+        }
+        if input.interfaces.is_empty() || input.interfaces.iter().any(|i| i.is_empty()) {
+            return_errno!(EINVAL, "invalid interfaces of D-Bus bridge");
+        }
+        Ok(ConfigDbusBridge {
+            name: name.clone(),
+            host_path: input.host_path.clone(),
+            interfaces: input.interfaces.clone(),
+        })
+    }
+}
+
+impl ConfigUnixRelay {
+    fn from_input(input: &InputConfigUnixRelay) -> Result<ConfigUnixRelay> {
+        // The paths must fit in sun_path of sockaddr_un with the trailing NUL
+        const UNIX_PATH_MAX: usize = 108;
+        if input.path.is_empty() || input.path.starts_with('@') || input.path.len() >= UNIX_PATH_MAX
+        {
+            return_errno!(EINVAL, "invalid path of unix relay");
+        }
+        if !input.host_path.starts_with('/') || input.host_path.len() >= UNIX_PATH_MAX {
+            return_errno!(EINVAL, "invalid host path of unix relay");
+        }
+        Ok(ConfigUnixRelay {
+            path: input.path.clone(),
+            host_path: input.host_path.clone(),
+        })
+    }
+}
+
+fn parse_address_family(family: &str) -> Result<libc::c_int> {
+    let family = match family {
+        "unix" => libc::AF_UNIX,
+        "inet" => libc::AF_INET,
+        "inet6" => libc::AF_INET6,
+        "netlink" => libc::AF_NETLINK,
+        "packet" => libc::AF_PACKET,
+        _ => return_errno!(EINVAL, "unknown address family"),
+    };
+    Ok(family)
+}
+
+fn is_valid_ifname(name: &str) -> bool {
+    const IFNAMSIZ: usize = 16;
+    !name.is_empty() && name.len() < IFNAMSIZ && !name.contains('/')
+}
+
+fn parse_ether_addr(addr: &str) -> Result<[u8; 6]> {
+    let mut bytes = [0u8; 6];
+    let mut parts = addr.split(':');
+    for byte in bytes.iter_mut() {
+        *byte = parts
+            .next()
+            .filter(|part| part.len() == 2)
+            .and_then(|part| u8::from_str_radix(part, 16).ok())
+            .ok_or_else(|| errno!(EINVAL, "invalid MAC address"))?;
+    }
+    // A multicast address cannot be the address of an interface
+    if parts.next().is_some() || bytes[0] & 0x01 != 0 {
+        return_errno!(EINVAL, "invalid MAC address");
+    }
+    Ok(bytes)
+}
+
+impl ConfigEgressLimit {
+    fn from_input(input: &InputConfigEgressLimit) -> Result<ConfigEgressLimit> {
+        let target = match (&input.cidr, &input.process) {
+            (Some(cidr), None) => {
+                let (addr, prefix_len) = parse_cidr(cidr)?;
+                ConfigEgressTarget::Cidr { addr, prefix_len }
+            }
+            (None, Some(process)) => {
+                let path = PathBuf::from(process);
+                if !path.is_absolute() {
+                    return_errno!(
+                        EINVAL,
+                        "the process of egress limit must be an absolute path"
+                    );
+                }
+                ConfigEgressTarget::Process(path)
+            }
+            _ => return_errno!(EINVAL, "either cidr or process of egress limit is expected"),
+        };
+        let rate = parse_memory_size(&input.rate)?;
+        if rate == 0 {
+            return_errno!(EINVAL, "the rate of egress limit must not be zero");
+        }
+        // The burst is one second of data by default
+        let burst = match &input.burst {
+            Some(burst) => parse_memory_size(burst)?,
+            None => rate,
+        };
+        if burst == 0 {
+            return_errno!(EINVAL, "the burst of egress limit must not be zero");
+        }
+        Ok(ConfigEgressLimit {
+            target,
+            rate,
+            burst,
+        })
+    }
+}
+
+fn parse_cidr(cidr: &str) -> Result<(IpAddr, u8)> {
+    let (addr_str, prefix_len_str) = match cidr.find('/') {
+        Some(idx) => (&cidr[..idx], &cidr[idx + 1..]),
+        None => return_errno!(EINVAL, "no prefix length in CIDR"),
+    };
+    let addr = addr_str
+        .parse::<IpAddr>()
+        .map_err(|_| errno!(EINVAL, "invalid address in CIDR"))?;
+    let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix_len = match prefix_len_str.parse::<u8>() {
+        Ok(len) if len <= max_prefix_len => len,
+        _ => return_errno!(EINVAL, "invalid prefix length in CIDR"),
+    };
+    Ok((addr, prefix_len))
+}
+
+impl ConfigDevices {
+    fn from_input(input: &InputConfigDevices) -> Result<ConfigDevices> {
+        let mut emulations = HashMap::new();
+        for (path, emulation) in &input.emulations {
+            let path = PathBuf::from(path);
+            if !crate::fs::is_hw_device(&path) {
+                return_errno!(EINVAL, "only hardware devices can be emulated");
+            }
+            let emulation = match emulation.as_str() {
+                "null" => ConfigDeviceEmulation::Null,
+                "zero" => ConfigDeviceEmulation::Zero,
+                _ => return_errno!(EINVAL, "unsupported device emulation"),
+            };
+            emulations.insert(path, emulation);
+        }
+        Ok(ConfigDevices { emulations })
+    }
+}
+
+impl ConfigWatchdog {
+    fn from_input(input: &InputConfigWatchdog) -> Result<ConfigWatchdog> {
+        let ocall_threshold = match input.ocall_threshold_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        if ocall_threshold.is_none() && input.fail_stuck_ocalls {
+            return_errno!(EINVAL, "fail_stuck_ocalls requires ocall_threshold_ms");
+        }
+        Ok(ConfigWatchdog {
+            ocall_threshold,
+            fail_stuck_ocalls: input.fail_stuck_ocalls,
+        })
+    }
+}
+
+impl ConfigTime {
+    fn from_input(input: &InputConfigTime) -> Result<ConfigTime> {
+        let max_host_clock_adjustment = match input.max_host_clock_adjustment_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        Ok(ConfigTime {
+            max_host_clock_adjustment,
+        })
+    }
+}
+
+impl ConfigSync {
+    fn from_input(input: &InputConfigSync) -> Result<ConfigSync> {
+        let interval = match input.interval_ms {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        };
+        Ok(ConfigSync { interval })
+    }
+}
+
+impl ConfigMount {
+    fn from_input(input: &InputConfigMount) -> Result<ConfigMount> {
+        let type_ = input.type_.clone();
         let target = {
             let target = PathBuf::from(&input.target);
             if !target.starts_with("/") {
@@ -253,11 +1017,42 @@ impl ConfigMountOptions {
         } else {
             None
         };
+        let page_hashes = input.page_hashes.as_ref().map(PathBuf::from);
+        let size = match &input.size {
+            Some(size) => Some(parse_memory_size(size)?),
+            None => None,
+        };
+        let integrity_only_paths = input
+            .integrity_only_paths
+            .iter()
+            .map(|path| {
+                let path = PathBuf::from(path.trim_start_matches('/'));
+                if path.components().any(|c| c == Component::ParentDir) {
+                    return_errno!(EINVAL, "integrity-only path must not contain \"..\"");
+                }
+                Ok(path)
+            })
+            .collect::<Result<Vec<PathBuf>>>()?;
+        if !integrity_only_paths.is_empty() {
+            if integrity_only {
+                return_errno!(EINVAL, "integrity-only paths are for writable SEFS");
+            }
+            if input.file_hashes.is_none() {
+                return_errno!(EINVAL, "file hashes are expected for integrity-only paths");
+            }
+        }
+        let file_hashes = input.file_hashes.as_ref().map(PathBuf::from);
         Ok(ConfigMountOptions {
             integrity_only,
             mac,
             layers,
             temporary: input.temporary,
+            page_hashes,
+            aname: input.aname.clone(),
+            url: input.url.clone(),
+            size,
+            integrity_only_paths,
+            file_hashes,
         })
     }
 }
@@ -300,6 +1095,16 @@ struct InputConfig {
     pub entry_points: Vec<String>,
     #[serde(default)]
     pub mount: Vec<InputConfigMount>,
+    #[serde(default)]
+    pub network: InputConfigNetwork,
+    #[serde(default)]
+    pub devices: InputConfigDevices,
+    #[serde(default)]
+    pub watchdog: InputConfigWatchdog,
+    #[serde(default)]
+    pub time: InputConfigTime,
+    #[serde(default)]
+    pub sync: InputConfigSync,
 }
 
 #[derive(Deserialize, Debug)]
@@ -331,6 +1136,12 @@ struct InputConfigProcess {
     pub default_heap_size: String,
     #[serde(default = "InputConfigProcess::get_default_mmap_size")]
     pub default_mmap_size: String,
+    #[serde(default = "InputConfigProcess::get_mem_pressure_threshold")]
+    pub mem_pressure_threshold: String,
+    #[serde(default)]
+    pub exec_allowlist: Option<Vec<String>>,
+    #[serde(default)]
+    pub syscall_denylist: Vec<String>,
 }
 
 impl InputConfigProcess {
@@ -345,6 +1156,10 @@ impl InputConfigProcess {
     fn get_default_mmap_size() -> String {
         "32MB".to_string()
     }
+
+    fn get_mem_pressure_threshold() -> String {
+        "0B".to_string()
+    }
 }
 
 impl Default for InputConfigProcess {
@@ -353,6 +1168,9 @@ impl Default for InputConfigProcess {
             default_stack_size: InputConfigProcess::get_default_stack_size(),
             default_heap_size: InputConfigProcess::get_default_heap_size(),
             default_mmap_size: InputConfigProcess::get_default_mmap_size(),
+            mem_pressure_threshold: InputConfigProcess::get_mem_pressure_threshold(),
+            exec_allowlist: None,
+            syscall_denylist: Vec::new(),
         }
     }
 }
@@ -373,6 +1191,225 @@ impl Default for InputConfigEnv {
     }
 }
 
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetwork {
+    #[serde(default)]
+    pub bind_devices: HashSet<String>,
+    #[serde(default)]
+    pub egress_limits: Vec<InputConfigEgressLimit>,
+    #[serde(default)]
+    pub stack: Option<InputConfigNetStack>,
+    #[serde(default)]
+    pub tls: Option<InputConfigNetTls>,
+    #[serde(default)]
+    pub dbus_bridges: Vec<InputConfigDbusBridge>,
+    #[serde(default)]
+    pub buffering: Option<InputConfigNetBuffering>,
+    #[serde(default)]
+    pub async_io: Option<InputConfigNetAsyncIo>,
+    #[serde(default)]
+    pub address_families: Option<Vec<String>>,
+    #[serde(default)]
+    pub firewall: Option<InputConfigFirewall>,
+    #[serde(default)]
+    pub unix_relays: Vec<InputConfigUnixRelay>,
+    #[serde(default)]
+    pub host_dns: Option<InputConfigHostDns>,
+    #[serde(default)]
+    pub keepalive: Option<InputConfigNetKeepalive>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetKeepalive {
+    #[serde(default = "InputConfigNetKeepalive::get_default_idle_secs")]
+    pub idle_secs: u32,
+    #[serde(default = "InputConfigNetKeepalive::get_default_interval_secs")]
+    pub interval_secs: u32,
+    #[serde(default = "InputConfigNetKeepalive::get_default_count")]
+    pub count: u32,
+}
+
+impl InputConfigNetKeepalive {
+    fn get_default_idle_secs() -> u32 {
+        300
+    }
+
+    fn get_default_interval_secs() -> u32 {
+        30
+    }
+
+    fn get_default_count() -> u32 {
+        5
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigHostDns {
+    #[serde(default = "InputConfigHostDns::get_max_results")]
+    pub max_results: usize,
+    #[serde(default)]
+    pub pinned: HashMap<String, Vec<String>>,
+}
+
+impl InputConfigHostDns {
+    fn get_max_results() -> usize {
+        16
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigFirewall {
+    #[serde(default = "InputConfigFirewall::get_default_action")]
+    pub default: String,
+    #[serde(default)]
+    pub rules: Vec<InputConfigFirewallRule>,
+}
+
+impl InputConfigFirewall {
+    fn get_default_action() -> String {
+        "allow".to_string()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigFirewallRule {
+    pub action: String,
+    #[serde(default)]
+    pub direction: Option<String>,
+    #[serde(default)]
+    pub family: Option<String>,
+    #[serde(default)]
+    pub cidr: Option<String>,
+    #[serde(default)]
+    pub ports: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetStack {
+    pub tap: String,
+    #[serde(default)]
+    pub mac: Option<String>,
+    pub addr: String,
+    #[serde(default)]
+    pub gateway: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetBuffering {
+    #[serde(default = "InputConfigNetBuffering::get_default_size")]
+    pub send_flush_size: String,
+    #[serde(default = "InputConfigNetBuffering::get_default_size")]
+    pub recv_prefetch_size: String,
+}
+
+impl InputConfigNetBuffering {
+    fn get_default_size() -> String {
+        "0B".to_string()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetAsyncIo {
+    #[serde(default = "InputConfigNetAsyncIo::get_default_threads")]
+    pub threads: usize,
+}
+
+impl InputConfigNetAsyncIo {
+    fn get_default_threads() -> usize {
+        2
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigNetTls {
+    #[serde(default)]
+    pub server: Option<InputConfigTlsServer>,
+    #[serde(default)]
+    pub client: Option<InputConfigTlsClient>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigTlsServer {
+    pub ports: HashSet<u16>,
+    pub cert: String,
+    pub key: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigTlsClient {
+    pub ports: HashSet<u16>,
+    pub ca: String,
+    pub server_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigDbusBridge {
+    pub name: String,
+    pub host_path: String,
+    pub interfaces: HashSet<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigUnixRelay {
+    pub path: String,
+    pub host_path: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigDevices {
+    #[serde(default)]
+    pub emulations: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigWatchdog {
+    #[serde(default)]
+    pub ocall_threshold_ms: u64,
+    #[serde(default)]
+    pub fail_stuck_ocalls: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigTime {
+    #[serde(default)]
+    pub max_host_clock_adjustment_ms: u64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct InputConfigSync {
+    #[serde(default)]
+    pub interval_ms: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct InputConfigEgressLimit {
+    #[serde(default)]
+    pub cidr: Option<String>,
+    #[serde(default)]
+    pub process: Option<String>,
+    pub rate: String,
+    #[serde(default)]
+    pub burst: Option<String>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 struct InputConfigMount {
@@ -396,4 +1433,16 @@ struct InputConfigMountOptions {
     pub layers: Option<Vec<InputConfigMount>>,
     #[serde(default)]
     pub temporary: bool,
+    #[serde(default)]
+    pub page_hashes: Option<String>,
+    #[serde(default)]
+    pub aname: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub size: Option<String>,
+    #[serde(default)]
+    pub integrity_only_paths: Vec<String>,
+    #[serde(default)]
+    pub file_hashes: Option<String>,
 }
@@ -7,7 +7,7 @@ use super::*;
 use crate::exception::*;
 use crate::fs::HostStdioFds;
 use crate::interrupt;
-use crate::process::ProcessFilter;
+use crate::process::{EnvOrigin, EnvVars, ProcessFilter};
 use crate::signal::SigNum;
 use crate::time::up_time::init;
 use crate::util::log::LevelFilter;
@@ -23,6 +23,11 @@ lazy_static! {
     static ref HAS_INIT: AtomicBool = AtomicBool::new(false);
 }
 
+/// Whether the LibOS is initialized
+pub fn has_init() -> bool {
+    HAS_INIT.load(Ordering::SeqCst)
+}
+
 macro_rules! ecall_errno {
     ($errno:expr) => {{
         let errno: Errno = $errno;
@@ -54,6 +59,7 @@ pub extern "C" fn occlum_ecall_init(log_level: *const c_char, instance_dir: *con
         }
     };
 
+    let mut init_result = Ok(());
     INIT_ONCE.call_once(|| {
         // Init the log infrastructure first so that log messages will be printed afterwards
         util::log::init(log_level);
@@ -80,8 +86,15 @@ pub extern "C" fn occlum_ecall_init(log_level: *const c_char, instance_dir: *con
 
         // Init boot up time stamp here.
         time::up_time::init();
+
+        // Start the in-enclave network stack if configured
+        init_result = net::init_stack();
     });
 
+    if let Err(e) = init_result {
+        eprintln!("failed to init LibOS: {}", e.backtrace());
+        return ecall_errno!(e.errno());
+    }
     0
 }
 
@@ -168,6 +181,8 @@ pub extern "C" fn occlum_ecall_broadcast_interrupts() -> i32 {
 
     let _ = unsafe { backtrace::enable_backtrace(&ENCLAVE_PATH, PrintFormat::Short) };
     panic::catch_unwind(|| {
+        // The interrupt thread of the host is also the clock of the background works
+        sched::tick_background_works();
         backtrace::__rust_begin_short_backtrace(|| match interrupt::broadcast_interrupts() {
             Ok(count) => count as i32,
             Err(e) => {
@@ -211,7 +226,7 @@ fn parse_arguments(
     argv: *const *const c_char,
     env: *const *const c_char,
     host_stdio_fds: *const HostStdioFds,
-) -> Result<(PathBuf, Vec<CString>, Vec<CString>, HostStdioFds)> {
+) -> Result<(PathBuf, Vec<CString>, EnvVars, HostStdioFds)> {
     let path_buf = {
         if path_ptr.is_null() {
             return_errno!(EINVAL, "empty path");
@@ -242,7 +257,7 @@ fn parse_arguments(
 fn do_new_process(
     program_path: &PathBuf,
     argv: &Vec<CString>,
-    env_concat: Vec<CString>,
+    env_merged: EnvVars,
     host_stdio_fds: &HostStdioFds,
 ) -> Result<pid_t> {
     validate_program_path(program_path)?;
@@ -253,7 +268,7 @@ fn do_new_process(
     let new_tid = process::do_spawn_without_exec(
         &program_path_str,
         argv,
-        &env_concat,
+        env_merged,
         &file_actions,
         host_stdio_fds,
         current,
@@ -264,10 +279,9 @@ fn do_new_process(
 fn do_exec_thread(libos_tid: pid_t, host_tid: pid_t) -> Result<i32> {
     let status = process::task::exec(libos_tid, host_tid)?;
 
-    // sync file system
-    // TODO: only sync when all processes exit
-    use rcore_fs::vfs::FileSystem;
-    crate::fs::ROOT_INODE.fs().sync()?;
+    // Run the background works, e.g., syncing the file systems
+    // TODO: only run them when all processes exit
+    sched::run_all_background_works()?;
 
     // Not to be confused with the return value of a main function.
     // The exact meaning of status is described in wait(2) man page.
@@ -323,41 +337,24 @@ fn do_kill(pid: i32, sig: i32) -> Result<()> {
     crate::signal::do_kill_from_outside_enclave(filter, signum)
 }
 
-fn merge_env(env: *const *const c_char) -> Result<Vec<CString>> {
-    #[derive(Debug)]
-    struct EnvDefaultInner {
-        content: Vec<CString>,
-        helper: HashMap<String, usize>, // Env key: index of content
-    }
-
+fn merge_env(env: *const *const c_char) -> Result<EnvVars> {
     let env_listed = &config::LIBOS_CONFIG.env.untrusted;
-    let mut env_checked: Vec<CString> = Vec::new();
-    let mut env_default = EnvDefaultInner {
-        content: Vec::new(),
-        helper: HashMap::new(),
-    };
+    let mut env_merged = EnvVars::new();
 
-    // Use inner struct to parse env default
-    for (idx, val) in config::LIBOS_CONFIG.env.default.iter().enumerate() {
-        env_default.content.push(CString::new(val.clone())?);
-        let kv: Vec<&str> = val.to_str().unwrap().splitn(2, '=').collect(); // only split the first "="
-        env_default.helper.insert(kv[0].to_string(), idx);
+    for val in config::LIBOS_CONFIG.env.default.iter() {
+        env_merged.set(CString::new(val.clone())?, EnvOrigin::Trusted);
     }
 
     // Filter out env which are not listed in Occlum.json env untrusted section
-    // and remove env default element if it is overrided
+    // and override env default element of the same key
     if (!env.is_null()) {
         let env_untrusted = clone_cstrings_safely(env)?;
-        for iter in env_untrusted.iter() {
+        for iter in env_untrusted.into_iter() {
             let env_kv: Vec<&str> = iter.to_str().unwrap().splitn(2, '=').collect();
             if env_listed.contains(env_kv[0]) {
-                env_checked.push(iter.clone());
-                if let Some(idx) = env_default.helper.get(env_kv[0]) {
-                    env_default.content.remove(*idx);
-                }
+                env_merged.set(iter, EnvOrigin::Untrusted);
             }
         }
     }
-    trace!("env_checked from env untrusted: {:?}", env_checked);
-    Ok([env_default.content, env_checked].concat())
+    Ok(env_merged)
 }
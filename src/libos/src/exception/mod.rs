@@ -58,6 +58,8 @@ pub fn do_handle_exception(
 ) -> Result<isize> {
     let info = unsafe { &mut *info };
     check_exception_type(info.exception_type)?;
+    let is_page_fault = info.exception_vector == sgx_exception_vector_t::SGX_EXCEPTION_VECTOR_PF;
+    current!().sched_stat().account_exception(is_page_fault);
 
     let user_context = unsafe { &mut *user_context };
     *user_context = CpuContext::from_sgx(&info.cpu_context);
@@ -76,6 +78,21 @@ pub fn do_handle_exception(
         }
     }
 
+    // The page faults in the mappings of host file windows are handled by populating
+    // the pages, and those in the ranges registered to userfaultfds are handled by
+    // the user, after which the faulting instruction is executed again
+    if is_page_fault {
+        const PF_ERR_FLAG_WRITE: u32 = 1u32 << 1;
+        let is_write = info.exinfo.errcd & PF_ERR_FLAG_WRITE != 0;
+        let addr = info.exinfo.maddr as usize;
+        if crate::vm::handle_window_fault(addr, is_write)? {
+            return Ok(0);
+        }
+        if crate::vm::handle_user_fault(addr, is_write)? {
+            return Ok(0);
+        }
+    }
+
     // Then, it must be a "real" exception. Convert it to signal and force delivering it.
     // The generated signal is SIGBUS, SIGFPE, SIGILL, or SIGSEGV.
     //
@@ -0,0 +1,47 @@
+use super::xts::Xts;
+use super::{BlockDevice, BLOCK_SIZE};
+use rcore_fs::vfs::Result;
+
+/// A block device that encrypts the blocks of the device below it.
+///
+/// Each block is encrypted by XTS-AES-128 with its ID as the tweak, so a block
+/// can be read and written alone, and a block written at different places has
+/// different ciphertext. Like the encryption of disks, XTS protects only the
+/// confidentiality: the blocks tampered or rolled back by the host are not
+/// detected, but are decrypted into garbage.
+pub struct CryptDisk<D: BlockDevice> {
+    disk: D,
+    xts: Xts,
+}
+
+impl<D: BlockDevice> CryptDisk<D> {
+    pub fn new(disk: D, key: &[u8; 32]) -> Self {
+        Self {
+            disk,
+            xts: Xts::new(key),
+        }
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for CryptDisk<D> {
+    fn num_blocks(&self) -> usize {
+        self.disk.num_blocks()
+    }
+
+    fn read_block(&self, id: usize, buf: &mut [u8]) -> Result<()> {
+        self.disk.read_block(id, buf)?;
+        self.xts.decrypt(id as u64, buf);
+        Ok(())
+    }
+
+    fn write_block(&self, id: usize, buf: &[u8]) -> Result<()> {
+        let mut ciphertext = [0u8; BLOCK_SIZE];
+        ciphertext.copy_from_slice(buf);
+        self.xts.encrypt(id as u64, &mut ciphertext);
+        self.disk.write_block(id, &ciphertext)
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.disk.sync()
+    }
+}
@@ -0,0 +1,89 @@
+use super::{BlockDevice, BLOCK_SIZE};
+use crate::prelude::SgxMutex as Mutex;
+use rcore_fs::vfs::{FsError, Result};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::untrusted::fs;
+
+/// A block device stored in a file of the host, which is accessed by OCalls.
+///
+/// The blocks are as untrusted as the host, so this device is meant to be used
+/// below `CryptDisk`.
+pub struct HostDisk {
+    file: Mutex<fs::File>,
+    num_blocks: usize,
+}
+
+impl HostDisk {
+    /// Create a device of `num_blocks` blocks in the host file at `path`, which
+    /// is truncated if it exists
+    pub fn create(path: &Path, num_blocks: usize) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(host_error)?;
+        // The file is sparse, so the blocks never written take no space
+        file.set_len((num_blocks * BLOCK_SIZE) as u64)
+            .map_err(host_error)?;
+        Ok(Self {
+            file: Mutex::new(file),
+            num_blocks,
+        })
+    }
+
+    /// Open the device in the existing host file at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(host_error)?;
+        let len = file.metadata().map_err(host_error)?.len() as usize;
+        Ok(Self {
+            file: Mutex::new(file),
+            num_blocks: len / BLOCK_SIZE,
+        })
+    }
+
+    fn seek_block(&self, file: &mut fs::File, id: usize) -> Result<()> {
+        if id >= self.num_blocks {
+            return Err(FsError::InvalidParam);
+        }
+        file.seek(SeekFrom::Start((id * BLOCK_SIZE) as u64))
+            .map_err(host_error)?;
+        Ok(())
+    }
+}
+
+impl BlockDevice for HostDisk {
+    fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    fn read_block(&self, id: usize, buf: &mut [u8]) -> Result<()> {
+        debug_assert!(buf.len() == BLOCK_SIZE);
+        let mut file = self.file.lock().unwrap();
+        self.seek_block(&mut file, id)?;
+        file.read_exact(buf).map_err(host_error)
+    }
+
+    fn write_block(&self, id: usize, buf: &[u8]) -> Result<()> {
+        debug_assert!(buf.len() == BLOCK_SIZE);
+        let mut file = self.file.lock().unwrap();
+        self.seek_block(&mut file, id)?;
+        file.write_all(buf).map_err(host_error)
+    }
+
+    fn sync(&self) -> Result<()> {
+        let file = self.file.lock().unwrap();
+        file.sync_data().map_err(host_error)
+    }
+}
+
+fn host_error(e: std::io::Error) -> FsError {
+    warn!("HostDisk: failed to access the host file: {:?}", e);
+    FsError::DeviceError
+}
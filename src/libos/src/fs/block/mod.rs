@@ -0,0 +1,32 @@
+//! The block layer of the file systems stored in block devices.
+//!
+//! A block device is read and written in blocks of `BLOCK_SIZE` bytes. The
+//! devices are stacked: `HostDisk` keeps the blocks in a file of the host, and
+//! `CryptDisk` encrypts the blocks of the device below it. So a file system on
+//! `CryptDisk<HostDisk>` leaves only the ciphertext of its blocks to the host.
+use rcore_fs::vfs::Result;
+
+pub use self::crypt_disk::CryptDisk;
+pub use self::host_disk::HostDisk;
+
+mod crypt_disk;
+mod host_disk;
+mod xts;
+
+/// The size of a block in bytes
+pub const BLOCK_SIZE: usize = 4096;
+
+/// A device of fixed-size blocks
+pub trait BlockDevice: Send + Sync {
+    /// The number of the blocks in the device
+    fn num_blocks(&self) -> usize;
+
+    /// Read the block `id` into `buf`, which is of `BLOCK_SIZE` bytes
+    fn read_block(&self, id: usize, buf: &mut [u8]) -> Result<()>;
+
+    /// Write `buf`, which is of `BLOCK_SIZE` bytes, to the block `id`
+    fn write_block(&self, id: usize, buf: &[u8]) -> Result<()>;
+
+    /// Flush the written blocks to the persistent storage
+    fn sync(&self) -> Result<()>;
+}
@@ -0,0 +1,150 @@
+//! XTS-AES-128 (IEEE 1619) for the encryption of blocks.
+//!
+//! There is neither XTS nor a raw block cipher of AES in the crypto library of
+//! the SGX SDK, so AES is done here with the AES-NI instructions, which run in
+//! constant time unlike the implementations of AES with tables. Every CPU with
+//! SGX has AES-NI.
+use core::arch::x86_64::*;
+
+/// The size of a block of AES in bytes
+const AES_BLOCK_SIZE: usize = 16;
+
+/// The cipher of XTS, whose key is made of the key of data and that of tweaks
+pub struct Xts {
+    data_key: Aes128,
+    tweak_key: Aes128,
+}
+
+impl Xts {
+    pub fn new(key: &[u8; 32]) -> Self {
+        let mut data_key = [0u8; 16];
+        let mut tweak_key = [0u8; 16];
+        data_key.copy_from_slice(&key[..16]);
+        tweak_key.copy_from_slice(&key[16..]);
+        Self {
+            data_key: Aes128::new(&data_key),
+            tweak_key: Aes128::new(&tweak_key),
+        }
+    }
+
+    /// Encrypt the data unit of number `unit` in place, whose length is a
+    /// multiple of 16 bytes
+    pub fn encrypt(&self, unit: u64, data: &mut [u8]) {
+        unsafe { self.crypt(unit, data, true) }
+    }
+
+    /// Decrypt the data unit of number `unit` in place, whose length is a
+    /// multiple of 16 bytes
+    pub fn decrypt(&self, unit: u64, data: &mut [u8]) {
+        unsafe { self.crypt(unit, data, false) }
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn crypt(&self, unit: u64, data: &mut [u8], encrypt: bool) {
+        assert!(data.len() % AES_BLOCK_SIZE == 0);
+        let mut tweak = u128::from(unit).to_le_bytes();
+        tweak = store(self.tweak_key.encrypt(load(&tweak)));
+        for block in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+            let tweak_m128 = load(&tweak);
+            let input = _mm_xor_si128(load(block), tweak_m128);
+            let output = if encrypt {
+                self.data_key.encrypt(input)
+            } else {
+                self.data_key.decrypt(input)
+            };
+            block.copy_from_slice(&store(_mm_xor_si128(output, tweak_m128)));
+            tweak = mul_alpha(tweak);
+        }
+    }
+}
+
+/// Multiply the tweak by the primitive element α of GF(2^128), where the bytes
+/// are in little endian
+fn mul_alpha(tweak: [u8; 16]) -> [u8; 16] {
+    let tweak = u128::from_le_bytes(tweak);
+    let carry = tweak >> 127;
+    ((tweak << 1) ^ (carry * 0x87)).to_le_bytes()
+}
+
+/// The round keys of AES-128 for encryption and decryption
+struct Aes128 {
+    enc_keys: [__m128i; 11],
+    dec_keys: [__m128i; 11],
+}
+
+impl Aes128 {
+    fn new(key: &[u8; 16]) -> Self {
+        unsafe { Self::expand_key(key) }
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn expand_key(key: &[u8; 16]) -> Self {
+        let mut enc_keys = [_mm_setzero_si128(); 11];
+        enc_keys[0] = load(key);
+        // The round constant must be an immediate
+        macro_rules! expand_round {
+            ($round: expr, $rcon: expr) => {
+                let prev = enc_keys[$round - 1];
+                enc_keys[$round] = next_round_key(prev, _mm_aeskeygenassist_si128(prev, $rcon));
+            };
+        }
+        expand_round!(1, 0x01);
+        expand_round!(2, 0x02);
+        expand_round!(3, 0x04);
+        expand_round!(4, 0x08);
+        expand_round!(5, 0x10);
+        expand_round!(6, 0x20);
+        expand_round!(7, 0x40);
+        expand_round!(8, 0x80);
+        expand_round!(9, 0x1b);
+        expand_round!(10, 0x36);
+
+        // The round keys of the equivalent inverse cipher
+        let mut dec_keys = [_mm_setzero_si128(); 11];
+        dec_keys[0] = enc_keys[10];
+        for round in 1..10 {
+            dec_keys[round] = _mm_aesimc_si128(enc_keys[10 - round]);
+        }
+        dec_keys[10] = enc_keys[0];
+        Self { enc_keys, dec_keys }
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn encrypt(&self, block: __m128i) -> __m128i {
+        let mut state = _mm_xor_si128(block, self.enc_keys[0]);
+        for round in 1..10 {
+            state = _mm_aesenc_si128(state, self.enc_keys[round]);
+        }
+        _mm_aesenclast_si128(state, self.enc_keys[10])
+    }
+
+    #[target_feature(enable = "aes")]
+    unsafe fn decrypt(&self, block: __m128i) -> __m128i {
+        let mut state = _mm_xor_si128(block, self.dec_keys[0]);
+        for round in 1..10 {
+            state = _mm_aesdec_si128(state, self.dec_keys[round]);
+        }
+        _mm_aesdeclast_si128(state, self.dec_keys[10])
+    }
+}
+
+#[target_feature(enable = "aes")]
+unsafe fn next_round_key(key: __m128i, assist: __m128i) -> __m128i {
+    let assist = _mm_shuffle_epi32(assist, 0xff);
+    let mut key = key;
+    key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+    key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+    key = _mm_xor_si128(key, _mm_slli_si128(key, 4));
+    _mm_xor_si128(key, assist)
+}
+
+fn load(bytes: &[u8]) -> __m128i {
+    debug_assert!(bytes.len() == AES_BLOCK_SIZE);
+    unsafe { _mm_loadu_si128(bytes.as_ptr() as *const __m128i) }
+}
+
+fn store(block: __m128i) -> [u8; 16] {
+    let mut bytes = [0u8; 16];
+    unsafe { _mm_storeu_si128(bytes.as_mut_ptr() as *mut __m128i, block) };
+    bytes
+}
@@ -0,0 +1,129 @@
+use super::super::block::{BlockDevice, BLOCK_SIZE};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use rcore_fs::vfs::Result;
+use std::collections::HashMap;
+
+/// The max number of the blocks in the cache
+const CACHE_CAPACITY: usize = 1024;
+
+/// A write-back cache of the blocks of a device.
+///
+/// The blocks are kept decrypted in the enclave, and the dirty ones are written
+/// to the device when evicted or flushed. So the blocks of metadata, which are
+/// written again and again, cost little until the file system is synced.
+pub struct BlockCache {
+    device: Box<dyn BlockDevice>,
+    blocks: HashMap<usize, CachedBlock>,
+    /// The clock of the accesses to decide the least recently used blocks
+    clock: u64,
+}
+
+struct CachedBlock {
+    data: Box<[u8]>,
+    dirty: bool,
+    last_access: u64,
+}
+
+impl BlockCache {
+    pub fn new(device: Box<dyn BlockDevice>) -> Self {
+        Self {
+            device,
+            blocks: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Access the content of the block `id` by `f`
+    pub fn read<R>(&mut self, id: usize, f: impl FnOnce(&[u8]) -> R) -> Result<R> {
+        let block = self.get(id, true)?;
+        Ok(f(&block.data))
+    }
+
+    /// Modify the content of the block `id` by `f`
+    pub fn write<R>(&mut self, id: usize, f: impl FnOnce(&mut [u8]) -> R) -> Result<R> {
+        let block = self.get(id, true)?;
+        block.dirty = true;
+        Ok(f(&mut block.data))
+    }
+
+    /// Fill the block `id` by `f` from zeros, without reading the block from the
+    /// device, e.g., when the block is new or is written as a whole
+    pub fn overwrite<R>(&mut self, id: usize, f: impl FnOnce(&mut [u8]) -> R) -> Result<R> {
+        let block = self.get(id, false)?;
+        for byte in block.data.iter_mut() {
+            *byte = 0;
+        }
+        block.dirty = true;
+        Ok(f(&mut block.data))
+    }
+
+    /// Drop the block `id` without writing it back, e.g., when it is freed
+    pub fn discard(&mut self, id: usize) {
+        self.blocks.remove(&id);
+    }
+
+    /// Write all the dirty blocks to the device, and sync the device
+    pub fn flush(&mut self) -> Result<()> {
+        let mut dirty_ids: Vec<usize> = self
+            .blocks
+            .iter()
+            .filter(|(_, block)| block.dirty)
+            .map(|(id, _)| *id)
+            .collect();
+        dirty_ids.sort_unstable();
+        for id in dirty_ids {
+            let block = self.blocks.get_mut(&id).unwrap();
+            self.device.write_block(id, &block.data)?;
+            block.dirty = false;
+        }
+        self.device.sync()
+    }
+
+    fn get(&mut self, id: usize, load: bool) -> Result<&mut CachedBlock> {
+        self.clock += 1;
+        if !self.blocks.contains_key(&id) {
+            if self.blocks.len() >= CACHE_CAPACITY {
+                self.evict()?;
+            }
+            let mut data = vec![0u8; BLOCK_SIZE].into_boxed_slice();
+            if load {
+                self.device.read_block(id, &mut data)?;
+            }
+            let block = CachedBlock {
+                data,
+                dirty: false,
+                last_access: 0,
+            };
+            self.blocks.insert(id, block);
+        }
+        let block = self.blocks.get_mut(&id).unwrap();
+        block.last_access = self.clock;
+        Ok(block)
+    }
+
+    /// Evict the least recently used quarter of the blocks, which is cheaper
+    /// than evicting one block at a time
+    fn evict(&mut self) -> Result<()> {
+        let mut accesses: Vec<(u64, usize)> = self
+            .blocks
+            .iter()
+            .map(|(id, block)| (block.last_access, *id))
+            .collect();
+        accesses.sort_unstable();
+        let mut victims: Vec<usize> = accesses[..CACHE_CAPACITY / 4]
+            .iter()
+            .map(|(_, id)| *id)
+            .collect();
+        // Write back in the order of the blocks, which is friendly to the host
+        victims.sort_unstable();
+        for id in victims {
+            let block = &self.blocks[&id];
+            if block.dirty {
+                self.device.write_block(id, &block.data)?;
+            }
+            self.blocks.remove(&id);
+        }
+        Ok(())
+    }
+}
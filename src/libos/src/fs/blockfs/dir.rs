@@ -0,0 +1,95 @@
+use super::super::block::BLOCK_SIZE;
+use super::layout::*;
+use super::state::FsState;
+use alloc::string::{String, ToString};
+use rcore_fs::vfs::{FsError, Result, Timespec};
+
+/// The operations of the directories, whose entries are kept dense: an entry
+/// removed is replaced by the last entry, as in SEFS.
+impl FsState {
+    /// Add the entries "." and ".." to the new directory
+    pub fn dir_init(&mut self, ino: u32, parent: u32) -> Result<()> {
+        let now = self.read_inode(ino)?.ctime;
+        self.dir_append(ino, ".", ino, now)?;
+        self.dir_append(ino, "..", parent, now)
+    }
+
+    /// Get the inode and the name of the entry at `idx` of the directory
+    pub fn dir_entry(&mut self, ino: u32, idx: usize) -> Result<Option<(u32, String)>> {
+        let inode = self.read_inode(ino)?;
+        if idx >= inode.size / DIRENT_SIZE {
+            return Ok(None);
+        }
+        let mut buf = [0u8; DIRENT_SIZE];
+        self.read_data(&inode, idx * DIRENT_SIZE, &mut buf)?;
+        let (child, name) = decode_dirent(&buf);
+        let name = core::str::from_utf8(name).map_err(|_| {
+            warn!("BlockFS: invalid name of the entry in directory {}", ino);
+            FsError::DeviceError
+        })?;
+        Ok(Some((child, name.to_string())))
+    }
+
+    /// Find the entry of `name` in the directory, and return its index and inode
+    pub fn dir_lookup(&mut self, ino: u32, name: &str) -> Result<Option<(usize, u32)>> {
+        let inode = self.read_inode(ino)?;
+        let num_entries = inode.size / DIRENT_SIZE;
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut idx = 0;
+        while idx < num_entries {
+            self.read_data(&inode, idx * DIRENT_SIZE, &mut buf)?;
+            for dirent in buf.chunks(DIRENT_SIZE) {
+                if idx >= num_entries {
+                    break;
+                }
+                let (child, child_name) = decode_dirent(dirent);
+                if child_name == name.as_bytes() {
+                    return Ok(Some((idx, child)));
+                }
+                idx += 1;
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn dir_append(&mut self, ino: u32, name: &str, child: u32, now: Timespec) -> Result<()> {
+        if name.len() > MAX_NAME_LEN {
+            return Err(FsError::NameTooLong);
+        }
+        let mut buf = [0u8; DIRENT_SIZE];
+        encode_dirent(child, name, &mut buf);
+        let mut inode = self.read_inode(ino)?;
+        let offset = inode.size;
+        let ret = self.write_data(&mut inode, offset, &buf);
+        inode.mtime = now;
+        inode.ctime = now;
+        self.write_inode(ino, &inode)?;
+        // An entry never spans two blocks, so it is either written or not
+        ret.map(|_| ())
+    }
+
+    /// Remove the entry at `idx` of the directory
+    pub fn dir_remove(&mut self, ino: u32, idx: usize, now: Timespec) -> Result<()> {
+        let mut inode = self.read_inode(ino)?;
+        let last_offset = inode.size - DIRENT_SIZE;
+        if idx * DIRENT_SIZE != last_offset {
+            let mut buf = [0u8; DIRENT_SIZE];
+            self.read_data(&inode, last_offset, &mut buf)?;
+            self.write_data(&mut inode, idx * DIRENT_SIZE, &buf)?;
+        }
+        self.resize(&mut inode, last_offset)?;
+        inode.mtime = now;
+        inode.ctime = now;
+        self.write_inode(ino, &inode)
+    }
+
+    /// Point the entry at `idx` of the directory to another inode
+    pub fn dir_set_entry(&mut self, ino: u32, idx: usize, child: u32) -> Result<()> {
+        let (_, name) = self.dir_entry(ino, idx)?.ok_or(FsError::EntryNotFound)?;
+        let mut buf = [0u8; DIRENT_SIZE];
+        encode_dirent(child, &name, &mut buf);
+        let mut inode = self.read_inode(ino)?;
+        self.write_data(&mut inode, idx * DIRENT_SIZE, &buf)?;
+        self.write_inode(ino, &inode)
+    }
+}
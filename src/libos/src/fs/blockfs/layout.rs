@@ -0,0 +1,331 @@
+//! The on-disk layout of BlockFS, which is like that of ext2 with one group:
+//!
+//! | superblock | inode bitmap | block bitmap | inode table | data blocks |
+//!
+//! The superblock is the first block. An inode has 12 direct pointers to the
+//! blocks of data, one pointer to an indirect block and one pointer to a double
+//! indirect block, where a pointer of zero is a hole. A directory is a file of
+//! fixed-size entries, whose first two entries are "." and "..". All the numbers
+//! are in little endian.
+use super::super::block::BLOCK_SIZE;
+use core::convert::TryInto;
+use rcore_fs::vfs::{FileType, Timespec};
+
+const MAGIC: u32 = 0x5346_4b42;
+const VERSION: u32 = 1;
+
+/// The inode of the root directory, where inode 0 is reserved as no inode
+pub const ROOT_INO: u32 = 1;
+pub const INODE_SIZE: usize = 128;
+pub const INODES_PER_BLOCK: usize = BLOCK_SIZE / INODE_SIZE;
+/// The bytes of data per inode, i.e., the ratio of inodes as in ext2
+const BYTES_PER_INODE: usize = 16 * 1024;
+pub const BITS_PER_BLOCK: usize = BLOCK_SIZE * 8;
+pub const NUM_DIRECT: usize = 12;
+pub const PTRS_PER_BLOCK: usize = BLOCK_SIZE / 4;
+/// The max number of the blocks of a file
+pub const MAX_FILE_BLOCKS: usize = NUM_DIRECT + PTRS_PER_BLOCK + PTRS_PER_BLOCK * PTRS_PER_BLOCK;
+pub const DIRENT_SIZE: usize = 256;
+/// An entry is made of the inode (4 bytes), the length of the name (1 byte) and
+/// the name
+pub const MAX_NAME_LEN: usize = DIRENT_SIZE - 5;
+
+/// The superblock, which tells where the other regions are
+#[derive(Debug, PartialEq)]
+pub struct SuperBlock {
+    pub num_blocks: usize,
+    pub num_inodes: usize,
+    pub inode_bitmap_start: usize,
+    pub block_bitmap_start: usize,
+    pub inode_table_start: usize,
+    pub data_start: usize,
+}
+
+impl SuperBlock {
+    /// Lay out a device of `num_blocks` blocks, which fails if the device is
+    /// too small or too large
+    pub fn new(num_blocks: usize) -> Option<Self> {
+        if num_blocks > u32::max_value() as usize {
+            return None;
+        }
+        let num_inodes = div_round_up(num_blocks * BLOCK_SIZE / BYTES_PER_INODE, INODES_PER_BLOCK)
+            .max(1)
+            * INODES_PER_BLOCK;
+        let inode_bitmap_start = 1;
+        let block_bitmap_start = inode_bitmap_start + div_round_up(num_inodes, BITS_PER_BLOCK);
+        let inode_table_start = block_bitmap_start + div_round_up(num_blocks, BITS_PER_BLOCK);
+        let data_start = inode_table_start + num_inodes / INODES_PER_BLOCK;
+        // There must be room for the root directory at least
+        if data_start >= num_blocks {
+            return None;
+        }
+        Some(Self {
+            num_blocks,
+            num_inodes,
+            inode_bitmap_start,
+            block_bitmap_start,
+            inode_table_start,
+            data_start,
+        })
+    }
+
+    /// Decode the superblock, which fails if it is not of BlockFS, e.g., when
+    /// the device is decrypted by a wrong key
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let mut decoder = Decoder::new(buf);
+        if decoder.u32() != MAGIC || decoder.u32() != VERSION {
+            return None;
+        }
+        let num_blocks = decoder.u32() as usize;
+        // The layout is determined by the number of blocks
+        let sb = Self::new(num_blocks)?;
+        let stored = [
+            sb.num_inodes,
+            sb.inode_bitmap_start,
+            sb.block_bitmap_start,
+            sb.inode_table_start,
+            sb.data_start,
+        ];
+        if stored.iter().any(|&val| decoder.u32() as usize != val) {
+            return None;
+        }
+        Some(sb)
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        let mut encoder = Encoder::new(buf);
+        encoder.u32(MAGIC);
+        encoder.u32(VERSION);
+        encoder.u32(self.num_blocks as u32);
+        encoder.u32(self.num_inodes as u32);
+        encoder.u32(self.inode_bitmap_start as u32);
+        encoder.u32(self.block_bitmap_start as u32);
+        encoder.u32(self.inode_table_start as u32);
+        encoder.u32(self.data_start as u32);
+    }
+}
+
+/// An inode in the inode table
+#[derive(Clone)]
+pub struct DiskInode {
+    pub type_: FileType,
+    pub mode: u16,
+    pub nlinks: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: usize,
+    pub atime: Timespec,
+    pub mtime: Timespec,
+    pub ctime: Timespec,
+    pub direct: [u32; NUM_DIRECT],
+    pub indirect: u32,
+    pub double_indirect: u32,
+}
+
+impl DiskInode {
+    pub fn new(type_: FileType, mode: u16, now: Timespec) -> Self {
+        Self {
+            type_,
+            mode,
+            nlinks: 0,
+            uid: 0,
+            gid: 0,
+            size: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+            direct: [0; NUM_DIRECT],
+            indirect: 0,
+            double_indirect: 0,
+        }
+    }
+
+    /// Decode the inode, which fails if the type is invalid
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        let mut decoder = Decoder::new(buf);
+        let type_ = file_type_of_code(decoder.u16())?;
+        let mode = decoder.u16();
+        let nlinks = decoder.u32();
+        let uid = decoder.u32();
+        let gid = decoder.u32();
+        let size = decoder.u64() as usize;
+        let atime = decoder.timespec();
+        let mtime = decoder.timespec();
+        let ctime = decoder.timespec();
+        let mut direct = [0; NUM_DIRECT];
+        for ptr in direct.iter_mut() {
+            *ptr = decoder.u32();
+        }
+        let indirect = decoder.u32();
+        let double_indirect = decoder.u32();
+        Some(Self {
+            type_,
+            mode,
+            nlinks,
+            uid,
+            gid,
+            size,
+            atime,
+            mtime,
+            ctime,
+            direct,
+            indirect,
+            double_indirect,
+        })
+    }
+
+    pub fn encode(&self, buf: &mut [u8]) {
+        for byte in buf[..INODE_SIZE].iter_mut() {
+            *byte = 0;
+        }
+        let mut encoder = Encoder::new(buf);
+        encoder.u16(code_of_file_type(self.type_));
+        encoder.u16(self.mode);
+        encoder.u32(self.nlinks);
+        encoder.u32(self.uid);
+        encoder.u32(self.gid);
+        encoder.u64(self.size as u64);
+        encoder.timespec(&self.atime);
+        encoder.timespec(&self.mtime);
+        encoder.timespec(&self.ctime);
+        for &ptr in self.direct.iter() {
+            encoder.u32(ptr);
+        }
+        encoder.u32(self.indirect);
+        encoder.u32(self.double_indirect);
+    }
+}
+
+/// Decode the directory entry into the inode and the name
+pub fn decode_dirent(buf: &[u8]) -> (u32, &[u8]) {
+    let mut decoder = Decoder::new(buf);
+    let ino = decoder.u32();
+    let name_len = (decoder.u8() as usize).min(MAX_NAME_LEN);
+    (ino, &buf[5..5 + name_len])
+}
+
+pub fn encode_dirent(ino: u32, name: &str, buf: &mut [u8]) {
+    debug_assert!(name.len() <= MAX_NAME_LEN);
+    for byte in buf[..DIRENT_SIZE].iter_mut() {
+        *byte = 0;
+    }
+    let mut encoder = Encoder::new(buf);
+    encoder.u32(ino);
+    encoder.u8(name.len() as u8);
+    buf[5..5 + name.len()].copy_from_slice(name.as_bytes());
+}
+
+/// Get the pointer at `idx` of an indirect block
+pub fn get_ptr(buf: &[u8], idx: usize) -> u32 {
+    Decoder::new(&buf[idx * 4..]).u32()
+}
+
+pub fn set_ptr(buf: &mut [u8], idx: usize, ptr: u32) {
+    Encoder::new(&mut buf[idx * 4..]).u32(ptr);
+}
+
+fn code_of_file_type(type_: FileType) -> u16 {
+    match type_ {
+        FileType::File => 1,
+        FileType::Dir => 2,
+        FileType::SymLink => 3,
+        FileType::CharDevice => 4,
+        FileType::BlockDevice => 5,
+        FileType::NamedPipe => 6,
+        FileType::Socket => 7,
+    }
+}
+
+fn file_type_of_code(code: u16) -> Option<FileType> {
+    let type_ = match code {
+        1 => FileType::File,
+        2 => FileType::Dir,
+        3 => FileType::SymLink,
+        4 => FileType::CharDevice,
+        5 => FileType::BlockDevice,
+        6 => FileType::NamedPipe,
+        7 => FileType::Socket,
+        _ => return None,
+    };
+    Some(type_)
+}
+
+fn div_round_up(n: usize, d: usize) -> usize {
+    (n + d - 1) / d
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take(2).try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+
+    fn timespec(&mut self) -> Timespec {
+        let sec = i64::from_le_bytes(self.take(8).try_into().unwrap());
+        let nsec = i32::from_le_bytes(self.take(4).try_into().unwrap());
+        Timespec { sec, nsec }
+    }
+}
+
+struct Encoder<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+
+    fn u8(&mut self, val: u8) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    fn u16(&mut self, val: u16) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    fn u32(&mut self, val: u32) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    fn u64(&mut self, val: u64) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    fn timespec(&mut self, time: &Timespec) {
+        self.bytes(&time.sec.to_le_bytes());
+        self.bytes(&time.nsec.to_le_bytes());
+    }
+}
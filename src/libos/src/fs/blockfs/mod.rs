@@ -0,0 +1,481 @@
+//! A file system on an encrypted block device stored in a host file.
+//!
+//! SEFS keeps each file in a protected file of the SGX SDK, where every write
+//! updates the tree of MACs of the file up to its root, which is slow for the
+//! databases that write small pieces of large files at random. BlockFS instead
+//! lays out an ext2-like file system (see `layout`) on the blocks of a
+//! `CryptDisk` over a `HostDisk`, where every block is encrypted by XTS on its
+//! own, and the blocks are cached in the enclave and written back lazily.
+//!
+//! The source of the mount is a host directory, which has the file of the blocks
+//! and the protected file of the key. The key is generated randomly when the file
+//! system is created, and the protected file is sealed by the key of the signer
+//! of the enclave.
+//!
+//! Like the encryption of disks, only the confidentiality of the data is
+//! protected, but not the integrity or the freshness. And the file system is not
+//! journaled, so it is written back on sync and on unmount, and may be
+//! inconsistent after a crash.
+use crate::prelude::SgxMutex as Mutex;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use core::any::Any;
+use rcore_fs::dev::TimeProvider;
+use rcore_fs::vfs::*;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sgxfs::SgxFile;
+use std::untrusted::fs;
+use std::untrusted::path::PathEx;
+
+use self::layout::{DiskInode, DIRENT_SIZE, ROOT_INO};
+use self::state::FsState;
+use super::block::{CryptDisk, HostDisk, BLOCK_SIZE};
+use super::{DevRandom, File};
+use crate::time::OcclumTimeProvider;
+
+mod cache;
+mod dir;
+mod layout;
+mod state;
+
+/// The file of the blocks in the source directory
+const BLOCKS_FILE: &str = "blocks";
+/// The protected file of the key in the source directory
+const KEY_FILE: &str = "key";
+
+/// File system on an encrypted block device
+pub struct BlockFS {
+    state: Mutex<FsState>,
+    /// The INodes in use, so that an unlinked file is freed after they are dropped
+    nodes: Mutex<BTreeMap<u32, Weak<BNode>>>,
+    self_ref: Weak<BlockFS>,
+}
+
+/// INode for `BlockFS`
+pub struct BNode {
+    ino: u32,
+    fs: Arc<BlockFS>,
+}
+
+impl FileSystem for BlockFS {
+    fn sync(&self) -> Result<()> {
+        self.state.lock().unwrap().sync()
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.get_node(ROOT_INO)
+    }
+
+    fn info(&self) -> FsInfo {
+        unimplemented!()
+    }
+}
+
+impl BlockFS {
+    /// Create a new `BlockFS` of `size` bytes in the host directory `dir`,
+    /// replacing the existing one
+    pub fn create(dir: &Path, size: usize) -> crate::prelude::Result<Arc<BlockFS>> {
+        fs::create_dir_all(dir).map_err(|e| errno!(e))?;
+        let mut key = [0u8; 32];
+        DevRandom.read(&mut key)?;
+        let mut key_file = SgxFile::create(dir.join(KEY_FILE)).map_err(|e| errno!(e))?;
+        key_file.write_all(&key).map_err(|e| errno!(e))?;
+        key_file.flush().map_err(|e| errno!(e))?;
+
+        let disk = HostDisk::create(&dir.join(BLOCKS_FILE), size / BLOCK_SIZE)?;
+        let state = FsState::format(Box::new(CryptDisk::new(disk, &key)), now())?;
+        Ok(Self::new(state))
+    }
+
+    /// Open the `BlockFS` in the host directory `dir`
+    pub fn open(dir: &Path) -> crate::prelude::Result<Arc<BlockFS>> {
+        let mut key = [0u8; 32];
+        let mut key_file = SgxFile::open(dir.join(KEY_FILE)).map_err(|e| errno!(e))?;
+        key_file.read_exact(&mut key).map_err(|e| errno!(e))?;
+
+        let disk = HostDisk::open(&dir.join(BLOCKS_FILE))?;
+        let state = FsState::open(Box::new(CryptDisk::new(disk, &key)))?;
+        Ok(Self::new(state))
+    }
+
+    /// Whether there is a `BlockFS` in the host directory `dir`
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(BLOCKS_FILE).exists()
+    }
+
+    fn new(state: FsState) -> Arc<Self> {
+        BlockFS {
+            state: Mutex::new(state),
+            nodes: Mutex::new(BTreeMap::new()),
+            self_ref: Weak::default(),
+        }
+        .wrap()
+    }
+
+    /// Wrap pure `BlockFS` with Arc
+    /// Used in constructors
+    fn wrap(self) -> Arc<Self> {
+        let fs = Arc::new(self);
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+        }
+        unsafe { Arc::from_raw(ptr) }
+    }
+
+    /// Get the INode of `ino`, which is shared by all the users of the inode
+    fn get_node(&self, ino: u32) -> Arc<BNode> {
+        let mut nodes = self.nodes.lock().unwrap();
+        if let Some(node) = nodes.get(&ino).and_then(|node| node.upgrade()) {
+            return node;
+        }
+        let node = Arc::new(BNode {
+            ino,
+            fs: self.self_ref.upgrade().unwrap(),
+        });
+        nodes.insert(ino, Arc::downgrade(&node));
+        node
+    }
+
+    fn is_in_use(&self, ino: u32) -> bool {
+        let nodes = self.nodes.lock().unwrap();
+        nodes
+            .get(&ino)
+            .map_or(false, |node| node.strong_count() > 0)
+    }
+
+    /// Remove the entry at `idx` of the directory `dir_ino`, which is of the
+    /// inode `ino`, and free the inode if it is neither linked nor in use
+    fn remove_entry(&self, state: &mut FsState, dir_ino: u32, idx: usize, ino: u32) -> Result<()> {
+        let now = now();
+        let mut inode = state.read_inode(ino)?;
+        let is_dir = inode.type_ == FileType::Dir;
+        if is_dir {
+            if inode.size > 2 * DIRENT_SIZE {
+                return Err(FsError::DirNotEmpty);
+            }
+            inode.nlinks = 0;
+        } else {
+            inode.nlinks = inode.nlinks.saturating_sub(1);
+        }
+        inode.ctime = now;
+        state.dir_remove(dir_ino, idx, now)?;
+        state.write_inode(ino, &inode)?;
+        if is_dir {
+            // The entry ".." of the directory removed
+            let mut dir = state.read_inode(dir_ino)?;
+            dir.nlinks = dir.nlinks.saturating_sub(1);
+            state.write_inode(dir_ino, &dir)?;
+        }
+        if inode.nlinks == 0 && !self.is_in_use(ino) {
+            state.free_inode_if_unlinked(ino)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BlockFS {
+    fn drop(&mut self) {
+        if let Err(e) = self.state.lock().unwrap().sync() {
+            warn!("BlockFS: failed to sync on unmount: {:?}", e);
+        }
+    }
+}
+
+impl INode for BNode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.fs.state.lock().unwrap();
+        let inode = state.read_inode(self.ino)?;
+        if inode.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        state.read_data(&inode, offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        let mut state = self.fs.state.lock().unwrap();
+        let mut inode = state.read_inode(self.ino)?;
+        if inode.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        let ret = state.write_data(&mut inode, offset, buf);
+        let now = now();
+        inode.mtime = now;
+        inode.ctime = now;
+        state.write_inode(self.ino, &inode)?;
+        ret
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let inode = self.fs.state.lock().unwrap().read_inode(self.ino)?;
+        Ok(Metadata {
+            dev: 0,
+            inode: self.ino as usize,
+            size: inode.size,
+            blk_size: BLOCK_SIZE,
+            blocks: (inode.size + 511) / 512,
+            atime: inode.atime,
+            mtime: inode.mtime,
+            ctime: inode.ctime,
+            type_: inode.type_,
+            mode: inode.mode,
+            nlinks: inode.nlinks as usize,
+            uid: inode.uid as usize,
+            gid: inode.gid as usize,
+            rdev: 0,
+        })
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<()> {
+        let mut state = self.fs.state.lock().unwrap();
+        let mut inode = state.read_inode(self.ino)?;
+        inode.mode = metadata.mode;
+        inode.uid = metadata.uid as u32;
+        inode.gid = metadata.gid as u32;
+        inode.atime = metadata.atime;
+        inode.mtime = metadata.mtime;
+        inode.ctime = metadata.ctime;
+        state.write_inode(self.ino, &inode)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.fs.state.lock().unwrap().sync()
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.fs.state.lock().unwrap().sync()
+    }
+
+    fn resize(&self, len: usize) -> Result<()> {
+        let mut state = self.fs.state.lock().unwrap();
+        let mut inode = state.read_inode(self.ino)?;
+        if inode.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        let ret = state.resize(&mut inode, len);
+        let now = now();
+        inode.mtime = now;
+        inode.ctime = now;
+        state.write_inode(self.ino, &inode)?;
+        ret
+    }
+
+    fn create(&self, name: &str, type_: FileType, mode: u32) -> Result<Arc<dyn INode>> {
+        if name == "." || name == ".." {
+            return Err(FsError::EntryExist);
+        }
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        if state.dir_lookup(self.ino, name)?.is_some() {
+            return Err(FsError::EntryExist);
+        }
+
+        let is_dir = type_ == FileType::Dir;
+        let mut inode = DiskInode::new(type_, mode as u16, now());
+        inode.nlinks = if is_dir { 2 } else { 1 };
+        let ino = state.alloc_inode(&inode)?;
+        let ret = if is_dir {
+            state.dir_init(ino, self.ino)
+        } else {
+            Ok(())
+        }
+        .and_then(|_| state.dir_append(self.ino, name, ino, inode.ctime));
+        if let Err(e) = ret {
+            inode.nlinks = 0;
+            state.write_inode(ino, &inode)?;
+            state.free_inode_if_unlinked(ino)?;
+            return Err(e);
+        }
+        if is_dir {
+            // The entry ".." of the new directory
+            let mut dir = state.read_inode(self.ino)?;
+            dir.nlinks += 1;
+            state.write_inode(self.ino, &dir)?;
+        }
+        Ok(self.fs.get_node(ino))
+    }
+
+    fn link(&self, name: &str, other: &Arc<dyn INode>) -> Result<()> {
+        if name == "." || name == ".." {
+            return Err(FsError::EntryExist);
+        }
+        let other = other.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        if !Arc::ptr_eq(&self.fs, &other.fs) {
+            return Err(FsError::NotSameFs);
+        }
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        let mut inode = state.read_inode(other.ino)?;
+        if inode.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        if state.dir_lookup(self.ino, name)?.is_some() {
+            return Err(FsError::EntryExist);
+        }
+        let now = now();
+        state.dir_append(self.ino, name, other.ino, now)?;
+        inode.nlinks += 1;
+        inode.ctime = now;
+        state.write_inode(other.ino, &inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        if name == "." || name == ".." {
+            return Err(FsError::IsDir);
+        }
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        let (idx, ino) = state
+            .dir_lookup(self.ino, name)?
+            .ok_or(FsError::EntryNotFound)?;
+        self.fs.remove_entry(&mut state, self.ino, idx, ino)
+    }
+
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        let names = [old_name, new_name];
+        if names.iter().any(|name| *name == "." || *name == "..") {
+            return Err(FsError::InvalidParam);
+        }
+        let target = target.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        if !Arc::ptr_eq(&self.fs, &target.fs) {
+            return Err(FsError::NotSameFs);
+        }
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        target.check_dir(&mut state)?;
+        let (_, ino) = state
+            .dir_lookup(self.ino, old_name)?
+            .ok_or(FsError::EntryNotFound)?;
+        let is_dir = state.read_inode(ino)?.type_ == FileType::Dir;
+        if is_dir {
+            // A directory cannot be moved into itself or its subdirectories
+            let mut ancestor = target.ino;
+            while ancestor != ROOT_INO {
+                if ancestor == ino {
+                    return Err(FsError::InvalidParam);
+                }
+                ancestor = state.dir_entry(ancestor, 1)?.ok_or(FsError::DeviceError)?.0;
+            }
+        }
+
+        if let Some((idx, old_ino)) = state.dir_lookup(target.ino, new_name)? {
+            if old_ino == ino {
+                return Ok(());
+            }
+            match (is_dir, state.read_inode(old_ino)?.type_ == FileType::Dir) {
+                (true, false) => return Err(FsError::NotDir),
+                (false, true) => return Err(FsError::IsDir),
+                _ => {}
+            }
+            self.fs.remove_entry(&mut state, target.ino, idx, old_ino)?;
+        }
+        // The index of the entry may be changed by the removal above
+        let (idx, _) = state
+            .dir_lookup(self.ino, old_name)?
+            .ok_or(FsError::EntryNotFound)?;
+        let now = now();
+        state.dir_remove(self.ino, idx, now)?;
+        state.dir_append(target.ino, new_name, ino, now)?;
+        if is_dir && target.ino != self.ino {
+            state.dir_set_entry(ino, 1, target.ino)?;
+            let mut old_dir = state.read_inode(self.ino)?;
+            old_dir.nlinks = old_dir.nlinks.saturating_sub(1);
+            state.write_inode(self.ino, &old_dir)?;
+            let mut new_dir = state.read_inode(target.ino)?;
+            new_dir.nlinks += 1;
+            state.write_inode(target.ino, &new_dir)?;
+        }
+        let mut inode = state.read_inode(ino)?;
+        inode.ctime = now;
+        state.write_inode(ino, &inode)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        let ino = match name {
+            "." => self.ino,
+            ".." => state.dir_entry(self.ino, 1)?.ok_or(FsError::DeviceError)?.0,
+            _ => {
+                state
+                    .dir_lookup(self.ino, name)?
+                    .ok_or(FsError::EntryNotFound)?
+                    .1
+            }
+        };
+        Ok(self.fs.get_node(ino))
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        let mut state = self.fs.state.lock().unwrap();
+        self.check_dir(&mut state)?;
+        state
+            .dir_entry(self.ino, id)?
+            .map(|(_, name)| name)
+            .ok_or(FsError::EntryNotFound)
+    }
+
+    fn io_control(&self, _cmd: u32, _data: usize) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl BNode {
+    /// Check that the INode is a directory that is not removed
+    fn check_dir(&self, state: &mut FsState) -> Result<()> {
+        let inode = state.read_inode(self.ino)?;
+        if inode.type_ != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        if inode.nlinks == 0 {
+            return Err(FsError::DirRemoved);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BNode {
+    fn drop(&mut self) {
+        let mut state = self.fs.state.lock().unwrap();
+        let is_last = {
+            let mut nodes = self.fs.nodes.lock().unwrap();
+            let is_last = nodes
+                .get(&self.ino)
+                .map_or(false, |node| node.strong_count() == 0);
+            if is_last {
+                nodes.remove(&self.ino);
+            }
+            is_last
+        };
+        // The file unlinked when in use is freed now
+        if is_last {
+            if let Err(e) = state.free_inode_if_unlinked(self.ino) {
+                warn!("BlockFS: failed to free inode {}: {:?}", self.ino, e);
+            }
+        }
+    }
+}
+
+fn now() -> Timespec {
+    OcclumTimeProvider.current_time()
+}
@@ -0,0 +1,387 @@
+use super::super::block::{BlockDevice, BLOCK_SIZE};
+use super::cache::BlockCache;
+use super::layout::*;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use rcore_fs::vfs::{FileType, FsError, Result, Timespec};
+
+/// The state of a mounted BlockFS, which is protected by one lock
+pub struct FsState {
+    sb: SuperBlock,
+    cache: BlockCache,
+    /// Where to search for a free inode next time
+    next_ino: usize,
+    /// Where to search for a free block next time
+    next_block: usize,
+}
+
+impl FsState {
+    /// Make a new file system of an empty root directory on the device
+    pub fn format(device: Box<dyn BlockDevice>, now: Timespec) -> Result<Self> {
+        let sb = SuperBlock::new(device.num_blocks()).ok_or(FsError::InvalidParam)?;
+        let mut state = Self::new(sb, device);
+        let sb = &state.sb;
+        state.cache.overwrite(0, |buf| sb.encode(buf))?;
+        // Only the bitmaps need to be zeroed, as an inode or a block is written
+        // as a whole when allocated
+        for id in state.sb.inode_bitmap_start..state.sb.inode_table_start {
+            state.cache.overwrite(id, |_| ())?;
+        }
+        for id in 0..state.sb.data_start {
+            state.set_bit(state.sb.block_bitmap_start, id, true)?;
+        }
+        // Inode 0 is never used
+        state.set_bit(state.sb.inode_bitmap_start, 0, true)?;
+        state.set_bit(state.sb.inode_bitmap_start, ROOT_INO as usize, true)?;
+        let mut root = DiskInode::new(FileType::Dir, 0o755, now);
+        root.nlinks = 2;
+        state.write_inode(ROOT_INO, &root)?;
+        state.dir_init(ROOT_INO, ROOT_INO)?;
+        state.cache.flush()?;
+        Ok(state)
+    }
+
+    /// Open the file system on the device
+    pub fn open(device: Box<dyn BlockDevice>) -> Result<Self> {
+        let mut sb_buf = vec![0u8; BLOCK_SIZE];
+        device.read_block(0, &mut sb_buf)?;
+        let sb = SuperBlock::decode(&sb_buf).ok_or(FsError::WrongFs)?;
+        if sb.num_blocks > device.num_blocks() {
+            return Err(FsError::WrongFs);
+        }
+        Ok(Self::new(sb, device))
+    }
+
+    fn new(sb: SuperBlock, device: Box<dyn BlockDevice>) -> Self {
+        let next_block = sb.data_start;
+        Self {
+            sb,
+            cache: BlockCache::new(device),
+            next_ino: ROOT_INO as usize + 1,
+            next_block,
+        }
+    }
+
+    /// Write all the cached changes to the device
+    pub fn sync(&mut self) -> Result<()> {
+        self.cache.flush()
+    }
+
+    pub fn read_inode(&mut self, ino: u32) -> Result<DiskInode> {
+        let (block, offset) = self.inode_location(ino)?;
+        self.cache
+            .read(block, |buf| {
+                DiskInode::decode(&buf[offset..offset + INODE_SIZE])
+            })?
+            .ok_or_else(|| {
+                warn!("BlockFS: inode {} is corrupted", ino);
+                FsError::DeviceError
+            })
+    }
+
+    pub fn write_inode(&mut self, ino: u32, inode: &DiskInode) -> Result<()> {
+        let (block, offset) = self.inode_location(ino)?;
+        self.cache.write(block, |buf| {
+            inode.encode(&mut buf[offset..offset + INODE_SIZE])
+        })
+    }
+
+    fn inode_location(&self, ino: u32) -> Result<(usize, usize)> {
+        let ino = ino as usize;
+        if ino == 0 || ino >= self.sb.num_inodes {
+            warn!("BlockFS: invalid inode {}", ino);
+            return Err(FsError::DeviceError);
+        }
+        let block = self.sb.inode_table_start + ino / INODES_PER_BLOCK;
+        Ok((block, ino % INODES_PER_BLOCK * INODE_SIZE))
+    }
+
+    /// Allocate an inode for `inode`
+    pub fn alloc_inode(&mut self, inode: &DiskInode) -> Result<u32> {
+        let ino = self.alloc_bit(
+            self.sb.inode_bitmap_start,
+            self.sb.num_inodes,
+            self.next_ino,
+        )?;
+        self.next_ino = ino + 1;
+        self.write_inode(ino as u32, inode)?;
+        Ok(ino as u32)
+    }
+
+    /// Free the inode and its blocks if it has no links
+    pub fn free_inode_if_unlinked(&mut self, ino: u32) -> Result<()> {
+        let mut inode = self.read_inode(ino)?;
+        if inode.nlinks > 0 {
+            return Ok(());
+        }
+        self.free_blocks_from(&mut inode, 0)?;
+        self.set_bit(self.sb.inode_bitmap_start, ino as usize, false)?;
+        self.next_ino = self.next_ino.min(ino as usize);
+        // Leave no valid inode in the table, so that it is not freed twice
+        let (block, offset) = self.inode_location(ino)?;
+        self.cache.write(block, |buf| {
+            for byte in buf[offset..offset + INODE_SIZE].iter_mut() {
+                *byte = 0;
+            }
+        })
+    }
+
+    fn alloc_block(&mut self) -> Result<u32> {
+        let id = self.alloc_bit(
+            self.sb.block_bitmap_start,
+            self.sb.num_blocks,
+            self.next_block,
+        )?;
+        self.next_block = id + 1;
+        self.cache.overwrite(id, |_| ())?;
+        Ok(id as u32)
+    }
+
+    fn free_block(&mut self, id: u32) -> Result<()> {
+        self.set_bit(self.sb.block_bitmap_start, id as usize, false)?;
+        self.cache.discard(id as usize);
+        self.next_block = self.next_block.min(id as usize);
+        Ok(())
+    }
+
+    /// Find a zero bit in the bitmap of `num_bits` bits from `hint` and set it
+    fn alloc_bit(&mut self, bitmap_start: usize, num_bits: usize, hint: usize) -> Result<usize> {
+        let num_bitmap_blocks = (num_bits + BITS_PER_BLOCK - 1) / BITS_PER_BLOCK;
+        let hint = if hint < num_bits { hint } else { 0 };
+        for i in 0..num_bitmap_blocks {
+            let bitmap_block = (hint / BITS_PER_BLOCK + i) % num_bitmap_blocks;
+            let first_bit = bitmap_block * BITS_PER_BLOCK;
+            let bits_in_block = (num_bits - first_bit).min(BITS_PER_BLOCK);
+            let found = self.cache.read(bitmap_start + bitmap_block, |buf| {
+                let num_bytes = (bits_in_block + 7) / 8;
+                let byte_idx = buf[..num_bytes].iter().position(|&byte| byte != 0xff)?;
+                let bit = byte_idx * 8 + (!buf[byte_idx]).trailing_zeros() as usize;
+                Some(bit).filter(|&bit| bit < bits_in_block)
+            })?;
+            if let Some(bit) = found {
+                self.set_bit(bitmap_start, first_bit + bit, true)?;
+                return Ok(first_bit + bit);
+            }
+        }
+        Err(FsError::NoDeviceSpace)
+    }
+
+    fn set_bit(&mut self, bitmap_start: usize, bit: usize, val: bool) -> Result<()> {
+        let block = bitmap_start + bit / BITS_PER_BLOCK;
+        let bit = bit % BITS_PER_BLOCK;
+        self.cache.write(block, |buf| {
+            if val {
+                buf[bit / 8] |= 1 << (bit % 8);
+            } else {
+                buf[bit / 8] &= !(1 << (bit % 8));
+            }
+        })
+    }
+
+    /// Check the pointer read from the device, which points to a data block
+    fn check_ptr(&self, ptr: u32) -> Result<u32> {
+        let id = ptr as usize;
+        if id != 0 && (id < self.sb.data_start || id >= self.sb.num_blocks) {
+            warn!("BlockFS: invalid pointer to block {}", id);
+            return Err(FsError::DeviceError);
+        }
+        Ok(ptr)
+    }
+
+    /// Get the block of the device at the block `file_block` of the file, which
+    /// is allocated together with the indirect blocks if `create` is true
+    fn map_block(
+        &mut self,
+        inode: &mut DiskInode,
+        file_block: usize,
+        create: bool,
+    ) -> Result<Option<u32>> {
+        if file_block < NUM_DIRECT {
+            return self.map_ptr(&mut inode.direct[file_block], create);
+        }
+        let idx = file_block - NUM_DIRECT;
+        if idx < PTRS_PER_BLOCK {
+            return match self.map_ptr(&mut inode.indirect, create)? {
+                Some(indirect) => self.map_indirect(indirect, idx, create),
+                None => Ok(None),
+            };
+        }
+        let idx = idx - PTRS_PER_BLOCK;
+        if idx >= PTRS_PER_BLOCK * PTRS_PER_BLOCK {
+            return Err(FsError::InvalidParam);
+        }
+        let indirect = match self.map_ptr(&mut inode.double_indirect, create)? {
+            Some(double_indirect) => {
+                self.map_indirect(double_indirect, idx / PTRS_PER_BLOCK, create)?
+            }
+            None => None,
+        };
+        match indirect {
+            Some(indirect) => self.map_indirect(indirect, idx % PTRS_PER_BLOCK, create),
+            None => Ok(None),
+        }
+    }
+
+    fn map_ptr(&mut self, ptr: &mut u32, create: bool) -> Result<Option<u32>> {
+        if self.check_ptr(*ptr)? == 0 {
+            if !create {
+                return Ok(None);
+            }
+            *ptr = self.alloc_block()?;
+        }
+        Ok(Some(*ptr))
+    }
+
+    fn map_indirect(&mut self, indirect: u32, idx: usize, create: bool) -> Result<Option<u32>> {
+        let mut ptr = self
+            .cache
+            .read(indirect as usize, |buf| get_ptr(buf, idx))?;
+        if self.check_ptr(ptr)? == 0 {
+            if !create {
+                return Ok(None);
+            }
+            ptr = self.alloc_block()?;
+            self.cache
+                .write(indirect as usize, |buf| set_ptr(buf, idx, ptr))?;
+        }
+        Ok(Some(ptr))
+    }
+
+    /// Free the blocks of the file from the block `first`
+    fn free_blocks_from(&mut self, inode: &mut DiskInode, first: usize) -> Result<()> {
+        for idx in first.min(NUM_DIRECT)..NUM_DIRECT {
+            let mut ptr = inode.direct[idx];
+            self.free_tree(&mut ptr, 0, 0)?;
+            inode.direct[idx] = ptr;
+        }
+        let first = first.saturating_sub(NUM_DIRECT);
+        self.free_tree(&mut inode.indirect, 1, first)?;
+        let first = first.saturating_sub(PTRS_PER_BLOCK);
+        self.free_tree(&mut inode.double_indirect, 2, first)
+    }
+
+    /// Free the blocks from `first` in the tree of `level` levels of indirect
+    /// blocks at `ptr`, including the block at `ptr` if `first` is 0
+    fn free_tree(&mut self, ptr: &mut u32, level: u32, first: usize) -> Result<()> {
+        if self.check_ptr(*ptr)? == 0 {
+            return Ok(());
+        }
+        if level > 0 {
+            // The number of the blocks of data under each pointer of the block
+            let span = PTRS_PER_BLOCK.pow(level - 1);
+            let mut ptrs: Vec<u32> = self.cache.read(*ptr as usize, |buf| {
+                (0..PTRS_PER_BLOCK).map(|idx| get_ptr(buf, idx)).collect()
+            })?;
+            let first_idx = first / span;
+            for idx in first_idx..PTRS_PER_BLOCK {
+                let sub_first = if idx == first_idx { first % span } else { 0 };
+                self.free_tree(&mut ptrs[idx], level - 1, sub_first)?;
+            }
+            if first > 0 && first_idx < PTRS_PER_BLOCK {
+                self.cache.write(*ptr as usize, |buf| {
+                    for idx in first_idx..PTRS_PER_BLOCK {
+                        set_ptr(buf, idx, ptrs[idx]);
+                    }
+                })?;
+            }
+        }
+        if first == 0 {
+            self.free_block(*ptr)?;
+            *ptr = 0;
+        }
+        Ok(())
+    }
+
+    /// Read the data of the file at `offset`
+    pub fn read_data(&mut self, inode: &DiskInode, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if offset >= inode.size {
+            return Ok(0);
+        }
+        let len = buf.len().min(inode.size - offset);
+        let mut inode = inode.clone();
+        let mut read_len = 0;
+        while read_len < len {
+            let file_offset = offset + read_len;
+            let block_offset = file_offset % BLOCK_SIZE;
+            let copy_len = (len - read_len).min(BLOCK_SIZE - block_offset);
+            let dst = &mut buf[read_len..read_len + copy_len];
+            match self.map_block(&mut inode, file_offset / BLOCK_SIZE, false)? {
+                Some(block) => self.cache.read(block as usize, |src| {
+                    dst.copy_from_slice(&src[block_offset..block_offset + copy_len])
+                })?,
+                // A hole is read as zeros
+                None => {
+                    for byte in dst.iter_mut() {
+                        *byte = 0;
+                    }
+                }
+            }
+            read_len += copy_len;
+        }
+        Ok(len)
+    }
+
+    /// Write the data of the file at `offset`, which extends the file if needed.
+    ///
+    /// The inode is updated but not written.
+    pub fn write_data(
+        &mut self,
+        inode: &mut DiskInode,
+        offset: usize,
+        buf: &[u8],
+    ) -> Result<usize> {
+        if (offset + buf.len() + BLOCK_SIZE - 1) / BLOCK_SIZE > MAX_FILE_BLOCKS {
+            return Err(FsError::InvalidParam);
+        }
+        let mut written_len = 0;
+        while written_len < buf.len() {
+            let file_offset = offset + written_len;
+            let block_offset = file_offset % BLOCK_SIZE;
+            let copy_len = (buf.len() - written_len).min(BLOCK_SIZE - block_offset);
+            let src = &buf[written_len..written_len + copy_len];
+            let block = match self.map_block(inode, file_offset / BLOCK_SIZE, true) {
+                Ok(block) => block.unwrap() as usize,
+                // Return what has been written, e.g., when the device is full
+                Err(_) if written_len > 0 => break,
+                Err(e) => return Err(e),
+            };
+            let copy =
+                |dst: &mut [u8]| dst[block_offset..block_offset + copy_len].copy_from_slice(src);
+            if copy_len == BLOCK_SIZE {
+                // No need to read the block that is overwritten as a whole
+                self.cache.overwrite(block, copy)?;
+            } else {
+                self.cache.write(block, copy)?;
+            }
+            written_len += copy_len;
+            inode.size = inode.size.max(file_offset + copy_len);
+        }
+        Ok(written_len)
+    }
+
+    /// Resize the file to `len` bytes.
+    ///
+    /// The inode is updated but not written.
+    pub fn resize(&mut self, inode: &mut DiskInode, len: usize) -> Result<()> {
+        if (len + BLOCK_SIZE - 1) / BLOCK_SIZE > MAX_FILE_BLOCKS {
+            return Err(FsError::InvalidParam);
+        }
+        if len < inode.size {
+            self.free_blocks_from(inode, (len + BLOCK_SIZE - 1) / BLOCK_SIZE)?;
+            // Keep the bytes beyond the end zero, which are read if the file is
+            // extended again
+            let tail_offset = len % BLOCK_SIZE;
+            if tail_offset > 0 {
+                if let Some(block) = self.map_block(inode, len / BLOCK_SIZE, false)? {
+                    self.cache.write(block as usize, |buf| {
+                        for byte in buf[tail_offset..].iter_mut() {
+                            *byte = 0;
+                        }
+                    })?;
+                }
+            }
+        }
+        inode.size = len;
+        Ok(())
+    }
+}
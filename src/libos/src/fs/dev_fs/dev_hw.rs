@@ -0,0 +1,117 @@
+//! The hardware devices, which cannot be accessed inside the enclave.
+//!
+//! Instead of failing with ENOENT as if the devices do not exist, the open of a
+//! hardware device fails with EPERM, as the enclave lacks the capability to access
+//! it, and the denial is recorded for diagnostics in /proc/occlum/denied_devices.
+//! A device can also be emulated by a safe device (e.g., /dev/null) according to
+//! the config, for the applications that probe the devices but can work without.
+use super::*;
+use crate::config::ConfigDeviceEmulation;
+
+const HW_DEVICES: [&str; 4] = ["/dev/mem", "/dev/kmem", "/dev/port", "/dev/kvm"];
+const HW_DEVICE_PREFIXES: [&str; 2] = ["/dev/uio", "/dev/vfio/"];
+
+// The max number of the denials kept, after which the oldest ones are dropped
+const MAX_DENIALS: usize = 64;
+
+lazy_static! {
+    static ref DENIALS: SgxMutex<VecDeque<Denial>> = SgxMutex::new(VecDeque::new());
+}
+
+struct Denial {
+    pid: pid_t,
+    exec_path: String,
+    dev_path: String,
+}
+
+/// Check whether the absolute path is a hardware device
+pub fn is_hw_device(path: &Path) -> bool {
+    let path = match path.to_str() {
+        Some(path) => normalize_abs_path(path),
+        None => return false,
+    };
+    let path = path.as_str();
+    HW_DEVICES.contains(&path)
+        || HW_DEVICE_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix) && path.len() > prefix.len())
+}
+
+/// Open the hardware device at the path, or return `None` if the path is not
+/// a hardware device
+pub fn open_hw_device(fs: &FsView, path: &str) -> Option<Result<Box<dyn File>>> {
+    // Compare the absolute path, so that the devices cannot be reached by
+    // relative paths or paths like /dev/../dev/mem
+    let path = normalize_abs_path(&fs.convert_to_abs_path(path));
+    if !is_hw_device(Path::new(&path)) {
+        return None;
+    }
+    let path = path.as_str();
+    // The paths in the config may be not normalized either
+    let emulation = config::LIBOS_CONFIG
+        .devices
+        .emulations
+        .iter()
+        .find(|(emulated_path, _)| {
+            emulated_path.to_str().map(normalize_abs_path).as_deref() == Some(path)
+        })
+        .map(|(_, emulation)| emulation);
+    let file: Box<dyn File> = match emulation {
+        Some(ConfigDeviceEmulation::Null) => Box::new(DevNull),
+        Some(ConfigDeviceEmulation::Zero) => Box::new(DevZero),
+        None => {
+            record_denial(path);
+            return Some(Err(errno!(
+                EPERM,
+                "hardware devices cannot be accessed inside the enclave"
+            )));
+        }
+    };
+    Some(Ok(file))
+}
+
+/// Remove the empty, "." and ".." components of an absolute path lexically. The
+/// devices are not on any file system, so there are no symlinks to resolve.
+fn normalize_abs_path(path: &str) -> String {
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    format!("/{}", components.join("/"))
+}
+
+fn record_denial(dev_path: &str) {
+    let current = current!();
+    let process = current.process();
+    let denial = Denial {
+        pid: process.pid(),
+        exec_path: process.exec_path().to_owned(),
+        dev_path: dev_path.to_owned(),
+    };
+    warn!(
+        "denied the access to the hardware device {} by process {} ({})",
+        denial.dev_path, denial.pid, denial.exec_path
+    );
+
+    let mut denials = DENIALS.lock().unwrap();
+    if denials.len() == MAX_DENIALS {
+        denials.pop_front();
+    }
+    denials.push_back(denial);
+}
+
+/// Format the recent denials, one per line in the form of "pid exec_path dev_path"
+pub fn format_denials() -> String {
+    DENIALS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|denial| format!("{} {} {}\n", denial.pid, denial.exec_path, denial.dev_path))
+        .collect()
+}
@@ -0,0 +1,49 @@
+use super::*;
+
+/// The control device of the in-enclave test runner.
+///
+/// Writing a filter to the device runs the LibOS tests that match the filter;
+/// reading the device then returns the report of the last run.
+#[derive(Debug, Default)]
+pub struct DevLibosTest {
+    report: SgxMutex<Report>,
+}
+
+#[derive(Debug, Default)]
+struct Report {
+    content: Vec<u8>,
+    read_offset: usize,
+}
+
+impl DevLibosTest {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl File for DevLibosTest {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut report = self.report.lock().unwrap();
+        let unread = &report.content[report.read_offset..];
+        let len = min(buf.len(), unread.len());
+        buf[..len].copy_from_slice(&unread[..len]);
+        report.read_offset += len;
+        Ok(len)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        let filter = std::str::from_utf8(buf)
+            .map_err(|_| errno!(EINVAL, "the test filter is not valid UTF-8"))?
+            .trim();
+        let content = crate::libos_test::run_tests(filter).into_bytes();
+        *self.report.lock().unwrap() = Report {
+            content,
+            read_offset: 0,
+        };
+        Ok(buf.len())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
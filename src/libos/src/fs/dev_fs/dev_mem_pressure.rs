@@ -0,0 +1,68 @@
+use super::*;
+
+/// The notifier of the memory pressure of the current process, which is backed by
+/// an eventfd. See vm::MemPressure for the details.
+#[derive(Debug)]
+pub struct DevMemPressure {
+    event: Arc<EventFile>,
+}
+
+impl DevMemPressure {
+    pub fn new(flags: u32) -> Result<Self> {
+        let current = current!();
+        let mem_pressure = current.vm().mem_pressure();
+        if !mem_pressure.is_enabled() {
+            return_errno!(ENODEV, "the memory pressure threshold is not configured");
+        }
+        let status_flags = StatusFlags::from_bits_truncate(flags);
+        let event_flags = if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            EventCreationFlags::EFD_NONBLOCK
+        } else {
+            EventCreationFlags::empty()
+        };
+        let event = Arc::new(EventFile::new(0, event_flags)?);
+        mem_pressure.add_notifier(&event);
+        Ok(Self { event })
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.event.get_host_fd()
+    }
+}
+
+impl File for DevMemPressure {
+    // Read the number of times that the process comes under pressure since the
+    // last read, as an u64
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.event.read(buf)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        self.event.get_status_flags()
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        self.event
+            .set_status_flags(new_status_flags & StatusFlags::O_NONBLOCK)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait AsDevMemPressure {
+    fn as_dev_mem_pressure(&self) -> Result<&DevMemPressure>;
+}
+
+impl AsDevMemPressure for FileRef {
+    fn as_dev_mem_pressure(&self) -> Result<&DevMemPressure> {
+        self.as_any()
+            .downcast_ref::<DevMemPressure>()
+            .ok_or_else(|| errno!(EBADF, "not a memory pressure device"))
+    }
+}
@@ -4,6 +4,19 @@ use super::*;
 pub struct DevNull;
 
 impl File for DevNull {
+    // Reading /dev/null always returns end of file
+    fn read(&self, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn readv(&self, _bufs: &mut [&mut [u8]]) -> Result<usize> {
+        Ok(0)
+    }
+
     fn write(&self, _buf: &[u8]) -> Result<usize> {
         Ok(_buf.len())
     }
@@ -0,0 +1,205 @@
+//! The stream of security events, which is read from /dev/occlum_security_events.
+//!
+//! The LibOS reports the suspicious behaviors that it sees (e.g., the exec of a
+//! file that is not allowed, or a mapping that is writable and executable) as
+//! security events, so that a security agent in the enclave can react to them,
+//! e.g., by killing the process or reporting to a remote verifier.
+//!
+//! Each open of the device is a reader that receives the events reported after
+//! the open. A read returns one event as a `SecurityEventRecord`, and the reader is
+//! readable with poll, select and epoll as long as it has events to read, as it is
+//! backed by an eventfd in the semaphore mode. The events queued for a reader are
+//! limited, beyond which the new events are dropped and counted in the `lost`
+//! field of the next event that is queued.
+use super::*;
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::sync::Weak;
+
+/// The max number of events that are queued for a reader
+const MAX_QUEUED_EVENTS: usize = 128;
+/// The max length of the detail of an event, beyond which it is truncated
+const MAX_DETAIL_LEN: usize = 240;
+
+lazy_static! {
+    static ref SECURITY_EVENT_READERS: SgxMutex<Vec<Weak<SecurityEventReader>>> =
+        SgxMutex::new(Vec::new());
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u32)]
+pub enum SecurityEventKind {
+    /// A file is denied to be executed, e.g., by the exec allowlist
+    ExecDenied = 1,
+    /// A mapping is requested to be both writable and executable
+    WxMapping = 2,
+    /// A network operation is denied by the config
+    NetDenied = 3,
+    /// A system call is denied by the syscall denylist in the config
+    SyscallDenied = 4,
+}
+
+/// C struct of a security event read from the device
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct SecurityEventRecord {
+    kind: u32,
+    pid: u32,
+    // The number of events dropped for the reader before this one
+    lost: u32,
+    detail_len: u32,
+    // The description of the event in text, e.g., the path of the file
+    detail: [u8; MAX_DETAIL_LEN],
+}
+
+impl fmt::Debug for SecurityEventRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SecurityEventRecord")
+            .field("kind", &self.kind)
+            .field("pid", &self.pid)
+            .field("lost", &self.lost)
+            .field(
+                "detail",
+                &String::from_utf8_lossy(&self.detail[..self.detail_len as usize]),
+            )
+            .finish()
+    }
+}
+
+/// Report a security event of the current process to all the readers
+pub fn report_security_event(kind: SecurityEventKind, detail: &str) {
+    let pid = current!().process().pid();
+    warn!("security event: {:?} of process {}: {}", kind, pid, detail);
+
+    let detail_len = min(detail.len(), MAX_DETAIL_LEN);
+    let mut record = SecurityEventRecord {
+        kind: kind as u32,
+        pid,
+        lost: 0,
+        detail_len: detail_len as u32,
+        detail: [0; MAX_DETAIL_LEN],
+    };
+    record.detail[..detail_len].copy_from_slice(&detail.as_bytes()[..detail_len]);
+
+    let mut readers = SECURITY_EVENT_READERS.lock().unwrap();
+    readers.retain(|reader| match reader.upgrade() {
+        Some(reader) => {
+            reader.push(record);
+            true
+        }
+        None => false,
+    });
+}
+
+#[derive(Debug)]
+struct SecurityEventReader {
+    // The counter is the number of the queued events
+    event: EventFile,
+    inner: SgxMutex<SecurityEventReaderInner>,
+}
+
+#[derive(Debug, Default)]
+struct SecurityEventReaderInner {
+    events: VecDeque<SecurityEventRecord>,
+    lost: u32,
+}
+
+impl SecurityEventReader {
+    fn push(&self, mut record: SecurityEventRecord) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.events.len() >= MAX_QUEUED_EVENTS {
+            inner.lost = inner.lost.saturating_add(1);
+            return;
+        }
+        record.lost = std::mem::take(&mut inner.lost);
+        inner.events.push_back(record);
+        if let Err(e) = self.event.write(&1u64.to_ne_bytes()) {
+            warn!("failed to notify the security event: {:?}", e);
+        }
+    }
+
+    fn pop(&self) -> Result<SecurityEventRecord> {
+        // Wait for an event, or fail with EAGAIN if non-blocking
+        let mut counter = [0u8; size_of::<u64>()];
+        self.event.read(&mut counter)?;
+        self.inner
+            .lock()
+            .unwrap()
+            .events
+            .pop_front()
+            .ok_or_else(|| errno!(EAGAIN, "no security event is available"))
+    }
+}
+
+#[derive(Debug)]
+pub struct DevSecurityEvents {
+    reader: Arc<SecurityEventReader>,
+}
+
+impl DevSecurityEvents {
+    pub fn new(flags: u32) -> Result<Self> {
+        let status_flags = StatusFlags::from_bits_truncate(flags);
+        let mut event_flags = EventCreationFlags::EFD_SEMAPHORE;
+        if status_flags.contains(StatusFlags::O_NONBLOCK) {
+            event_flags |= EventCreationFlags::EFD_NONBLOCK;
+        }
+        let reader = Arc::new(SecurityEventReader {
+            event: EventFile::new(0, event_flags)?,
+            inner: Default::default(),
+        });
+        SECURITY_EVENT_READERS
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&reader));
+        Ok(Self { reader })
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.reader.event.get_host_fd()
+    }
+}
+
+impl File for DevSecurityEvents {
+    // Read one event as a SecurityEventRecord
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let record_len = size_of::<SecurityEventRecord>();
+        if buf.len() < record_len {
+            return_errno!(EINVAL, "the buffer is too small for a security event");
+        }
+        let record = self.reader.pop()?;
+        let record_bytes =
+            unsafe { std::slice::from_raw_parts(&record as *const _ as *const u8, record_len) };
+        buf[..record_len].copy_from_slice(record_bytes);
+        Ok(record_len)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        self.reader.event.get_status_flags()
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        self.reader
+            .event
+            .set_status_flags(new_status_flags & StatusFlags::O_NONBLOCK)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait AsDevSecurityEvents {
+    fn as_dev_security_events(&self) -> Result<&DevSecurityEvents>;
+}
+
+impl AsDevSecurityEvents for FileRef {
+    fn as_dev_security_events(&self) -> Result<&DevSecurityEvents> {
+        self.as_any()
+            .downcast_ref::<DevSecurityEvents>()
+            .ok_or_else(|| errno!(EBADF, "not a security event device"))
+    }
+}
@@ -1,11 +1,86 @@
+use super::proc_fs::{DirEntry, ProcDir};
 use super::*;
 
+pub use self::dev_hw::{format_denials, is_hw_device, open_hw_device};
+#[cfg(feature = "libos_test")]
+pub use self::dev_libos_test::DevLibosTest;
+pub use self::dev_mem_pressure::{AsDevMemPressure, DevMemPressure};
 pub use self::dev_null::DevNull;
 pub use self::dev_random::{AsDevRandom, DevRandom};
+pub use self::dev_security_events::{
+    report_security_event, AsDevSecurityEvents, DevSecurityEvents, SecurityEventKind,
+};
 pub use self::dev_sgx::DevSgx;
 pub use self::dev_zero::DevZero;
 
+mod dev_hw;
+#[cfg(feature = "libos_test")]
+mod dev_libos_test;
+mod dev_mem_pressure;
 mod dev_null;
 mod dev_random;
+mod dev_security_events;
 mod dev_sgx;
 mod dev_zero;
+
+/// The devices handled by the LibOS, which are opened by their paths under /dev
+const DEVICE_NAMES: [&str; 8] = [
+    "null",
+    "zero",
+    "random",
+    "urandom",
+    "arandom",
+    "sgx",
+    "occlum_mem_pressure",
+    "occlum_security_events",
+];
+
+/// Open /dev, whose entries are the files under /dev of the file system (e.g.,
+/// /dev/shm), if any, along with the devices handled by the LibOS.
+///
+/// Return None if the path is not /dev.
+pub fn open_dir(fs: &FsView, path: &str) -> Option<Result<ProcDir>> {
+    if path.trim_end_matches('/') != "/dev" {
+        return None;
+    }
+    Some(read_dev_dir(fs))
+}
+
+fn read_dev_dir(fs: &FsView) -> Result<ProcDir> {
+    let mut entries = Vec::new();
+    if let Ok(dir_inode) = fs.lookup_inode("/dev") {
+        let mut idx = 0;
+        while let Ok(name) = dir_inode.get_entry(idx) {
+            idx += 1;
+            if name == "." || name == ".." {
+                continue;
+            }
+            let metadata = dir_inode.find(&name)?.metadata()?;
+            entries.push(DirEntry {
+                name,
+                ino: metadata.inode as u64,
+                type_: metadata.type_,
+            });
+        }
+    }
+
+    let mut device_names: Vec<String> = DEVICE_NAMES.iter().map(|name| name.to_string()).collect();
+    #[cfg(feature = "libos_test")]
+    device_names.push("libos_test".to_string());
+    // The emulated hardware devices right under /dev, e.g., /dev/kvm
+    let mut emulated_names: Vec<String> = config::LIBOS_CONFIG
+        .devices
+        .emulations
+        .keys()
+        .filter(|path| path.parent() == Some(Path::new("/dev")))
+        .filter_map(|path| path.file_name()?.to_str().map(|name| name.to_string()))
+        .collect();
+    emulated_names.sort();
+    device_names.extend(emulated_names);
+    for name in device_names {
+        // The devices handled by the LibOS shadow the files of the file system
+        entries.retain(|entry| entry.name != name);
+        entries.push(DirEntry::new("/dev", name, FileType::CharDevice));
+    }
+    Ok(ProcDir::new("/dev", entries))
+}
@@ -24,11 +24,11 @@ pub trait File: Debug + Sync + Send + Any {
     }
 
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
-        return_op_unsupported_error!("read_at")
+        return_op_unsupported_error!("read_at", ESPIPE)
     }
 
     fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
-        return_op_unsupported_error!("write_at")
+        return_op_unsupported_error!("write_at", ESPIPE)
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
@@ -40,7 +40,7 @@ pub trait File: Debug + Sync + Send + Any {
     }
 
     fn seek(&self, pos: SeekFrom) -> Result<off_t> {
-        return_op_unsupported_error!("seek")
+        return_op_unsupported_error!("seek", ESPIPE)
     }
 
     fn metadata(&self) -> Result<Metadata> {
@@ -59,6 +59,12 @@ pub trait File: Debug + Sync + Send + Any {
         return_op_unsupported_error!("read_entry", ENOTDIR)
     }
 
+    /// Read the next entry of the directory, along with its inode number and type,
+    /// which are zero and None if unknown
+    fn read_dirent(&self) -> Result<(String, u64, Option<FileType>)> {
+        self.read_entry().map(|name| (name, 0, None))
+    }
+
     fn sync_all(&self) -> Result<()> {
         Ok(())
     }
@@ -103,6 +109,13 @@ pub trait File: Debug + Sync + Send + Any {
         return_op_unsupported_error!("dequeue_event");
     }
 
+    /// The sequence number that changes whenever the file may become ready again,
+    /// e.g., when new data arrives, even if the file is ready all the time. It is
+    /// used to find the edges for the edge-triggered epoll.
+    fn poll_seq(&self) -> Result<usize> {
+        return_op_unsupported_error!("poll_seq");
+    }
+
     fn as_any(&self) -> &dyn Any;
 }
 
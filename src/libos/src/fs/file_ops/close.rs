@@ -1,9 +1,10 @@
 use super::*;
+use crate::util::sync::LockBackoff;
 
 pub fn do_close(fd: FileDesc) -> Result<()> {
     debug!("close: fd: {}", fd);
     let current = current!();
-    let mut files = current.files().lock().unwrap();
+    let mut files = current.files().lock_with_backoff().unwrap();
     files.del(fd)?;
     Ok(())
 }
@@ -54,6 +54,20 @@ impl<'a> DirentBufWriter<'a> {
     }
 }
 
+/// Get d_type of the file type, which is DT_UNKNOWN if unknown
+fn dirent_type(type_: Option<FileType>) -> u8 {
+    match type_ {
+        None => 0,
+        Some(FileType::NamedPipe) => 1,
+        Some(FileType::CharDevice) => 2,
+        Some(FileType::Dir) => 4,
+        Some(FileType::BlockDevice) => 6,
+        Some(FileType::File) => 8,
+        Some(FileType::SymLink) => 10,
+        Some(FileType::Socket) => 12,
+    }
+}
+
 /// Write a Rust string to C string
 unsafe fn write_cstr(ptr: *mut u8, s: &str) {
     ptr.copy_from(s.as_ptr(), s.len());
@@ -74,7 +88,7 @@ pub fn do_getdents64(fd: FileDesc, buf: &mut [u8]) -> Result<usize> {
     }
     let mut writer = unsafe { DirentBufWriter::new(buf) };
     loop {
-        let name = match file_ref.read_entry() {
+        let (name, ino, type_) = match file_ref.read_dirent() {
             Err(e) => {
                 let errno = e.errno();
                 if errno == ENOENT {
@@ -82,10 +96,10 @@ pub fn do_getdents64(fd: FileDesc, buf: &mut [u8]) -> Result<usize> {
                 }
                 return Err(e.cause_err(|_| errno!(errno, "failed to read entry")));
             }
-            Ok(name) => name,
+            Ok(dirent) => dirent,
         };
-        // TODO: get ino from dirent
-        if let Err(e) = writer.try_write(0, 0, &name) {
+        // TODO: get ino and type from the dirents of the inodes
+        if let Err(e) = writer.try_write(ino, dirent_type(type_), &name) {
             file_ref.seek(SeekFrom::Current(-1))?;
             if writer.written_size == 0 {
                 return Err(e);
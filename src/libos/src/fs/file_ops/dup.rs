@@ -1,4 +1,5 @@
 use super::*;
+use crate::util::sync::LockBackoff;
 
 pub fn do_dup(old_fd: FileDesc) -> Result<FileDesc> {
     let current = current!();
@@ -9,7 +10,7 @@ pub fn do_dup(old_fd: FileDesc) -> Result<FileDesc> {
 
 pub fn do_dup2(old_fd: FileDesc, new_fd: FileDesc) -> Result<FileDesc> {
     let current = current!();
-    let mut files = current.files().lock().unwrap();
+    let mut files = current.files().lock_with_backoff().unwrap();
     let file = files.get(old_fd)?;
     if old_fd != new_fd {
         files.put_at(new_fd, file, false);
@@ -20,7 +21,7 @@ pub fn do_dup2(old_fd: FileDesc, new_fd: FileDesc) -> Result<FileDesc> {
 pub fn do_dup3(old_fd: FileDesc, new_fd: FileDesc, flags: u32) -> Result<FileDesc> {
     let creation_flags = CreationFlags::from_bits_truncate(flags);
     let current = current!();
-    let mut files = current.files().lock().unwrap();
+    let mut files = current.files().lock_with_backoff().unwrap();
     let file = files.get(old_fd)?;
     if old_fd == new_fd {
         return_errno!(EINVAL, "old_fd must not be equal to new_fd");
@@ -0,0 +1,46 @@
+use super::*;
+
+bitflags! {
+    pub struct FallocateFlags: u32 {
+        /// Do not change the file size even if the range is beyond the end of file
+        const FALLOC_FL_KEEP_SIZE = 0x01;
+    }
+}
+
+pub fn do_fallocate(fd: FileDesc, flags: FallocateFlags, offset: off_t, len: off_t) -> Result<()> {
+    debug!(
+        "fallocate: fd: {}, flags: {:?}, offset: {}, len: {}",
+        fd, flags, offset, len
+    );
+    if offset < 0 || len <= 0 {
+        return_errno!(EINVAL, "invalid offset or len");
+    }
+    let end = offset
+        .checked_add(len)
+        .ok_or_else(|| errno!(EFBIG, "offset + len exceeds the maximum file size"))?;
+
+    let file_ref = current!().file(fd)?;
+    if file_ref.as_pipe_reader().is_ok() || file_ref.as_pipe_writer().is_ok() {
+        return_errno!(ESPIPE, "cannot fallocate a pipe");
+    }
+    let inode_file = file_ref
+        .as_inode_file()
+        .map_err(|_| errno!(ENODEV, "not a regular file"))?;
+    if !inode_file.get_access_mode()?.writable() {
+        return_errno!(EBADF, "file is not opened for writing");
+    }
+    let metadata = inode_file.metadata()?;
+    if metadata.type_ == FileType::Dir {
+        return_errno!(EISDIR, "cannot fallocate a directory");
+    }
+    if metadata.type_ != FileType::File {
+        return_errno!(ENODEV, "not a regular file");
+    }
+
+    // The blocks are allocated on demand by the underlying file systems, so
+    // the only observable effect of fallocate is the change of file size.
+    if !flags.contains(FallocateFlags::FALLOC_FL_KEEP_SIZE) && end as usize > metadata.size {
+        inode_file.set_len(end as u64)?;
+    }
+    Ok(())
+}
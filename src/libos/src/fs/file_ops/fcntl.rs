@@ -1,5 +1,6 @@
 use super::flock::flock;
 use super::*;
+use crate::util::sync::LockBackoff;
 use util::mem_util::from_user;
 
 #[derive(Debug)]
@@ -55,7 +56,7 @@ pub fn do_fcntl(fd: FileDesc, cmd: &mut FcntlCmd) -> Result<isize> {
     debug!("fcntl: fd: {:?}, cmd: {:?}", &fd, cmd);
 
     let current = current!();
-    let mut file_table = current.files().lock().unwrap();
+    let mut file_table = current.files().lock_with_backoff().unwrap();
 
     let ret = match cmd {
         FcntlCmd::DupFd(min_fd) => {
@@ -0,0 +1,111 @@
+//! Descriptors of the arguments of non-builtin ioctls.
+//!
+//! The direction and size of the argument of an ioctl are usually encoded in
+//! its number by the _IOR/_IOW/_IOWR macros, from which the descriptor of the
+//! argument can be derived automatically. But many legacy ioctls (e.g., those
+//! for terminals and sockets) do not follow the encoding. These ioctls must be
+//! described explicitly in the table below to be passed to the host safely.
+
+use super::*;
+use std::convert::TryInto;
+
+/// The direction, size and validator of the argument of an ioctl
+#[derive(Debug, Copy, Clone)]
+pub struct IoctlArgDesc {
+    arg_type: StructuredIoctlArgType,
+    arg_size: usize,
+    /// Check the value of the argument that is returned from the host
+    validator: Option<fn(&[u8]) -> Result<()>>,
+}
+
+impl IoctlArgDesc {
+    /// Get the descriptor of the argument of an ioctl
+    pub fn of(cmd_num: &StructuredIoctlNum) -> IoctlArgDesc {
+        let derived_desc = IoctlArgDesc {
+            arg_type: cmd_num.arg_type(),
+            arg_size: cmd_num.arg_size(),
+            validator: None,
+        };
+        match find_ioctl_arg_desc(cmd_num.as_u32()) {
+            Some(desc) => {
+                // The table must be consistent with the encoding if any
+                debug_assert!(
+                    cmd_num.arg_type() == StructuredIoctlArgType::Void
+                        || (desc.arg_type == derived_desc.arg_type
+                            && desc.arg_size == derived_desc.arg_size)
+                );
+                desc
+            }
+            None => derived_desc,
+        }
+    }
+
+    pub fn arg_type(&self) -> StructuredIoctlArgType {
+        self.arg_type
+    }
+
+    pub fn arg_size(&self) -> usize {
+        self.arg_size
+    }
+
+    pub fn validate(&self, arg: &[u8]) -> Result<()> {
+        match self.validator {
+            Some(validator) => validator(arg),
+            None => Ok(()),
+        }
+    }
+}
+
+/// This is the centralized place to describe the non-builtin ioctls that need
+/// more than what is encoded in their numbers.
+///
+/// Each entry gives the direction and type of the argument, and optionally a
+/// function to validate the argument returned from the host.
+impl_ioctl_arg_descs! {
+    // Format:
+    // ioctl_name => (ioctl_num, arg_direction, arg_type[, validator])
+
+    // Get and set the terminal attributes
+    TCGETS => (0x5401, Output, KernelTermios),
+    TCSETS => (0x5402, Input, KernelTermios),
+    TCSETSW => (0x5403, Input, KernelTermios),
+    TCSETSF => (0x5404, Input, KernelTermios),
+    // Get the number of bytes in the output buffer
+    TIOCOUTQ => (0x5411, Output, i32, validate_non_negative_i32),
+    // Check whether the socket is at the out-of-band mark
+    SIOCATMARK => (0x8905, Output, i32, validate_bool_i32),
+}
+
+/// The termios struct used by the TCGETS and TCSETS* ioctls of Linux, which is
+/// different from the one of libc.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct KernelTermios {
+    pub c_iflag: u32,
+    pub c_oflag: u32,
+    pub c_cflag: u32,
+    pub c_lflag: u32,
+    pub c_line: u8,
+    pub c_cc: [u8; 19],
+}
+
+fn read_i32(arg: &[u8]) -> Result<i32> {
+    let bytes = arg
+        .try_into()
+        .map_err(|_| errno!(EINVAL, "the size of argument is not that of i32"))?;
+    Ok(i32::from_ne_bytes(bytes))
+}
+
+fn validate_non_negative_i32(arg: &[u8]) -> Result<()> {
+    if read_i32(arg)? < 0 {
+        return_errno!(EINVAL, "invalid data from host");
+    }
+    Ok(())
+}
+
+fn validate_bool_i32(arg: &[u8]) -> Result<()> {
+    match read_i32(arg)? {
+        0 | 1 => Ok(()),
+        _ => return_errno!(EINVAL, "invalid data from host"),
+    }
+}
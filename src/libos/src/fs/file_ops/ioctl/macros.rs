@@ -186,3 +186,40 @@ macro_rules! get_arg_ptr {
         (*$arg_ref as *const $type) as *const u8
     };
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// IoctlArgDesc
+////////////////////////////////////////////////////////////////////////////////
+
+/// Implement the lookup of `IoctlArgDesc` given a list of ioctl names, numbers,
+/// argument directions, argument types and optional validators.
+macro_rules! impl_ioctl_arg_descs {
+    ($( $ioctl_name: ident => ( $ioctl_num: expr, $arg_type: ident, $arg_ty: ty $(, $validator: expr)? ) ),+,) => {
+        $(
+            pub const $ioctl_name: u32 = $ioctl_num;
+        )*
+
+        fn find_ioctl_arg_desc(raw_cmd_num: u32) -> Option<IoctlArgDesc> {
+            let desc = match raw_cmd_num {
+                $(
+                    $ioctl_num => IoctlArgDesc {
+                        arg_type: StructuredIoctlArgType::$arg_type,
+                        arg_size: std::mem::size_of::<$arg_ty>(),
+                        validator: get_validator!($($validator)?),
+                    },
+                )*
+                _ => return None,
+            };
+            Some(desc)
+        }
+    }
+}
+
+macro_rules! get_validator {
+    () => {
+        None
+    };
+    ($validator: expr) => {
+        Some($validator)
+    };
+}
@@ -6,11 +6,13 @@
 
 use super::*;
 
+pub use self::arg_desc::{IoctlArgDesc, KernelTermios};
 pub use self::builtin::*;
 pub use self::non_builtin::{NonBuiltinIoctlCmd, StructuredIoctlArgType, StructuredIoctlNum};
 
 #[macro_use]
 mod macros;
+mod arg_desc;
 mod builtin;
 mod non_builtin;
 
@@ -35,8 +37,16 @@ impl_ioctl_nums_and_cmds! {
     // the foreground process group and all processes in the current session lose their controlling
     // terminal
     TIOCNOTTY => (0x5422, ()),
-    // Get the number of bytes in the input buffer
+    // Get the number of bytes in the input buffer, a.k.a. SIOCINQ for sockets
     FIONREAD => (0x541B, mut i32),
+    // Get the number of bytes in the output buffer that are not sent (or not read
+    // by the peer for unix sockets) yet, a.k.a. SIOCOUTQ for sockets
+    TIOCOUTQ => (0x5411, mut i32),
+    // Set or clear the non-blocking mode of the file, like O_NONBLOCK of fcntl
+    FIONBIO => (0x5421, i32),
+    // Set or clear the close-on-exec flag of the fd, like FD_CLOEXEC of fcntl
+    FIONCLEX => (0x5450, ()),
+    FIOCLEX => (0x5451, ()),
     // Low-level access to Linux network devices on man7/netdevice.7
     // Only non-privileged operations are supported for now
     SIOCGIFNAME => (0x8910, mut IfReq),
@@ -76,6 +86,12 @@ impl<'a> IoctlCmd<'a> {
                     return_errno!(EINVAL, "invalid data from host");
                 }
             }
+            IoctlCmd::TIOCOUTQ(nbytes_ref) => {
+                if **nbytes_ref < 0 {
+                    return_errno!(EINVAL, "invalid data from host");
+                }
+            }
+            IoctlCmd::NonBuiltin(inner) => inner.validate_arg()?,
             _ => {}
         }
 
@@ -89,7 +105,22 @@ impl<'a> IoctlCmd<'a> {
 
 pub fn do_ioctl(fd: FileDesc, cmd: &mut IoctlCmd) -> Result<i32> {
     debug!("ioctl: fd: {}, cmd: {:?}", fd, cmd);
-    let file_ref = current!().file(fd)?;
+    let current = current!();
+    // The close-on-exec flag belongs to the fd rather than the file
+    if let IoctlCmd::FIOCLEX(_) | IoctlCmd::FIONCLEX(_) = cmd {
+        let mut files = current.files().lock().unwrap();
+        let entry = files.get_entry_mut(fd)?;
+        entry.set_close_on_spawn(matches!(cmd, IoctlCmd::FIOCLEX(_)));
+        return Ok(0);
+    }
+    let file_ref = current.file(fd)?;
+    // Like Linux, FIONBIO is handled the same way for all files
+    if let IoctlCmd::FIONBIO(nonblocking) = cmd {
+        let mut status_flags = file_ref.get_status_flags()?;
+        status_flags.set(StatusFlags::O_NONBLOCK, **nonblocking != 0);
+        file_ref.set_status_flags(status_flags)?;
+        return Ok(0);
+    }
     file_ref.ioctl(cmd)
 }
 
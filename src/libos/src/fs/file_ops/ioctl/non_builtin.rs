@@ -1,10 +1,12 @@
 //! Non-builtin ioctls.
 
 use super::*;
+use util::mem_util::from_user;
 
 #[derive(Debug)]
 pub struct NonBuiltinIoctlCmd<'a> {
     cmd_num: StructuredIoctlNum,
+    arg_desc: IoctlArgDesc,
     arg_buf: Option<&'a mut [u8]>,
 }
 
@@ -13,17 +15,23 @@ impl<'a> NonBuiltinIoctlCmd<'a> {
         cmd_num: StructuredIoctlNum,
         arg_ptr: *mut u8,
     ) -> Result<NonBuiltinIoctlCmd<'a>> {
-        let arg_buf = if cmd_num.require_arg() {
+        let arg_desc = IoctlArgDesc::of(&cmd_num);
+        let arg_buf = if arg_desc.arg_type() != StructuredIoctlArgType::Void {
             if arg_ptr.is_null() {
                 return_errno!(EINVAL, "arg_ptr must be provided for the ioctl");
             }
-            let arg_size = cmd_num.arg_size();
-            let arg_slice = unsafe { std::slice::from_raw_parts_mut::<'a>(arg_ptr, arg_size) };
-            Some(arg_slice)
+            let arg_size = arg_desc.arg_size();
+            from_user::check_mut_array(arg_ptr, arg_size)?;
+            // The caller makes sure that the argument outlives the command
+            Some(std::slice::from_raw_parts_mut(arg_ptr, arg_size))
         } else {
             None
         };
-        Ok(NonBuiltinIoctlCmd { cmd_num, arg_buf })
+        Ok(NonBuiltinIoctlCmd {
+            cmd_num,
+            arg_desc,
+            arg_buf,
+        })
     }
 
     pub fn cmd_num(&self) -> &StructuredIoctlNum {
@@ -31,10 +39,10 @@ impl<'a> NonBuiltinIoctlCmd<'a> {
     }
 
     pub fn arg<T>(&self) -> Result<&T> {
-        if self.cmd_num.arg_type().can_be_input() == false {
+        if self.arg_desc.arg_type().can_be_input() == false {
             return_errno!(EINVAL, "cannot get a constant argument");
         }
-        if std::mem::size_of::<T>() != self.cmd_num.arg_size() {
+        if std::mem::size_of::<T>() != self.arg_desc.arg_size() {
             return_errno!(
                 EINVAL,
                 "the size of target type does not match the given buf size"
@@ -46,10 +54,10 @@ impl<'a> NonBuiltinIoctlCmd<'a> {
     }
 
     pub fn arg_mut<T>(&mut self) -> Result<&mut T> {
-        if self.cmd_num.arg_type().can_be_output() == false {
+        if self.arg_desc.arg_type().can_be_output() == false {
             return_errno!(EINVAL, "cannot get a mutable argument");
         }
-        if std::mem::size_of::<T>() != self.cmd_num.arg_size() {
+        if std::mem::size_of::<T>() != self.arg_desc.arg_size() {
             return_errno!(
                 EINVAL,
                 "the size of target type does not match the given buf size"
@@ -67,7 +75,17 @@ impl<'a> NonBuiltinIoctlCmd<'a> {
     }
 
     pub fn arg_len(&self) -> usize {
-        self.cmd_num.arg_size()
+        self.arg_desc.arg_size()
+    }
+
+    /// Check the value of the argument returned from the host
+    pub fn validate_arg(&self) -> Result<()> {
+        match self.arg_buf.as_ref() {
+            Some(arg_buf) if self.arg_desc.arg_type().can_be_output() => {
+                self.arg_desc.validate(arg_buf)
+            }
+            _ => Ok(()),
+        }
     }
 }
 
@@ -161,11 +179,13 @@ impl StructuredIoctlNum {
     }
 }
 
+/// The direction of the argument of an ioctl, which is encoded in the same way
+/// as _IOC_NONE, _IOC_WRITE and _IOC_READ of Linux
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum StructuredIoctlArgType {
     Void = 0,
-    Output = 1,
-    Input = 2,
+    Input = 1,
+    Output = 2,
     InputOutput = 3,
 }
 
@@ -8,6 +8,7 @@ pub use self::chown::{do_fchown, do_fchownat, ChownFlags};
 pub use self::close::do_close;
 pub use self::dirent::do_getdents64;
 pub use self::dup::{do_dup, do_dup2, do_dup3};
+pub use self::fallocate::{do_fallocate, FallocateFlags};
 pub use self::fcntl::{do_fcntl, FcntlCmd};
 pub use self::file_flags::{AccessMode, CreationFlags, StatusFlags};
 pub use self::flock::{Flock, FlockType};
@@ -37,6 +38,7 @@ mod chown;
 mod close;
 mod dirent;
 mod dup;
+mod fallocate;
 mod fcntl;
 mod file_flags;
 mod flock;
@@ -14,9 +14,18 @@ pub fn do_readv(fd: FileDesc, bufs: &mut [&mut [u8]]) -> Result<usize> {
 
 pub fn do_pread(fd: FileDesc, buf: &mut [u8], offset: off_t) -> Result<usize> {
     debug!("pread: fd: {}, offset: {}", fd, offset);
+    check_offset_and_len(offset, buf.len())?;
+    let file_ref = current!().file(fd)?;
+    file_ref.read_at(offset as usize, buf)
+}
+
+/// Check the offset and length of pread/pwrite the same way as Linux does
+pub(super) fn check_offset_and_len(offset: off_t, len: usize) -> Result<()> {
     if offset < 0 {
         return_errno!(EINVAL, "the offset is negative");
     }
-    let file_ref = current!().file(fd)?;
-    file_ref.read_at(offset as usize, buf)
+    if len > off_t::max_value() as usize || offset.checked_add(len as off_t).is_none() {
+        return_errno!(EINVAL, "offset + len overflows");
+    }
+    Ok(())
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::net::{AsSocket, SocketFile};
 
 pub fn do_sendfile(
     out_fd: FileDesc,
@@ -15,13 +16,24 @@ pub fn do_sendfile(
     let current = current!();
     let in_file = current.file(in_fd)?;
     let out_file = current.file(out_fd)?;
-    let mut buffer: [u8; 1024 * 11] = unsafe { MaybeUninit::uninit().assume_init() };
-
     let mut read_offset = match offset {
         Some(offset) => offset,
         None => in_file.seek(SeekFrom::Current(0))?,
     } as usize;
 
+    if let Ok(socket) = out_file.as_socket() {
+        if !socket.is_tls() && !socket.is_ping() {
+            let bytes_sent = send_file_to_socket(socket, &in_file, read_offset, count)?;
+            if offset.is_none() {
+                in_file.seek(SeekFrom::Current(bytes_sent as i64))?;
+            }
+            return Ok((bytes_sent, read_offset + bytes_sent));
+        }
+    }
+
+    // Copy the data through the enclave, e.g., for the LibOS sockets and files
+    let mut buffer: [u8; 1024 * 11] = unsafe { MaybeUninit::uninit().assume_init() };
+
     // read from specified offset and write new offset back
     let mut bytes_read = 0;
     while bytes_read < count {
@@ -47,3 +59,24 @@ pub fn do_sendfile(
     }
     Ok((bytes_read, read_offset))
 }
+
+/// Send the data from the file to the host socket chunk by chunk, where the data
+/// is never copied into the enclave. Like Linux, the bytes sent are returned if
+/// some data is sent before an error.
+fn send_file_to_socket(
+    socket: &SocketFile,
+    in_file: &FileRef,
+    offset: usize,
+    count: usize,
+) -> Result<usize> {
+    let mut bytes_sent = 0;
+    while bytes_sent < count {
+        match socket.send_file(in_file, offset + bytes_sent, count - bytes_sent) {
+            Ok(0) => break,
+            Ok(len) => bytes_sent += len,
+            Err(_) if bytes_sent > 0 => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(bytes_sent)
+}
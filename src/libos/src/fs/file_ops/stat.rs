@@ -144,8 +144,12 @@ pub fn do_fstat(fd: u32) -> Result<Stat> {
 pub fn do_fstatat(fs_path: &FsPath, flags: StatFlags) -> Result<Stat> {
     debug!("fstatat: fs_path: {:?}, flags: {:?}", fs_path, flags);
 
+    let path = fs_path.to_abs_path()?;
+    // The directories under /proc, e.g., /proc/[pid], are not backed by inodes
+    if let Some(proc_dir) = crate::fs::proc_fs::open_dir(&path) {
+        return Ok(Stat::from(proc_dir?.metadata()?));
+    }
     let inode = {
-        let path = fs_path.to_abs_path()?;
         let current = current!();
         let fs = current.fs().lock().unwrap();
         if flags.contains(StatFlags::AT_SYMLINK_NOFOLLOW) {
@@ -5,20 +5,8 @@ pub fn do_readlinkat(fs_path: &FsPath, buf: &mut [u8]) -> Result<usize> {
 
     let path = fs_path.to_abs_path()?;
     let file_path = {
-        if path.as_str() == "/proc/self/exe" {
-            current!().process().exec_path().to_owned()
-        } else if path.starts_with("/proc/self/fd") {
-            let fd = path
-                .trim_start_matches("/proc/self/fd/")
-                .parse::<FileDesc>()
-                .map_err(|_| errno!(EBADF, "Invalid file descriptor"))?;
-            let file_ref = current!().file(fd)?;
-            if let Ok(inode_file) = file_ref.as_inode_file() {
-                inode_file.get_abs_path().to_owned()
-            } else {
-                // TODO: support special device files
-                return_errno!(EINVAL, "not a normal file link")
-            }
+        if let Some(link) = crate::fs::proc_fs::read_link(&path) {
+            link?
         } else {
             let inode = {
                 let current = current!();
@@ -1,18 +1,24 @@
 use super::*;
 
-pub fn do_truncate(path: &str, len: usize) -> Result<()> {
+pub fn do_truncate(path: &str, len: off_t) -> Result<()> {
     debug!("truncate: path: {:?}, len: {}", path, len);
+    if len < 0 {
+        return_errno!(EINVAL, "the length is negative");
+    }
     let inode = {
         let current = current!();
         let fs = current.fs().lock().unwrap();
         fs.lookup_inode(&path)?
     };
-    inode.resize(len)?;
+    inode.resize(len as usize)?;
     Ok(())
 }
 
-pub fn do_ftruncate(fd: FileDesc, len: usize) -> Result<()> {
+pub fn do_ftruncate(fd: FileDesc, len: off_t) -> Result<()> {
     debug!("ftruncate: fd: {}, len: {}", fd, len);
+    if len < 0 {
+        return_errno!(EINVAL, "the length is negative");
+    }
     let file_ref = current!().file(fd)?;
     file_ref.set_len(len as u64)?;
     Ok(())
@@ -14,9 +14,7 @@ pub fn do_writev(fd: FileDesc, bufs: &[&[u8]]) -> Result<usize> {
 
 pub fn do_pwrite(fd: FileDesc, buf: &[u8], offset: off_t) -> Result<usize> {
     debug!("pwrite: fd: {}, offset: {}", fd, offset);
-    if offset < 0 {
-        return_errno!(EINVAL, "the offset is negative");
-    }
+    super::read::check_offset_and_len(offset, buf.len())?;
     let file_ref = current!().file(fd)?;
     file_ref.write_at(offset as usize, buf)
 }
@@ -136,6 +136,14 @@ impl FileTable {
         }
     }
 
+    /// Iterate over the opened files and their file descriptors
+    pub fn iter(&self) -> impl Iterator<Item = (FileDesc, &FileRef)> {
+        self.table
+            .iter()
+            .enumerate()
+            .filter_map(|(fd, entry)| entry.as_ref().map(|entry| (fd as FileDesc, &entry.file)))
+    }
+
     /// Remove file descriptors that are close-on-spawn
     pub fn close_on_spawn(&mut self) {
         for entry in self.table.iter_mut() {
@@ -2,7 +2,7 @@ use super::*;
 
 pub use self::chdir::do_chdir;
 pub use self::getcwd::do_getcwd;
-pub use self::sync::do_sync;
+pub use self::sync::{do_sync, sync_work};
 
 mod chdir;
 mod getcwd;
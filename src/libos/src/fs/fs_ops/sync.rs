@@ -1,7 +1,23 @@
 use super::*;
+use crate::config::LIBOS_CONFIG;
+use crate::sched::{BackgroundWork, WorkPriority};
 
 pub fn do_sync() -> Result<()> {
     debug!("sync:");
     ROOT_INODE.fs().sync()?;
     Ok(())
 }
+
+/// The background work that syncs the file systems at the interval in
+/// `Occlum.json`, if any, and on shutdown
+pub fn sync_work() -> BackgroundWork {
+    BackgroundWork::new(
+        "fs_sync",
+        WorkPriority::Low,
+        LIBOS_CONFIG.sync.interval,
+        || {
+            ROOT_INODE.fs().sync()?;
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,162 @@
+//! The types of file systems that can be mounted via `Occlum.json`.
+//!
+//! Each type is registered in `FS_TYPES` with the name given as the "type" of a
+//! mount config, together with the factory that creates the file system from the
+//! mount config. A new type of file system is added by implementing `FsFactory`
+//! and registering it here, without touching the config parser or the mount logic.
+//!
+//! The root file system is assembled from the layers of UnionFS by `rootfs`, so the
+//! factories only create the file systems mounted at the non-root paths.
+use super::blockfs::BlockFS;
+use super::hostfs::HostFS;
+use super::httpfs::HttpFS;
+use super::ninep::NinePFS;
+use super::sefs::{SgxStorage, SgxUuidProvider};
+use super::*;
+use config::ConfigMount;
+
+use rcore_fs_ramfs::RamFS;
+use rcore_fs_sefs::SEFS;
+
+/// The factory of a type of file system
+pub trait FsFactory: Sync {
+    /// Create the file system as given by the mount config
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>>;
+}
+
+/// The registered types of file systems, keyed by the type in the mount config
+static FS_TYPES: &[(&str, &dyn FsFactory)] = &[
+    ("sefs", &SefsFactory),
+    ("hostfs", &HostFsFactory),
+    ("ramfs", &RamFsFactory),
+    ("9p", &NinePFactory),
+    ("httpfs", &HttpFsFactory),
+    ("blockfs", &BlockFsFactory),
+    ("unionfs", &UnionFsFactory),
+];
+
+/// Create the file system of the type given by the mount config
+pub fn create_fs(config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+    let factory = FS_TYPES
+        .iter()
+        .find(|(type_, _)| *type_ == config.type_)
+        .map(|(_, factory)| factory)
+        .ok_or_else(|| errno!(EINVAL, "Unsupported file system type"))?;
+    factory.create(config)
+}
+
+struct SefsFactory;
+
+impl FsFactory for SefsFactory {
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        if config.options.integrity_only {
+            return_errno!(EINVAL, "Cannot mount integrity-only SEFS at non-root path");
+        }
+        let source_path = config
+            .source
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "Source is expected for SEFS"))?;
+        let create_sefs = || {
+            SEFS::create(
+                Box::new(SgxStorage::new(source_path, false, None)),
+                &time::OcclumTimeProvider,
+                &SgxUuidProvider,
+            )
+        };
+        let sefs = if !config.options.temporary {
+            SEFS::open(
+                Box::new(SgxStorage::new(source_path, false, None)),
+                &time::OcclumTimeProvider,
+                &SgxUuidProvider,
+            )
+            .or_else(|_| create_sefs())?
+        } else {
+            create_sefs()?
+        };
+        Ok(sefs)
+    }
+}
+
+struct HostFsFactory;
+
+impl FsFactory for HostFsFactory {
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        let source_path = config
+            .source
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "Source is expected for HostFS"))?;
+        Ok(HostFS::new(source_path, config.options.page_hashes.clone()))
+    }
+}
+
+struct NinePFactory;
+
+impl FsFactory for NinePFactory {
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        let socket_path = config
+            .source
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "Source is expected for 9P"))?;
+        let aname = config
+            .options
+            .aname
+            .as_ref()
+            .map_or("", |aname| aname.as_str());
+        Ok(NinePFS::new(socket_path, aname)?)
+    }
+}
+
+struct HttpFsFactory;
+
+impl FsFactory for HttpFsFactory {
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        let url = config
+            .options
+            .url
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "URL is expected for HttpFS"))?;
+        let manifest_path = config
+            .options
+            .page_hashes
+            .clone()
+            .ok_or_else(|| errno!(EINVAL, "Page hashes are expected for HttpFS"))?;
+        Ok(HttpFS::new(url, manifest_path)?)
+    }
+}
+
+struct BlockFsFactory;
+
+impl FsFactory for BlockFsFactory {
+    fn create(&self, config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        let source_path = config
+            .source
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "Source is expected for BlockFS"))?;
+        // Unlike SEFS, an existing file system that fails to open, e.g., whose
+        // key is sealed by another signer, is never replaced by a new one
+        if !config.options.temporary && BlockFS::exists(source_path) {
+            return Ok(BlockFS::open(source_path)?);
+        }
+        let size = config
+            .options
+            .size
+            .ok_or_else(|| errno!(EINVAL, "Size is expected to create BlockFS"))?;
+        Ok(BlockFS::create(source_path, size)?)
+    }
+}
+
+struct RamFsFactory;
+
+impl FsFactory for RamFsFactory {
+    fn create(&self, _config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        Ok(RamFS::new())
+    }
+}
+
+struct UnionFsFactory;
+
+impl FsFactory for UnionFsFactory {
+    fn create(&self, _config: &ConfigMount) -> Result<Arc<dyn FileSystem>> {
+        return_errno!(EINVAL, "Cannot mount UnionFS at non-root path");
+    }
+}
@@ -1,4 +1,5 @@
-use super::dev_fs::{DevNull, DevRandom, DevSgx, DevZero};
+use super::dev_fs::{DevMemPressure, DevNull, DevRandom, DevSecurityEvents, DevSgx, DevZero};
+use super::integrity_only_files::{check_open_integrity_only, verify_integrity_only_inode};
 /// Present a per-process view of FS.
 use super::*;
 
@@ -52,7 +53,35 @@ impl FsView {
         if path == "/dev/sgx" {
             return Ok(Box::new(DevSgx));
         }
+        if path == "/dev/occlum_mem_pressure" {
+            return Ok(Box::new(DevMemPressure::new(flags)?));
+        }
+        if path == "/dev/occlum_security_events" {
+            return Ok(Box::new(DevSecurityEvents::new(flags)?));
+        }
+        #[cfg(feature = "libos_test")]
+        {
+            if path == "/dev/libos_test" {
+                return Ok(Box::new(super::dev_fs::DevLibosTest::new()));
+            }
+        }
+        if let Some(hw_device) = super::dev_fs::open_hw_device(self, path) {
+            return hw_device;
+        }
+        if let Some(dev_dir) = super::dev_fs::open_dir(self, path) {
+            return Ok(Box::new(dev_dir?));
+        }
+        if let Some(proc_dir) = super::proc_fs::open_dir(path) {
+            return Ok(Box::new(proc_dir?));
+        }
+        if let Some(proc_file) = super::proc_fs::open(path) {
+            return Ok(Box::new(proc_file?));
+        }
+        if let Some(sys_file) = super::sys_fs::open(path) {
+            return Ok(Box::new(sys_file?));
+        }
         let creation_flags = CreationFlags::from_bits_truncate(flags);
+        let integrity_only_path = check_open_integrity_only(self, path, flags)?;
         let inode = if creation_flags.no_follow_symlink() {
             match self.lookup_inode_no_follow(path) {
                 Ok(inode) => {
@@ -112,6 +141,9 @@ impl FsView {
                 Err(e) => return Err(e),
             }
         };
+        if let Some(integrity_only_path) = integrity_only_path {
+            verify_integrity_only_inode(&integrity_only_path, &inode)?;
+        }
         let abs_path = self.convert_to_abs_path(&path);
         Ok(Box::new(INodeFile::open(inode, &abs_path, flags)?))
     }
@@ -0,0 +1,300 @@
+//! Read-only windows of host files, whose pages are verified against trusted hashes.
+//!
+//! A window is a read-only mapping of a host file in the untrusted memory. Reading
+//! the content of a file through its window requires no OCalls except the one to
+//! set up the mapping, which saves the OCalls of reading large, read-only datasets
+//! on HostFS (e.g., models or reference data) chunk by chunk at mmap.
+//!
+//! Since the untrusted memory can be modified by the host at any time, the content
+//! of a page is verified against its trusted SHA-256 hash every time it is copied
+//! into the enclave. The trusted hashes are given by a manifest file in the trusted
+//! root file system (see the `page_hashes` mount option of HostFS). Each line of
+//! the manifest describes one file:
+//!
+//! ```text
+//! <path relative to the HostFS source> <file size> <hash of page 0> <hash of page 1> ...
+//! ```
+//!
+//! where the hashes are hex strings. The pages of a mapping are populated into
+//! the enclave on first access (see `vm::lazy_window`), so a window saves the time
+//! of copying and verifying the pages that the user never accesses, but not the
+//! EPC, which is committed at mmap as for any other mapping.
+//!
+//! A host file shorter than its trusted size fails the mmap with EIO. The host may
+//! still truncate the file after it is mapped, after which the first access to the
+//! pages beyond the end faults on the host, as any other misbehavior of the host.
+
+use super::hostfs::HNode;
+use super::*;
+use std::ffi::CString;
+use std::path::Path;
+use vm::PAGE_SIZE;
+
+/// The trusted hashes of the pages of the files on a HostFS
+#[derive(Debug, Default)]
+pub struct PageHashManifest {
+    files: HashMap<String, Arc<FilePageHashes>>,
+}
+
+/// The trusted size and page hashes of a file
+#[derive(Debug)]
+pub struct FilePageHashes {
+    size: usize,
+    hashes: Vec<sgx_sha256_hash_t>,
+}
+
+impl PageHashManifest {
+    /// Load the manifest from a file in the trusted root file system
+    pub fn load(manifest_path: &Path) -> Result<Self> {
+        let manifest_path = manifest_path
+            .to_str()
+            .ok_or_else(|| errno!(EINVAL, "invalid path of page hash manifest"))?;
+        let manifest_inode = ROOT_INODE.lookup(manifest_path)?;
+        let content = manifest_inode.read_as_vec()?;
+        let content = std::str::from_utf8(&content)
+            .map_err(|_| errno!(EINVAL, "page hash manifest is not valid UTF-8"))?;
+        Self::parse(content)
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut files = HashMap::new();
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let path = match fields.next() {
+                Some(path) => path,
+                // Skip empty lines
+                None => continue,
+            };
+            let size = fields
+                .next()
+                .and_then(|size| size.parse::<usize>().ok())
+                .ok_or_else(|| errno!(EINVAL, "invalid file size in page hash manifest"))?;
+            let hashes = fields
+                .map(parse_sha256_hash)
+                .collect::<Result<Vec<sgx_sha256_hash_t>>>()?;
+            if hashes.len() != align_up(size, PAGE_SIZE) / PAGE_SIZE {
+                return_errno!(EINVAL, "the number of page hashes mismatches the file size");
+            }
+            files.insert(
+                path.trim_start_matches('/').to_string(),
+                Arc::new(FilePageHashes { size, hashes }),
+            );
+        }
+        Ok(Self { files })
+    }
+
+    /// Get the page hashes of the file at `path`, which is relative to the HostFS source
+    pub fn get(&self, path: &str) -> Option<Arc<FilePageHashes>> {
+        self.files.get(path.trim_start_matches('/')).cloned()
+    }
+
+    /// Iterate over the relative paths and the page hashes of all the files
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<FilePageHashes>)> {
+        self.files
+            .iter()
+            .map(|(path, hashes)| (path.as_str(), hashes))
+    }
+}
+
+impl FilePageHashes {
+    /// The trusted size of the file
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Verify the content of the page at `page_idx`, which must be complete
+    pub fn verify_page(&self, page_idx: usize, page: &[u8]) -> Result<()> {
+        let expected_hash = self
+            .hashes
+            .get(page_idx)
+            .ok_or_else(|| errno!(EIO, "no hash for the page"))?;
+        // A page that is partially filled must be the last one of the file
+        let expected_len = min(PAGE_SIZE, self.size - page_idx * PAGE_SIZE);
+        if page.len() != expected_len {
+            return_errno!(EIO, "the length of the page is unexpected");
+        }
+        let actual_hash = sgx_tcrypto::rsgx_sha256_slice(page)
+            .map_err(|_| errno!(EIO, "failed to calculate the hash of the page"))?;
+        if &actual_hash != expected_hash {
+            return_errno!(EIO, "the page of the file has been tampered");
+        }
+        Ok(())
+    }
+}
+
+fn parse_sha256_hash(hash_str: &str) -> Result<sgx_sha256_hash_t> {
+    let mut hash: sgx_sha256_hash_t = Default::default();
+    if hash_str.len() != hash.len() * 2 {
+        return_errno!(EINVAL, "invalid page hash in page hash manifest");
+    }
+    for (byte_i, byte) in hash.iter_mut().enumerate() {
+        let byte_str = &hash_str[byte_i * 2..byte_i * 2 + 2];
+        *byte = u8::from_str_radix(byte_str, 16)
+            .map_err(|_| errno!(EINVAL, "invalid page hash in page hash manifest"))?;
+    }
+    Ok(hash)
+}
+
+/// A read-only window of a host file in the untrusted memory
+pub struct HostFileWindow {
+    // The start address and length of the host mapping
+    addr: usize,
+    len: usize,
+    // The file offset that the mapping starts from, aligned to pages
+    file_offset: usize,
+    page_hashes: Arc<FilePageHashes>,
+}
+
+impl HostFileWindow {
+    /// Map the part of the host file at `host_path`, which starts from `file_offset`
+    /// and spans at most `len` bytes, as a window.
+    pub fn new(
+        host_path: &Path,
+        file_offset: usize,
+        len: usize,
+        page_hashes: Arc<FilePageHashes>,
+    ) -> Result<Self> {
+        if file_offset % PAGE_SIZE != 0 {
+            return_errno!(EINVAL, "the offset of window must be page aligned");
+        }
+        // Never map the part beyond the trusted end of file. The host checks the
+        // file is not shorter than that, or the accesses beyond the actual end of
+        // file would trigger SIGBUS on the host.
+        let len = min(len, page_hashes.size.saturating_sub(file_offset));
+        if len == 0 {
+            return_errno!(EINVAL, "the window is empty");
+        }
+
+        let c_path = host_path
+            .to_str()
+            .and_then(|path| CString::new(path).ok())
+            .ok_or_else(|| errno!(EINVAL, "invalid host path"))?;
+        let addr = try_libc!({
+            let mut addr: *mut c_void = std::ptr::null_mut();
+            let status = occlum_ocall_mmap_host_file(&mut addr, c_path.as_ptr(), file_offset, len);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            if addr.is_null() {
+                -1
+            } else {
+                addr as isize
+            }
+        }) as usize;
+        // Sanity check: the host must give a mapping outside the enclave
+        if !sgx_trts::trts::rsgx_raw_is_outside_enclave(addr as *const u8, len) {
+            unmap_window(addr, len);
+            return_errno!(EFAULT, "the host file window is not outside the enclave");
+        }
+
+        Ok(Self {
+            addr,
+            len,
+            file_offset,
+            page_hashes,
+        })
+    }
+
+    /// Copy the content of the file starting from `offset` into `buf`, verifying
+    /// every page that is copied. Bytes beyond the end of the window are filled
+    /// with zeros.
+    pub fn read_verified(&self, offset: usize, buf: &mut [u8]) -> Result<()> {
+        if offset < self.file_offset || offset % PAGE_SIZE != 0 {
+            return_errno!(EINVAL, "invalid offset to read the window");
+        }
+        let mut page_buf = [0u8; PAGE_SIZE];
+        let mut buf_offset = 0;
+        let mut window_offset = offset - self.file_offset;
+        while buf_offset < buf.len() {
+            let copy_len = min(PAGE_SIZE, buf.len() - buf_offset);
+            let dst = &mut buf[buf_offset..buf_offset + copy_len];
+            if window_offset >= self.len {
+                for b in dst {
+                    *b = 0;
+                }
+            } else {
+                let page_len = min(PAGE_SIZE, self.len - window_offset);
+                // Copy the page into the enclave before verification, so that
+                // the host cannot modify it after it is verified
+                let page = &mut page_buf[..page_len];
+                unsafe {
+                    std::ptr::copy_nonoverlapping(
+                        (self.addr + window_offset) as *const u8,
+                        page.as_mut_ptr(),
+                        page_len,
+                    );
+                }
+                let page_idx = (self.file_offset + window_offset) / PAGE_SIZE;
+                self.page_hashes.verify_page(page_idx, page)?;
+
+                let valid_len = min(page_len, copy_len);
+                dst[..valid_len].copy_from_slice(&page[..valid_len]);
+                for b in &mut dst[valid_len..] {
+                    *b = 0;
+                }
+            }
+            buf_offset += copy_len;
+            window_offset += PAGE_SIZE;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HostFileWindow {
+    fn drop(&mut self) {
+        unmap_window(self.addr, self.len);
+    }
+}
+
+fn unmap_window(addr: usize, len: usize) {
+    let ret = unsafe {
+        let mut ret = 0;
+        let status = occlum_ocall_munmap(&mut ret, addr as *mut c_void, len);
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        ret
+    };
+    if ret < 0 {
+        warn!("failed to unmap the host file window");
+    }
+}
+
+impl Debug for HostFileWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HostFileWindow")
+            .field("addr", &self.addr)
+            .field("len", &self.len)
+            .field("file_offset", &self.file_offset)
+            .finish()
+    }
+}
+
+unsafe impl Send for HostFileWindow {}
+unsafe impl Sync for HostFileWindow {}
+
+extern "C" {
+    fn occlum_ocall_mmap_host_file(
+        ret: *mut *mut c_void,
+        path: *const i8,
+        offset: usize,
+        len: usize,
+    ) -> sgx_status_t;
+    fn occlum_ocall_munmap(ret: *mut i32, addr: *mut c_void, len: usize) -> sgx_status_t;
+}
+
+/// Try to map a window of a file opened on HostFS.
+///
+/// Return None if the file is not on HostFS or has no trusted page hashes, in
+/// which case the caller should fall back to reading the file as usual.
+pub fn try_map_host_file_window(
+    file: &FileRef,
+    offset: usize,
+    len: usize,
+) -> Option<Result<HostFileWindow>> {
+    let inode_file = file.as_inode_file().ok()?;
+    let hnode = HNode::from_inode(inode_file.inode().as_ref())?;
+    let page_hashes = hnode.page_hashes()?;
+    Some(HostFileWindow::new(
+        hnode.host_path(),
+        offset,
+        len,
+        page_hashes,
+    ))
+}
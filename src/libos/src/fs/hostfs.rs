@@ -1,17 +1,33 @@
+use crate::prelude::{SgxMutex as Mutex, SgxMutexGuard as MutexGuard};
 use alloc::string::String;
 use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use core::any::Any;
+use core::mem;
 use rcore_fs::vfs::*;
+use rcore_fs_mountfs::MNode;
+use sgx_types::sgx_status_t;
+use std::ffi::CString;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::{SgxMutex as Mutex, SgxMutexGuard as MutexGuard};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::untrusted::fs;
 use std::untrusted::path::PathEx;
 
+use super::host_file_window::{FilePageHashes, PageHashManifest};
+
 /// Untrusted file system at host
 pub struct HostFS {
     path: PathBuf,
     self_ref: Weak<HostFS>,
+    /// The path of the page hash manifest in the trusted root file system
+    page_hash_manifest_path: Option<PathBuf>,
+    /// The page hash manifest, which is loaded on first use
+    page_hash_manifest: Mutex<Option<Arc<PageHashManifest>>>,
+    /// Bumped whenever a file is changed through this HostFS, which makes the
+    /// metadata prefetched before the change stale. Unlike a clock, it needs no
+    /// OCall to read.
+    generation: AtomicU64,
 }
 
 /// INode for `HostFS`
@@ -19,6 +35,10 @@ pub struct HNode {
     path: PathBuf,
     file: Mutex<Option<fs::File>>,
     fs: Arc<HostFS>,
+    /// The entries of the directory, got in one batch on the first `get_entry`
+    dir_entries: Mutex<Option<Vec<DirEntry>>>,
+    /// The metadata got when the parent directory was scanned, used only once
+    prefetched_metadata: Mutex<Option<PrefetchedMetadata>>,
 }
 
 impl FileSystem for HostFS {
@@ -28,11 +48,11 @@ impl FileSystem for HostFS {
     }
 
     fn root_inode(&self) -> Arc<dyn INode> {
-        Arc::new(HNode {
-            path: self.path.clone(),
-            file: Mutex::new(None),
-            fs: self.self_ref.upgrade().unwrap(),
-        })
+        Arc::new(HNode::new(
+            self.path.clone(),
+            self.self_ref.upgrade().unwrap(),
+            None,
+        ))
     }
 
     fn info(&self) -> FsInfo {
@@ -42,14 +62,49 @@ impl FileSystem for HostFS {
 
 impl HostFS {
     /// Create a new `HostFS` from host `path`
-    pub fn new(path: impl AsRef<Path>) -> Arc<HostFS> {
+    ///
+    /// If `page_hash_manifest_path` is given, the files listed in the manifest
+    /// can be mmap-ed through verified windows of the host files.
+    pub fn new(path: impl AsRef<Path>, page_hash_manifest_path: Option<PathBuf>) -> Arc<HostFS> {
         HostFS {
             path: path.as_ref().to_path_buf(),
             self_ref: Weak::default(),
+            page_hash_manifest_path,
+            page_hash_manifest: Mutex::new(None),
+            generation: AtomicU64::new(0),
         }
         .wrap()
     }
 
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Mark the metadata prefetched so far as stale, after a file is changed
+    fn bump_generation(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Get the trusted page hashes of the file at the host path
+    fn page_hashes(&self, host_path: &Path) -> Option<Arc<FilePageHashes>> {
+        let manifest_path = self.page_hash_manifest_path.as_ref()?;
+        let manifest = {
+            let mut manifest = self.page_hash_manifest.lock().unwrap();
+            if manifest.is_none() {
+                match PageHashManifest::load(manifest_path) {
+                    Ok(new_manifest) => *manifest = Some(Arc::new(new_manifest)),
+                    Err(e) => {
+                        warn!("HostFS: failed to load the page hash manifest: {:?}", e);
+                        return None;
+                    }
+                }
+            }
+            manifest.as_ref().unwrap().clone()
+        };
+        let rel_path = host_path.strip_prefix(&self.path).ok()?.to_str()?;
+        manifest.get(rel_path)
+    }
+
     /// Wrap pure `HostFS` with Arc
     /// Used in constructors
     fn wrap(self) -> Arc<Self> {
@@ -86,6 +141,7 @@ impl INode for HNode {
         let file = guard.as_mut().unwrap();
         try_std!(file.seek(SeekFrom::Start(offset as u64)));
         let len = try_std!(file.write(buf));
+        self.fs.bump_generation();
         Ok(len)
     }
 
@@ -94,6 +150,10 @@ impl INode for HNode {
     }
 
     fn metadata(&self) -> Result<Metadata> {
+        let prefetched_metadata = self.prefetched_metadata.lock().unwrap().take();
+        if let Some(metadata) = prefetched_metadata.and_then(|m| m.into_fresh(&self.fs)) {
+            return Ok(metadata);
+        }
         let metadata = try_std!(self.path.metadata());
         Ok(metadata.into_fs_metadata())
     }
@@ -135,16 +195,14 @@ impl INode for HNode {
                 return Err(FsError::PermError);
             }
         }
-        Ok(Arc::new(HNode {
-            path: new_path,
-            file: Mutex::new(None),
-            fs: self.fs.clone(),
-        }))
+        self.fs.bump_generation();
+        Ok(Arc::new(HNode::new(new_path, self.fs.clone(), None)))
     }
 
     fn link(&self, name: &str, other: &Arc<dyn INode>) -> Result<()> {
         let other = other.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
         try_std!(fs::hard_link(&other.path, &self.path.join(name)));
+        self.fs.bump_generation();
         Ok(())
     }
 
@@ -157,6 +215,7 @@ impl INode for HNode {
         } else {
             return Err(FsError::EntryNotFound);
         }
+        self.fs.bump_generation();
         Ok(())
     }
 
@@ -167,33 +226,38 @@ impl INode for HNode {
             target.path.join(new_name)
         };
         try_std!(fs::rename(&old_path, &new_path));
+        self.fs.bump_generation();
         Ok(())
     }
 
     fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
         let new_path = self.path.join(name);
-        if !new_path.exists() {
+        // Reuse the metadata got by the last scan of this directory, which
+        // saves the OCalls of the check and the stat for the common
+        // readdir-then-stat pattern.
+        let prefetched_metadata = self.take_prefetched_metadata(name);
+        if prefetched_metadata.is_none() && !new_path.exists() {
             return Err(FsError::EntryNotFound);
         }
-        Ok(Arc::new(HNode {
-            path: new_path,
-            file: Mutex::new(None),
-            fs: self.fs.clone(),
-        }))
+        Ok(Arc::new(HNode::new(
+            new_path,
+            self.fs.clone(),
+            prefetched_metadata,
+        )))
     }
 
     fn get_entry(&self, id: usize) -> Result<String> {
-        if !self.path.is_dir() {
-            return Err(FsError::NotDir);
-        }
-        if let Some(entry) = try_std!(self.path.read_dir()).nth(id) {
-            try_std!(entry)
-                .file_name()
-                .into_string()
-                .map_err(|_| FsError::InvalidParam)
-        } else {
-            return Err(FsError::EntryNotFound);
+        let mut dir_entries = self.dir_entries.lock().unwrap();
+        // Scan the whole directory when reading from its beginning
+        if id == 0 || dir_entries.is_none() {
+            *dir_entries = Some(read_dir_entries(&self.path, self.fs.generation())?);
         }
+        dir_entries
+            .as_ref()
+            .unwrap()
+            .get(id)
+            .map(|entry| entry.name.clone())
+            .ok_or(FsError::EntryNotFound)
     }
 
     fn io_control(&self, cmd: u32, data: usize) -> Result<()> {
@@ -211,6 +275,55 @@ impl INode for HNode {
 }
 
 impl HNode {
+    fn new(
+        path: PathBuf,
+        fs: Arc<HostFS>,
+        prefetched_metadata: Option<PrefetchedMetadata>,
+    ) -> Self {
+        Self {
+            path,
+            file: Mutex::new(None),
+            fs,
+            dir_entries: Mutex::new(None),
+            prefetched_metadata: Mutex::new(prefetched_metadata),
+        }
+    }
+
+    /// Get the HNode of an inode, which may be wrapped by MNode if the HostFS is
+    /// mounted. Return None if the inode is not on HostFS.
+    pub fn from_inode(inode: &dyn INode) -> Option<&HNode> {
+        match inode.downcast_ref::<MNode>() {
+            Some(mnode) => mnode.inode.downcast_ref::<HNode>(),
+            None => inode.downcast_ref::<HNode>(),
+        }
+    }
+
+    /// Get the path of the file on the host
+    pub fn host_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the trusted page hashes of the file, if any
+    pub fn page_hashes(&self) -> Option<Arc<FilePageHashes>> {
+        self.fs.page_hashes(&self.path)
+    }
+
+    /// Take the metadata of the entry named `name` from the last scan of this
+    /// directory, if no file has been changed since the scan
+    fn take_prefetched_metadata(&self, name: &str) -> Option<PrefetchedMetadata> {
+        let mut dir_entries = self.dir_entries.lock().unwrap();
+        let prefetched_metadata = dir_entries
+            .as_mut()?
+            .iter_mut()
+            .find(|entry| entry.name == name)?
+            .metadata
+            .take()?;
+        if !prefetched_metadata.is_fresh(&self.fs) {
+            return None;
+        }
+        Some(prefetched_metadata)
+    }
+
     /// Ensure to open the file and store a `File` into `self.file`,
     /// return the `MutexGuard`.
     /// If the type of `self.path` is not file, then return Err
@@ -234,6 +347,155 @@ impl HNode {
     }
 }
 
+/// A directory entry with its metadata
+struct DirEntry {
+    name: String,
+    metadata: Option<PrefetchedMetadata>,
+}
+
+/// The metadata of a file got by a scan of its parent directory.
+///
+/// It is used at most once, and only if no file of the HostFS has been changed
+/// through the LibOS since the scan. Like the attribute caches of the network file
+/// systems, the changes made by the host in the meantime are not seen.
+struct PrefetchedMetadata {
+    metadata: Metadata,
+    /// The generation of the HostFS before the scan
+    generation: u64,
+}
+
+impl PrefetchedMetadata {
+    fn is_fresh(&self, fs: &HostFS) -> bool {
+        self.generation == fs.generation()
+    }
+
+    fn into_fresh(self, fs: &HostFS) -> Option<Metadata> {
+        if !self.is_fresh(fs) {
+            return None;
+        }
+        Some(self.metadata)
+    }
+}
+
+/// The header of a directory entry returned by `occlum_ocall_read_dir_entries`.
+///
+/// It must be kept in sync with the C definition in pal/src/ocalls/fs.c.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct occlum_dir_entry {
+    rec_len: u32,
+    name_len: u32,
+    dev: u64,
+    ino: u64,
+    size: u64,
+    blksize: u64,
+    blocks: u64,
+    atime_sec: i64,
+    atime_nsec: i64,
+    mtime_sec: i64,
+    mtime_nsec: i64,
+    ctime_sec: i64,
+    ctime_nsec: i64,
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+}
+
+extern "C" {
+    fn occlum_ocall_read_dir_entries(
+        ret: *mut i64,
+        path: *const i8,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> sgx_status_t;
+}
+
+/// Read all the entries of a host directory, together with their metadata,
+/// with (usually) one OCall. The metadata is of the `generation` of the HostFS.
+fn read_dir_entries(path: &Path, generation: u64) -> Result<Vec<DirEntry>> {
+    const INIT_BUF_LEN: usize = 64 * 1024;
+    const MAX_BUF_LEN: usize = 16 * 1024 * 1024;
+
+    let c_path = path
+        .to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or(FsError::InvalidParam)?;
+
+    let mut buf_len = INIT_BUF_LEN;
+    loop {
+        let mut buf = vec![0u8; buf_len];
+        let ret = unsafe {
+            let mut ret = 0;
+            let status =
+                occlum_ocall_read_dir_entries(&mut ret, c_path.as_ptr(), buf.as_mut_ptr(), buf_len);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            ret
+        };
+        if ret < 0 {
+            let errno = unsafe { sgx_trts::libc::errno() };
+            if errno == sgx_trts::libc::ENOTDIR {
+                return Err(FsError::NotDir);
+            }
+            return Err(std::io::Error::from_raw_os_error(errno).into_fs_error());
+        }
+
+        let total_len = ret as usize;
+        if total_len > buf_len {
+            // The directory is larger than the buffer; retry with a larger one
+            if total_len > MAX_BUF_LEN {
+                warn!("HostFS: the directory {:?} has too many entries", path);
+                return Err(FsError::NoDeviceSpace);
+            }
+            buf_len = total_len;
+            continue;
+        }
+        return parse_dir_entries(&buf[..total_len], generation);
+    }
+}
+
+/// Parse the entries returned by the untrusted host
+fn parse_dir_entries(buf: &[u8], generation: u64) -> Result<Vec<DirEntry>> {
+    let header_len = mem::size_of::<occlum_dir_entry>();
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < buf.len() {
+        // Sanity checks: the host is not trusted
+        if buf.len() - offset < header_len {
+            return Err(FsError::InvalidParam);
+        }
+        let header =
+            unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr() as *const occlum_dir_entry) };
+        let rec_len = header.rec_len as usize;
+        let name_len = header.name_len as usize;
+        if rec_len > buf.len() - offset || name_len >= rec_len.saturating_sub(header_len) {
+            return Err(FsError::InvalidParam);
+        }
+        let name_start = offset + header_len;
+        let name = core::str::from_utf8(&buf[name_start..name_start + name_len])
+            .map_err(|_| FsError::InvalidParam)?;
+        if name.contains('/') || name.contains('\0') {
+            return Err(FsError::InvalidParam);
+        }
+
+        // Skip "." and "..", as what std::fs::read_dir does
+        if name != "." && name != ".." {
+            entries.push(DirEntry {
+                name: String::from(name),
+                metadata: header
+                    .into_fs_metadata()
+                    .map(|metadata| PrefetchedMetadata {
+                        metadata,
+                        generation,
+                    }),
+            });
+        }
+        offset += rec_len;
+    }
+    Ok(entries)
+}
+
 trait IntoFsError {
     fn into_fs_error(self) -> FsError;
 }
@@ -256,6 +518,48 @@ trait IntoFsMetadata {
     fn into_fs_metadata(self) -> Metadata;
 }
 
+impl occlum_dir_entry {
+    /// Convert to metadata, or None if the file type is unknown, e.g., the zero
+    /// mode of an entry without stat data
+    fn into_fs_metadata(self) -> Option<Metadata> {
+        use sgx_trts::libc;
+        let type_ = match self.mode & 0xf000 {
+            libc::S_IFCHR => FileType::CharDevice,
+            libc::S_IFBLK => FileType::BlockDevice,
+            libc::S_IFDIR => FileType::Dir,
+            libc::S_IFREG => FileType::File,
+            libc::S_IFLNK => FileType::SymLink,
+            libc::S_IFSOCK => FileType::Socket,
+            _ => return None,
+        };
+        Some(Metadata {
+            dev: self.dev as usize,
+            inode: self.ino as usize,
+            size: self.size as usize,
+            blk_size: self.blksize as usize,
+            blocks: self.blocks as usize,
+            atime: Timespec {
+                sec: self.atime_sec,
+                nsec: self.atime_nsec as i32,
+            },
+            mtime: Timespec {
+                sec: self.mtime_sec,
+                nsec: self.mtime_nsec as i32,
+            },
+            ctime: Timespec {
+                sec: self.ctime_sec,
+                nsec: self.ctime_nsec as i32,
+            },
+            type_,
+            mode: self.mode as u16 & 0o777,
+            nlinks: self.nlink as usize,
+            uid: self.uid as usize,
+            gid: self.gid as usize,
+            rdev: self.rdev as usize,
+        })
+    }
+}
+
 impl IntoFsMetadata for fs::Metadata {
     fn into_fs_metadata(self) -> Metadata {
         use sgx_trts::libc;
@@ -0,0 +1,285 @@
+//! A minimal client of HTTP/1.1 over the TLS of the host sockets.
+//!
+//! Only the GET requests of byte ranges are sent, which is all that a read-only
+//! share needs. The connection is kept alive between the requests, and is made
+//! again if the server has closed it. The responses are as untrusted as the host,
+//! so the lengths are checked here and the content is verified by the caller.
+use crate::net::{RecvFlags, SendFlags, SocketFile};
+use crate::prelude::*;
+use std::net::SocketAddr;
+
+/// The max length of the header of a response
+const MAX_HEADER_LEN: usize = 16 * 1024;
+
+/// The client of the share at a URL of HTTPS
+pub struct HttpClient {
+    /// The host and port in the URL, which is sent as the Host header
+    authority: String,
+    addr: SocketAddr,
+    /// The path in the URL, which ends with '/'
+    path_prefix: String,
+    conn: SgxMutex<Option<Connection>>,
+}
+
+impl HttpClient {
+    /// Create the client of the share at `url`, e.g., "https://10.0.0.1:8443/models".
+    ///
+    /// The host must be an IP address, since the names are not resolved in the
+    /// enclave. The port must be one of the client ports of TLS in Occlum.json,
+    /// whose CA and server name are used to authenticate the server.
+    pub fn new(url: &str) -> Result<Self> {
+        let scheme = "https://";
+        if !url.starts_with(scheme) {
+            return_errno!(EINVAL, "the URL of the share must be of HTTPS");
+        }
+        let rest = &url[scheme.len()..];
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => rest.split_at(idx),
+            None => (rest, "/"),
+        };
+        let addr = authority
+            .parse::<SocketAddr>()
+            .map_err(|_| errno!(EINVAL, "invalid address in the URL"))?;
+        let path_prefix = format!("{}/", path.trim_end_matches('/'));
+        Ok(Self {
+            authority: authority.to_string(),
+            addr,
+            path_prefix,
+            conn: SgxMutex::new(None),
+        })
+    }
+
+    /// Get `len` bytes of the file at `path`, which is relative to the URL,
+    /// starting from `offset`
+    pub fn get_range(&self, path: &str, offset: usize, len: usize) -> Result<Vec<u8>> {
+        debug_assert!(len > 0);
+        let request = format!(
+            "GET {}{} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\n\r\n",
+            self.path_prefix,
+            encode_path(path.trim_start_matches('/')),
+            self.authority,
+            offset,
+            offset + len - 1
+        );
+        let mut conn = self.conn.lock().unwrap();
+        // The idle connection may have been closed by the server, so retry once
+        // with a new connection
+        let is_reused = conn.is_some();
+        match self.do_request(&mut conn, request.as_bytes(), len) {
+            Err(e) if is_reused => {
+                debug!("retry the HTTP request on a new connection: {:?}", e);
+                self.do_request(&mut conn, request.as_bytes(), len)
+            }
+            ret => ret,
+        }
+    }
+
+    fn do_request(
+        &self,
+        conn: &mut Option<Connection>,
+        request: &[u8],
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        if conn.is_none() {
+            *conn = Some(Connection::connect(&self.addr)?);
+        }
+        let ret = conn.as_mut().unwrap().request(request, len);
+        // The connection is in an unknown state after any error
+        match &ret {
+            Ok((_, true)) | Err(_) => *conn = None,
+            Ok((_, false)) => {}
+        }
+        ret.map(|(body, _)| body)
+    }
+}
+
+/// A connection to the server, whose data are wrapped in TLS in the enclave
+struct Connection {
+    socket: SocketFile,
+    /// The data received but not consumed by the responses yet
+    received: Vec<u8>,
+}
+
+impl Connection {
+    fn connect(addr: &SocketAddr) -> Result<Self> {
+        let (domain, addr) = sockaddr_of(addr);
+        let socket = SocketFile::new(domain, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0)?;
+        socket.set_peer_addr(Some(&addr));
+        socket.init_tls_client(Some(&addr))?;
+        // Never fetch the share in plaintext
+        if !socket.is_tls() {
+            return_errno!(EINVAL, "TLS is not configured for the port of the share");
+        }
+        try_libc!(crate::sched::account_wait(|| unsafe {
+            libc::ocall::connect(
+                socket.fd(),
+                addr.as_ptr() as *const libc::sockaddr,
+                addr.len() as libc::socklen_t,
+            )
+        }));
+        Ok(Self {
+            socket,
+            received: Vec::new(),
+        })
+    }
+
+    /// Send the request of a range of `len` bytes, and return the body of the
+    /// response and whether the server closes the connection after it
+    fn request(&mut self, request: &[u8], len: usize) -> Result<(Vec<u8>, bool)> {
+        let mut sent = 0;
+        while sent < request.len() {
+            sent += self
+                .socket
+                .tls_sendv(&[&request[sent..]], SendFlags::MSG_NOSIGNAL)?;
+        }
+
+        let header_len = self.recv_header()?;
+        let header = std::str::from_utf8(&self.received[..header_len])
+            .map_err(|_| errno!(EIO, "the HTTP response is not valid UTF-8"))?;
+        let response = Response::parse(header)?;
+        self.received.drain(..header_len);
+        // A full response (200) is never accepted, as the file may be too large
+        if response.status != 206 {
+            warn!(
+                "unexpected status of the HTTP response: {}",
+                response.status
+            );
+            return_errno!(EIO, "the range of the file is not returned");
+        }
+        if response.content_len != Some(len) {
+            return_errno!(EIO, "unexpected length of the range of the file");
+        }
+
+        self.recv_until(len)?;
+        let body = self.received.drain(..len).collect();
+        Ok((body, response.closes_conn))
+    }
+
+    /// Receive until the end of the header, and return the length of the header
+    fn recv_header(&mut self) -> Result<usize> {
+        let mut searched_len = 0;
+        loop {
+            // The end of header may span the data received by the last recv
+            let start = searched_len.saturating_sub(3);
+            if let Some(idx) = find_subslice(&self.received[start..], b"\r\n\r\n") {
+                return Ok(start + idx + 4);
+            }
+            searched_len = self.received.len();
+            if searched_len > MAX_HEADER_LEN {
+                return_errno!(EIO, "the header of the HTTP response is too long");
+            }
+            self.recv_more()?;
+        }
+    }
+
+    fn recv_until(&mut self, len: usize) -> Result<()> {
+        while self.received.len() < len {
+            self.recv_more()?;
+        }
+        Ok(())
+    }
+
+    fn recv_more(&mut self) -> Result<()> {
+        let mut buf = vec![0u8; 16 * 1024];
+        let len = self
+            .socket
+            .tls_recvv(&mut [&mut buf[..]], RecvFlags::empty())?;
+        if len == 0 {
+            return_errno!(ECONNRESET, "the connection is closed by the server");
+        }
+        self.received.extend_from_slice(&buf[..len]);
+        Ok(())
+    }
+}
+
+/// The fields of the header of a response that matter
+struct Response {
+    status: u16,
+    content_len: Option<usize>,
+    closes_conn: bool,
+}
+
+impl Response {
+    fn parse(header: &str) -> Result<Self> {
+        let mut lines = header.split("\r\n");
+        let status_line = lines.next().unwrap_or("");
+        let mut fields = status_line.split(' ');
+        let version = fields.next().unwrap_or("");
+        if version != "HTTP/1.1" && version != "HTTP/1.0" {
+            return_errno!(EIO, "invalid HTTP response");
+        }
+        let status = fields
+            .next()
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or_else(|| errno!(EIO, "invalid status of HTTP response"))?;
+        let mut response = Self {
+            status,
+            content_len: None,
+            // The connections of HTTP/1.0 are not kept alive by default
+            closes_conn: version == "HTTP/1.0",
+        };
+        for line in lines {
+            let idx = match line.find(':') {
+                Some(idx) => idx,
+                None => continue,
+            };
+            let (name, value) = (&line[..idx], line[idx + 1..].trim());
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let content_len = value
+                    .parse::<usize>()
+                    .map_err(|_| errno!(EIO, "invalid Content-Length of HTTP response"))?;
+                response.content_len = Some(content_len);
+            } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                return_errno!(EIO, "the transfer encoding of HTTP is not supported");
+            } else if name.eq_ignore_ascii_case("Connection") {
+                response.closes_conn = value.eq_ignore_ascii_case("close");
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Convert the address into the domain and the bytes of `struct sockaddr_*`
+fn sockaddr_of(addr: &SocketAddr) -> (c_int, Vec<u8>) {
+    fn as_bytes<T>(addr: &T) -> Vec<u8> {
+        let len = std::mem::size_of::<T>();
+        unsafe { std::slice::from_raw_parts(addr as *const T as *const u8, len) }.to_vec()
+    }
+
+    match addr {
+        SocketAddr::V4(addr) => {
+            let mut sockaddr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+            sockaddr.sin_port = addr.port().to_be();
+            sockaddr.sin_addr.s_addr = u32::from_ne_bytes(addr.ip().octets());
+            (libc::AF_INET, as_bytes(&sockaddr))
+        }
+        SocketAddr::V6(addr) => {
+            let mut sockaddr: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sockaddr.sin6_port = addr.port().to_be();
+            sockaddr.sin6_addr.s6_addr = addr.ip().octets();
+            (libc::AF_INET6, as_bytes(&sockaddr))
+        }
+    }
+}
+
+/// Percent-encode the path, except the unreserved characters and '/'
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for &byte in path.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
@@ -0,0 +1,366 @@
+//! A read-only file system shared over HTTPS.
+//!
+//! Large files that are the same for many enclaves, e.g., models or reference
+//! data, can be fetched from a share on the network instead of being baked into
+//! the image of each enclave. The files are read by the GET requests of byte
+//! ranges over the TLS of the host sockets, so the server is authenticated by
+//! the client config of TLS in Occlum.json.
+//!
+//! The server is trusted for neither the list nor the content of the files. The
+//! files of the share are those listed in a page hash manifest in the trusted
+//! root file system (see `host_file_window`), and every page fetched is verified
+//! against its hash in the manifest before it is read by the user.
+use crate::prelude::{align_down, SgxMutex as Mutex};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use rcore_fs::vfs::*;
+use std::path::PathBuf;
+
+use self::http::HttpClient;
+use super::host_file_window::{FilePageHashes, PageHashManifest};
+use crate::vm::PAGE_SIZE;
+
+mod http;
+
+/// The length of the data fetched by a request, which is cached by the file
+const FETCH_LEN: usize = 64 * PAGE_SIZE;
+
+/// Read-only file system shared by an HTTPS server
+pub struct HttpFS {
+    client: HttpClient,
+    /// The path of the page hash manifest in the trusted root file system
+    manifest_path: PathBuf,
+    /// The tree of the files in the manifest, which is loaded on first use
+    tree: Mutex<Option<Arc<Tree>>>,
+    self_ref: Weak<HttpFS>,
+}
+
+/// INode for `HttpFS`
+pub struct HttpNode {
+    /// The index of the node in the tree
+    id: usize,
+    fs: Arc<HttpFS>,
+    /// The chunk of the file fetched last time, and its offset
+    cache: Mutex<Option<(usize, Vec<u8>)>>,
+}
+
+/// The files and directories of the share, where the root is the first one
+struct Tree {
+    nodes: Vec<TreeNode>,
+}
+
+struct TreeNode {
+    /// The path relative to the URL of the share
+    path: String,
+    parent: usize,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Dir(BTreeMap<String, usize>),
+    File(Arc<FilePageHashes>),
+}
+
+const ROOT_ID: usize = 0;
+
+impl FileSystem for HttpFS {
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        Arc::new(HttpNode::new(ROOT_ID, self.self_ref.upgrade().unwrap()))
+    }
+
+    fn info(&self) -> FsInfo {
+        unimplemented!()
+    }
+}
+
+impl HttpFS {
+    /// Create a new `HttpFS` of the share at `url`, whose files are listed in
+    /// the page hash manifest at `manifest_path`
+    pub fn new(url: &str, manifest_path: PathBuf) -> crate::prelude::Result<Arc<HttpFS>> {
+        let client = HttpClient::new(url)?;
+        Ok(HttpFS {
+            client,
+            manifest_path,
+            tree: Mutex::new(None),
+            self_ref: Weak::default(),
+        }
+        .wrap())
+    }
+
+    /// Wrap pure `HttpFS` with Arc
+    /// Used in constructors
+    fn wrap(self) -> Arc<Self> {
+        let fs = Arc::new(self);
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+        }
+        unsafe { Arc::from_raw(ptr) }
+    }
+
+    fn tree(&self) -> Result<Arc<Tree>> {
+        let mut tree = self.tree.lock().unwrap();
+        if tree.is_none() {
+            // The manifest is in the root file system, which may not be ready
+            // when the share is mounted
+            let manifest = PageHashManifest::load(&self.manifest_path).map_err(|e| {
+                warn!("HttpFS: failed to load the page hash manifest: {:?}", e);
+                FsError::NoDevice
+            })?;
+            *tree = Some(Arc::new(Tree::new(&manifest)?));
+        }
+        Ok(tree.as_ref().unwrap().clone())
+    }
+}
+
+impl INode for HttpNode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let tree = self.fs.tree()?;
+        let node = &tree.nodes[self.id];
+        let hashes = match &node.kind {
+            NodeKind::Dir(_) => return Err(FsError::IsDir),
+            NodeKind::File(hashes) => hashes,
+        };
+        if offset >= hashes.size() {
+            return Ok(0);
+        }
+        let len = buf.len().min(hashes.size() - offset);
+        let mut cache = self.cache.lock().unwrap();
+        let mut read_len = 0;
+        while read_len < len {
+            let file_offset = offset + read_len;
+            let chunk_offset = align_down(file_offset, FETCH_LEN);
+            if cache
+                .as_ref()
+                .map_or(true, |(cached, _)| *cached != chunk_offset)
+            {
+                let chunk = self.fetch_chunk(&node.path, hashes, chunk_offset)?;
+                *cache = Some((chunk_offset, chunk));
+            }
+            let chunk = &cache.as_ref().unwrap().1;
+            let chunk_start = file_offset - chunk_offset;
+            let copy_len = (len - read_len).min(chunk.len() - chunk_start);
+            buf[read_len..read_len + copy_len]
+                .copy_from_slice(&chunk[chunk_start..chunk_start + copy_len]);
+            read_len += copy_len;
+        }
+        Ok(len)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::WrProtected)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: false,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let tree = self.fs.tree()?;
+        let (type_, size, mode, nlinks) = match &tree.nodes[self.id].kind {
+            NodeKind::Dir(_) => (FileType::Dir, 0, 0o555, 2),
+            NodeKind::File(hashes) => (FileType::File, hashes.size(), 0o444, 1),
+        };
+        let time = Timespec { sec: 0, nsec: 0 };
+        Ok(Metadata {
+            dev: 0,
+            inode: self.id + 1,
+            size,
+            blk_size: PAGE_SIZE,
+            blocks: (size + 511) / 512,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            type_,
+            mode,
+            nlinks,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn set_metadata(&self, _metadata: &Metadata) -> Result<()> {
+        Err(FsError::WrProtected)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn resize(&self, _len: usize) -> Result<()> {
+        Err(FsError::WrProtected)
+    }
+
+    fn create(&self, _name: &str, _type_: FileType, _mode: u32) -> Result<Arc<dyn INode>> {
+        Err(FsError::WrProtected)
+    }
+
+    fn link(&self, _name: &str, _other: &Arc<dyn INode>) -> Result<()> {
+        Err(FsError::WrProtected)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<()> {
+        Err(FsError::WrProtected)
+    }
+
+    fn move_(&self, _old_name: &str, _target: &Arc<dyn INode>, _new_name: &str) -> Result<()> {
+        Err(FsError::WrProtected)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let tree = self.fs.tree()?;
+        let node = &tree.nodes[self.id];
+        let entries = match &node.kind {
+            NodeKind::Dir(entries) => entries,
+            NodeKind::File(_) => return Err(FsError::NotDir),
+        };
+        let id = match name {
+            "." => self.id,
+            ".." => node.parent,
+            _ => *entries.get(name).ok_or(FsError::EntryNotFound)?,
+        };
+        Ok(Arc::new(HttpNode::new(id, self.fs.clone())))
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        let tree = self.fs.tree()?;
+        let entries = match &tree.nodes[self.id].kind {
+            NodeKind::Dir(entries) => entries,
+            NodeKind::File(_) => return Err(FsError::NotDir),
+        };
+        match id {
+            0 => Ok(String::from(".")),
+            1 => Ok(String::from("..")),
+            _ => entries
+                .keys()
+                .nth(id - 2)
+                .cloned()
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn io_control(&self, _cmd: u32, _data: usize) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl HttpNode {
+    fn new(id: usize, fs: Arc<HttpFS>) -> Self {
+        Self {
+            id,
+            fs,
+            cache: Mutex::new(None),
+        }
+    }
+
+    /// Fetch the chunk of the file at `chunk_offset`, and verify every page of it
+    fn fetch_chunk(
+        &self,
+        path: &str,
+        hashes: &FilePageHashes,
+        chunk_offset: usize,
+    ) -> Result<Vec<u8>> {
+        let len = FETCH_LEN.min(hashes.size() - chunk_offset);
+        let chunk = self
+            .fs
+            .client
+            .get_range(path, chunk_offset, len)
+            .map_err(|e| {
+                warn!("HttpFS: failed to fetch {:?}: {:?}", path, e);
+                FsError::DeviceError
+            })?;
+        for (page_i, page) in chunk.chunks(PAGE_SIZE).enumerate() {
+            if let Err(e) = hashes.verify_page(chunk_offset / PAGE_SIZE + page_i, page) {
+                warn!("HttpFS: failed to verify {:?}: {:?}", path, e);
+                return Err(FsError::NoIntegrity);
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+impl Tree {
+    /// Build the tree of the files in the manifest, whose parent directories are
+    /// implied by the paths
+    fn new(manifest: &PageHashManifest) -> Result<Self> {
+        let root = TreeNode {
+            path: String::new(),
+            parent: ROOT_ID,
+            kind: NodeKind::Dir(BTreeMap::new()),
+        };
+        let mut tree = Self { nodes: vec![root] };
+        for (path, hashes) in manifest.iter() {
+            let names: Vec<&str> = path
+                .split('/')
+                .filter(|name| !name.is_empty() && *name != ".")
+                .collect();
+            let (file_name, dir_names) = names.split_last().ok_or(FsError::InvalidParam)?;
+            let mut dir_id = ROOT_ID;
+            for dir_name in dir_names {
+                dir_id = match tree.lookup(dir_id, dir_name)? {
+                    Some(id) => id,
+                    None => tree.insert(dir_id, dir_name, NodeKind::Dir(BTreeMap::new()))?,
+                };
+            }
+            if tree.lookup(dir_id, file_name)?.is_some() {
+                return Err(FsError::EntryExist);
+            }
+            tree.insert(dir_id, file_name, NodeKind::File(hashes.clone()))?;
+        }
+        Ok(tree)
+    }
+
+    fn lookup(&self, dir_id: usize, name: &str) -> Result<Option<usize>> {
+        match &self.nodes[dir_id].kind {
+            NodeKind::Dir(entries) => Ok(entries.get(name).cloned()),
+            NodeKind::File(_) => Err(FsError::NotDir),
+        }
+    }
+
+    fn insert(&mut self, dir_id: usize, name: &str, kind: NodeKind) -> Result<usize> {
+        if name == ".." {
+            return Err(FsError::InvalidParam);
+        }
+        let id = self.nodes.len();
+        let path = if dir_id == ROOT_ID {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.nodes[dir_id].path, name)
+        };
+        self.nodes.push(TreeNode {
+            path,
+            parent: dir_id,
+            kind,
+        });
+        match &mut self.nodes[dir_id].kind {
+            NodeKind::Dir(entries) => entries.insert(name.to_string(), id),
+            NodeKind::File(_) => return Err(FsError::NotDir),
+        };
+        Ok(id)
+    }
+}
@@ -29,6 +29,7 @@ impl File for INodeFile {
             let info = self.inode.metadata()?;
             *offset = info.size;
         }
+        check_file_size_limit(*offset, buf.len())?;
         let len = self.inode.write_at(*offset, buf)?;
         *offset += len;
         Ok(len)
@@ -46,6 +47,7 @@ impl File for INodeFile {
         if !self.access_mode.writable() {
             return_errno!(EACCES, "File not writable");
         }
+        check_file_size_limit(offset, buf.len())?;
         let len = self.inode.write_at(offset, buf)?;
         Ok(len)
     }
@@ -95,7 +97,12 @@ impl File for INodeFile {
     fn seek(&self, pos: SeekFrom) -> Result<off_t> {
         let mut offset = self.offset.lock().unwrap();
         let new_offset = match pos {
-            SeekFrom::Start(off) => off as i64,
+            SeekFrom::Start(off) => {
+                if off > i64::max_value() as u64 {
+                    return_errno!(EINVAL, "file offset is too large");
+                }
+                off as i64
+            }
             SeekFrom::End(off) => (self.inode.metadata()?.size as i64)
                 .checked_add(off)
                 .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
@@ -226,6 +233,18 @@ impl INodeFile {
     pub fn get_abs_path(&self) -> &str {
         &self.abs_path
     }
+
+    pub fn inode(&self) -> &Arc<dyn INode> {
+        &self.inode
+    }
+}
+
+/// Writing beyond the maximum offset representable by off_t is not allowed
+fn check_file_size_limit(offset: usize, len: usize) -> Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= off_t::max_value() as usize => Ok(()),
+        _ => return_errno!(EFBIG, "the file would exceed the maximum file size"),
+    }
 }
 
 impl Debug for INodeFile {
@@ -0,0 +1,248 @@
+//! Inotify for the files on HostFS.
+//!
+//! The files on HostFS can be changed by the host at any time, e.g., the config
+//! files mounted from the host, so the applications watch them to reload. An
+//! inotify file is backed by an inotify fd of the host, which can be polled with
+//! poll, select and epoll like the other files backed by the host. Only the files
+//! on HostFS can be watched, as the changes of the other files are invisible to
+//! the host.
+//!
+//! The events are read from the host, so they are untrusted. Each event is checked
+//! before it is given to the user: the events of unknown watches are dropped, and
+//! the names must be single path components.
+use super::hostfs::HNode;
+use super::*;
+use std::ffi::CString;
+
+/// The length of struct inotify_event without the name
+const EVENT_HEADER_LEN: usize = 16;
+/// The max length of the name of an event, including the NUL and the padding
+const MAX_EVENT_NAME_LEN: usize = 256;
+
+/// The events that can be watched and reported
+const IN_ALL_EVENTS: u32 = 0x0000_0fff;
+const IN_UNMOUNT: u32 = 0x0000_2000;
+const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+const IN_IGNORED: u32 = 0x0000_8000;
+const IN_ISDIR: u32 = 0x4000_0000;
+/// The options of inotify_add_watch
+const IN_ONLYDIR: u32 = 0x0100_0000;
+pub const IN_DONT_FOLLOW: u32 = 0x0200_0000;
+const IN_EXCL_UNLINK: u32 = 0x0400_0000;
+const IN_MASK_CREATE: u32 = 0x1000_0000;
+const IN_MASK_ADD: u32 = 0x2000_0000;
+const IN_ONESHOT: u32 = 0x8000_0000;
+
+const VALID_WATCH_MASK: u32 = IN_ALL_EVENTS
+    | IN_ONLYDIR
+    | IN_DONT_FOLLOW
+    | IN_EXCL_UNLINK
+    | IN_MASK_CREATE
+    | IN_MASK_ADD
+    | IN_ONESHOT;
+const VALID_EVENT_MASK: u32 = IN_ALL_EVENTS | IN_UNMOUNT | IN_Q_OVERFLOW | IN_IGNORED | IN_ISDIR;
+
+#[derive(Debug)]
+pub struct InotifyFile {
+    host_fd: c_int,
+    // The watches added by the user, from the watch descriptors to the paths
+    watches: SgxMutex<HashMap<c_int, String>>,
+}
+
+impl InotifyFile {
+    pub fn new(flags: InotifyFlags) -> Result<Self> {
+        let host_fd = try_libc!({
+            let mut ret: i32 = 0;
+            let status = occlum_ocall_inotify_init1(&mut ret, flags.bits());
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            ret
+        });
+        Ok(Self {
+            host_fd,
+            watches: SgxMutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.host_fd
+    }
+
+    /// Watch the inode at `path`, which must be on HostFS
+    pub fn add_watch(&self, path: &str, inode: &Arc<dyn INode>, mask: u32) -> Result<c_int> {
+        if mask & !VALID_WATCH_MASK != 0 || mask & IN_ALL_EVENTS == 0 {
+            return_errno!(EINVAL, "invalid mask of inotify");
+        }
+        let hnode = HNode::from_inode(inode.as_ref())
+            .ok_or_else(|| errno!(EOPNOTSUPP, "only the files on HostFS can be watched"))?;
+        let host_path = hnode
+            .host_path()
+            .to_str()
+            .and_then(|host_path| CString::new(host_path).ok())
+            .ok_or_else(|| errno!(EINVAL, "invalid host path"))?;
+        let wd = try_libc!({
+            let mut ret: i32 = 0;
+            let status =
+                occlum_ocall_inotify_add_watch(&mut ret, self.host_fd, host_path.as_ptr(), mask);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            ret
+        });
+        if wd <= 0 {
+            return_errno!(EIO, "invalid watch descriptor from the host");
+        }
+        self.watches.lock().unwrap().insert(wd, path.to_string());
+        Ok(wd)
+    }
+
+    /// Remove the watch, which is forgotten once IN_IGNORED of it is read
+    pub fn rm_watch(&self, wd: c_int) -> Result<()> {
+        if !self.watches.lock().unwrap().contains_key(&wd) {
+            return_errno!(EINVAL, "invalid watch descriptor");
+        }
+        try_libc!({
+            let mut ret: i32 = 0;
+            let status = occlum_ocall_inotify_rm_watch(&mut ret, self.host_fd, wd);
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            ret
+        });
+        Ok(())
+    }
+
+    /// Copy the valid events from the host to `buf`, and return the length copied
+    fn filter_events(&self, events: &[u8], buf: &mut [u8]) -> Result<usize> {
+        let mut watches = self.watches.lock().unwrap();
+        let mut offset = 0;
+        let mut copied_len = 0;
+        while offset < events.len() {
+            let event = &events[offset..];
+            if event.len() < EVENT_HEADER_LEN {
+                return_errno!(EIO, "the inotify event is truncated");
+            }
+            let wd = c_int::from_ne_bytes([event[0], event[1], event[2], event[3]]);
+            let mask = u32::from_ne_bytes([event[4], event[5], event[6], event[7]]);
+            let name_len =
+                u32::from_ne_bytes([event[12], event[13], event[14], event[15]]) as usize;
+            if mask & !VALID_EVENT_MASK != 0
+                || name_len > MAX_EVENT_NAME_LEN
+                || event.len() < EVENT_HEADER_LEN + name_len
+            {
+                return_errno!(EIO, "invalid inotify event from the host");
+            }
+            let event_len = EVENT_HEADER_LEN + name_len;
+            let name = &event[EVENT_HEADER_LEN..event_len];
+            offset += event_len;
+
+            if mask & IN_Q_OVERFLOW == 0 && !watches.contains_key(&wd) {
+                warn!("drop the inotify event of an unknown watch: {}", wd);
+                continue;
+            }
+            if name_len > 0 && !is_valid_event_name(name) {
+                return_errno!(EIO, "invalid name of inotify event from the host");
+            }
+            if mask & IN_IGNORED != 0 {
+                watches.remove(&wd);
+            }
+            // The padding after the name is zeroed, rather than copied from the host
+            let dst = &mut buf[copied_len..copied_len + event_len];
+            dst[..EVENT_HEADER_LEN].copy_from_slice(&event[..EVENT_HEADER_LEN]);
+            let name_end = name.iter().position(|&byte| byte == 0).unwrap_or(0);
+            for byte in dst[EVENT_HEADER_LEN..].iter_mut() {
+                *byte = 0;
+            }
+            dst[EVENT_HEADER_LEN..EVENT_HEADER_LEN + name_end].copy_from_slice(&name[..name_end]);
+            copied_len += event_len;
+        }
+        Ok(copied_len)
+    }
+}
+
+/// The name must be a non-empty path component followed by the NULs
+fn is_valid_event_name(name: &[u8]) -> bool {
+    match name.iter().position(|&byte| byte == 0) {
+        Some(name_end) => name_end > 0 && !name[..name_end].contains(&b'/'),
+        None => false,
+    }
+}
+
+bitflags! {
+    pub struct InotifyFlags: i32 {
+        /// Non-blocking
+        const IN_NONBLOCK = 1 << 11;
+        /// Close on exec
+        const IN_CLOEXEC  = 1 << 19;
+    }
+}
+
+extern "C" {
+    fn occlum_ocall_inotify_init1(ret: *mut i32, flags: i32) -> sgx_status_t;
+    fn occlum_ocall_inotify_add_watch(
+        ret: *mut i32,
+        fd: c_int,
+        path: *const c_char,
+        mask: u32,
+    ) -> sgx_status_t;
+    fn occlum_ocall_inotify_rm_watch(ret: *mut i32, fd: c_int, wd: c_int) -> sgx_status_t;
+}
+
+impl Drop for InotifyFile {
+    fn drop(&mut self) {
+        let ret = unsafe { libc::ocall::close(self.host_fd) };
+        assert!(ret == 0);
+    }
+}
+
+impl File for InotifyFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut events = vec![0u8; buf.len()];
+        loop {
+            let len = try_libc!(libc::ocall::read(
+                self.host_fd,
+                events.as_mut_ptr() as *mut c_void,
+                events.len()
+            )) as usize;
+            if len > events.len() {
+                return_errno!(EIO, "invalid length of inotify events");
+            }
+            let copied_len = self.filter_events(&events[..len], buf)?;
+            // Read again if all of the events are dropped, which blocks or fails with
+            // EAGAIN as if there were no events
+            if copied_len > 0 || len == 0 {
+                return Ok(copied_len);
+            }
+        }
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        let ret = try_libc!(libc::ocall::fcntl_arg0(self.get_host_fd(), libc::F_GETFL));
+        Ok(StatusFlags::from_bits_truncate(ret as u32))
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        let raw_status_flags = (new_status_flags & StatusFlags::O_NONBLOCK).bits();
+        try_libc!(libc::ocall::fcntl_arg1(
+            self.get_host_fd(),
+            libc::F_SETFL,
+            raw_status_flags as c_int
+        ));
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait AsInotify {
+    fn as_inotify(&self) -> Result<&InotifyFile>;
+}
+
+impl AsInotify for FileRef {
+    fn as_inotify(&self) -> Result<&InotifyFile> {
+        self.as_any()
+            .downcast_ref::<InotifyFile>()
+            .ok_or_else(|| errno!(EBADF, "not an inotify file"))
+    }
+}
@@ -0,0 +1,217 @@
+//! The integrity-only paths inside the writable SEFS mounts.
+//!
+//! A writable SEFS mount may mark some of its paths (e.g., the directory of the
+//! binaries of an application) as integrity-only, so that code and data can share
+//! a mount while the code stays immutable and measured. The files at or under an
+//! integrity-only path must be listed in a manifest of file hashes, which is a
+//! file in the trusted root file system (see the `file_hashes` mount option) in
+//! the format of `sha256sum`:
+//!
+//! ```text
+//! <SHA-256 of the content> <path relative to the mount target>
+//! ```
+//!
+//! The policy is enforced when a file is opened or executed: a file at an
+//! integrity-only path cannot be opened to write, truncate or create, and the
+//! content of the file must match its hash in the manifest to be opened or
+//! executed. So a file that is modified in any other way, e.g., through a hard
+//! link, by the host or in a previous run of the enclave, is never used.
+use super::*;
+use config::ConfigMount;
+use sgx_tcrypto::SgxShaHandle;
+use std::path::{Component, Path, PathBuf};
+
+/// The size of the chunks in which a file is read to be hashed
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+lazy_static! {
+    static ref INTEGRITY_ONLY_FILES: IntegrityOnlyFiles =
+        IntegrityOnlyFiles::from_config(&config::LIBOS_CONFIG.mount);
+}
+
+#[derive(Debug, Default)]
+struct IntegrityOnlyFiles {
+    // The absolute integrity-only paths
+    paths: Vec<PathBuf>,
+    // The expected hashes of the files, keyed by the absolute paths
+    hashes: HashMap<PathBuf, sgx_sha256_hash_t>,
+}
+
+impl IntegrityOnlyFiles {
+    fn from_config(mount_configs: &[ConfigMount]) -> Self {
+        let mut files = Self::default();
+        for mount_config in mount_configs {
+            files.add_mount(mount_config);
+        }
+        files
+    }
+
+    fn add_mount(&mut self, mount_config: &ConfigMount) {
+        // The writable SEFS of the root file system is a layer of the UnionFS
+        if let Some(layers) = &mount_config.options.layers {
+            for layer in layers {
+                self.add_mount(layer);
+            }
+        }
+        if mount_config.type_ != "sefs" || mount_config.options.integrity_only_paths.is_empty() {
+            return;
+        }
+        let target = &mount_config.target;
+        self.paths.extend(
+            mount_config
+                .options
+                .integrity_only_paths
+                .iter()
+                .map(|path| target.join(path)),
+        );
+        // The paths are protected even if the manifest fails to load, where no
+        // file under them can be opened
+        let manifest_path = mount_config.options.file_hashes.as_ref().unwrap();
+        if let Err(e) = self.load_manifest(manifest_path, target) {
+            error!(
+                "failed to load the file hashes {:?} of {:?}: {:?}",
+                manifest_path, target, e
+            );
+        }
+    }
+
+    fn load_manifest(&mut self, manifest_path: &Path, target: &Path) -> Result<()> {
+        let manifest_path = manifest_path
+            .to_str()
+            .ok_or_else(|| errno!(EINVAL, "invalid path of file hashes"))?;
+        let content = ROOT_INODE.lookup(manifest_path)?.read_as_vec()?;
+        let content = std::str::from_utf8(&content)
+            .map_err(|_| errno!(EINVAL, "file hashes are not valid UTF-8"))?;
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let hash = match fields.next() {
+                Some(hash) => parse_file_hash(hash)?,
+                // Skip empty lines
+                None => continue,
+            };
+            // The path is marked by '*' if the file is hashed in binary mode
+            let path = fields
+                .next()
+                .map(|path| path.trim_start_matches('*').trim_start_matches('/'))
+                .ok_or_else(|| errno!(EINVAL, "no path in file hashes"))?;
+            self.hashes.insert(normalize_path(&target.join(path)), hash);
+        }
+        Ok(())
+    }
+
+    fn is_integrity_only(&self, abs_path: &Path) -> bool {
+        self.paths.iter().any(|path| abs_path.starts_with(path))
+    }
+
+    fn verify(&self, abs_path: &Path, actual_hash: &sgx_sha256_hash_t) -> Result<()> {
+        match self.hashes.get(abs_path) {
+            Some(hash) if hash == actual_hash => Ok(()),
+            Some(_) => return_errno!(EACCES, "the integrity-only file has been modified"),
+            None => return_errno!(EACCES, "the integrity-only file has no known hash"),
+        }
+    }
+}
+
+/// Check the open of the file at `path` with `flags`, before the file is looked
+/// up or created. An integrity-only file cannot be written, truncated or created.
+///
+/// Return the absolute path of the file if it is integrity-only, whose content
+/// must be verified once it is looked up.
+pub fn check_open_integrity_only(fs: &FsView, path: &str, flags: u32) -> Result<Option<PathBuf>> {
+    if INTEGRITY_ONLY_FILES.paths.is_empty() {
+        return Ok(None);
+    }
+    // The file is opened at where the symlink points to if it is followed. The
+    // open fails anyway if the path cannot be resolved.
+    let creation_flags = CreationFlags::from_bits_truncate(flags);
+    let real_path = if creation_flags.no_follow_symlink() {
+        path.to_owned()
+    } else {
+        fs.lookup_real_path(path)
+            .unwrap_or_else(|_| path.to_owned())
+    };
+    let abs_path = normalize_path(Path::new(&fs.convert_to_abs_path(&real_path)));
+    if !INTEGRITY_ONLY_FILES.is_integrity_only(&abs_path) {
+        return Ok(None);
+    }
+    let access_mode = AccessMode::from_u32(flags)?;
+    if access_mode.writable()
+        || creation_flags.contains(CreationFlags::O_TRUNC)
+        || creation_flags.can_create()
+    {
+        return_errno!(EPERM, "the file is integrity-only");
+    }
+    Ok(Some(abs_path))
+}
+
+/// Verify the content of the integrity-only file `inode` opened at `abs_path`
+pub fn verify_integrity_only_inode(abs_path: &Path, inode: &Arc<dyn INode>) -> Result<()> {
+    // The directories (and the symlinks opened with O_PATH) have no content to verify
+    if inode.metadata()?.type_ != FileType::File {
+        return Ok(());
+    }
+    let hash = hash_inode(inode)?;
+    INTEGRITY_ONLY_FILES.verify(abs_path, &hash)
+}
+
+/// Verify the content of the file executed at `path` if it is an integrity-only
+/// file. The content is the one loaded, so it cannot be changed after the check.
+pub fn verify_integrity_only_exec(fs: &FsView, path: &str, content: &[u8]) -> Result<()> {
+    if INTEGRITY_ONLY_FILES.paths.is_empty() {
+        return Ok(());
+    }
+    let real_path = fs.lookup_real_path(path)?;
+    let abs_path = normalize_path(Path::new(&fs.convert_to_abs_path(&real_path)));
+    if !INTEGRITY_ONLY_FILES.is_integrity_only(&abs_path) {
+        return Ok(());
+    }
+    let hash = sgx_tcrypto::rsgx_sha256_slice(content)
+        .map_err(|_| errno!(EIO, "failed to calculate the hash of the file"))?;
+    INTEGRITY_ONLY_FILES.verify(&abs_path, &hash)
+}
+
+fn hash_inode(inode: &Arc<dyn INode>) -> Result<sgx_sha256_hash_t> {
+    let sgx_err = |_| errno!(EIO, "failed to calculate the hash of the file");
+    let handle = SgxShaHandle::new();
+    handle.init().map_err(sgx_err)?;
+    let mut buf = vec![0; HASH_CHUNK_SIZE];
+    let mut offset = 0;
+    loop {
+        let len = inode.read_at(offset, &mut buf)?;
+        if len == 0 {
+            break;
+        }
+        handle.update_slice(&buf[..len]).map_err(sgx_err)?;
+        offset += len;
+    }
+    handle.get_hash().map_err(sgx_err)
+}
+
+/// Normalize the absolute path lexically, so that the same file is not reached by
+/// another spelling of the path, e.g., "/app/./bin" or "/app/lib/../bin"
+fn normalize_path(abs_path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in abs_path.components() {
+        match component {
+            Component::Normal(name) => normalized.push(name),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+fn parse_file_hash(hash_str: &str) -> Result<sgx_sha256_hash_t> {
+    let mut hash: sgx_sha256_hash_t = Default::default();
+    if hash_str.len() != hash.len() * 2 {
+        return_errno!(EINVAL, "invalid hash in file hashes");
+    }
+    for (byte_i, byte) in hash.iter_mut().enumerate() {
+        let byte_str = &hash_str[byte_i * 2..byte_i * 2 + 2];
+        *byte = u8::from_str_radix(byte_str, 16)
+            .map_err(|_| errno!(EINVAL, "invalid hash in file hashes"))?;
+    }
+    Ok(hash)
+}
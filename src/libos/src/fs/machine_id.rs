@@ -0,0 +1,63 @@
+//! The machine ID in /etc/machine-id.
+//!
+//! Many applications use the machine ID as the identity of the node. The ID is
+//! generated randomly inside the enclave on the first boot, and is kept in the
+//! container layer of the root file system, which is sealed. So the ID is stable
+//! across the reboots of an instance, and is never given by the host. The ID in
+//! the image, if any, is hidden by the one in the container layer, as the files
+//! of the image are visible to the host when the image is built.
+use super::dev_fs::DevRandom;
+use super::*;
+
+/// The length of the ID in hex digits, without the trailing newline
+const MACHINE_ID_LEN: usize = 32;
+
+/// Make sure that there is a valid machine ID in the container layer
+pub fn init_machine_id(container_root: &Arc<dyn INode>) -> Result<()> {
+    let etc_inode = match container_root.find("etc") {
+        Ok(inode) => inode,
+        Err(FsError::EntryNotFound) => container_root.create("etc", FileType::Dir, 0o755)?,
+        Err(e) => return Err(e.into()),
+    };
+    let id_inode = match etc_inode.find("machine-id") {
+        Ok(inode) => {
+            if is_valid_machine_id(&inode.read_as_vec()?) {
+                return Ok(());
+            }
+            inode
+        }
+        Err(FsError::EntryNotFound) => etc_inode.create("machine-id", FileType::File, 0o444)?,
+        Err(e) => return Err(e.into()),
+    };
+
+    let machine_id = new_machine_id()?;
+    info!("generated the machine ID: {}", machine_id);
+    let content = format!("{}\n", machine_id);
+    id_inode.resize(0)?;
+    id_inode.write_at(0, content.as_bytes())?;
+    id_inode.sync_all()?;
+    Ok(())
+}
+
+/// Generate a random ID, which is formatted like a UUID of version 4 as systemd
+/// does, but without the hyphens
+fn new_machine_id() -> Result<String> {
+    let mut bytes = [0u8; MACHINE_ID_LEN / 2];
+    DevRandom.read(&mut bytes)?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Check the ID in the format of machine-id(5), which is not all zeros
+fn is_valid_machine_id(content: &[u8]) -> bool {
+    let id = match content.last() {
+        Some(b'\n') => &content[..content.len() - 1],
+        _ => content,
+    };
+    id.len() == MACHINE_ID_LEN
+        && id
+            .iter()
+            .all(|&c| c.is_ascii_digit() || (b'a'..=b'f').contains(&c))
+        && id.iter().any(|&c| c != b'0')
+}
@@ -10,7 +10,10 @@ use std::mem::MaybeUninit;
 use std::path::Path;
 use untrusted::{SliceAsMutPtrAndLen, SliceAsPtrAndLen};
 
-pub use self::dev_fs::AsDevRandom;
+pub use self::dev_fs::{
+    is_hw_device, report_security_event, AsDevMemPressure, AsDevRandom, AsDevSecurityEvents,
+    DevRandom, SecurityEventKind,
+};
 pub use self::event_file::{AsEvent, EventCreationFlags, EventFile};
 pub use self::file::{File, FileRef};
 pub use self::file_ops::{
@@ -18,26 +21,41 @@ pub use self::file_ops::{
     IfConf, IoctlCmd, Stat, StatusFlags, StructuredIoctlArgType, StructuredIoctlNum,
 };
 pub use self::file_table::{FileDesc, FileTable};
+pub use self::fs_ops::sync_work;
 pub use self::fs_view::FsView;
+pub use self::host_file_window::{try_map_host_file_window, HostFileWindow};
 pub use self::inode_file::{AsINodeFile, INodeExt, INodeFile};
+pub use self::inotify_file::{AsInotify, InotifyFile, InotifyFlags};
+pub use self::integrity_only_files::verify_integrity_only_exec;
 pub use self::pipe::PipeType;
 pub use self::rootfs::ROOT_INODE;
-pub use self::stdio::{HostStdioFds, StdinFile, StdoutFile};
+pub use self::stdio::{AsStdin, AsStdout, HostStdioFds, StdinFile, StdoutFile};
 pub use self::syscalls::*;
 
+mod block;
+mod blockfs;
 mod dev_fs;
 mod event_file;
 mod file;
 mod file_ops;
 mod file_table;
 mod fs_ops;
+mod fs_type;
 mod fs_view;
+mod host_file_window;
 mod hostfs;
+mod httpfs;
 mod inode_file;
+mod inotify_file;
+mod integrity_only_files;
+mod machine_id;
+mod ninep;
 mod pipe;
+mod proc_fs;
 mod rootfs;
 mod sefs;
 mod stdio;
+mod sys_fs;
 mod syscalls;
 
 /// Split a `path` str to `(base_path, file_name)`
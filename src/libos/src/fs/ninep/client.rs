@@ -0,0 +1,491 @@
+use crate::prelude::SgxMutex as Mutex;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+use rcore_fs::vfs::{FileType, FsError, Metadata, Result, Timespec};
+use sgx_trts::libc::{self, c_int, c_void};
+use std::path::Path;
+
+use super::wire::*;
+
+/// The max size of a message proposed to the server
+const MAX_MSIZE: u32 = 128 * 1024;
+/// The min size of a message accepted from the server, which holds a page of data
+const MIN_MSIZE: u32 = 4096 + 64;
+const VERSION: &str = "9P2000.L";
+/// The tag of all requests, as there is at most one request in flight
+const TAG: u16 = 1;
+
+/// Request all the fields in Tgetattr that are in `struct stat`
+const GETATTR_BASIC: u64 = 0x7ff;
+
+const SETATTR_MODE: u32 = 0x1;
+const SETATTR_UID: u32 = 0x2;
+const SETATTR_GID: u32 = 0x4;
+const SETATTR_SIZE: u32 = 0x8;
+const SETATTR_ATIME: u32 = 0x10;
+const SETATTR_MTIME: u32 = 0x20;
+const SETATTR_ATIME_SET: u32 = 0x80;
+const SETATTR_MTIME_SET: u32 = 0x100;
+
+/// The fields to change by Tsetattr, where None means unchanged
+#[derive(Default)]
+pub struct SetAttr {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub size: Option<u64>,
+    pub atime: Option<Timespec>,
+    pub mtime: Option<Timespec>,
+}
+
+/// A client of 9P2000.L connected to a server by a unix socket of the host.
+///
+/// The requests are sent one by one on the connection, which keeps the client
+/// simple at the cost of concurrency.
+pub struct Client {
+    host_fd: c_int,
+    /// The max size of a message, as negotiated with the server
+    msize: u32,
+    /// Held during a request and its reply
+    lock: Mutex<()>,
+    next_fid: AtomicU32,
+}
+
+impl Client {
+    /// Connect to the server listening on the unix socket at `host_path`
+    pub fn connect(host_path: &Path) -> Result<Self> {
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let path = host_path.to_str().ok_or(FsError::InvalidParam)?.as_bytes();
+        if path.len() >= addr.sun_path.len() {
+            return Err(FsError::NameTooLong);
+        }
+        for (dst, &src) in addr.sun_path.iter_mut().zip(path.iter()) {
+            *dst = src as libc::c_char;
+        }
+
+        let host_fd = unsafe {
+            libc::ocall::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0)
+        };
+        if host_fd < 0 {
+            return Err(FsError::NoDevice);
+        }
+        let mut client = Self {
+            host_fd,
+            msize: MAX_MSIZE,
+            lock: Mutex::new(()),
+            next_fid: AtomicU32::new(0),
+        };
+        let ret = unsafe {
+            libc::ocall::connect(
+                host_fd,
+                &addr as *const _ as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            warn!("9P: failed to connect to the server at {:?}", host_path);
+            return Err(FsError::NoDevice);
+        }
+        client.msize = client.version()?;
+        Ok(client)
+    }
+
+    /// Allocate a fid that is not used by any file
+    pub fn alloc_fid(&self) -> u32 {
+        loop {
+            let fid = self.next_fid.fetch_add(1, Ordering::Relaxed);
+            if fid != NOFID {
+                return fid;
+            }
+        }
+    }
+
+    /// The max length of the data in a Tread or Twrite
+    pub fn max_io_len(&self) -> usize {
+        // Twrite has the longest header, i.e., fid[4] offset[8] count[4]
+        self.msize as usize - HEADER_LEN - 16
+    }
+
+    fn version(&self) -> Result<u32> {
+        let mut request = Encoder::new(TVERSION, NOTAG);
+        request.u32(MAX_MSIZE).str(VERSION);
+        let reply = self.rpc_with_tag(&mut request, NOTAG)?;
+        let mut reply = Decoder::new(&reply);
+        let msize = reply.u32()?;
+        let version = reply.str()?;
+        if version != VERSION {
+            warn!("9P: the version {:?} is not supported", version);
+            return Err(FsError::NotSupported);
+        }
+        if msize < MIN_MSIZE {
+            warn!("9P: the max size of messages is too small: {}", msize);
+            return Err(FsError::NotSupported);
+        }
+        Ok(msize.min(MAX_MSIZE))
+    }
+
+    pub fn attach(&self, fid: u32, aname: &str) -> Result<Qid> {
+        let mut request = Encoder::new(TATTACH, TAG);
+        request.u32(fid).u32(NOFID).str("").str(aname).u32(0);
+        let reply = self.rpc(&mut request)?;
+        Decoder::new(&reply).qid()
+    }
+
+    /// Walk from `fid` by the names to `newfid`, which fails with EntryNotFound
+    /// unless all the names are walked
+    pub fn walk(&self, fid: u32, newfid: u32, names: &[&str]) -> Result<Vec<Qid>> {
+        let mut request = Encoder::new(TWALK, TAG);
+        request.u32(fid).u32(newfid).u16(names.len() as u16);
+        for name in names {
+            request.str(name);
+        }
+        let reply = self.rpc(&mut request)?;
+        let mut reply = Decoder::new(&reply);
+        let nr_qids = reply.u16()? as usize;
+        if nr_qids > names.len() {
+            return Err(FsError::DeviceError);
+        }
+        // The newfid is not created if the walk stops early
+        if nr_qids < names.len() {
+            return Err(FsError::EntryNotFound);
+        }
+        (0..nr_qids).map(|_| reply.qid()).collect()
+    }
+
+    pub fn lopen(&self, fid: u32, flags: u32) -> Result<()> {
+        let mut request = Encoder::new(TLOPEN, TAG);
+        request.u32(fid).u32(flags);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    /// Create and open a regular file in the directory of `fid`, after which
+    /// `fid` refers to the new file
+    pub fn lcreate(&self, fid: u32, name: &str, flags: u32, mode: u32) -> Result<()> {
+        let mut request = Encoder::new(TLCREATE, TAG);
+        request.u32(fid).str(name).u32(flags).u32(mode).u32(0);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn symlink(&self, dir_fid: u32, name: &str, target: &str) -> Result<()> {
+        let mut request = Encoder::new(TSYMLINK, TAG);
+        request.u32(dir_fid).str(name).str(target).u32(0);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn readlink(&self, fid: u32) -> Result<String> {
+        let mut request = Encoder::new(TREADLINK, TAG);
+        request.u32(fid);
+        let reply = self.rpc(&mut request)?;
+        Decoder::new(&reply).str()
+    }
+
+    pub fn getattr(&self, fid: u32) -> Result<Metadata> {
+        let mut request = Encoder::new(TGETATTR, TAG);
+        request.u32(fid).u64(GETATTR_BASIC);
+        let reply = self.rpc(&mut request)?;
+        let mut reply = Decoder::new(&reply);
+        let _valid = reply.u64()?;
+        let qid = reply.qid()?;
+        let mode = reply.u32()?;
+        let uid = reply.u32()?;
+        let gid = reply.u32()?;
+        let nlinks = reply.u64()?;
+        let rdev = reply.u64()?;
+        let size = reply.u64()?;
+        let blk_size = reply.u64()?;
+        let blocks = reply.u64()?;
+        let mut timespec = || -> Result<Timespec> {
+            Ok(Timespec {
+                sec: reply.u64()? as i64,
+                nsec: reply.u64()? as i32,
+            })
+        };
+        let atime = timespec()?;
+        let mtime = timespec()?;
+        let ctime = timespec()?;
+        let type_ = match mode & libc::S_IFMT {
+            libc::S_IFCHR => FileType::CharDevice,
+            libc::S_IFBLK => FileType::BlockDevice,
+            libc::S_IFDIR => FileType::Dir,
+            libc::S_IFREG => FileType::File,
+            libc::S_IFLNK => FileType::SymLink,
+            libc::S_IFSOCK => FileType::Socket,
+            libc::S_IFIFO => FileType::NamedPipe,
+            _ => return Err(FsError::DeviceError),
+        };
+        Ok(Metadata {
+            dev: 0,
+            inode: qid.path as usize,
+            size: size as usize,
+            blk_size: blk_size as usize,
+            blocks: blocks as usize,
+            atime,
+            mtime,
+            ctime,
+            type_,
+            mode: mode as u16 & 0o7777,
+            nlinks: nlinks as usize,
+            uid: uid as usize,
+            gid: gid as usize,
+            rdev: rdev as usize,
+        })
+    }
+
+    pub fn setattr(&self, fid: u32, attr: &SetAttr) -> Result<()> {
+        let mut valid = 0;
+        let mut set = |bit: u32, is_set: bool| {
+            if is_set {
+                valid |= bit;
+            }
+        };
+        set(SETATTR_MODE, attr.mode.is_some());
+        set(SETATTR_UID, attr.uid.is_some());
+        set(SETATTR_GID, attr.gid.is_some());
+        set(SETATTR_SIZE, attr.size.is_some());
+        set(SETATTR_ATIME | SETATTR_ATIME_SET, attr.atime.is_some());
+        set(SETATTR_MTIME | SETATTR_MTIME_SET, attr.mtime.is_some());
+        let atime = attr.atime.unwrap_or(Timespec { sec: 0, nsec: 0 });
+        let mtime = attr.mtime.unwrap_or(Timespec { sec: 0, nsec: 0 });
+
+        let mut request = Encoder::new(TSETATTR, TAG);
+        request
+            .u32(fid)
+            .u32(valid)
+            .u32(attr.mode.unwrap_or(0))
+            .u32(attr.uid.unwrap_or(0))
+            .u32(attr.gid.unwrap_or(0))
+            .u64(attr.size.unwrap_or(0))
+            .u64(atime.sec as u64)
+            .u64(atime.nsec as u64)
+            .u64(mtime.sec as u64)
+            .u64(mtime.nsec as u64);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    /// Read the entries of the opened directory from `offset`, returning the
+    /// names and the offsets of the next entries. The end is reached if empty.
+    pub fn readdir(&self, fid: u32, offset: u64) -> Result<Vec<(String, u64)>> {
+        let mut request = Encoder::new(TREADDIR, TAG);
+        request.u32(fid).u64(offset).u32(self.max_io_len() as u32);
+        let reply = self.rpc(&mut request)?;
+        let mut reply = Decoder::new(&reply);
+        let len = reply.u32()? as usize;
+        let mut data = Decoder::new(reply.bytes(len)?);
+        let mut entries = Vec::new();
+        while !data.is_empty() {
+            let _qid = data.qid()?;
+            let next_offset = data.u64()?;
+            let _type = data.u8()?;
+            let name = data.str()?;
+            if name.is_empty() || name.contains('/') {
+                warn!("9P: invalid name of the directory entry: {:?}", name);
+                return Err(FsError::DeviceError);
+            }
+            entries.push((name, next_offset));
+        }
+        Ok(entries)
+    }
+
+    pub fn fsync(&self, fid: u32, datasync: bool) -> Result<()> {
+        let mut request = Encoder::new(TFSYNC, TAG);
+        request.u32(fid).u32(datasync as u32);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn link(&self, dir_fid: u32, fid: u32, name: &str) -> Result<()> {
+        let mut request = Encoder::new(TLINK, TAG);
+        request.u32(dir_fid).u32(fid).str(name);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn mkdir(&self, dir_fid: u32, name: &str, mode: u32) -> Result<()> {
+        let mut request = Encoder::new(TMKDIR, TAG);
+        request.u32(dir_fid).str(name).u32(mode).u32(0);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn renameat(
+        &self,
+        old_dir_fid: u32,
+        old_name: &str,
+        new_dir_fid: u32,
+        new_name: &str,
+    ) -> Result<()> {
+        let mut request = Encoder::new(TRENAMEAT, TAG);
+        request
+            .u32(old_dir_fid)
+            .str(old_name)
+            .u32(new_dir_fid)
+            .str(new_name);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    pub fn unlinkat(&self, dir_fid: u32, name: &str, flags: u32) -> Result<()> {
+        let mut request = Encoder::new(TUNLINKAT, TAG);
+        request.u32(dir_fid).str(name).u32(flags);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    /// Read at most `max_io_len` bytes
+    pub fn read(&self, fid: u32, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(self.max_io_len());
+        let mut request = Encoder::new(TREAD, TAG);
+        request.u32(fid).u64(offset).u32(len as u32);
+        let reply = self.rpc(&mut request)?;
+        let mut reply = Decoder::new(&reply);
+        let read_len = reply.u32()? as usize;
+        if read_len > len {
+            return Err(FsError::DeviceError);
+        }
+        buf[..read_len].copy_from_slice(reply.bytes(read_len)?);
+        Ok(read_len)
+    }
+
+    /// Write at most `max_io_len` bytes
+    pub fn write(&self, fid: u32, offset: u64, buf: &[u8]) -> Result<usize> {
+        let len = buf.len().min(self.max_io_len());
+        let mut request = Encoder::new(TWRITE, TAG);
+        request
+            .u32(fid)
+            .u64(offset)
+            .u32(len as u32)
+            .bytes(&buf[..len]);
+        let reply = self.rpc(&mut request)?;
+        let written_len = Decoder::new(&reply).u32()? as usize;
+        if written_len > len {
+            return Err(FsError::DeviceError);
+        }
+        Ok(written_len)
+    }
+
+    pub fn clunk(&self, fid: u32) -> Result<()> {
+        let mut request = Encoder::new(TCLUNK, TAG);
+        request.u32(fid);
+        self.rpc(&mut request)?;
+        Ok(())
+    }
+
+    fn rpc(&self, request: &mut Encoder) -> Result<Vec<u8>> {
+        self.rpc_with_tag(request, TAG)
+    }
+
+    /// Send the request and receive the body of its reply. An Rlerror is
+    /// converted to the error.
+    fn rpc_with_tag(&self, request: &mut Encoder, tag: u16) -> Result<Vec<u8>> {
+        let request = request.finish();
+        let reply_type = request[4] + 1;
+
+        let _lock = self.lock.lock().unwrap();
+        self.send(&request)?;
+        let mut header = [0u8; HEADER_LEN];
+        self.recv(&mut header)?;
+        let mut header = Decoder::new(&header);
+        let size = header.u32()? as usize;
+        let type_ = header.u8()?;
+        let reply_tag = header.u16()?;
+        if size < HEADER_LEN || size > self.msize as usize || reply_tag != tag {
+            warn!("9P: invalid header of the reply");
+            return Err(FsError::DeviceError);
+        }
+        let mut body = vec![0u8; size - HEADER_LEN];
+        self.recv(&mut body)?;
+
+        if type_ == RLERROR {
+            let errno = Decoder::new(&body).u32()?;
+            return Err(errno_to_fs_error(errno as c_int));
+        }
+        if type_ != reply_type {
+            warn!("9P: unexpected type of the reply: {}", type_);
+            return Err(FsError::DeviceError);
+        }
+        Ok(body)
+    }
+
+    fn send(&self, data: &[u8]) -> Result<()> {
+        let mut sent = 0;
+        while sent < data.len() {
+            let ret = unsafe {
+                libc::ocall::sendto(
+                    self.host_fd,
+                    data[sent..].as_ptr() as *const c_void,
+                    data.len() - sent,
+                    libc::MSG_NOSIGNAL,
+                    std::ptr::null(),
+                    0,
+                )
+            };
+            if ret < 0 && unsafe { libc::errno() } == libc::EINTR {
+                continue;
+            }
+            if ret <= 0 || ret as usize > data.len() - sent {
+                return Err(FsError::DeviceError);
+            }
+            sent += ret as usize;
+        }
+        Ok(())
+    }
+
+    fn recv(&self, buf: &mut [u8]) -> Result<()> {
+        let mut received = 0;
+        while received < buf.len() {
+            let ret = unsafe {
+                libc::ocall::recvfrom(
+                    self.host_fd,
+                    buf[received..].as_mut_ptr() as *mut c_void,
+                    buf.len() - received,
+                    0,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if ret < 0 && unsafe { libc::errno() } == libc::EINTR {
+                continue;
+            }
+            // The connection is closed by the server if zero
+            if ret <= 0 || ret as usize > buf.len() - received {
+                return Err(FsError::DeviceError);
+            }
+            received += ret as usize;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        unsafe { libc::ocall::close(self.host_fd) };
+    }
+}
+
+/// Convert the errno of Linux in an Rlerror
+fn errno_to_fs_error(errno: c_int) -> FsError {
+    match errno {
+        libc::ENOENT => FsError::EntryNotFound,
+        libc::EEXIST => FsError::EntryExist,
+        libc::ENOTDIR => FsError::NotDir,
+        libc::EISDIR => FsError::IsDir,
+        libc::ENOTEMPTY => FsError::DirNotEmpty,
+        libc::EXDEV => FsError::NotSameFs,
+        libc::EINVAL => FsError::InvalidParam,
+        libc::ENOSPC => FsError::NoDeviceSpace,
+        libc::ELOOP => FsError::SymLoop,
+        libc::EAGAIN => FsError::Again,
+        libc::EBUSY => FsError::Busy,
+        libc::EROFS => FsError::WrProtected,
+        libc::EPERM | libc::EACCES | libc::EBADF => FsError::PermError,
+        libc::ENAMETOOLONG => FsError::NameTooLong,
+        libc::ENOSYS | libc::EOPNOTSUPP => FsError::NotSupported,
+        _ => FsError::DeviceError,
+    }
+}
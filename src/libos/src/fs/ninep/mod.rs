@@ -0,0 +1,419 @@
+//! A file system shared by a 9P2000.L server on the host.
+//!
+//! Unlike HostFS, which does a file operation by an OCall of the same kind, the
+//! file operations are sent as 9P messages over one unix socket of the host, so
+//! the OCalls used are only those of the socket. The server, e.g., diod or
+//! the 9P export of a VMM, keeps the semantics of POSIX on the host, e.g., the
+//! permissions, the hard links and the symlinks.
+//!
+//! The server is as untrusted as the host, so the replies are checked for the
+//! lengths and the names, but the data are not protected.
+use crate::prelude::SgxMutex as Mutex;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use rcore_fs::vfs::*;
+use std::path::Path;
+
+use self::client::{Client, SetAttr};
+use self::wire::{Qid, QTDIR, QTSYMLINK};
+
+mod client;
+mod wire;
+
+const O_RDONLY: u32 = 0;
+const O_RDWR: u32 = 2;
+const O_CREAT: u32 = 0o100;
+const O_EXCL: u32 = 0o200;
+const O_DIRECTORY: u32 = 0o200000;
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// The max number of the entries in a directory
+const MAX_DIR_ENTRIES: usize = 1 << 20;
+
+/// File system shared by a 9P server at host
+pub struct NinePFS {
+    client: Client,
+    /// The fid of the root directory, which is kept until unmounted
+    root_fid: u32,
+    self_ref: Weak<NinePFS>,
+}
+
+/// INode for `NinePFS`
+pub struct NNode {
+    /// The fid walked to the file, which is not opened
+    fid: u32,
+    type_: FileType,
+    fs: Arc<NinePFS>,
+    /// The fid opened for I/O on first use
+    io_fid: Mutex<Option<u32>>,
+    /// The names of the entries of the directory, got on the first `get_entry`
+    dir_entries: Mutex<Option<Vec<String>>>,
+    /// The name of the symlink to create in the directory of `fid`, which is
+    /// created when its target is written
+    pending_symlink: Mutex<Option<String>>,
+}
+
+impl FileSystem for NinePFS {
+    fn sync(&self) -> Result<()> {
+        // The writes are sent to the server once done, and the files are synced
+        // by fsync on the server
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        Arc::new(NNode::new(
+            self.root_fid,
+            FileType::Dir,
+            self.self_ref.upgrade().unwrap(),
+        ))
+    }
+
+    fn info(&self) -> FsInfo {
+        unimplemented!()
+    }
+}
+
+impl NinePFS {
+    /// Connect to the 9P server on the unix socket at host `path` and attach to
+    /// the tree named `aname`
+    pub fn new(path: impl AsRef<Path>, aname: &str) -> Result<Arc<NinePFS>> {
+        let client = Client::connect(path.as_ref())?;
+        let root_fid = client.alloc_fid();
+        let qid = client.attach(root_fid, aname)?;
+        if qid.type_ & QTDIR == 0 {
+            return Err(FsError::NotDir);
+        }
+        Ok(NinePFS {
+            client,
+            root_fid,
+            self_ref: Weak::default(),
+        }
+        .wrap())
+    }
+
+    /// Wrap pure `NinePFS` with Arc
+    /// Used in constructors
+    fn wrap(self) -> Arc<Self> {
+        let fs = Arc::new(self);
+        let weak = Arc::downgrade(&fs);
+        let ptr = Arc::into_raw(fs) as *mut Self;
+        unsafe {
+            (*ptr).self_ref = weak;
+        }
+        unsafe { Arc::from_raw(ptr) }
+    }
+}
+
+impl INode for NNode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        match self.type_ {
+            FileType::Dir => return Err(FsError::IsDir),
+            FileType::SymLink => {
+                let target = self.fs.client.readlink(self.fid)?;
+                let target = target.as_bytes().get(offset..).unwrap_or(&[]);
+                let len = buf.len().min(target.len());
+                buf[..len].copy_from_slice(&target[..len]);
+                return Ok(len);
+            }
+            _ => {}
+        }
+        let io_fid = self.io_fid()?;
+        let mut read_len = 0;
+        while read_len < buf.len() {
+            let len =
+                self.fs
+                    .client
+                    .read(io_fid, (offset + read_len) as u64, &mut buf[read_len..])?;
+            if len == 0 {
+                break;
+            }
+            read_len += len;
+        }
+        Ok(read_len)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if let Some(name) = self.pending_symlink.lock().unwrap().take() {
+            let target = core::str::from_utf8(buf).map_err(|_| FsError::InvalidParam)?;
+            if offset != 0 {
+                return Err(FsError::InvalidParam);
+            }
+            self.fs.client.symlink(self.fid, &name, target)?;
+            // Walk the fid from the directory to the symlink in place
+            self.fs.client.walk(self.fid, self.fid, &[&name])?;
+            return Ok(buf.len());
+        }
+        if self.type_ == FileType::Dir {
+            return Err(FsError::IsDir);
+        }
+        let io_fid = self.io_fid()?;
+        let mut written_len = 0;
+        while written_len < buf.len() {
+            let len =
+                self.fs
+                    .client
+                    .write(io_fid, (offset + written_len) as u64, &buf[written_len..])?;
+            if len == 0 {
+                return Err(FsError::DeviceError);
+            }
+            written_len += len;
+        }
+        Ok(written_len)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        if self.pending_symlink.lock().unwrap().is_some() {
+            return Err(FsError::EntryNotFound);
+        }
+        self.fs.client.getattr(self.fid)
+    }
+
+    fn set_metadata(&self, metadata: &Metadata) -> Result<()> {
+        // Change only what differs, e.g., not to chown the file on chmod, which
+        // may be denied by the host
+        let old = self.metadata()?;
+        let attr = SetAttr {
+            mode: Some(metadata.mode as u32).filter(|_| metadata.mode != old.mode),
+            uid: Some(metadata.uid as u32).filter(|_| metadata.uid != old.uid),
+            gid: Some(metadata.gid as u32).filter(|_| metadata.gid != old.gid),
+            size: None,
+            atime: Some(metadata.atime).filter(|_| metadata.atime != old.atime),
+            mtime: Some(metadata.mtime).filter(|_| metadata.mtime != old.mtime),
+        };
+        self.fs.client.setattr(self.fid, &attr)
+    }
+
+    fn sync_all(&self) -> Result<()> {
+        self.sync(false)
+    }
+
+    fn sync_data(&self) -> Result<()> {
+        self.sync(true)
+    }
+
+    fn resize(&self, len: usize) -> Result<()> {
+        // The length of a symlink is that of the target to write
+        if self.pending_symlink.lock().unwrap().is_some() {
+            return Ok(());
+        }
+        let attr = SetAttr {
+            size: Some(len as u64),
+            ..Default::default()
+        };
+        self.fs.client.setattr(self.fid, &attr)
+    }
+
+    fn create(&self, name: &str, type_: FileType, mode: u32) -> Result<Arc<dyn INode>> {
+        let client = &self.fs.client;
+        match type_ {
+            FileType::File => {
+                let (io_fid, _) = self.walk(&[])?;
+                let node = client
+                    .lcreate(io_fid, name, O_RDWR | O_CREAT | O_EXCL, mode)
+                    .and_then(|_| self.find(name));
+                let node = match node {
+                    Ok(node) => node,
+                    Err(e) => {
+                        client.clunk(io_fid).ok();
+                        return Err(e);
+                    }
+                };
+                let nnode = node.downcast_ref::<NNode>().unwrap();
+                *nnode.io_fid.lock().unwrap() = Some(io_fid);
+                Ok(node)
+            }
+            FileType::Dir => {
+                client.mkdir(self.fid, name, mode)?;
+                self.find(name)
+            }
+            FileType::SymLink => {
+                if self.find(name).is_ok() {
+                    return Err(FsError::EntryExist);
+                }
+                let (fid, _) = self.walk(&[])?;
+                let node = NNode::new(fid, FileType::SymLink, self.fs.clone());
+                *node.pending_symlink.lock().unwrap() = Some(name.to_string());
+                Ok(Arc::new(node))
+            }
+            _ => {
+                warn!("only support creating regular file, directory or symlink in 9P");
+                Err(FsError::PermError)
+            }
+        }
+    }
+
+    fn link(&self, name: &str, other: &Arc<dyn INode>) -> Result<()> {
+        let other = other.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        self.fs.client.link(self.fid, other.fid, name)
+    }
+
+    fn unlink(&self, name: &str) -> Result<()> {
+        match self.fs.client.unlinkat(self.fid, name, 0) {
+            Err(FsError::IsDir) => self.fs.client.unlinkat(self.fid, name, AT_REMOVEDIR),
+            ret => ret,
+        }
+    }
+
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        let target = target.downcast_ref::<Self>().ok_or(FsError::NotSameFs)?;
+        self.fs
+            .client
+            .renameat(self.fid, old_name, target.fid, new_name)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        // A walk to "." is not defined by 9P, but a walk of no names is
+        let (fid, type_) = if name == "." {
+            (self.walk(&[])?.0, self.type_)
+        } else {
+            let (fid, qids) = self.walk(&[name])?;
+            (fid, qid_to_file_type(&qids[0]))
+        };
+        Ok(Arc::new(NNode::new(fid, type_, self.fs.clone())))
+    }
+
+    fn get_entry(&self, id: usize) -> Result<String> {
+        let mut dir_entries = self.dir_entries.lock().unwrap();
+        // Read the whole directory when reading from its beginning
+        if id == 0 || dir_entries.is_none() {
+            *dir_entries = Some(self.read_dir_entries()?);
+        }
+        dir_entries
+            .as_ref()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or(FsError::EntryNotFound)
+    }
+
+    fn io_control(&self, _cmd: u32, _data: usize) -> Result<()> {
+        Err(FsError::NotSupported)
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.clone()
+    }
+
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl NNode {
+    fn new(fid: u32, type_: FileType, fs: Arc<NinePFS>) -> Self {
+        // The root fid is kept by the file system, so walk a new one for the node
+        let fid = if fid == fs.root_fid {
+            let new_fid = fs.client.alloc_fid();
+            match fs.client.walk(fid, new_fid, &[]) {
+                Ok(_) => new_fid,
+                Err(e) => {
+                    warn!("9P: failed to walk from the root: {:?}", e);
+                    fid
+                }
+            }
+        } else {
+            fid
+        };
+        Self {
+            fid,
+            type_,
+            fs,
+            io_fid: Mutex::new(None),
+            dir_entries: Mutex::new(None),
+            pending_symlink: Mutex::new(None),
+        }
+    }
+
+    /// Walk from the file by the names to a new fid
+    fn walk(&self, names: &[&str]) -> Result<(u32, Vec<Qid>)> {
+        let new_fid = self.fs.client.alloc_fid();
+        let qids = self.fs.client.walk(self.fid, new_fid, names)?;
+        Ok((new_fid, qids))
+    }
+
+    /// Get the fid opened for I/O, which is opened for reading and writing if
+    /// allowed, or for reading only
+    fn io_fid(&self) -> Result<u32> {
+        let mut io_fid = self.io_fid.lock().unwrap();
+        if let Some(fid) = *io_fid {
+            return Ok(fid);
+        }
+        let client = &self.fs.client;
+        let (fid, _) = self.walk(&[])?;
+        let ret = match client.lopen(fid, O_RDWR) {
+            Err(FsError::PermError) | Err(FsError::WrProtected) => client.lopen(fid, O_RDONLY),
+            ret => ret,
+        };
+        if let Err(e) = ret {
+            client.clunk(fid).ok();
+            return Err(e);
+        }
+        *io_fid = Some(fid);
+        Ok(fid)
+    }
+
+    fn sync(&self, datasync: bool) -> Result<()> {
+        // Nothing is written if the file is not opened
+        match *self.io_fid.lock().unwrap() {
+            Some(fid) => self.fs.client.fsync(fid, datasync),
+            None => Ok(()),
+        }
+    }
+
+    fn read_dir_entries(&self) -> Result<Vec<String>> {
+        let client = &self.fs.client;
+        let (fid, _) = self.walk(&[])?;
+        let read_entries = || {
+            client.lopen(fid, O_RDONLY | O_DIRECTORY)?;
+            let mut entries = Vec::new();
+            let mut offset = 0;
+            loop {
+                let batch = client.readdir(fid, offset)?;
+                match batch.last() {
+                    Some((_, next_offset)) => offset = *next_offset,
+                    None => return Ok(entries),
+                }
+                entries.extend(batch.into_iter().map(|(name, _)| name));
+                if entries.len() > MAX_DIR_ENTRIES {
+                    warn!("9P: the directory has too many entries");
+                    return Err(FsError::NoDeviceSpace);
+                }
+            }
+        };
+        let ret = read_entries();
+        client.clunk(fid).ok();
+        ret
+    }
+}
+
+impl Drop for NNode {
+    fn drop(&mut self) {
+        let client = &self.fs.client;
+        if let Some(io_fid) = self.io_fid.lock().unwrap().take() {
+            client.clunk(io_fid).ok();
+        }
+        if self.fid != self.fs.root_fid {
+            client.clunk(self.fid).ok();
+        }
+    }
+}
+
+fn qid_to_file_type(qid: &Qid) -> FileType {
+    if qid.type_ & QTDIR != 0 {
+        FileType::Dir
+    } else if qid.type_ & QTSYMLINK != 0 {
+        FileType::SymLink
+    } else {
+        FileType::File
+    }
+}
@@ -0,0 +1,152 @@
+//! The encoding of the messages of 9P2000.L.
+//!
+//! A message is `size[4] type[1] tag[2]` followed by the fields of its type, all
+//! in little endian. A string is `len[2]` followed by the bytes in UTF-8.
+use alloc::string::String;
+use alloc::vec::Vec;
+use rcore_fs::vfs::{FsError, Result};
+
+pub const HEADER_LEN: usize = 7;
+pub const NOTAG: u16 = !0;
+pub const NOFID: u32 = !0;
+
+pub const RLERROR: u8 = 7;
+pub const TLOPEN: u8 = 12;
+pub const TLCREATE: u8 = 14;
+pub const TSYMLINK: u8 = 16;
+pub const TREADLINK: u8 = 22;
+pub const TGETATTR: u8 = 24;
+pub const TSETATTR: u8 = 26;
+pub const TREADDIR: u8 = 40;
+pub const TFSYNC: u8 = 50;
+pub const TLINK: u8 = 70;
+pub const TMKDIR: u8 = 72;
+pub const TRENAMEAT: u8 = 74;
+pub const TUNLINKAT: u8 = 76;
+pub const TVERSION: u8 = 100;
+pub const TATTACH: u8 = 104;
+pub const TWALK: u8 = 110;
+pub const TREAD: u8 = 116;
+pub const TWRITE: u8 = 118;
+pub const TCLUNK: u8 = 120;
+
+pub const QTDIR: u8 = 0x80;
+pub const QTSYMLINK: u8 = 0x02;
+
+/// The unique id of a file on the server, without the version
+#[derive(Debug, Clone, Copy)]
+pub struct Qid {
+    pub type_: u8,
+    pub path: u64,
+}
+
+/// The encoder of a request
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(type_: u8, tag: u16) -> Self {
+        let mut encoder = Self { buf: Vec::new() };
+        encoder.u32(0).u8(type_).u16(tag);
+        encoder
+    }
+
+    pub fn u8(&mut self, val: u8) -> &mut Self {
+        self.buf.push(val);
+        self
+    }
+
+    pub fn u16(&mut self, val: u16) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, val: u32) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, val: u64) -> &mut Self {
+        self.buf.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn str(&mut self, val: &str) -> &mut Self {
+        self.u16(val.len() as u16);
+        self.buf.extend_from_slice(val.as_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, val: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(val);
+        self
+    }
+
+    /// Finish the request with its size filled
+    pub fn finish(&mut self) -> Vec<u8> {
+        let size = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&size.to_le_bytes());
+        core::mem::replace(&mut self.buf, Vec::new())
+    }
+}
+
+/// The decoder of the body of a reply, which is untrusted. Any field beyond the
+/// end of the reply is an error of the device.
+pub struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.buf.len() {
+            return Err(FsError::DeviceError);
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn str(&mut self) -> Result<String> {
+        let len = self.u16()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| FsError::DeviceError)
+    }
+
+    pub fn qid(&mut self) -> Result<Qid> {
+        let type_ = self.u8()?;
+        let _version = self.u32()?;
+        let path = self.u64()?;
+        Ok(Qid { type_, path })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
@@ -1,5 +1,6 @@
 use super::*;
 use net::{IoEvent, PollEventFlags};
+use signal::send_sigpipe_on_epipe;
 use util::ring_buf::*;
 
 // TODO: Add F_SETPIPE_SZ in fcntl to dynamically change the size of pipe
@@ -23,7 +24,7 @@ pub fn pipe(flags: StatusFlags) -> Result<(PipeReader, PipeWriter)> {
             status_flags: RwLock::new(valid_flags),
         },
         PipeWriter {
-            inner: SgxMutex::new(buffer_writer),
+            inner: buffer_writer,
             status_flags: RwLock::new(valid_flags),
         },
     ))
@@ -92,6 +93,11 @@ impl File for PipeReader {
         ringbuf_reader.dequeue_event()
     }
 
+    fn poll_seq(&self) -> Result<usize> {
+        let ringbuf_reader = self.inner.lock().unwrap();
+        Ok(ringbuf_reader.poll_seq())
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -101,19 +107,18 @@ unsafe impl Send for PipeReader {}
 unsafe impl Sync for PipeReader {}
 
 pub struct PipeWriter {
-    inner: SgxMutex<RingBufWriter>,
+    // Shared by the writers without any lock
+    inner: RingBufWriter,
     status_flags: RwLock<StatusFlags>,
 }
 
 impl File for PipeWriter {
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        let mut ringbuf = self.inner.lock().unwrap();
-        ringbuf.write_to_buffer(buf)
+        send_sigpipe_on_epipe(self.inner.write_to_buffer(buf))
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
-        let mut ringbuf = self.inner.lock().unwrap();
-        ringbuf.write_to_vector(bufs)
+        send_sigpipe_on_epipe(self.inner.write_to_vector(bufs))
     }
 
     fn seek(&self, pos: SeekFrom) -> Result<off_t> {
@@ -136,20 +141,19 @@ impl File for PipeWriter {
             & (StatusFlags::O_NONBLOCK | StatusFlags::O_ASYNC | StatusFlags::O_DIRECT);
 
         if new_status_flags.contains(StatusFlags::O_NONBLOCK) {
-            self.inner.lock().unwrap().set_non_blocking();
+            self.inner.set_non_blocking();
         } else {
-            self.inner.lock().unwrap().set_blocking();
+            self.inner.set_blocking();
         }
         Ok(())
     }
 
     fn poll(&self) -> Result<PollEventFlags> {
-        let ringbuf_writer = self.inner.lock().unwrap();
         let mut events = PollEventFlags::empty();
-        if ringbuf_writer.can_write() {
+        if self.inner.can_write() {
             events |= PollEventFlags::POLLOUT | PollEventFlags::POLLWRNORM;
         }
-        if ringbuf_writer.is_peer_closed() {
+        if self.inner.is_peer_closed() {
             events |= PollEventFlags::POLLERR;
         }
 
@@ -157,13 +161,15 @@ impl File for PipeWriter {
     }
 
     fn enqueue_event(&self, event: IoEvent) -> Result<()> {
-        let ringbuf_writer = self.inner.lock().unwrap();
-        ringbuf_writer.enqueue_event(event)
+        self.inner.enqueue_event(event)
     }
 
     fn dequeue_event(&self) -> Result<()> {
-        let ringbuf_writer = self.inner.lock().unwrap();
-        ringbuf_writer.dequeue_event()
+        self.inner.dequeue_event()
+    }
+
+    fn poll_seq(&self) -> Result<usize> {
+        Ok(self.inner.poll_seq())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -0,0 +1,147 @@
+//! A minimal procfs.
+//!
+//! The files under /proc are not backed by any file system. Instead, the
+//! content of a file is generated when the file is opened, and so are the entries
+//! of a directory, e.g., one per process under /proc.
+//!
+//! The inode number of a file is given by its path, where /proc/self is resolved,
+//! so the numbers are the same whenever the same files are listed.
+use super::*;
+use crate::process::table;
+
+pub use self::proc_dir::{ino_of, DirEntry, ProcDir};
+pub use self::proc_file::ProcFile;
+
+mod net;
+mod occlum;
+mod pid;
+mod proc_dir;
+mod proc_file;
+
+/// Open a file under /proc.
+///
+/// Return None if the path is not handled by procfs.
+pub fn open(path: &str) -> Option<Result<ProcFile>> {
+    let path = path.strip_prefix("/proc/")?.trim_end_matches('/');
+    let (first, rest) = split_first(path);
+
+    let content = match first {
+        "self" => pid::read_file(current!().process(), rest),
+        "stat" if rest.is_empty() => Some(Ok(read_stat())),
+        "net" => net::read_file(rest),
+        "occlum" => occlum::read_file(rest),
+        _ => {
+            let pid = first.parse::<pid_t>().ok()?;
+            match table::get_process(pid) {
+                Ok(process) => pid::read_file(&process, rest),
+                Err(_) => Some(Err(errno!(ENOENT, "no such process"))),
+            }
+        }
+    }?;
+    let canonical_path = match first {
+        "self" => format!("/proc/{}/{}", current!().process().pid(), rest),
+        _ => format!("/proc/{}", path),
+    };
+    Some(content.map(|content| ProcFile::new(&canonical_path, content)))
+}
+
+/// Open a directory under /proc.
+///
+/// Return None if the path is not a directory handled by procfs.
+pub fn open_dir(path: &str) -> Option<Result<ProcDir>> {
+    let path = match path.trim_end_matches('/') {
+        "/proc" => "",
+        path => path.strip_prefix("/proc/")?,
+    };
+    let (first, rest) = split_first(path);
+
+    let (dir_path, entries) = match first {
+        "" => ("/proc".to_string(), read_root_dir()),
+        "net" if rest.is_empty() => {
+            let dir_path = "/proc/net".to_string();
+            let entries = net::FILE_NAMES
+                .iter()
+                .map(|name| DirEntry::new(&dir_path, *name, FileType::File))
+                .collect();
+            (dir_path, entries)
+        }
+        "occlum" if rest.is_empty() => {
+            let dir_path = "/proc/occlum".to_string();
+            let entries = occlum::file_names()
+                .into_iter()
+                .map(|name| DirEntry::new(&dir_path, name, FileType::File))
+                .collect();
+            (dir_path, entries)
+        }
+        _ => {
+            let process = match first {
+                "self" => current!().process().clone(),
+                _ => match table::get_process(first.parse::<pid_t>().ok()?) {
+                    Ok(process) => process,
+                    Err(_) => return Some(Err(errno!(ENOENT, "no such process"))),
+                },
+            };
+            let dir_path = match rest {
+                "" => format!("/proc/{}", process.pid()),
+                _ => format!("/proc/{}/{}", process.pid(), rest),
+            };
+            let entries = match pid::read_dir(&process, rest, &dir_path)? {
+                Ok(entries) => entries,
+                Err(e) => return Some(Err(e)),
+            };
+            (dir_path, entries)
+        }
+    };
+    Some(Ok(ProcDir::new(&dir_path, entries)))
+}
+
+/// Read the symbolic link under /proc.
+///
+/// Return None if the path is not a link handled by procfs.
+pub fn read_link(path: &str) -> Option<Result<String>> {
+    let path = path.strip_prefix("/proc/")?.trim_end_matches('/');
+    let (first, rest) = split_first(path);
+    let process = match first {
+        "self" if rest.is_empty() => return Some(Ok(current!().process().pid().to_string())),
+        "self" => current!().process().clone(),
+        _ => match table::get_process(first.parse::<pid_t>().ok()?) {
+            Ok(process) => process,
+            Err(_) => return Some(Err(errno!(ENOENT, "no such process"))),
+        },
+    };
+    pid::read_link(&process, rest)
+}
+
+/// Split the path into the first component and the rest
+fn split_first(path: &str) -> (&str, &str) {
+    match path.find('/') {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => (path, ""),
+    }
+}
+
+// The processes are listed in the order of the pids, like Linux
+fn read_root_dir() -> Vec<DirEntry> {
+    let mut entries = vec![
+        DirEntry::new("/proc", "stat", FileType::File),
+        DirEntry::new("/proc", "net", FileType::Dir),
+        DirEntry::new("/proc", "occlum", FileType::Dir),
+        DirEntry::new("/proc", "self", FileType::SymLink),
+    ];
+    let mut pids: Vec<pid_t> = table::get_all_processes()
+        .iter()
+        .map(|process| process.pid())
+        .collect();
+    pids.sort_unstable();
+    entries.extend(
+        pids.into_iter()
+            .map(|pid| DirEntry::new("/proc", pid.to_string(), FileType::Dir)),
+    );
+    entries
+}
+
+// Only the boot time, in seconds since the Epoch, is supported
+fn read_stat() -> Vec<u8> {
+    let boot_time = crate::time::up_time::boot_time();
+    format!("btime {}\n", boot_time.as_secs()).into_bytes()
+}
@@ -0,0 +1,216 @@
+//! The files under /proc/net.
+use super::*;
+use crate::net::{
+    get_net_dev_stats, occlum_sock_stats, AsSocket, AsTcpStackSocket, AsUnixSocket, NetDevStats,
+    TcpSocketEntry, UnixSocketEntry,
+};
+use crate::process::table;
+use std::fmt::Write;
+
+/// The files under /proc/net
+pub const FILE_NAMES: [&str; 4] = ["dev", "occlum_sockets", "tcp", "unix"];
+
+/// Generate the content of the file at `path`, which is relative to /proc/net
+pub fn read_file(path: &str) -> Option<Result<Vec<u8>>> {
+    match path {
+        "dev" => Some(read_dev()),
+        "occlum_sockets" => Some(Ok(read_occlum_sockets())),
+        "tcp" => Some(Ok(read_tcp())),
+        "unix" => Some(Ok(read_unix())),
+        _ => None,
+    }
+}
+
+/// Generate /proc/net/dev in the format of Linux, where the statistics that
+/// are not tracked are shown as zeros.
+fn read_dev() -> Result<Vec<u8>> {
+    let mut content = String::from(
+        "Inter-|   Receive                                                |  Transmit\n \
+         face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n",
+    );
+    for stats in get_net_dev_stats()? {
+        let NetDevStats {
+            name,
+            rx_bytes,
+            rx_packets,
+            rx_errors,
+            rx_dropped,
+            tx_bytes,
+            tx_packets,
+            tx_errors,
+            tx_dropped,
+        } = stats;
+        writeln!(
+            content,
+            "{:>6}: {:>7} {:>7} {:>4} {:>4} {:>4} {:>5} {:>10} {:>9} {:>8} {:>7} {:>4} {:>4} {:>4} {:>5} {:>7} {:>10}",
+            name, rx_bytes, rx_packets, rx_errors, rx_dropped, 0, 0, 0, 0,
+            tx_bytes, tx_packets, tx_errors, tx_dropped, 0, 0, 0, 0
+        )
+        .unwrap();
+    }
+    Ok(content.into_bytes())
+}
+
+/// Generate /proc/net/occlum_sockets, which lists the traffic statistics of the
+/// sockets opened by all processes. A socket shared by multiple file descriptors
+/// is listed once for each of them.
+fn read_occlum_sockets() -> Vec<u8> {
+    let mut content = String::from(
+        "pid fd domain type protocol rx_bytes rx_packets tx_bytes tx_packets lifetime_ns\n",
+    );
+    for process in table::get_all_processes() {
+        // The threads of a process share the same file table
+        let thread = match process.threads().into_iter().next() {
+            Some(thread) => thread,
+            None => continue,
+        };
+        let files = thread.files().lock().unwrap();
+        for (fd, file_ref) in files.iter() {
+            let (domain, socket_type, protocol, stats) = if let Ok(socket) = file_ref.as_socket() {
+                (
+                    socket.domain(),
+                    socket.socket_type(),
+                    socket.protocol(),
+                    socket.stats().snapshot(),
+                )
+            } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
+                (
+                    libc::AF_UNIX,
+                    unix_socket.socket_type(),
+                    0,
+                    unix_socket.stats().snapshot(),
+                )
+            } else {
+                continue;
+            };
+            let occlum_sock_stats {
+                rx_bytes,
+                rx_packets,
+                tx_bytes,
+                tx_packets,
+                lifetime_ns,
+            } = stats;
+            writeln!(
+                content,
+                "{} {} {} {} {} {} {} {} {} {}",
+                process.pid(),
+                fd,
+                domain,
+                socket_type,
+                protocol,
+                rx_bytes,
+                rx_packets,
+                tx_bytes,
+                tx_packets,
+                lifetime_ns
+            )
+            .unwrap();
+        }
+    }
+    content.into_bytes()
+}
+
+/// Generate /proc/net/tcp in the format of Linux, which lists the IPv4 TCP sockets
+/// of the host and of the stack. The timers, uids and inodes are shown as zeros.
+fn read_tcp() -> Vec<u8> {
+    let mut content = String::from(
+        "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n",
+    );
+    let mut sl = 0;
+    for (file_ref, _) in get_all_files() {
+        let entry = if let Ok(socket) = file_ref.as_socket() {
+            socket.proc_tcp_entry()
+        } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+            tcp_socket.proc_entry().ok()
+        } else {
+            None
+        };
+        let TcpSocketEntry {
+            local,
+            remote,
+            state,
+            tx_queue,
+            rx_queue,
+        } = match entry {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let (remote_addr, remote_port) = remote.map_or((0, 0), |remote| {
+            (remote.sin_addr.s_addr, u16::from_be(remote.sin_port))
+        });
+        writeln!(
+            content,
+            "{:>4}: {:08X}:{:04X} {:08X}:{:04X} {:02X} {:08X}:{:08X} 00:00000000 00000000 {:>5} {:>8} {}",
+            sl, local.sin_addr.s_addr, u16::from_be(local.sin_port), remote_addr, remote_port,
+            state as u8, tx_queue, rx_queue, 0, 0, 0
+        )
+        .unwrap();
+        sl += 1;
+    }
+    content.into_bytes()
+}
+
+/// Generate /proc/net/unix in the format of Linux, which lists the unix sockets
+/// of the LibOS. The addresses of the kernel and the inodes are shown as zeros.
+fn read_unix() -> Vec<u8> {
+    const SO_ACCEPTCON: u32 = 1 << 16;
+    const SS_UNCONNECTED: u8 = 1;
+    const SS_CONNECTED: u8 = 3;
+
+    let mut content = String::from("Num       RefCount Protocol Flags    Type St Inode Path\n");
+    for (file_ref, ref_count) in get_all_files() {
+        let unix_socket = match file_ref.as_unix_socket() {
+            Ok(unix_socket) => unix_socket,
+            Err(_) => continue,
+        };
+        let UnixSocketEntry {
+            socket_type,
+            is_listening,
+            is_connected,
+            path,
+        } = unix_socket.proc_entry();
+        let flags = if is_listening { SO_ACCEPTCON } else { 0 };
+        let state = if is_connected {
+            SS_CONNECTED
+        } else {
+            SS_UNCONNECTED
+        };
+        write!(
+            content,
+            "{:016x}: {:08X} {:08X} {:08X} {:04X} {:02X} {:5}",
+            0, ref_count, 0, flags, socket_type, state, 0
+        )
+        .unwrap();
+        if let Some(path) = path {
+            write!(content, " {}", path).unwrap();
+        }
+        content.push('\n');
+    }
+    content.into_bytes()
+}
+
+/// The files opened by all processes, each of which is listed once along with the
+/// number of the file descriptors referring to it
+fn get_all_files() -> Vec<(FileRef, usize)> {
+    let mut all_files: Vec<(FileRef, usize)> = Vec::new();
+    let mut indexes: HashMap<usize, usize> = HashMap::new();
+    for process in table::get_all_processes() {
+        // The threads of a process share the same file table
+        let thread = match process.threads().into_iter().next() {
+            Some(thread) => thread,
+            None => continue,
+        };
+        let files = thread.files().lock().unwrap();
+        for (_, file_ref) in files.iter() {
+            let key = Arc::as_ptr(file_ref) as *const u8 as usize;
+            match indexes.get(&key) {
+                Some(&index) => all_files[index].1 += 1,
+                None => {
+                    indexes.insert(key, all_files.len());
+                    all_files.push((file_ref.clone(), 1));
+                }
+            }
+        }
+    }
+    all_files
+}
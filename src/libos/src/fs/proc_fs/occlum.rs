@@ -0,0 +1,49 @@
+//! The Occlum-specific files under /proc/occlum.
+use super::*;
+
+/// Generate the content of the file at `path`, which is relative to /proc/occlum
+pub fn read_file(path: &str) -> Option<Result<Vec<u8>>> {
+    match path {
+        #[cfg(feature = "lock_stat")]
+        "lockstat" => Some(Ok(
+            crate::util::sync::lock_stat::format_lock_stats().into_bytes()
+        )),
+        "untrusted_mem" => Some(Ok(read_untrusted_mem())),
+        "denied_devices" => Some(Ok(crate::fs::dev_fs::format_denials().into_bytes())),
+        "sgxstats" => Some(Ok(read_sgxstats())),
+        "background_works" => Some(Ok(crate::sched::format_background_works().into_bytes())),
+        "host_fd_exhaustion" => Some(Ok(crate::net::format_host_fd_exhaustion().into_bytes())),
+        _ => None,
+    }
+}
+
+/// The files under /proc/occlum, which are the ones handled by `read_file`
+pub fn file_names() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    #[cfg(feature = "lock_stat")]
+    names.push("lockstat");
+    names.extend(&[
+        "untrusted_mem",
+        "denied_devices",
+        "sgxstats",
+        "background_works",
+        "host_fd_exhaustion",
+    ]);
+    names
+}
+
+fn read_sgxstats() -> Vec<u8> {
+    let threads = crate::process::table::get_all_threads();
+    crate::sched::format_sgx_stats(&threads).into_bytes()
+}
+
+fn read_untrusted_mem() -> Vec<u8> {
+    let stats = crate::untrusted::UNTRUSTED_ARENA.stats();
+    format!(
+        "in_use_chunks {}\nin_use_bytes {}\ntotal_bytes {}\n",
+        stats.in_use_chunks(),
+        stats.in_use_bytes(),
+        stats.total_bytes()
+    )
+    .into_bytes()
+}
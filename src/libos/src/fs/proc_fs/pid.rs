@@ -0,0 +1,159 @@
+//! The files under /proc/[pid].
+use super::*;
+use crate::process::{ProcessRef, ProcessStatus};
+use crate::sched::format_sched_stat;
+use crate::time::up_time;
+
+/// Generate the content of the file at `path`, which is relative to /proc/[pid]
+pub fn read_file(process: &ProcessRef, path: &str) -> Option<Result<Vec<u8>>> {
+    let mut components = path.splitn(3, '/');
+    match (components.next(), components.next(), components.next()) {
+        (Some("stat"), None, None) => Some(Ok(read_process_stat(process))),
+        (Some("schedstat"), None, None) => Some(Ok(read_process_schedstat(process))),
+        (Some("environ"), None, None) => Some(Ok(process.env().read().unwrap().to_environ())),
+        (Some("environ_origin"), None, None) => {
+            Some(Ok(process.env().read().unwrap().to_origins().into_bytes()))
+        }
+        (Some("task"), Some(tid), Some(rest)) => {
+            let thread = match tid.parse::<pid_t>().ok().and_then(|tid| {
+                process
+                    .threads()
+                    .into_iter()
+                    .find(|thread| thread.tid() == tid)
+            }) {
+                Some(thread) => thread,
+                None => return Some(Err(errno!(ENOENT, "no such thread"))),
+            };
+            match rest {
+                "schedstat" => Some(Ok(
+                    format_sched_stat(std::iter::once(thread.sched_stat())).into_bytes()
+                )),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Generate the entries of the directory at `path`, which is relative to
+/// /proc/[pid], and whose full path is `dir_path`
+pub fn read_dir(process: &ProcessRef, path: &str, dir_path: &str) -> Option<Result<Vec<DirEntry>>> {
+    let mut components = path.splitn(3, '/');
+    let entries = match (components.next(), components.next(), components.next()) {
+        (Some(""), None, None) => {
+            let files = ["stat", "schedstat", "environ", "environ_origin"];
+            let mut entries: Vec<DirEntry> = files
+                .iter()
+                .map(|name| DirEntry::new(dir_path, *name, FileType::File))
+                .collect();
+            entries.push(DirEntry::new(dir_path, "exe", FileType::SymLink));
+            entries.push(DirEntry::new(dir_path, "fd", FileType::Dir));
+            entries.push(DirEntry::new(dir_path, "task", FileType::Dir));
+            entries
+        }
+        (Some("fd"), None, None) => {
+            // The threads of a process share the file table, while a zombie has none
+            match process.threads().first() {
+                Some(thread) => thread
+                    .files()
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(fd, _)| DirEntry::new(dir_path, fd.to_string(), FileType::SymLink))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+        (Some("task"), None, None) => {
+            let mut tids: Vec<pid_t> = process
+                .threads()
+                .iter()
+                .map(|thread| thread.tid())
+                .collect();
+            tids.sort_unstable();
+            tids.into_iter()
+                .map(|tid| DirEntry::new(dir_path, tid.to_string(), FileType::Dir))
+                .collect()
+        }
+        (Some("task"), Some(tid), None) => {
+            let is_found = tid.parse::<pid_t>().ok().map_or(false, |tid| {
+                process.threads().iter().any(|thread| thread.tid() == tid)
+            });
+            if !is_found {
+                return Some(Err(errno!(ENOENT, "no such thread")));
+            }
+            vec![DirEntry::new(dir_path, "schedstat", FileType::File)]
+        }
+        _ => return None,
+    };
+    Some(Ok(entries))
+}
+
+/// Read the symbolic link at `path`, which is relative to /proc/[pid]
+pub fn read_link(process: &ProcessRef, path: &str) -> Option<Result<String>> {
+    if path == "exe" {
+        return Some(Ok(process.exec_path().to_owned()));
+    }
+    let fd = path.strip_prefix("fd/")?;
+    Some(read_fd_link(process, fd))
+}
+
+fn read_fd_link(process: &ProcessRef, fd: &str) -> Result<String> {
+    let fd = fd
+        .parse::<FileDesc>()
+        .map_err(|_| errno!(EBADF, "Invalid file descriptor"))?;
+    let file_ref = match process.threads().first() {
+        Some(thread) => thread.files().lock().unwrap().get(fd)?,
+        None => return_errno!(ENOENT, "the process has exited"),
+    };
+    if let Ok(inode_file) = file_ref.as_inode_file() {
+        Ok(inode_file.get_abs_path().to_owned())
+    } else {
+        // TODO: support special device files
+        return_errno!(EINVAL, "not a normal file link")
+    }
+}
+
+fn read_process_schedstat(process: &ProcessRef) -> Vec<u8> {
+    let threads = process.threads();
+    format_sched_stat(threads.iter().map(|thread| thread.sched_stat())).into_bytes()
+}
+
+// Only the fields below are supported, and the others are zeros:
+//
+// (1) pid, (2) comm, (3) state, (4) ppid, (5) pgrp, (20) num_threads,
+// (22) starttime, i.e., the time the process started after the boot in clock ticks
+fn read_process_stat(process: &ProcessRef) -> Vec<u8> {
+    let comm = match process.main_thread() {
+        Some(thread) => thread.name().as_c_str().to_string_lossy().into_owned(),
+        None => process.exec_path().rsplit('/').next().unwrap().to_string(),
+    };
+    let state = match process.status() {
+        ProcessStatus::Running => 'R',
+        ProcessStatus::Stopped => 'T',
+        ProcessStatus::Zombie => 'Z',
+    };
+    let ppid = if process.pid() > 0 {
+        process.parent().pid()
+    } else {
+        0
+    };
+    let start_time = up_time::as_clock_ticks(process.start_time());
+    // Fields (6) to (19)
+    let zeros_before_num_threads = ["0"; 14].join(" ");
+    // Fields (23) to (52)
+    let zeros_after_start_time = ["0"; 30].join(" ");
+    format!(
+        "{} ({}) {} {} {} {} {} 0 {} {}\n",
+        process.pid(),
+        comm,
+        state,
+        ppid,
+        process.pgid(),
+        zeros_before_num_threads,
+        process.threads().len(),
+        start_time,
+        zeros_after_start_time
+    )
+    .into_bytes()
+}
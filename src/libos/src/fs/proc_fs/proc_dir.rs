@@ -0,0 +1,135 @@
+use super::*;
+
+/// A directory under /proc or /dev, whose entries are generated when it is opened.
+///
+/// The entries are a snapshot, so a directory read by getdents64 in pieces is
+/// consistent, even if the processes or the files come and go meanwhile.
+#[derive(Debug)]
+pub struct ProcDir {
+    ino: u64,
+    entries: Vec<DirEntry>,
+    offset: SgxMutex<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name: String,
+    pub ino: u64,
+    pub type_: FileType,
+}
+
+impl DirEntry {
+    /// Create an entry of the directory at `dir_path`, whose inode number is given
+    /// by its path
+    pub fn new(dir_path: &str, name: impl Into<String>, type_: FileType) -> Self {
+        let name = name.into();
+        let ino = ino_of(&format!("{}/{}", dir_path, name));
+        Self { name, ino, type_ }
+    }
+}
+
+impl ProcDir {
+    /// Create the directory at `path`, where "." and ".." come before the entries
+    pub fn new(path: &str, entries: Vec<DirEntry>) -> Self {
+        let ino = ino_of(path);
+        let parent_path = match path.rfind('/') {
+            Some(0) | None => "/",
+            Some(idx) => &path[..idx],
+        };
+        let dot_entries = vec![
+            DirEntry {
+                name: ".".to_string(),
+                ino,
+                type_: FileType::Dir,
+            },
+            DirEntry {
+                name: "..".to_string(),
+                ino: ino_of(parent_path),
+                type_: FileType::Dir,
+            },
+        ];
+        Self {
+            ino,
+            entries: dot_entries.into_iter().chain(entries).collect(),
+            offset: SgxMutex::new(0),
+        }
+    }
+}
+
+/// Get the inode number of the file at the path, which is stable as long as the
+/// path refers to the same file, e.g., /proc/[pid] of the same process.
+pub fn ino_of(path: &str) -> u64 {
+    // FNV-1a, which is the same across runs, unlike the hashers of std
+    let hash = path.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    // Some applications skip the entries whose inode numbers are zero
+    max(hash, 1)
+}
+
+impl File for ProcDir {
+    fn read_entry(&self) -> Result<String> {
+        self.read_dirent().map(|(name, _ino, _type)| name)
+    }
+
+    fn read_dirent(&self) -> Result<(String, u64, Option<FileType>)> {
+        let mut offset = self.offset.lock().unwrap();
+        let entry = self
+            .entries
+            .get(*offset)
+            .ok_or_else(|| errno!(ENOENT, "no more entries"))?;
+        *offset += 1;
+        Ok((entry.name.clone(), entry.ino, Some(entry.type_)))
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<off_t> {
+        let mut offset = self.offset.lock().unwrap();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => off as i64,
+            SeekFrom::End(off) => (self.entries.len() as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+            SeekFrom::Current(off) => (*offset as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+        };
+        if new_offset < 0 {
+            return_errno!(EINVAL, "file offset is negative");
+        }
+        *offset = new_offset as usize;
+        Ok(new_offset)
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let nsubdirs = self
+            .entries
+            .iter()
+            .filter(|entry| entry.type_ == FileType::Dir)
+            .count();
+        Ok(Metadata {
+            dev: 0,
+            inode: self.ino as usize,
+            size: 0,
+            blk_size: 0,
+            blocks: 0,
+            atime: Timespec { sec: 0, nsec: 0 },
+            mtime: Timespec { sec: 0, nsec: 0 },
+            ctime: Timespec { sec: 0, nsec: 0 },
+            type_: FileType::Dir,
+            mode: 0o555,
+            // Like Linux, the subdirectories, "." and ".." are counted
+            nlinks: nsubdirs,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
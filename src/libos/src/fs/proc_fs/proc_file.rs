@@ -0,0 +1,96 @@
+use super::*;
+
+/// A read-only file under /proc or /sys, whose content is generated when it is opened.
+#[derive(Debug)]
+pub struct ProcFile {
+    content: Vec<u8>,
+    offset: SgxMutex<usize>,
+    // The inode number, which is given by the path
+    ino: u64,
+}
+
+impl ProcFile {
+    /// Create a file of the content, where `path` is the canonical path of the file
+    pub fn new(path: &str, content: Vec<u8>) -> Self {
+        Self {
+            content,
+            offset: SgxMutex::new(0),
+            ino: ino_of(path),
+        }
+    }
+}
+
+impl File for ProcFile {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let mut offset = self.offset.lock().unwrap();
+        let len = self.read_at(*offset, buf)?;
+        *offset += len;
+        Ok(len)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if offset >= self.content.len() {
+            return Ok(0);
+        }
+        let len = min(buf.len(), self.content.len() - offset);
+        buf[..len].copy_from_slice(&self.content[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        let mut total_len = 0;
+        for buf in bufs {
+            let len = self.read(buf)?;
+            total_len += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(total_len)
+    }
+
+    fn seek(&self, pos: SeekFrom) -> Result<off_t> {
+        let mut offset = self.offset.lock().unwrap();
+        let new_offset = match pos {
+            SeekFrom::Start(off) => off as i64,
+            SeekFrom::End(off) => (self.content.len() as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+            SeekFrom::Current(off) => (*offset as i64)
+                .checked_add(off)
+                .ok_or_else(|| errno!(EOVERFLOW, "file offset overflow"))?,
+        };
+        if new_offset < 0 {
+            return_errno!(EINVAL, "file offset is negative");
+        }
+        *offset = new_offset as usize;
+        Ok(new_offset)
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(Metadata {
+            dev: 0,
+            inode: self.ino as usize,
+            size: self.content.len(),
+            blk_size: 0,
+            blocks: 0,
+            atime: Timespec { sec: 0, nsec: 0 },
+            mtime: Timespec { sec: 0, nsec: 0 },
+            ctime: Timespec { sec: 0, nsec: 0 },
+            type_: FileType::File,
+            mode: 0o444,
+            nlinks: 1,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -1,11 +1,11 @@
-use super::hostfs::HostFS;
+use super::fs_type::create_fs;
+use super::machine_id::init_machine_id;
 use super::sefs::{SgxStorage, SgxUuidProvider};
 use super::*;
-use config::{ConfigMount, ConfigMountFsType};
+use config::ConfigMount;
 use std::path::{Path, PathBuf};
 
 use rcore_fs_mountfs::{MNode, MountFS};
-use rcore_fs_ramfs::RamFS;
 use rcore_fs_sefs::dev::*;
 use rcore_fs_sefs::SEFS;
 use rcore_fs_unionfs::UnionFS;
@@ -33,7 +33,7 @@ lazy_static! {
 fn open_root_fs_according_to(mount_configs: &Vec<ConfigMount>) -> Result<Arc<MountFS>> {
     let mount_config = mount_configs
         .iter()
-        .find(|m| m.target == Path::new("/") && m.type_ == ConfigMountFsType::TYPE_UNIONFS)
+        .find(|m| m.target == Path::new("/") && m.type_ == "unionfs")
         .ok_or_else(|| errno!(Errno::ENOENT, "the root UnionFS is not valid"))?;
     if mount_config.options.layers.is_none() {
         return_errno!(EINVAL, "The root UnionFS must be given the layers");
@@ -43,7 +43,7 @@ fn open_root_fs_according_to(mount_configs: &Vec<ConfigMount>) -> Result<Arc<Mou
     let (root_image_sefs_mac, root_image_sefs_source) = {
         let mount_config = layer_mount_configs
             .iter()
-            .find(|m| m.type_ == ConfigMountFsType::TYPE_SEFS && m.options.integrity_only)
+            .find(|m| m.type_ == "sefs" && m.options.integrity_only)
             .ok_or_else(|| errno!(Errno::ENOENT, "the image SEFS in layers is not valid"))?;
         (
             mount_config.options.mac,
@@ -63,7 +63,7 @@ fn open_root_fs_according_to(mount_configs: &Vec<ConfigMount>) -> Result<Arc<Mou
     let root_container_sefs_source = {
         let mount_config = layer_mount_configs
             .iter()
-            .find(|m| m.type_ == ConfigMountFsType::TYPE_SEFS && !m.options.integrity_only)
+            .find(|m| m.type_ == "sefs" && !m.options.integrity_only)
             .ok_or_else(|| errno!(Errno::ENOENT, "the container SEFS in layers is not valid"))?;
         mount_config.source.as_ref().unwrap()
     };
@@ -82,6 +82,11 @@ fn open_root_fs_according_to(mount_configs: &Vec<ConfigMount>) -> Result<Arc<Mou
         )
     })?;
 
+    // The machine ID is not critical to boot, so the failure is ignored
+    if let Err(e) = init_machine_id(&root_container_sefs.root_inode()) {
+        warn!("failed to init the machine ID: {:?}", e);
+    }
+
     let root_unionfs = UnionFS::new(vec![root_container_sefs, root_image_sefs])?;
     let root_mountable_unionfs = MountFS::new(root_unionfs);
     Ok(root_mountable_unionfs)
@@ -101,57 +106,8 @@ fn mount_nonroot_fs_according_to(mount_config: &Vec<ConfigMount>, root: &MNode)
         }
         let target_dirname = mc.target.file_name().unwrap().to_str().unwrap();
 
-        use self::ConfigMountFsType::*;
-        match mc.type_ {
-            TYPE_SEFS => {
-                if mc.options.integrity_only {
-                    return_errno!(EINVAL, "Cannot mount integrity-only SEFS at non-root path");
-                }
-                if mc.source.is_none() {
-                    return_errno!(EINVAL, "Source is expected for SEFS");
-                }
-                let source_path = mc.source.as_ref().unwrap();
-                let sefs = if !mc.options.temporary {
-                    {
-                        SEFS::open(
-                            Box::new(SgxStorage::new(source_path, false, None)),
-                            &time::OcclumTimeProvider,
-                            &SgxUuidProvider,
-                        )
-                    }
-                    .or_else(|_| {
-                        SEFS::create(
-                            Box::new(SgxStorage::new(source_path, false, None)),
-                            &time::OcclumTimeProvider,
-                            &SgxUuidProvider,
-                        )
-                    })?
-                } else {
-                    SEFS::create(
-                        Box::new(SgxStorage::new(source_path, false, None)),
-                        &time::OcclumTimeProvider,
-                        &SgxUuidProvider,
-                    )?
-                };
-                mount_fs_at(sefs, &root, target_dirname)?;
-            }
-            TYPE_HOSTFS => {
-                if mc.source.is_none() {
-                    return_errno!(EINVAL, "Source is expected for HostFS");
-                }
-                let source_path = mc.source.as_ref().unwrap();
-
-                let hostfs = HostFS::new(source_path);
-                mount_fs_at(hostfs, &root, target_dirname)?;
-            }
-            TYPE_RAMFS => {
-                let ramfs = RamFS::new();
-                mount_fs_at(ramfs, &root, target_dirname)?;
-            }
-            TYPE_UNIONFS => {
-                return_errno!(EINVAL, "Cannot mount UnionFS at non-root path");
-            }
-        }
+        let fs = create_fs(mc)?;
+        mount_fs_at(fs, &root, target_dirname)?;
     }
     Ok(())
 }
@@ -1,7 +1,8 @@
 use super::*;
+use crate::net::{poll_host_fd, PollEventFlags};
 use core::cell::RefCell;
 use core::cmp;
-use std::io::{BufReader, LineWriter};
+use std::io::LineWriter;
 use std::sync::SgxMutex;
 
 macro_rules! try_libc_stdio {
@@ -48,6 +49,40 @@ impl HostStdioFds {
     }
 }
 
+/// Check whether the I/O of the events can be done on the host file without blocking,
+/// if the file is non-blocking.
+///
+/// The host files of stdio are shared with the host, so they are never set to be
+/// non-blocking. Instead, they are polled before the I/O.
+fn check_nonblocking_io(
+    host_fd: FileDesc,
+    status_flags: &RwLock<StatusFlags>,
+    events: PollEventFlags,
+) -> Result<()> {
+    if !status_flags
+        .read()
+        .unwrap()
+        .contains(StatusFlags::O_NONBLOCK)
+    {
+        return Ok(());
+    }
+    let revents = poll_host_fd(host_fd, events)?;
+    // Errors and hangups are reported by the I/O itself
+    if (revents & (events | PollEventFlags::POLLERR | PollEventFlags::POLLHUP)).is_empty() {
+        return_errno!(EAGAIN, "the host file is not ready");
+    }
+    Ok(())
+}
+
+fn set_stdio_status_flags(
+    status_flags: &RwLock<StatusFlags>,
+    new_status_flags: StatusFlags,
+) -> Result<()> {
+    // Only O_NONBLOCK can be set
+    *status_flags.write().unwrap() = new_status_flags & StatusFlags::O_NONBLOCK;
+    Ok(())
+}
+
 struct StdoutRaw {
     host_fd: i32,
 }
@@ -86,6 +121,7 @@ impl std::io::Write for StdoutRaw {
 pub struct StdoutFile {
     inner: SgxMutex<LineWriter<StdoutRaw>>,
     host_fd: FileDesc,
+    status_flags: RwLock<StatusFlags>,
 }
 
 impl StdoutFile {
@@ -93,16 +129,18 @@ impl StdoutFile {
         StdoutFile {
             inner: SgxMutex::new(LineWriter::new(StdoutRaw::new(host_fd))),
             host_fd,
+            status_flags: RwLock::new(StatusFlags::empty()),
         }
     }
 
-    fn get_host_fd(&self) -> FileDesc {
+    pub fn get_host_fd(&self) -> FileDesc {
         self.host_fd
     }
 }
 
 impl File for StdoutFile {
     fn write(&self, buf: &[u8]) -> Result<usize> {
+        check_nonblocking_io(self.host_fd, &self.status_flags, PollEventFlags::POLLOUT)?;
         let write_len = {
             self.inner
                 .lock()
@@ -118,6 +156,7 @@ impl File for StdoutFile {
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
+        check_nonblocking_io(self.host_fd, &self.status_flags, PollEventFlags::POLLOUT)?;
         let mut guard = self.inner.lock().unwrap();
         let mut total_bytes = 0;
         for buf in bufs {
@@ -200,6 +239,18 @@ impl File for StdoutFile {
         Ok(ret)
     }
 
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_WRONLY)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        Ok(*self.status_flags.read().unwrap())
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        set_stdio_status_flags(&self.status_flags, new_status_flags)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -230,11 +281,9 @@ impl std::io::Read for StdinRaw {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         let reading_len = cmp::min(buf.len(), size_t::max_value() as usize);
         let (buf_ptr, _) = buf.as_mut().as_mut_ptr_and_len();
-        let ret = try_libc_stdio!(libc::ocall::read(
-            self.host_fd,
-            buf_ptr as *mut c_void,
-            reading_len,
-        ))
+        let ret = try_libc_stdio!(crate::sched::account_wait(|| unsafe {
+            libc::ocall::read(self.host_fd, buf_ptr as *mut c_void, reading_len)
+        }))
         .unwrap_or_else(|err| {
             warn!("tolerate the read error: {:?}", err.errno());
             0
@@ -245,26 +294,31 @@ impl std::io::Read for StdinRaw {
     }
 }
 
+// The data read from the host is not buffered in the LibOS. Otherwise, the buffered
+// data is invisible to the poll on the host file.
 pub struct StdinFile {
-    inner: SgxMutex<BufReader<StdinRaw>>,
+    inner: SgxMutex<StdinRaw>,
     host_fd: FileDesc,
+    status_flags: RwLock<StatusFlags>,
 }
 
 impl StdinFile {
     pub fn new(host_fd: FileDesc) -> Self {
         StdinFile {
-            inner: SgxMutex::new(BufReader::new(StdinRaw::new(host_fd))),
+            inner: SgxMutex::new(StdinRaw::new(host_fd)),
             host_fd,
+            status_flags: RwLock::new(StatusFlags::empty()),
         }
     }
 
-    fn get_host_fd(&self) -> FileDesc {
+    pub fn get_host_fd(&self) -> FileDesc {
         self.host_fd
     }
 }
 
 impl File for StdinFile {
     fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        check_nonblocking_io(self.host_fd, &self.status_flags, PollEventFlags::POLLIN)?;
         let read_len = {
             self.inner
                 .lock()
@@ -276,6 +330,7 @@ impl File for StdinFile {
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        check_nonblocking_io(self.host_fd, &self.status_flags, PollEventFlags::POLLIN)?;
         let mut guard = self.inner.lock().unwrap();
         let mut total_bytes = 0;
         for buf in bufs {
@@ -349,6 +404,18 @@ impl File for StdinFile {
         Ok(ret)
     }
 
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDONLY)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        Ok(*self.status_flags.read().unwrap())
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        set_stdio_status_flags(&self.status_flags, new_status_flags)
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -362,3 +429,27 @@ impl Debug for StdinFile {
 
 unsafe impl Send for StdinFile {}
 unsafe impl Sync for StdinFile {}
+
+pub trait AsStdin {
+    fn as_stdin(&self) -> Result<&StdinFile>;
+}
+
+impl AsStdin for FileRef {
+    fn as_stdin(&self) -> Result<&StdinFile> {
+        self.as_any()
+            .downcast_ref::<StdinFile>()
+            .ok_or_else(|| errno!(EBADF, "not a stdin file"))
+    }
+}
+
+pub trait AsStdout {
+    fn as_stdout(&self) -> Result<&StdoutFile>;
+}
+
+impl AsStdout for FileRef {
+    fn as_stdout(&self) -> Result<&StdoutFile> {
+        self.as_any()
+            .downcast_ref::<StdoutFile>()
+            .ok_or_else(|| errno!(EBADF, "not a stdout file"))
+    }
+}
@@ -0,0 +1,56 @@
+//! A minimal sysfs.
+//!
+//! Language runtimes (e.g., JVM, .NET and Go) read a few files under /sys to learn
+//! about the CPUs and the limits of their containers, by which they size their
+//! heaps and thread pools. So the files are generated from what the LibOS knows,
+//! like the files under /proc.
+//!
+//! The memory limit of the cgroup is the size of the mmap area of the current
+//! process, which is the max memory that the runtime can allocate for its heap,
+//! rather than the total memory of the enclave. The CPU time is not limited, and
+//! huge pages are not supported.
+use super::proc_fs::ProcFile;
+use super::*;
+use crate::sched::NCORES;
+
+/// Open a file under /sys.
+///
+/// Return None if the path is not handled by sysfs.
+pub fn open(path: &str) -> Option<Result<ProcFile>> {
+    let canonical_path = path.trim_end_matches('/');
+    let path = canonical_path.strip_prefix("/sys/")?;
+    let content = match path {
+        "devices/system/cpu/online"
+        | "devices/system/cpu/possible"
+        | "devices/system/cpu/present" => read_cpu_list(),
+        "fs/cgroup/memory/memory.limit_in_bytes" => read_memory_limit(),
+        "fs/cgroup/memory/memory.usage_in_bytes" => read_memory_usage(),
+        // No quota, like Linux
+        "fs/cgroup/cpu/cpu.cfs_quota_us" => "-1\n".to_string(),
+        "fs/cgroup/cpu/cpu.cfs_period_us" => "100000\n".to_string(),
+        "fs/cgroup/cpu/cpu.shares" => "1024\n".to_string(),
+        "kernel/mm/transparent_hugepage/enabled" => "always madvise [never]\n".to_string(),
+        "kernel/mm/transparent_hugepage/hpage_pmd_size" => "2097152\n".to_string(),
+        "kernel/mm/hugepages/hugepages-2048kB/nr_hugepages"
+        | "kernel/mm/hugepages/hugepages-2048kB/free_hugepages" => "0\n".to_string(),
+        _ => return None,
+    };
+    Some(Ok(ProcFile::new(canonical_path, content.into_bytes())))
+}
+
+fn read_cpu_list() -> String {
+    match *NCORES {
+        1 => "0\n".to_string(),
+        ncores => format!("0-{}\n", ncores - 1),
+    }
+}
+
+fn read_memory_limit() -> String {
+    let (total_size, _) = current!().vm().get_mmap_usage();
+    format!("{}\n", total_size)
+}
+
+fn read_memory_usage() -> String {
+    let (total_size, free_size) = current!().vm().get_mmap_usage();
+    format!("{}\n", total_size - free_size)
+}
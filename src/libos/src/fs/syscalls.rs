@@ -1,11 +1,13 @@
 use super::event_file::EventCreationFlags;
 use super::file_ops;
 use super::file_ops::{
-    AccessibilityCheckFlags, AccessibilityCheckMode, ChmodFlags, ChownFlags, FcntlCmd, FsPath,
-    LinkFlags, StatFlags, UnlinkFlags, AT_FDCWD,
+    AccessibilityCheckFlags, AccessibilityCheckMode, ChmodFlags, ChownFlags, FallocateFlags,
+    FcntlCmd, FsPath, LinkFlags, StatFlags, UnlinkFlags, AT_FDCWD,
 };
 use super::fs_ops;
+use super::inotify_file::IN_DONT_FOLLOW;
 use super::*;
+use net::{check_iovs_total_len, IovVec, IOV_MAX};
 use util::mem_util::from_user;
 
 #[allow(non_camel_case_types)]
@@ -14,6 +16,26 @@ pub struct iovec_t {
     len: size_t,
 }
 
+impl iovec_t {
+    /// Get the buffer of the iovec, which is checked to be in the user space, or
+    /// None if the buffer is empty
+    fn user_buf(&self) -> Result<Option<from_user::UserPtr<u8>>> {
+        // The base of an empty buffer can be anything
+        if self.len == 0 {
+            return Ok(None);
+        }
+        from_user::UserPtr::new(self.base as *const u8, self.len).map(Some)
+    }
+
+    /// Same as `user_buf`, except that the buffer is mutable
+    fn user_buf_mut(&self) -> Result<Option<from_user::UserMutPtr<u8>>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        from_user::UserMutPtr::new(self.base as *mut u8, self.len).map(Some)
+    }
+}
+
 pub fn do_eventfd(init_val: u32) -> Result<isize> {
     do_eventfd2(init_val, 0)
 }
@@ -35,6 +57,49 @@ pub fn do_eventfd2(init_val: u32, flags: i32) -> Result<isize> {
     Ok(fd as isize)
 }
 
+pub fn do_inotify_init() -> Result<isize> {
+    do_inotify_init1(0)
+}
+
+pub fn do_inotify_init1(flags: i32) -> Result<isize> {
+    debug!("inotify_init1: flags: {}", flags);
+    let flags = InotifyFlags::from_bits(flags).ok_or_else(|| errno!(EINVAL, "invalid flags"))?;
+    let file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(InotifyFile::new(flags)?));
+    let fd = current!().add_file(file_ref, flags.contains(InotifyFlags::IN_CLOEXEC));
+    Ok(fd as isize)
+}
+
+pub fn do_inotify_add_watch(fd: FileDesc, path: *const i8, mask: u32) -> Result<isize> {
+    let path = from_user::clone_cstring_safely(path)?
+        .to_string_lossy()
+        .into_owned();
+    debug!(
+        "inotify_add_watch: fd: {}, path: {:?}, mask: {:#x}",
+        fd, path, mask
+    );
+    let file_ref = current!().file(fd)?;
+    let inotify_file = file_ref.as_inotify()?;
+    let abs_path = FsPath::new(&path, AT_FDCWD, false)?.to_abs_path()?;
+    let inode = {
+        let current = current!();
+        let fs = current.fs().lock().unwrap();
+        if mask & IN_DONT_FOLLOW != 0 {
+            fs.lookup_inode_no_follow(&abs_path)?
+        } else {
+            fs.lookup_inode(&abs_path)?
+        }
+    };
+    let wd = inotify_file.add_watch(&abs_path, &inode, mask)?;
+    Ok(wd as isize)
+}
+
+pub fn do_inotify_rm_watch(fd: FileDesc, wd: i32) -> Result<isize> {
+    debug!("inotify_rm_watch: fd: {}, wd: {}", fd, wd);
+    let file_ref = current!().file(fd)?;
+    file_ref.as_inotify()?.rm_watch(wd)?;
+    Ok(0)
+}
+
 pub fn do_open(path: *const i8, flags: u32, mode: u32) -> Result<isize> {
     self::do_openat(AT_FDCWD, path, flags, mode)
 }
@@ -54,42 +119,38 @@ pub fn do_close(fd: FileDesc) -> Result<isize> {
 }
 
 pub fn do_read(fd: FileDesc, buf: *mut u8, size: usize) -> Result<isize> {
-    let safe_buf = {
-        from_user::check_mut_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf, size) }
-    };
+    let mut user_buf = from_user::UserMutPtr::new(buf, size)?;
+    let safe_buf = user_buf.as_mut_slice();
     let len = file_ops::do_read(fd, safe_buf)?;
     Ok(len as isize)
 }
 
 pub fn do_write(fd: FileDesc, buf: *const u8, size: usize) -> Result<isize> {
-    let safe_buf = {
-        from_user::check_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts(buf, size) }
-    };
+    let user_buf = from_user::UserPtr::new(buf, size)?;
+    let safe_buf = user_buf.as_slice();
     let len = file_ops::do_write(fd, safe_buf)?;
     Ok(len as isize)
 }
 
 pub fn do_writev(fd: FileDesc, iov: *const iovec_t, count: i32) -> Result<isize> {
     let count = {
-        if count < 0 {
+        if count < 0 || count as usize > IOV_MAX {
             return_errno!(EINVAL, "Invalid count of iovec");
         }
         count as usize
     };
 
-    from_user::check_array(iov, count)?;
-    let bufs_vec = {
-        let mut bufs_vec = Vec::with_capacity(count);
-        for iov_i in 0..count {
-            let iov_ptr = unsafe { iov.offset(iov_i as isize) };
-            let iov = unsafe { &*iov_ptr };
-            let buf = unsafe { std::slice::from_raw_parts(iov.base as *const u8, iov.len) };
-            bufs_vec.push(buf);
-        }
-        bufs_vec
-    };
+    let iovs = from_user::UserPtr::new(iov, count)?;
+    check_iovs_total_len(iovs.as_slice().iter().map(|iov| iov.len))?;
+    let user_bufs = iovs
+        .as_slice()
+        .iter()
+        .map(|iov| iov.user_buf())
+        .collect::<Result<IovVec<_>>>()?;
+    let bufs_vec = user_bufs
+        .iter()
+        .map(|buf| buf.as_ref().map_or(&[][..], |buf| buf.as_slice()))
+        .collect::<IovVec<&[u8]>>();
     let bufs = &bufs_vec[..];
 
     let len = file_ops::do_writev(fd, bufs)?;
@@ -98,23 +159,23 @@ pub fn do_writev(fd: FileDesc, iov: *const iovec_t, count: i32) -> Result<isize>
 
 pub fn do_readv(fd: FileDesc, iov: *mut iovec_t, count: i32) -> Result<isize> {
     let count = {
-        if count < 0 {
+        if count < 0 || count as usize > IOV_MAX {
             return_errno!(EINVAL, "Invalid count of iovec");
         }
         count as usize
     };
 
-    from_user::check_array(iov, count)?;
-    let mut bufs_vec = {
-        let mut bufs_vec = Vec::with_capacity(count);
-        for iov_i in 0..count {
-            let iov_ptr = unsafe { iov.offset(iov_i as isize) };
-            let iov = unsafe { &*iov_ptr };
-            let buf = unsafe { std::slice::from_raw_parts_mut(iov.base as *mut u8, iov.len) };
-            bufs_vec.push(buf);
-        }
-        bufs_vec
-    };
+    let iovs = from_user::UserPtr::new(iov, count)?;
+    check_iovs_total_len(iovs.as_slice().iter().map(|iov| iov.len))?;
+    let mut user_bufs = iovs
+        .as_slice()
+        .iter()
+        .map(|iov| iov.user_buf_mut())
+        .collect::<Result<IovVec<_>>>()?;
+    let mut bufs_vec = user_bufs
+        .iter_mut()
+        .map(|buf| buf.as_mut().map_or(&mut [][..], |buf| buf.as_mut_slice()))
+        .collect::<IovVec<&mut [u8]>>();
     let bufs = &mut bufs_vec[..];
 
     let len = file_ops::do_readv(fd, bufs)?;
@@ -122,19 +183,15 @@ pub fn do_readv(fd: FileDesc, iov: *mut iovec_t, count: i32) -> Result<isize> {
 }
 
 pub fn do_pread(fd: FileDesc, buf: *mut u8, size: usize, offset: off_t) -> Result<isize> {
-    let safe_buf = {
-        from_user::check_mut_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf, size) }
-    };
+    let mut user_buf = from_user::UserMutPtr::new(buf, size)?;
+    let safe_buf = user_buf.as_mut_slice();
     let len = file_ops::do_pread(fd, safe_buf, offset)?;
     Ok(len as isize)
 }
 
 pub fn do_pwrite(fd: FileDesc, buf: *const u8, size: usize, offset: off_t) -> Result<isize> {
-    let safe_buf = {
-        from_user::check_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts(buf, size) }
-    };
+    let user_buf = from_user::UserPtr::new(buf, size)?;
+    let safe_buf = user_buf.as_slice();
     let len = file_ops::do_pwrite(fd, safe_buf, offset)?;
     Ok(len as isize)
 }
@@ -226,7 +283,7 @@ pub fn do_fdatasync(fd: FileDesc) -> Result<isize> {
     Ok(0)
 }
 
-pub fn do_truncate(path: *const i8, len: usize) -> Result<isize> {
+pub fn do_truncate(path: *const i8, len: off_t) -> Result<isize> {
     let path = from_user::clone_cstring_safely(path)?
         .to_string_lossy()
         .into_owned();
@@ -234,16 +291,20 @@ pub fn do_truncate(path: *const i8, len: usize) -> Result<isize> {
     Ok(0)
 }
 
-pub fn do_ftruncate(fd: FileDesc, len: usize) -> Result<isize> {
+pub fn do_ftruncate(fd: FileDesc, len: off_t) -> Result<isize> {
     file_ops::do_ftruncate(fd, len)?;
     Ok(0)
 }
 
+pub fn do_fallocate(fd: FileDesc, mode: u32, offset: off_t, len: off_t) -> Result<isize> {
+    let flags = FallocateFlags::from_bits(mode)
+        .ok_or_else(|| errno!(EOPNOTSUPP, "the mode of fallocate is not supported"))?;
+    file_ops::do_fallocate(fd, flags, offset, len)?;
+    Ok(0)
+}
+
 pub fn do_getdents64(fd: FileDesc, buf: *mut u8, buf_size: usize) -> Result<isize> {
-    let safe_buf = {
-        from_user::check_mut_array(buf, buf_size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf, buf_size) }
-    };
+    let safe_buf = from_user::UserMutPtr::new(buf, buf_size)?.as_mut_slice();
     let len = file_ops::do_getdents64(fd, safe_buf)?;
     Ok(len as isize)
 }
@@ -292,10 +353,7 @@ pub fn do_chdir(path: *const i8) -> Result<isize> {
 }
 
 pub fn do_getcwd(buf_ptr: *mut u8, size: usize) -> Result<isize> {
-    let buf = {
-        from_user::check_mut_array(buf_ptr, size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf_ptr, size) }
-    };
+    let buf = from_user::UserMutPtr::new(buf_ptr, size)?.as_mut_slice();
 
     let cwd = fs_ops::do_getcwd()?;
 
@@ -399,10 +457,7 @@ pub fn do_readlinkat(dirfd: i32, path: *const i8, buf: *mut u8, size: usize) ->
     let path = from_user::clone_cstring_safely(path)?
         .to_string_lossy()
         .into_owned();
-    let buf = {
-        from_user::check_array(buf, size)?;
-        unsafe { std::slice::from_raw_parts_mut(buf, size) }
-    };
+    let buf = from_user::UserMutPtr::new(buf, size)?.as_mut_slice();
     let fs_path = FsPath::new(&path, dirfd, false)?;
     let len = file_ops::do_readlinkat(&fs_path, buf)?;
     Ok(len as isize)
@@ -46,6 +46,7 @@ pub fn do_handle_interrupt(
 ) -> Result<isize> {
     let info = unsafe { &*info };
     let context = unsafe { &mut *cpu_context };
+    current!().sched_stat().account_interrupt();
     // The cpu context is overriden so that it is as if the syscall is called from where the
     // interrupt happened
     *context = CpuContext::from_sgx(&info.cpu_context);
@@ -18,6 +18,8 @@
 #![feature(option_expect_none)]
 // for UntrustedSliceAlloc in slice_alloc
 #![feature(slice_ptr_get)]
+// for SmallVec in small_vec
+#![feature(min_const_generics)]
 
 #[macro_use]
 extern crate alloc;
@@ -44,9 +46,11 @@ extern crate rcore_fs_sefs;
 extern crate rcore_fs_unionfs;
 #[macro_use]
 extern crate derive_builder;
-extern crate ringbuf;
+extern crate rustls;
 extern crate serde;
 extern crate serde_json;
+extern crate smoltcp;
+extern crate webpki;
 
 use sgx_trts::libc;
 use sgx_types::*;
@@ -67,6 +71,8 @@ mod entry;
 mod exception;
 mod fs;
 mod interrupt;
+#[cfg(feature = "libos_test")]
+mod libos_test;
 mod misc;
 mod net;
 mod process;
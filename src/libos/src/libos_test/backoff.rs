@@ -0,0 +1,53 @@
+use super::*;
+use crate::util::sync::LockBackoff;
+
+#[cfg(not(feature = "lock_stat"))]
+libos_tests!(test_lock_free, test_try_lock_held);
+#[cfg(feature = "lock_stat")]
+libos_tests!(test_lock_free, test_try_lock_held, test_contention_recorded);
+
+fn test_lock_free() -> Result<()> {
+    let mutex = SgxMutex::new(1);
+    *mutex.lock_with_backoff().unwrap() += 1;
+    let guard = mutex.try_lock_with_backoff();
+    test_assert!(guard.map(|guard| *guard) == Some(2));
+    Ok(())
+}
+
+fn test_try_lock_held() -> Result<()> {
+    let mutex = SgxMutex::new(());
+    let _guard = mutex.lock().unwrap();
+    // Give up after the backoff instead of sleeping in the lock forever
+    test_assert!(mutex.try_lock_with_backoff().is_none());
+    Ok(())
+}
+
+// A lock that is not available right away must be recorded as a contended one
+#[cfg(feature = "lock_stat")]
+fn test_contention_recorded() -> Result<()> {
+    use crate::util::sync::lock_stat::format_lock_stats;
+    use core::panic::Location;
+
+    let mutex = SgxMutex::new(());
+    let guard = mutex.lock().unwrap();
+    let site = Location::caller();
+    let _guard = mutex
+        .lock_or_else::<()>(site, |inner| {
+            // The holder releases the lock in the meantime
+            drop(guard);
+            Ok(inner.lock())
+        })
+        .unwrap()
+        .unwrap();
+
+    // The columns are the site, the acquisitions and the contentions, where every
+    // acquisition at the site is contended
+    let site_name = format!("{}:{}", site.file(), site.line());
+    let lock_stats = format_lock_stats();
+    let stat = lock_stats
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|stat| stat.first() == Some(&site_name.as_str()));
+    test_assert!(stat.map_or(false, |stat| stat[1] == stat[2] && stat[2] != "0"));
+    Ok(())
+}
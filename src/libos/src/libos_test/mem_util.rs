@@ -0,0 +1,53 @@
+use super::*;
+use crate::util::mem_util::from_user::{UserMutPtr, UserPtr};
+use crate::vm::{MMapFlags, VMPerms, PAGE_SIZE};
+
+libos_tests!(test_user_ptr_read_write, test_user_ptr_invalid);
+
+// Map a page in the user space for the test, which is unmapped by the caller
+fn mmap_user_page() -> Result<usize> {
+    let perms = VMPerms::READ | VMPerms::WRITE;
+    let flags = MMapFlags::MAP_PRIVATE | MMapFlags::MAP_ANONYMOUS;
+    crate::vm::do_mmap(0, PAGE_SIZE, perms, flags, 0, 0)
+}
+
+fn test_user_ptr_read_write() -> Result<()> {
+    let addr = mmap_user_page()?;
+    let res = (|| -> Result<()> {
+        let mut buf = UserMutPtr::new(addr as *mut u32, 4)?;
+        buf.as_mut_slice().copy_from_slice(&[1, 2, 3, 4]);
+        buf.write(5)?;
+        test_assert!(buf.as_slice() == [5, 2, 3, 4]);
+
+        // The same array is seen through the immutable pointer
+        let buf = UserPtr::new(addr as *const u32, 4)?;
+        test_assert!(buf.read()? == 5);
+        test_assert!(buf.as_slice()[1..] == [2, 3, 4]);
+        // An empty array has no element to read
+        let empty_buf = UserPtr::new(addr as *const u32, 0)?;
+        test_assert!(empty_buf.as_slice().is_empty());
+        test_assert!(matches!(empty_buf.read(), Err(e) if e.errno() == EFAULT));
+        Ok(())
+    })();
+    crate::vm::do_munmap(addr, PAGE_SIZE)?;
+    res
+}
+
+fn test_user_ptr_invalid() -> Result<()> {
+    // NULL is only allowed by new_nullable
+    let res = UserPtr::new(std::ptr::null::<u8>(), 1);
+    test_assert!(matches!(res, Err(e) if e.errno() == EFAULT));
+    test_assert!(UserMutPtr::new_nullable(std::ptr::null_mut::<u8>(), 1)?.is_none());
+
+    // The memory of the LibOS is not in the user space
+    let mut val = 0u64;
+    let res = UserMutPtr::new(&mut val as *mut u64, 1);
+    test_assert!(matches!(res, Err(e) if e.errno() == EFAULT));
+
+    // Nor is the array that overflows or runs past the user space
+    let addr = mmap_user_page()?;
+    let res = UserPtr::new(addr as *const u64, usize::max_value() / 4);
+    crate::vm::do_munmap(addr, PAGE_SIZE)?;
+    test_assert!(matches!(res, Err(e) if e.errno() == EFAULT));
+    Ok(())
+}
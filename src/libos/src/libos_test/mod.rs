@@ -0,0 +1,102 @@
+//! An in-enclave test runner for the LibOS.
+//!
+//! The C test programs under test/ check the LibOS through system calls, which
+//! makes it hard to tell which layer a regression comes from. The tests here
+//! call the internal APIs of the LibOS (e.g., VFS, sockets and signals) directly
+//! and run inside the enclave, in the context of the thread that triggers them.
+//!
+//! The tests are only built with the `libos_test` feature. They are triggered
+//! through the control device /dev/libos_test: writing a filter to the device
+//! runs all the tests whose names contain the filter (an empty filter matches all
+//! tests), and then reading the device returns the report of the run.
+//!
+//! Note that a test that panics aborts the whole run, so a test should return an
+//! error on failure (see `test_assert!`) instead of panicking.
+use super::*;
+use std::fmt::Write;
+
+/// Return an error from the test if the condition does not hold
+macro_rules! test_assert {
+    ($cond: expr) => {
+        if !$cond {
+            return_errno!(EINVAL, concat!("assertion failed: ", stringify!($cond)));
+        }
+    };
+}
+
+/// Define the tests of a suite, which are functions of type `fn() -> Result<()>`
+macro_rules! libos_tests {
+    ($($test_fn: ident),* $(,)?) => {
+        pub const TESTS: &[LibosTest] = &[
+            $(
+                LibosTest {
+                    name: stringify!($test_fn),
+                    func: $test_fn,
+                },
+            )*
+        ];
+    };
+}
+
+mod backoff;
+mod mem_util;
+mod ring_buf;
+mod seq_range_set;
+mod signal;
+mod small_vec;
+mod socket;
+mod tls;
+mod trace;
+mod vfs;
+
+/// A LibOS-internal test
+pub struct LibosTest {
+    pub name: &'static str,
+    pub func: fn() -> Result<()>,
+}
+
+const TEST_SUITES: &[(&str, &[LibosTest])] = &[
+    ("vfs", vfs::TESTS),
+    ("socket", socket::TESTS),
+    ("signal", signal::TESTS),
+    ("small_vec", small_vec::TESTS),
+    ("seq_range_set", seq_range_set::TESTS),
+    ("backoff", backoff::TESTS),
+    ("ring_buf", ring_buf::TESTS),
+    ("trace", trace::TESTS),
+    ("tls", tls::TESTS),
+    ("mem_util", mem_util::TESTS),
+];
+
+/// Run the tests whose full names (i.e., `<suite>::<test>`) contain `filter` and
+/// return the report.
+pub fn run_tests(filter: &str) -> String {
+    let mut report = String::new();
+    let mut num_passed = 0;
+    let mut num_failed = 0;
+    for (suite_name, tests) in TEST_SUITES {
+        for test in tests.iter() {
+            let test_name = format!("{}::{}", suite_name, test.name);
+            if !test_name.contains(filter) {
+                continue;
+            }
+            match (test.func)() {
+                Ok(()) => {
+                    num_passed += 1;
+                    writeln!(report, "test {} ... ok", test_name).unwrap();
+                }
+                Err(e) => {
+                    num_failed += 1;
+                    writeln!(report, "test {} ... FAILED: {}", test_name, e).unwrap();
+                }
+            }
+        }
+    }
+    writeln!(
+        report,
+        "test result: {} passed; {} failed",
+        num_passed, num_failed
+    )
+    .unwrap();
+    report
+}
@@ -0,0 +1,102 @@
+use super::*;
+use crate::util::ring_buf::{ring_buffer, RECORD_HEADER_LEN};
+
+libos_tests!(
+    test_bytes_wraparound,
+    test_bytes_full,
+    test_records_wraparound,
+    test_record_all_or_nothing,
+    test_resize,
+);
+
+fn test_bytes_wraparound() -> Result<()> {
+    let (mut reader, writer) = ring_buffer(8)?;
+    // Move the indices near the end of the storage
+    test_assert!(writer.write_to_buffer(&[0; 6])? == 6);
+    test_assert!(reader.read_from_buffer(&mut [0; 6])? == 6);
+
+    // The bytes wrap around the end of the storage
+    test_assert!(writer.write_to_vector(&[&[1, 2, 3][..], &[4, 5, 6, 7]])? == 7);
+    let (mut buf0, mut buf1) = ([0u8; 2], [0u8; 8]);
+    let count = reader.read_from_vector_with(&mut [&mut buf0[..], &mut buf1[..]], true, true)?;
+    test_assert!(count == 7);
+    test_assert!(buf0 == [1, 2] && buf1[..5] == [3, 4, 5, 6, 7]);
+    // The bytes peeked are left in the ring
+    test_assert!(reader.bytes_to_read() == 7);
+
+    let (mut buf0, mut buf1) = ([0u8; 5], [0u8; 5]);
+    let count = reader.read_from_vector_with(&mut [&mut buf0[..], &mut buf1[..]], false, true)?;
+    test_assert!(count == 7);
+    test_assert!(buf0 == [1, 2, 3, 4, 5] && buf1[..2] == [6, 7]);
+    test_assert!(reader.bytes_to_read() == 0);
+    Ok(())
+}
+
+fn test_bytes_full() -> Result<()> {
+    let (mut reader, writer) = ring_buffer(8)?;
+    writer.set_non_blocking();
+    test_assert!(writer.write_to_buffer(&[0; 5])? == 5);
+    // Only the free space is written
+    test_assert!(writer.write_to_vector(&[&[1, 2][..], &[3, 4]])? == 3);
+    test_assert!(!writer.can_write());
+    let res = writer.write_to_buffer(&[5]);
+    test_assert!(matches!(res, Err(e) if e.errno() == EAGAIN));
+
+    let mut buf = [0u8; 8];
+    test_assert!(reader.read_from_buffer(&mut buf)? == 8);
+    test_assert!(buf[5..] == [1, 2, 3]);
+    test_assert!(writer.can_write());
+    Ok(())
+}
+
+fn test_records_wraparound() -> Result<()> {
+    let (mut reader, writer) = ring_buffer(16)?;
+    test_assert!(writer.write_record(&[&[1; 6][..]])? == 6);
+    test_assert!(reader.read_record(&mut [&mut [0; 6][..]], false, true)? == Some((6, 6)));
+
+    // The header and the data wrap around the end of the storage
+    test_assert!(writer.write_record(&[&[2, 3, 4][..], &[5, 6, 7, 8, 9]])? == 8);
+    test_assert!(reader.next_record_len() == Some(8));
+    let mut buf = [0u8; 8];
+    test_assert!(reader.read_record(&mut [&mut buf[..]], true, true)? == Some((8, 8)));
+    test_assert!(buf == [2, 3, 4, 5, 6, 7, 8, 9]);
+    // The data beyond the buffers is discarded
+    let mut buf = [0u8; 4];
+    test_assert!(reader.read_record(&mut [&mut buf[..]], false, true)? == Some((4, 8)));
+    test_assert!(buf == [2, 3, 4, 5]);
+    test_assert!(reader.bytes_to_read() == 0);
+    Ok(())
+}
+
+fn test_record_all_or_nothing() -> Result<()> {
+    let (_reader, writer) = ring_buffer(16)?;
+    writer.set_non_blocking();
+    test_assert!(writer.write_record(&[&[0; 8][..]])? == 8);
+    // The record is not written in part
+    let res = writer.write_record(&[&[0; 4][..]]);
+    test_assert!(matches!(res, Err(e) if e.errno() == EAGAIN));
+    test_assert!(writer.bytes_unread() == RECORD_HEADER_LEN + 8);
+    // Nor is the record larger than the ring
+    let res = writer.write_record(&[&[0; 16][..]]);
+    test_assert!(matches!(res, Err(e) if e.errno() == EMSGSIZE));
+    Ok(())
+}
+
+fn test_resize() -> Result<()> {
+    let (mut reader, mut writer) = ring_buffer(8)?;
+    test_assert!(writer.write_to_buffer(&[1; 4])? == 4);
+    // The ring is not resized while it is not empty
+    test_assert!(!writer.resize(16)?);
+    test_assert!(reader.read_from_buffer(&mut [0; 4])? == 4);
+    test_assert!(writer.resize(16)?);
+    test_assert!(writer.capacity() == 16);
+
+    // The bytes wrap around the end of the new storage
+    test_assert!(writer.write_to_buffer(&[2; 14])? == 14);
+    test_assert!(reader.read_from_buffer(&mut [0; 14])? == 14);
+    test_assert!(writer.write_to_buffer(&[3; 4])? == 4);
+    let mut buf = [0u8; 4];
+    test_assert!(reader.read_from_buffer(&mut buf)? == 4);
+    test_assert!(buf == [3; 4]);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use super::*;
+use crate::util::sync::SeqRangeSet;
+
+libos_tests!(test_overlaps, test_remove, test_overflow_is_conservative);
+
+fn test_overlaps() -> Result<()> {
+    let set = SeqRangeSet::new();
+    test_assert!(!set.overlaps(0, usize::max_value()));
+    set.insert(0x1000, 0x3000);
+    set.insert(0x8000, 0x9000);
+    test_assert!(set.overlaps(0x2000, 0x2001));
+    test_assert!(set.overlaps(0, 0x1001));
+    test_assert!(set.overlaps(0x2fff, 0x8001));
+    // The ranges are half-open
+    test_assert!(!set.overlaps(0, 0x1000));
+    test_assert!(!set.overlaps(0x3000, 0x8000));
+    test_assert!(!set.overlaps(0x9000, 0xa000));
+    Ok(())
+}
+
+fn test_remove() -> Result<()> {
+    let set = SeqRangeSet::new();
+    set.insert(0x1000, 0x2000);
+    set.insert(0x3000, 0x4000);
+    set.insert(0x5000, 0x6000);
+    set.remove(0x1000, 0x2000);
+    test_assert!(!set.overlaps(0x1000, 0x2000));
+    // The last range is moved into the slot of the removed one
+    test_assert!(set.overlaps(0x3000, 0x4000));
+    test_assert!(set.overlaps(0x5000, 0x6000));
+    set.remove(0x5000, 0x6000);
+    set.remove(0x3000, 0x4000);
+    test_assert!(!set.overlaps(0, usize::max_value()));
+    Ok(())
+}
+
+fn test_overflow_is_conservative() -> Result<()> {
+    let set = SeqRangeSet::new();
+    let num_ranges = 100;
+    for i in 0..num_ranges {
+        set.insert(i * 0x2000, i * 0x2000 + 0x1000);
+    }
+    // The ranges that do not fit in the slots are not tracked
+    test_assert!(set.overlaps(0x1000, 0x2000));
+    for i in 0..num_ranges {
+        set.remove(i * 0x2000, i * 0x2000 + 0x1000);
+    }
+    test_assert!(!set.overlaps(0, usize::max_value()));
+    Ok(())
+}
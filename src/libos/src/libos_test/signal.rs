@@ -0,0 +1,92 @@
+use super::*;
+use crate::signal::constants::*;
+use crate::signal::{KernelSignal, SigNum, SigQueues, SigSet};
+
+libos_tests!(
+    test_sigset_ops,
+    test_std_signal_not_queued,
+    test_rt_signal_queued,
+    test_dequeue_skips_blocked,
+    test_dequeue_std_before_rt,
+);
+
+fn rt_signum(offset: u8) -> Result<SigNum> {
+    // SIGRTMIN is 32 in Occlum
+    SigNum::from_u8(32 + offset)
+}
+
+fn enqueue(queues: &mut SigQueues, signum: SigNum) {
+    queues.enqueue(Box::new(KernelSignal::new(signum)));
+}
+
+fn dequeue(queues: &mut SigQueues, blocked: &SigSet) -> Option<SigNum> {
+    queues.dequeue(blocked).map(|signal| signal.num())
+}
+
+fn test_sigset_ops() -> Result<()> {
+    let mut set = SigSet::new_empty();
+    test_assert!(set.empty());
+    set += SIGUSR1;
+    set += SIGUSR2;
+    test_assert!(set.contains(SIGUSR1) && set.contains(SIGUSR2));
+    test_assert!(set.count() == 2);
+    set -= SIGUSR1;
+    test_assert!(!set.contains(SIGUSR1));
+    test_assert!((!set).contains(SIGUSR1));
+    test_assert!((set & SigSet::from(SIGUSR2)).count() == 1);
+    test_assert!(SigSet::new_full().full());
+    Ok(())
+}
+
+fn test_std_signal_not_queued() -> Result<()> {
+    let mut queues = SigQueues::new();
+    enqueue(&mut queues, SIGUSR1);
+    enqueue(&mut queues, SIGUSR1);
+    test_assert!(queues.pending() == SigSet::from(SIGUSR1));
+
+    let no_blocked = SigSet::new_empty();
+    test_assert!(dequeue(&mut queues, &no_blocked) == Some(SIGUSR1));
+    // The second instance of a standard signal is discarded
+    test_assert!(dequeue(&mut queues, &no_blocked) == None);
+    test_assert!(queues.empty());
+    Ok(())
+}
+
+fn test_rt_signal_queued() -> Result<()> {
+    let rt_sig = rt_signum(1)?;
+    let mut queues = SigQueues::new();
+    enqueue(&mut queues, rt_sig);
+    enqueue(&mut queues, rt_sig);
+
+    let no_blocked = SigSet::new_empty();
+    test_assert!(dequeue(&mut queues, &no_blocked) == Some(rt_sig));
+    test_assert!(dequeue(&mut queues, &no_blocked) == Some(rt_sig));
+    test_assert!(queues.empty());
+    Ok(())
+}
+
+fn test_dequeue_skips_blocked() -> Result<()> {
+    let mut queues = SigQueues::new();
+    enqueue(&mut queues, SIGUSR1);
+    enqueue(&mut queues, SIGUSR2);
+
+    let blocked = SigSet::from(SIGUSR1);
+    test_assert!(dequeue(&mut queues, &blocked) == Some(SIGUSR2));
+    test_assert!(dequeue(&mut queues, &blocked) == None);
+    // The blocked signal is still pending
+    test_assert!(queues.pending() == SigSet::from(SIGUSR1));
+    test_assert!(dequeue(&mut queues, &SigSet::new_empty()) == Some(SIGUSR1));
+    Ok(())
+}
+
+fn test_dequeue_std_before_rt() -> Result<()> {
+    let rt_sig = rt_signum(0)?;
+    let mut queues = SigQueues::new();
+    enqueue(&mut queues, rt_sig);
+    enqueue(&mut queues, SIGUSR2);
+
+    let no_blocked = SigSet::new_empty();
+    test_assert!(dequeue(&mut queues, &no_blocked) == Some(SIGUSR2));
+    test_assert!(dequeue(&mut queues, &no_blocked) == Some(rt_sig));
+    Ok(())
+}
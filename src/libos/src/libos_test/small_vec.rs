@@ -0,0 +1,45 @@
+use super::*;
+use crate::util::small_vec::SmallVec;
+
+libos_tests!(
+    test_inline_elements,
+    test_spilled_elements,
+    test_elements_dropped
+);
+
+fn test_inline_elements() -> Result<()> {
+    let vec: SmallVec<usize, 4> = (0..4).collect();
+    test_assert!(!vec.spilled());
+    test_assert!(vec.as_slice() == &[0, 1, 2, 3]);
+    Ok(())
+}
+
+fn test_spilled_elements() -> Result<()> {
+    let mut vec: SmallVec<usize, 4> = SmallVec::new();
+    for i in 0..10 {
+        vec.push(i);
+    }
+    test_assert!(vec.spilled());
+    test_assert!(vec.iter().cloned().eq(0..10));
+
+    // The elements go to the heap directly if there are too many of them
+    let vec: SmallVec<usize, 4> = (0..5).collect();
+    test_assert!(vec.spilled());
+    test_assert!(vec.len() == 5);
+    Ok(())
+}
+
+fn test_elements_dropped() -> Result<()> {
+    let value = Arc::new(0);
+    {
+        let vec: SmallVec<Arc<i32>, 4> = (0..3).map(|_| value.clone()).collect();
+        test_assert!(Arc::strong_count(&value) == 4);
+    }
+    test_assert!(Arc::strong_count(&value) == 1);
+    {
+        let vec: SmallVec<Arc<i32>, 2> = (0..3).map(|_| value.clone()).collect();
+        test_assert!(Arc::strong_count(&value) == 4);
+    }
+    test_assert!(Arc::strong_count(&value) == 1);
+    Ok(())
+}
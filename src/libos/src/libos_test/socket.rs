@@ -0,0 +1,125 @@
+use super::*;
+use crate::fs::{File, StatusFlags};
+use crate::net::UnixSocketFile;
+
+libos_tests!(
+    test_socketpair_read_write,
+    test_read_after_peer_closed,
+    test_connect_then_accept,
+    test_bind_on_used_path,
+    test_connect_after_listener_closed,
+    test_accept_nonblocking,
+    test_connect_with_full_backlog,
+);
+
+fn check_transfer(writer: &UnixSocketFile, reader: &UnixSocketFile) -> Result<()> {
+    let data = b"Hello from the LibOS test";
+    test_assert!(writer.write(data)? == data.len());
+    let mut buf = vec![0; data.len()];
+    test_assert!(reader.read(&mut buf)? == data.len());
+    test_assert!(&buf[..] == &data[..]);
+    Ok(())
+}
+
+fn test_socketpair_read_write() -> Result<()> {
+    let (socket1, socket2) = UnixSocketFile::socketpair(libc::SOCK_STREAM, 0)?;
+    check_transfer(&socket1, &socket2)?;
+    check_transfer(&socket2, &socket1)?;
+    Ok(())
+}
+
+fn test_read_after_peer_closed() -> Result<()> {
+    let (socket1, socket2) = UnixSocketFile::socketpair(libc::SOCK_STREAM, 0)?;
+    let data = b"the last words";
+    test_assert!(socket1.write(data)? == data.len());
+    drop(socket1);
+    // The data written before the peer is closed can still be read
+    let mut buf = vec![0; data.len() * 2];
+    test_assert!(socket2.read(&mut buf)? == data.len());
+    // And then EOF
+    test_assert!(socket2.read(&mut buf)? == 0);
+    Ok(())
+}
+
+fn test_connect_then_accept() -> Result<()> {
+    let path = "/libos_test/connect_then_accept.sock";
+    let listener = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    listener.bind(path)?;
+    listener.listen(16)?;
+
+    let client = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    client.connect(path)?;
+    test_assert!(client.is_connected());
+    let server = listener.accept()?;
+    test_assert!(server.is_connected());
+
+    check_transfer(&client, &server)?;
+    check_transfer(&server, &client)?;
+    Ok(())
+}
+
+fn test_bind_on_used_path() -> Result<()> {
+    let path = "/libos_test/bind_on_used_path.sock";
+    let socket1 = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    socket1.bind(path)?;
+    socket1.listen(16)?;
+
+    let socket2 = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    let res = socket2.bind(path);
+    test_assert!(matches!(res, Err(e) if e.errno() == EADDRINUSE));
+    Ok(())
+}
+
+fn test_connect_after_listener_closed() -> Result<()> {
+    let path = "/libos_test/connect_after_listener_closed.sock";
+    let listener = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    listener.bind(path)?;
+    listener.listen(16)?;
+    drop(listener);
+
+    let client = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    test_assert!(client.connect(path).is_err());
+    test_assert!(!client.is_connected());
+    Ok(())
+}
+
+fn test_accept_nonblocking() -> Result<()> {
+    let path = "/libos_test/accept_nonblocking.sock";
+    let listener = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    listener.bind(path)?;
+    listener.listen(16)?;
+    listener.set_status_flags(StatusFlags::O_NONBLOCK)?;
+    test_assert!(listener.get_status_flags()? == StatusFlags::O_NONBLOCK);
+
+    let res = listener.accept();
+    test_assert!(matches!(res, Err(e) if e.errno() == EAGAIN));
+    let client = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    client.connect(path)?;
+    let server = listener.accept()?;
+    check_transfer(&client, &server)?;
+    Ok(())
+}
+
+fn test_connect_with_full_backlog() -> Result<()> {
+    let path = "/libos_test/connect_with_full_backlog.sock";
+    let listener = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    listener.bind(path)?;
+    // Like Linux, one more connection than the backlog can be pending
+    listener.listen(0)?;
+
+    let client1 = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    client1.connect(path)?;
+    let client2 = UnixSocketFile::new(libc::SOCK_STREAM, 0)?;
+    client2.set_status_flags(StatusFlags::O_NONBLOCK)?;
+    let res = client2.connect(path);
+    test_assert!(matches!(res, Err(e) if e.errno() == EAGAIN));
+    test_assert!(!client2.is_connected());
+
+    // Accepting a connection makes room for another one
+    let server1 = listener.accept()?;
+    client2.connect(path)?;
+    let server2 = listener.accept()?;
+    check_transfer(&client1, &server1)?;
+    check_transfer(&client2, &server2)?;
+    Ok(())
+}
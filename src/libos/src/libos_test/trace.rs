@@ -0,0 +1,70 @@
+use super::*;
+use crate::syscall::trace::{record_ocall, replay_ocall, ReplayTrace};
+
+libos_tests!(test_record_then_replay, test_replay_diverged);
+
+const OCALL_READ: u32 = 7;
+const OCALL_CLOCK_GETTIME: u32 = 11;
+const FRAME_SIZE: usize = 64;
+
+// The frame of an OCall given back by the host, i.e., the marshalled arguments
+// followed by the return value, the errno and the buffers of [out] parameters
+fn host_frame(index: u32) -> Vec<u8> {
+    (0..FRAME_SIZE).map(|i| (i as u32 * index) as u8).collect()
+}
+
+fn record_trace(tid: pid_t) -> Vec<u8> {
+    let mut trace = Some(Vec::new());
+    for index in &[OCALL_READ, OCALL_CLOCK_GETTIME] {
+        let frame = host_frame(*index);
+        let status = sgx_status_t::SGX_SUCCESS;
+        record_ocall(&mut trace, tid, *index, frame.as_ptr(), frame.len(), status);
+    }
+    trace.unwrap()
+}
+
+fn test_record_then_replay() -> Result<()> {
+    let tid = 1;
+    let trace = record_trace(tid);
+    let mut replay_trace = ReplayTrace::load(trace.as_slice())?;
+    for index in &[OCALL_READ, OCALL_CLOCK_GETTIME] {
+        let mut frame = vec![0u8; FRAME_SIZE];
+        let status = replay_ocall(
+            &mut replay_trace,
+            tid,
+            *index,
+            frame.as_mut_ptr(),
+            frame.len(),
+        );
+        // Replayed without calling the host
+        test_assert!(status == Some(sgx_status_t::SGX_SUCCESS));
+        test_assert!(frame == host_frame(*index));
+    }
+    Ok(())
+}
+
+fn test_replay_diverged() -> Result<()> {
+    let trace = record_trace(1);
+    let mut replay_trace = ReplayTrace::load(trace.as_slice())?;
+    let mut frame = vec![0u8; FRAME_SIZE];
+    // The OCalls of another thread are not recorded
+    let status = replay_ocall(
+        &mut replay_trace,
+        2,
+        OCALL_READ,
+        frame.as_mut_ptr(),
+        frame.len(),
+    );
+    test_assert!(status.is_none());
+    // The OCall is not the one recorded
+    let status = replay_ocall(
+        &mut replay_trace,
+        1,
+        OCALL_CLOCK_GETTIME,
+        frame.as_mut_ptr(),
+        frame.len(),
+    );
+    test_assert!(status.is_none());
+    test_assert!(frame == vec![0u8; FRAME_SIZE]);
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use super::*;
+use crate::fs::{AccessMode, CreationFlags, File};
+use std::io::SeekFrom;
+
+libos_tests!(
+    test_write_then_read,
+    test_open_excl_on_existing_file,
+    test_open_after_unlink,
+    test_truncate_on_open,
+);
+
+const TEST_DIR: &str = "/root";
+
+fn open_file(path: &str, flags: u32) -> Result<Box<dyn File>> {
+    let current = current!();
+    let fs = current.fs().lock().unwrap();
+    fs.open_file(path, flags, 0o666)
+}
+
+fn unlink_file(file_name: &str) -> Result<()> {
+    let dir_inode = {
+        let current = current!();
+        let fs = current.fs().lock().unwrap();
+        fs.lookup_inode(TEST_DIR)?
+    };
+    dir_inode.unlink(file_name)?;
+    Ok(())
+}
+
+fn create_file(file_name: &str) -> Result<Box<dyn File>> {
+    let flags = AccessMode::O_RDWR as u32 | CreationFlags::O_CREAT.bits();
+    open_file(&format!("{}/{}", TEST_DIR, file_name), flags)
+}
+
+fn test_write_then_read() -> Result<()> {
+    let file_name = "libos_test_write_then_read";
+    let file = create_file(file_name)?;
+    let data = b"Hello from the LibOS test";
+    let res = (|| {
+        test_assert!(file.write(data)? == data.len());
+        test_assert!(file.seek(SeekFrom::Start(0))? == 0);
+        let mut buf = vec![0; data.len()];
+        test_assert!(file.read(&mut buf)? == data.len());
+        test_assert!(&buf[..] == &data[..]);
+        // Reading at the end of file returns nothing
+        test_assert!(file.read(&mut buf)? == 0);
+        Ok(())
+    })();
+    unlink_file(file_name)?;
+    res
+}
+
+fn test_open_excl_on_existing_file() -> Result<()> {
+    let file_name = "libos_test_open_excl";
+    create_file(file_name)?;
+    let flags = AccessMode::O_RDWR as u32 | (CreationFlags::O_CREAT | CreationFlags::O_EXCL).bits();
+    let res = open_file(&format!("{}/{}", TEST_DIR, file_name), flags);
+    unlink_file(file_name)?;
+    test_assert!(matches!(res, Err(e) if e.errno() == EEXIST));
+    Ok(())
+}
+
+fn test_open_after_unlink() -> Result<()> {
+    let file_name = "libos_test_open_after_unlink";
+    create_file(file_name)?;
+    unlink_file(file_name)?;
+    let res = open_file(
+        &format!("{}/{}", TEST_DIR, file_name),
+        AccessMode::O_RDONLY as u32,
+    );
+    test_assert!(matches!(res, Err(e) if e.errno() == ENOENT));
+    Ok(())
+}
+
+fn test_truncate_on_open() -> Result<()> {
+    let file_name = "libos_test_truncate_on_open";
+    let res = (|| {
+        create_file(file_name)?.write(b"some content")?;
+        let flags = AccessMode::O_RDWR as u32 | CreationFlags::O_TRUNC.bits();
+        let file = open_file(&format!("{}/{}", TEST_DIR, file_name), flags)?;
+        test_assert!(file.metadata()?.size == 0);
+        Ok(())
+    })();
+    unlink_file(file_name)?;
+    res
+}
@@ -24,7 +24,7 @@ pub struct sysinfo_t {
 
 pub fn do_sysinfo() -> Result<sysinfo_t> {
     let info = sysinfo_t {
-        uptime: time::up_time::get().unwrap().as_secs() as i64, // Duration can't be negative
+        uptime: time::up_time::get().as_secs() as i64,
         totalram: USER_SPACE_VM_MANAGER.get_total_size() as u64,
         freeram: USER_SPACE_VM_MANAGER.get_free_size() as u64,
         procs: table::get_all_processes().len() as u16,
@@ -0,0 +1,57 @@
+//! The address families of the sockets that applications may use, which may be
+//! restricted by the config.
+//!
+//! A socket of a family that is not allowed cannot be created. A dual-stack IPv6
+//! socket carries the IPv4 traffic as well, with the IPv4 addresses mapped into
+//! IPv6 (i.e., ::ffff:a.b.c.d). So if IPv6 is allowed but IPv4 is not, the IPv6
+//! sockets are made IPv6-only, and the IPv4-mapped addresses are neither taken
+//! from the user nor given by the host.
+use super::egress_limit::parse_sockaddr_ip;
+use super::*;
+use crate::fs::{report_security_event, SecurityEventKind};
+use std::net::IpAddr;
+
+pub fn is_family_allowed(domain: c_int) -> bool {
+    match &config::LIBOS_CONFIG.network.address_families {
+        Some(families) => families.contains(&domain),
+        None => true,
+    }
+}
+
+/// Check the family of a socket to be created
+pub fn check_socket_family(domain: c_int) -> Result<()> {
+    if !is_family_allowed(domain) {
+        let detail = format!("socket of address family {}", domain);
+        report_security_event(SecurityEventKind::NetDenied, &detail);
+        return_errno!(EAFNOSUPPORT, "the address family is not allowed");
+    }
+    Ok(())
+}
+
+/// Whether the IPv6 sockets must be IPv6-only, as IPv4 is not allowed
+pub fn is_ipv6_only() -> bool {
+    is_family_allowed(libc::AF_INET6) && !is_family_allowed(libc::AF_INET)
+}
+
+/// Check the address given by the user to a socket of `domain`, e.g., to bind,
+/// connect or send to
+pub fn check_user_addr(domain: c_int, addr: &[u8]) -> Result<()> {
+    if domain != libc::AF_INET6 || !is_ipv6_only() {
+        return Ok(());
+    }
+    if let Some(IpAddr::V4(ip)) = parse_sockaddr_ip(addr) {
+        let detail = format!("IPv4-mapped address {}", ip);
+        report_security_event(SecurityEventKind::NetDenied, &detail);
+        return_errno!(EAFNOSUPPORT, "IPv4 is not allowed by the config");
+    }
+    Ok(())
+}
+
+/// Whether the address given by the host, e.g., the peer of an accepted
+/// connection, is allowed
+pub fn is_host_addr_allowed(addr: &SockAddr) -> bool {
+    match addr.ip() {
+        Some(IpAddr::V4(_)) => is_family_allowed(libc::AF_INET),
+        _ => true,
+    }
+}
@@ -0,0 +1,260 @@
+//! The asynchronous OCalls of the sends and receives of host sockets.
+//!
+//! An OCall exits the enclave, which costs far more than a small send or receive
+//! itself. If configured, the requests are passed to the host through a queue in
+//! the untrusted memory instead, and are served by dedicated host threads that are
+//! started by the PAL, like the switchless OCalls of the SGX SDK:
+//!
+//! * A thread submits a request by putting its index into the queue, and spins
+//! until the request is done. If it takes long, the thread is parked in the host
+//! until the worker that serves the request wakes it up.
+//! * A worker spins on the queue for a while before it sleeps, and is woken up by
+//! an OCall only if the workers are sleeping.
+//!
+//! The workers never block on a socket, as the requests are done with MSG_DONTWAIT.
+//! The callers wait for the socket to be ready in the enclave if a request would
+//! block, so that the waits can be interrupted and the timeouts of the sockets are
+//! honored. So a few workers are enough for any number of sockets.
+//!
+//! The host can read and write the queue at will, so nothing in the queue is
+//! trusted: the index of the next submission is kept in the enclave, and the
+//! results of the requests are checked before returned.
+//!
+//! The layout of the queue must be kept in sync with pal/src/pal_async_io.h.
+use super::*;
+use crate::untrusted::{UntrustedChunk, UNTRUSTED_ARENA};
+use std::ptr;
+use std::sync::atomic::{spin_loop_hint, AtomicU32, Ordering};
+
+/// The max number of the requests in flight, which must be a power of two
+const QUEUE_DEPTH: usize = 256;
+/// The max length of the data of a request, which is served by the untrusted
+/// arena without OCalls
+pub const ASYNC_IO_MAX_LEN: usize = 64 * 1024;
+/// The rounds to spin for a request to complete before the thread is parked
+const SPIN_ROUNDS: usize = 4096;
+
+// The states of a request
+const STATE_IDLE: u32 = 0;
+const STATE_SUBMITTED: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u32)]
+pub enum AsyncIoOp {
+    Recv = 1,
+    Send = 2,
+}
+
+#[repr(C)]
+struct AsyncIoRequest {
+    state: AtomicU32,
+    // If the submitter is parked on `state`
+    is_parked: AtomicU32,
+    op: u32,
+    fd: i32,
+    flags: i32,
+    _padding: u32,
+    buf: u64,
+    len: u64,
+    // The bytes sent or received, or -errno on error
+    result: i64,
+}
+
+#[repr(C)]
+struct AsyncIoQueue {
+    // The number of the requests submitted, which wraps around
+    tail: AtomicU32,
+    // The number of the requests taken by the workers, which wraps around
+    head: AtomicU32,
+    // The number of the workers sleeping on `tail`
+    num_sleeping: AtomicU32,
+    // Set by the PAL to stop the workers
+    stop: AtomicU32,
+    // The indices of the requests submitted, in the order of submission
+    submissions: [u32; QUEUE_DEPTH],
+    requests: [AsyncIoRequest; QUEUE_DEPTH],
+}
+
+pub struct AsyncIoEngine {
+    // The untrusted memory of the queue
+    chunk: UntrustedChunk,
+    // The indices of the requests not in flight
+    free_requests: SgxMutex<Vec<u32>>,
+    // The number of the requests submitted, which is the copy of the tail of the
+    // queue in the enclave. The lock also serializes the submissions.
+    tail: SgxMutex<u32>,
+}
+
+unsafe impl Send for AsyncIoEngine {}
+unsafe impl Sync for AsyncIoEngine {}
+
+lazy_static! {
+    static ref ASYNC_IO_ENGINE: Option<AsyncIoEngine> = match AsyncIoEngine::new() {
+        Ok(engine) => engine,
+        Err(e) => {
+            warn!("failed to start the async I/O of sockets: {:?}", e);
+            None
+        }
+    };
+}
+
+/// The engine of the asynchronous OCalls, which is None if it is not configured.
+///
+/// The results of the requests are not seen by the syscall trace, so the engine is
+/// disabled while the syscalls are traced or replayed.
+pub fn async_io_engine() -> Option<&'static AsyncIoEngine> {
+    if cfg!(any(feature = "syscall_trace", feature = "syscall_replay")) {
+        return None;
+    }
+    ASYNC_IO_ENGINE.as_ref()
+}
+
+impl AsyncIoEngine {
+    fn new() -> Result<Option<Self>> {
+        let config = match &config::LIBOS_CONFIG.network.async_io {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        let chunk = UNTRUSTED_ARENA.alloc(std::mem::size_of::<AsyncIoQueue>())?;
+        if chunk.as_mut_ptr() as usize % std::mem::align_of::<AsyncIoQueue>() != 0 {
+            return_errno!(EINVAL, "the queue of async I/O is not aligned");
+        }
+        // All of the requests are idle
+        unsafe { ptr::write_bytes(chunk.as_mut_ptr(), 0, chunk.size()) };
+
+        let mut ret = 0;
+        let status = unsafe {
+            occlum_ocall_async_io_start(
+                &mut ret,
+                chunk.as_mut_ptr() as *mut c_void,
+                config.num_threads as c_int,
+            )
+        };
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        if ret < 0 {
+            return_errno!(Errno::from(unsafe { libc::errno() } as u32), "libc error");
+        }
+        info!(
+            "async I/O of sockets is started with {} threads",
+            config.num_threads
+        );
+        Ok(Some(Self {
+            chunk,
+            free_requests: SgxMutex::new((0..QUEUE_DEPTH as u32).rev().collect()),
+            tail: SgxMutex::new(0),
+        }))
+    }
+
+    /// Do a request on the host socket `fd` with the data in `buf`, which is in the
+    /// untrusted memory, and park the current thread until it is done. Return the
+    /// bytes sent or received, or None if the queue is full.
+    pub fn execute(
+        &self,
+        op: AsyncIoOp,
+        fd: c_int,
+        buf: &UntrustedChunk,
+        len: usize,
+        flags: c_int,
+    ) -> Option<Result<usize>> {
+        debug_assert!(len <= min(buf.size(), ASYNC_IO_MAX_LEN));
+        let idx = self.free_requests.lock().unwrap().pop()?;
+        let request = self.request(idx);
+        unsafe {
+            let request = request as *const AsyncIoRequest as *mut AsyncIoRequest;
+            ptr::write_volatile(&mut (*request).op, op as u32);
+            ptr::write_volatile(&mut (*request).fd, fd);
+            ptr::write_volatile(&mut (*request).flags, flags);
+            ptr::write_volatile(&mut (*request).buf, buf.as_mut_ptr() as u64);
+            ptr::write_volatile(&mut (*request).len, len as u64);
+        }
+        request.is_parked.store(0, Ordering::SeqCst);
+        request.state.store(STATE_SUBMITTED, Ordering::SeqCst);
+        self.submit(idx);
+
+        let result = self.wait_for_completion(request);
+        request.state.store(STATE_IDLE, Ordering::SeqCst);
+        self.free_requests.lock().unwrap().push(idx);
+        Some(check_result(result, len))
+    }
+
+    fn submit(&self, idx: u32) {
+        let queue = self.queue();
+        {
+            let mut tail = self.tail.lock().unwrap();
+            let slot = &queue.submissions[*tail as usize % QUEUE_DEPTH];
+            unsafe { ptr::write_volatile(slot as *const u32 as *mut u32, idx) };
+            *tail = tail.wrapping_add(1);
+            queue.tail.store(*tail, Ordering::SeqCst);
+        }
+        // The worker that goes to sleep after the check is sure to see the new tail
+        if queue.num_sleeping.load(Ordering::SeqCst) > 0 {
+            current!().sched_stat().account_exit();
+            let status =
+                unsafe { occlum_ocall_async_io_wake(self.chunk.as_mut_ptr() as *mut c_void) };
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+        }
+    }
+
+    /// Spin until the request is done, and park the current thread if it takes long
+    fn wait_for_completion(&self, request: &AsyncIoRequest) -> i64 {
+        // The request is done once the worker changes the state, which is
+        // STATE_DONE unless the host is malicious
+        let is_done = || request.state.load(Ordering::SeqCst) != STATE_SUBMITTED;
+        for _ in 0..SPIN_ROUNDS {
+            if is_done() {
+                break;
+            }
+            spin_loop_hint();
+        }
+        while !is_done() {
+            request.is_parked.store(1, Ordering::SeqCst);
+            // The worker that completes the request after the check is sure to see
+            // that the thread is parked
+            if is_done() {
+                break;
+            }
+            let state_ptr = &request.state as *const AtomicU32 as *mut u32;
+            let status = crate::sched::account_wait(|| unsafe {
+                occlum_ocall_async_io_wait(state_ptr, STATE_SUBMITTED)
+            });
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+        }
+        unsafe { ptr::read_volatile(&request.result) }
+    }
+
+    fn queue(&self) -> &AsyncIoQueue {
+        unsafe { &*(self.chunk.as_mut_ptr() as *const AsyncIoQueue) }
+    }
+
+    fn request(&self, idx: u32) -> &AsyncIoRequest {
+        &self.queue().requests[idx as usize]
+    }
+}
+
+/// Check the result of a request of `len` bytes given by the host
+fn check_result(result: i64, len: usize) -> Result<usize> {
+    if result >= 0 {
+        if result as u64 > len as u64 {
+            return_errno!(
+                EIO,
+                "the length of async I/O given by the host is too large"
+            );
+        }
+        return Ok(result as usize);
+    }
+    let errno = result
+        .checked_neg()
+        .filter(|&errno| errno <= Errno::EHWPOISON as i64)
+        .ok_or_else(|| errno!(EIO, "invalid errno of async I/O given by the host"))?;
+    return_errno!(Errno::from(errno as u32), "async I/O error")
+}
+
+extern "C" {
+    fn occlum_ocall_async_io_start(
+        ret: *mut c_int,
+        queue: *mut c_void,
+        num_threads: c_int,
+    ) -> sgx_status_t;
+    fn occlum_ocall_async_io_wake(queue: *mut c_void) -> sgx_status_t;
+    fn occlum_ocall_async_io_wait(state: *mut u32, expected: u32) -> sgx_status_t;
+}
@@ -0,0 +1,237 @@
+//! Control messages, i.e., the ancillary data of sendmsg and recvmsg.
+//!
+//! SCM_RIGHTS and SCM_CREDENTIALS pass fds and the credentials of the sender
+//! between the processes connected by a unix socket. SCM_TIMESTAMP and
+//! SCM_TIMESTAMPNS give the times when the messages are received, if enabled by
+//! SO_TIMESTAMP or SO_TIMESTAMPNS.
+//!
+//! The control messages of host sockets are given by the host, which are checked
+//! before they are returned to the user.
+use super::sockopt::{SO_TIMESTAMP, SO_TIMESTAMPNS};
+use super::*;
+use std::mem::size_of;
+use std::ptr;
+use std::time::Duration;
+
+/// C struct for the header of a control message, whose layout is the same as the
+/// one of musl
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+#[allow(non_camel_case_types)]
+struct cmsghdr {
+    cmsg_len: u32,
+    __pad1: u32,
+    cmsg_level: c_int,
+    cmsg_type: c_int,
+}
+
+const CMSG_HDR_LEN: usize = size_of::<cmsghdr>();
+const SCM_RIGHTS: c_int = 1;
+const SCM_CREDENTIALS: c_int = 2;
+const SCM_TIMESTAMP: c_int = SO_TIMESTAMP;
+const SCM_TIMESTAMPNS: c_int = SO_TIMESTAMPNS;
+// Both struct timeval and struct timespec are two longs
+const TIMESTAMP_LEN: usize = 2 * size_of::<i64>();
+// The max number of fds passed by a sendmsg, which is the same as Linux
+const SCM_MAX_FD: usize = 253;
+
+/// C struct for the credentials of SCM_CREDENTIALS and SO_PEERCRED
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[allow(non_camel_case_types)]
+pub struct ucred {
+    pub pid: pid_t,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// The format of the receive timestamps, which is given by SO_TIMESTAMP or
+/// SO_TIMESTAMPNS
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimestampFormat {
+    /// SCM_TIMESTAMP with a struct timeval
+    Timeval,
+    /// SCM_TIMESTAMPNS with a struct timespec
+    Timespec,
+}
+
+impl TimestampFormat {
+    pub fn from_sockopt(optname: c_int) -> Option<Self> {
+        match optname {
+            SO_TIMESTAMP => Some(TimestampFormat::Timeval),
+            SO_TIMESTAMPNS => Some(TimestampFormat::Timespec),
+            _ => None,
+        }
+    }
+}
+
+/// Get the fds in the SCM_RIGHTS control messages, and the credentials in the
+/// SCM_CREDENTIALS control message if any.
+pub fn parse_control_msgs(control: &[u8]) -> Result<(Vec<FileDesc>, Option<ucred>)> {
+    let mut fds = Vec::new();
+    let mut cred = None;
+    let mut offset = 0;
+    while offset + CMSG_HDR_LEN <= control.len() {
+        let hdr = unsafe { ptr::read_unaligned(control[offset..].as_ptr() as *const cmsghdr) };
+        let cmsg_len = hdr.cmsg_len as usize;
+        if cmsg_len < CMSG_HDR_LEN || cmsg_len > control.len() - offset {
+            return_errno!(EINVAL, "invalid length of control message");
+        }
+        if hdr.cmsg_level != libc::SOL_SOCKET {
+            return_errno!(EINVAL, "only SOL_SOCKET control messages are supported");
+        }
+        let data = &control[offset + CMSG_HDR_LEN..offset + cmsg_len];
+        match hdr.cmsg_type {
+            SCM_RIGHTS => {
+                for fd in data.chunks_exact(size_of::<c_int>()) {
+                    fds.push(c_int::from_ne_bytes([fd[0], fd[1], fd[2], fd[3]]) as FileDesc);
+                }
+            }
+            SCM_CREDENTIALS => {
+                if data.len() != size_of::<ucred>() {
+                    return_errno!(EINVAL, "invalid length of SCM_CREDENTIALS");
+                }
+                cred = Some(unsafe { ptr::read_unaligned(data.as_ptr() as *const ucred) });
+            }
+            _ => return_errno!(EINVAL, "unsupported type of control message"),
+        }
+        offset += cmsg_align(cmsg_len);
+    }
+    if fds.len() > SCM_MAX_FD {
+        return_errno!(EINVAL, "too many fds in control messages");
+    }
+    Ok((fds, cred))
+}
+
+/// Get the max number of fds that a SCM_RIGHTS control message of the buffer
+/// can hold.
+pub fn scm_rights_capacity(control_len: usize) -> usize {
+    control_len.saturating_sub(CMSG_HDR_LEN) / size_of::<c_int>()
+}
+
+/// Write the fds as a SCM_RIGHTS control message, and return the length of the
+/// control message.
+///
+/// The fds must fit in the buffer, see `scm_rights_capacity`.
+pub fn write_scm_rights(control: &mut [u8], fds: &[FileDesc]) -> usize {
+    if fds.is_empty() {
+        return 0;
+    }
+    debug_assert!(fds.len() <= scm_rights_capacity(control.len()));
+    let cmsg_len = CMSG_HDR_LEN + fds.len() * size_of::<c_int>();
+    let hdr = cmsghdr {
+        cmsg_len: cmsg_len as u32,
+        __pad1: 0,
+        cmsg_level: libc::SOL_SOCKET,
+        cmsg_type: SCM_RIGHTS,
+    };
+    unsafe { ptr::write_unaligned(control.as_mut_ptr() as *mut cmsghdr, hdr) };
+    for (fd, buf) in fds
+        .iter()
+        .zip(control[CMSG_HDR_LEN..].chunks_exact_mut(size_of::<c_int>()))
+    {
+        buf.copy_from_slice(&(*fd as c_int).to_ne_bytes());
+    }
+    min(cmsg_align(cmsg_len), control.len())
+}
+
+/// Write the credentials as a SCM_CREDENTIALS control message, and return the
+/// length of the control message, which is zero if it does not fit in the buffer.
+pub fn write_scm_credentials(control: &mut [u8], cred: &ucred) -> usize {
+    let cmsg_len = CMSG_HDR_LEN + size_of::<ucred>();
+    if cmsg_len > control.len() {
+        return 0;
+    }
+    let hdr = cmsghdr {
+        cmsg_len: cmsg_len as u32,
+        __pad1: 0,
+        cmsg_level: libc::SOL_SOCKET,
+        cmsg_type: SCM_CREDENTIALS,
+    };
+    unsafe {
+        ptr::write_unaligned(control.as_mut_ptr() as *mut cmsghdr, hdr);
+        ptr::write_unaligned(control[CMSG_HDR_LEN..].as_mut_ptr() as *mut ucred, *cred);
+    }
+    min(cmsg_align(cmsg_len), control.len())
+}
+
+/// Write the time as a SCM_TIMESTAMP or SCM_TIMESTAMPNS control message, and
+/// return the length of the control message, which is zero if it does not fit in
+/// the buffer.
+pub fn write_scm_timestamp(control: &mut [u8], format: TimestampFormat, time: Duration) -> usize {
+    let cmsg_len = CMSG_HDR_LEN + TIMESTAMP_LEN;
+    if cmsg_len > control.len() {
+        return 0;
+    }
+    let (cmsg_type, subsec) = match format {
+        TimestampFormat::Timeval => (SCM_TIMESTAMP, time.subsec_micros() as i64),
+        TimestampFormat::Timespec => (SCM_TIMESTAMPNS, time.subsec_nanos() as i64),
+    };
+    let hdr = cmsghdr {
+        cmsg_len: cmsg_len as u32,
+        __pad1: 0,
+        cmsg_level: libc::SOL_SOCKET,
+        cmsg_type,
+    };
+    let timestamp = [time.as_secs() as i64, subsec];
+    unsafe {
+        ptr::write_unaligned(control.as_mut_ptr() as *mut cmsghdr, hdr);
+        ptr::write_unaligned(
+            control[CMSG_HDR_LEN..].as_mut_ptr() as *mut [i64; 2],
+            timestamp,
+        );
+    }
+    min(cmsg_align(cmsg_len), control.len())
+}
+
+/// Check the control messages of a host socket given by the host.
+///
+/// The fds and the credentials of the host are meaningless in the enclave, so they
+/// are never expected. The timestamps must be valid times. Like Linux, the last
+/// control message is cut short to the buffer if `is_truncated`, i.e., with
+/// MSG_CTRUNC, whose data is not checked then.
+pub fn check_host_control_msgs(control: &[u8], is_truncated: bool) -> Result<()> {
+    let mut offset = 0;
+    while offset + CMSG_HDR_LEN <= control.len() {
+        let hdr = unsafe { ptr::read_unaligned(control[offset..].as_ptr() as *const cmsghdr) };
+        let cmsg_len = hdr.cmsg_len as usize;
+        if cmsg_len < CMSG_HDR_LEN || cmsg_len > control.len() - offset {
+            return_errno!(EIO, "invalid length of control message from the host");
+        }
+        let data = &control[offset + CMSG_HDR_LEN..offset + cmsg_len];
+        offset += cmsg_align(cmsg_len);
+        if hdr.cmsg_level != libc::SOL_SOCKET {
+            continue;
+        }
+        match hdr.cmsg_type {
+            SCM_RIGHTS | SCM_CREDENTIALS => {
+                return_errno!(EIO, "unexpected fds or credentials from the host");
+            }
+            SCM_TIMESTAMP | SCM_TIMESTAMPNS => {
+                let is_cut_short = is_truncated && offset >= control.len();
+                if data.len() != TIMESTAMP_LEN {
+                    if is_cut_short && data.len() < TIMESTAMP_LEN {
+                        continue;
+                    }
+                    return_errno!(EIO, "invalid length of timestamp from the host");
+                }
+                let [sec, subsec] =
+                    unsafe { ptr::read_unaligned(data.as_ptr() as *const [i64; 2]) };
+                let max_subsec = if hdr.cmsg_type == SCM_TIMESTAMP {
+                    1_000_000
+                } else {
+                    1_000_000_000
+                };
+                if sec < 0 || subsec < 0 || subsec >= max_subsec {
+                    return_errno!(EIO, "invalid timestamp from the host");
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn cmsg_align(len: usize) -> usize {
+    align_up(len, size_of::<usize>())
+}
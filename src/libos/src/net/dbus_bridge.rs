@@ -0,0 +1,423 @@
+//! The bridges from the abstract unix sockets to the D-Bus services of the host.
+//!
+//! The unix sockets in the enclave are not visible to the host. If an abstract
+//! name is configured as a bridge in Occlum.json, connecting to the name connects
+//! to the unix socket of the host instead, e.g., the socket of the system bus. The
+//! messages sent by the enclave are filtered by the interfaces in the allowlist
+//! of the bridge, so that the enclave can only ask the host services to do what
+//! is declared explicitly. The messages from the host are untrusted data, which
+//! are passed through as they are.
+//!
+//! The authentication of D-Bus is passed through, e.g., EXTERNAL, which is done
+//! with the credentials of the host process. Only the commands that a client may
+//! send are allowed, and any line whose command is BEGIN ends the authentication,
+//! as the bus daemon does. The files cannot be passed over the bridges, so the
+//! messages with UNIX_FDS are refused.
+use super::*;
+use crate::config::ConfigDbusBridge;
+use crate::fs::{report_security_event, SecurityEventKind};
+use std::fmt;
+
+/// The max length of a message, which is the same as the D-Bus specification
+const MAX_MESSAGE_LEN: usize = 128 * 1024 * 1024;
+/// The max length of a line of the authentication
+const MAX_AUTH_LINE_LEN: usize = 16 * 1024;
+/// The commands of the authentication that the client may send besides BEGIN
+const AUTH_COMMANDS: [&[u8]; 5] = [b"AUTH", b"CANCEL", b"DATA", b"ERROR", b"NEGOTIATE_UNIX_FD"];
+/// The methods of the bus that are always allowed, which are needed to use the bus
+const BUS_INTERFACE: &str = "org.freedesktop.DBus";
+const BUS_METHODS: [&str; 3] = ["Hello", "AddMatch", "RemoveMatch"];
+
+// The types of messages
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+
+// The codes of header fields
+const FIELD_INTERFACE: u8 = 2;
+const FIELD_MEMBER: u8 = 3;
+const FIELD_UNIX_FDS: u8 = 9;
+
+/// Find the bridge of an abstract name, which is given with a leading '@'
+pub fn find_bridge(name: &str) -> Option<&'static ConfigDbusBridge> {
+    config::LIBOS_CONFIG
+        .network
+        .dbus_bridges
+        .iter()
+        .find(|bridge| {
+            if bridge.name.ends_with('*') {
+                name.starts_with(&bridge.name[..bridge.name.len() - 1])
+            } else {
+                name == bridge.name
+            }
+        })
+}
+
+pub struct DbusBridge {
+    host_fd: c_int,
+    policy: &'static ConfigDbusBridge,
+    // The data sent but not forwarded yet, which is an incomplete line of the
+    // authentication or an incomplete message
+    pending: Vec<u8>,
+    // Whether the byte of credentials, which is the first byte, is forwarded
+    is_cred_sent: bool,
+    // Whether BEGIN is sent, after which the data is the binary messages
+    is_auth_done: bool,
+}
+
+impl DbusBridge {
+    /// Connect to the unix socket of the host given by the bridge
+    pub fn connect(policy: &'static ConfigDbusBridge) -> Result<Self> {
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        let path = policy.host_path.as_bytes();
+        // The length is checked when the config is loaded
+        for (dst, &src) in addr.sun_path.iter_mut().zip(path.iter()) {
+            *dst = src as c_char;
+        }
+        let host_fd = try_libc!(libc::ocall::socket(
+            libc::AF_UNIX,
+            libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+            0
+        ));
+        let bridge = Self {
+            host_fd,
+            policy,
+            pending: Vec::new(),
+            is_cred_sent: false,
+            is_auth_done: false,
+        };
+        try_libc!(libc::ocall::connect(
+            host_fd,
+            &addr as *const _ as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t
+        ));
+        info!("connected to the D-Bus bridge: {}", policy.name);
+        Ok(bridge)
+    }
+
+    pub fn host_fd(&self) -> c_int {
+        self.host_fd
+    }
+
+    /// Send the data, where the messages denied by the policy are dropped with
+    /// EACCES. The data of an incomplete message is kept until it is complete.
+    pub fn writev(&mut self, bufs: &[&[u8]]) -> Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        for buf in bufs {
+            self.pending.extend_from_slice(buf);
+        }
+        self.forward()?;
+        Ok(len)
+    }
+
+    /// Receive the data without blocking, which fails with EAGAIN if there is no
+    /// data. The callers wait on the host fd for blocking reads.
+    pub fn readv(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        let mut total_len = 0;
+        for buf in bufs.iter_mut().filter(|buf| !buf.is_empty()) {
+            let ret = unsafe {
+                libc::ocall::recvfrom(
+                    self.host_fd,
+                    buf.as_mut_ptr() as *mut c_void,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                )
+            };
+            if ret < 0 {
+                if total_len > 0 {
+                    break;
+                }
+                return_errno!(Errno::from(unsafe { libc::errno() } as u32), "recv error");
+            }
+            let len = ret as usize;
+            if len > buf.len() {
+                return_errno!(EIO, "invalid length of the data received");
+            }
+            total_len += len;
+            if len < buf.len() {
+                break;
+            }
+        }
+        Ok(total_len)
+    }
+
+    /// Forward the complete lines or messages that are allowed
+    fn forward(&mut self) -> Result<()> {
+        loop {
+            let len = if !self.is_cred_sent {
+                match self.pending.first() {
+                    Some(0) => 1,
+                    Some(_) => return_errno!(EPROTO, "the byte of credentials is not zero"),
+                    None => return Ok(()),
+                }
+            } else if !self.is_auth_done {
+                match self.auth_line_len()? {
+                    Some(len) => len,
+                    None => return Ok(()),
+                }
+            } else {
+                match message_len(&self.pending)? {
+                    Some(len) => len,
+                    None => return Ok(()),
+                }
+            };
+
+            let data: Vec<u8> = self.pending.drain(..len).collect();
+            if !self.is_cred_sent {
+                self.is_cred_sent = true;
+            } else if !self.is_auth_done {
+                match auth_command(&data) {
+                    Ok(command) => self.is_auth_done = command == b"BEGIN",
+                    Err(e) => {
+                        warn!("the D-Bus authentication is denied: {:?}", e);
+                        return Err(e);
+                    }
+                }
+            } else if let Err(e) = self.check_message(&data) {
+                warn!("the D-Bus message is denied: {:?}", e);
+                return Err(e);
+            }
+            self.send_to_host(&data)?;
+        }
+    }
+
+    fn auth_line_len(&self) -> Result<Option<usize>> {
+        match self.pending.windows(2).position(|bytes| bytes == b"\r\n") {
+            Some(pos) => Ok(Some(pos + 2)),
+            None if self.pending.len() > MAX_AUTH_LINE_LEN => {
+                return_errno!(EPROTO, "the line of the authentication is too long")
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn check_message(&self, message: &[u8]) -> Result<()> {
+        let header = MessageHeader::parse(message)?;
+        if header.has_unix_fds {
+            report_security_event(SecurityEventKind::NetDenied, "D-Bus message with files");
+            return_errno!(EACCES, "the files cannot be passed to the host");
+        }
+        match header.type_ {
+            METHOD_RETURN | ERROR => Ok(()),
+            METHOD_CALL | SIGNAL => {
+                let interface = header
+                    .interface
+                    .ok_or_else(|| errno!(EACCES, "the interface is not given"))?;
+                let member = header.member.unwrap_or("");
+                if self.policy.interfaces.contains(interface)
+                    || (interface == BUS_INTERFACE && BUS_METHODS.contains(&member))
+                {
+                    Ok(())
+                } else {
+                    let detail = format!("D-Bus message to {}.{}", interface, member);
+                    report_security_event(SecurityEventKind::NetDenied, &detail);
+                    return_errno!(EACCES, "the interface is not allowed")
+                }
+            }
+            _ => return_errno!(EACCES, "unknown type of message"),
+        }
+    }
+
+    fn send_to_host(&self, data: &[u8]) -> Result<()> {
+        let mut sent = 0;
+        while sent < data.len() {
+            let ret = try_libc!(libc::ocall::sendto(
+                self.host_fd,
+                data[sent..].as_ptr() as *const c_void,
+                data.len() - sent,
+                libc::MSG_NOSIGNAL,
+                std::ptr::null(),
+                0
+            )) as usize;
+            if ret == 0 || ret > data.len() - sent {
+                return_errno!(EIO, "invalid length of the data sent");
+            }
+            sent += ret;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DbusBridge {
+    fn drop(&mut self) {
+        unsafe { libc::ocall::close(self.host_fd) };
+    }
+}
+
+impl Debug for DbusBridge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DbusBridge")
+            .field("host_fd", &self.host_fd)
+            .field("name", &self.policy.name)
+            .field("pending_len", &self.pending.len())
+            .finish()
+    }
+}
+
+/// Get the command of a complete line of the authentication, which must be BEGIN
+/// or one of the allowed commands
+fn auth_command(line: &[u8]) -> Result<&[u8]> {
+    // Like the bus daemon, the command ends at the first blank
+    let command = line[..line.len() - 2]
+        .split(|&byte| byte == b' ' || byte == b'\t')
+        .next()
+        .unwrap_or_default();
+    if command != b"BEGIN" && !AUTH_COMMANDS.contains(&command) {
+        report_security_event(SecurityEventKind::NetDenied, "D-Bus authentication command");
+        return_errno!(EACCES, "the command of the authentication is not allowed");
+    }
+    Ok(command)
+}
+
+/// Get the length of the message at the beginning of the data, or None if the
+/// message is incomplete
+fn message_len(data: &[u8]) -> Result<Option<usize>> {
+    const FIXED_HEADER_LEN: usize = 16;
+    if data.len() < FIXED_HEADER_LEN {
+        return Ok(None);
+    }
+    let reader = Reader::new(data)?;
+    if data[3] != 1 {
+        return_errno!(EPROTO, "unknown version of D-Bus");
+    }
+    let body_len = reader.u32_at(4) as usize;
+    let fields_len = reader.u32_at(12) as usize;
+    let len = align_up(FIXED_HEADER_LEN + fields_len, 8) + body_len;
+    if fields_len > MAX_MESSAGE_LEN || body_len > MAX_MESSAGE_LEN || len > MAX_MESSAGE_LEN {
+        return_errno!(EMSGSIZE, "the message is too long");
+    }
+    if data.len() < len {
+        return Ok(None);
+    }
+    Ok(Some(len))
+}
+
+struct MessageHeader<'a> {
+    type_: u8,
+    interface: Option<&'a str>,
+    member: Option<&'a str>,
+    has_unix_fds: bool,
+}
+
+impl<'a> MessageHeader<'a> {
+    /// Parse the header of a complete message
+    fn parse(message: &'a [u8]) -> Result<Self> {
+        let reader = Reader::new(message)?;
+        let mut header = Self {
+            type_: message[1],
+            interface: None,
+            member: None,
+            has_unix_fds: false,
+        };
+        let fields_end = 16 + reader.u32_at(12) as usize;
+        let mut offset = 16;
+        while offset < fields_end {
+            // A field is a struct of the code and a variant, which is aligned to 8
+            offset = align_up(offset, 8);
+            if offset >= fields_end {
+                break;
+            }
+            let code = reader.u8_at(offset)?;
+            // The signature of the variant, which is a single type
+            if reader.u8_at(offset + 1)? != 1 || reader.u8_at(offset + 3)? != 0 {
+                return_errno!(EPROTO, "invalid header field");
+            }
+            let type_ = reader.u8_at(offset + 2)?;
+            offset += 4;
+            match type_ {
+                b's' | b'o' => {
+                    offset = align_up(offset, 4);
+                    let len = reader.u32_at_checked(offset)? as usize;
+                    let value = reader.str_at(offset + 4, len)?;
+                    offset += 4 + len + 1;
+                    match code {
+                        FIELD_INTERFACE => header.interface = Some(value),
+                        FIELD_MEMBER => header.member = Some(value),
+                        _ => {}
+                    }
+                }
+                b'g' => {
+                    let len = reader.u8_at(offset)? as usize;
+                    reader.str_at(offset + 1, len)?;
+                    offset += 1 + len + 1;
+                }
+                b'u' => {
+                    offset = align_up(offset, 4);
+                    let value = reader.u32_at_checked(offset)?;
+                    offset += 4;
+                    if code == FIELD_UNIX_FDS && value != 0 {
+                        header.has_unix_fds = true;
+                    }
+                }
+                _ => return_errno!(EPROTO, "unknown type of header field"),
+            }
+            if offset > fields_end {
+                return_errno!(EPROTO, "the header field is out of the header");
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// The reader of the integers and strings of a message in its byte order
+struct Reader<'a> {
+    data: &'a [u8],
+    is_big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let is_big_endian = match data.first() {
+            Some(b'l') => false,
+            Some(b'B') => true,
+            _ => return_errno!(EPROTO, "unknown byte order of D-Bus"),
+        };
+        Ok(Self {
+            data,
+            is_big_endian,
+        })
+    }
+
+    fn u8_at(&self, offset: usize) -> Result<u8> {
+        self.data
+            .get(offset)
+            .cloned()
+            .ok_or_else(|| errno!(EPROTO, "the message is truncated"))
+    }
+
+    /// Read an u32 that is known to be in the data
+    fn u32_at(&self, offset: usize) -> u32 {
+        let bytes = [
+            self.data[offset],
+            self.data[offset + 1],
+            self.data[offset + 2],
+            self.data[offset + 3],
+        ];
+        if self.is_big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32_at_checked(&self, offset: usize) -> Result<u32> {
+        if offset + 4 > self.data.len() {
+            return_errno!(EPROTO, "the message is truncated");
+        }
+        Ok(self.u32_at(offset))
+    }
+
+    /// Read a string of `len` bytes, which is followed by a NUL
+    fn str_at(&self, offset: usize, len: usize) -> Result<&'a str> {
+        let end = offset
+            .checked_add(len)
+            .filter(|&end| end < self.data.len() && self.data[end] == 0)
+            .ok_or_else(|| errno!(EPROTO, "invalid string in the message"))?;
+        std::str::from_utf8(&self.data[offset..end])
+            .map_err(|_| errno!(EPROTO, "invalid string in the message"))
+    }
+}
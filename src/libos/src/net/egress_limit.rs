@@ -0,0 +1,185 @@
+//! The rate limits of the data sent by host sockets.
+//!
+//! Each limit in the config is a token bucket, which is shared by all the sockets
+//! that send data to the addresses in its CIDR block, or by all the processes of
+//! its executable. A send is allowed as long as there are tokens left in the
+//! buckets of the matched limits, after which the bucket may be in debt by up to
+//! the length of the data. So datagrams are never split or dropped to conform to
+//! a limit. Instead, the following sends wait until the debt is paid off, or fail
+//! with EAGAIN if the socket is non-blocking.
+use super::sock_addr::unmap_ipv6;
+use super::*;
+use crate::config::{ConfigEgressLimit, ConfigEgressTarget};
+use crate::time::{do_clock_gettime, do_nanosleep, timespec_t, ClockID};
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
+lazy_static! {
+    static ref EGRESS_LIMITS: Vec<(&'static ConfigEgressTarget, SgxMutex<TokenBucket>)> =
+        config::LIBOS_CONFIG
+            .network
+            .egress_limits
+            .iter()
+            .map(|limit| (&limit.target, SgxMutex::new(TokenBucket::new(limit))))
+            .collect();
+}
+
+/// Check whether any limit applies to the data sent to addresses
+pub fn has_addr_egress_limits() -> bool {
+    EGRESS_LIMITS.iter().any(|(target, _)| match target {
+        ConfigEgressTarget::Cidr { .. } => true,
+        ConfigEgressTarget::Process(_) => false,
+    })
+}
+
+/// Wait until the data of `len` bytes can be sent to `dest` by the current process
+/// without exceeding the limits.
+///
+/// The `is_nonblocking` closure is called only if the data has to wait.
+pub fn throttle_egress<F>(dest: Option<IpAddr>, len: usize, is_nonblocking: F) -> Result<()>
+where
+    F: FnOnce() -> Result<bool>,
+{
+    if EGRESS_LIMITS.is_empty() || len == 0 {
+        return Ok(());
+    }
+    let current = current!();
+    let exec_path = current.process().exec_path();
+    let mut is_nonblocking = Some(is_nonblocking);
+    let mut nonblocking = false;
+    for (target, bucket) in EGRESS_LIMITS.iter() {
+        let is_matched = match target {
+            ConfigEgressTarget::Cidr { addr, prefix_len } => match dest {
+                Some(dest) => is_in_cidr(&dest, addr, *prefix_len),
+                None => false,
+            },
+            ConfigEgressTarget::Process(path) => path.as_path() == std::path::Path::new(exec_path),
+        };
+        if !is_matched {
+            continue;
+        }
+        loop {
+            let wait_time = match bucket.lock().unwrap().consume(len) {
+                None => break,
+                Some(wait_time) => wait_time,
+            };
+            if let Some(is_nonblocking) = is_nonblocking.take() {
+                nonblocking = is_nonblocking()?;
+            }
+            if nonblocking {
+                return_errno!(EAGAIN, "the egress rate limit is exceeded");
+            }
+            do_nanosleep(&timespec_t::from(wait_time), None)?;
+        }
+    }
+    Ok(())
+}
+
+/// Get the IP address in the socket address given by the user. An IPv4-mapped
+/// address is the IPv4 address, which is where the data of a dual-stack socket goes.
+pub fn parse_sockaddr_ip(addr: &[u8]) -> Option<IpAddr> {
+    // The family is the first field of all the socket addresses
+    if addr.len() < std::mem::size_of::<libc::sa_family_t>() {
+        return None;
+    }
+    let family = libc::sa_family_t::from_ne_bytes([addr[0], addr[1]]) as c_int;
+    match family {
+        // struct sockaddr_in: family, port and the address of 4 bytes
+        libc::AF_INET if addr.len() >= 8 => {
+            let mut octets = [0u8; 4];
+            octets.copy_from_slice(&addr[4..8]);
+            Some(IpAddr::from(octets))
+        }
+        // struct sockaddr_in6: family, port, flow info and the address of 16 bytes
+        libc::AF_INET6 if addr.len() >= 24 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&addr[8..24]);
+            Some(unmap_ipv6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+pub fn is_in_cidr(addr: &IpAddr, cidr_addr: &IpAddr, prefix_len: u8) -> bool {
+    let (addr, cidr_addr) = match (addr, cidr_addr) {
+        (IpAddr::V4(addr), IpAddr::V4(cidr_addr)) => {
+            (addr.octets().to_vec(), cidr_addr.octets().to_vec())
+        }
+        (IpAddr::V6(addr), IpAddr::V6(cidr_addr)) => {
+            (addr.octets().to_vec(), cidr_addr.octets().to_vec())
+        }
+        // IPv4-mapped IPv6 addresses (i.e., ::ffff:a.b.c.d) are matched by IPv4 CIDR blocks
+        (IpAddr::V6(addr), IpAddr::V4(cidr_addr)) => {
+            let octets = addr.octets();
+            if octets[..10].iter().any(|&b| b != 0) || octets[10..12] != [0xff, 0xff] {
+                return false;
+            }
+            (octets[12..].to_vec(), cidr_addr.octets().to_vec())
+        }
+        _ => return false,
+    };
+    let prefix_len = prefix_len as usize;
+    let (full_bytes, rest_bits) = (prefix_len / 8, prefix_len % 8);
+    if addr[..full_bytes] != cidr_addr[..full_bytes] {
+        return false;
+    }
+    if rest_bits == 0 {
+        return true;
+    }
+    let mask = !0u8 << (8 - rest_bits);
+    addr[full_bytes] & mask == cidr_addr[full_bytes] & mask
+}
+
+struct TokenBucket {
+    rate: u64,
+    burst: i64,
+    // The tokens in bytes, which are negative if the bucket is in debt
+    tokens: i64,
+    // The monotonic time when the tokens are refilled last time, in nanoseconds
+    refill_time: u64,
+}
+
+impl TokenBucket {
+    fn new(limit: &ConfigEgressLimit) -> Self {
+        Self {
+            rate: limit.rate as u64,
+            burst: limit.burst as i64,
+            tokens: limit.burst as i64,
+            refill_time: now_ns(),
+        }
+    }
+
+    /// Consume the tokens for the data of `len` bytes, or return the time to wait
+    /// before the tokens are available
+    fn consume(&mut self, len: usize) -> Option<Duration> {
+        self.refill();
+        if self.tokens > 0 {
+            self.tokens -= len as i64;
+            return None;
+        }
+        let wait_ns = (1 - self.tokens) as u128 * 1_000_000_000 / self.rate as u128;
+        Some(Duration::from_nanos((wait_ns as u64).max(1)))
+    }
+
+    fn refill(&mut self) {
+        let now = now_ns();
+        let elapsed = now.saturating_sub(self.refill_time) as u128;
+        let new_tokens = elapsed * self.rate as u128 / 1_000_000_000;
+        let max_new_tokens = (self.burst - self.tokens) as u128;
+        if new_tokens >= max_new_tokens {
+            // The bucket is full
+            self.tokens = self.burst;
+            self.refill_time = now;
+        } else if new_tokens > 0 {
+            self.tokens += new_tokens as i64;
+            // Only the time that has been converted into tokens is consumed
+            self.refill_time += (new_tokens * 1_000_000_000 / self.rate as u128) as u64;
+        }
+    }
+}
+
+fn now_ns() -> u64 {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration().as_nanos() as u64)
+        .unwrap_or(0)
+}
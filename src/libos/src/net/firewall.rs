@@ -0,0 +1,70 @@
+//! The firewall of the host sockets, which restricts the addresses that the
+//! applications can talk to.
+//!
+//! The rules in the config are checked in order, and the first one that matches
+//! an address decides whether it is allowed. If none matches, the default action
+//! decides. The outbound addresses are the ones that are connected or sent to,
+//! while the inbound ones are the ones that are bound to, and the peers of the
+//! accepted connections. An IPv4-mapped IPv6 address is matched as the IPv4
+//! address that it carries.
+//!
+//! The addresses are checked in the enclave before they are given to the host. A
+//! denied address fails with EACCES and is reported as a security event. A denied
+//! connection that has been accepted by the host is closed instead, and accept
+//! goes on to the next connection, as if the peer were filtered by the host.
+use super::egress_limit::{is_in_cidr, parse_sockaddr_ip};
+use super::*;
+use crate::config::{ConfigFirewallAction, ConfigFirewallDirection, ConfigFirewallRule};
+use crate::fs::{report_security_event, SecurityEventKind};
+use std::net::{IpAddr, SocketAddr};
+
+/// Check the socket address in `addr` against the firewall, if it is configured
+pub fn check_firewall(direction: ConfigFirewallDirection, addr: &[u8]) -> Result<()> {
+    let firewall = match &config::LIBOS_CONFIG.network.firewall {
+        Some(firewall) => firewall,
+        None => return Ok(()),
+    };
+    // Only the IP addresses are filtered, e.g., not AF_UNSPEC to disconnect
+    let ip = match parse_sockaddr_ip(addr) {
+        Some(ip) => ip,
+        None => return Ok(()),
+    };
+    // The port follows the family in both sockaddr_in and sockaddr_in6
+    let port = u16::from_be_bytes([addr[2], addr[3]]);
+    let action = firewall
+        .rules
+        .iter()
+        .find(|rule| is_rule_matched(rule, direction, &ip, port))
+        .map_or(firewall.default, |rule| rule.action);
+    if action == ConfigFirewallAction::Deny {
+        let detail = format!(
+            "{:?} {} denied by firewall",
+            direction,
+            SocketAddr::new(ip, port)
+        );
+        report_security_event(SecurityEventKind::NetDenied, &detail);
+        return_errno!(EACCES, "the address is denied by the firewall");
+    }
+    Ok(())
+}
+
+fn is_rule_matched(
+    rule: &ConfigFirewallRule,
+    direction: ConfigFirewallDirection,
+    ip: &IpAddr,
+    port: u16,
+) -> bool {
+    let family = match ip {
+        IpAddr::V4(_) => libc::AF_INET,
+        IpAddr::V6(_) => libc::AF_INET6,
+    };
+    let is_direction_matched = rule.direction.map_or(true, |d| d == direction);
+    let is_family_matched = rule.family.map_or(true, |f| f == family);
+    let is_cidr_matched = rule.cidr.map_or(true, |(cidr_addr, prefix_len)| {
+        is_in_cidr(ip, &cidr_addr, prefix_len)
+    });
+    let is_port_matched = rule
+        .ports
+        .map_or(true, |(start, end)| start <= port && port <= end);
+    is_direction_matched && is_family_matched && is_cidr_matched && is_port_matched
+}
@@ -0,0 +1,199 @@
+//! The name resolution by the resolver of the host, i.e., getaddrinfo.
+//!
+//! There is no resolver in the enclave, so if host_dns is enabled in the config,
+//! the applications can resolve names by the Occlum-specific syscall, which is
+//! served by getaddrinfo of the host. The host is untrusted, so every address given
+//! by it is checked: the family, the length and the port of the address, the socket
+//! type and the protocol must be consistent with the hints and the service. The
+//! number of the addresses is capped by max_results in the config.
+//!
+//! The critical hostnames can be pinned to the addresses that they are expected to
+//! resolve to. If the host gives any other address for a pinned name, the whole
+//! resolution fails with EACCES and is reported as a security event.
+use super::egress_limit::parse_sockaddr_ip;
+use super::*;
+use crate::fs::{report_security_event, SecurityEventKind};
+use std::ffi::CStr;
+
+const AI_PASSIVE: c_int = 0x01;
+const AI_NUMERICHOST: c_int = 0x04;
+const AI_ADDRCONFIG: c_int = 0x20;
+const AI_NUMERICSERV: c_int = 0x400;
+// The flags that need no more than the addresses, e.g., not AI_CANONNAME
+const SUPPORTED_FLAGS: c_int = AI_PASSIVE | AI_NUMERICHOST | AI_ADDRCONFIG | AI_NUMERICSERV;
+
+const SOCKADDR_IN_LEN: usize = 16;
+const SOCKADDR_IN6_LEN: usize = 28;
+
+/// The hints of a resolution, which are the first fields of struct addrinfo
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct addrinfo_hints {
+    pub flags: c_int,
+    pub family: c_int,
+    pub socktype: c_int,
+    pub protocol: c_int,
+}
+
+/// An address resolved by `occlum_ocall_getaddrinfo`, which is also the one
+/// returned to the user.
+///
+/// It must be kept in sync with the C definition in pal/src/ocalls/net.c.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct occlum_addrinfo {
+    pub family: c_int,
+    pub socktype: c_int,
+    pub protocol: c_int,
+    pub addr_len: u32,
+    pub addr: [u8; SOCKADDR_IN6_LEN],
+}
+
+/// Resolve the node and the service by the resolver of the host into at most
+/// `max_count` addresses
+pub fn getaddrinfo(
+    node: Option<&CStr>,
+    service: Option<&CStr>,
+    hints: &addrinfo_hints,
+    max_count: usize,
+) -> Result<Vec<occlum_addrinfo>> {
+    let config = match &config::LIBOS_CONFIG.network.host_dns {
+        Some(config) => config,
+        None => return_errno!(ENOSYS, "the resolver of the host is not enabled"),
+    };
+    check_hints(hints)?;
+    // A numeric service must be the port of all the addresses, while a named
+    // one can be any port but zero
+    let port = match service {
+        Some(service) => service.to_str().ok().and_then(|s| s.parse::<u16>().ok()),
+        None => Some(0),
+    };
+
+    let max_count = min(max_count, config.max_results);
+    let mut buf = vec![occlum_addrinfo::default(); max_count];
+    let count = try_libc!({
+        let mut ret = 0;
+        let status = occlum_ocall_getaddrinfo(
+            &mut ret,
+            node.map_or(std::ptr::null(), |node| node.as_ptr()),
+            service.map_or(std::ptr::null(), |service| service.as_ptr()),
+            hints.flags,
+            hints.family,
+            hints.socktype,
+            hints.protocol,
+            buf.as_mut_ptr() as *mut u8,
+            max_count * std::mem::size_of::<occlum_addrinfo>(),
+        );
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        ret
+    }) as usize;
+    // The addresses beyond max_count are dropped
+    let addrs = buf[..min(count, max_count)]
+        .iter()
+        .map(|addr| addr.sanitize(hints, port))
+        .collect::<Result<Vec<_>>>()?;
+
+    if let Some(node) = node {
+        let name = node
+            .to_string_lossy()
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        if let Some(pinned_ips) = config.pinned.get(&name) {
+            for addr in &addrs {
+                let ip = parse_sockaddr_ip(&addr.addr[..addr.addr_len as usize]).unwrap();
+                if !pinned_ips.contains(&ip) {
+                    let detail = format!("{} resolved to unexpected address {} by host", name, ip);
+                    report_security_event(SecurityEventKind::NetDenied, &detail);
+                    return_errno!(
+                        EACCES,
+                        "the pinned name is resolved to an unexpected address"
+                    );
+                }
+            }
+        }
+    }
+    Ok(addrs)
+}
+
+fn check_hints(hints: &addrinfo_hints) -> Result<()> {
+    if hints.flags & !SUPPORTED_FLAGS != 0 {
+        return_errno!(EINVAL, "unsupported flags of getaddrinfo");
+    }
+    match hints.family {
+        libc::AF_UNSPEC | libc::AF_INET | libc::AF_INET6 => {}
+        _ => return_errno!(EAFNOSUPPORT, "unsupported family of getaddrinfo"),
+    }
+    match hints.socktype {
+        0 | libc::SOCK_STREAM | libc::SOCK_DGRAM | libc::SOCK_RAW => {}
+        _ => return_errno!(EINVAL, "unsupported socket type of getaddrinfo"),
+    }
+    if hints.protocol < 0 || hints.protocol > u8::max_value() as c_int {
+        return_errno!(EINVAL, "invalid protocol of getaddrinfo");
+    }
+    Ok(())
+}
+
+impl occlum_addrinfo {
+    /// Check the address given by the untrusted host, where `port` is the expected
+    /// port, or None if any port but zero is expected
+    fn sanitize(&self, hints: &addrinfo_hints, port: Option<u16>) -> Result<occlum_addrinfo> {
+        let addr_len = match self.family {
+            libc::AF_INET => SOCKADDR_IN_LEN,
+            libc::AF_INET6 => SOCKADDR_IN6_LEN,
+            _ => return_errno!(EIO, "invalid family of address given by the host"),
+        };
+        let sa_family = libc::sa_family_t::from_ne_bytes([self.addr[0], self.addr[1]]) as c_int;
+        let is_valid_family = (hints.family == libc::AF_UNSPEC || self.family == hints.family)
+            && self.addr_len as usize == addr_len
+            && sa_family == self.family;
+        if !is_valid_family {
+            return_errno!(EIO, "inconsistent address given by the host");
+        }
+        let is_valid_socktype = match self.socktype {
+            libc::SOCK_STREAM | libc::SOCK_DGRAM | libc::SOCK_RAW => {
+                hints.socktype == 0 || self.socktype == hints.socktype
+            }
+            _ => false,
+        };
+        let is_valid_protocol = self.protocol >= 0
+            && self.protocol <= u8::max_value() as c_int
+            && (hints.protocol == 0 || self.protocol == hints.protocol);
+        if !is_valid_socktype || !is_valid_protocol {
+            return_errno!(EIO, "invalid socket type or protocol given by the host");
+        }
+        // The port follows the family in both sockaddr_in and sockaddr_in6
+        let addr_port = u16::from_be_bytes([self.addr[2], self.addr[3]]);
+        let is_valid_port = match port {
+            Some(port) => addr_port == port,
+            None => addr_port != 0,
+        };
+        if !is_valid_port {
+            return_errno!(EIO, "unexpected port of address given by the host");
+        }
+
+        // Only the family, the port and the IP address of sockaddr_in are kept, so
+        // sin_zero is zero as expected
+        let mut addr = [0; SOCKADDR_IN6_LEN];
+        let kept_len = if self.family == libc::AF_INET {
+            8
+        } else {
+            SOCKADDR_IN6_LEN
+        };
+        addr[..kept_len].copy_from_slice(&self.addr[..kept_len]);
+        Ok(occlum_addrinfo { addr, ..*self })
+    }
+}
+
+extern "C" {
+    fn occlum_ocall_getaddrinfo(
+        ret: *mut i32,
+        node: *const c_char,
+        service: *const c_char,
+        flags: c_int,
+        family: c_int,
+        socktype: c_int,
+        protocol: c_int,
+        buf: *mut u8,
+        buf_len: usize,
+    ) -> sgx_status_t;
+}
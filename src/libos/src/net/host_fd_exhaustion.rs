@@ -0,0 +1,75 @@
+//! The backpressure of the host sockets on the exhaustion of the host fds.
+//!
+//! The host sockets of all the LibOS processes are the fds of one host process, so
+//! the applications together may reach the limit of the open files of the host
+//! process or of the whole host, which fails socket and accept with EMFILE or ENFILE.
+//! Such failures are often transient. A socket closed by the application keeps its
+//! host fd until the last reference to it is dropped, e.g., by the threads blocked in
+//! poll on it, which are not woken up until the events that they wait for occur.
+//!
+//! So the failure is not returned to the application at once. Instead, the threads
+//! blocked in poll on the host files are woken up, which drop the files closed in the
+//! meantime and release their host fds, and the OCall is retried after a backoff. A
+//! non-blocking socket or accept is not delayed by the backoff, and fails at once. The
+//! numbers of the exhaustions, and of the ones recovered by the retries, are shown in
+//! /proc/occlum/host_fd_exhaustion.
+use super::io_multiplexing::invalidate_all_host_fds;
+use super::*;
+use crate::time::{do_nanosleep, timespec_t};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// The backoffs of the retries are doubled from INIT_BACKOFF, which add up to
+// about 30ms before the failure is returned
+const MAX_RETRIES: u32 = 5;
+const INIT_BACKOFF: Duration = Duration::from_millis(1);
+
+static NUM_EXHAUSTED: AtomicU64 = AtomicU64::new(0);
+static NUM_RECOVERED: AtomicU64 = AtomicU64::new(0);
+
+/// Do the OCall that creates a host fd, e.g., socket or accept, which is retried
+/// with a backoff if the host fds are exhausted, unless it is `nonblocking`
+pub fn retry_on_host_fd_exhaustion<T>(
+    nonblocking: bool,
+    mut ocall: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    let mut backoff = INIT_BACKOFF;
+    let mut retries = 0;
+    loop {
+        let error = match ocall() {
+            Err(e) if e.errno() == EMFILE || e.errno() == ENFILE => e,
+            ret => {
+                if retries > 0 && ret.is_ok() {
+                    NUM_RECOVERED.fetch_add(1, Ordering::Relaxed);
+                }
+                return ret;
+            }
+        };
+        if retries == 0 {
+            NUM_EXHAUSTED.fetch_add(1, Ordering::Relaxed);
+        }
+        if nonblocking || retries == MAX_RETRIES {
+            return Err(error);
+        }
+        debug!("the host fds are exhausted; retry after {:?}", backoff);
+        // The failure of the OCall, rather than EINTR, is returned if the backoff
+        // is interrupted
+        if invalidate_all_host_fds().is_err()
+            || do_nanosleep(&timespec_t::from(backoff), None).is_err()
+        {
+            return Err(error);
+        }
+        backoff *= 2;
+        retries += 1;
+    }
+}
+
+/// Format the numbers of the exhaustions of the host fds, for
+/// /proc/occlum/host_fd_exhaustion
+pub fn format_host_fd_exhaustion() -> String {
+    format!(
+        "exhausted {}\nrecovered {}\n",
+        NUM_EXHAUSTED.load(Ordering::Relaxed),
+        NUM_RECOVERED.load(Ordering::Relaxed)
+    )
+}
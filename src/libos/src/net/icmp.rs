@@ -0,0 +1,80 @@
+//! The validation of the payloads of ICMP sockets, a.k.a. ping sockets.
+//!
+//! A ping socket (i.e., a socket of type SOCK_DGRAM and protocol IPPROTO_ICMP or
+//! IPPROTO_ICMPV6) can only send ICMP echo requests and receive ICMP echo replies,
+//! which does not require the privilege of raw sockets on the host. The ICMP
+//! messages are checked before they are sent and after they are received, so that
+//! neither the application nor the host can abuse the socket for other kinds of
+//! ICMP messages.
+use super::*;
+
+pub const IPPROTO_ICMPV6: c_int = 58;
+
+// The ICMP header consists of type, code, checksum, identifier and sequence number
+const ICMP_HEADER_LEN: usize = 8;
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// Check whether the arguments of socket() specify a ping socket
+pub fn is_ping_socket(domain: c_int, socket_type: c_int, protocol: c_int) -> bool {
+    let socket_type = socket_type & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+    if socket_type != libc::SOCK_DGRAM {
+        return false;
+    }
+    match domain {
+        libc::AF_INET => protocol == libc::IPPROTO_ICMP,
+        libc::AF_INET6 => protocol == IPPROTO_ICMPV6,
+        _ => false,
+    }
+}
+
+/// Check the ICMP message given by the user, before it is sent by the host
+pub fn check_echo_request(domain: c_int, msg: &[u8]) -> Result<()> {
+    if msg.len() < ICMP_HEADER_LEN {
+        return_errno!(EINVAL, "the ICMP message is too short");
+    }
+    if msg[0] != echo_request_type(domain) || msg[1] != 0 {
+        return_errno!(
+            EINVAL,
+            "only ICMP echo requests can be sent by ping sockets"
+        );
+    }
+    Ok(())
+}
+
+/// Check the ICMP message given by the host, before it is returned to the user.
+///
+/// The message in `msg` may be truncated to the buffer of the user, whose length
+/// is `buf_len`, while `msg_len` is the length of the message received.
+pub fn check_echo_reply(domain: c_int, msg: &[u8], msg_len: usize, buf_len: usize) -> Result<()> {
+    // The message is not truncated unless the buffer is full
+    if msg_len < ICMP_HEADER_LEN && msg_len < buf_len {
+        return_errno!(EIO, "the ICMP message given by the host is too short");
+    }
+    let is_valid = match msg.len() {
+        0 => true,
+        1 => msg[0] == echo_reply_type(domain),
+        _ => msg[0] == echo_reply_type(domain) && msg[1] == 0,
+    };
+    if !is_valid {
+        return_errno!(EIO, "not an ICMP echo reply given by the host");
+    }
+    Ok(())
+}
+
+fn echo_request_type(domain: c_int) -> u8 {
+    match domain {
+        libc::AF_INET6 => ICMPV6_ECHO_REQUEST,
+        _ => ICMP_ECHO_REQUEST,
+    }
+}
+
+fn echo_reply_type(domain: c_int) -> u8 {
+    match domain {
+        libc::AF_INET6 => ICMPV6_ECHO_REPLY,
+        _ => ICMP_ECHO_REPLY,
+    }
+}
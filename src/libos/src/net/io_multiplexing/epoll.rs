@@ -1,4 +1,9 @@
+use super::waiter::Waiter;
 use super::*;
+use crate::signal::has_deliverable_signals;
+use crate::time::{do_clock_gettime, ClockID};
+use std::sync::Weak;
+use std::time::Duration;
 
 #[derive(Debug, Copy, Clone)]
 pub enum EpollCtlCmd {
@@ -69,6 +74,14 @@ impl EpollEvent {
         ))
     }
 
+    pub fn events(&self) -> EpollEventFlags {
+        self.events
+    }
+
+    pub fn data(&self) -> u64 {
+        self.data
+    }
+
     pub fn to_raw(&self) -> libc::epoll_event {
         libc::epoll_event {
             events: self.events.bits(),
@@ -80,6 +93,78 @@ impl EpollEvent {
 #[derive(Debug)]
 pub struct EpollFile {
     host_fd: c_int,
+    // The files of LibOS in the interest list, which are invisible to the host epoll.
+    // The files are not kept open by the epoll file, so they are removed from the
+    // interest list once closed as Linux does.
+    libos_files: SgxMutex<HashMap<FileDesc, LibosEpollEntry>>,
+    // The sockets in the interest list whose data are wrapped in TLS. The plaintext
+    // buffered in the enclave is invisible to the host epoll, so the sockets with
+    // the plaintext are reported as readable in the enclave.
+    tls_sockets: SgxMutex<HashMap<FileDesc, (Weak<Box<dyn File>>, EpollEvent)>>,
+}
+
+/// A file of LibOS in the interest list of an epoll file
+#[derive(Debug)]
+struct LibosEpollEntry {
+    file: Weak<Box<dyn File>>,
+    event: EpollEvent,
+    // The events and the poll sequence number seen last time, which tell the new
+    // events for EPOLLET
+    last_events: EpollEventFlags,
+    last_seq: Option<usize>,
+    // Whether the entry is disabled after an event is reported for EPOLLONESHOT,
+    // until it is re-armed by EPOLL_CTL_MOD
+    disabled: bool,
+}
+
+impl LibosEpollEntry {
+    fn new(file: &FileRef, event: EpollEvent) -> Self {
+        Self {
+            file: Arc::downgrade(file),
+            event,
+            last_events: EpollEventFlags::empty(),
+            last_seq: None,
+            disabled: false,
+        }
+    }
+
+    /// Poll the file, and return the event to report if any
+    fn poll(&mut self) -> Option<EpollEvent> {
+        if self.disabled {
+            return None;
+        }
+        let file = self.file.upgrade()?;
+        let flags = self.event.events();
+        let interest = flags | EpollEventFlags::EPOLLERR | EpollEventFlags::EPOLLHUP;
+        let ready = match file.poll() {
+            Ok(revents) => {
+                EpollEventFlags::from_bits_truncate(revents.bits() as u16 as u32) & interest
+            }
+            Err(_) => EpollEventFlags::EPOLLERR,
+        };
+        let new_events = if flags.contains(EpollEventFlags::EPOLLET) {
+            // The file may become ready again without becoming unready in between as
+            // seen by epoll, e.g., when the data are read out and then written again
+            let seq = file.poll_seq().ok();
+            let new_events = if seq.is_some() && seq != self.last_seq {
+                ready
+            } else {
+                ready & !self.last_events
+            };
+            self.last_events = ready;
+            self.last_seq = seq;
+            new_events
+        } else {
+            ready
+        };
+        if new_events.is_empty() {
+            return None;
+        }
+        if flags.contains(EpollEventFlags::EPOLLONESHOT) {
+            self.disabled = true;
+        }
+        Some(EpollEvent::new(new_events, self.event.data()))
+    }
 }
 
 impl EpollFile {
@@ -87,22 +172,59 @@ impl EpollFile {
     pub fn new(flags: CreationFlags) -> Result<Self> {
         debug!("create epollfile: flags: {:?}", flags);
         let host_fd = try_libc!(libc::ocall::epoll_create1(flags.bits() as i32));
-        Ok(Self { host_fd })
+        Ok(Self {
+            host_fd,
+            libos_files: SgxMutex::new(HashMap::new()),
+            tls_sockets: SgxMutex::new(HashMap::new()),
+        })
     }
 
     pub fn control(&self, op: EpollCtlCmd, fd: FileDesc, event: Option<&EpollEvent>) -> Result<()> {
+        let mut tls_socket = None;
         let host_fd = {
             let fd_ref = current!().file(fd)?;
-            if let Ok(socket) = fd_ref.as_socket() {
+            let bridge_host_fd = fd_ref
+                .as_unix_socket()
+                .ok()
+                .and_then(|unix_socket| unix_socket.bridge_host_fd());
+            if let Some(bridge_host_fd) = bridge_host_fd {
+                bridge_host_fd
+            } else if is_libos_file(&fd_ref) {
+                return self.control_libos_file(op, fd, &fd_ref, event);
+            } else if let Ok(socket) = fd_ref.as_socket() {
+                // The data prefetched later would be invisible to the epoll of the host
+                socket.buffering().set_epolled();
+                if socket.is_tls() {
+                    tls_socket = Some(fd_ref.clone());
+                }
                 socket.fd()
             } else if let Ok(eventfd) = fd_ref.as_event() {
                 eventfd.get_host_fd()
+            } else if let Ok(dev_mem_pressure) = fd_ref.as_dev_mem_pressure() {
+                dev_mem_pressure.get_host_fd()
+            } else if let Ok(dev_security_events) = fd_ref.as_dev_security_events() {
+                dev_security_events.get_host_fd()
+            } else if let Ok(uevent_socket) = fd_ref.as_uevent_socket() {
+                uevent_socket.get_host_fd()
+            } else if let Ok(pidfd) = fd_ref.as_pidfd() {
+                pidfd.get_host_fd()
+            } else if let Ok(userfault_file) = fd_ref.as_userfault_file() {
+                userfault_file.get_host_fd()
+            } else if let Ok(inotify_file) = fd_ref.as_inotify() {
+                inotify_file.get_host_fd()
+            } else if let Ok(stdin) = fd_ref.as_stdin() {
+                stdin.get_host_fd() as c_int
+            } else if let Ok(stdout) = fd_ref.as_stdout() {
+                stdout.get_host_fd() as c_int
             } else if let Ok(epoll_file) = fd_ref.as_epfile() {
                 let target_host_fd = epoll_file.get_host_fd();
                 if self.host_fd == target_host_fd {
                     return_errno!(EINVAL, "epfd should not be same as the target fd");
                 }
                 target_host_fd
+            } else if fd_ref.as_tcp_stack_socket().is_ok() {
+                // The stack is polled by the threads, which the host cannot see
+                return_errno!(EPERM, "the sockets of the stack cannot be added to epoll");
             } else {
                 return_errno!(EPERM, "unsupported file type");
             }
@@ -128,21 +250,173 @@ impl EpollFile {
             host_fd,
             raw_epevent_ptr,
         ));
+        if let Some(tls_socket) = tls_socket {
+            let mut tls_sockets = self.tls_sockets.lock().unwrap();
+            tls_sockets.retain(|_, (file, _)| file.strong_count() > 0);
+            match (op, event) {
+                (EpollCtlCmd::Add, Some(event)) | (EpollCtlCmd::Mod, Some(event)) => {
+                    tls_sockets.insert(fd, (Arc::downgrade(&tls_socket), *event));
+                }
+                _ => {
+                    tls_sockets.remove(&fd);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn control_libos_file(
+        &self,
+        op: EpollCtlCmd,
+        fd: FileDesc,
+        fd_ref: &FileRef,
+        event: Option<&EpollEvent>,
+    ) -> Result<()> {
+        let mut libos_files = self.libos_files.lock().unwrap();
+        // The entries of the closed files are stale, even if the fds are reused
+        libos_files.retain(|_, entry| entry.file.strong_count() > 0);
+        let is_added = match libos_files.get(&fd) {
+            Some(entry) => match entry.file.upgrade() {
+                Some(file) => Arc::ptr_eq(&file, fd_ref),
+                None => false,
+            },
+            None => false,
+        };
+        match op {
+            EpollCtlCmd::Add | EpollCtlCmd::Mod => {
+                if let EpollCtlCmd::Add = op {
+                    if is_added {
+                        return_errno!(EEXIST, "the file is already added");
+                    }
+                } else if !is_added {
+                    return_errno!(ENOENT, "the file is not added");
+                }
+                let event = event.ok_or_else(|| errno!(EFAULT, "the event is null"))?;
+                // The readiness is checked again, even for EPOLLET, as Linux does
+                libos_files.insert(fd, LibosEpollEntry::new(fd_ref, *event));
+            }
+            EpollCtlCmd::Del => {
+                if !is_added {
+                    return_errno!(ENOENT, "the file is not added");
+                }
+                libos_files.remove(&fd);
+            }
+        }
         Ok(())
     }
 
     /// Waits for an I/O event on the epoll file.
     ///
+    /// The timeout, which is infinite if it is `None`, is measured against the
+    /// monotonic clock. If the wait in the host returns early, or is interrupted
+    /// without any signal to deliver, it is restarted with the remaining time.
+    ///
     /// Returns the number of file descriptors ready for the requested I/O.
-    pub fn wait(&self, events: &mut [EpollEvent], timeout: c_int) -> Result<usize> {
+    pub fn wait(&self, events: &mut [EpollEvent], timeout: Option<Duration>) -> Result<usize> {
+        // A timeout that is too long to be represented is infinite
+        let deadline = timeout.and_then(|timeout| now().checked_add(timeout));
+        loop {
+            let remaining =
+                deadline.map(|deadline| deadline.checked_sub(now()).unwrap_or_default());
+            let ret = self.wait_once(events, remaining);
+            let has_time_left = match deadline {
+                Some(deadline) => now() < deadline,
+                None => true,
+            };
+            match ret {
+                Ok(0) if has_time_left => {
+                    debug!("epoll_wait returns early; restart it");
+                }
+                Err(e) if e.errno() == EINTR && has_time_left && !has_deliverable_signals() => {
+                    debug!("epoll_wait is interrupted without any signal; restart it");
+                }
+                ret => return ret,
+            }
+        }
+    }
+
+    /// Wait for the files of LibOS and the host epoll at the same time. The files of
+    /// LibOS notify the current thread, which is waiting for the host epoll and the
+    /// notifier of the thread, when they may become ready.
+    fn wait_once(&self, events: &mut [EpollEvent], timeout: Option<Duration>) -> Result<usize> {
+        // The sockets with the plaintext of TLS are ready without waiting
+        let tls_ready_num = self.poll_tls_sockets(events);
+        if tls_ready_num > 0 {
+            let mut ready_num = tls_ready_num + self.poll_libos_files(&mut events[tls_ready_num..]);
+            if ready_num < events.len() {
+                ready_num +=
+                    self.wait_in_host(&mut events[ready_num..], Some(Duration::from_secs(0)))?;
+            }
+            return Ok(merge_tls_events(events, tls_ready_num, ready_num));
+        }
+
+        let libos_files: Vec<(FileRef, EpollEvent)> = self
+            .libos_files
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| !entry.disabled)
+            .filter_map(|entry| entry.file.upgrade().map(|file| (file, entry.event)))
+            .collect();
+        if libos_files.is_empty() {
+            return self.wait_in_host(events, timeout);
+        }
+
+        // The waiter clears the notifier before the events are queued, and removes
+        // them on return
+        let mut waiter = Waiter::new()?;
+        let mut ready_num = self.poll_libos_files(events);
+        if ready_num == 0 {
+            for (file, event) in &libos_files {
+                waiter.enqueue(file, IoEvent::Epoll(*event))?;
+            }
+            // Check again as the files may become ready before the events are queued
+            ready_num = self.poll_libos_files(events);
+            if ready_num == 0 {
+                let mut host_pollfds = vec![PollEvent::new(
+                    self.host_fd as FileDesc,
+                    PollEventFlags::POLLIN,
+                )];
+                let mut timeout = timeout.map(timeval_t::from);
+                let timeout_ptr = match &mut timeout {
+                    Some(timeout) => timeout as *mut timeval_t,
+                    None => std::ptr::null_mut(),
+                };
+                waiter.wait(&mut host_pollfds, timeout_ptr)?;
+                ready_num = self.poll_libos_files(events);
+            }
+        }
+        drop(waiter);
+        if ready_num == 0 && has_deliverable_signals() {
+            return_errno!(EINTR, "interrupted by signals");
+        }
+        if ready_num < events.len() {
+            ready_num +=
+                self.wait_in_host(&mut events[ready_num..], Some(Duration::from_secs(0)))?;
+        }
+        Ok(ready_num)
+    }
+
+    fn wait_in_host(&self, events: &mut [EpollEvent], timeout: Option<Duration>) -> Result<usize> {
+        // The timeout of the host is in milliseconds, which is rounded up so
+        // that the host never returns before the deadline
+        let timeout_ms = match timeout {
+            Some(timeout) => {
+                let ms = (timeout.as_nanos() + 999_999) / 1_000_000;
+                ms.min(c_int::max_value() as u128) as c_int
+            }
+            None => -1,
+        };
         let mut raw_events: Vec<libc::epoll_event> =
             vec![libc::epoll_event { events: 0, u64: 0 }; events.len()];
-        let ret = try_libc!(libc::ocall::epoll_wait(
-            self.host_fd,
-            raw_events.as_mut_ptr(),
-            raw_events.len() as c_int,
-            timeout,
-        )) as usize;
+        let ret = try_libc!(crate::sched::account_wait(|| {
+            libc::ocall::epoll_wait(
+                self.host_fd,
+                raw_events.as_mut_ptr(),
+                raw_events.len() as c_int,
+                timeout_ms,
+            )
+        })) as usize;
 
         assert!(ret <= events.len());
         for i in 0..ret {
@@ -152,11 +426,95 @@ impl EpollFile {
         Ok(ret)
     }
 
+    /// Poll the files of LibOS, and fill `events` with the ready ones. The events are
+    /// reported by one of the threads waiting on the epoll file for EPOLLET or
+    /// EPOLLONESHOT, as the entries are updated with the lock held.
+    fn poll_libos_files(&self, events: &mut [EpollEvent]) -> usize {
+        let mut libos_files = self.libos_files.lock().unwrap();
+        let mut ready_num = 0;
+        for entry in libos_files.values_mut() {
+            if ready_num == events.len() {
+                break;
+            }
+            if let Some(event) = entry.poll() {
+                events[ready_num] = event;
+                ready_num += 1;
+            }
+        }
+        ready_num
+    }
+
+    /// Fill `events` with the TLS sockets that have the plaintext buffered in the
+    /// enclave and are waited for EPOLLIN
+    fn poll_tls_sockets(&self, events: &mut [EpollEvent]) -> usize {
+        let mut tls_sockets = self.tls_sockets.lock().unwrap();
+        let mut ready_num = 0;
+        for (file, event) in tls_sockets.values_mut() {
+            if ready_num == events.len() {
+                break;
+            }
+            let flags = event.events();
+            if !flags.contains(EpollEventFlags::EPOLLIN) {
+                continue;
+            }
+            let has_plaintext = file.upgrade().map_or(false, |file| {
+                file.as_socket()
+                    .map_or(false, |socket| socket.has_tls_plaintext())
+            });
+            if has_plaintext {
+                events[ready_num] = EpollEvent::new(EpollEventFlags::EPOLLIN, event.data());
+                ready_num += 1;
+                // Disarmed until EPOLL_CTL_MOD as the host does
+                if flags.contains(EpollEventFlags::EPOLLONESHOT) {
+                    *event = EpollEvent::new(EpollEventFlags::empty(), event.data());
+                }
+            }
+        }
+        ready_num
+    }
+
     fn get_host_fd(&self) -> c_int {
         self.host_fd
     }
 }
 
+/// Merge the events in `events[tls_ready_num..ready_num]` reported by the host into
+/// the ones of the TLS sockets reported in the enclave, i.e., the first
+/// `tls_ready_num` ones, which are told apart by the data given by the application.
+/// Return the number of the events left.
+fn merge_tls_events(events: &mut [EpollEvent], tls_ready_num: usize, ready_num: usize) -> usize {
+    let mut merged_num = tls_ready_num;
+    for i in tls_ready_num..ready_num {
+        let event = events[i];
+        match events[..tls_ready_num]
+            .iter_mut()
+            .find(|tls_event| tls_event.data() == event.data())
+        {
+            Some(tls_event) => {
+                *tls_event = EpollEvent::new(tls_event.events() | event.events(), event.data())
+            }
+            None => {
+                events[merged_num] = event;
+                merged_num += 1;
+            }
+        }
+    }
+    merged_num
+}
+
+/// The files of LibOS, which are polled in the enclave rather than by the host
+fn is_libos_file(file_ref: &FileRef) -> bool {
+    file_ref.as_unix_socket().is_ok()
+        || file_ref.as_pipe_reader().is_ok()
+        || file_ref.as_pipe_writer().is_ok()
+}
+
+fn now() -> Duration {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration())
+        .unwrap_or_default()
+}
+
 impl Drop for EpollFile {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,84 @@
+//! The registry of the threads that are blocked in poll on host files.
+//!
+//! The host files (e.g., host sockets) are shared by all LibOS processes, which
+//! run in the same host process. A host socket that is shared across spawn can be
+//! shut down by one process while the other processes are blocked in poll on it.
+//! The host wakes up those threads by itself only if the events that they wait for
+//! occur, so the readiness of the host file is invalidated here by notifying them,
+//! after which they poll the host files again for the up-to-date events.
+//!
+//! The host fds of the files closed under poll are released only after the threads
+//! are woken up, so all of them are invalidated when the host fds are exhausted.
+//!
+//! The threads blocked in epoll_wait are not registered, as the host epoll file
+//! reports the events of shutdown without the help of the LibOS.
+use super::*;
+
+lazy_static! {
+    static ref HOST_FD_WAITERS: SgxMutex<HashMap<c_int, Vec<pid_t>>> =
+        SgxMutex::new(HashMap::new());
+}
+
+/// Register the thread as being blocked on the host files
+pub fn register_host_fd_waiter(host_fds: &[c_int], tid: pid_t) {
+    let mut waiters = HOST_FD_WAITERS.lock().unwrap();
+    for host_fd in host_fds {
+        waiters.entry(*host_fd).or_insert_with(Vec::new).push(tid);
+    }
+}
+
+/// Unregister the thread after it is no longer blocked on the host files
+pub fn unregister_host_fd_waiter(host_fds: &[c_int], tid: pid_t) {
+    let mut waiters = HOST_FD_WAITERS.lock().unwrap();
+    for host_fd in host_fds {
+        let is_empty = match waiters.get_mut(host_fd) {
+            Some(tids) => {
+                if let Some(pos) = tids.iter().position(|t| *t == tid) {
+                    tids.swap_remove(pos);
+                }
+                tids.is_empty()
+            }
+            None => false,
+        };
+        if is_empty {
+            waiters.remove(host_fd);
+        }
+    }
+}
+
+/// Invalidate the readiness of the host file by notifying the threads blocked on it
+pub fn invalidate_host_fd(host_fd: c_int) -> Result<()> {
+    let tids = match HOST_FD_WAITERS.lock().unwrap().get(&host_fd) {
+        Some(tids) => tids.clone(),
+        None => return Ok(()),
+    };
+    let current_tid = current!().tid();
+    for tid in tids {
+        // The current thread cannot be blocked
+        if tid != current_tid {
+            notify_thread(tid)?;
+        }
+    }
+    Ok(())
+}
+
+/// Invalidate the readiness of all the host files, which wakes up all the threads
+/// blocked on them
+pub fn invalidate_all_host_fds() -> Result<()> {
+    let mut tids: Vec<pid_t> = HOST_FD_WAITERS
+        .lock()
+        .unwrap()
+        .values()
+        .flatten()
+        .cloned()
+        .collect();
+    tids.sort();
+    tids.dedup();
+    let current_tid = current!().tid();
+    for tid in tids {
+        if tid != current_tid {
+            notify_thread(tid)?;
+        }
+    }
+    Ok(())
+}
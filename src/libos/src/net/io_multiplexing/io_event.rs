@@ -1,12 +1,14 @@
+use super::poll::do_poll_in_host;
 use super::*;
 use crate::fs::EventFile;
+use std::time::Duration;
 
 lazy_static! {
     pub static ref THREAD_NOTIFIERS: SgxMutex<HashMap<pid_t, EventFile>> =
         SgxMutex::new(HashMap::new());
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum IoEvent {
     Poll(PollEvent),
     Epoll(EpollEvent),
@@ -48,7 +50,32 @@ pub fn clear_notifier_status(tid: pid_t) -> Result<()> {
     Ok(())
 }
 
+/// Wait until the current thread is notified.
+///
+/// The notifier is not cleared here. The caller clears it before checking what to
+/// wait for, so that the notifications since the check are not missed.
 pub fn wait_for_notification() -> Result<()> {
-    do_poll(&mut vec![], std::ptr::null_mut())?;
+    wait_for_notification_timeout(None)
+}
+
+/// Like `wait_for_notification`, but also returns once the timeout expires
+pub fn wait_for_notification_timeout(timeout: Option<Duration>) -> Result<()> {
+    let notifier_host_fd = THREAD_NOTIFIERS
+        .lock()
+        .unwrap()
+        .get(&current!().tid())
+        .unwrap()
+        .get_host_fd();
+    let mut timeout = timeout.map(timeval_t::from);
+    let timeout_ptr = match &mut timeout {
+        Some(timeout) => timeout as *mut timeval_t,
+        None => std::ptr::null_mut(),
+    };
+    // Return once notified, instead of polling until any file is ready
+    let mut host_pollfds = [PollEvent::new(
+        notifier_host_fd as FileDesc,
+        PollEventFlags::POLLIN,
+    )];
+    do_poll_in_host(&mut host_pollfds, timeout_ptr, notifier_host_fd)?;
     Ok(())
 }
@@ -1,20 +1,29 @@
 use super::*;
 
 mod epoll;
+mod host_fd_registry;
 mod io_event;
 mod poll;
 mod select;
+mod waiter;
 
 pub use self::epoll::{AsEpollFile, EpollCtlCmd, EpollEvent, EpollEventFlags, EpollFile};
+pub use self::host_fd_registry::{invalidate_all_host_fds, invalidate_host_fd};
 pub use self::io_event::{
-    clear_notifier_status, notify_thread, wait_for_notification, IoEvent, THREAD_NOTIFIERS,
+    clear_notifier_status, notify_thread, wait_for_notification, wait_for_notification_timeout,
+    IoEvent, THREAD_NOTIFIERS,
 };
-pub use self::poll::{do_poll, PollEvent, PollEventFlags};
+pub use self::poll::{do_poll, poll_host_fd, wait_host_fd, Deadline, PollEvent, PollEventFlags};
 pub use self::select::{select, FdSetExt};
 
-use fs::{AsDevRandom, AsEvent, CreationFlags, File, FileDesc, FileRef, PipeType};
+use fs::{
+    AsDevMemPressure, AsDevRandom, AsDevSecurityEvents, AsEvent, AsInotify, AsStdin, AsStdout,
+    CreationFlags, File, FileDesc, FileRef, PipeType,
+};
+use process::AsPidFd;
 use std::any::Any;
 use std::convert::TryFrom;
 use std::fmt;
 use std::sync::atomic::spin_loop_hint;
 use time::timeval_t;
+use vm::AsUserfaultFile;
@@ -1,4 +1,9 @@
+use super::waiter::Waiter;
 use super::*;
+use crate::net::stack;
+use crate::signal::has_deliverable_signals;
+use crate::time::{do_clock_gettime, ClockID};
+use std::time::Duration;
 
 bitflags! {
     #[derive(Default)]
@@ -63,19 +68,65 @@ impl PollEvent {
     }
 }
 
+/// Poll the files until any of them is ready or the timeout expires.
+///
+/// The timeout is measured against the monotonic clock, so that a poll that is
+/// woken up spuriously (e.g., by an event of a LibOS file that is consumed by
+/// another thread) or interrupted by the host without any signal to deliver, is
+/// restarted with the remaining time rather than the whole timeout. The remaining
+/// time is written back to `timeout` on return, as select does on Linux.
 pub fn do_poll(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usize> {
+    // A timeout that is too long to be represented is infinite
+    let deadline = if timeout.is_null() {
+        None
+    } else {
+        now().checked_add(unsafe { *timeout }.as_duration())
+    };
+    loop {
+        let ret = do_poll_once(pollfds, timeout);
+        let remaining = deadline.map(|deadline| deadline.checked_sub(now()).unwrap_or_default());
+        if let Some(remaining) = remaining {
+            unsafe {
+                *timeout = timeval_t::from(remaining);
+            }
+        }
+        let has_time_left = remaining.map_or(true, |remaining| remaining > Duration::new(0, 0));
+        match ret {
+            Ok(0) if has_time_left => {
+                debug!("poll is woken up spuriously; restart it");
+            }
+            Err(e) if e.errno() == EINTR && has_time_left && !has_deliverable_signals() => {
+                debug!("poll is interrupted without any signal; restart it");
+            }
+            ret => return ret,
+        }
+    }
+}
+
+/// Poll the files once, which returns when any of them is ready, the timeout
+/// expires, or the notifier of the current thread is notified.
+fn do_poll_once(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usize> {
     let mut libos_ready_num = 0;
     let mut host_ready_num = 0;
     let mut notified = 0;
+    // Whether any socket of the in-enclave stack is polled
+    let mut has_stack_sockets = false;
     let current = current!();
 
     // The pollfd of the host file
     let mut host_pollfds: Vec<PollEvent> = Vec::new();
     // The indices in pollfds of host file
     let mut index_host_pollfds: Vec<usize> = Vec::new();
+    // The host files are kept open during poll, even if they are closed by other
+    // threads or processes, as the host fds must not be closed or reused under poll
+    let mut host_file_refs: Vec<FileRef> = Vec::new();
+    // The indices in host_pollfds of the sockets with the data buffered in the enclave,
+    // which are readable no matter what the host reports
+    let mut buffered_readable_pollfds: Vec<usize> = Vec::new();
     // Vec<usize>: The indices in pollfds which may be more than one for the same file
     // PollEvent: the merged pollfd of FileDesc
-    let mut libos_pollfds: HashMap<FileDesc, (PollEvent, Vec<usize>)> = HashMap::new();
+    // FileRef: the file, which is kept open during poll
+    let mut libos_pollfds: HashMap<FileDesc, (PollEvent, Vec<usize>, FileRef)> = HashMap::new();
 
     for (i, pollfd) in pollfds.iter_mut().enumerate() {
         // Ignore negative fds
@@ -90,11 +141,26 @@ pub fn do_poll(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usi
             continue;
         };
 
+        // The unix sockets connected to the D-Bus bridges are polled on the host
+        let bridge_host_fd = file_ref
+            .as_unix_socket()
+            .ok()
+            .and_then(|unix_socket| unix_socket.bridge_host_fd());
+        if let Some(fd) = bridge_host_fd {
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd as FileDesc, pollfd.events()));
+            continue;
+        }
+
         if file_ref.as_unix_socket().is_ok()
             || file_ref.as_pipe_reader().is_ok()
             || file_ref.as_pipe_writer().is_ok()
             || file_ref.as_dev_random().is_ok()
+            || file_ref.as_tcp_stack_socket().is_ok()
         {
+            if file_ref.as_tcp_stack_socket().is_ok() {
+                has_stack_sockets = true;
+            }
             let events = file_ref.poll()?;
             debug!("polled events are {:?}", events);
             if pollfd.get_revents(events) {
@@ -102,10 +168,10 @@ pub fn do_poll(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usi
             }
 
             // Merge pollfds with the same fd
-            if let Some((old_pollfd, index_vec)) =
-                libos_pollfds.insert(pollfd.fd(), (*pollfd, vec![i]))
+            if let Some((old_pollfd, index_vec, _)) =
+                libos_pollfds.insert(pollfd.fd(), (*pollfd, vec![i], file_ref))
             {
-                let (new_pollfd, new_index_vec) = libos_pollfds.get_mut(&pollfd.fd()).unwrap();
+                let (new_pollfd, new_index_vec, _) = libos_pollfds.get_mut(&pollfd.fd()).unwrap();
                 new_pollfd.set_events(old_pollfd.events() | new_pollfd.events());
                 new_index_vec.extend_from_slice(&index_vec);
             }
@@ -114,72 +180,159 @@ pub fn do_poll(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usi
 
         if let Ok(socket) = file_ref.as_socket() {
             let fd = socket.fd() as FileDesc;
+            if socket.has_tls_plaintext() || socket.prefetched_len() > 0 {
+                buffered_readable_pollfds.push(host_pollfds.len());
+            }
             index_host_pollfds.push(i);
             host_pollfds.push(PollEvent::new(fd, pollfd.events()));
         } else if let Ok(eventfd) = file_ref.as_event() {
             let fd = eventfd.get_host_fd() as FileDesc;
             index_host_pollfds.push(i);
             host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(dev_mem_pressure) = file_ref.as_dev_mem_pressure() {
+            let fd = dev_mem_pressure.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(dev_security_events) = file_ref.as_dev_security_events() {
+            let fd = dev_security_events.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(uevent_socket) = file_ref.as_uevent_socket() {
+            let fd = uevent_socket.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(pidfd) = file_ref.as_pidfd() {
+            let fd = pidfd.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(userfault_file) = file_ref.as_userfault_file() {
+            let fd = userfault_file.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(inotify_file) = file_ref.as_inotify() {
+            let fd = inotify_file.get_host_fd() as FileDesc;
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(fd, pollfd.events()));
+        } else if let Ok(stdin) = file_ref.as_stdin() {
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(stdin.get_host_fd(), pollfd.events()));
+        } else if let Ok(stdout) = file_ref.as_stdout() {
+            index_host_pollfds.push(i);
+            host_pollfds.push(PollEvent::new(stdout.get_host_fd(), pollfd.events()));
         } else {
             return_errno!(EBADF, "not a supported file type");
         }
+        host_file_refs.push(file_ref);
     }
 
-    let notifier_host_fd = THREAD_NOTIFIERS
-        .lock()
-        .unwrap()
-        .get(&current.tid())
-        .unwrap()
-        .get_host_fd();
+    debug!("number of ready libos fd is {}", libos_ready_num);
 
-    debug!(
-        "number of ready libos fd is {}; notifier_host_fd is {}",
-        libos_ready_num, notifier_host_fd
-    );
-
-    let ret = if libos_ready_num != 0 {
-        // Clear the status of notifier before wait
-        clear_notifier_status(current!().tid())?;
+    let ret = if libos_ready_num != 0 || !buffered_readable_pollfds.is_empty() {
+        let mut zero_timeout: timeval_t = timeval_t::new(0, 0);
+        let waiter = Waiter::new()?;
+        do_poll_in_host(
+            &mut host_pollfds,
+            &mut zero_timeout,
+            waiter.notifier_host_fd(),
+        )?
+    } else {
+        // The waiter clears the notifier before the events are queued, and removes
+        // them on return
+        let mut waiter = Waiter::new()?;
+        let host_fds: Vec<c_int> = host_pollfds
+            .iter()
+            .map(|pollfd| pollfd.fd() as c_int)
+            .collect();
+        waiter.register_host_fds(&host_fds);
+        for (pollfd, _, file_ref) in libos_pollfds.values() {
+            waiter.enqueue(file_ref, IoEvent::Poll(*pollfd))?;
+        }
+        // Check again as the LibOS files may become ready before the events are
+        // queued, whose notifications would have been missed
+        let is_libos_ready = libos_pollfds.values().any(|(pollfd, _, file_ref)| {
+            let mut pollfd = *pollfd;
+            file_ref
+                .poll()
+                .map_or(false, |events| pollfd.get_revents(events))
+        });
 
+        // The sockets of the stack may become ready when a frame arrives at the tap
+        // device, or when a timer of the stack expires, e.g., for retransmission
+        let stack_wakeup = if has_stack_sockets && !is_libos_ready {
+            stack::next_wakeup()
+        } else {
+            None
+        };
         let mut zero_timeout: timeval_t = timeval_t::new(0, 0);
+        let mut stack_timeout: timeval_t;
+        let timeout = if is_libos_ready {
+            &mut zero_timeout as *mut timeval_t
+        } else {
+            timeout
+        };
+        let timeout = match stack_wakeup {
+            Some((stack_host_fd, delay)) => {
+                host_pollfds.push(PollEvent::new(stack_host_fd as u32, PollEventFlags::POLLIN));
+                match delay {
+                    Some(delay)
+                        if timeout.is_null() || delay < unsafe { *timeout }.as_duration() =>
+                    {
+                        stack_timeout = timeval_t::from(delay);
+                        &mut stack_timeout as *mut timeval_t
+                    }
+                    _ => timeout,
+                }
+            }
+            None => timeout,
+        };
 
-        do_poll_in_host(&mut host_pollfds, &mut zero_timeout, notifier_host_fd)?
-    } else {
-        host_pollfds.push(PollEvent::new(
-            notifier_host_fd as u32,
-            PollEventFlags::POLLIN,
-        ));
-        // Clear the status of notifier before queue
-        clear_notifier_status(current!().tid())?;
-
-        for (fd, (pollfd, _)) in &libos_pollfds {
-            let file_ref = current.file(*fd)?;
-            file_ref.enqueue_event(IoEvent::Poll(*pollfd))?;
+        let (mut ret, is_notified) = waiter.wait(&mut host_pollfds, timeout)?;
+        // Pop the tap device of the stack, whose readiness is reflected by polling
+        // the sockets of the stack below
+        if stack_wakeup.is_some() && !host_pollfds.pop().unwrap().revents().is_empty() {
+            stack::set_rx_ready();
+            ret -= 1;
         }
-        let ret = do_poll_in_host(&mut host_pollfds, timeout, notifier_host_fd)?;
-        // Pop the notifier first
-        if !host_pollfds.pop().unwrap().revents().is_empty() {
+        if is_notified {
             notified = 1;
         }
-        // Set the return events and dequeue
-        for (fd, (pollfd, index_vec)) in &libos_pollfds {
-            let file_ref = current.file(*fd)?;
+        // The readiness of the host files may be invalidated by other processes,
+        // e.g., by shutting down a shared socket. Poll the host files again for the
+        // up-to-date events.
+        let ret = if is_notified && ret == 0 && !host_pollfds.is_empty() {
+            let mut zero_timeout: timeval_t = timeval_t::new(0, 0);
+            do_poll_in_host(
+                &mut host_pollfds,
+                &mut zero_timeout,
+                waiter.notifier_host_fd(),
+            )? + notified
+        } else {
+            ret + notified
+        };
+        // Set the return events, and the events are dequeued as the waiter drops
+        for (_, index_vec, file_ref) in libos_pollfds.values() {
             let events = file_ref.poll()?;
             for i in index_vec {
                 if pollfds[*i].get_revents(events) {
                     libos_ready_num += 1;
                 }
             }
-            file_ref.dequeue_event()?;
         }
         ret
     };
 
     // Copy back revents for host pollfd
     for (i, pollfd) in host_pollfds.iter().enumerate() {
-        if pollfds[index_host_pollfds[i]].get_revents(pollfd.revents()) {
+        let host_ready = pollfds[index_host_pollfds[i]].get_revents(pollfd.revents());
+        if host_ready {
             host_ready_num += 1;
         }
+        if buffered_readable_pollfds.contains(&i) {
+            let pollfd = &mut pollfds[index_host_pollfds[i]];
+            if pollfd.get_revents(pollfd.revents() | PollEventFlags::POLLIN) && !host_ready {
+                libos_ready_num += 1;
+            }
+        }
     }
 
     assert!(ret == host_ready_num + notified);
@@ -187,14 +340,66 @@ pub fn do_poll(pollfds: &mut [PollEvent], timeout: *mut timeval_t) -> Result<usi
     Ok(host_ready_num + libos_ready_num)
 }
 
-fn do_poll_in_host(
+/// Wait until a host file is ready for the events, the timeout expires, or the
+/// current thread is notified, e.g., interrupted by signals
+pub fn wait_host_fd(
+    host_fd: FileDesc,
+    events: PollEventFlags,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    clear_notifier_status(current!().tid())?;
+    wait_host_fd_or_notification(host_fd, events, timeout)
+}
+
+/// Same as `wait_host_fd`, except that the notifier is not cleared here. The caller
+/// clears it before checking what to wait for, so that the notifications since the
+/// check are not missed.
+pub fn wait_host_fd_or_notification(
+    host_fd: FileDesc,
+    events: PollEventFlags,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let notifier_host_fd = THREAD_NOTIFIERS
+        .lock()
+        .unwrap()
+        .get(&current!().tid())
+        .unwrap()
+        .get_host_fd();
+    let mut host_pollfds = [
+        PollEvent::new(host_fd, events),
+        PollEvent::new(notifier_host_fd as u32, PollEventFlags::POLLIN),
+    ];
+    let mut timeout = timeout.map(timeval_t::from);
+    let timeout_ptr = match &mut timeout {
+        Some(timeout) => timeout as *mut timeval_t,
+        None => std::ptr::null_mut(),
+    };
+    do_poll_in_host(&mut host_pollfds, timeout_ptr, notifier_host_fd)?;
+    Ok(())
+}
+
+/// Poll a host file without blocking, and return its events
+pub fn poll_host_fd(host_fd: FileDesc, events: PollEventFlags) -> Result<PollEventFlags> {
+    let notifier_host_fd = THREAD_NOTIFIERS
+        .lock()
+        .unwrap()
+        .get(&current!().tid())
+        .unwrap()
+        .get_host_fd();
+    let mut host_pollfds = [PollEvent::new(host_fd, events)];
+    let mut zero_timeout: timeval_t = timeval_t::new(0, 0);
+    do_poll_in_host(&mut host_pollfds, &mut zero_timeout, notifier_host_fd)?;
+    Ok(host_pollfds[0].revents())
+}
+
+pub(super) fn do_poll_in_host(
     mut host_pollfds: &mut [PollEvent],
     timeout: *mut timeval_t,
     notifier_host_fd: c_int,
 ) -> Result<usize> {
     let (host_pollfds_ptr, host_pollfds_len) = host_pollfds.as_mut_ptr_and_len();
 
-    let ret = try_libc!({
+    let ret = try_libc!(crate::sched::account_wait(|| {
         let mut retval: c_int = 0;
         let status = occlum_ocall_poll(
             &mut retval,
@@ -206,12 +411,44 @@ fn do_poll_in_host(
         assert!(status == sgx_status_t::SGX_SUCCESS);
 
         retval
-    }) as usize;
+    })) as usize;
 
     assert!(ret <= host_pollfds.len());
     Ok(ret)
 }
 
+/// The deadline of a blocking operation with a timeout, e.g., given by
+/// SO_RCVTIMEO, which is measured against the monotonic clock
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline(Option<Duration>);
+
+impl Deadline {
+    /// The deadline after `timeout`, which never comes if `timeout` is None
+    pub fn after(timeout: Option<Duration>) -> Self {
+        // A timeout that is too long to be represented is infinite
+        Self(timeout.and_then(|timeout| now().checked_add(timeout)))
+    }
+
+    /// The time left to wait, which is None if infinite. It fails with EAGAIN if
+    /// the deadline has passed, as a blocking socket operation does on timeout.
+    pub fn remaining(&self) -> Result<Option<Duration>> {
+        let deadline = match self.0 {
+            Some(deadline) => deadline,
+            None => return Ok(None),
+        };
+        match deadline.checked_sub(now()) {
+            Some(remaining) if remaining > Duration::from_secs(0) => Ok(Some(remaining)),
+            _ => return_errno!(EAGAIN, "the timeout expired"),
+        }
+    }
+}
+
+fn now() -> Duration {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration())
+        .unwrap_or_default()
+}
+
 extern "C" {
     fn occlum_ocall_poll(
         ret: *mut c_int,
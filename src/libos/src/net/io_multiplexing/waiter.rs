@@ -0,0 +1,96 @@
+//! The waiter of a thread that waits for the LibOS files and the host files at once.
+//!
+//! The LibOS files (e.g., unix sockets and pipes) have no host fds to poll. Instead,
+//! a thread queues its events on the wait queues of the files, and is notified by
+//! the files through its notifier, which is an eventfd of the host. The notifier is
+//! polled in the host along with the host files, so it interrupts the host poll as
+//! soon as any LibOS file may become ready.
+//!
+//! The waiter clears the notifier when it is created, so the notifications since
+//! then are never missed. The events queued and the host fds registered through the
+//! waiter are removed when it is dropped, including on the paths of errors.
+use super::host_fd_registry::{register_host_fd_waiter, unregister_host_fd_waiter};
+use super::poll::do_poll_in_host;
+use super::*;
+
+pub struct Waiter {
+    tid: pid_t,
+    notifier_host_fd: c_int,
+    // The LibOS files with the events of the current thread queued
+    queued_files: Vec<FileRef>,
+    // The host fds that the current thread is registered as blocked on
+    host_fds: Vec<c_int>,
+}
+
+impl Waiter {
+    pub fn new() -> Result<Self> {
+        let tid = current!().tid();
+        let notifier_host_fd = THREAD_NOTIFIERS
+            .lock()
+            .unwrap()
+            .get(&tid)
+            .unwrap()
+            .get_host_fd();
+        clear_notifier_status(tid)?;
+        Ok(Self {
+            tid,
+            notifier_host_fd,
+            queued_files: Vec::new(),
+            host_fds: Vec::new(),
+        })
+    }
+
+    pub fn notifier_host_fd(&self) -> c_int {
+        self.notifier_host_fd
+    }
+
+    /// Queue the event on the LibOS file, which notifies the current thread when
+    /// the file may become ready. The caller should check the file again after it
+    /// is queued, as the file may become ready before.
+    pub fn enqueue(&mut self, file: &FileRef, event: IoEvent) -> Result<()> {
+        file.enqueue_event(event)?;
+        self.queued_files.push(file.clone());
+        Ok(())
+    }
+
+    /// Register the current thread as blocked on the host files, whose readiness
+    /// may be invalidated by other processes (see `invalidate_host_fd`)
+    pub fn register_host_fds(&mut self, host_fds: &[c_int]) {
+        register_host_fd_waiter(host_fds, self.tid);
+        self.host_fds.extend_from_slice(host_fds);
+    }
+
+    /// Poll the host files along with the notifier until any of them is ready or
+    /// the timeout expires.
+    ///
+    /// Return the number of the ready host files, and whether the current thread is
+    /// notified, e.g., by a LibOS file or a signal.
+    pub fn wait(
+        &self,
+        host_pollfds: &mut Vec<PollEvent>,
+        timeout: *mut timeval_t,
+    ) -> Result<(usize, bool)> {
+        host_pollfds.push(PollEvent::new(
+            self.notifier_host_fd as FileDesc,
+            PollEventFlags::POLLIN,
+        ));
+        let ret = do_poll_in_host(host_pollfds, timeout, self.notifier_host_fd);
+        let notified = !host_pollfds.pop().unwrap().revents().is_empty();
+        let ret = ret?;
+        Ok((ret - notified as usize, notified))
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        for file in &self.queued_files {
+            if let Err(e) = file.dequeue_event() {
+                warn!(
+                    "failed to dequeue the event of thread {}: {:?}",
+                    self.tid, e
+                );
+            }
+        }
+        unregister_host_fd_waiter(&self.host_fds, self.tid);
+    }
+}
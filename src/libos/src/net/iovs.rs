@@ -2,15 +2,36 @@
 
 use super::*;
 use crate::untrusted::SliceAsPtrAndLen;
+use crate::util::small_vec::SmallVec;
 use std::iter::Iterator;
 
+/// The max number of I/O vectors in one system call, which is UIO_MAXIOV on Linux
+pub const IOV_MAX: usize = 1024;
+/// The number of I/O vectors that are kept inline without heap allocation
+pub const INLINE_IOVS: usize = 8;
+
+/// A vector of I/O vectors, which are kept inline if there are only a few of them
+pub type IovVec<T> = SmallVec<T, INLINE_IOVS>;
+
+/// Check the total length of I/O vectors, which must not exceed SSIZE_MAX
+pub fn check_iovs_total_len(lens: impl Iterator<Item = usize>) -> Result<usize> {
+    let mut total_len: usize = 0;
+    for len in lens {
+        total_len = total_len
+            .checked_add(len)
+            .filter(|total_len| *total_len <= isize::MAX as usize)
+            .ok_or_else(|| errno!(EINVAL, "the total length of iovecs exceeds SSIZE_MAX"))?;
+    }
+    Ok(total_len)
+}
+
 /// A memory safe, immutable version of C iovec array
 pub struct Iovs<'a> {
-    iovs: Vec<&'a [u8]>,
+    iovs: IovVec<&'a [u8]>,
 }
 
 impl<'a> Iovs<'a> {
-    pub fn new(slices: Vec<&'a [u8]>) -> Iovs {
+    pub fn new(slices: IovVec<&'a [u8]>) -> Iovs {
         Self { iovs: slices }
     }
 
@@ -25,11 +46,11 @@ impl<'a> Iovs<'a> {
 
 /// A memory safe, mutable version of C iovec array
 pub struct IovsMut<'a> {
-    iovs: Vec<&'a mut [u8]>,
+    iovs: IovVec<&'a mut [u8]>,
 }
 
 impl<'a> IovsMut<'a> {
-    pub fn new(slices: Vec<&'a mut [u8]>) -> Self {
+    pub fn new(slices: IovVec<&'a mut [u8]>) -> Self {
         Self { iovs: slices }
     }
 
@@ -2,21 +2,52 @@ use super::*;
 use std;
 use untrusted::{SliceAsMutPtrAndLen, SliceAsPtrAndLen, UntrustedSliceAlloc};
 
+mod addr_families;
+mod async_io;
+mod cmsg;
+mod dbus_bridge;
+mod egress_limit;
+mod firewall;
+mod host_dns;
+mod host_fd_exhaustion;
+mod icmp;
 mod io_multiplexing;
 mod iovs;
 mod msg;
 mod msg_flags;
+mod net_dev;
+mod proc_entry;
+mod sock_addr;
+mod socket;
 mod socket_file;
+mod socket_stats;
+mod sockopt;
+mod stack;
 mod syscalls;
+mod tls;
+mod uevent_socket;
+mod unix_relay;
 mod unix_socket;
 
+pub use self::host_dns::{addrinfo_hints, occlum_addrinfo};
+pub use self::host_fd_exhaustion::format_host_fd_exhaustion;
 pub use self::io_multiplexing::{
-    clear_notifier_status, notify_thread, wait_for_notification, EpollEvent, IoEvent, PollEvent,
-    PollEventFlags, THREAD_NOTIFIERS,
+    clear_notifier_status, notify_thread, poll_host_fd, wait_for_notification,
+    wait_for_notification_timeout, wait_host_fd, Deadline, EpollEvent, EpollEventFlags, IoEvent,
+    PollEvent, PollEventFlags, THREAD_NOTIFIERS,
 };
-pub use self::iovs::{Iovs, IovsMut, SliceAsLibcIovec};
+pub use self::iovs::{check_iovs_total_len, IovVec, Iovs, IovsMut, SliceAsLibcIovec, IOV_MAX};
 pub use self::msg::{msghdr, msghdr_mut, MsgHdr, MsgHdrMut};
 pub use self::msg_flags::{MsgHdrFlags, RecvFlags, SendFlags};
-pub use self::socket_file::{AsSocket, SocketFile};
+pub use self::net_dev::{get_net_dev_stats, NetDevStats};
+pub use self::proc_entry::{TcpEntryState, TcpSocketEntry, UnixSocketEntry};
+pub use self::sock_addr::SockAddr;
+pub use self::socket::{AsDynSocket, Socket};
+pub use self::socket_file::{flush_pending_sends, AsSocket, SocketFile};
+pub use self::socket_stats::{occlum_sock_stats, SocketStats};
+pub use self::stack::{init_stack, AsTcpStackSocket, TcpStackSocket};
 pub use self::syscalls::*;
+pub use self::tls::{client_config_from_pem, server_config_from_pem, TlsSession};
+pub use self::uevent_socket::{AsUeventSocket, UeventSocket};
+pub use self::unix_relay::unix_relay_work;
 pub use self::unix_socket::{AsUnixSocket, UnixSocketFile};
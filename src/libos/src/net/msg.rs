@@ -59,7 +59,7 @@ impl<'a> MsgHdr<'a> {
                     .iter()
                     .flat_map(|iov| new_optional_slice(iov.iov_base as *const u8, iov.iov_len))
                     .collect(),
-                None => Vec::new(),
+                None => IovVec::new(),
             };
             Iovs::new(iovs_vec)
         };
@@ -119,7 +119,7 @@ impl<'a> MsgHdrMut<'a> {
                     .iter()
                     .flat_map(|iov| new_optional_slice_mut(iov.iov_base as *mut u8, iov.iov_len))
                     .collect(),
-                None => Vec::new(),
+                None => IovVec::new(),
             };
             IovsMut::new(iovs_vec)
         };
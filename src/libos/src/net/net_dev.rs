@@ -0,0 +1,105 @@
+//! The statistics of the network interfaces on the host.
+use super::*;
+
+/// The statistics of a network interface, which are given by the host
+#[derive(Debug, Clone, Default)]
+pub struct NetDevStats {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+/// Get the statistics of all the network interfaces on the host
+pub fn get_net_dev_stats() -> Result<Vec<NetDevStats>> {
+    // The number of interfaces should be small; retry if it is not
+    const INIT_MAX_COUNT: usize = 16;
+    const MAX_COUNT: usize = 1024;
+
+    let mut max_count = INIT_MAX_COUNT;
+    loop {
+        let mut buf = vec![occlum_net_dev_stats::default(); max_count];
+        let count = try_libc!({
+            let mut ret = 0;
+            let status = occlum_ocall_get_net_dev_stats(
+                &mut ret,
+                buf.as_mut_ptr() as *mut u8,
+                max_count * std::mem::size_of::<occlum_net_dev_stats>(),
+            );
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            ret
+        }) as usize;
+        if count > max_count {
+            if count > MAX_COUNT {
+                return_errno!(EIO, "too many network interfaces given by the host");
+            }
+            max_count = count;
+            continue;
+        }
+        return buf[..count].iter().map(|stats| stats.sanitize()).collect();
+    }
+}
+
+/// The statistics of a network interface returned by `occlum_ocall_get_net_dev_stats`.
+///
+/// It must be kept in sync with the C definition in pal/src/ocalls/net.c.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+struct occlum_net_dev_stats {
+    name: [u8; IFNAMSIZ],
+    rx_bytes: u64,
+    rx_packets: u64,
+    rx_errors: u64,
+    rx_dropped: u64,
+    tx_bytes: u64,
+    tx_packets: u64,
+    tx_errors: u64,
+    tx_dropped: u64,
+}
+
+/// The max length of the name of a network interface, including the NUL
+pub const IFNAMSIZ: usize = 16;
+
+impl occlum_net_dev_stats {
+    /// Check the statistics given by the untrusted host
+    fn sanitize(&self) -> Result<NetDevStats> {
+        let name_len = self
+            .name
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| errno!(EIO, "the interface name is not terminated"))?;
+        let name = &self.name[..name_len];
+        // The names of interfaces are shown in /proc/net/dev, which must not be
+        // able to forge the other lines or columns
+        let is_valid_name = name_len > 0
+            && name
+                .iter()
+                .all(|&b| b.is_ascii_graphic() && b != b':' && b != b'/');
+        if !is_valid_name {
+            return_errno!(EIO, "invalid interface name given by the host");
+        }
+        if self.rx_packets > self.rx_bytes || self.tx_packets > self.tx_bytes {
+            return_errno!(EIO, "inconsistent interface statistics given by the host");
+        }
+        Ok(NetDevStats {
+            name: String::from_utf8(name.to_vec()).unwrap(),
+            rx_bytes: self.rx_bytes,
+            rx_packets: self.rx_packets,
+            rx_errors: self.rx_errors,
+            rx_dropped: self.rx_dropped,
+            tx_bytes: self.tx_bytes,
+            tx_packets: self.tx_packets,
+            tx_errors: self.tx_errors,
+            tx_dropped: self.tx_dropped,
+        })
+    }
+}
+
+extern "C" {
+    fn occlum_ocall_get_net_dev_stats(ret: *mut i32, buf: *mut u8, buf_len: usize) -> sgx_status_t;
+}
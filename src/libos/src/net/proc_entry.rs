@@ -0,0 +1,37 @@
+//! The entries of the sockets in /proc/net/unix and /proc/net/tcp.
+use super::*;
+
+/// An entry of /proc/net/unix
+pub struct UnixSocketEntry {
+    pub socket_type: c_int,
+    pub is_listening: bool,
+    pub is_connected: bool,
+    /// The path bound by the socket, or by the listening socket that accepts it
+    pub path: Option<String>,
+}
+
+/// An entry of /proc/net/tcp
+pub struct TcpSocketEntry {
+    pub local: libc::sockaddr_in,
+    /// The address of the peer, which is None if not connected
+    pub remote: Option<libc::sockaddr_in>,
+    pub state: TcpEntryState,
+    pub tx_queue: usize,
+    pub rx_queue: usize,
+}
+
+/// The states of TCP, which are numbered as Linux
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TcpEntryState {
+    Established = 1,
+    SynSent = 2,
+    SynRecv = 3,
+    FinWait1 = 4,
+    FinWait2 = 5,
+    TimeWait = 6,
+    Close = 7,
+    CloseWait = 8,
+    LastAck = 9,
+    Listen = 10,
+    Closing = 11,
+}
@@ -0,0 +1,139 @@
+use super::*;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ptr;
+
+/// A socket address given by the host, which is parsed in the enclave before it
+/// is used or copied to the user, so that a malformed address is never passed on
+#[derive(Clone)]
+pub enum SockAddr {
+    Inet(libc::sockaddr_in),
+    Inet6(libc::sockaddr_in6),
+    /// The address of any other family, which is kept as is
+    Other(Vec<u8>),
+}
+
+impl SockAddr {
+    /// Parse the address in `bytes`, whose length is the one reported by the host.
+    ///
+    /// The length must cover the address of the family, and must not exceed
+    /// sockaddr_storage, which is the most that the kernel ever returns.
+    pub fn try_from_raw(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < size_of::<libc::sa_family_t>()
+            || bytes.len() > size_of::<libc::sockaddr_storage>()
+        {
+            return_errno!(EINVAL, "invalid length of socket address");
+        }
+        let family = libc::sa_family_t::from_ne_bytes([bytes[0], bytes[1]]) as c_int;
+        let addr = match family {
+            libc::AF_INET => {
+                let mut addr: libc::sockaddr_in = read_sockaddr(bytes)?;
+                addr.sin_zero = [0; 8];
+                SockAddr::Inet(addr)
+            }
+            libc::AF_INET6 => SockAddr::Inet6(read_sockaddr(bytes)?),
+            _ => SockAddr::Other(bytes.to_vec()),
+        };
+        Ok(addr)
+    }
+
+    /// Parse the address of a socket of `domain` given by the host, e.g., by
+    /// getsockname, getpeername, accept or recvfrom.
+    ///
+    /// The address must be of the domain of the socket. An IPv6 address is
+    /// normalized, so that it is the same as the one given by Linux.
+    pub fn from_host(bytes: &[u8], domain: c_int) -> Result<Self> {
+        let mut addr =
+            Self::try_from_raw(bytes).map_err(|_| errno!(EIO, "invalid address from the host"))?;
+        if addr.family() != domain {
+            return_errno!(EIO, "invalid family of the address from the host");
+        }
+        if let SockAddr::Inet6(addr) = &mut addr {
+            normalize_sockaddr_in6(addr)?;
+        }
+        Ok(addr)
+    }
+
+    /// The IP address, where an IPv4-mapped IPv6 address (i.e., ::ffff:a.b.c.d)
+    /// is the IPv4 address that it carries
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            SockAddr::Inet(addr) => Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into()),
+            SockAddr::Inet6(addr) => Some(unmap_ipv6(Ipv6Addr::from(addr.sin6_addr.s6_addr))),
+            SockAddr::Other(_) => None,
+        }
+    }
+
+    pub fn family(&self) -> c_int {
+        match self {
+            SockAddr::Inet(_) => libc::AF_INET,
+            SockAddr::Inet6(_) => libc::AF_INET6,
+            SockAddr::Other(bytes) => {
+                libc::sa_family_t::from_ne_bytes([bytes[0], bytes[1]]) as c_int
+            }
+        }
+    }
+
+    /// The bytes of the address, whose length is the full length of the address
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            SockAddr::Inet(addr) => bytes_of(addr),
+            SockAddr::Inet6(addr) => bytes_of(addr),
+            SockAddr::Other(bytes) => bytes,
+        }
+    }
+}
+
+/// Normalize an IPv6 address given by the host.
+///
+/// Linux gives the scope ID only for the addresses of link-local scope, which are
+/// ambiguous without the interface, and never for IPv4-mapped addresses. A scope
+/// ID of any other address is rejected rather than passed on, as the user would
+/// use it to pick the interface, e.g., when it connects back. The flow info of an
+/// IPv4-mapped address is cleared, which the IPv4 traffic does not carry.
+fn normalize_sockaddr_in6(addr: &mut libc::sockaddr_in6) -> Result<()> {
+    let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+    if addr.sin6_scope_id != 0 && !is_link_scope(&ip) {
+        return_errno!(EIO, "invalid scope ID of the address from the host");
+    }
+    if let IpAddr::V4(_) = unmap_ipv6(ip) {
+        addr.sin6_flowinfo = 0;
+    }
+    Ok(())
+}
+
+/// Get the IPv4 address that an IPv4-mapped IPv6 address carries, or the IPv6
+/// address as is if it is not mapped
+pub fn unmap_ipv6(ip: Ipv6Addr) -> IpAddr {
+    let octets = ip.octets();
+    if octets[..10].iter().all(|&b| b == 0) && octets[10..12] == [0xff, 0xff] {
+        let mut ipv4_octets = [0u8; 4];
+        ipv4_octets.copy_from_slice(&octets[12..]);
+        IpAddr::from(ipv4_octets)
+    } else {
+        IpAddr::V6(ip)
+    }
+}
+
+/// Whether the IPv6 address is of link-local scope, i.e., a unicast address in
+/// fe80::/10, or a multicast address of interface-local or link-local scope
+fn is_link_scope(ip: &Ipv6Addr) -> bool {
+    let octets = ip.octets();
+    match octets[0] {
+        0xfe => octets[1] & 0xc0 == 0x80,
+        0xff => matches!(octets[1] & 0x0f, 0x1 | 0x2),
+        _ => false,
+    }
+}
+
+/// Read an address of fixed length from `bytes`, which must not be shorter
+fn read_sockaddr<T: Copy>(bytes: &[u8]) -> Result<T> {
+    if bytes.len() < size_of::<T>() {
+        return_errno!(EINVAL, "socket address is too short for the family");
+    }
+    Ok(unsafe { ptr::read_unaligned(bytes.as_ptr() as *const T) })
+}
+
+fn bytes_of<T>(addr: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(addr as *const T as *const u8, size_of::<T>()) }
+}
@@ -0,0 +1,57 @@
+//! The operations shared by all the kinds of sockets.
+//!
+//! The host sockets, the unix sockets, the uevent sockets and the sockets of the
+//! in-enclave TCP stack all implement `Socket`, so that the syscalls whose
+//! arguments mean the same for every kind of socket are dispatched on a
+//! `dyn Socket`, instead of trying every kind of socket in turn.
+use super::*;
+
+/// The socket operations that do not depend on the kind of the socket
+pub trait Socket {
+    /// Bind the socket to the address given as the bytes of `struct sockaddr_*`
+    fn bind(&self, addr: &[u8]) -> Result<()>;
+
+    fn listen(&self, backlog: c_int) -> Result<()>;
+
+    fn shutdown(&self, how: c_int) -> Result<()>;
+
+    /// Get the local address as the bytes of `struct sockaddr_*`
+    fn addr(&self) -> Result<Vec<u8>>;
+
+    /// Get the address of the peer as the bytes of `struct sockaddr_*`
+    fn peer_addr(&self) -> Result<Vec<u8>>;
+
+    fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()>;
+
+    /// Get the value of the option, which is no longer than `max_len` bytes
+    fn getsockopt(&self, level: c_int, optname: c_int, max_len: usize) -> Result<Vec<u8>>;
+}
+
+pub trait AsDynSocket {
+    fn as_dyn_socket(&self) -> Result<&dyn Socket>;
+}
+
+impl AsDynSocket for FileRef {
+    fn as_dyn_socket(&self) -> Result<&dyn Socket> {
+        let any = self.as_any();
+        if let Some(socket) = any.downcast_ref::<SocketFile>() {
+            Ok(socket)
+        } else if let Some(socket) = any.downcast_ref::<UnixSocketFile>() {
+            Ok(socket)
+        } else if let Some(socket) = any.downcast_ref::<TcpStackSocket>() {
+            Ok(socket)
+        } else if let Some(socket) = any.downcast_ref::<UeventSocket>() {
+            Ok(socket)
+        } else {
+            return_errno!(EBADF, "not a socket")
+        }
+    }
+}
+
+/// Get the bytes of a socket address of fixed length
+pub fn sockaddr_bytes_of<T>(sockaddr: &T) -> Vec<u8> {
+    let addr_bytes = unsafe {
+        std::slice::from_raw_parts(sockaddr as *const T as *const u8, std::mem::size_of::<T>())
+    };
+    addr_bytes.to_vec()
+}
@@ -0,0 +1,118 @@
+use super::*;
+use crate::net::async_io::{async_io_engine, AsyncIoEngine, AsyncIoOp, ASYNC_IO_MAX_LEN};
+use crate::untrusted::UNTRUSTED_ARENA;
+use signal::{has_deliverable_signals, send_sigpipe_on_epipe};
+
+impl SocketFile {
+    /// Receive the data into `buf` by the asynchronous OCalls, without exiting the
+    /// enclave unless the thread has to wait. None is returned if the async I/O is
+    /// not configured or not applicable, where the data is received by OCalls.
+    pub fn submit_read(&self, buf: &mut [u8], flags: RecvFlags) -> Option<Result<usize>> {
+        let supported_flags = RecvFlags::MSG_DONTWAIT | RecvFlags::MSG_PEEK;
+        if !supported_flags.contains(flags) {
+            return None;
+        }
+        let engine = self.async_io_engine()?;
+        let len = min(buf.len(), ASYNC_IO_MAX_LEN);
+        if len == 0 {
+            return None;
+        }
+        let u_buf = match UNTRUSTED_ARENA.alloc(len) {
+            Ok(u_buf) => u_buf,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let deadline = Deadline::after(self.recv_timeout());
+        loop {
+            match engine.execute(AsyncIoOp::Recv, self.host_fd, &u_buf, len, flags.bits())? {
+                Ok(bytes_recvd) => {
+                    let u_data =
+                        unsafe { std::slice::from_raw_parts(u_buf.as_mut_ptr(), bytes_recvd) };
+                    buf[..bytes_recvd].copy_from_slice(u_data);
+                    if !flags.contains(RecvFlags::MSG_PEEK) {
+                        self.stats.account_recvd(bytes_recvd);
+                    }
+                    return Some(Ok(bytes_recvd));
+                }
+                Err(e) if e.errno() == EAGAIN => {}
+                Err(e) => return Some(Err(e)),
+            }
+            let dontwait = flags.contains(RecvFlags::MSG_DONTWAIT);
+            if let Err(e) = self.wait_async_io(dontwait, PollEventFlags::POLLIN, &deadline) {
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// Send the data in `buf` by the asynchronous OCalls, like `submit_read`. At
+    /// most ASYNC_IO_MAX_LEN bytes are sent at once.
+    pub fn submit_write(&self, buf: &[u8], flags: SendFlags) -> Option<Result<usize>> {
+        let supported_flags = SendFlags::MSG_DONTWAIT | SendFlags::MSG_NOSIGNAL;
+        if !supported_flags.contains(flags) {
+            return None;
+        }
+        let engine = self.async_io_engine()?;
+        let len = min(buf.len(), ASYNC_IO_MAX_LEN);
+        if len == 0 {
+            return None;
+        }
+        let u_buf = match UNTRUSTED_ARENA.alloc(len) {
+            Ok(u_buf) => u_buf,
+            Err(e) => return Some(Err(e)),
+        };
+        unsafe { std::slice::from_raw_parts_mut(u_buf.as_mut_ptr(), len) }
+            .copy_from_slice(&buf[..len]);
+
+        // SIGPIPE is sent by the LibOS rather than by the host
+        let raw_flags = (flags | SendFlags::MSG_NOSIGNAL).bits();
+        let deadline = Deadline::after(self.send_timeout());
+        loop {
+            match engine.execute(AsyncIoOp::Send, self.host_fd, &u_buf, len, raw_flags)? {
+                Ok(bytes_sent) => {
+                    self.stats.account_sent(bytes_sent);
+                    return Some(Ok(bytes_sent));
+                }
+                Err(e) if e.errno() == EAGAIN => {}
+                Err(e) if flags.contains(SendFlags::MSG_NOSIGNAL) => return Some(Err(e)),
+                Err(e) => return Some(send_sigpipe_on_epipe(Err(e))),
+            }
+            let dontwait = flags.contains(SendFlags::MSG_DONTWAIT);
+            if let Err(e) = self.wait_async_io(dontwait, PollEventFlags::POLLOUT, &deadline) {
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// The engine of the async I/O if it applies to the socket. The messages of
+    /// the other types of sockets must not be split by ASYNC_IO_MAX_LEN, and the data
+    /// of TLS is sent and received by the session.
+    fn async_io_engine(&self) -> Option<&'static AsyncIoEngine> {
+        if self.socket_type != libc::SOCK_STREAM || self.is_tls() {
+            return None;
+        }
+        async_io_engine()
+    }
+
+    /// Wait for the host socket to be ready for the request that would block, which
+    /// fails with EAGAIN if the socket is non-blocking or the deadline passes
+    fn wait_async_io(
+        &self,
+        dontwait: bool,
+        events: PollEventFlags,
+        deadline: &Deadline,
+    ) -> Result<()> {
+        if self.is_nonblocking(dontwait)? {
+            return_errno!(EAGAIN, "the async I/O would block");
+        }
+        let timeout = deadline.remaining()?;
+        if let Err(e) = wait_host_fd(self.host_fd as FileDesc, events, timeout) {
+            if e.errno() != EINTR {
+                return Err(e);
+            }
+        }
+        if has_deliverable_signals() {
+            return_errno!(EINTR, "interrupted by signals");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,449 @@
+//! The buffering of the small sends and receives of the host sockets.
+//!
+//! Each send or receive of a host socket takes an OCall, which dominates the
+//! latency of small messages. If configured in `Occlum.json`, the small sends of a
+//! blocking stream socket are staged in an untrusted buffer, and coalesced into one
+//! sendmsg OCall once they add up to the flush size. The staged data is sent before
+//! the thread that stages it makes any other syscall, e.g., poll, recv or close, and
+//! before the socket is received from, shut down or closed by any thread, so that
+//! the data is never held while the application waits for the peer. The errors of
+//! sending the staged data are reported by the next send.
+//!
+//! A small receive of a stream socket prefetches more data than asked into the
+//! enclave. The size of the prefetch adapts to the traffic, up to the configured
+//! size. The prefetched data is reported by poll, select and FIONREAD, but not by
+//! the epoll of the host, so the sockets added to epoll no longer prefetch.
+//!
+//! The buffering of a socket can be turned off by SO_OCCLUM_BUFFERING of level
+//! SOL_OCCLUM.
+use super::*;
+use crate::config::LIBOS_CONFIG;
+use crate::untrusted::{UntrustedChunk, UNTRUSTED_ARENA};
+use core::sync::atomic::{AtomicBool, Ordering};
+use signal::send_sigpipe_on_epipe;
+use std::sync::Weak;
+use syscall::SyscallNum;
+
+/// Get or set whether the socket is buffered, as an int
+pub const SO_OCCLUM_BUFFERING: c_int = 2;
+
+/// The size of the first prefetch, which adapts to the traffic later
+const MIN_PREFETCH_SIZE: usize = 4 * 1024;
+
+#[derive(Debug)]
+pub struct SocketBuffering {
+    is_enabled: AtomicBool,
+    // O_NONBLOCK of the host socket, which is tracked by the LibOS so that it is
+    // known without an OCall
+    is_nonblocking: AtomicBool,
+    // Whether the socket has been added to epoll, which cannot see the prefetched data
+    is_epolled: AtomicBool,
+    send_stage: Arc<SgxMutex<SendStage>>,
+    recv_cache: SgxMutex<RecvCache>,
+}
+
+struct SendStage {
+    host_fd: c_int,
+    // The untrusted buffer, which is allocated when the data is staged
+    buf: Option<UntrustedChunk>,
+    len: usize,
+    // The error of sending the staged data, which is reported by the next send
+    error: Option<Error>,
+}
+
+#[derive(Debug)]
+struct RecvCache {
+    data: Vec<u8>,
+    pos: usize,
+    prefetch_size: usize,
+}
+
+thread_local! {
+    /// The stage of the socket that the current thread has staged data in
+    static PENDING_STAGE: RefCell<Option<Weak<SgxMutex<SendStage>>>> = RefCell::new(None);
+}
+
+impl SocketBuffering {
+    pub fn new(host_fd: c_int, is_nonblocking: bool) -> Self {
+        Self {
+            is_enabled: AtomicBool::new(true),
+            is_nonblocking: AtomicBool::new(is_nonblocking),
+            is_epolled: AtomicBool::new(false),
+            send_stage: Arc::new(SgxMutex::new(SendStage {
+                host_fd,
+                buf: None,
+                len: 0,
+                error: None,
+            })),
+            recv_cache: SgxMutex::new(RecvCache {
+                data: Vec::new(),
+                pos: 0,
+                prefetch_size: min(MIN_PREFETCH_SIZE, recv_prefetch_size()),
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.is_nonblocking.load(Ordering::Relaxed)
+    }
+
+    pub fn set_nonblocking(&self, is_nonblocking: bool) {
+        self.is_nonblocking.store(is_nonblocking, Ordering::Relaxed);
+    }
+
+    /// Stop prefetching, as the prefetched data is invisible to epoll
+    pub fn set_epolled(&self) {
+        self.is_epolled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl SocketFile {
+    /// Turn on or off the buffering of the socket. The staged data is sent before
+    /// the buffering is turned off, while the prefetched data can still be received.
+    pub fn set_buffering(&self, is_enabled: bool) -> Result<()> {
+        if !is_enabled {
+            self.flush_sends()?;
+        }
+        self.buffering
+            .is_enabled
+            .store(is_enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Send the staged data, and report the error of sending it if any
+    pub fn flush_sends(&self) -> Result<()> {
+        let mut stage = self.buffering.send_stage.lock().unwrap();
+        stage.flush();
+        match stage.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Stage the data to be sent if it is small enough. Otherwise, the staged data
+    /// is sent first, and None is returned so that the data is sent directly.
+    pub fn stage_sends(&self, bufs: &[&[u8]], flags: SendFlags) -> Option<Result<usize>> {
+        let flush_size = send_flush_size();
+        if flush_size == 0 {
+            return None;
+        }
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        let ret = if self.may_stage_sends(len, flush_size, flags) {
+            self.do_stage_sends(bufs, len, flush_size)
+        } else {
+            match self.flush_sends() {
+                Ok(()) => return None,
+                Err(e) => Err(e),
+            }
+        };
+        Some(if flags.contains(SendFlags::MSG_NOSIGNAL) {
+            ret
+        } else {
+            send_sigpipe_on_epipe(ret)
+        })
+    }
+
+    fn may_stage_sends(&self, len: usize, flush_size: usize, flags: SendFlags) -> bool {
+        len > 0
+            && len < flush_size
+            && (flags - (SendFlags::MSG_NOSIGNAL | SendFlags::MSG_MORE)).is_empty()
+            && self.socket_type == libc::SOCK_STREAM
+            && self.buffering.is_enabled()
+            && !self.buffering.is_nonblocking.load(Ordering::Relaxed)
+            && !self.is_tls()
+    }
+
+    fn do_stage_sends(&self, bufs: &[&[u8]], len: usize, flush_size: usize) -> Result<usize> {
+        let send_stage = &self.buffering.send_stage;
+        // The data staged by the thread in the other socket is sent first
+        let pending_stage = PENDING_STAGE.with(|pending| pending.borrow_mut().take());
+        if let Some(pending_stage) = pending_stage.and_then(|stage| stage.upgrade()) {
+            if !Arc::ptr_eq(&pending_stage, send_stage) {
+                pending_stage.lock().unwrap().flush();
+            }
+        }
+        self.throttle_egress(None, len, SendFlags::empty())?;
+
+        let mut stage = send_stage.lock().unwrap();
+        if let Some(e) = stage.error.take() {
+            return Err(e);
+        }
+        if stage.len + len > flush_size {
+            stage.flush();
+            if let Some(e) = stage.error.take() {
+                return Err(e);
+            }
+        }
+        if stage.buf.is_none() {
+            stage.buf = Some(UNTRUSTED_ARENA.alloc(flush_size)?);
+        }
+        for buf in bufs {
+            let offset = stage.len;
+            stage.as_mut_slice()[offset..offset + buf.len()].copy_from_slice(buf);
+            stage.len += buf.len();
+        }
+        if stage.len == flush_size {
+            stage.flush();
+        } else {
+            PENDING_STAGE.with(|pending| *pending.borrow_mut() = Some(Arc::downgrade(send_stage)));
+        }
+        drop(stage);
+
+        self.stats.account_sent(len);
+        Ok(len)
+    }
+
+    /// Receive the data from the prefetched data, or prefetch the data if the
+    /// receive is small enough. Return None if the data is to be received directly.
+    pub fn recv_buffered(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> Option<Result<usize>> {
+        if self.socket_type != libc::SOCK_STREAM
+            || flags.intersects(RecvFlags::MSG_OOB | RecvFlags::MSG_ERRQUEUE)
+        {
+            return None;
+        }
+        // The peer may wait for the staged data before it sends anything
+        if send_flush_size() > 0 {
+            self.buffering.send_stage.lock().unwrap().flush();
+        }
+        if recv_prefetch_size() == 0 {
+            return None;
+        }
+
+        let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        let mut cache = self.buffering.recv_cache.lock().unwrap();
+        let ret = if cache.available() > 0 {
+            self.recv_from_cache(&mut cache, bufs, len, flags)
+        } else if self.may_prefetch(&cache, len, flags) {
+            self.prefetch(&mut cache, bufs, flags)
+        } else {
+            return None;
+        };
+        if let Ok(bytes_recvd) = ret {
+            if !flags.contains(RecvFlags::MSG_PEEK) {
+                self.stats.account_recvd(bytes_recvd);
+            }
+        }
+        Some(ret)
+    }
+
+    /// The length of the prefetched data, which is unknown to the host
+    pub fn prefetched_len(&self) -> usize {
+        // A socket being received from is reported to have no prefetched data
+        match self.buffering.recv_cache.try_lock() {
+            Ok(cache) => cache.available(),
+            Err(_) => 0,
+        }
+    }
+
+    /// The length of the staged data, which is unknown to the host
+    pub fn staged_len(&self) -> usize {
+        match self.buffering.send_stage.try_lock() {
+            Ok(stage) => stage.len,
+            Err(_) => 0,
+        }
+    }
+
+    fn may_prefetch(&self, cache: &RecvCache, len: usize, flags: RecvFlags) -> bool {
+        len > 0
+            && len < cache.prefetch_size
+            && (flags - RecvFlags::MSG_DONTWAIT).is_empty()
+            && self.buffering.is_enabled()
+            && !self.buffering.is_epolled.load(Ordering::Relaxed)
+            && !self.is_tls()
+    }
+
+    fn recv_from_cache(
+        &self,
+        cache: &mut RecvCache,
+        bufs: &mut [&mut [u8]],
+        len: usize,
+        flags: RecvFlags,
+    ) -> Result<usize> {
+        let data = &cache.data[cache.pos..];
+        let copied_len = copy_to_bufs(bufs, 0, data);
+        if flags.contains(RecvFlags::MSG_PEEK) {
+            return Ok(copied_len);
+        }
+        cache.consume(copied_len);
+        if copied_len == len || !flags.contains(RecvFlags::MSG_WAITALL) {
+            return Ok(copied_len);
+        }
+
+        // Wait for the rest of the data, and return the data received on errors
+        let rest_len = len - copied_len;
+        let mut recv_rest = || -> Result<usize> {
+            let u_slice_alloc = UntrustedSliceAlloc::new(rest_len)?;
+            let u_buf = u_slice_alloc.new_slice_mut(rest_len)?;
+            let (bytes_recvd, _, _, _) =
+                self.do_recvmsg(&mut [&mut u_buf[..]], flags, None, None)?;
+            Ok(copy_to_bufs(bufs, copied_len, &u_buf[..bytes_recvd]))
+        };
+        match recv_rest() {
+            Ok(bytes_recvd) => Ok(copied_len + bytes_recvd),
+            Err(e) => {
+                debug!("failed to receive the rest of the data: {:?}", e);
+                Ok(copied_len)
+            }
+        }
+    }
+
+    fn prefetch(
+        &self,
+        cache: &mut RecvCache,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+    ) -> Result<usize> {
+        let prefetch_size = cache.prefetch_size;
+        let u_slice_alloc = UntrustedSliceAlloc::new(prefetch_size)?;
+        let u_buf = u_slice_alloc.new_slice_mut(prefetch_size)?;
+        let (bytes_recvd, _, _, _) = self.do_recvmsg(&mut [&mut u_buf[..]], flags, None, None)?;
+        let data = &u_buf[..bytes_recvd];
+        let copied_len = copy_to_bufs(bufs, 0, data);
+        cache.data.extend_from_slice(&data[copied_len..]);
+
+        // Prefetch more if the prefetch is full, or less if it is mostly unused
+        let max_prefetch_size = recv_prefetch_size();
+        if bytes_recvd == prefetch_size {
+            cache.prefetch_size = min(prefetch_size * 2, max_prefetch_size);
+        } else if bytes_recvd < prefetch_size / 4 {
+            cache.prefetch_size = max(prefetch_size / 2, min(MIN_PREFETCH_SIZE, max_prefetch_size));
+        }
+        Ok(copied_len)
+    }
+}
+
+/// Send the data staged by the current thread before a syscall, unless the syscall
+/// is a send on the same socket, which may be staged along with it
+pub fn flush_pending_sends(num: SyscallNum, fd: isize) {
+    let pending_stage = match PENDING_STAGE.with(|pending| pending.borrow().clone()) {
+        Some(pending_stage) => pending_stage,
+        None => return,
+    };
+    if let Some(pending_stage) = pending_stage.upgrade() {
+        if is_send_syscall(num) && is_stage_of_fd(&pending_stage, fd) {
+            return;
+        }
+        pending_stage.lock().unwrap().flush();
+    }
+    PENDING_STAGE.with(|pending| pending.borrow_mut().take());
+}
+
+fn is_send_syscall(num: SyscallNum) -> bool {
+    match num {
+        SyscallNum::Write | SyscallNum::Writev | SyscallNum::Sendto | SyscallNum::Sendmsg => true,
+        _ => false,
+    }
+}
+
+fn is_stage_of_fd(stage: &Arc<SgxMutex<SendStage>>, fd: isize) -> bool {
+    let file_ref = match current!().file(fd as FileDesc) {
+        Ok(file_ref) => file_ref,
+        Err(_) => return false,
+    };
+    let is_stage_of_fd = match file_ref.as_socket() {
+        Ok(socket) => Arc::ptr_eq(&socket.buffering.send_stage, stage),
+        Err(_) => false,
+    };
+    is_stage_of_fd
+}
+
+impl SendStage {
+    fn as_slice(&self) -> &[u8] {
+        let buf = self.buf.as_ref().unwrap();
+        unsafe { std::slice::from_raw_parts(buf.as_mut_ptr(), buf.size()) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        let buf = self.buf.as_ref().unwrap();
+        unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.size()) }
+    }
+
+    /// Send all of the staged data, whose error is kept to be reported later. The
+    /// untrusted buffer is returned to the arena once the data is sent.
+    fn flush(&mut self) {
+        let mut sent_len = 0;
+        while sent_len < self.len {
+            let rest_len = self.len - sent_len;
+            let data = &self.as_slice()[sent_len..self.len];
+            let ret = sendmsg_to_host(self.host_fd, &[data], SendFlags::MSG_NOSIGNAL, None, None);
+            match ret {
+                Ok(len) if len > 0 && len <= rest_len => sent_len += len,
+                Ok(_) => {
+                    self.error = Some(errno!(EIO, "invalid length of the data sent by the host"));
+                    break;
+                }
+                Err(e) if e.errno() == EINTR => continue,
+                Err(e) => {
+                    debug!("failed to send the staged data: {:?}", e);
+                    self.error = Some(e);
+                    break;
+                }
+            }
+        }
+        self.len = 0;
+        self.buf = None;
+    }
+}
+
+impl std::fmt::Debug for SendStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SendStage")
+            .field("host_fd", &self.host_fd)
+            .field("len", &self.len)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl RecvCache {
+    fn available(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+        if self.pos == self.data.len() {
+            self.data.clear();
+            self.pos = 0;
+        }
+    }
+}
+
+/// Copy `data` into the buffers from `offset`, and return the length copied
+fn copy_to_bufs(bufs: &mut [&mut [u8]], mut offset: usize, data: &[u8]) -> usize {
+    let mut copied_len = 0;
+    for buf in bufs.iter_mut() {
+        if offset >= buf.len() {
+            offset -= buf.len();
+            continue;
+        }
+        let len = min(buf.len() - offset, data.len() - copied_len);
+        buf[offset..offset + len].copy_from_slice(&data[copied_len..copied_len + len]);
+        copied_len += len;
+        offset = 0;
+        if copied_len == data.len() {
+            break;
+        }
+    }
+    copied_len
+}
+
+fn send_flush_size() -> usize {
+    LIBOS_CONFIG
+        .network
+        .buffering
+        .as_ref()
+        .map_or(0, |buffering| buffering.send_flush_size)
+}
+
+fn recv_prefetch_size() -> usize {
+    LIBOS_CONFIG
+        .network
+        .buffering
+        .as_ref()
+        .map_or(0, |buffering| buffering.recv_prefetch_size)
+}
@@ -23,6 +23,12 @@ impl SocketFile {
         });
         // FIXME: add sanity checks for results returned for socket-related ioctls
         cmd.validate_arg_and_ret_vals(ret)?;
+        // The data buffered in the enclave is unknown to the host
+        match cmd {
+            IoctlCmd::FIONREAD(nread_ref) => **nread_ref += self.prefetched_len() as i32,
+            IoctlCmd::TIOCOUTQ(nbytes_ref) => **nbytes_ref += self.staged_len() as i32,
+            _ => {}
+        }
         Ok(ret)
     }
 
@@ -1,43 +1,429 @@
+use self::buffering::SocketBuffering;
+use super::addr_families::{check_user_addr, is_host_addr_allowed, is_ipv6_only};
+use super::egress_limit::{has_addr_egress_limits, parse_sockaddr_ip, throttle_egress};
+use super::firewall::check_firewall;
+use super::host_fd_exhaustion::retry_on_host_fd_exhaustion;
+use super::icmp::{check_echo_reply, check_echo_request, is_ping_socket};
+use super::socket_stats::SocketStats;
+use super::sockopt::{
+    IPPROTO_IPV6, IPV6_V6ONLY, SO_KEEPALIVE, TCP_KEEPCNT, TCP_KEEPIDLE, TCP_KEEPINTVL,
+};
+use super::tls::{self, TlsSession};
 use super::*;
+use crate::config::{ConfigFirewallDirection, ConfigNetKeepalive};
 use crate::fs::IfConf;
 
+mod async_io_impl;
+mod buffering;
 mod ioctl_impl;
 mod recv;
 mod send;
+mod socket_impl;
+mod tls_impl;
 
 use fs::{AccessMode, CreationFlags, File, FileRef, IoctlCmd, StatusFlags};
 use std::any::Any;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::net::IpAddr;
+use std::time::Duration;
+
+pub use self::buffering::{flush_pending_sends, SO_OCCLUM_BUFFERING};
 
 /// Native Linux socket
 #[derive(Debug)]
 pub struct SocketFile {
     host_fd: c_int,
+    // The domain, type and protocol are tracked by the LibOS, so that they can
+    // be reported without trusting the host
+    domain: c_int,
+    socket_type: c_int,
+    protocol: c_int,
+    stats: SocketStats,
+    // The address of the peer, which decides the egress limits of the data sent
+    // without a destination address
+    peer_addr: SgxMutex<Option<IpAddr>>,
+    // The TLS session if the connection is wrapped in TLS in the enclave
+    tls: SgxMutex<Option<TlsSession>>,
+    // The sends staged and the data prefetched, if the buffering is configured
+    buffering: SocketBuffering,
+    // The timeouts given by SO_RCVTIMEO and SO_SNDTIMEO, which are also set on the
+    // host socket. They bound the waits in the enclave, e.g., for TLS records.
+    recv_timeout: SgxMutex<Option<Duration>>,
+    send_timeout: SgxMutex<Option<Duration>>,
 }
 
 impl SocketFile {
     pub fn new(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<Self> {
-        let ret = try_libc!(libc::ocall::socket(domain, socket_type, protocol));
-        Ok(SocketFile { host_fd: ret })
+        let is_nonblocking = socket_type & libc::SOCK_NONBLOCK != 0;
+        let ret = retry_on_host_fd_exhaustion(is_nonblocking, || {
+            Ok(try_libc!(libc::ocall::socket(
+                domain,
+                socket_type,
+                protocol
+            )))
+        })?;
+        let socket_type = socket_type & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        let protocol = if protocol == 0 {
+            default_protocol(domain, socket_type)
+        } else {
+            protocol
+        };
+        let socket = SocketFile {
+            host_fd: ret,
+            domain,
+            socket_type,
+            protocol,
+            stats: SocketStats::new(),
+            peer_addr: SgxMutex::new(None),
+            tls: SgxMutex::new(None),
+            buffering: SocketBuffering::new(ret, is_nonblocking),
+            recv_timeout: SgxMutex::new(None),
+            send_timeout: SgxMutex::new(None),
+        };
+        // The host socket is closed when the socket is dropped if it fails
+        if domain == libc::AF_INET6 && is_ipv6_only() {
+            set_ipv6_only(socket.host_fd)?;
+        }
+        // The accepted connections of a listener inherit the keepalive of it
+        if let Some(keepalive) = &config::LIBOS_CONFIG.network.keepalive {
+            if socket.protocol == libc::IPPROTO_TCP {
+                set_keepalive(socket.host_fd, keepalive)?;
+            }
+        }
+        Ok(socket)
     }
 
-    pub fn accept(
-        &self,
-        addr: *mut libc::sockaddr,
-        addr_len: *mut libc::socklen_t,
-        flags: c_int,
-    ) -> Result<Self> {
-        let ret = try_libc!(libc::ocall::accept4(self.host_fd, addr, addr_len, flags));
-        Ok(SocketFile { host_fd: ret })
+    /// Accept a connection, and return the socket and the address of the peer.
+    ///
+    /// The address is received into the enclave and checked, rather than written
+    /// to the buffer of the user by the host.
+    pub fn accept(&self, flags: c_int) -> Result<(Self, SockAddr)> {
+        loop {
+            let (new_socket, peer_addr) = self.accept_from_host(flags)?;
+            // The connection from a denied peer is dropped, which closes the host
+            // socket, and the next one is accepted, or EAGAIN if non-blocking
+            if check_firewall(ConfigFirewallDirection::Inbound, peer_addr.as_bytes()).is_err() {
+                continue;
+            }
+            new_socket.init_tls_server(self)?;
+            if has_addr_egress_limits() {
+                *new_socket.peer_addr.lock().unwrap() = parse_sockaddr_ip(peer_addr.as_bytes());
+            }
+            return Ok((new_socket, peer_addr));
+        }
+    }
+
+    fn accept_from_host(&self, flags: c_int) -> Result<(Self, SockAddr)> {
+        let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let mut addr_len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        // The address is written only if a connection is accepted, so it is intact
+        // for the retries, which are skipped if the listener is non-blocking
+        let ret = retry_on_host_fd_exhaustion(self.buffering.is_nonblocking(), || {
+            Ok(try_libc!(crate::sched::account_wait(|| unsafe {
+                libc::ocall::accept4(
+                    self.host_fd,
+                    &mut addr as *mut _ as *mut libc::sockaddr,
+                    &mut addr_len,
+                    flags,
+                )
+            })))
+        })?;
+        // The accepted socket has the same domain, type and protocol as the listener.
+        // Like Linux, it inherits the timeouts of the listener, as does the host socket.
+        let new_socket = SocketFile {
+            host_fd: ret,
+            domain: self.domain,
+            socket_type: self.socket_type,
+            protocol: self.protocol,
+            stats: SocketStats::new(),
+            peer_addr: SgxMutex::new(None),
+            tls: SgxMutex::new(None),
+            buffering: SocketBuffering::new(ret, flags & libc::SOCK_NONBLOCK != 0),
+            recv_timeout: SgxMutex::new(self.recv_timeout()),
+            send_timeout: SgxMutex::new(self.send_timeout()),
+        };
+        // The connection is dropped, which closes the host socket, if the address
+        // is malformed, not of the domain of the listener or not allowed
+        let addr_bytes = unsafe {
+            std::slice::from_raw_parts(
+                &addr as *const _ as *const u8,
+                std::mem::size_of::<libc::sockaddr_storage>(),
+            )
+        };
+        let peer_addr = addr_bytes
+            .get(..addr_len as usize)
+            .and_then(|addr_bytes| self.parse_host_addr(addr_bytes).ok())
+            .ok_or_else(|| errno!(ECONNABORTED, "invalid address of the accepted connection"))?;
+        Ok((new_socket, peer_addr))
+    }
+
+    /// Parse and normalize an address given by the host, which must be of the
+    /// domain of the socket and allowed by the config
+    fn parse_host_addr(&self, bytes: &[u8]) -> Result<SockAddr> {
+        let addr = SockAddr::from_host(bytes, self.domain)?;
+        if !is_host_addr_allowed(&addr) {
+            return_errno!(EIO, "the address from the host is not allowed");
+        }
+        Ok(addr)
     }
 
     pub fn fd(&self) -> c_int {
         self.host_fd
     }
+
+    pub fn domain(&self) -> c_int {
+        self.domain
+    }
+
+    /// The type of the socket, without SOCK_NONBLOCK and SOCK_CLOEXEC
+    pub fn socket_type(&self) -> c_int {
+        self.socket_type
+    }
+
+    pub fn protocol(&self) -> c_int {
+        self.protocol
+    }
+
+    pub fn stats(&self) -> &SocketStats {
+        &self.stats
+    }
+
+    pub fn buffering(&self) -> &SocketBuffering {
+        &self.buffering
+    }
+
+    /// The timeout of the blocking receives, which is infinite if None
+    pub fn recv_timeout(&self) -> Option<Duration> {
+        *self.recv_timeout.lock().unwrap()
+    }
+
+    /// The timeout of the blocking sends, which is infinite if None
+    pub fn send_timeout(&self) -> Option<Duration> {
+        *self.send_timeout.lock().unwrap()
+    }
+
+    /// Check the address given by the user to bind (inbound), or to connect or
+    /// send to (outbound). The address must have been copied into the enclave,
+    /// so that it cannot be changed after it is checked.
+    pub fn check_user_addr(&self, addr: &[u8], direction: ConfigFirewallDirection) -> Result<()> {
+        check_user_addr(self.domain, addr)?;
+        check_firewall(direction, addr)
+    }
+
+    /// Record the address given by connect as the peer address
+    pub fn set_peer_addr(&self, addr: Option<&[u8]>) {
+        *self.peer_addr.lock().unwrap() = addr.and_then(parse_sockaddr_ip);
+    }
+
+    /// Wait until the data of `len` bytes can be sent to `dest`, or to the peer if
+    /// `dest` is None, without exceeding the egress limits
+    pub fn throttle_egress(&self, dest: Option<&[u8]>, len: usize, flags: SendFlags) -> Result<()> {
+        let dest = match dest {
+            Some(dest) => parse_sockaddr_ip(dest),
+            None => *self.peer_addr.lock().unwrap(),
+        };
+        throttle_egress(dest, len, || {
+            Ok(flags.contains(SendFlags::MSG_DONTWAIT)
+                || self.get_status_flags()?.contains(StatusFlags::O_NONBLOCK))
+        })
+    }
+
+    /// Check the data to be sent if the socket is a ping socket
+    pub fn check_sent_data(&self, data: &[u8]) -> Result<()> {
+        if !self.is_ping() {
+            return Ok(());
+        }
+        check_echo_request(self.domain, data)
+    }
+
+    /// Check the data received if the socket is a ping socket.
+    ///
+    /// The data in `data` may be truncated to the buffer, whose length is `buf_len`,
+    /// while `data_len` is the length of the data received.
+    pub fn check_recvd_data(
+        &self,
+        data: &[u8],
+        data_len: usize,
+        buf_len: usize,
+        flags: RecvFlags,
+    ) -> Result<()> {
+        if !self.is_ping() {
+            return Ok(());
+        }
+        let domain = self.domain;
+        if flags.contains(RecvFlags::MSG_ERRQUEUE) {
+            // The error queue returns the echo requests that caused the errors
+            if data.len() == data_len {
+                check_echo_request(domain, data)
+                    .map_err(|_| errno!(EIO, "invalid ICMP message in the error queue"))?;
+            }
+            return Ok(());
+        }
+        check_echo_reply(domain, data, data_len, buf_len)
+    }
+
+    pub fn is_ping(&self) -> bool {
+        is_ping_socket(self.domain, self.socket_type, self.protocol)
+    }
+
+    /// The entry of an IPv4 TCP socket in /proc/net/tcp, which is given by the host.
+    /// The queues are shown as empty if unknown, e.g., for a listening socket.
+    pub fn proc_tcp_entry(&self) -> Option<TcpSocketEntry> {
+        if self.domain != libc::AF_INET || self.socket_type != libc::SOCK_STREAM {
+            return None;
+        }
+        let local = self.get_sockaddr_in(false).ok()?;
+        let remote = self.get_sockaddr_in(true).ok();
+        let state = if remote.is_some() {
+            TcpEntryState::Established
+        } else if self.is_listening() {
+            TcpEntryState::Listen
+        } else {
+            TcpEntryState::Close
+        };
+        let mut len = 0;
+        let rx_queue = match self.ioctl_impl(&mut IoctlCmd::FIONREAD(&mut len)) {
+            Ok(_) => len as usize,
+            Err(_) => 0,
+        };
+        let tx_queue = match self.ioctl_impl(&mut IoctlCmd::TIOCOUTQ(&mut len)) {
+            Ok(_) => len as usize,
+            Err(_) => 0,
+        };
+        Some(TcpSocketEntry {
+            local,
+            remote,
+            state,
+            tx_queue,
+            rx_queue,
+        })
+    }
+
+    /// Get the local or peer address of an IPv4 socket from the host
+    fn get_sockaddr_in(&self, is_peer: bool) -> Result<libc::sockaddr_in> {
+        let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+        let mut addr_len = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+        let addr_ptr = &mut addr as *mut _ as *mut libc::sockaddr;
+        if is_peer {
+            try_libc!(libc::ocall::getpeername(
+                self.host_fd,
+                addr_ptr,
+                &mut addr_len
+            ));
+        } else {
+            try_libc!(libc::ocall::getsockname(
+                self.host_fd,
+                addr_ptr,
+                &mut addr_len
+            ));
+        }
+        if addr.sin_family != libc::AF_INET as libc::sa_family_t {
+            return_errno!(EIO, "invalid address from the host");
+        }
+        Ok(addr)
+    }
+
+    fn is_listening(&self) -> bool {
+        let mut val: c_int = 0;
+        let mut val_len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        let ret = unsafe {
+            libc::ocall::getsockopt(
+                self.host_fd,
+                libc::SOL_SOCKET,
+                libc::SO_ACCEPTCONN,
+                &mut val as *mut _ as *mut c_void,
+                &mut val_len,
+            )
+        };
+        ret == 0 && val != 0
+    }
+
+    /// Write the data, where SIGPIPE is raised on EPIPE unless MSG_NOSIGNAL is given
+    fn write_with_flags(&self, buf: &[u8], flags: SendFlags) -> Result<usize> {
+        if self.is_tls() {
+            return self.tls_sendv(&[buf], flags);
+        }
+        if let Some(ret) = self.stage_sends(&[buf], flags) {
+            return ret;
+        }
+        self.check_sent_data(buf)?;
+        self.throttle_egress(None, buf.len(), flags)?;
+        if let Some(ret) = self.submit_write(buf, flags) {
+            return ret;
+        }
+        let (buf_ptr, buf_len) = buf.as_ptr_and_len();
+        let ret = if flags.contains(SendFlags::MSG_NOSIGNAL) {
+            try_libc!(libc::ocall::write(
+                self.host_fd,
+                buf_ptr as *const c_void,
+                buf_len
+            ))
+        } else {
+            try_libc_may_epipe!(libc::ocall::write(
+                self.host_fd,
+                buf_ptr as *const c_void,
+                buf_len
+            ))
+        } as usize;
+        assert!(ret <= buf_len);
+        self.stats.account_sent(ret);
+        Ok(ret)
+    }
+}
+
+/// Make the IPv6 socket of the host not carry the IPv4 traffic
+fn set_ipv6_only(host_fd: c_int) -> Result<()> {
+    let val: c_int = 1;
+    try_libc!(libc::ocall::setsockopt(
+        host_fd,
+        IPPROTO_IPV6,
+        IPV6_V6ONLY,
+        &val as *const _ as *const c_void,
+        std::mem::size_of::<c_int>() as libc::socklen_t
+    ));
+    Ok(())
+}
+
+/// Enable the keepalive of the TCP socket of the host, so that a dead peer of an
+/// idle connection is detected by the host, which fails the socket with ETIMEDOUT
+/// and reports POLLERR. The application can still change the keepalive by itself.
+fn set_keepalive(host_fd: c_int, keepalive: &ConfigNetKeepalive) -> Result<()> {
+    let opts = [
+        (libc::SOL_SOCKET, SO_KEEPALIVE, 1),
+        (libc::IPPROTO_TCP, TCP_KEEPIDLE, keepalive.idle_secs),
+        (libc::IPPROTO_TCP, TCP_KEEPINTVL, keepalive.interval_secs),
+        (libc::IPPROTO_TCP, TCP_KEEPCNT, keepalive.count),
+    ];
+    for &(level, optname, val) in opts.iter() {
+        let val = val as c_int;
+        try_libc!(libc::ocall::setsockopt(
+            host_fd,
+            level,
+            optname,
+            &val as *const _ as *const c_void,
+            std::mem::size_of::<c_int>() as libc::socklen_t
+        ));
+    }
+    Ok(())
+}
+
+/// The protocol that the host chooses if the protocol is not specified
+fn default_protocol(domain: c_int, socket_type: c_int) -> c_int {
+    match (domain, socket_type) {
+        (libc::AF_INET, libc::SOCK_STREAM) | (libc::AF_INET6, libc::SOCK_STREAM) => {
+            libc::IPPROTO_TCP
+        }
+        (libc::AF_INET, libc::SOCK_DGRAM) | (libc::AF_INET6, libc::SOCK_DGRAM) => libc::IPPROTO_UDP,
+        _ => 0,
+    }
 }
 
 impl Drop for SocketFile {
     fn drop(&mut self) {
+        if let Err(e) = self.flush_sends() {
+            debug!("failed to send the staged data: {:?}", e);
+        }
+        self.close_tls();
         let ret = unsafe { libc::ocall::close(self.host_fd) };
         assert!(ret == 0);
     }
@@ -47,6 +433,15 @@ impl Drop for SocketFile {
 // TODO: implement readfrom/sendto
 impl File for SocketFile {
     fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        if self.is_tls() {
+            return self.tls_recvv(&mut [buf], RecvFlags::empty());
+        }
+        if let Some(ret) = self.recv_buffered(&mut [&mut buf[..]], RecvFlags::empty()) {
+            return ret;
+        }
+        if let Some(ret) = self.submit_read(buf, RecvFlags::empty()) {
+            return ret;
+        }
         let (buf_ptr, buf_len) = buf.as_mut().as_mut_ptr_and_len();
         let ret = try_libc!(libc::ocall::read(
             self.host_fd,
@@ -54,18 +449,13 @@ impl File for SocketFile {
             buf_len
         )) as usize;
         assert!(ret <= buf_len);
+        self.check_recvd_data(&buf[..ret], ret, buf_len, RecvFlags::empty())?;
+        self.stats.account_recvd(ret);
         Ok(ret)
     }
 
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        let (buf_ptr, buf_len) = buf.as_ptr_and_len();
-        let ret = try_libc_may_epipe!(libc::ocall::write(
-            self.host_fd,
-            buf_ptr as *const c_void,
-            buf_len
-        )) as usize;
-        assert!(ret <= buf_len);
-        Ok(ret)
+        self.write_with_flags(buf, SendFlags::empty())
     }
 
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
@@ -77,6 +467,9 @@ impl File for SocketFile {
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        if self.is_tls() {
+            return self.tls_recvv(bufs, RecvFlags::empty());
+        }
         let mut total_len = 0;
         for buf in bufs {
             match self.read(buf) {
@@ -91,9 +484,18 @@ impl File for SocketFile {
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
+        if self.is_tls() {
+            return self.tls_sendv(bufs, SendFlags::empty());
+        }
         let mut total_len = 0;
         for buf in bufs {
-            match self.write(buf) {
+            // Like Linux, no SIGPIPE is raised if some data has been written
+            let flags = if total_len == 0 {
+                SendFlags::empty()
+            } else {
+                SendFlags::MSG_NOSIGNAL
+            };
+            match self.write_with_flags(buf, flags) {
                 Ok(len) => {
                     total_len += len;
                 }
@@ -133,6 +535,8 @@ impl File for SocketFile {
             libc::F_SETFL,
             raw_status_flags as c_int
         ));
+        self.buffering
+            .set_nonblocking(new_status_flags.contains(StatusFlags::O_NONBLOCK));
         Ok(())
     }
 
@@ -1,25 +1,57 @@
+use super::super::cmsg::check_host_control_msgs;
 use super::*;
 use crate::untrusted::{SliceAsMutPtrAndLen, SliceAsPtrAndLen, UntrustedSliceAlloc};
 
 impl SocketFile {
-    // TODO: need sockaddr type to implement send/sento
-    /*
-    pub fn recv(&self, buf: &mut [u8], flags: MsgHdrFlags) -> Result<usize> {
-        let (bytes_recvd, _) = self.recvfrom(buf, flags, None)?;
-        Ok(bytes_recvd)
-    }
+    /// Receive data into `buf`, and the source address into `addr` if given.
+    ///
+    /// Like Linux, the full lengths of the data and of the source address are
+    /// returned, even if they are truncated to the buffers. The length of the data
+    /// exceeds the buffer only with MSG_TRUNC.
+    pub fn recvfrom(
+        &self,
+        buf: &mut [u8],
+        flags: RecvFlags,
+        addr: Option<&mut [u8]>,
+    ) -> Result<(usize, usize)> {
+        let u_slice_alloc = UntrustedSliceAlloc::new(buf.len())?;
+        let mut u_bufs = [u_slice_alloc
+            .new_slice_mut(buf.len())
+            .expect("unexpected out of memory error in UntrustedSliceAlloc")];
+        let (bytes_recvd, addr_len, _, _) = self.do_recvmsg(&mut u_bufs, flags, addr, None)?;
 
-    pub fn recvfrom(&self, buf: &mut [u8], flags: MsgHdrFlags, src_addr: Option<&mut [u8]>) -> Result<(usize, usize)> {
-        let (bytes_recvd, src_addr_len, _, _) = self.do_recvmsg(
-            &mut buf[..],
-            flags,
-            src_addr,
-            None,
-        )?;
-        Ok((bytes_recvd, src_addr_len))
-    }*/
+        let copied_len = min(bytes_recvd, buf.len());
+        if self.is_ping() {
+            // Check the data after it is copied into the enclave, and before it is
+            // copied to the user
+            let data = u_bufs[0][..copied_len].to_vec();
+            self.check_recvd_data(&data, bytes_recvd, buf.len(), flags)?;
+            buf[..copied_len].copy_from_slice(&data);
+        } else {
+            buf[..copied_len].copy_from_slice(&u_bufs[0][..copied_len]);
+        }
+        if !flags.contains(RecvFlags::MSG_PEEK) {
+            self.stats.account_recvd(bytes_recvd);
+        }
+        Ok((bytes_recvd, addr_len))
+    }
 
     pub fn recvmsg<'a, 'b>(&self, msg: &'b mut MsgHdrMut<'a>, flags: RecvFlags) -> Result<usize> {
+        if self.is_tls() {
+            let bytes_recvd = self.tls_recvv(msg.get_iovs_mut().as_slices_mut(), flags)?;
+            msg.set_name_len(0)?;
+            msg.set_control_len(0)?;
+            msg.set_flags(MsgHdrFlags::empty());
+            return Ok(bytes_recvd);
+        }
+        if let Some(ret) = self.recv_buffered(msg.get_iovs_mut().as_slices_mut(), flags) {
+            let bytes_recvd = ret?;
+            msg.set_name_len(0)?;
+            msg.set_control_len(0)?;
+            msg.set_flags(MsgHdrFlags::empty());
+            return Ok(bytes_recvd);
+        }
+
         // Alloc untrusted iovecs to receive data via OCall
         let msg_iov = msg.get_iovs();
         let u_slice_alloc = UntrustedSliceAlloc::new(msg_iov.total_bytes())?;
@@ -42,7 +74,8 @@ impl SocketFile {
             self.do_recvmsg(u_iovs.as_slices_mut(), flags, name, control)?
         };
 
-        // Update the output lengths and flags
+        // Update the output lengths and flags. The address is truncated to the buffer.
+        let namelen_recvd = min(namelen_recvd, msg.get_name_max_len());
         msg.set_name_len(namelen_recvd)?;
         msg.set_control_len(controllen_recvd)?;
         msg.set_flags(flags_recvd);
@@ -54,8 +87,18 @@ impl SocketFile {
             .iter()
             .flat_map(|slice| slice.iter())
             .take(bytes_recvd);
-        msg_iov.copy_from_iter(&mut u_iovs_iter);
+        if self.is_ping() {
+            // Check the data after it is copied into the enclave
+            let data: Vec<u8> = u_iovs_iter.cloned().collect();
+            self.check_recvd_data(&data, bytes_recvd, msg_iov.total_bytes(), flags)?;
+            msg_iov.copy_from_iter(&mut data.iter());
+        } else {
+            msg_iov.copy_from_iter(&mut u_iovs_iter);
+        }
 
+        if !flags.contains(RecvFlags::MSG_PEEK) {
+            self.stats.account_recvd(bytes_recvd);
+        }
         Ok(bytes_recvd)
     }
 
@@ -74,7 +117,7 @@ impl SocketFile {
         let msg_name = msg_name as *mut c_void;
         let mut msg_namelen_recvd = 0_u32;
         // Iovs
-        let mut raw_iovs: Vec<libc::iovec> =
+        let mut raw_iovs: IovVec<libc::iovec> =
             data.iter().map(|slice| slice.as_libc_iovec()).collect();
         let (msg_iov, msg_iovlen) = raw_iovs.as_mut_slice().as_mut_ptr_and_len();
         // Control
@@ -85,23 +128,25 @@ impl SocketFile {
         let raw_flags = flags.bits();
         let mut msg_flags_recvd = 0;
 
-        // Do OCall
+        // Do OCall, which may block in the host
         let retval = try_libc!({
             let mut retval = 0_isize;
-            let status = occlum_ocall_recvmsg(
-                &mut retval as *mut isize,
-                host_fd,
-                msg_name,
-                msg_namelen as u32,
-                &mut msg_namelen_recvd as *mut u32,
-                msg_iov,
-                msg_iovlen,
-                msg_control,
-                msg_controllen,
-                &mut msg_controllen_recvd as *mut usize,
-                &mut msg_flags_recvd as *mut i32,
-                raw_flags,
-            );
+            let status = crate::sched::account_wait(|| {
+                occlum_ocall_recvmsg(
+                    &mut retval as *mut isize,
+                    host_fd,
+                    msg_name,
+                    msg_namelen as u32,
+                    &mut msg_namelen_recvd as *mut u32,
+                    msg_iov,
+                    msg_iovlen,
+                    msg_control,
+                    msg_controllen,
+                    &mut msg_controllen_recvd as *mut usize,
+                    &mut msg_flags_recvd as *mut i32,
+                    raw_flags,
+                )
+            });
             assert!(status == sgx_status_t::SGX_SUCCESS);
 
             // TODO: what if retval < 0 but buffers are modified by the
@@ -109,7 +154,8 @@ impl SocketFile {
             retval
         });
 
-        let flags_recvd = MsgHdrFlags::from_bits(msg_flags_recvd).unwrap();
+        let flags_recvd = MsgHdrFlags::from_bits(msg_flags_recvd)
+            .ok_or_else(|| errno!(EIO, "invalid flags of recvmsg from the host"))?;
 
         // Check values returned from outside the enclave
         let bytes_recvd = {
@@ -122,16 +168,35 @@ impl SocketFile {
 
             // For MSG_TRUNC recvmsg returns the real length of the packet or datagram,
             // even when it was longer than the passed buffer.
-            if flags.contains(RecvFlags::MSG_TRUNC) && retval > max_bytes_recvd {
-                assert!(flags_recvd.contains(MsgHdrFlags::MSG_TRUNC));
-            } else {
-                assert!(retval <= max_bytes_recvd);
+            if retval > max_bytes_recvd
+                && !(flags.contains(RecvFlags::MSG_TRUNC)
+                    && flags_recvd.contains(MsgHdrFlags::MSG_TRUNC))
+            {
+                return_errno!(EIO, "the length of data from the host is too large");
             }
             retval
         };
         let msg_namelen_recvd = msg_namelen_recvd as usize;
-        assert!(msg_namelen_recvd <= msg_namelen);
-        assert!(msg_controllen_recvd <= msg_controllen);
+        if let Some(name) = name {
+            self.check_src_addr(name, msg_namelen_recvd)?;
+            // The bytes after the address are not given by the host
+            let name_len = min(msg_namelen_recvd, name.len());
+            for byte in name[name_len..].iter_mut() {
+                *byte = 0;
+            }
+        }
+        if msg_controllen_recvd > msg_controllen {
+            return_errno!(EIO, "the length of control from the host is too large");
+        }
+        if let Some(control) = control {
+            check_host_control_msgs(
+                &control[..msg_controllen_recvd],
+                flags_recvd.contains(MsgHdrFlags::MSG_CTRUNC),
+            )?;
+            for byte in control[msg_controllen_recvd..].iter_mut() {
+                *byte = 0;
+            }
+        }
         Ok((
             bytes_recvd,
             msg_namelen_recvd,
@@ -139,6 +204,38 @@ impl SocketFile {
             flags_recvd,
         ))
     }
+
+    /// Check the source address given by the host, whose full length is `addr_len`
+    /// while `addr` is the buffer that may truncate it. The address is normalized
+    /// in place unless it is truncated.
+    fn check_src_addr(&self, addr: &mut [u8], addr_len: usize) -> Result<()> {
+        // No address is given for a connection-mode socket
+        if addr_len == 0 {
+            return Ok(());
+        }
+        let is_valid_len = match self.domain {
+            libc::AF_INET => addr_len == std::mem::size_of::<libc::sockaddr_in>(),
+            libc::AF_INET6 => addr_len == std::mem::size_of::<libc::sockaddr_in6>(),
+            _ => addr_len <= std::mem::size_of::<libc::sockaddr_storage>(),
+        };
+        if !is_valid_len {
+            return_errno!(EIO, "invalid length of the source address from the host");
+        }
+        if addr.len() >= addr_len {
+            let src_addr = self.parse_host_addr(&addr[..addr_len])?;
+            addr[..addr_len].copy_from_slice(src_addr.as_bytes());
+            return Ok(());
+        }
+        // The family is the first field of the address
+        let family_len = std::mem::size_of::<libc::sa_family_t>();
+        if addr.len() >= family_len && addr_len >= family_len {
+            let family = libc::sa_family_t::from_ne_bytes([addr[0], addr[1]]);
+            if family as c_int != self.domain {
+                return_errno!(EIO, "invalid family of the source address from the host");
+            }
+        }
+        Ok(())
+    }
 }
 
 extern "C" {
@@ -1,25 +1,45 @@
 use super::*;
 
-impl SocketFile {
-    // TODO: need sockaddr type to implement send/sento
-    /*
-    pub fn send(&self, buf: &[u8], flags: MsgFlags) -> Result<usize> {
-        self.sendto(buf, flags, None)
-    }
+/// The max size of the data sent by an OCall of sendfile
+const SEND_FILE_CHUNK_SIZE: usize = 64 * 1024;
 
-    pub fn sendto(&self, buf: &[u8], flags: MsgFlags, dest_addr: Option<&[u8]>) -> Result<usize> {
-        Self::do_sendmsg(
-            self.host_fd,
-            &buf[..],
-            flags,
-            dest_addr,
-            None)
+impl SocketFile {
+    /// Send the data in `buf` to `dest_addr`, or to the peer if it is None.
+    ///
+    /// The data is copied into an untrusted buffer before it is checked, and the
+    /// copy is sent, so the user cannot change the data after it is checked.
+    pub fn sendto(&self, buf: &[u8], flags: SendFlags, dest_addr: Option<&[u8]>) -> Result<usize> {
+        let u_slice_alloc = UntrustedSliceAlloc::new(buf.len())?;
+        let u_buf = u_slice_alloc
+            .new_slice(buf)
+            .expect("unexpected out of memory");
+        self.check_sent_data(u_buf)?;
+        self.throttle_egress(dest_addr, buf.len(), flags)?;
+        let bytes_sent = self.do_sendmsg(&[u_buf], flags, dest_addr, None)?;
+        self.stats.account_sent(bytes_sent);
+        Ok(bytes_sent)
     }
-    */
 
     pub fn sendmsg<'a, 'b>(&self, msg: &'b MsgHdr<'a>, flags: SendFlags) -> Result<usize> {
-        // Copy message's iovecs into untrusted iovecs
+        // The name is ignored for a connection-mode socket, and the control
+        // messages are not carried by TLS
+        if self.is_tls() {
+            return self.tls_sendv(msg.get_iovs().as_slices(), flags);
+        }
         let msg_iov = msg.get_iovs();
+        if msg.get_name().is_none() && msg.get_control().is_none() {
+            if let Some(ret) = self.stage_sends(msg_iov.as_slices(), flags) {
+                return ret;
+            }
+        } else {
+            self.flush_sends()?;
+        }
+        // Copy the name into the enclave before checking it
+        let name = msg.get_name().map(|name| name.to_vec());
+        if let Some(name) = &name {
+            self.check_user_addr(name, ConfigFirewallDirection::Outbound)?;
+        }
+        // Copy message's iovecs into untrusted iovecs
         let u_slice_alloc = UntrustedSliceAlloc::new(msg_iov.total_bytes())?;
         let u_slices = msg_iov
             .as_slices()
@@ -31,8 +51,43 @@ impl SocketFile {
             })
             .collect();
         let u_iovs = Iovs::new(u_slices);
+        // Check the copy, which is the data sent
+        if self.is_ping() {
+            self.check_sent_data(&u_iovs.as_slices().concat())?;
+        }
+        self.throttle_egress(name.as_deref(), msg_iov.total_bytes(), flags)?;
 
-        self.do_sendmsg(u_iovs.as_slices(), flags, msg.get_name(), msg.get_control())
+        let bytes_sent = self.do_sendmsg(
+            u_iovs.as_slices(),
+            flags,
+            name.as_deref(),
+            msg.get_control(),
+        )?;
+        self.stats.account_sent(bytes_sent);
+        Ok(bytes_sent)
+    }
+
+    /// Send the data of `in_file` at `offset`, which is read into an untrusted
+    /// buffer and sent by a single OCall, without the copy into the enclave. At most
+    /// one chunk is sent; return the bytes sent.
+    pub fn send_file(&self, in_file: &FileRef, offset: usize, count: usize) -> Result<usize> {
+        // The data must be checked or encrypted in the enclave for these sockets
+        debug_assert!(!self.is_tls() && !self.is_ping());
+        let len = min(count, SEND_FILE_CHUNK_SIZE);
+        if len == 0 {
+            return Ok(0);
+        }
+        self.flush_sends()?;
+        self.throttle_egress(None, len, SendFlags::empty())?;
+        let u_slice_alloc = UntrustedSliceAlloc::new(len)?;
+        let u_buf = u_slice_alloc.new_slice_mut(len)?;
+        let read_len = in_file.read_at(offset, u_buf)?;
+        if read_len == 0 {
+            return Ok(0);
+        }
+        let bytes_sent = self.do_sendmsg(&[&u_buf[..read_len]], SendFlags::empty(), None, None)?;
+        self.stats.account_sent(bytes_sent);
+        Ok(bytes_sent)
     }
 
     fn do_sendmsg(
@@ -42,47 +97,56 @@ impl SocketFile {
         name: Option<&[u8]>,
         control: Option<&[u8]>,
     ) -> Result<usize> {
-        // Prepare the arguments for OCall
-        let mut retval: isize = 0;
-        // Host socket fd
-        let host_fd = self.host_fd;
-        // Name
-        let (msg_name, msg_namelen) = name.as_ptr_and_len();
-        let msg_name = msg_name as *const c_void;
-        // Iovs
-        let raw_iovs: Vec<libc::iovec> = data.iter().map(|slice| slice.as_libc_iovec()).collect();
-        let (msg_iov, msg_iovlen) = raw_iovs.as_slice().as_ptr_and_len();
-        // Control
-        let (msg_control, msg_controllen) = control.as_ptr_and_len();
-        let msg_control = msg_control as *const c_void;
-        // Flags
-        let raw_flags = flags.bits();
+        sendmsg_to_host(self.host_fd, data, flags, name, control)
+    }
+}
 
-        // Do OCall
-        unsafe {
-            let status = occlum_ocall_sendmsg(
-                &mut retval as *mut isize,
-                host_fd,
-                msg_name,
-                msg_namelen as u32,
-                msg_iov,
-                msg_iovlen,
-                msg_control,
-                msg_controllen,
-                raw_flags,
-            );
-            assert!(status == sgx_status_t::SGX_SUCCESS);
-        }
+/// Send the data in the untrusted buffers to the host socket
+pub(super) fn sendmsg_to_host(
+    host_fd: c_int,
+    data: &[&[u8]],
+    flags: SendFlags,
+    name: Option<&[u8]>,
+    control: Option<&[u8]>,
+) -> Result<usize> {
+    // Prepare the arguments for OCall
+    let mut retval: isize = 0;
+    // Name
+    let (msg_name, msg_namelen) = name.as_ptr_and_len();
+    let msg_name = msg_name as *const c_void;
+    // Iovs
+    let raw_iovs: IovVec<libc::iovec> = data.iter().map(|slice| slice.as_libc_iovec()).collect();
+    let (msg_iov, msg_iovlen) = raw_iovs.as_slice().as_ptr_and_len();
+    // Control
+    let (msg_control, msg_controllen) = control.as_ptr_and_len();
+    let msg_control = msg_control as *const c_void;
+    // Flags
+    let raw_flags = flags.bits();
 
-        let bytes_sent = if flags.contains(SendFlags::MSG_NOSIGNAL) {
-            try_libc!(retval)
-        } else {
-            try_libc_may_epipe!(retval)
-        };
+    // Do OCall, which may block in the host
+    let status = crate::sched::account_wait(|| unsafe {
+        occlum_ocall_sendmsg(
+            &mut retval as *mut isize,
+            host_fd,
+            msg_name,
+            msg_namelen as u32,
+            msg_iov,
+            msg_iovlen,
+            msg_control,
+            msg_controllen,
+            raw_flags,
+        )
+    });
+    assert!(status == sgx_status_t::SGX_SUCCESS);
 
-        debug_assert!(bytes_sent >= 0);
-        Ok(bytes_sent as usize)
-    }
+    let bytes_sent = if flags.contains(SendFlags::MSG_NOSIGNAL) {
+        try_libc!(retval)
+    } else {
+        try_libc_may_epipe!(retval)
+    };
+
+    debug_assert!(bytes_sent >= 0);
+    Ok(bytes_sent as usize)
 }
 
 extern "C" {
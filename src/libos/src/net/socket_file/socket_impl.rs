@@ -0,0 +1,204 @@
+use super::*;
+use crate::config::ConfigFirewallDirection;
+use crate::fs::{report_security_event, SecurityEventKind};
+use crate::net::io_multiplexing::invalidate_host_fd;
+use crate::net::net_dev::IFNAMSIZ;
+use crate::net::socket::Socket;
+use crate::net::socket_stats::SOL_OCCLUM;
+use crate::net::sockopt::{
+    check_getsockopt_val, check_setsockopt_val, int_optval, timeout_optval, timeout_to_optval,
+    IPPROTO_IPV6, IPV6_V6ONLY, MAX_SOCKOPT_LEN, SO_BINDTODEVICE, SO_DOMAIN, SO_PROTOCOL,
+    SO_RCVTIMEO, SO_SNDTIMEO, SO_TYPE,
+};
+use std::ffi::CString;
+
+impl Socket for SocketFile {
+    fn bind(&self, addr: &[u8]) -> Result<()> {
+        // Copy the address into the enclave before checking it
+        let addr = addr.to_vec();
+        self.check_user_addr(&addr, ConfigFirewallDirection::Inbound)?;
+        try_libc!(libc::ocall::bind(
+            self.host_fd,
+            addr.as_ptr() as *const libc::sockaddr,
+            addr.len() as libc::socklen_t
+        ));
+        Ok(())
+    }
+
+    fn listen(&self, backlog: c_int) -> Result<()> {
+        try_libc!(libc::ocall::listen(self.host_fd, backlog));
+        Ok(())
+    }
+
+    fn shutdown(&self, how: c_int) -> Result<()> {
+        if how == libc::SHUT_WR || how == libc::SHUT_RDWR {
+            if let Err(e) = self.flush_sends() {
+                debug!("failed to send the staged data: {:?}", e);
+            }
+            if self.is_tls() {
+                self.close_tls();
+            }
+        }
+        try_libc!(libc::ocall::shutdown(self.host_fd, how));
+        // Wake up the threads of other processes that are blocked in poll on the socket
+        invalidate_host_fd(self.host_fd)?;
+        Ok(())
+    }
+
+    fn addr(&self) -> Result<Vec<u8>> {
+        self.host_sockaddr(libc::ocall::getsockname)
+    }
+
+    fn peer_addr(&self) -> Result<Vec<u8>> {
+        self.host_sockaddr(libc::ocall::getpeername)
+    }
+
+    fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        if level == SOL_OCCLUM {
+            return self.setsockopt_occlum(optname, optval);
+        }
+        if level == libc::SOL_SOCKET && optname == SO_BINDTODEVICE {
+            return self.bind_to_device(optval);
+        }
+        // Copy the value into the enclave before checking it, so that it cannot
+        // be modified by other threads after it is checked
+        let optval = optval.to_vec();
+        check_setsockopt_val(level, optname, &optval)?;
+        // The IPv6 sockets cannot carry the IPv4 traffic if IPv4 is not allowed
+        if level == IPPROTO_IPV6 && optname == IPV6_V6ONLY && is_ipv6_only() {
+            let val = int_optval(&optval)?;
+            if val == 0 {
+                return_errno!(EACCES, "IPv4 is not allowed by the config");
+            }
+        }
+        // The timeouts are kept in the enclave as well, as the waits for TLS records
+        // are done by the LibOS rather than by the blocking I/O of the host
+        let timeout = match (level, optname) {
+            (libc::SOL_SOCKET, SO_RCVTIMEO) => Some((timeout_optval(&optval)?, &self.recv_timeout)),
+            (libc::SOL_SOCKET, SO_SNDTIMEO) => Some((timeout_optval(&optval)?, &self.send_timeout)),
+            _ => None,
+        };
+        try_libc!(libc::ocall::setsockopt(
+            self.host_fd,
+            level,
+            optname,
+            optval.as_ptr() as *const c_void,
+            optval.len() as libc::socklen_t
+        ));
+        if let Some((timeout, slot)) = timeout {
+            *slot.lock().unwrap() = timeout;
+        }
+        Ok(())
+    }
+
+    fn getsockopt(&self, level: c_int, optname: c_int, max_len: usize) -> Result<Vec<u8>> {
+        // Some libraries check the socket with these options, which are served from
+        // the state tracked by the LibOS rather than by the host
+        let trusted_val = match (level, optname) {
+            (libc::SOL_SOCKET, SO_TYPE) => Some(self.socket_type),
+            (libc::SOL_SOCKET, SO_DOMAIN) => Some(self.domain),
+            (libc::SOL_SOCKET, SO_PROTOCOL) => Some(self.protocol),
+            _ => None,
+        };
+        if let Some(val) = trusted_val {
+            return Ok(val.to_ne_bytes().to_vec());
+        }
+        match (level, optname) {
+            (libc::SOL_SOCKET, SO_RCVTIMEO) => return Ok(timeout_to_optval(self.recv_timeout())),
+            (libc::SOL_SOCKET, SO_SNDTIMEO) => return Ok(timeout_to_optval(self.send_timeout())),
+            _ => {}
+        }
+
+        // Get the value into the enclave, so that it can be checked before returned
+        // to the user. No buffer is needed if the user gives none.
+        let max_len = min(max_len, MAX_SOCKOPT_LEN);
+        let mut optval = vec![0u8; max_len];
+        let optval_ptr = if max_len == 0 {
+            std::ptr::null_mut()
+        } else {
+            optval.as_mut_ptr() as *mut c_void
+        };
+        let mut optlen = max_len as libc::socklen_t;
+        try_libc!(libc::ocall::getsockopt(
+            self.host_fd,
+            level,
+            optname,
+            optval_ptr,
+            &mut optlen
+        ));
+        // Sanity check: the host cannot return a value longer than the buffer
+        if optlen as usize > max_len {
+            return_errno!(
+                EIO,
+                "the length of socket option given by the host is too large"
+            );
+        }
+        optval.truncate(optlen as usize);
+        check_getsockopt_val(level, optname, &optval)?;
+        Ok(optval)
+    }
+}
+
+impl SocketFile {
+    /// Get an address of the host socket by `get_addr`, i.e., getsockname or
+    /// getpeername, which is checked and normalized before it is returned
+    fn host_sockaddr(
+        &self,
+        get_addr: unsafe fn(c_int, *mut libc::sockaddr, *mut libc::socklen_t) -> c_int,
+    ) -> Result<Vec<u8>> {
+        let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let mut addr_len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        try_libc!(get_addr(
+            self.host_fd,
+            &mut addr as *mut _ as *mut libc::sockaddr,
+            &mut addr_len
+        ));
+        let addr_len = min(
+            addr_len as usize,
+            std::mem::size_of::<libc::sockaddr_storage>(),
+        );
+        let addr_bytes =
+            unsafe { std::slice::from_raw_parts(&addr as *const _ as *const u8, addr_len) };
+        Ok(self.parse_host_addr(addr_bytes)?.as_bytes().to_vec())
+    }
+
+    /// Set the Occlum-specific socket options of host sockets
+    fn setsockopt_occlum(&self, optname: c_int, optval: &[u8]) -> Result<()> {
+        if optname != SO_OCCLUM_BUFFERING {
+            return_errno!(ENOPROTOOPT, "unknown socket option of level SOL_OCCLUM");
+        }
+        if optval.len() < std::mem::size_of::<c_int>() {
+            return_errno!(EINVAL, "optlen is too small for an int");
+        }
+        let val = c_int::from_ne_bytes([optval[0], optval[1], optval[2], optval[3]]);
+        self.set_buffering(val != 0)
+    }
+
+    /// Bind the socket to the network interface named by `optval`, which must be
+    /// allowed by the config. An empty name removes the binding.
+    fn bind_to_device(&self, optval: &[u8]) -> Result<()> {
+        // Like Linux, the name is truncated to IFNAMSIZ - 1 bytes
+        let name_buf = &optval[..min(optval.len(), IFNAMSIZ - 1)];
+        let name_len = name_buf
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(name_buf.len());
+        let name = std::str::from_utf8(&name_buf[..name_len])
+            .map_err(|_| errno!(ENODEV, "invalid name of network interface"))?;
+        if !name.is_empty() && !config::LIBOS_CONFIG.network.bind_devices.contains(name) {
+            let detail = format!("SO_BINDTODEVICE to {}", name);
+            report_security_event(SecurityEventKind::NetDenied, &detail);
+            return_errno!(EPERM, "the network interface is not allowed by the config");
+        }
+
+        let c_name = CString::new(name).unwrap();
+        try_libc!(libc::ocall::setsockopt(
+            self.host_fd,
+            libc::SOL_SOCKET,
+            SO_BINDTODEVICE,
+            c_name.as_ptr() as *const c_void,
+            c_name.as_bytes_with_nul().len() as libc::socklen_t
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use super::*;
+use signal::{has_deliverable_signals, send_sigpipe_on_epipe};
+
+impl SocketFile {
+    /// Start TLS on the connection to `addr` if TLS is configured for the port
+    pub fn init_tls_client(&self, addr: Option<&[u8]>) -> Result<()> {
+        let addr = match addr {
+            Some(addr) if self.may_use_tls() => addr,
+            _ => return Ok(()),
+        };
+        // The session is kept if connect is called again to check the progress of
+        // a non-blocking connect
+        if self.is_tls() {
+            return Ok(());
+        }
+        *self.tls.lock().unwrap() = tls::new_client_session(addr)?;
+        Ok(())
+    }
+
+    /// Start TLS on the connection accepted by `listener` if TLS is configured for
+    /// the port of the listener
+    pub(super) fn init_tls_server(&self, listener: &SocketFile) -> Result<()> {
+        if !self.may_use_tls() || !tls::is_server_enabled() {
+            return Ok(());
+        }
+        let local_addr = listener.addr()?;
+        *self.tls.lock().unwrap() = tls::new_server_session(&local_addr)?;
+        Ok(())
+    }
+
+    /// Wrap the data of the socket in the session, which is started elsewhere
+    #[cfg(feature = "libos_test")]
+    pub fn set_tls_session(&self, session: TlsSession) {
+        *self.tls.lock().unwrap() = Some(session);
+    }
+
+    /// Whether the data of the socket is wrapped in TLS
+    pub fn is_tls(&self) -> bool {
+        self.tls.lock().unwrap().is_some()
+    }
+
+    /// Whether there is any data decrypted but not read yet, which is invisible
+    /// to the host
+    pub fn has_tls_plaintext(&self) -> bool {
+        self.tls
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(false, |session| session.has_plaintext())
+    }
+
+    pub fn tls_sendv(&self, bufs: &[&[u8]], flags: SendFlags) -> Result<usize> {
+        let ret = self.do_tls_sendv(bufs, flags);
+        if flags.contains(SendFlags::MSG_NOSIGNAL) {
+            ret
+        } else {
+            send_sigpipe_on_epipe(ret)
+        }
+    }
+
+    fn do_tls_sendv(&self, bufs: &[&[u8]], flags: SendFlags) -> Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        self.throttle_egress(None, len, flags)?;
+        let nonblocking = self.is_nonblocking(flags.contains(SendFlags::MSG_DONTWAIT))?;
+        // The timeout bounds the whole send, including the handshake and the flush
+        let deadline = Deadline::after(self.send_timeout());
+        if !nonblocking {
+            self.do_tls(false, &deadline, |session, host_fd| {
+                session.handshake(host_fd)
+            })?;
+        }
+        let bytes_sent = self.do_tls(nonblocking, &deadline, |session, host_fd| {
+            session.send(host_fd, bufs)
+        })?;
+        if !nonblocking {
+            // The data is accepted, so the errors are reported by the following I/O
+            let ret = self.do_tls(false, &deadline, |session, host_fd| session.flush(host_fd));
+            if let Err(e) = ret {
+                debug!("failed to flush the TLS records: {:?}", e);
+            }
+        }
+        self.stats.account_sent(bytes_sent);
+        Ok(bytes_sent)
+    }
+
+    pub fn tls_recvv(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> Result<usize> {
+        let nonblocking = self.is_nonblocking(flags.contains(RecvFlags::MSG_DONTWAIT))?;
+        let peek = flags.contains(RecvFlags::MSG_PEEK);
+        let deadline = Deadline::after(self.recv_timeout());
+        let bytes_recvd = self.do_tls(nonblocking, &deadline, |session, host_fd| {
+            session.recv(host_fd, bufs, peek)
+        })?;
+        if !peek {
+            self.stats.account_recvd(bytes_recvd);
+        }
+        Ok(bytes_recvd)
+    }
+
+    /// Send close_notify to the peer, which is best-effort
+    pub fn close_tls(&self) {
+        if let Some(session) = self.tls.lock().unwrap().as_mut() {
+            if let Err(e) = session.close(self.host_fd) {
+                debug!("failed to close TLS: {:?}", e);
+            }
+        }
+    }
+
+    /// Do a non-blocking operation on the session, and retry it when the host
+    /// socket is ready if the operation would block, until the deadline passes
+    fn do_tls<T>(
+        &self,
+        nonblocking: bool,
+        deadline: &Deadline,
+        mut f: impl FnMut(&mut TlsSession, c_int) -> Result<T>,
+    ) -> Result<T> {
+        loop {
+            let events = {
+                let mut tls = self.tls.lock().unwrap();
+                let session = tls
+                    .as_mut()
+                    .ok_or_else(|| errno!(EINVAL, "TLS is not started"))?;
+                match f(session, self.host_fd) {
+                    Err(e) if e.errno() == EAGAIN && !nonblocking => session.wait_events(),
+                    ret => return ret,
+                }
+            };
+            // The session is unlocked during the wait, so that the socket can be read
+            // and written by different threads at the same time
+            let timeout = deadline.remaining()?;
+            if let Err(e) = wait_host_fd(self.host_fd as FileDesc, events, timeout) {
+                if e.errno() != EINTR {
+                    return Err(e);
+                }
+            }
+            if has_deliverable_signals() {
+                return_errno!(EINTR, "interrupted by signals");
+            }
+        }
+    }
+
+    pub(super) fn is_nonblocking(&self, dontwait: bool) -> Result<bool> {
+        Ok(dontwait || self.get_status_flags()?.contains(StatusFlags::O_NONBLOCK))
+    }
+
+    fn may_use_tls(&self) -> bool {
+        self.socket_type == libc::SOCK_STREAM
+            && (self.domain == libc::AF_INET || self.domain == libc::AF_INET6)
+    }
+}
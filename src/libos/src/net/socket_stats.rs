@@ -0,0 +1,82 @@
+//! The traffic statistics of sockets.
+//!
+//! The bytes and packets sent and received are counted per socket file, i.e., per
+//! connection for stream sockets. A packet is counted for each send or receive
+//! that transfers data, which is a datagram for datagram sockets. The statistics
+//! are got by applications via getsockopt of level SOL_OCCLUM, and listed for all
+//! sockets in `/proc/net/occlum_sockets`.
+use super::*;
+use crate::time::{do_clock_gettime, ClockID};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The level of the Occlum-specific socket options, which is not used by Linux
+pub const SOL_OCCLUM: c_int = 0x4f43;
+/// Get the statistics of the socket as struct occlum_sock_stats
+pub const SO_OCCLUM_STATS: c_int = 1;
+
+/// The statistics returned by getsockopt of SO_OCCLUM_STATS.
+///
+/// The average bandwidth of the socket can be computed by dividing the bytes by
+/// `lifetime_ns`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct occlum_sock_stats {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    // The time since the socket is created, in nanoseconds
+    pub lifetime_ns: u64,
+}
+
+#[derive(Debug)]
+pub struct SocketStats {
+    // The monotonic time when the socket is created, in nanoseconds
+    create_time: u64,
+    rx_bytes: AtomicU64,
+    rx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_packets: AtomicU64,
+}
+
+impl SocketStats {
+    pub fn new() -> Self {
+        Self {
+            create_time: now_ns(),
+            rx_bytes: AtomicU64::new(0),
+            rx_packets: AtomicU64::new(0),
+            tx_bytes: AtomicU64::new(0),
+            tx_packets: AtomicU64::new(0),
+        }
+    }
+
+    pub fn account_sent(&self, len: usize) {
+        if len > 0 {
+            self.tx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+            self.tx_packets.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn account_recvd(&self, len: usize) {
+        if len > 0 {
+            self.rx_bytes.fetch_add(len as u64, Ordering::Relaxed);
+            self.rx_packets.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> occlum_sock_stats {
+        occlum_sock_stats {
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            lifetime_ns: now_ns().saturating_sub(self.create_time),
+        }
+    }
+}
+
+fn now_ns() -> u64 {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration().as_nanos() as u64)
+        .unwrap_or(0)
+}
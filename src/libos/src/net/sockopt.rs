@@ -0,0 +1,252 @@
+//! The validation of the socket options that are passed through to the host.
+//!
+//! Most socket options are set and got by the host directly. But the values
+//! given by the user are checked before they are passed to the host, and the
+//! values given by the host are checked before they are returned to the user,
+//! for the options that applications depend on.
+use super::net_dev::IFNAMSIZ;
+use super::*;
+use std::time::Duration;
+use time::timeval_t;
+
+pub const SO_TYPE: c_int = 3;
+pub const SO_SNDBUF: c_int = 7;
+pub const SO_KEEPALIVE: c_int = 9;
+pub const SO_RCVBUF: c_int = 8;
+pub const SO_REUSEPORT: c_int = 15;
+pub const SO_PASSCRED: c_int = 16;
+pub const SO_PEERCRED: c_int = 17;
+pub const SO_RCVTIMEO: c_int = 20;
+pub const SO_SNDTIMEO: c_int = 21;
+pub const SO_BINDTODEVICE: c_int = 25;
+pub const SO_TIMESTAMP: c_int = 29;
+pub const SO_TIMESTAMPNS: c_int = 35;
+pub const SO_PROTOCOL: c_int = 38;
+pub const SO_DOMAIN: c_int = 39;
+
+pub const IP_MTU_DISCOVER: c_int = 10;
+pub const IP_MTU: c_int = 14;
+pub const IP_FREEBIND: c_int = 15;
+pub const IP_MULTICAST_TTL: c_int = 33;
+pub const IP_MULTICAST_LOOP: c_int = 34;
+pub const IP_ADD_MEMBERSHIP: c_int = 35;
+pub const IP_DROP_MEMBERSHIP: c_int = 36;
+
+pub const TCP_KEEPIDLE: c_int = 4;
+pub const TCP_KEEPINTVL: c_int = 5;
+pub const TCP_KEEPCNT: c_int = 6;
+
+pub const IPPROTO_IPV6: c_int = 41;
+pub const IPV6_MULTICAST_HOPS: c_int = 18;
+pub const IPV6_MULTICAST_LOOP: c_int = 19;
+pub const IPV6_ADD_MEMBERSHIP: c_int = 20;
+pub const IPV6_DROP_MEMBERSHIP: c_int = 21;
+pub const IPV6_V6ONLY: c_int = 26;
+
+// The valid values of IP_MTU_DISCOVER are IP_PMTUDISC_DONT (0) to IP_PMTUDISC_OMIT (5)
+const IP_PMTUDISC_OMIT: c_int = 5;
+// The valid range of MTU of IPv4, according to RFC 791
+const IP_MIN_MTU: c_int = 68;
+const IP_MAX_MTU: c_int = 65535;
+
+// The valid ranges of the keepalive options of TCP, as Linux
+pub const MAX_TCP_KEEPALIVE_SECS: c_int = 32767;
+pub const MAX_TCP_KEEPCNT: c_int = 127;
+
+// The max length of the value of a socket option got from the host. The length
+// is given by the user, while the values of the options of Linux are much smaller.
+pub const MAX_SOCKOPT_LEN: usize = 1024;
+
+// The sizes of struct ip_mreq and struct ipv6_mreq
+const IP_MREQ_SIZE: usize = 8;
+const IPV6_MREQ_SIZE: usize = 20;
+
+/// Check the value of a socket option given by the user, before it is set by the host
+pub fn check_setsockopt_val(level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+    match (level, optname) {
+        (libc::IPPROTO_IP, IP_MTU_DISCOVER) => {
+            let val = int_optval(optval)?;
+            if val < 0 || val > IP_PMTUDISC_OMIT {
+                return_errno!(EINVAL, "invalid value of IP_MTU_DISCOVER");
+            }
+        }
+        // Allow binding to a non-local or not-yet-configured address, which is
+        // required by multi-homed deployments
+        (libc::IPPROTO_IP, IP_FREEBIND) => {
+            int_or_byte_optval(optval)?;
+        }
+        (libc::IPPROTO_IP, IP_MULTICAST_TTL) => {
+            let ttl = int_or_byte_optval(optval)?;
+            // -1 means the default TTL
+            if ttl < -1 || ttl > 255 {
+                return_errno!(EINVAL, "invalid value of IP_MULTICAST_TTL");
+            }
+        }
+        (libc::IPPROTO_IP, IP_MULTICAST_LOOP) => {
+            int_or_byte_optval(optval)?;
+        }
+        (libc::IPPROTO_IP, IP_ADD_MEMBERSHIP) | (libc::IPPROTO_IP, IP_DROP_MEMBERSHIP) => {
+            // struct ip_mreqn, which is a superset of struct ip_mreq, is also accepted
+            if optval.len() < IP_MREQ_SIZE {
+                return_errno!(EINVAL, "optlen is too small for ip_mreq");
+            }
+            // imr_multiaddr must be in 224.0.0.0/4
+            if optval[0] & 0xf0 != 0xe0 {
+                return_errno!(EINVAL, "not a multicast address");
+            }
+        }
+        (libc::IPPROTO_TCP, TCP_KEEPIDLE) | (libc::IPPROTO_TCP, TCP_KEEPINTVL) => {
+            let secs = int_optval(optval)?;
+            if secs < 1 || secs > MAX_TCP_KEEPALIVE_SECS {
+                return_errno!(EINVAL, "invalid time of TCP keepalive");
+            }
+        }
+        (libc::IPPROTO_TCP, TCP_KEEPCNT) => {
+            let count = int_optval(optval)?;
+            if count < 1 || count > MAX_TCP_KEEPCNT {
+                return_errno!(EINVAL, "invalid value of TCP_KEEPCNT");
+            }
+        }
+        (IPPROTO_IPV6, IPV6_MULTICAST_HOPS) => {
+            let hops = int_optval(optval)?;
+            // -1 means the default hop limit
+            if hops < -1 || hops > 255 {
+                return_errno!(EINVAL, "invalid value of IPV6_MULTICAST_HOPS");
+            }
+        }
+        (IPPROTO_IPV6, IPV6_MULTICAST_LOOP) => {
+            let val = int_optval(optval)?;
+            if val < 0 || val > 1 {
+                return_errno!(EINVAL, "invalid value of IPV6_MULTICAST_LOOP");
+            }
+        }
+        (IPPROTO_IPV6, IPV6_ADD_MEMBERSHIP) | (IPPROTO_IPV6, IPV6_DROP_MEMBERSHIP) => {
+            if optval.len() < IPV6_MREQ_SIZE {
+                return_errno!(EINVAL, "optlen is too small for ipv6_mreq");
+            }
+            // ipv6mr_multiaddr must be in ff00::/8
+            if optval[0] != 0xff {
+                return_errno!(EINVAL, "not a multicast address");
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Check the value of a socket option given by the host, before it is returned to the user.
+///
+/// Applications act on these options (e.g., adaptive protocols size their packets
+/// by IP_MTU), so a bogus value given by the host must not be passed to them.
+pub fn check_getsockopt_val(level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+    let valid_range = match (level, optname) {
+        (libc::SOL_SOCKET, SO_BINDTODEVICE) => return check_bound_device(optval),
+        // The pending error, e.g., the result of a non-blocking connect
+        (libc::SOL_SOCKET, libc::SO_ERROR) => 0..=Errno::EHWPOISON as c_int,
+        (libc::SOL_SOCKET, SO_TIMESTAMP) | (libc::SOL_SOCKET, SO_TIMESTAMPNS) => 0..=1,
+        (libc::SOL_SOCKET, SO_KEEPALIVE) => 0..=1,
+        (libc::IPPROTO_IP, IP_MTU) => IP_MIN_MTU..=IP_MAX_MTU,
+        (libc::IPPROTO_IP, IP_MTU_DISCOVER) => 0..=IP_PMTUDISC_OMIT,
+        (libc::IPPROTO_IP, IP_MULTICAST_TTL) => 0..=255,
+        (libc::IPPROTO_IP, IP_MULTICAST_LOOP) => 0..=1,
+        (libc::IPPROTO_TCP, TCP_KEEPIDLE) | (libc::IPPROTO_TCP, TCP_KEEPINTVL) => {
+            1..=MAX_TCP_KEEPALIVE_SECS
+        }
+        (libc::IPPROTO_TCP, TCP_KEEPCNT) => 1..=MAX_TCP_KEEPCNT,
+        (IPPROTO_IPV6, IPV6_MULTICAST_HOPS) => 0..=255,
+        (IPPROTO_IPV6, IPV6_MULTICAST_LOOP) => 0..=1,
+        _ => return Ok(()),
+    };
+    // Like Linux, the value is given as a byte if the buffer of the user is
+    // smaller than an int. A value that is truncated otherwise cannot be checked.
+    let val = match optval.len() {
+        1 => optval[0] as c_int,
+        len if len == std::mem::size_of::<c_int>() => int_optval(optval)?,
+        _ => return Ok(()),
+    };
+    if !valid_range.contains(&val) {
+        return_errno!(EIO, "invalid socket option given by the host");
+    }
+    Ok(())
+}
+
+/// Check the name of the network interface that the socket is bound to, which
+/// is given by the host
+fn check_bound_device(name_buf: &[u8]) -> Result<()> {
+    // The name is empty if the socket is not bound
+    if name_buf.is_empty() {
+        return Ok(());
+    }
+    let name_len = name_buf
+        .iter()
+        .position(|&b| b == 0)
+        .filter(|&len| len < IFNAMSIZ)
+        .ok_or_else(|| errno!(EIO, "invalid interface name given by the host"))?;
+    let is_allowed = match std::str::from_utf8(&name_buf[..name_len]) {
+        Ok(name) => name.is_empty() || config::LIBOS_CONFIG.network.bind_devices.contains(name),
+        Err(_) => false,
+    };
+    if !is_allowed {
+        return_errno!(
+            EIO,
+            "the socket is bound to an unexpected interface by the host"
+        );
+    }
+    Ok(())
+}
+
+pub fn int_optval(optval: &[u8]) -> Result<c_int> {
+    if optval.len() < std::mem::size_of::<c_int>() {
+        return_errno!(EINVAL, "optlen is too small");
+    }
+    let mut bytes = [0; std::mem::size_of::<c_int>()];
+    bytes.copy_from_slice(&optval[..std::mem::size_of::<c_int>()]);
+    Ok(c_int::from_ne_bytes(bytes))
+}
+
+/// Get the timeout of SO_RCVTIMEO or SO_SNDTIMEO, which is given as a struct
+/// timeval. Like Linux, a zero timeout is infinite, i.e., None, and a negative
+/// one expires at once.
+pub fn timeout_optval(optval: &[u8]) -> Result<Option<Duration>> {
+    if optval.len() < std::mem::size_of::<timeval_t>() {
+        return_errno!(EINVAL, "optlen is too small for timeval");
+    }
+    let (sec, usec) = {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&optval[..8]);
+        let sec = i64::from_ne_bytes(bytes);
+        bytes.copy_from_slice(&optval[8..16]);
+        (sec, i64::from_ne_bytes(bytes))
+    };
+    if usec < 0 || usec >= 1_000_000 {
+        return_errno!(EDOM, "invalid usec of the timeout");
+    }
+    if sec < 0 {
+        return Ok(Some(Duration::from_secs(0)));
+    }
+    if sec == 0 && usec == 0 {
+        return Ok(None);
+    }
+    Ok(Some(Duration::new(sec as u64, usec as u32 * 1_000)))
+}
+
+/// The struct timeval of SO_RCVTIMEO or SO_SNDTIMEO, which is zero if infinite
+pub fn timeout_to_optval(timeout: Option<Duration>) -> Vec<u8> {
+    let timeval = timeout.map(timeval_t::from).unwrap_or_default();
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            &timeval as *const timeval_t as *const u8,
+            std::mem::size_of::<timeval_t>(),
+        )
+    };
+    bytes.to_vec()
+}
+
+/// Some options of level IPPROTO_IP accept a byte as well as an int
+fn int_or_byte_optval(optval: &[u8]) -> Result<c_int> {
+    match optval.len() {
+        0 => return_errno!(EINVAL, "optlen is too small"),
+        len if len < std::mem::size_of::<c_int>() => Ok(optval[0] as c_int),
+        _ => int_optval(optval),
+    }
+}
@@ -0,0 +1,240 @@
+//! The virtual NIC of the stack, which is backed by a tap device of the host.
+//!
+//! The frames sent by the stack to itself, e.g., of the connections to its own
+//! address, are looped back in the device and never reach the host.
+use super::*;
+use smoltcp::phy::{Device, DeviceCapabilities, RxToken, TxToken};
+use smoltcp::time::Instant;
+use smoltcp::wire::{ArpPacket, EthernetFrame, EthernetProtocol};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// The max length of an Ethernet frame without the FCS
+const MAX_FRAME_LEN: usize = 1514;
+/// The max number of the frames looped back but not received yet, beyond which
+/// the frames are dropped
+const MAX_LOOPBACK_FRAMES: usize = 64;
+/// The interval to read the tap device even if it is not known to be readable, so
+/// that the frames still arrive at the sockets that are never waited on
+const RX_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug)]
+pub struct TapDevice {
+    host_fd: c_int,
+    // The buffers of the frames, which are reused by the tokens
+    rx_buf: Vec<u8>,
+    tx_buf: Vec<u8>,
+    rx_readiness: Arc<RxReadiness>,
+    loopback: Loopback,
+}
+
+/// The frames sent by the stack to itself
+#[derive(Debug)]
+struct Loopback {
+    mac: EthernetAddress,
+    addr: Ipv4Address,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl Loopback {
+    /// Whether the frame is sent to the stack itself, i.e., to its MAC address, or
+    /// an ARP request for its IP address
+    fn is_to_self(&self, frame: &[u8]) -> bool {
+        let frame = match EthernetFrame::new_checked(frame) {
+            Ok(frame) => frame,
+            Err(_) => return false,
+        };
+        if frame.dst_addr() == self.mac {
+            return true;
+        }
+        if frame.dst_addr().is_broadcast() && frame.ethertype() == EthernetProtocol::Arp {
+            if let Ok(packet) = ArpPacket::new_checked(frame.payload()) {
+                return packet.target_protocol_addr() == self.addr.as_bytes();
+            }
+        }
+        false
+    }
+
+    fn push(&mut self, frame: &[u8]) {
+        // Dropped like a frame lost in the network if too many are not received
+        if self.frames.len() < MAX_LOOPBACK_FRAMES {
+            self.frames.push_back(frame.to_vec());
+        }
+    }
+}
+
+/// Whether a frame may have arrived at the tap device. A read of the tap is an
+/// OCall, so the device only reads the tap when it may be readable, i.e., after a
+/// wait on the tap returns, until a read finds nothing.
+#[derive(Debug, Default)]
+pub struct RxReadiness {
+    is_ready: AtomicBool,
+    // The time when the tap is last marked readable by `refresh`, in milliseconds
+    // since boot
+    last_refresh_ms: AtomicU64,
+}
+
+impl RxReadiness {
+    /// Mark the tap as readable, e.g., after a wait on the tap returns
+    pub fn set_ready(&self) {
+        self.is_ready.store(true, Ordering::Release);
+    }
+
+    /// Mark the tap as readable if it has not been read for a while
+    pub fn refresh(&self, now: Instant) {
+        let now_ms = now.total_millis() as u64;
+        let last_refresh_ms = self.last_refresh_ms.load(Ordering::Relaxed);
+        if now_ms.saturating_sub(last_refresh_ms) >= RX_CHECK_INTERVAL.as_millis() as u64 {
+            self.last_refresh_ms.store(now_ms, Ordering::Relaxed);
+            self.set_ready();
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        self.is_ready.load(Ordering::Acquire)
+    }
+
+    fn clear(&self) {
+        self.is_ready.store(false, Ordering::Release);
+    }
+}
+
+impl TapDevice {
+    /// Open the tap device for the stack of the MAC address and the IP address
+    pub fn open(name: &str, mac: EthernetAddress, addr: Ipv4Address) -> Result<Self> {
+        let c_name = CString::new(name).map_err(|_| errno!(EINVAL, "invalid name of tap"))?;
+        let host_fd = try_libc!({
+            let mut retval: c_int = 0;
+            let status = occlum_ocall_open_tap(&mut retval, c_name.as_ptr());
+            assert!(status == sgx_status_t::SGX_SUCCESS);
+            retval
+        });
+        Ok(Self {
+            host_fd,
+            rx_buf: vec![0; MAX_FRAME_LEN],
+            tx_buf: vec![0; MAX_FRAME_LEN],
+            rx_readiness: Arc::new(RxReadiness::default()),
+            loopback: Loopback {
+                mac,
+                addr,
+                frames: VecDeque::new(),
+            },
+        })
+    }
+
+    pub fn host_fd(&self) -> c_int {
+        self.host_fd
+    }
+
+    pub fn rx_readiness(&self) -> &Arc<RxReadiness> {
+        &self.rx_readiness
+    }
+}
+
+impl Drop for TapDevice {
+    fn drop(&mut self) {
+        unsafe { libc::ocall::close(self.host_fd) };
+    }
+}
+
+impl<'a> Device<'a> for TapDevice {
+    type RxToken = TapRxToken<'a>;
+    type TxToken = TapTxToken<'a>;
+
+    fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+        if let Some(frame) = self.loopback.frames.pop_front() {
+            let rx_frame = &mut self.rx_buf[..frame.len()];
+            rx_frame.copy_from_slice(&frame);
+            return Some((
+                TapRxToken(rx_frame),
+                TapTxToken {
+                    host_fd: self.host_fd,
+                    buf: &mut self.tx_buf,
+                    loopback: &mut self.loopback,
+                },
+            ));
+        }
+        if !self.rx_readiness.is_ready() {
+            return None;
+        }
+        // The tap device is non-blocking, so it fails with EAGAIN if there is no frame
+        let len = unsafe {
+            libc::ocall::read(
+                self.host_fd,
+                self.rx_buf.as_mut_ptr() as *mut c_void,
+                self.rx_buf.len(),
+            )
+        };
+        // The frames given by the host are untrusted, which are checked by the stack
+        // like the ones from the network. Only the length is checked here.
+        if len <= 0 || len as usize > self.rx_buf.len() {
+            self.rx_readiness.clear();
+            return None;
+        }
+        Some((
+            TapRxToken(&mut self.rx_buf[..len as usize]),
+            TapTxToken {
+                host_fd: self.host_fd,
+                buf: &mut self.tx_buf,
+                loopback: &mut self.loopback,
+            },
+        ))
+    }
+
+    fn transmit(&'a mut self) -> Option<Self::TxToken> {
+        Some(TapTxToken {
+            host_fd: self.host_fd,
+            buf: &mut self.tx_buf,
+            loopback: &mut self.loopback,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = MAX_FRAME_LEN;
+        caps
+    }
+}
+
+pub struct TapRxToken<'a>(&'a mut [u8]);
+
+impl<'a> RxToken for TapRxToken<'a> {
+    fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        f(self.0)
+    }
+}
+
+pub struct TapTxToken<'a> {
+    host_fd: c_int,
+    buf: &'a mut [u8],
+    loopback: &'a mut Loopback,
+}
+
+impl<'a> TxToken for TapTxToken<'a> {
+    fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+    where
+        F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+    {
+        // The stack never sends a frame larger than the MTU
+        let frame = self.buf.get_mut(..len).ok_or(smoltcp::Error::Exhausted)?;
+        let ret = f(frame)?;
+        if self.loopback.is_to_self(frame) {
+            self.loopback.push(frame);
+            return Ok(ret);
+        }
+        let written =
+            unsafe { libc::ocall::write(self.host_fd, frame.as_ptr() as *const c_void, len) };
+        // A frame that fails to be sent is dropped, like a frame lost in the network,
+        // which is recovered by the retransmission of TCP
+        if written != len as isize {
+            debug!("failed to send a frame of {} bytes to the tap", len);
+        }
+        Ok(ret)
+    }
+}
+
+extern "C" {
+    fn occlum_ocall_open_tap(ret: *mut c_int, name: *const c_char) -> sgx_status_t;
+}
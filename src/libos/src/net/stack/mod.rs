@@ -0,0 +1,346 @@
+//! The in-enclave TCP/IP stack.
+//!
+//! By default, the PF_INET sockets are host sockets, whose data and states are
+//! exposed to the host as the arguments of ocalls. If the stack is configured in
+//! Occlum.json, the TCP sockets are served by the stack of smoltcp in the enclave
+//! instead, and only the Ethernet frames are exchanged with a tap device of the
+//! host. So the TCP state machines and the socket states are trusted, while the
+//! payload is still visible to the host unless the application encrypts it, e.g.,
+//! with TLS.
+//!
+//! There is no kernel thread in the LibOS, so the stack is driven by the threads
+//! that use its sockets. The stack is polled whenever its sockets are operated on,
+//! and a blocking operation waits for the next frame from the tap device or the
+//! next timer of the stack, e.g., a retransmission. The sockets can be polled with
+//! poll and select. As the host cannot tell when they are ready, they cannot be
+//! added to epoll, where epoll_ctl fails with EPERM.
+//!
+//! A listening socket takes a socket of the stack for each pending connection.
+//! Only a few of them listen ahead of the connections, and more are added as the
+//! connections arrive, up to the backlog.
+//!
+//! Only TCP sockets are supported for now. The UDP sockets and the other sockets
+//! of PF_INET are still host sockets.
+use super::*;
+use crate::config::ConfigNetStack;
+use crate::signal::has_deliverable_signals;
+use smoltcp::iface::{EthernetInterface, EthernetInterfaceBuilder, NeighborCache, Routes};
+use smoltcp::socket::{SocketHandle, SocketSet, TcpSocket, TcpSocketBuffer, TcpState};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::CString;
+use std::time::Duration;
+
+pub use self::tcp_socket::{AsTcpStackSocket, TcpStackSocket};
+
+use self::device::{RxReadiness, TapDevice};
+
+mod device;
+mod tcp_socket;
+
+/// The size of the receive buffer and the send buffer of a TCP socket
+const TCP_BUF_SIZE: usize = 64 * 1024;
+/// The max number of the sockets of the stack that wait for new connections to a
+/// listening port
+const MAX_IDLE_LISTENERS: usize = 4;
+/// The local ports assigned to the sockets that are not bound, as on Linux
+const EPHEMERAL_PORT_MIN: u16 = 32768;
+const EPHEMERAL_PORT_MAX: u16 = 60999;
+
+lazy_static! {
+    static ref STACK: Option<NetStack> = match &config::LIBOS_CONFIG.network.stack {
+        Some(config) => match NetStack::new(config) {
+            Ok(stack) => Some(stack),
+            Err(e) => {
+                error!("failed to start the network stack: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
+}
+
+/// Start the stack if it is configured in Occlum.json. The LibOS fails to start if
+/// the stack fails, so that the TCP sockets never fall back to host sockets.
+pub fn init_stack() -> Result<()> {
+    if config::LIBOS_CONFIG.network.stack.is_some() && STACK.is_none() {
+        return_errno!(ENETDOWN, "the network stack fails to start");
+    }
+    Ok(())
+}
+
+/// Whether the TCP sockets are served by the stack
+pub fn is_enabled() -> bool {
+    STACK.is_some()
+}
+
+/// Mark the tap device of the stack as readable, after a wait on it returns
+pub fn set_rx_ready() {
+    if let Some(stack) = STACK.as_ref() {
+        stack.rx_readiness.set_ready();
+    }
+}
+
+/// Get the host fd to wait on and the max time to wait, so that a poll on the
+/// sockets of the stack is woken up when the stack may make progress
+pub fn next_wakeup() -> Option<(c_int, Option<Duration>)> {
+    let stack = STACK.as_ref()?;
+    let delay = stack.inner.lock().unwrap().poll_delay();
+    Some((stack.host_fd, delay))
+}
+
+fn stack() -> Result<&'static NetStack> {
+    STACK
+        .as_ref()
+        .ok_or_else(|| errno!(ENETDOWN, "the network stack fails to start"))
+}
+
+struct NetStack {
+    inner: SgxMutex<NetStackInner>,
+    // The fd of the tap device, which is readable when a frame arrives
+    host_fd: c_int,
+    rx_readiness: Arc<RxReadiness>,
+    addr: Ipv4Address,
+}
+
+struct NetStackInner {
+    iface: EthernetInterface<'static, 'static, 'static, TapDevice>,
+    sockets: SocketSet<'static, 'static, 'static>,
+    rx_readiness: Arc<RxReadiness>,
+    // The local ports that are bound or assigned
+    used_ports: HashSet<u16>,
+    next_ephemeral_port: u16,
+    // The sockets closed by the users, which are kept until the connections are
+    // closed gracefully. The port is released along with the socket if owned.
+    closing: Vec<(SocketHandle, Option<u16>)>,
+    // The backlogs of the listening sockets, keyed by the local ports
+    backlogs: HashMap<u16, Backlog>,
+}
+
+/// The sockets of the stack that serve a listening socket, i.e., the ones waiting
+/// for new connections and the connections not accepted yet
+struct Backlog {
+    local: IpEndpoint,
+    max_len: usize,
+    handles: VecDeque<SocketHandle>,
+}
+
+impl Backlog {
+    /// Replace the sockets aborted before accepted, and add the sockets to listen
+    /// until enough are idle or the backlog is full
+    fn refill(&mut self, sockets: &mut SocketSet<'static, 'static, 'static>) {
+        self.handles.retain(|&handle| {
+            if sockets.get::<TcpSocket>(handle).state() != TcpState::Closed {
+                return true;
+            }
+            sockets.remove(handle);
+            false
+        });
+        let mut num_idle = self
+            .handles
+            .iter()
+            .filter(|&&handle| sockets.get::<TcpSocket>(handle).state() == TcpState::Listen)
+            .count();
+        while num_idle < MAX_IDLE_LISTENERS && self.handles.len() < self.max_len {
+            let mut socket = new_tcp_socket();
+            // Never fails, as the port is not zero and the socket is closed
+            socket.listen(self.local).unwrap();
+            self.handles.push_back(sockets.add(socket));
+            num_idle += 1;
+        }
+    }
+}
+
+impl NetStack {
+    fn new(config: &ConfigNetStack) -> Result<Self> {
+        let addr = Ipv4Address(config.addr.octets());
+        let device = TapDevice::open(&config.tap, EthernetAddress(config.mac), addr)?;
+        let host_fd = device.host_fd();
+        let rx_readiness = device.rx_readiness().clone();
+        let mut routes = Routes::new(BTreeMap::new());
+        if let Some(gateway) = config.gateway {
+            routes
+                .add_default_ipv4_route(Ipv4Address(gateway.octets()))
+                .map_err(|_| errno!(EINVAL, "invalid gateway"))?;
+        }
+        let iface = EthernetInterfaceBuilder::new(device)
+            .ethernet_addr(EthernetAddress(config.mac))
+            .neighbor_cache(NeighborCache::new(BTreeMap::new()))
+            .ip_addrs(vec![IpCidr::new(IpAddress::Ipv4(addr), config.prefix_len)])
+            .routes(routes)
+            .finalize();
+        let inner = NetStackInner {
+            iface,
+            sockets: SocketSet::new(vec![]),
+            rx_readiness: rx_readiness.clone(),
+            used_ports: HashSet::new(),
+            next_ephemeral_port: EPHEMERAL_PORT_MIN,
+            closing: Vec::new(),
+            backlogs: HashMap::new(),
+        };
+        info!(
+            "network stack is up: tap: {}, addr: {}/{}",
+            config.tap, addr, config.prefix_len
+        );
+        Ok(Self {
+            inner: SgxMutex::new(inner),
+            host_fd,
+            rx_readiness,
+            addr,
+        })
+    }
+
+    /// Lock the stack, which is polled first so that the sockets are up to date
+    fn lock(&self) -> SgxMutexGuard<NetStackInner> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.poll();
+        inner
+    }
+
+    /// Wait until the stack may make progress, i.e., a frame arrives or a timer of
+    /// the stack expires, or the thread is interrupted by signals.
+    fn wait(&self) -> Result<()> {
+        let delay = self.inner.lock().unwrap().poll_delay();
+        if let Err(e) = wait_host_fd(self.host_fd as FileDesc, PollEventFlags::POLLIN, delay) {
+            if e.errno() != EINTR {
+                return Err(e);
+            }
+        }
+        // The wait does not tell whether the tap is readable, which is found out by
+        // the next read
+        self.rx_readiness.set_ready();
+        if has_deliverable_signals() {
+            return_errno!(EINTR, "interrupted by signals");
+        }
+        Ok(())
+    }
+}
+
+impl NetStackInner {
+    /// Process the frames received and send the frames of the sockets
+    fn poll(&mut self) {
+        let timestamp = now();
+        self.rx_readiness.refresh(timestamp);
+        if let Err(e) = self.iface.poll(&mut self.sockets, timestamp) {
+            debug!("network stack poll error: {:?}", e);
+        }
+
+        let sockets = &mut self.sockets;
+        let used_ports = &mut self.used_ports;
+        self.closing.retain(|&(handle, port)| {
+            let state = sockets.get::<TcpSocket>(handle).state();
+            if state != TcpState::Closed && state != TcpState::TimeWait {
+                return true;
+            }
+            sockets.remove(handle);
+            if let Some(port) = port {
+                used_ports.remove(&port);
+            }
+            false
+        });
+
+        // The idle sockets taken by the connections just arrived are replenished
+        for backlog in self.backlogs.values_mut() {
+            backlog.refill(&mut self.sockets);
+        }
+    }
+
+    fn poll_delay(&mut self) -> Option<Duration> {
+        self.iface
+            .poll_delay(&self.sockets, now())
+            .map(|delay| Duration::from_millis(delay.total_millis()))
+    }
+
+    fn new_tcp_socket(&mut self) -> SocketHandle {
+        self.sockets.add(new_tcp_socket())
+    }
+
+    /// Listen on the local endpoint for at most `max_len` pending connections
+    fn listen(&mut self, local: IpEndpoint, max_len: usize) {
+        let mut backlog = Backlog {
+            local,
+            max_len,
+            handles: VecDeque::new(),
+        };
+        backlog.refill(&mut self.sockets);
+        self.backlogs.insert(local.port, backlog);
+    }
+
+    /// Take an established connection from the backlog of the port
+    fn accept(&mut self, port: u16) -> Option<SocketHandle> {
+        let backlog = self.backlogs.get_mut(&port)?;
+        let sockets = &mut self.sockets;
+        let i = backlog
+            .handles
+            .iter()
+            .position(|&handle| is_established(sockets.get::<TcpSocket>(handle).state()))?;
+        let handle = backlog.handles.remove(i).unwrap();
+        backlog.refill(sockets);
+        Some(handle)
+    }
+
+    /// Whether any connection in the backlog of the port can be accepted
+    fn has_pending(&mut self, port: u16) -> bool {
+        let sockets = &mut self.sockets;
+        match self.backlogs.get(&port) {
+            Some(backlog) => backlog
+                .handles
+                .iter()
+                .any(|&handle| is_established(sockets.get::<TcpSocket>(handle).state())),
+            None => false,
+        }
+    }
+
+    /// Stop listening on the port, where the connections not accepted are reset
+    fn unlisten(&mut self, port: u16) {
+        if let Some(backlog) = self.backlogs.remove(&port) {
+            for handle in backlog.handles {
+                self.sockets.get::<TcpSocket>(handle).abort();
+                self.sockets.remove(handle);
+            }
+        }
+    }
+
+    /// Take the local port, or an ephemeral port if the port is zero
+    fn alloc_port(&mut self, port: u16) -> Result<u16> {
+        if port != 0 {
+            if !self.used_ports.insert(port) {
+                return_errno!(EADDRINUSE, "the port is in use");
+            }
+            return Ok(port);
+        }
+        let num_ports = EPHEMERAL_PORT_MAX - EPHEMERAL_PORT_MIN + 1;
+        for _ in 0..num_ports {
+            let port = self.next_ephemeral_port;
+            self.next_ephemeral_port = if port == EPHEMERAL_PORT_MAX {
+                EPHEMERAL_PORT_MIN
+            } else {
+                port + 1
+            };
+            if self.used_ports.insert(port) {
+                return Ok(port);
+            }
+        }
+        return_errno!(EADDRNOTAVAIL, "no ephemeral port is available");
+    }
+
+    fn release_port(&mut self, port: u16) {
+        self.used_ports.remove(&port);
+    }
+}
+
+fn new_tcp_socket() -> TcpSocket<'static> {
+    let rx_buffer = TcpSocketBuffer::new(vec![0; TCP_BUF_SIZE]);
+    let tx_buffer = TcpSocketBuffer::new(vec![0; TCP_BUF_SIZE]);
+    TcpSocket::new(rx_buffer, tx_buffer)
+}
+
+/// Whether the connection of a listening socket can be accepted
+fn is_established(state: TcpState) -> bool {
+    state == TcpState::Established || state == TcpState::CloseWait
+}
+
+fn now() -> Instant {
+    Instant::from_millis(crate::time::up_time::get().as_millis() as i64)
+}
@@ -0,0 +1,722 @@
+//! The TCP sockets served by the in-enclave stack.
+use super::super::socket::{sockaddr_bytes_of, Socket};
+use super::super::sockopt::{SO_DOMAIN, SO_PROTOCOL, SO_TYPE};
+use super::*;
+use fs::{AccessMode, File, FileRef, IoctlCmd, StatusFlags};
+use signal::send_sigpipe_on_epipe;
+use std::any::Any;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The max number of the pending connections of a listening socket, each of which
+/// takes a socket of the stack along with its buffers
+const MAX_BACKLOG: usize = 32;
+
+#[derive(Debug)]
+pub struct TcpStackSocket {
+    state: SgxMutex<State>,
+    nonblocking: AtomicBool,
+}
+
+#[derive(Debug)]
+enum State {
+    // Neither connecting nor listening, which may be bound to a local address
+    Init { local: Option<IpEndpoint> },
+    // The backlog is kept by the stack, which adds the sockets to listen as the
+    // connections arrive
+    Listening { local: IpEndpoint },
+    Connected(Connection),
+}
+
+#[derive(Debug)]
+struct Connection {
+    handle: SocketHandle,
+    // The local port owned by the connection, i.e., not accepted from a listener
+    local_port: Option<u16>,
+    // Whether the connection is being established by connect
+    connecting: bool,
+    // The error of connect, which is taken by SO_ERROR
+    error: Option<Errno>,
+    shut_rd: bool,
+}
+
+impl Connection {
+    /// Check whether the connection in progress is established or refused
+    fn update(&mut self, socket: &TcpSocket) {
+        if !self.connecting {
+            return;
+        }
+        match socket.state() {
+            TcpState::SynSent | TcpState::SynReceived => {}
+            TcpState::Closed => {
+                self.connecting = false;
+                self.error = Some(ECONNREFUSED);
+            }
+            _ => self.connecting = false,
+        }
+    }
+}
+
+impl TcpStackSocket {
+    pub fn new(socket_type: c_int, protocol: c_int) -> Result<Self> {
+        if !Self::is_tcp(socket_type, protocol) {
+            return_errno!(EPROTONOSUPPORT, "only TCP is supported by the stack");
+        }
+        stack()?;
+        let nonblocking = socket_type & libc::SOCK_NONBLOCK != 0;
+        Ok(Self::new_with_state(
+            State::Init { local: None },
+            nonblocking,
+        ))
+    }
+
+    /// Whether the socket of PF_INET with the type and the protocol is TCP
+    pub fn is_tcp(socket_type: c_int, protocol: c_int) -> bool {
+        let socket_type = socket_type & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        socket_type == libc::SOCK_STREAM && (protocol == 0 || protocol == libc::IPPROTO_TCP)
+    }
+
+    fn new_with_state(state: State, nonblocking: bool) -> Self {
+        Self {
+            state: SgxMutex::new(state),
+            nonblocking: AtomicBool::new(nonblocking),
+        }
+    }
+
+    pub fn bind(&self, addr_buf: &[u8]) -> Result<()> {
+        let local = parse_sockaddr_in(addr_buf)?;
+        let stack = stack()?;
+        if let IpAddress::Ipv4(addr) = local.addr {
+            if addr != stack.addr {
+                return_errno!(EADDRNOTAVAIL, "the address is not of the stack");
+            }
+        }
+        let mut state = self.state.lock().unwrap();
+        match &*state {
+            State::Init { local: None } => {}
+            _ => return_errno!(EINVAL, "the socket is already bound"),
+        }
+        let port = stack.lock().alloc_port(local.port)?;
+        *state = State::Init {
+            local: Some(IpEndpoint::new(local.addr, port)),
+        };
+        Ok(())
+    }
+
+    pub fn listen(&self, backlog: c_int) -> Result<()> {
+        let stack = stack()?;
+        let mut state = self.state.lock().unwrap();
+        let local = match &*state {
+            State::Init { local } => *local,
+            // The backlog cannot be changed for now
+            State::Listening { .. } => return Ok(()),
+            State::Connected(_) => return_errno!(EINVAL, "the socket is connected"),
+        };
+
+        let mut stack_inner = stack.lock();
+        // Like Linux, the socket that is not bound is bound to an ephemeral port
+        let local = match local {
+            Some(local) => local,
+            None => IpEndpoint::new(IpAddress::Unspecified, stack_inner.alloc_port(0)?),
+        };
+        let backlog_len = min(max(backlog, 1) as usize, MAX_BACKLOG);
+        stack_inner.listen(local, backlog_len);
+        *state = State::Listening { local };
+        Ok(())
+    }
+
+    /// Accept a connection, which returns the socket of the connection and the
+    /// address of the peer
+    pub fn accept(&self, nonblocking: bool) -> Result<(Self, libc::sockaddr_in)> {
+        let stack = stack()?;
+        loop {
+            {
+                let state = self.state.lock().unwrap();
+                let local = match &*state {
+                    State::Listening { local } => *local,
+                    _ => return_errno!(EINVAL, "the socket is not listening"),
+                };
+                let mut stack_inner = stack.lock();
+                if let Some(handle) = stack_inner.accept(local.port) {
+                    let peer = stack_inner
+                        .sockets
+                        .get::<TcpSocket>(handle)
+                        .remote_endpoint();
+                    let connection = Connection {
+                        handle,
+                        local_port: None,
+                        connecting: false,
+                        error: None,
+                        shut_rd: false,
+                    };
+                    let new_socket =
+                        Self::new_with_state(State::Connected(connection), nonblocking);
+                    return Ok((new_socket, sockaddr_in_of(peer)));
+                }
+            }
+            if self.is_nonblocking() {
+                return_errno!(EAGAIN, "no connection is pending");
+            }
+            stack.wait()?;
+        }
+    }
+
+    pub fn connect(&self, addr_buf: &[u8]) -> Result<()> {
+        let remote = parse_sockaddr_in(addr_buf)?;
+        if !remote.is_specified() {
+            return_errno!(ECONNREFUSED, "the address to connect is unspecified");
+        }
+        let stack = stack()?;
+        {
+            let mut state = self.state.lock().unwrap();
+            let bound = match &*state {
+                State::Init { local } => *local,
+                State::Listening { .. } => return_errno!(EINVAL, "the socket is listening"),
+                State::Connected(connection) if connection.connecting => {
+                    return_errno!(EALREADY, "the connection is in progress")
+                }
+                State::Connected(_) => return_errno!(EISCONN, "the socket is connected"),
+            };
+
+            let mut stack_inner = stack.lock();
+            let port = match bound {
+                Some(local) => local.port,
+                None => stack_inner.alloc_port(0)?,
+            };
+            let local = IpEndpoint::new(IpAddress::Ipv4(stack.addr), port);
+            let handle = stack_inner.new_tcp_socket();
+            if stack_inner
+                .sockets
+                .get::<TcpSocket>(handle)
+                .connect(remote, local)
+                .is_err()
+            {
+                stack_inner.sockets.remove(handle);
+                if bound.is_none() {
+                    stack_inner.release_port(port);
+                }
+                return_errno!(EADDRNOTAVAIL, "failed to connect to the address");
+            }
+            // Send SYN right away
+            stack_inner.poll();
+            *state = State::Connected(Connection {
+                handle,
+                local_port: Some(port),
+                connecting: true,
+                error: None,
+                shut_rd: false,
+            });
+        }
+
+        if self.is_nonblocking() {
+            return_errno!(EINPROGRESS, "the connection is in progress");
+        }
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                let connection = connection_mut(&mut state)?;
+                let mut stack_inner = stack.lock();
+                connection.update(&stack_inner.sockets.get::<TcpSocket>(connection.handle));
+                if !connection.connecting {
+                    return match connection.error.take() {
+                        Some(errno) => Err(errno!(errno, "failed to connect")),
+                        None => Ok(()),
+                    };
+                }
+            }
+            stack.wait()?;
+        }
+    }
+
+    pub fn sendv(&self, bufs: &[&[u8]], flags: SendFlags) -> Result<usize> {
+        let ret = self.do_sendv(bufs, flags);
+        if flags.contains(SendFlags::MSG_NOSIGNAL) {
+            ret
+        } else {
+            send_sigpipe_on_epipe(ret)
+        }
+    }
+
+    fn do_sendv(&self, bufs: &[&[u8]], flags: SendFlags) -> Result<usize> {
+        let stack = stack()?;
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                let connection = connection_mut(&mut state)?;
+                let mut stack_inner = stack.lock();
+                let mut socket = stack_inner.sockets.get::<TcpSocket>(connection.handle);
+                connection.update(&socket);
+                if !connection.connecting {
+                    if !socket.may_send() {
+                        return_errno!(EPIPE, "the socket is shut down for writing");
+                    }
+                    if socket.can_send() {
+                        let mut bytes_sent = 0;
+                        for buf in bufs {
+                            let len = socket
+                                .send_slice(buf)
+                                .map_err(|_| errno!(EPIPE, "failed to send"))?;
+                            bytes_sent += len;
+                            if len < buf.len() {
+                                break;
+                            }
+                        }
+                        drop(socket);
+                        stack_inner.poll();
+                        return Ok(bytes_sent);
+                    }
+                }
+            }
+            if self.is_nonblocking() || flags.contains(SendFlags::MSG_DONTWAIT) {
+                return_errno!(EAGAIN, "the send buffer is full");
+            }
+            stack.wait()?;
+        }
+    }
+
+    pub fn recvv(&self, bufs: &mut [&mut [u8]], flags: RecvFlags) -> Result<usize> {
+        let stack = stack()?;
+        loop {
+            {
+                let mut state = self.state.lock().unwrap();
+                let connection = connection_mut(&mut state)?;
+                if connection.shut_rd {
+                    return Ok(0);
+                }
+                let mut stack_inner = stack.lock();
+                let mut socket = stack_inner.sockets.get::<TcpSocket>(connection.handle);
+                connection.update(&socket);
+                if !connection.connecting {
+                    if socket.can_recv() {
+                        let bytes_recvd = if flags.contains(RecvFlags::MSG_PEEK) {
+                            peek_into(&mut socket, bufs)?
+                        } else {
+                            recv_into(&mut socket, bufs)?
+                        };
+                        drop(socket);
+                        // Tell the peer that the window is open again
+                        stack_inner.poll();
+                        return Ok(bytes_recvd);
+                    }
+                    // Like Linux, EOF is returned even if the connection is reset
+                    if !socket.may_recv() {
+                        return Ok(0);
+                    }
+                }
+            }
+            if self.is_nonblocking() || flags.contains(RecvFlags::MSG_DONTWAIT) {
+                return_errno!(EAGAIN, "no data is available");
+            }
+            stack.wait()?;
+        }
+    }
+
+    pub fn shutdown(&self, how: c_int) -> Result<()> {
+        let (shut_rd, shut_wr) = match how {
+            libc::SHUT_RD => (true, false),
+            libc::SHUT_WR => (false, true),
+            libc::SHUT_RDWR => (true, true),
+            _ => return_errno!(EINVAL, "invalid argument of shutdown"),
+        };
+        let stack = stack()?;
+        let mut state = self.state.lock().unwrap();
+        let connection = connection_mut(&mut state)?;
+        if shut_rd {
+            connection.shut_rd = true;
+        }
+        if shut_wr {
+            let mut stack_inner = stack.lock();
+            stack_inner
+                .sockets
+                .get::<TcpSocket>(connection.handle)
+                .close();
+            // Send FIN right away
+            stack_inner.poll();
+        }
+        Ok(())
+    }
+
+    pub fn addr(&self) -> Result<libc::sockaddr_in> {
+        let stack = stack()?;
+        let state = self.state.lock().unwrap();
+        let local = match &*state {
+            State::Init { local } => {
+                local.unwrap_or_else(|| IpEndpoint::new(IpAddress::Unspecified, 0))
+            }
+            State::Listening { local, .. } => *local,
+            State::Connected(connection) => stack
+                .lock()
+                .sockets
+                .get::<TcpSocket>(connection.handle)
+                .local_endpoint(),
+        };
+        Ok(sockaddr_in_of(local))
+    }
+
+    pub fn peer_addr(&self) -> Result<libc::sockaddr_in> {
+        let stack = stack()?;
+        let mut state = self.state.lock().unwrap();
+        let connection = connection_mut(&mut state)?;
+        let mut stack_inner = stack.lock();
+        let socket = stack_inner.sockets.get::<TcpSocket>(connection.handle);
+        connection.update(&socket);
+        let remote = socket.remote_endpoint();
+        if connection.connecting || !remote.is_specified() {
+            return_errno!(ENOTCONN, "the socket is not connected");
+        }
+        Ok(sockaddr_in_of(remote))
+    }
+
+    /// The entry of the socket in /proc/net/tcp
+    pub fn proc_entry(&self) -> Result<TcpSocketEntry> {
+        let local = self.addr()?;
+        let remote = self.peer_addr().ok();
+        let stack = stack()?;
+        let state = self.state.lock().unwrap();
+        let (state, tx_queue, rx_queue) = match &*state {
+            State::Init { .. } => (TcpEntryState::Close, 0, 0),
+            State::Listening { .. } => (TcpEntryState::Listen, 0, 0),
+            State::Connected(connection) => {
+                let mut stack_inner = stack.lock();
+                let socket = stack_inner.sockets.get::<TcpSocket>(connection.handle);
+                (
+                    tcp_entry_state_of(socket.state()),
+                    socket.send_queue(),
+                    socket.recv_queue(),
+                )
+            }
+        };
+        Ok(TcpSocketEntry {
+            local,
+            remote,
+            state,
+            tx_queue,
+            rx_queue,
+        })
+    }
+
+    pub fn setsockopt(&self, level: c_int, optname: c_int, _optval: &[u8]) -> Result<()> {
+        // The common options, e.g., SO_REUSEADDR and TCP_NODELAY, are set by most
+        // servers, which make little difference to the stack
+        debug!(
+            "setsockopt for TCP socket of the stack is ignored: level: {}, optname: {}",
+            level, optname
+        );
+        Ok(())
+    }
+
+    pub fn getsockopt(&self, level: c_int, optname: c_int) -> Result<Vec<u8>> {
+        let val = match (level, optname) {
+            (libc::SOL_SOCKET, SO_TYPE) => libc::SOCK_STREAM,
+            (libc::SOL_SOCKET, SO_DOMAIN) => libc::AF_INET,
+            (libc::SOL_SOCKET, SO_PROTOCOL) => libc::IPPROTO_TCP,
+            (libc::SOL_SOCKET, libc::SO_ACCEPTCONN) => {
+                let state = self.state.lock().unwrap();
+                matches!(&*state, State::Listening { .. }) as c_int
+            }
+            (libc::SOL_SOCKET, libc::SO_ERROR) => self.take_error()?,
+            _ => return_errno!(ENOPROTOOPT, "unknown socket option for TCP socket"),
+        };
+        Ok(val.to_ne_bytes().to_vec())
+    }
+
+    /// Take the error of a non-blocking connect, if it has failed
+    fn take_error(&self) -> Result<c_int> {
+        let stack = stack()?;
+        let mut state = self.state.lock().unwrap();
+        if let State::Connected(connection) = &mut *state {
+            connection.update(&stack.lock().sockets.get::<TcpSocket>(connection.handle));
+            if let Some(errno) = connection.error.take() {
+                return Ok(errno as c_int);
+            }
+        }
+        Ok(0)
+    }
+
+    fn is_nonblocking(&self) -> bool {
+        self.nonblocking.load(Ordering::SeqCst)
+    }
+}
+
+impl File for TcpStackSocket {
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        self.recvv(&mut [buf], RecvFlags::empty())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.sendv(&[buf], SendFlags::empty())
+    }
+
+    fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
+        self.recvv(bufs, RecvFlags::empty())
+    }
+
+    fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
+        self.sendv(bufs, SendFlags::empty())
+    }
+
+    fn ioctl(&self, cmd: &mut IoctlCmd) -> Result<i32> {
+        let stack = stack()?;
+        let state = self.state.lock().unwrap();
+        let connection = match &*state {
+            State::Connected(connection) => Some(connection),
+            _ => None,
+        };
+        let mut stack_inner = stack.lock();
+        match cmd {
+            IoctlCmd::FIONREAD(arg) => {
+                let bytes_to_read = match connection {
+                    Some(connection) => stack_inner
+                        .sockets
+                        .get::<TcpSocket>(connection.handle)
+                        .recv_queue(),
+                    None => return_errno!(EINVAL, "the socket is not connected"),
+                };
+                **arg = bytes_to_read.min(std::i32::MAX as usize) as i32;
+            }
+            IoctlCmd::TIOCOUTQ(arg) => {
+                let bytes_unsent = match connection {
+                    Some(connection) => stack_inner
+                        .sockets
+                        .get::<TcpSocket>(connection.handle)
+                        .send_queue(),
+                    None => 0,
+                };
+                **arg = bytes_unsent.min(std::i32::MAX as usize) as i32;
+            }
+            _ => return_errno!(EINVAL, "unknown ioctl cmd for TCP socket"),
+        }
+        Ok(0)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDWR)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        if self.is_nonblocking() {
+            Ok(StatusFlags::O_NONBLOCK)
+        } else {
+            Ok(StatusFlags::empty())
+        }
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        let nonblocking = new_status_flags.contains(StatusFlags::O_NONBLOCK);
+        self.nonblocking.store(nonblocking, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn poll(&self) -> Result<PollEventFlags> {
+        let stack = stack()?;
+        let mut state = self.state.lock().unwrap();
+        let mut stack_inner = stack.lock();
+        let events = match &mut *state {
+            State::Init { .. } => PollEventFlags::POLLOUT | PollEventFlags::POLLHUP,
+            State::Listening { local } => {
+                if stack_inner.has_pending(local.port) {
+                    PollEventFlags::POLLIN | PollEventFlags::POLLRDNORM
+                } else {
+                    PollEventFlags::empty()
+                }
+            }
+            State::Connected(connection) => {
+                let socket = stack_inner.sockets.get::<TcpSocket>(connection.handle);
+                connection.update(&socket);
+                let mut events = PollEventFlags::empty();
+                if connection.connecting {
+                    // Not ready for anything yet
+                } else if connection.error.is_some() {
+                    events |= PollEventFlags::POLLERR | PollEventFlags::POLLHUP;
+                } else {
+                    if socket.can_recv() || !socket.may_recv() || connection.shut_rd {
+                        events |= PollEventFlags::POLLIN | PollEventFlags::POLLRDNORM;
+                    }
+                    if !socket.may_recv() {
+                        events |= PollEventFlags::POLLRDHUP;
+                    }
+                    // The socket that cannot send is writable, where send fails with EPIPE
+                    if socket.can_send() || !socket.may_send() {
+                        events |= PollEventFlags::POLLOUT | PollEventFlags::POLLWRNORM;
+                    }
+                    if !socket.may_send() && !socket.may_recv() {
+                        events |= PollEventFlags::POLLHUP;
+                    }
+                }
+                events
+            }
+        };
+        Ok(events)
+    }
+
+    // The sockets are polled with the tap device, so there is nothing to queue
+    fn enqueue_event(&self, _: IoEvent) -> Result<()> {
+        Ok(())
+    }
+
+    fn dequeue_event(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Drop for TcpStackSocket {
+    fn drop(&mut self) {
+        let stack = match STACK.as_ref() {
+            Some(stack) => stack,
+            None => return,
+        };
+        let state = self.state.lock().unwrap();
+        let mut stack_inner = stack.lock();
+        match &*state {
+            State::Init { local } => {
+                if let Some(local) = local {
+                    stack_inner.release_port(local.port);
+                }
+            }
+            State::Listening { local } => {
+                // Like Linux, the connections that are not accepted are reset
+                stack_inner.unlisten(local.port);
+                stack_inner.release_port(local.port);
+            }
+            State::Connected(connection) => {
+                // The data left is still sent, and the connection is closed gracefully
+                stack_inner
+                    .sockets
+                    .get::<TcpSocket>(connection.handle)
+                    .close();
+                stack_inner
+                    .closing
+                    .push((connection.handle, connection.local_port));
+            }
+        }
+        stack_inner.poll();
+    }
+}
+
+impl Socket for TcpStackSocket {
+    fn bind(&self, addr: &[u8]) -> Result<()> {
+        TcpStackSocket::bind(self, addr)
+    }
+
+    fn listen(&self, backlog: c_int) -> Result<()> {
+        TcpStackSocket::listen(self, backlog)
+    }
+
+    fn shutdown(&self, how: c_int) -> Result<()> {
+        TcpStackSocket::shutdown(self, how)
+    }
+
+    fn addr(&self) -> Result<Vec<u8>> {
+        Ok(sockaddr_bytes_of(&TcpStackSocket::addr(self)?))
+    }
+
+    fn peer_addr(&self) -> Result<Vec<u8>> {
+        Ok(sockaddr_bytes_of(&TcpStackSocket::peer_addr(self)?))
+    }
+
+    fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        TcpStackSocket::setsockopt(self, level, optname, optval)
+    }
+
+    fn getsockopt(&self, level: c_int, optname: c_int, _max_len: usize) -> Result<Vec<u8>> {
+        TcpStackSocket::getsockopt(self, level, optname)
+    }
+}
+
+pub trait AsTcpStackSocket {
+    fn as_tcp_stack_socket(&self) -> Result<&TcpStackSocket>;
+}
+
+impl AsTcpStackSocket for FileRef {
+    fn as_tcp_stack_socket(&self) -> Result<&TcpStackSocket> {
+        self.as_any()
+            .downcast_ref::<TcpStackSocket>()
+            .ok_or_else(|| errno!(EBADF, "not a TCP socket of the stack"))
+    }
+}
+
+fn connection_mut(state: &mut State) -> Result<&mut Connection> {
+    match state {
+        State::Connected(connection) => Ok(connection),
+        _ => return_errno!(ENOTCONN, "the socket is not connected"),
+    }
+}
+
+fn tcp_entry_state_of(state: TcpState) -> TcpEntryState {
+    match state {
+        TcpState::Closed => TcpEntryState::Close,
+        TcpState::Listen => TcpEntryState::Listen,
+        TcpState::SynSent => TcpEntryState::SynSent,
+        TcpState::SynReceived => TcpEntryState::SynRecv,
+        TcpState::Established => TcpEntryState::Established,
+        TcpState::FinWait1 => TcpEntryState::FinWait1,
+        TcpState::FinWait2 => TcpEntryState::FinWait2,
+        TcpState::CloseWait => TcpEntryState::CloseWait,
+        TcpState::Closing => TcpEntryState::Closing,
+        TcpState::LastAck => TcpEntryState::LastAck,
+        TcpState::TimeWait => TcpEntryState::TimeWait,
+    }
+}
+
+fn recv_into(socket: &mut TcpSocket, bufs: &mut [&mut [u8]]) -> Result<usize> {
+    let mut bytes_recvd = 0;
+    for buf in bufs.iter_mut() {
+        let len = socket
+            .recv_slice(buf)
+            .map_err(|_| errno!(ENOTCONN, "failed to receive"))?;
+        bytes_recvd += len;
+        if len < buf.len() {
+            break;
+        }
+    }
+    Ok(bytes_recvd)
+}
+
+fn peek_into(socket: &mut TcpSocket, bufs: &mut [&mut [u8]]) -> Result<usize> {
+    let total_len = bufs.iter().map(|buf| buf.len()).sum();
+    let mut data = vec![0u8; total_len];
+    let len = socket
+        .peek_slice(&mut data)
+        .map_err(|_| errno!(ENOTCONN, "failed to receive"))?;
+    let mut data = &data[..len];
+    for buf in bufs.iter_mut() {
+        let copy_len = min(buf.len(), data.len());
+        buf[..copy_len].copy_from_slice(&data[..copy_len]);
+        data = &data[copy_len..];
+    }
+    Ok(len)
+}
+
+fn parse_sockaddr_in(addr_buf: &[u8]) -> Result<IpEndpoint> {
+    if addr_buf.len() < size_of::<libc::sockaddr_in>() {
+        return_errno!(EINVAL, "the address is too short for sockaddr_in");
+    }
+    let addr = unsafe { std::ptr::read_unaligned(addr_buf.as_ptr() as *const libc::sockaddr_in) };
+    if addr.sin_family != libc::AF_INET as libc::sa_family_t {
+        return_errno!(EAFNOSUPPORT, "not an IPv4 address");
+    }
+    // Both the address and the port are in network byte order
+    let ip = Ipv4Address::from_bytes(&addr.sin_addr.s_addr.to_ne_bytes());
+    let ip = if ip.is_unspecified() {
+        IpAddress::Unspecified
+    } else {
+        IpAddress::Ipv4(ip)
+    };
+    Ok(IpEndpoint::new(ip, u16::from_be(addr.sin_port)))
+}
+
+fn sockaddr_in_of(endpoint: IpEndpoint) -> libc::sockaddr_in {
+    let ip = match endpoint.addr {
+        IpAddress::Ipv4(ip) => ip,
+        _ => Ipv4Address::UNSPECIFIED,
+    };
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = endpoint.port.to_be();
+    addr.sin_addr.s_addr = u32::from_ne_bytes(ip.0);
+    addr
+}
@@ -1,11 +1,26 @@
 use super::*;
 
+use super::addr_families::check_socket_family;
+use super::cmsg::{
+    parse_control_msgs, scm_rights_capacity, write_scm_credentials, write_scm_rights,
+    write_scm_timestamp,
+};
+use super::dbus_bridge;
+use super::host_dns;
 use super::io_multiplexing::{AsEpollFile, EpollCtlCmd, EpollEventFlags, EpollFile, FdSetExt};
-use fs::{CreationFlags, File, FileDesc, FileRef};
+use super::socket_file::SO_OCCLUM_BUFFERING;
+use super::socket_stats::{occlum_sock_stats, SOL_OCCLUM, SO_OCCLUM_STATS};
+use super::stack;
+use super::uevent_socket::NETLINK_KOBJECT_UEVENT;
+use config::ConfigFirewallDirection;
+use fs::{CreationFlags, File, FileDesc, FileRef, StatusFlags};
 use misc::resource_t;
 use process::Process;
+use signal::{has_deliverable_signals, replace_sig_mask, restore_sig_mask, sigset_t, SigSet};
+use std::borrow::Cow;
 use std::convert::TryFrom;
-use time::timeval_t;
+use std::time::Duration;
+use time::{timespec_t, timeval_t};
 use util::mem_util::from_user;
 
 pub fn do_socket(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<isize> {
@@ -14,18 +29,30 @@ pub fn do_socket(domain: c_int, socket_type: c_int, protocol: c_int) -> Result<i
         domain, socket_type, protocol
     );
 
+    check_socket_family(domain)?;
     let file_ref: Arc<Box<dyn File>> = match domain {
         libc::AF_LOCAL => {
             let unix_socket = UnixSocketFile::new(socket_type, protocol)?;
             Arc::new(Box::new(unix_socket))
         }
+        libc::AF_NETLINK if protocol == NETLINK_KOBJECT_UEVENT => {
+            let uevent_socket = UeventSocket::new(socket_type)?;
+            Arc::new(Box::new(uevent_socket))
+        }
+        libc::AF_INET if stack::is_enabled() && TcpStackSocket::is_tcp(socket_type, protocol) => {
+            let tcp_socket = TcpStackSocket::new(socket_type, protocol)?;
+            Arc::new(Box::new(tcp_socket))
+        }
         _ => {
             let socket = SocketFile::new(domain, socket_type, protocol)?;
             Arc::new(Box::new(socket))
         }
     };
 
-    let fd = current!().add_file(file_ref, false);
+    // SOCK_NONBLOCK is handled by the socket, while SOCK_CLOEXEC is kept by the
+    // file table
+    let close_on_spawn = socket_type & libc::SOCK_CLOEXEC != 0;
+    let fd = current!().add_file(file_ref, close_on_spawn);
     Ok(fd as isize)
 }
 
@@ -51,16 +78,46 @@ pub fn do_connect(
         if need_check {
             from_user::check_ptr(addr as *const libc::sockaddr_in)?;
         }
-        let ret = try_libc!(libc::ocall::connect(socket.fd(), addr, addr_len));
+        // The address is copied into the enclave before it is checked, and the
+        // copy is given to the host
+        let addr_buf = if need_check {
+            let addr_buf =
+                unsafe { std::slice::from_raw_parts(addr as *const u8, addr_len as usize) };
+            Some(addr_buf.to_vec())
+        } else {
+            None
+        };
+        if let Some(addr_buf) = &addr_buf {
+            socket.check_user_addr(addr_buf, ConfigFirewallDirection::Outbound)?;
+        }
+        let addr_buf = addr_buf.as_deref();
+        let addr = addr_buf.map_or(addr, |addr_buf| addr_buf.as_ptr() as *const libc::sockaddr);
+        // The peer is recorded even if a non-blocking connect is still in progress
+        socket.set_peer_addr(addr_buf);
+        socket.init_tls_client(addr_buf)?;
+        let ret = try_libc!(crate::sched::account_wait(|| unsafe {
+            libc::ocall::connect(socket.fd(), addr, addr_len)
+        }));
         Ok(ret as isize)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
         let addr = addr as *const libc::sockaddr_un;
         from_user::check_ptr(addr)?;
-        let path = from_user::clone_cstring_safely(unsafe { (&*addr).sun_path.as_ptr() })?
-            .to_string_lossy()
-            .into_owned();
+        if let Some(name) = abstract_name_of(unsafe { &*addr }, addr_len) {
+            if let Some(bridge) = dbus_bridge::find_bridge(&name) {
+                unix_socket.connect_bridge(&name, bridge)?;
+                return Ok(0);
+            }
+        }
+        let path = sun_path_of(unsafe { &*addr });
         unix_socket.connect(path)?;
         Ok(0)
+    } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        if !need_check {
+            return_errno!(EINVAL, "the address is null");
+        }
+        let addr_buf = unsafe { std::slice::from_raw_parts(addr as *const u8, addr_len as usize) };
+        tcp_socket.connect(addr_buf)?;
+        Ok(0)
     } else {
         return_errno!(EBADF, "not a socket")
     }
@@ -91,18 +148,21 @@ pub fn do_accept4(
         return_errno!(EINVAL, "addr and ddr_len should be both null");
     }
     if need_check {
-        from_user::check_mut_array(addr as *mut u8, unsafe { *addr_len } as usize)?;
+        check_sockaddr_buf(addr, addr_len)?;
+    }
+    if flags & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC) != 0 {
+        return_errno!(EINVAL, "invalid flags of accept4");
     }
+    let close_on_spawn = flags & libc::SOCK_CLOEXEC != 0;
 
     let file_ref = current!().file(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
+        let (new_socket, peer_addr) = socket.accept(flags)?;
         if need_check {
-            from_user::check_mut_ptr(addr as *mut libc::sockaddr_in)?;
+            copy_sockaddr_bytes_to_user(peer_addr.as_bytes(), addr, addr_len)?;
         }
-
-        let new_socket = socket.accept(addr, addr_len, flags)?;
         let new_file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(new_socket));
-        let new_fd = current!().add_file(new_file_ref, false);
+        let new_fd = current!().add_file(new_file_ref, close_on_spawn);
 
         Ok(new_fd as isize)
     } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
@@ -112,8 +172,22 @@ pub fn do_accept4(
         }
         // TODO: handle addr
         let new_socket = unix_socket.accept()?;
+        // Like Linux, the accepted socket does not inherit O_NONBLOCK of the listener
+        if flags & libc::SOCK_NONBLOCK != 0 {
+            new_socket.set_status_flags(StatusFlags::O_NONBLOCK)?;
+        }
+        let new_file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(new_socket));
+        let new_fd = current!().add_file(new_file_ref, close_on_spawn);
+
+        Ok(new_fd as isize)
+    } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        let nonblocking = flags & libc::SOCK_NONBLOCK != 0;
+        let (new_socket, peer_addr) = tcp_socket.accept(nonblocking)?;
+        if need_check {
+            copy_sockaddr_to_user(&peer_addr, addr, addr_len)?;
+        }
         let new_file_ref: Arc<Box<dyn File>> = Arc::new(Box::new(new_socket));
-        let new_fd = current!().add_file(new_file_ref, false);
+        let new_fd = current!().add_file(new_file_ref, close_on_spawn);
 
         Ok(new_fd as isize)
     } else {
@@ -124,12 +198,8 @@ pub fn do_accept4(
 pub fn do_shutdown(fd: c_int, how: c_int) -> Result<isize> {
     debug!("shutdown: fd: {}, how: {}", fd, how);
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::shutdown(socket.fd(), how));
-        Ok(ret as isize)
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    file_ref.as_dyn_socket()?.shutdown(how)?;
+    Ok(0)
 }
 
 pub fn do_bind(fd: c_int, addr: *const libc::sockaddr, addr_len: libc::socklen_t) -> Result<isize> {
@@ -138,37 +208,18 @@ pub fn do_bind(fd: c_int, addr: *const libc::sockaddr, addr_len: libc::socklen_t
         return_errno!(EINVAL, "no address is specified");
     }
     from_user::check_array(addr as *const u8, addr_len as usize)?;
+    let addr_buf = unsafe { std::slice::from_raw_parts(addr as *const u8, addr_len as usize) };
 
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        from_user::check_ptr(addr as *const libc::sockaddr_in)?;
-        let ret = try_libc!(libc::ocall::bind(socket.fd(), addr, addr_len));
-        Ok(ret as isize)
-    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        let addr = addr as *const libc::sockaddr_un;
-        from_user::check_ptr(addr)?;
-        let path = from_user::clone_cstring_safely(unsafe { (&*addr).sun_path.as_ptr() })?
-            .to_string_lossy()
-            .into_owned();
-        unix_socket.bind(path)?;
-        Ok(0)
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    file_ref.as_dyn_socket()?.bind(addr_buf)?;
+    Ok(0)
 }
 
 pub fn do_listen(fd: c_int, backlog: c_int) -> Result<isize> {
     debug!("listen: fd: {}, backlog: {}", fd, backlog);
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::listen(socket.fd(), backlog));
-        Ok(ret as isize)
-    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        unix_socket.listen()?;
-        Ok(0)
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    file_ref.as_dyn_socket()?.listen(backlog)?;
+    Ok(0)
 }
 
 pub fn do_setsockopt(
@@ -182,22 +233,13 @@ pub fn do_setsockopt(
         "setsockopt: fd: {}, level: {}, optname: {}, optval: {:?}, optlen: {:?}",
         fd, level, optname, optval, optlen
     );
+    let optval = from_user::UserPtr::new_nullable(optval as *const u8, optlen as usize)?;
+    let optval_buf = optval.as_ref().map_or(&[][..], |optval| optval.as_slice());
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::setsockopt(
-            socket.fd(),
-            level,
-            optname,
-            optval,
-            optlen
-        ));
-        Ok(ret as isize)
-    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        warn!("setsockopt for unix socket is unimplemented");
-        Ok(0)
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    file_ref
+        .as_dyn_socket()?
+        .setsockopt(level, optname, optval_buf)?;
+    Ok(0)
 }
 
 pub fn do_getsockopt(
@@ -211,17 +253,77 @@ pub fn do_getsockopt(
         "getsockopt: fd: {}, level: {}, optname: {}, optval: {:?}, optlen: {:?}",
         fd, level, optname, optval, optlen
     );
+    let optlen_ptr = from_user::UserMutPtr::new(optlen, 1)?;
+    let optlen_val = optlen_ptr.read()?;
+    let optval = from_user::UserMutPtr::new_nullable(optval as *mut u8, optlen_val as usize)?;
     let file_ref = current!().file(fd as FileDesc)?;
-    let socket = file_ref.as_socket()?;
+    if level == SOL_OCCLUM {
+        return do_getsockopt_occlum(&file_ref, optname, optval, optlen_ptr);
+    }
+    // The value is got with no buffer if the user gives none
+    let max_len = optval.as_ref().map_or(0, |optval| optval.len());
+    let val = file_ref
+        .as_dyn_socket()?
+        .getsockopt(level, optname, max_len)?;
+    copy_optval_to_user(&val, optval, optlen_val, optlen_ptr)
+}
+
+/// Copy the value of a socket option given by the LibOS to the user. Like Linux,
+/// the value is truncated to the length of the buffer.
+fn copy_optval_to_user(
+    val: &[u8],
+    optval: Option<from_user::UserMutPtr<u8>>,
+    optlen_val: libc::socklen_t,
+    optlen_ptr: from_user::UserMutPtr<libc::socklen_t>,
+) -> Result<isize> {
+    let len = min(optlen_val as usize, val.len());
+    if let Some(mut optval) = optval {
+        optval.as_mut_slice()[..len].copy_from_slice(&val[..len]);
+    }
+    optlen_ptr.write(len as libc::socklen_t)?;
+    Ok(0)
+}
 
-    let ret = try_libc!(libc::ocall::getsockopt(
-        socket.fd(),
-        level,
-        optname,
-        optval,
-        optlen
-    ));
-    Ok(ret as isize)
+/// Get the Occlum-specific socket options, which are supported by both host
+/// sockets and unix sockets
+fn do_getsockopt_occlum(
+    file_ref: &FileRef,
+    optname: c_int,
+    optval: Option<from_user::UserMutPtr<u8>>,
+    optlen_ptr: from_user::UserMutPtr<libc::socklen_t>,
+) -> Result<isize> {
+    if optname == SO_OCCLUM_BUFFERING {
+        let socket = file_ref
+            .as_socket()
+            .map_err(|_| errno!(ENOPROTOOPT, "only host sockets are buffered"))?;
+        let val = socket.buffering().is_enabled() as c_int;
+        let optval_len = optval.as_ref().map_or(0, |optval| optval.len());
+        return copy_optval_to_user(
+            &val.to_ne_bytes(),
+            optval,
+            optval_len as libc::socklen_t,
+            optlen_ptr,
+        );
+    }
+    if optname != SO_OCCLUM_STATS {
+        return_errno!(ENOPROTOOPT, "unknown socket option of level SOL_OCCLUM");
+    }
+    let stats = if let Ok(socket) = file_ref.as_socket() {
+        socket.stats().snapshot()
+    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
+        unix_socket.stats().snapshot()
+    } else {
+        return_errno!(ENOTSOCK, "not a socket");
+    };
+    let optval = optval.ok_or_else(|| errno!(EFAULT, "optval is null"))?;
+    if optval.len() < std::mem::size_of::<occlum_sock_stats>() {
+        return_errno!(EINVAL, "optlen is too small for occlum_sock_stats");
+    }
+    unsafe {
+        std::ptr::write_unaligned(optval.as_mut_ptr() as *mut occlum_sock_stats, stats);
+    }
+    optlen_ptr.write(std::mem::size_of::<occlum_sock_stats>() as libc::socklen_t)?;
+    Ok(0)
 }
 
 pub fn do_getpeername(
@@ -233,19 +335,11 @@ pub fn do_getpeername(
         "getpeername: fd: {}, addr: {:?}, addr_len: {:?}",
         fd, addr, addr_len
     );
+    check_sockaddr_buf(addr, addr_len)?;
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::getpeername(socket.fd(), addr, addr_len));
-        Ok(ret as isize)
-    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        warn!("getpeername for unix socket is unimplemented");
-        return_errno!(
-            ENOTCONN,
-            "hack for php: Transport endpoint is not connected"
-        )
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    let peer_addr = file_ref.as_dyn_socket()?.peer_addr()?;
+    copy_sockaddr_bytes_to_user(&peer_addr, addr, addr_len)?;
+    Ok(0)
 }
 
 pub fn do_getsockname(
@@ -257,16 +351,11 @@ pub fn do_getsockname(
         "getsockname: fd: {}, addr: {:?}, addr_len: {:?}",
         fd, addr, addr_len
     );
+    check_sockaddr_buf(addr, addr_len)?;
     let file_ref = current!().file(fd as FileDesc)?;
-    if let Ok(socket) = file_ref.as_socket() {
-        let ret = try_libc!(libc::ocall::getsockname(socket.fd(), addr, addr_len));
-        Ok(ret as isize)
-    } else if let Ok(unix_socket) = file_ref.as_unix_socket() {
-        warn!("getsockname for unix socket is unimplemented");
-        Ok(0)
-    } else {
-        return_errno!(EBADF, "not a socket")
-    }
+    let local_addr = file_ref.as_dyn_socket()?.addr()?;
+    copy_sockaddr_bytes_to_user(&local_addr, addr, addr_len)?;
+    Ok(0)
 }
 
 pub fn do_sendto(
@@ -281,31 +370,45 @@ pub fn do_sendto(
         "sendto: fd: {}, base: {:?}, len: {}, flags: {} addr: {:?}, addr_len: {}",
         fd, base, len, flags, addr, addr_len
     );
-    from_user::check_array(base as *const u8, len)?;
+    let user_data = from_user::UserPtr::new(base as *const u8, len)?;
+    let data = user_data.as_slice();
+    // The address is copied into the enclave before it is checked, and the copy
+    // is given to the host
+    let addr_buf = from_user::UserPtr::new_nullable(addr as *const u8, addr_len as usize)?
+        .map(|addr| addr.as_slice().to_vec());
+    let addr_buf = addr_buf.as_deref();
+    let addr = addr_buf.map_or(addr, |addr_buf| addr_buf.as_ptr() as *const libc::sockaddr);
+
+    let send_flags = SendFlags::from_bits_truncate(flags);
 
     let file_ref = current!().file(fd as FileDesc)?;
     if let Ok(socket) = file_ref.as_socket() {
-        // TODO: check addr and addr_len according to connection mode
-        let ret = try_libc_may_epipe!(libc::ocall::sendto(
-            socket.fd(),
-            base,
-            len,
-            flags,
-            addr,
-            addr_len
-        ));
-        Ok(ret as isize)
-    } else if let Ok(unix) = file_ref.as_unix_socket() {
-        if !addr.is_null() || addr_len != 0 {
-            return_errno!(EISCONN, "Only connection-mode socket is supported");
+        if socket.is_tls() {
+            // Like Linux, the address is ignored for a connection-mode socket
+            return socket.tls_sendv(&[data], send_flags).map(|u| u as isize);
         }
-
-        if !unix.is_connected() {
-            return_errno!(ENOTCONN, "the socket has not been connected yet");
+        if addr_buf.is_none() {
+            if let Some(ret) = socket.stage_sends(&[data], send_flags) {
+                return ret.map(|u| u as isize);
+            }
+        } else {
+            socket.flush_sends()?;
         }
-
-        let data = unsafe { std::slice::from_raw_parts(base as *const u8, len) };
-        unix.write(data).map(|u| u as isize)
+        if let Some(addr_buf) = addr_buf {
+            socket.check_user_addr(addr_buf, ConfigFirewallDirection::Outbound)?;
+        }
+        // TODO: check addr and addr_len according to connection mode
+        socket
+            .sendto(data, send_flags, addr_buf)
+            .map(|u| u as isize)
+    } else if let Ok(unix) = file_ref.as_unix_socket() {
+        check_unix_dest_addr(unix, !addr.is_null() || addr_len != 0)?;
+        unix.send(data, send_flags).map(|u| u as isize)
+    } else if let Ok(uevent_socket) = file_ref.as_uevent_socket() {
+        uevent_socket.sendv(&[data]).map(|u| u as isize)
+    } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        // Like Linux, the address is ignored for a connection-mode socket
+        tcp_socket.sendv(&[data], send_flags).map(|u| u as isize)
     } else {
         return_errno!(EBADF, "unsupported file type");
     }
@@ -323,18 +426,79 @@ pub fn do_recvfrom(
         "recvfrom: fd: {}, base: {:?}, len: {}, flags: {}, addr: {:?}, addr_len: {:?}",
         fd, base, len, flags, addr, addr_len
     );
+    let mut user_buf = from_user::UserMutPtr::new(base as *mut u8, len)?;
+    if !addr.is_null() {
+        check_sockaddr_buf(addr, addr_len)?;
+    }
     let file_ref = current!().file(fd as FileDesc)?;
+    if let Ok(uevent_socket) = file_ref.as_uevent_socket() {
+        let recv_flags = RecvFlags::from_bits_truncate(flags);
+        return uevent_socket.recv(recv_flags).map(|u| u as isize);
+    }
+    if let Ok(unix) = file_ref.as_unix_socket() {
+        let recv_flags = RecvFlags::from_bits_truncate(flags);
+        let buf = user_buf.as_mut_slice();
+        // Like Linux, the files sent along with the data are discarded
+        let (bytes_recvd, _files, _cred, _timestamp, _msg_flags) =
+            unix.recvv_with_files(&mut [buf], recv_flags)?;
+        // Like Linux, the address of the sender is returned if it is named
+        if !addr.is_null() {
+            match unix.sender_addr() {
+                Some(sender_addr) => copy_sockaddr_bytes_to_user(&sender_addr, addr, addr_len)?,
+                None => from_user::UserMutPtr::new(addr_len, 1)?.write(0)?,
+            }
+        }
+        return Ok(bytes_recvd as isize);
+    }
+    if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        let recv_flags = RecvFlags::from_bits_truncate(flags);
+        let buf = user_buf.as_mut_slice();
+        let bytes_recvd = tcp_socket.recvv(&mut [buf], recv_flags)?;
+        if !addr.is_null() {
+            copy_sockaddr_to_user(&tcp_socket.peer_addr()?, addr, addr_len)?;
+        }
+        return Ok(bytes_recvd as isize);
+    }
     let socket = file_ref.as_socket()?;
+    if socket.is_tls() {
+        let recv_flags = RecvFlags::from_bits_truncate(flags);
+        let buf = user_buf.as_mut_slice();
+        let bytes_recvd = socket.tls_recvv(&mut [buf], recv_flags)?;
+        // Like Linux, no address is returned for a connection-mode socket
+        if !addr.is_null() {
+            from_user::UserMutPtr::new(addr_len, 1)?.write(0)?;
+        }
+        return Ok(bytes_recvd as isize);
+    }
+    {
+        let recv_flags = RecvFlags::from_bits_truncate(flags);
+        let buf = user_buf.as_mut_slice();
+        if let Some(ret) = socket.recv_buffered(&mut [buf], recv_flags) {
+            let bytes_recvd = ret?;
+            // Like Linux, no address is returned for a connection-mode socket
+            if !addr.is_null() {
+                from_user::UserMutPtr::new(addr_len, 1)?.write(0)?;
+            }
+            return Ok(bytes_recvd as isize);
+        }
+    }
 
-    let ret = try_libc!(libc::ocall::recvfrom(
-        socket.fd(),
-        base,
-        len,
-        flags,
-        addr,
-        addr_len
-    ));
-    Ok(ret as isize)
+    let recv_flags = RecvFlags::from_bits_truncate(flags);
+    let buf = user_buf.as_mut_slice();
+    let mut user_addr_buf = if addr.is_null() {
+        None
+    } else {
+        let addr_buf_len = from_user::UserMutPtr::new(addr_len, 1)?.read()? as usize;
+        Some(from_user::UserMutPtr::new(addr as *mut u8, addr_buf_len)?)
+    };
+    let addr_buf = user_addr_buf
+        .as_mut()
+        .map(|addr_buf| addr_buf.as_mut_slice());
+    let (bytes_recvd, src_addr_len) = socket.recvfrom(buf, recv_flags, addr_buf)?;
+    if !addr.is_null() {
+        from_user::UserMutPtr::new(addr_len, 1)?.write(src_addr_len as libc::socklen_t)?;
+    }
+    Ok(bytes_recvd as isize)
 }
 
 pub fn do_socketpair(
@@ -347,18 +511,18 @@ pub fn do_socketpair(
         "socketpair: domain: {}, type:0x{:x}, protocol: {}",
         domain, socket_type, protocol
     );
-    let mut sock_pair = unsafe {
-        from_user::check_mut_array(sv, 2)?;
-        std::slice::from_raw_parts_mut(sv as *mut u32, 2)
-    };
+    let mut user_sock_pair = from_user::UserMutPtr::new(sv as *mut u32, 2)?;
+    let sock_pair = user_sock_pair.as_mut_slice();
+    check_socket_family(domain)?;
 
     if (domain == libc::AF_UNIX) {
         let (client_socket, server_socket) =
             UnixSocketFile::socketpair(socket_type as i32, protocol as i32)?;
+        let close_on_spawn = socket_type & libc::SOCK_CLOEXEC != 0;
         let current = current!();
         let mut files = current.files().lock().unwrap();
-        sock_pair[0] = files.put(Arc::new(Box::new(client_socket)), false);
-        sock_pair[1] = files.put(Arc::new(Box::new(server_socket)), false);
+        sock_pair[0] = files.put(Arc::new(Box::new(client_socket)), close_on_spawn);
+        sock_pair[1] = files.put(Arc::new(Box::new(server_socket)), close_on_spawn);
 
         debug!("socketpair: ({}, {})", sock_pair[0], sock_pair[1]);
         Ok(0)
@@ -390,8 +554,60 @@ pub fn do_sendmsg(fd: c_int, msg_ptr: *const msghdr, flags_c: c_int) -> Result<i
         socket
             .sendmsg(&msg, flags)
             .map(|bytes_sent| bytes_sent as isize)
-    } else if let Ok(socket) = file_ref.as_unix_socket() {
-        return_errno!(EBADF, "does not support unix socket")
+    } else if let Ok(unix) = file_ref.as_unix_socket() {
+        let msg_c = {
+            from_user::check_ptr(msg_ptr)?;
+            let msg_c = unsafe { &*msg_ptr };
+            msg_c.check_member_ptrs()?;
+            msg_c
+        };
+        let msg = unsafe { MsgHdr::from_c(&msg_c)? };
+        check_unix_dest_addr(unix, msg.get_name().is_some())?;
+        let files = match msg.get_control() {
+            Some(control) => {
+                let current = current!();
+                let (fds, cred) = parse_control_msgs(control)?;
+                // The credentials of the sender are attached anyway. Unlike Linux, the
+                // ones given must be the real ones, even if sent by root.
+                if let Some(cred) = cred {
+                    if cred.pid != current.process().pid() || cred.uid != 0 || cred.gid != 0 {
+                        return_errno!(EPERM, "the credentials are not of the sender");
+                    }
+                }
+                fds.into_iter()
+                    .map(|fd| current.file(fd))
+                    .collect::<Result<Vec<FileRef>>>()?
+            }
+            None => Vec::new(),
+        };
+
+        let flags = SendFlags::from_bits_truncate(flags_c);
+
+        unix.sendv_with_files(msg.get_iovs().as_slices(), files, flags)
+            .map(|bytes_sent| bytes_sent as isize)
+    } else if let Ok(uevent_socket) = file_ref.as_uevent_socket() {
+        let msg_c = {
+            from_user::check_ptr(msg_ptr)?;
+            let msg_c = unsafe { &*msg_ptr };
+            msg_c.check_member_ptrs()?;
+            msg_c
+        };
+        let msg = unsafe { MsgHdr::from_c(&msg_c)? };
+        uevent_socket
+            .sendv(msg.get_iovs().as_slices())
+            .map(|bytes_sent| bytes_sent as isize)
+    } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        let msg_c = {
+            from_user::check_ptr(msg_ptr)?;
+            let msg_c = unsafe { &*msg_ptr };
+            msg_c.check_member_ptrs()?;
+            msg_c
+        };
+        let msg = unsafe { MsgHdr::from_c(&msg_c)? };
+        let flags = SendFlags::from_bits_truncate(flags_c);
+        tcp_socket
+            .sendv(msg.get_iovs().as_slices(), flags)
+            .map(|bytes_sent| bytes_sent as isize)
     } else {
         return_errno!(EBADF, "not a socket")
     }
@@ -418,8 +634,89 @@ pub fn do_recvmsg(fd: c_int, msg_mut_ptr: *mut msghdr_mut, flags_c: c_int) -> Re
         socket
             .recvmsg(&mut msg_mut, flags)
             .map(|bytes_recvd| bytes_recvd as isize)
-    } else if let Ok(socket) = file_ref.as_unix_socket() {
-        return_errno!(EBADF, "does not support unix socket")
+    } else if let Ok(unix) = file_ref.as_unix_socket() {
+        let msg_mut_c = {
+            from_user::check_mut_ptr(msg_mut_ptr)?;
+            let msg_mut_c = unsafe { &mut *msg_mut_ptr };
+            msg_mut_c.check_member_ptrs()?;
+            msg_mut_c
+        };
+        let mut msg_mut = unsafe { MsgHdrMut::from_c(msg_mut_c)? };
+
+        let flags = RecvFlags::from_bits_truncate(flags_c);
+
+        let (bytes_recvd, mut files, cred, timestamp, mut msg_flags) =
+            unix.recvv_with_files(msg_mut.get_iovs_mut().as_slices_mut(), flags)?;
+
+        // Like Linux, the timestamp comes first, then the credentials and the files,
+        // and the files that do not fit in the control buffer are closed
+        let timestamp_len = match (timestamp, msg_mut.get_control_mut()) {
+            (Some((format, time)), Some(control)) => write_scm_timestamp(control, format, time),
+            _ => 0,
+        };
+        if timestamp.is_some() && timestamp_len == 0 {
+            msg_flags |= MsgHdrFlags::MSG_CTRUNC;
+        }
+        let cred_len = match (cred, msg_mut.get_control_mut()) {
+            (Some(cred), Some(control)) => {
+                write_scm_credentials(&mut control[timestamp_len..], &cred)
+            }
+            _ => 0,
+        };
+        if cred.is_some() && cred_len == 0 {
+            msg_flags |= MsgHdrFlags::MSG_CTRUNC;
+        }
+        let cmsgs_len = timestamp_len + cred_len;
+        let max_files = scm_rights_capacity(msg_mut.get_control_max_len() - cmsgs_len);
+        if files.len() > max_files {
+            files.truncate(max_files);
+            msg_flags |= MsgHdrFlags::MSG_CTRUNC;
+        }
+        let close_on_spawn = flags.contains(RecvFlags::MSG_CMSG_CLOEXEC);
+        let current = current!();
+        let fds: Vec<FileDesc> = files
+            .into_iter()
+            .map(|file| current.add_file(file, close_on_spawn))
+            .collect();
+        let control_len = match msg_mut.get_control_mut() {
+            Some(control) => cmsgs_len + write_scm_rights(&mut control[cmsgs_len..], &fds),
+            None => 0,
+        };
+        msg_mut.set_control_len(control_len)?;
+        // Like Linux, the address of the sender is returned if it is named, which
+        // is truncated to the buffer
+        let name_len = match (unix.sender_addr(), msg_mut.get_name_mut()) {
+            (Some(sender_addr), Some(name)) => {
+                let name_len = min(sender_addr.len(), name.len());
+                name[..name_len].copy_from_slice(&sender_addr[..name_len]);
+                name_len
+            }
+            _ => 0,
+        };
+        msg_mut.set_name_len(name_len)?;
+        msg_mut.set_flags(msg_flags);
+        Ok(bytes_recvd as isize)
+    } else if let Ok(uevent_socket) = file_ref.as_uevent_socket() {
+        let flags = RecvFlags::from_bits_truncate(flags_c);
+        uevent_socket
+            .recv(flags)
+            .map(|bytes_recvd| bytes_recvd as isize)
+    } else if let Ok(tcp_socket) = file_ref.as_tcp_stack_socket() {
+        let msg_mut_c = {
+            from_user::check_mut_ptr(msg_mut_ptr)?;
+            let msg_mut_c = unsafe { &mut *msg_mut_ptr };
+            msg_mut_c.check_member_ptrs()?;
+            msg_mut_c
+        };
+        let mut msg_mut = unsafe { MsgHdrMut::from_c(msg_mut_c)? };
+
+        let flags = RecvFlags::from_bits_truncate(flags_c);
+
+        let bytes_recvd = tcp_socket.recvv(msg_mut.get_iovs_mut().as_slices_mut(), flags)?;
+        msg_mut.set_control_len(0)?;
+        msg_mut.set_name_len(0)?;
+        msg_mut.set_flags(MsgHdrFlags::empty());
+        Ok(bytes_recvd as isize)
     } else {
         return_errno!(EBADF, "not a socket")
     }
@@ -431,59 +728,171 @@ trait c_msghdr_ext {
 }
 
 impl c_msghdr_ext for msghdr {
-    // TODO: implement this!
     fn check_member_ptrs(&self) -> Result<()> {
+        from_user::UserPtr::new_nullable(self.msg_name as *const u8, self.msg_namelen as usize)?;
+        from_user::UserPtr::new_nullable(self.msg_control as *const u8, self.msg_controllen)?;
+        if self.msg_iovlen > IOV_MAX {
+            return_errno!(EMSGSIZE, "too many iovecs");
+        }
+        if let Some(iovs) = from_user::UserPtr::new_nullable(self.msg_iov, self.msg_iovlen)? {
+            check_iovs_total_len(iovs.as_slice().iter().map(|iov| iov.iov_len))?;
+            // The base of an empty buffer can be anything
+            for iov in iovs.as_slice().iter().filter(|iov| iov.iov_len > 0) {
+                from_user::UserPtr::new_nullable(iov.iov_base as *const u8, iov.iov_len)?;
+            }
+        }
         Ok(())
     }
-    /*
-            ///user space check
-            pub unsafe fn check_from_user(user_hdr: *const msghdr) -> Result<()> {
-                Self::check_pointer(user_hdr, from_user::check_ptr)
-            }
+}
 
-            ///Check msghdr ptr
-            pub unsafe fn check_pointer(
-                user_hdr: *const msghdr,
-                check_ptr: fn(*const u8) -> Result<()>,
-            ) -> Result<()> {
-                check_ptr(user_hdr as *const u8)?;
+impl c_msghdr_ext for msghdr_mut {
+    fn check_member_ptrs(&self) -> Result<()> {
+        from_user::UserMutPtr::new_nullable(self.msg_name as *mut u8, self.msg_namelen as usize)?;
+        from_user::UserMutPtr::new_nullable(self.msg_control as *mut u8, self.msg_controllen)?;
+        if self.msg_iovlen > IOV_MAX {
+            return_errno!(EMSGSIZE, "too many iovecs");
+        }
+        if let Some(iovs) = from_user::UserPtr::new_nullable(self.msg_iov, self.msg_iovlen)? {
+            check_iovs_total_len(iovs.as_slice().iter().map(|iov| iov.iov_len))?;
+            for iov in iovs.as_slice().iter().filter(|iov| iov.iov_len > 0) {
+                from_user::UserMutPtr::new_nullable(iov.iov_base as *mut u8, iov.iov_len)?;
+            }
+        }
+        Ok(())
+    }
+}
 
-                if (*user_hdr).msg_name.is_null() ^ ((*user_hdr).msg_namelen == 0) {
-                    return_errno!(EINVAL, "name length is invalid");
-                }
+/// Check the destination address given to send on a unix socket, which must be
+/// connected. Like Linux, the address is ignored by a SOCK_SEQPACKET socket, while
+/// it is rejected by a SOCK_STREAM one.
+fn check_unix_dest_addr(unix: &UnixSocketFile, has_addr: bool) -> Result<()> {
+    if has_addr && unix.socket_type() == libc::SOCK_STREAM {
+        if unix.is_connected() {
+            return_errno!(EISCONN, "the socket is already connected");
+        }
+        return_errno!(EOPNOTSUPP, "Only connection-mode socket is supported");
+    }
+    if !unix.is_connected() {
+        return_errno!(ENOTCONN, "the socket has not been connected yet");
+    }
+    Ok(())
+}
 
-                if (*user_hdr).msg_iov.is_null() ^ ((*user_hdr).msg_iovlen == 0) {
-                    return_errno!(EINVAL, "iov length is invalid");
-                }
+/// Get the path of a unix socket address, which is borrowed from the address
+/// rather than copied unless it is not valid UTF-8.
+///
+/// The path may not be null-terminated if it takes up the whole sun_path.
+pub(super) fn sun_path_of(addr: &libc::sockaddr_un) -> Cow<str> {
+    let sun_path = unsafe {
+        std::slice::from_raw_parts(addr.sun_path.as_ptr() as *const u8, addr.sun_path.len())
+    };
+    let len = sun_path
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(sun_path.len());
+    String::from_utf8_lossy(&sun_path[..len])
+}
 
-                if (*user_hdr).msg_control.is_null() ^ ((*user_hdr).msg_controllen == 0) {
-                    return_errno!(EINVAL, "control length is invalid");
-                }
+/// Get the abstract name in the address, which is given with a leading '@' like
+/// `ss`, or None if the address is a path.
+///
+/// The name is the bytes of sun_path after the leading NUL up to the length of
+/// the address, which may contain NULs.
+fn abstract_name_of(addr: &libc::sockaddr_un, addr_len: libc::socklen_t) -> Option<String> {
+    let path_offset = std::mem::size_of::<libc::sa_family_t>();
+    let sun_path = unsafe {
+        std::slice::from_raw_parts(addr.sun_path.as_ptr() as *const u8, addr.sun_path.len())
+    };
+    if sun_path[0] != 0 {
+        return None;
+    }
+    let len = min(
+        (addr_len as usize).saturating_sub(path_offset),
+        sun_path.len(),
+    );
+    if len <= 1 {
+        return None;
+    }
+    Some(format!("@{}", String::from_utf8_lossy(&sun_path[1..len])))
+}
 
-                if !(*user_hdr).msg_name.is_null() {
-                    check_ptr((*user_hdr).msg_name as *const u8)?;
-                }
+/// Check the buffer of a socket address and its length, which are given by the user
+/// as value-result arguments
+fn check_sockaddr_buf(addr: *mut libc::sockaddr, addr_len: *mut libc::socklen_t) -> Result<()> {
+    let addr_len = from_user::UserMutPtr::new(addr_len, 1)?.read()?;
+    from_user::UserMutPtr::new_nullable(addr as *mut u8, addr_len as usize)?;
+    Ok(())
+}
 
-                if !(*user_hdr).msg_iov.is_null() {
-                    check_ptr((*user_hdr).msg_iov as *const u8)?;
-                    let iov_slice = slice::from_raw_parts((*user_hdr).msg_iov, (*user_hdr).msg_iovlen);
-                    for iov in iov_slice {
-                        check_ptr(iov.iov_base as *const u8)?;
-                    }
-                }
+/// Copy a socket address given by the LibOS to the user. Like Linux, the address
+/// is truncated to the buffer, while the full length is returned.
+fn copy_sockaddr_to_user<T>(
+    sockaddr: &T,
+    addr: *mut libc::sockaddr,
+    addr_len: *mut libc::socklen_t,
+) -> Result<()> {
+    let addr_bytes = unsafe {
+        std::slice::from_raw_parts(sockaddr as *const T as *const u8, std::mem::size_of::<T>())
+    };
+    copy_sockaddr_bytes_to_user(addr_bytes, addr, addr_len)
+}
 
-                if !(*user_hdr).msg_control.is_null() {
-                    check_ptr((*user_hdr).msg_control as *const u8)?;
-                }
-                Ok(())
-            }
-    */
+/// Copy a socket address of variable length, e.g., sockaddr_un, to the user
+fn copy_sockaddr_bytes_to_user(
+    addr_bytes: &[u8],
+    addr: *mut libc::sockaddr,
+    addr_len: *mut libc::socklen_t,
+) -> Result<()> {
+    let addr_len_ptr = from_user::UserMutPtr::new(addr_len, 1)?;
+    let len = min(addr_len_ptr.read()? as usize, addr_bytes.len());
+    if let Some(mut addr) = from_user::UserMutPtr::new_nullable(addr as *mut u8, len)? {
+        addr.as_mut_slice().copy_from_slice(&addr_bytes[..len]);
+    }
+    addr_len_ptr.write(addr_bytes.len() as libc::socklen_t)?;
+    Ok(())
 }
 
-impl c_msghdr_ext for msghdr_mut {
-    fn check_member_ptrs(&self) -> Result<()> {
-        Ok(())
+/// Resolve the node and the service by the resolver of the host, which is an
+/// Occlum-specific syscall. Return the number of the addresses written to `res`.
+pub fn do_getaddrinfo(
+    node: *const i8,
+    service: *const i8,
+    hints: *const addrinfo_hints,
+    res: *mut occlum_addrinfo,
+    max_res: usize,
+) -> Result<isize> {
+    let node = if node.is_null() {
+        None
+    } else {
+        Some(from_user::clone_cstring_safely(node)?)
+    };
+    let service = if service.is_null() {
+        None
+    } else {
+        Some(from_user::clone_cstring_safely(service)?)
+    };
+    if node.is_none() && service.is_none() {
+        return_errno!(EINVAL, "either node or service is expected");
     }
+    let hints = if hints.is_null() {
+        addrinfo_hints::default()
+    } else {
+        from_user::check_ptr(hints)?;
+        unsafe { *hints }
+    };
+    if max_res == 0 {
+        return_errno!(EINVAL, "no room for the addresses");
+    }
+    from_user::check_mut_array(res, max_res)?;
+    debug!(
+        "getaddrinfo: node: {:?}, service: {:?}, hints: {:?}",
+        node, service, hints
+    );
+
+    let addrs = host_dns::getaddrinfo(node.as_deref(), service.as_deref(), &hints, max_res)?;
+    let res = unsafe { std::slice::from_raw_parts_mut(res, addrs.len()) };
+    res.copy_from_slice(&addrs);
+    Ok(addrs.len() as isize)
 }
 
 pub fn do_select(
@@ -544,10 +953,63 @@ pub fn do_select(
 }
 
 pub fn do_poll(fds: *mut PollEvent, nfds: libc::nfds_t, timeout: c_int) -> Result<isize> {
-    // It behaves like sleep when fds is null and nfds is zero.
-    if !fds.is_null() || nfds != 0 {
-        from_user::check_mut_array(fds, nfds as usize)?;
+    debug!("poll: timeout: {}", timeout);
+
+    let mut time_val = timeval_t::new(
+        ((timeout as u32) / 1000) as i64,
+        ((timeout as u32) % 1000 * 1000) as i64,
+    );
+    // Any negative timeout means an infinite timeout
+    let tmp_to = if timeout < 0 {
+        std::ptr::null_mut()
+    } else {
+        &mut time_val
+    };
+    poll(fds, nfds, tmp_to)
+}
+
+pub fn do_ppoll(
+    fds: *mut PollEvent,
+    nfds: libc::nfds_t,
+    timeout: *mut timespec_t,
+    sigmask: *const sigset_t,
+    sigsetsize: usize,
+) -> Result<isize> {
+    // A null timeout means an infinite timeout
+    let mut time_val = if timeout.is_null() {
+        None
+    } else {
+        from_user::check_mut_ptr(timeout)?;
+        let timeout = unsafe { *timeout };
+        timeout.validate()?;
+        Some(timeval_t::from(timeout.as_duration()))
+    };
+    debug!("ppoll: timeout: {:?}", time_val);
+
+    let tmp_to = match &mut time_val {
+        Some(time_val) => time_val as *mut timeval_t,
+        None => std::ptr::null_mut(),
+    };
+    let ret = wait_with_sig_mask(sigmask, sigsetsize, || poll(fds, nfds, tmp_to));
+    // The remaining time is written back, as ppoll does on Linux
+    if let Some(time_val) = time_val {
+        unsafe {
+            *timeout = timespec_t::from(time_val.as_duration());
+        }
     }
+    ret
+}
+
+fn poll(fds: *mut PollEvent, nfds: libc::nfds_t, timeout: *mut timeval_t) -> Result<isize> {
+    // It behaves like sleep when fds is null and nfds is zero.
+    let mut user_polls = if !fds.is_null() || nfds != 0 {
+        Some(from_user::UserMutPtr::new(fds, nfds as usize)?)
+    } else {
+        None
+    };
+    let polls = user_polls
+        .as_mut()
+        .map_or(&mut [][..], |polls| polls.as_mut_slice());
 
     let soft_rlimit_nofile = current!()
         .rlimits()
@@ -560,20 +1022,9 @@ pub fn do_poll(fds: *mut PollEvent, nfds: libc::nfds_t, timeout: c_int) -> Resul
         return_errno!(EINVAL, "The nfds value exceeds the RLIMIT_NOFILE value.");
     }
 
-    let polls = unsafe { std::slice::from_raw_parts_mut(fds, nfds as usize) };
-    debug!("poll: {:?}, timeout: {}", polls, timeout);
+    debug!("poll: {:?}", polls);
 
-    let mut time_val = timeval_t::new(
-        ((timeout as u32) / 1000) as i64,
-        ((timeout as u32) % 1000 * 1000) as i64,
-    );
-    let tmp_to = if timeout == -1 {
-        std::ptr::null_mut()
-    } else {
-        &mut time_val
-    };
-
-    let n = io_multiplexing::do_poll(polls, tmp_to)?;
+    let n = io_multiplexing::do_poll(polls, timeout)?;
     Ok(n as isize)
 }
 
@@ -627,6 +1078,21 @@ pub fn do_epoll_wait(
     events: *mut libc::epoll_event,
     max_events: c_int,
     timeout: c_int,
+) -> Result<isize> {
+    // Any negative timeout means an infinite timeout
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout as u64))
+    };
+    epoll_wait(epfd, events, max_events, timeout)
+}
+
+fn epoll_wait(
+    epfd: c_int,
+    events: *mut libc::epoll_event,
+    max_events: c_int,
+    timeout: Option<Duration>,
 ) -> Result<isize> {
     let max_events = {
         if max_events <= 0 {
@@ -634,17 +1100,15 @@ pub fn do_epoll_wait(
         }
         max_events as usize
     };
-    let raw_events = {
-        from_user::check_mut_array(events, max_events)?;
-        unsafe { std::slice::from_raw_parts_mut(events, max_events) }
-    };
+    let mut user_events = from_user::UserMutPtr::new(events, max_events)?;
+    let raw_events = user_events.as_mut_slice();
 
     // A new vector to store EpollEvent, which may degrade the performance due to extra copy.
     let mut inner_events: Vec<EpollEvent> =
         vec![EpollEvent::new(EpollEventFlags::empty(), 0); max_events];
 
     debug!(
-        "epoll_wait: epfd: {}, len: {:?}, timeout: {}",
+        "epoll_wait: epfd: {}, len: {:?}, timeout: {:?}",
         epfd,
         raw_events.len(),
         timeout
@@ -667,12 +1131,82 @@ pub fn do_epoll_pwait(
     events: *mut libc::epoll_event,
     maxevents: c_int,
     timeout: c_int,
-    sigmask: *const usize, //TODO:add sigset_t
+    sigmask: *const sigset_t,
+    sigsetsize: usize,
+) -> Result<isize> {
+    // Any negative timeout means an infinite timeout
+    let timeout = if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout as u64))
+    };
+    epoll_pwait(epfd, events, maxevents, timeout, sigmask, sigsetsize)
+}
+
+pub fn do_epoll_pwait2(
+    epfd: c_int,
+    events: *mut libc::epoll_event,
+    maxevents: c_int,
+    timeout: *const timespec_t,
+    sigmask: *const sigset_t,
+    sigsetsize: usize,
 ) -> Result<isize> {
-    if !sigmask.is_null() {
-        warn!("epoll_pwait cannot handle signal mask, yet");
+    // A null timeout means an infinite timeout
+    let timeout = if timeout.is_null() {
+        None
     } else {
-        info!("epoll_wait");
+        from_user::check_ptr(timeout)?;
+        let timeout = unsafe { *timeout };
+        timeout.validate()?;
+        Some(timeout.as_duration())
+    };
+    epoll_pwait(epfd, events, maxevents, timeout, sigmask, sigsetsize)
+}
+
+fn epoll_pwait(
+    epfd: c_int,
+    events: *mut libc::epoll_event,
+    maxevents: c_int,
+    timeout: Option<Duration>,
+    sigmask: *const sigset_t,
+    sigsetsize: usize,
+) -> Result<isize> {
+    wait_with_sig_mask(sigmask, sigsetsize, || {
+        epoll_wait(epfd, events, maxevents, timeout)
+    })
+}
+
+/// Wait with the signal mask replaced by `sigmask` during the wait, or with the
+/// signal mask unchanged if `sigmask` is null.
+///
+/// As the replacement and the wait are done atomically, a signal that is unblocked
+/// only during the wait is never missed. It interrupts the wait with EINTR, and its
+/// handler is called with the original signal mask restored on return.
+fn wait_with_sig_mask(
+    sigmask: *const sigset_t,
+    sigsetsize: usize,
+    wait: impl FnOnce() -> Result<isize>,
+) -> Result<isize> {
+    if sigmask.is_null() {
+        return wait();
+    }
+    if sigsetsize != std::mem::size_of::<sigset_t>() {
+        return_errno!(EINVAL, "unexpected sigset size");
+    }
+    from_user::check_ptr(sigmask)?;
+    let sigmask = SigSet::from_c(unsafe { *sigmask });
+
+    replace_sig_mask(sigmask);
+    let ret = if has_deliverable_signals() {
+        Err(errno!(EINTR, "interrupted by a signal before waiting"))
+    } else {
+        wait()
+    };
+    // If the wait is interrupted, the original signal mask is restored after the
+    // signal is delivered
+    match &ret {
+        Err(e) if e.errno() == EINTR => {}
+        _ => restore_sig_mask(),
     }
-    do_epoll_wait(epfd, events, maxevents, timeout)
+    ret
 }
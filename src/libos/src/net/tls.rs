@@ -0,0 +1,385 @@
+//! The TLS termination of the host sockets.
+//!
+//! The data of a host socket is exposed to the host as the arguments of ocalls.
+//! If TLS is configured in Occlum.json for some ports, the connections of the TCP
+//! sockets on the ports are wrapped in TLS inside the enclave, so the host only
+//! sees the TLS records, while the application still reads and writes plaintext.
+//! The connections accepted by the listeners on the server ports are the servers
+//! of TLS, and the connections to the client ports are the clients of TLS.
+//!
+//! The certificates and the private key are loaded from the trusted root file
+//! system, whose integrity and confidentiality are protected like the binaries.
+//!
+//! The plaintext decrypted but not read yet is buffered in the enclave, which the
+//! host cannot see. So poll, select and epoll report the sockets with the buffered
+//! plaintext as readable without waiting for the host.
+use super::*;
+use crate::config::{ConfigNetTls, ConfigTlsClient, ConfigTlsServer};
+use crate::fs::{INodeExt, ROOT_INODE};
+use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+use rustls::{ClientConfig, ClientSession, NoClientAuth, ServerConfig, ServerSession, Session};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// The max length of the plaintext decrypted at a time
+const PLAINTEXT_CHUNK_SIZE: usize = 16 * 1024;
+
+lazy_static! {
+    // The configs of TLS are loaded on the first connection, as the files are in
+    // the root file system
+    static ref SERVER_CONFIG: Option<Arc<ServerConfig>> =
+        match tls_config().and_then(|tls| tls.server.as_ref()) {
+            Some(server) => load_server_config(server)
+                .map_err(|e| error!("failed to load the TLS server config: {:?}", e))
+                .ok(),
+            None => None,
+        };
+    static ref CLIENT_CONFIG: Option<Arc<ClientConfig>> =
+        match tls_config().and_then(|tls| tls.client.as_ref()) {
+            Some(client) => load_client_config(client)
+                .map_err(|e| error!("failed to load the TLS client config: {:?}", e))
+                .ok(),
+            None => None,
+        };
+}
+
+/// Whether the connections accepted by some listeners are the servers of TLS
+pub fn is_server_enabled() -> bool {
+    tls_config().and_then(|tls| tls.server.as_ref()).is_some()
+}
+
+/// Create the session of a connection accepted by the listener on `local_addr`,
+/// or None if the port is not configured for TLS
+pub fn new_server_session(local_addr: &[u8]) -> Result<Option<TlsSession>> {
+    let server = match tls_config().and_then(|tls| tls.server.as_ref()) {
+        Some(server) if is_tls_port(&server.ports, local_addr) => server,
+        _ => return Ok(None),
+    };
+    // Never fall back to plaintext if TLS is configured for the port
+    let config = SERVER_CONFIG
+        .as_ref()
+        .ok_or_else(|| errno!(ECONNABORTED, "the TLS server config fails to load"))?;
+    Ok(Some(TlsSession::new_server(config)))
+}
+
+/// Create the session of a connection to `remote_addr`, or None if the port is
+/// not configured for TLS
+pub fn new_client_session(remote_addr: &[u8]) -> Result<Option<TlsSession>> {
+    let client = match tls_config().and_then(|tls| tls.client.as_ref()) {
+        Some(client) if is_tls_port(&client.ports, remote_addr) => client,
+        _ => return Ok(None),
+    };
+    let config = CLIENT_CONFIG
+        .as_ref()
+        .ok_or_else(|| errno!(ECONNREFUSED, "the TLS client config fails to load"))?;
+    TlsSession::new_client(config, &client.server_name).map(Some)
+}
+
+fn tls_config() -> Option<&'static ConfigNetTls> {
+    config::LIBOS_CONFIG.network.tls.as_ref()
+}
+
+/// Whether the port of an address of AF_INET or AF_INET6 is in `ports`. The port
+/// is at the same offset of the addresses of both families.
+fn is_tls_port(ports: &HashSet<u16>, addr: &[u8]) -> bool {
+    if addr.len() < 4 {
+        return false;
+    }
+    let family = u16::from_ne_bytes([addr[0], addr[1]]) as c_int;
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        return false;
+    }
+    ports.contains(&u16::from_be_bytes([addr[2], addr[3]]))
+}
+
+fn load_server_config(server: &ConfigTlsServer) -> Result<Arc<ServerConfig>> {
+    let cert_pem = read_trusted_file(&server.cert)?;
+    let key_pem = read_trusted_file(&server.key)?;
+    server_config_from_pem(&cert_pem, &key_pem)
+}
+
+fn load_client_config(client: &ConfigTlsClient) -> Result<Arc<ClientConfig>> {
+    let ca_pem = read_trusted_file(&client.ca)?;
+    client_config_from_pem(&ca_pem)
+}
+
+/// Make the config of the TLS servers from the certificate chain and the private
+/// key in PEM
+pub fn server_config_from_pem(cert_pem: &[u8], key_pem: &[u8]) -> Result<Arc<ServerConfig>> {
+    let cert_chain =
+        certs(&mut &cert_pem[..]).map_err(|_| errno!(EINVAL, "invalid certificates of TLS"))?;
+    if cert_chain.is_empty() {
+        return_errno!(EINVAL, "no certificate of TLS");
+    }
+    let mut keys = pkcs8_private_keys(&mut &key_pem[..])
+        .map_err(|_| errno!(EINVAL, "invalid private key of TLS"))?;
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut &key_pem[..])
+            .map_err(|_| errno!(EINVAL, "invalid private key of TLS"))?;
+    }
+    let key = keys
+        .into_iter()
+        .next()
+        .ok_or_else(|| errno!(EINVAL, "no private key of TLS"))?;
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config
+        .set_single_cert(cert_chain, key)
+        .map_err(|_| errno!(EINVAL, "the private key does not match the certificate"))?;
+    Ok(Arc::new(config))
+}
+
+/// Make the config of the TLS clients from the CA certificates in PEM
+pub fn client_config_from_pem(ca_pem: &[u8]) -> Result<Arc<ClientConfig>> {
+    let mut config = ClientConfig::new();
+    let (num_added, _) = config
+        .root_store
+        .add_pem_file(&mut &ca_pem[..])
+        .map_err(|_| errno!(EINVAL, "invalid CA certificates of TLS"))?;
+    if num_added == 0 {
+        return_errno!(EINVAL, "no CA certificate of TLS");
+    }
+    Ok(Arc::new(config))
+}
+
+fn read_trusted_file(path: &Path) -> Result<Vec<u8>> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| errno!(EINVAL, "invalid path of TLS file"))?;
+    ROOT_INODE.lookup(path)?.read_as_vec()
+}
+
+/// The state of TLS of a connection, whose records are exchanged via the host
+/// socket.
+///
+/// All the operations are non-blocking, which fail with EAGAIN if the host socket
+/// is not ready. The callers wait on the host socket and retry for blocking I/O.
+pub struct TlsSession {
+    session: Box<dyn Session>,
+    // The plaintext decrypted but not read yet
+    plaintext: Vec<u8>,
+    // Whether the peer has closed the connection
+    eof: bool,
+}
+
+impl TlsSession {
+    /// Create the session of a TLS server
+    pub fn new_server(config: &Arc<ServerConfig>) -> Self {
+        Self::new(Box::new(ServerSession::new(config)))
+    }
+
+    /// Create the session of a TLS client that connects to `server_name`
+    pub fn new_client(config: &Arc<ClientConfig>, server_name: &str) -> Result<Self> {
+        let server_name = webpki::DNSNameRef::try_from_ascii_str(server_name)
+            .map_err(|_| errno!(EINVAL, "invalid server name of TLS"))?;
+        Ok(Self::new(Box::new(ClientSession::new(config, server_name))))
+    }
+
+    fn new(session: Box<dyn Session>) -> Self {
+        Self {
+            session,
+            plaintext: Vec::new(),
+            eof: false,
+        }
+    }
+
+    /// Drive the handshake until it completes
+    pub fn handshake(&mut self, host_fd: c_int) -> Result<()> {
+        loop {
+            self.flush(host_fd)?;
+            if !self.session.is_handshaking() {
+                return Ok(());
+            }
+            if self.eof {
+                return_errno!(ECONNRESET, "the connection is closed in handshake");
+            }
+            self.fill(host_fd)?;
+        }
+    }
+
+    /// Encrypt and send the data, and return the length of the data accepted.
+    ///
+    /// The data written during the handshake is sent once the handshake completes.
+    pub fn send(&mut self, host_fd: c_int, bufs: &[&[u8]]) -> Result<usize> {
+        // Wait for the records of the previous data to be sent first, so that the
+        // records buffered in the enclave are bounded
+        self.flush(host_fd)?;
+        let mut len = 0;
+        for buf in bufs {
+            len += self
+                .session
+                .write(buf)
+                .map_err(|_| errno!(EIO, "failed to encrypt the data"))?;
+        }
+        self.try_flush(host_fd)?;
+        Ok(len)
+    }
+
+    /// Receive and decrypt the data. It returns 0 if the peer has closed the
+    /// connection.
+    pub fn recv(&mut self, host_fd: c_int, bufs: &mut [&mut [u8]], peek: bool) -> Result<usize> {
+        loop {
+            // The records of the handshake are sent in response to the records
+            // received
+            self.try_flush(host_fd)?;
+            if !self.plaintext.is_empty() || self.eof {
+                break;
+            }
+            self.fill(host_fd)?;
+        }
+        if self.plaintext.is_empty() && self.session.is_handshaking() {
+            return_errno!(ECONNRESET, "the connection is closed in handshake");
+        }
+
+        let mut len = 0;
+        for buf in bufs.iter_mut() {
+            let copy_len = min(buf.len(), self.plaintext.len() - len);
+            buf[..copy_len].copy_from_slice(&self.plaintext[len..len + copy_len]);
+            len += copy_len;
+        }
+        if !peek {
+            self.plaintext.drain(..len);
+        }
+        Ok(len)
+    }
+
+    /// Send all the records buffered in the enclave
+    pub fn flush(&mut self, host_fd: c_int) -> Result<()> {
+        while self.session.wants_write() {
+            self.session
+                .write_tls(&mut HostSocket(host_fd))
+                .map_err(from_io_error)?;
+        }
+        Ok(())
+    }
+
+    /// Tell the peer that no more data will be sent
+    pub fn close(&mut self, host_fd: c_int) -> Result<()> {
+        self.session.send_close_notify();
+        self.try_flush(host_fd)
+    }
+
+    /// Whether there is any plaintext that can be read without the host socket
+    pub fn has_plaintext(&self) -> bool {
+        !self.plaintext.is_empty()
+    }
+
+    /// The events of the host socket that the session is waiting for
+    pub fn wait_events(&self) -> PollEventFlags {
+        if self.session.wants_write() {
+            PollEventFlags::POLLIN | PollEventFlags::POLLOUT
+        } else {
+            PollEventFlags::POLLIN
+        }
+    }
+
+    fn try_flush(&mut self, host_fd: c_int) -> Result<()> {
+        match self.flush(host_fd) {
+            Err(e) if e.errno() == EAGAIN => Ok(()),
+            ret => ret,
+        }
+    }
+
+    /// Receive the records from the host socket, and decrypt the data in them
+    fn fill(&mut self, host_fd: c_int) -> Result<()> {
+        let len = self
+            .session
+            .read_tls(&mut HostSocket(host_fd))
+            .map_err(from_io_error)?;
+        if len == 0 {
+            self.eof = true;
+            return Ok(());
+        }
+        if let Err(e) = self.session.process_new_packets() {
+            warn!("invalid TLS records: {:?}", e);
+            // Send the alert to the peer
+            let _ = self.flush(host_fd);
+            return_errno!(ECONNRESET, "invalid TLS records");
+        }
+
+        let mut chunk = vec![0u8; PLAINTEXT_CHUNK_SIZE];
+        loop {
+            match self.session.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => self.plaintext.extend_from_slice(&chunk[..len]),
+                // The peer has sent close_notify
+                Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => {
+                    self.eof = true;
+                    break;
+                }
+                Err(_) => return_errno!(EIO, "failed to decrypt the data"),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Debug for TlsSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsSession")
+            .field("is_handshaking", &self.session.is_handshaking())
+            .field("plaintext_len", &self.plaintext.len())
+            .field("eof", &self.eof)
+            .finish()
+    }
+}
+
+/// The host socket that carries the records, which never blocks
+struct HostSocket(c_int);
+
+impl Read for HostSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let ret = unsafe {
+            libc::ocall::recvfrom(
+                self.0,
+                buf.as_mut_ptr() as *mut c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::from_raw_os_error(unsafe { libc::errno() }));
+        }
+        // The records are verified by TLS, but the length is checked here
+        if ret as usize > buf.len() {
+            return Err(io::Error::from_raw_os_error(Errno::EIO as i32));
+        }
+        Ok(ret as usize)
+    }
+}
+
+impl Write for HostSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let ret = unsafe {
+            libc::ocall::sendto(
+                self.0,
+                buf.as_ptr() as *const c_void,
+                buf.len(),
+                libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                std::ptr::null(),
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::from_raw_os_error(unsafe { libc::errno() }));
+        }
+        if ret as usize > buf.len() {
+            return Err(io::Error::from_raw_os_error(Errno::EIO as i32));
+        }
+        Ok(ret as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn from_io_error(e: io::Error) -> Error {
+    match e.raw_os_error() {
+        Some(errno) => errno!(Errno::from(errno as u32), "failed to transfer TLS records"),
+        None => errno!(EIO, "failed to transfer TLS records"),
+    }
+}
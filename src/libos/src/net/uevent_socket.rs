@@ -0,0 +1,201 @@
+//! A stub of the netlink socket of NETLINK_KOBJECT_UEVENT.
+//!
+//! Some daemons (e.g., the device monitors of udev or systemd) listen to the
+//! uevents of the kernel with the socket, and fail or hang if it cannot be created.
+//! There is no device hotplug in the enclave, so the socket can be bound and
+//! configured like on Linux, but it never receives any uevent. The uevents of the
+//! host are not passed through, as they are untrusted.
+//!
+//! The socket is backed by an eventfd that is never written, so that it can be
+//! polled with poll, select and epoll like the other files backed by the host,
+//! where it is never readable.
+use super::socket::{sockaddr_bytes_of, Socket};
+use super::sockopt::{SO_DOMAIN, SO_PROTOCOL, SO_TYPE};
+use super::*;
+use fs::{AccessMode, EventCreationFlags, EventFile, File, FileRef, StatusFlags};
+use std::any::Any;
+use std::mem::size_of;
+
+pub const NETLINK_KOBJECT_UEVENT: c_int = 15;
+
+/// C struct for the address of a netlink socket
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+#[allow(non_camel_case_types)]
+pub struct sockaddr_nl {
+    pub nl_family: u16,
+    nl_pad: u16,
+    pub nl_pid: u32,
+    pub nl_groups: u32,
+}
+
+#[derive(Debug)]
+pub struct UeventSocket {
+    socket_type: c_int,
+    // Never written, so the socket is never readable
+    event: EventFile,
+    // The port id and the multicast groups that the socket is bound to
+    addr: SgxMutex<sockaddr_nl>,
+}
+
+impl UeventSocket {
+    pub fn new(socket_type: c_int) -> Result<Self> {
+        let flags = socket_type & (libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        let socket_type = socket_type & !flags;
+        if socket_type != libc::SOCK_RAW && socket_type != libc::SOCK_DGRAM {
+            return_errno!(ESOCKTNOSUPPORT, "unsupported type of netlink socket");
+        }
+        let event_flags = if flags & libc::SOCK_NONBLOCK != 0 {
+            EventCreationFlags::EFD_NONBLOCK
+        } else {
+            EventCreationFlags::empty()
+        };
+        let event = EventFile::new(0, event_flags)?;
+        let addr = sockaddr_nl {
+            nl_family: libc::AF_NETLINK as u16,
+            ..Default::default()
+        };
+        Ok(Self {
+            socket_type,
+            event,
+            addr: SgxMutex::new(addr),
+        })
+    }
+
+    pub fn bind(&self, addr_buf: &[u8]) -> Result<()> {
+        if addr_buf.len() < size_of::<sockaddr_nl>() {
+            return_errno!(EINVAL, "the address is too short for sockaddr_nl");
+        }
+        let mut new_addr =
+            unsafe { std::ptr::read_unaligned(addr_buf.as_ptr() as *const sockaddr_nl) };
+        if new_addr.nl_family != libc::AF_NETLINK as u16 {
+            return_errno!(EINVAL, "not a netlink address");
+        }
+        // Like Linux, the port id of the first netlink socket of a process is the
+        // pid if it is chosen by the LibOS
+        if new_addr.nl_pid == 0 {
+            new_addr.nl_pid = current!().process().pid() as u32;
+        }
+        *self.addr.lock().unwrap() = new_addr;
+        Ok(())
+    }
+
+    pub fn addr(&self) -> sockaddr_nl {
+        *self.addr.lock().unwrap()
+    }
+
+    pub fn setsockopt(&self, level: c_int, optname: c_int, _optval: &[u8]) -> Result<()> {
+        // The options, e.g., the size of the receive buffer or the filter of uevents,
+        // make no difference as there are no uevents
+        debug!(
+            "setsockopt for uevent socket is ignored: level: {}, optname: {}",
+            level, optname
+        );
+        Ok(())
+    }
+
+    pub fn getsockopt(&self, level: c_int, optname: c_int) -> Result<Vec<u8>> {
+        let val = match (level, optname) {
+            (libc::SOL_SOCKET, SO_TYPE) => self.socket_type,
+            (libc::SOL_SOCKET, SO_DOMAIN) => libc::AF_NETLINK,
+            (libc::SOL_SOCKET, SO_PROTOCOL) => NETLINK_KOBJECT_UEVENT,
+            _ => return_errno!(ENOPROTOOPT, "unknown socket option for uevent socket"),
+        };
+        Ok(val.to_ne_bytes().to_vec())
+    }
+
+    /// Wait for a uevent, which never comes. So it blocks until interrupted, or
+    /// fails with EAGAIN if non-blocking.
+    pub fn recv(&self, flags: RecvFlags) -> Result<usize> {
+        if flags.contains(RecvFlags::MSG_DONTWAIT) {
+            return_errno!(EAGAIN, "no uevent is available");
+        }
+        let mut buf = [0u8; size_of::<u64>()];
+        self.event.read(&mut buf)?;
+        return_errno!(EIO, "unexpected uevent");
+    }
+
+    /// Like Linux, the messages sent to the multicast groups are dropped as there
+    /// is no other listener
+    pub fn sendv(&self, bufs: &[&[u8]]) -> Result<usize> {
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.event.get_host_fd()
+    }
+}
+
+impl File for UeventSocket {
+    fn read(&self, _buf: &mut [u8]) -> Result<usize> {
+        self.recv(RecvFlags::empty())
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        self.sendv(&[buf])
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDWR)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        self.event.get_status_flags()
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        self.event
+            .set_status_flags(new_status_flags & StatusFlags::O_NONBLOCK)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Socket for UeventSocket {
+    fn bind(&self, addr: &[u8]) -> Result<()> {
+        UeventSocket::bind(self, addr)
+    }
+
+    fn listen(&self, _backlog: c_int) -> Result<()> {
+        return_errno!(EOPNOTSUPP, "uevent socket cannot listen");
+    }
+
+    fn shutdown(&self, _how: c_int) -> Result<()> {
+        return_errno!(EOPNOTSUPP, "uevent socket cannot be shut down");
+    }
+
+    fn addr(&self) -> Result<Vec<u8>> {
+        Ok(sockaddr_bytes_of(&UeventSocket::addr(self)))
+    }
+
+    fn peer_addr(&self) -> Result<Vec<u8>> {
+        // Like Linux, the peer is the kernel
+        let addr = sockaddr_nl {
+            nl_family: libc::AF_NETLINK as u16,
+            ..Default::default()
+        };
+        Ok(sockaddr_bytes_of(&addr))
+    }
+
+    fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        UeventSocket::setsockopt(self, level, optname, optval)
+    }
+
+    fn getsockopt(&self, level: c_int, optname: c_int, _max_len: usize) -> Result<Vec<u8>> {
+        UeventSocket::getsockopt(self, level, optname)
+    }
+}
+
+pub trait AsUeventSocket {
+    fn as_uevent_socket(&self) -> Result<&UeventSocket>;
+}
+
+impl AsUeventSocket for FileRef {
+    fn as_uevent_socket(&self) -> Result<&UeventSocket> {
+        self.as_any()
+            .downcast_ref::<UeventSocket>()
+            .ok_or_else(|| errno!(EBADF, "not a uevent socket"))
+    }
+}
@@ -0,0 +1,372 @@
+//! The relays from the ends of socketpairs to the unix sockets of the host.
+//!
+//! The unix sockets in the enclave are not visible to the host. To pass an end of
+//! a socketpair to a host process, an application binds the end to a path that
+//! is configured as a relay in Occlum.json, which listens on the host path of the
+//! relay instead. The end is handed over to the relay then, i.e., it is neither
+//! readable nor writable any more, and may be closed. Once a host process connects
+//! to the host path, the data is pumped between the connection and the other end
+//! of the socketpair, which is used by the application as before.
+//!
+//! There is no kernel thread in the LibOS to run the pump. Instead, the relays are
+//! pumped by a background work, and by the threads that use the other ends, where
+//! a blocking send or receive waits on the host socket of the relay. The files
+//! passed by SCM_RIGHTS cannot be relayed to the host, so they are dropped.
+//!
+//! A relay is done once either side is closed and the data to the other side is
+//! delivered, so that the other side sees the end of the stream.
+use super::unix_socket::UnixSocket;
+use super::*;
+use crate::config::ConfigUnixRelay;
+use crate::sched::{BackgroundWork, WorkPriority};
+use std::time::Duration;
+
+/// The max length of the data pumped at a time in each direction
+const PUMP_BUF_LEN: usize = 64 * 1024;
+
+lazy_static! {
+    static ref RELAYS: SgxMutex<Vec<Arc<UnixRelay>>> = SgxMutex::new(Vec::new());
+}
+
+/// The slot of the relay of a socketpair, which is shared by both ends, so that
+/// the end that is not relayed can find the relay of its peer
+pub type RelaySlot = Arc<SgxMutex<Option<Arc<UnixRelay>>>>;
+
+/// Find the relay of a path given to bind
+pub fn find_relay(path: &str) -> Option<&'static ConfigUnixRelay> {
+    config::LIBOS_CONFIG
+        .network
+        .unix_relays
+        .iter()
+        .find(|relay| relay.path == path)
+}
+
+/// The background work that pumps the relays, if any is configured
+pub fn unix_relay_work() -> Option<BackgroundWork> {
+    if config::LIBOS_CONFIG.network.unix_relays.is_empty() {
+        return None;
+    }
+    Some(BackgroundWork::new(
+        "unix_relay",
+        WorkPriority::High,
+        Some(Duration::from_millis(10)),
+        pump_relays,
+    ))
+}
+
+fn pump_relays() -> Result<()> {
+    let relays = RELAYS.lock().unwrap().clone();
+    for relay in relays {
+        relay.pump();
+    }
+    Ok(())
+}
+
+pub struct UnixRelay {
+    policy: &'static ConfigUnixRelay,
+    slot: RelaySlot,
+    inner: SgxMutex<Inner>,
+}
+
+struct Inner {
+    // The end of the socketpair handed over to the relay, which is dropped once
+    // the relay is done
+    end: Option<UnixSocket>,
+    // The host socket listening on the host path, which is closed once connected
+    listen_host_fd: Option<c_int>,
+    // The connection of the host process
+    conn_host_fd: Option<c_int>,
+    // The data received from one side but not delivered to the other side yet
+    to_host: Vec<u8>,
+    to_enclave: Vec<u8>,
+    // Whether the end of the stream is received from either side
+    is_end_closed: bool,
+    is_host_closed: bool,
+}
+
+impl UnixRelay {
+    /// Listen on the host path of the relay. Like binding a path in Linux, it fails
+    /// with EADDRINUSE if the host path exists.
+    pub fn listen_on_host(policy: &'static ConfigUnixRelay) -> Result<c_int> {
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        // The length is checked when the config is loaded
+        for (dst, &src) in addr.sun_path.iter_mut().zip(policy.host_path.as_bytes()) {
+            *dst = src as c_char;
+        }
+        let host_fd = try_libc!(libc::ocall::socket(
+            libc::AF_UNIX,
+            libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            0
+        ));
+        let ret = unsafe {
+            let addr_len = std::mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+            if libc::ocall::bind(
+                host_fd,
+                &addr as *const _ as *const libc::sockaddr,
+                addr_len,
+            ) < 0
+            {
+                -1
+            } else {
+                libc::ocall::listen(host_fd, 1)
+            }
+        };
+        if ret < 0 {
+            let errno = Errno::from(unsafe { libc::errno() } as u32);
+            unsafe { libc::ocall::close(host_fd) };
+            return_errno!(errno, "failed to listen on the host path of the relay");
+        }
+        info!(
+            "listening on the host path of the unix relay: {}",
+            policy.path
+        );
+        Ok(host_fd)
+    }
+
+    /// Create the relay of the end of a socketpair, which listens on the host fd
+    /// given by `listen_on_host`. The caller puts the relay into the slot.
+    pub fn new(
+        policy: &'static ConfigUnixRelay,
+        slot: RelaySlot,
+        listen_host_fd: c_int,
+        mut end: UnixSocket,
+    ) -> Arc<Self> {
+        // The relay never blocks on the end
+        end.set_nonblocking(true);
+        let relay = Arc::new(Self {
+            policy,
+            slot,
+            inner: SgxMutex::new(Inner {
+                end: Some(end),
+                listen_host_fd: Some(listen_host_fd),
+                conn_host_fd: None,
+                to_host: Vec::new(),
+                to_enclave: Vec::new(),
+                is_end_closed: false,
+                is_host_closed: false,
+            }),
+        });
+        RELAYS.lock().unwrap().push(relay.clone());
+        relay
+    }
+
+    /// Pump the data in both directions as much as possible without blocking. The
+    /// relay is removed once it is done.
+    pub fn pump(self: &Arc<Self>) {
+        let is_done = {
+            let mut inner = self.inner.lock().unwrap();
+            if let Err(e) = inner.pump() {
+                warn!("the unix relay {} failed: {:?}", self.policy.path, e);
+                inner.is_end_closed = true;
+                inner.is_host_closed = true;
+            }
+            let is_done = (inner.is_end_closed && inner.to_host.is_empty())
+                || (inner.is_host_closed && inner.to_enclave.is_empty());
+            if is_done {
+                // The other sides see the end of the stream
+                inner.end = None;
+                inner.close_host_fds();
+            }
+            is_done
+        };
+        if is_done {
+            self.slot.lock().unwrap().take();
+            RELAYS
+                .lock()
+                .unwrap()
+                .retain(|relay| !Arc::ptr_eq(relay, self));
+        }
+    }
+
+    /// Wait until the host socket of the relay may be ready for `events`, i.e.,
+    /// the connection of the host process, or the listening socket if it is not
+    /// connected yet
+    pub fn wait(&self, events: PollEventFlags, timeout: Option<Duration>) -> Result<()> {
+        let (host_fd, events) = {
+            let inner = self.inner.lock().unwrap();
+            match (inner.conn_host_fd, inner.listen_host_fd) {
+                (Some(host_fd), _) => (host_fd, events),
+                (None, Some(host_fd)) => (host_fd, PollEventFlags::POLLIN),
+                // The relay is done, so there is nothing to wait for
+                (None, None) => return Ok(()),
+            }
+        };
+        wait_host_fd(host_fd as FileDesc, events, timeout)
+    }
+}
+
+impl Inner {
+    fn pump(&mut self) -> Result<()> {
+        if self.end.is_none() {
+            // The relay is done
+            return Ok(());
+        }
+        if self.conn_host_fd.is_none() && !self.accept()? {
+            self.check_end_closed()?;
+            return Ok(());
+        }
+        self.pump_to_host()?;
+        self.pump_to_enclave()
+    }
+
+    /// Check if the other end is closed with no data to relay before the host
+    /// process connects, after which the relay is useless
+    fn check_end_closed(&mut self) -> Result<()> {
+        let end = self.end.as_mut().unwrap();
+        let mut buf = [0; 1];
+        let flags = RecvFlags::MSG_PEEK | RecvFlags::MSG_DONTWAIT;
+        match end.readv_with_files(&mut [&mut buf[..]], flags) {
+            Ok((0, _, _, _, _)) => self.is_end_closed = true,
+            Ok(_) => {}
+            Err(e) if e.errno() == EAGAIN => {}
+            Err(e) if e.errno() == ECONNRESET => self.is_end_closed = true,
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Accept the connection of the host process, if any
+    fn accept(&mut self) -> Result<bool> {
+        let listen_host_fd = match self.listen_host_fd {
+            Some(host_fd) => host_fd,
+            None => return Ok(false),
+        };
+        let ret = unsafe {
+            libc::ocall::accept4(
+                listen_host_fd,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            )
+        };
+        if ret < 0 {
+            let errno = Errno::from(unsafe { libc::errno() } as u32);
+            if errno == EAGAIN || errno == EINTR {
+                return Ok(false);
+            }
+            return_errno!(errno, "failed to accept the host connection");
+        }
+        // Only one host process is connected, like the peer of a socketpair
+        self.conn_host_fd = Some(ret);
+        unsafe { libc::ocall::close(listen_host_fd) };
+        self.listen_host_fd = None;
+        Ok(true)
+    }
+
+    fn pump_to_host(&mut self) -> Result<()> {
+        let end = self.end.as_mut().unwrap();
+        let conn_host_fd = self.conn_host_fd.unwrap();
+        loop {
+            if self.to_host.is_empty() && !self.is_end_closed {
+                let mut buf = vec![0; PUMP_BUF_LEN];
+                match end.readv_with_files(&mut [&mut buf[..]], RecvFlags::MSG_DONTWAIT) {
+                    Ok((0, _, _, _, _)) => self.is_end_closed = true,
+                    Ok((count, files, _, _, _)) => {
+                        if !files.is_empty() {
+                            warn!("the files cannot be relayed to the host");
+                        }
+                        buf.truncate(count);
+                        self.to_host = buf;
+                    }
+                    Err(e) if e.errno() == EAGAIN => {}
+                    Err(e) if e.errno() == ECONNRESET => self.is_end_closed = true,
+                    Err(e) => return Err(e),
+                }
+            }
+            if self.to_host.is_empty() || self.is_host_closed {
+                return Ok(());
+            }
+            let ret = unsafe {
+                libc::ocall::sendto(
+                    conn_host_fd,
+                    self.to_host.as_ptr() as *const c_void,
+                    self.to_host.len(),
+                    libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+                    std::ptr::null(),
+                    0,
+                )
+            };
+            if ret < 0 {
+                let errno = Errno::from(unsafe { libc::errno() } as u32);
+                if errno == EAGAIN || errno == EINTR {
+                    return Ok(());
+                }
+                // The host process is gone, so the data can never be delivered
+                self.is_host_closed = true;
+                self.to_host.clear();
+                return Ok(());
+            }
+            let len = ret as usize;
+            if len == 0 || len > self.to_host.len() {
+                return_errno!(EIO, "invalid length of the data sent");
+            }
+            self.to_host.drain(..len);
+        }
+    }
+
+    fn pump_to_enclave(&mut self) -> Result<()> {
+        let end = self.end.as_mut().unwrap();
+        let conn_host_fd = self.conn_host_fd.unwrap();
+        loop {
+            if self.to_enclave.is_empty() && !self.is_host_closed {
+                let mut buf = vec![0; PUMP_BUF_LEN];
+                let ret = unsafe {
+                    libc::ocall::recvfrom(
+                        conn_host_fd,
+                        buf.as_mut_ptr() as *mut c_void,
+                        buf.len(),
+                        libc::MSG_DONTWAIT,
+                        std::ptr::null_mut(),
+                        std::ptr::null_mut(),
+                    )
+                };
+                if ret < 0 {
+                    let errno = Errno::from(unsafe { libc::errno() } as u32);
+                    if errno != EAGAIN && errno != EINTR {
+                        self.is_host_closed = true;
+                    }
+                } else if ret == 0 {
+                    self.is_host_closed = true;
+                } else {
+                    let len = ret as usize;
+                    if len > buf.len() {
+                        return_errno!(EIO, "invalid length of the data received");
+                    }
+                    buf.truncate(len);
+                    self.to_enclave = buf;
+                }
+            }
+            if self.to_enclave.is_empty() || self.is_end_closed {
+                return Ok(());
+            }
+            match end.write(&self.to_enclave) {
+                Ok(count) => {
+                    self.to_enclave.drain(..count);
+                }
+                Err(e) if e.errno() == EAGAIN => return Ok(()),
+                Err(_) => {
+                    // The other end is closed, so the data can never be delivered
+                    self.is_end_closed = true;
+                    self.to_enclave.clear();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn close_host_fds(&mut self) {
+        if let Some(host_fd) = self.listen_host_fd.take() {
+            unsafe { libc::ocall::close(host_fd) };
+        }
+        if let Some(host_fd) = self.conn_host_fd.take() {
+            unsafe { libc::ocall::close(host_fd) };
+        }
+    }
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.close_host_fds();
+    }
+}
@@ -1,27 +1,38 @@
+use super::cmsg::{ucred, TimestampFormat};
+use super::dbus_bridge::DbusBridge;
+use super::socket::Socket;
+use super::socket_stats::SocketStats;
+use super::sockopt::*;
+use super::syscalls::sun_path_of;
+use super::unix_relay::{find_relay, RelaySlot, UnixRelay};
 use super::*;
-use fs::{File, FileRef, IoctlCmd};
+use crate::prelude::SgxMutex as Mutex;
+use config::{ConfigDbusBridge, ConfigUnixRelay};
+use fs::{AccessMode, File, FileRef, IoctlCmd, StatusFlags};
 use rcore_fs::vfs::{FileType, Metadata, Timespec};
+use signal::{has_deliverable_signals, send_sigpipe_on_epipe};
 use std::any::Any;
-use std::collections::btree_map::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
-use std::sync::atomic::{spin_loop_hint, AtomicUsize, Ordering};
-use std::sync::SgxMutex as Mutex;
-use util::ring_buf::{ring_buffer, RingBufReader, RingBufWriter};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use util::ring_buf::{ring_buffer, RingBufReader, RingBufWriter, RECORD_HEADER_LEN};
 
 pub struct UnixSocketFile {
     inner: Mutex<UnixSocket>,
+    stats: SocketStats,
+    // SOCK_STREAM or SOCK_SEQPACKET
+    socket_type: c_int,
 }
 
-// TODO: add enqueue_event and dequeue_event
 impl File for UnixSocketFile {
     fn read(&self, buf: &mut [u8]) -> Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.read(buf)
+        self.readv(&mut [buf])
     }
 
     fn write(&self, buf: &[u8]) -> Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.write(buf)
+        self.send(buf, SendFlags::empty())
     }
 
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
@@ -33,13 +44,13 @@ impl File for UnixSocketFile {
     }
 
     fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.readv(bufs)
+        // Like Linux, the files sent along with the data are discarded
+        self.recvv_with_files(bufs, RecvFlags::empty())
+            .map(|(bytes_recvd, _files, _cred, _timestamp, _msg_flags)| bytes_recvd)
     }
 
     fn writev(&self, bufs: &[&[u8]]) -> Result<usize> {
-        let mut inner = self.inner.lock().unwrap();
-        inner.writev(bufs)
+        self.sendv(bufs, SendFlags::empty())
     }
 
     fn metadata(&self) -> Result<Metadata> {
@@ -66,11 +77,48 @@ impl File for UnixSocketFile {
         inner.ioctl(cmd)
     }
 
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDWR)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        let inner = self.inner.lock().unwrap();
+        if inner.is_nonblocking() {
+            Ok(StatusFlags::O_NONBLOCK)
+        } else {
+            Ok(StatusFlags::empty())
+        }
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.set_nonblocking(new_status_flags.contains(StatusFlags::O_NONBLOCK));
+        Ok(())
+    }
+
     fn poll(&self) -> Result<PollEventFlags> {
+        if let Some(relay) = self.peer_relay() {
+            relay.pump();
+        }
         let mut inner = self.inner.lock().unwrap();
         inner.poll()
     }
 
+    fn enqueue_event(&self, event: IoEvent) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        inner.enqueue_event(event)
+    }
+
+    fn dequeue_event(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        inner.dequeue_event()
+    }
+
+    fn poll_seq(&self) -> Result<usize> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.poll_seq())
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -80,28 +128,56 @@ static SOCKETPAIR_NUM: AtomicUsize = AtomicUsize::new(0);
 const SOCK_PATH_PREFIX: &str = "socketpair_";
 
 impl UnixSocketFile {
+    /// Create a unix socket, where SOCK_NONBLOCK may be given in the type
     pub fn new(socket_type: c_int, protocol: c_int) -> Result<Self> {
-        let inner = UnixSocket::new(socket_type, protocol)?;
+        let nonblocking = socket_type & libc::SOCK_NONBLOCK != 0;
+        let socket_type = socket_type & !(libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC);
+        let mut inner = UnixSocket::new(socket_type, protocol)?;
+        inner.set_nonblocking(nonblocking);
         Ok(UnixSocketFile {
             inner: Mutex::new(inner),
+            stats: SocketStats::new(),
+            socket_type,
         })
     }
 
+    pub fn socket_type(&self) -> c_int {
+        self.socket_type
+    }
+
+    /// Bind the socket to a path, or hand the socket over to the relay if the path
+    /// is configured as a relay to the host
     pub fn bind(&self, path: impl AsRef<str>) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        inner.bind(path)
+        match find_relay(path.as_ref()) {
+            Some(policy) => inner.bind_relay(policy),
+            None => inner.bind(path),
+        }
     }
 
-    pub fn listen(&self) -> Result<()> {
+    pub fn listen(&self, backlog: c_int) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        inner.listen()
+        inner.listen(backlog)
     }
 
-    pub fn accept(&self) -> Result<UnixSocketFile> {
+    pub fn shutdown(&self, how: c_int) -> Result<()> {
         let mut inner = self.inner.lock().unwrap();
-        let new_socket = inner.accept()?;
+        inner.shutdown(how)
+    }
+
+    pub fn accept(&self) -> Result<UnixSocketFile> {
+        let (obj, nonblocking) = {
+            let inner = self.inner.lock().unwrap();
+            (inner.listening_obj()?, inner.is_nonblocking())
+        };
+        // The lock of the socket is not held while waiting for connections, so that
+        // the threads or processes that share the listening socket can accept
+        // concurrently
+        let new_socket = obj.accept(nonblocking)?;
         Ok(UnixSocketFile {
             inner: Mutex::new(new_socket),
+            stats: SocketStats::new(),
+            socket_type: self.socket_type,
         })
     }
 
@@ -110,15 +186,330 @@ impl UnixSocketFile {
         inner.connect(path)
     }
 
+    /// Connect to the unix socket of the host given by the bridge of the abstract
+    /// name, which is given with a leading '@'
+    pub fn connect_bridge(&self, name: &str, policy: &'static ConfigDbusBridge) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.connect_bridge(name, policy)
+    }
+
+    /// Get the address of the peer in the form of sockaddr_un
+    pub fn peer_addr(&self) -> Result<Vec<u8>> {
+        let inner = self.inner.lock().unwrap();
+        inner.peer_addr()
+    }
+
+    /// Get the address of the sender of the data received, i.e., the peer, in the
+    /// form of sockaddr_un. Like Linux, it is None if the peer is unnamed.
+    pub fn sender_addr(&self) -> Option<Vec<u8>> {
+        self.peer_addr()
+            .ok()
+            .filter(|addr| addr.len() > std::mem::size_of::<libc::sa_family_t>())
+    }
+
+    pub fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.setsockopt(level, optname, optval)
+    }
+
+    pub fn getsockopt(&self, level: c_int, optname: c_int) -> Result<Vec<u8>> {
+        let inner = self.inner.lock().unwrap();
+        inner.getsockopt(level, optname)
+    }
+
+    pub fn stats(&self) -> &SocketStats {
+        &self.stats
+    }
+
+    /// The entry of the socket in /proc/net/unix. The socket locked by a blocking
+    /// read or write is shown as connected, without its path.
+    pub fn proc_entry(&self) -> UnixSocketEntry {
+        let inner = match self.inner.try_lock() {
+            Ok(inner) => inner,
+            Err(_) => {
+                return UnixSocketEntry {
+                    socket_type: self.socket_type,
+                    is_listening: false,
+                    is_connected: true,
+                    path: None,
+                }
+            }
+        };
+        UnixSocketEntry {
+            socket_type: self.socket_type,
+            is_listening: matches!(inner.status, Status::Listening),
+            is_connected: matches!(inner.status, Status::Connected(_)) || inner.bridge.is_some(),
+            path: inner.obj.as_ref().map(|obj| obj.path.clone()),
+        }
+    }
+
+    pub fn send(&self, buf: &[u8], flags: SendFlags) -> Result<usize> {
+        self.sendv(&[buf], flags)
+    }
+
+    pub fn sendv(&self, bufs: &[&[u8]], flags: SendFlags) -> Result<usize> {
+        self.sendv_with_files(bufs, Vec::new(), flags)
+    }
+
+    /// Send the data to the peer, along with the files passed by SCM_RIGHTS, where
+    /// SIGPIPE is raised on EPIPE unless MSG_NOSIGNAL is given
+    pub fn sendv_with_files(
+        &self,
+        bufs: &[&[u8]],
+        files: Vec<FileRef>,
+        flags: SendFlags,
+    ) -> Result<usize> {
+        let ret = match self.peer_relay() {
+            Some(relay) => self.sendv_via_relay(bufs, files, flags, &relay),
+            None => self.inner.lock().unwrap().writev_with_files(bufs, files),
+        };
+        let bytes_sent = if flags.contains(SendFlags::MSG_NOSIGNAL) {
+            ret
+        } else {
+            send_sigpipe_on_epipe(ret)
+        }?;
+        self.stats.account_sent(bytes_sent);
+        Ok(bytes_sent)
+    }
+
+    /// Receive the data from the peer, along with the files passed by SCM_RIGHTS,
+    /// the credentials of the sender if SO_PASSCRED is set, and the time when the
+    /// data is sent if SO_TIMESTAMP or SO_TIMESTAMPNS is set.
+    ///
+    /// Like Linux, MSG_WAITALL keeps receiving until the buffers are full, unless
+    /// the peer is closed, the files are received, the data is sent by another
+    /// process (if SO_PASSCRED is set), or an error occurs after some data is
+    /// received. It has no effect with MSG_PEEK, a nonblocking receive or a
+    /// SOCK_SEQPACKET socket, which receives one message at a time.
+    ///
+    /// The message of a SOCK_SEQPACKET socket that does not fit in the buffers is
+    /// truncated with MSG_TRUNC, where its real length is returned if MSG_TRUNC is
+    /// given in the flags.
+    pub fn recvv_with_files(
+        &self,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+    ) -> Result<(
+        usize,
+        Vec<FileRef>,
+        Option<ucred>,
+        Option<(TimestampFormat, Duration)>,
+        MsgHdrFlags,
+    )> {
+        let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let waitall = flags.contains(RecvFlags::MSG_WAITALL)
+            && !flags.intersects(RecvFlags::MSG_PEEK | RecvFlags::MSG_DONTWAIT)
+            && self.socket_type == libc::SOCK_STREAM;
+        let mut bytes_recvd = 0;
+        let mut files = Vec::new();
+        let mut cred = None;
+        let mut timestamp = None;
+        let mut msg_flags = MsgHdrFlags::empty();
+        loop {
+            let mut remaining_bufs = skip_bytes(bufs, bytes_recvd);
+            let ret = if let Some(host_fd) = self.bridge_host_fd() {
+                self.recvv_from_bridge(&mut remaining_bufs[..], host_fd, flags)
+                    .map(|count| (count, Vec::new(), None, None, MsgHdrFlags::empty()))
+            } else if let Some(relay) = self.peer_relay() {
+                self.recvv_via_relay(&mut remaining_bufs[..], flags, &relay)
+            } else {
+                let mut inner = self.inner.lock().unwrap();
+                if bytes_recvd > 0 && inner.is_sender_changed() {
+                    break;
+                }
+                inner.readv_with_files(&mut remaining_bufs[..], flags)
+            };
+            match ret {
+                Ok((count, new_files, new_cred, new_timestamp, new_msg_flags)) => {
+                    bytes_recvd += count;
+                    files.extend(new_files);
+                    cred = cred.or(new_cred);
+                    timestamp = timestamp.or(new_timestamp);
+                    msg_flags |= new_msg_flags;
+                    if !waitall || count == 0 || bytes_recvd == total_len || !files.is_empty() {
+                        break;
+                    }
+                }
+                Err(_) if bytes_recvd > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        if !flags.contains(RecvFlags::MSG_PEEK) {
+            self.stats.account_recvd(bytes_recvd);
+        }
+        Ok((bytes_recvd, files, cred, timestamp, msg_flags))
+    }
+
+    /// Receive the data from the bridge, where the socket is unlocked while waiting
+    /// for the data, so that the requests can be sent by other threads meanwhile
+    fn recvv_from_bridge(
+        &self,
+        bufs: &mut [&mut [u8]],
+        host_fd: c_int,
+        flags: RecvFlags,
+    ) -> Result<usize> {
+        let deadline = Deadline::after(self.inner.lock().unwrap().rcvtimeo);
+        loop {
+            let nonblocking = {
+                let mut inner = self.inner.lock().unwrap();
+                match inner.readv_with_files(bufs, flags) {
+                    Err(e) if e.errno() == EAGAIN => {
+                        inner.is_nonblocking() || flags.contains(RecvFlags::MSG_DONTWAIT)
+                    }
+                    ret => return ret.map(|(count, _files, _cred, _msg_flags)| count),
+                }
+            };
+            if nonblocking {
+                return_errno!(EAGAIN, "no data to receive from the bridge");
+            }
+            let timeout = deadline.remaining()?;
+            if let Err(e) = wait_host_fd(host_fd as FileDesc, PollEventFlags::POLLIN, timeout) {
+                if e.errno() != EINTR {
+                    return Err(e);
+                }
+            }
+            if has_deliverable_signals() {
+                return_errno!(EINTR, "interrupted by signals");
+            }
+        }
+    }
+
+    /// Receive the data from the peer that is relayed to the host, where the socket
+    /// is unlocked while waiting for the relay to pump the data from the host
+    fn recvv_via_relay(
+        &self,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+        relay: &Arc<UnixRelay>,
+    ) -> Result<(
+        usize,
+        Vec<FileRef>,
+        Option<ucred>,
+        Option<(TimestampFormat, Duration)>,
+        MsgHdrFlags,
+    )> {
+        let deadline = Deadline::after(self.inner.lock().unwrap().rcvtimeo);
+        loop {
+            relay.pump();
+            let nonblocking = {
+                let mut inner = self.inner.lock().unwrap();
+                match inner.readv_with_files(bufs, flags | RecvFlags::MSG_DONTWAIT) {
+                    Err(e) if e.errno() == EAGAIN => {
+                        inner.is_nonblocking() || flags.contains(RecvFlags::MSG_DONTWAIT)
+                    }
+                    ret => return ret,
+                }
+            };
+            if nonblocking {
+                return_errno!(EAGAIN, "no data to receive from the relay");
+            }
+            let timeout = deadline.remaining()?;
+            if let Err(e) = relay.wait(PollEventFlags::POLLIN, timeout) {
+                if e.errno() != EINTR {
+                    return Err(e);
+                }
+            }
+            if has_deliverable_signals() {
+                return_errno!(EINTR, "interrupted by signals");
+            }
+        }
+    }
+
+    /// Send the data to the peer that is relayed to the host, where the socket is
+    /// unlocked while waiting for the relay to pump the data to the host
+    fn sendv_via_relay(
+        &self,
+        bufs: &[&[u8]],
+        files: Vec<FileRef>,
+        flags: SendFlags,
+        relay: &Arc<UnixRelay>,
+    ) -> Result<usize> {
+        let data = bufs.concat();
+        let deadline = Deadline::after(self.inner.lock().unwrap().sndtimeo);
+        let mut bytes_sent = 0;
+        loop {
+            let (ret, nonblocking) = {
+                let mut inner = self.inner.lock().unwrap();
+                // The files are sent along with the first byte
+                let files = if bytes_sent == 0 {
+                    files.clone()
+                } else {
+                    Vec::new()
+                };
+                let ret = inner.writev_with_files_nonblocking(&[&data[bytes_sent..]], files);
+                let nonblocking = inner.is_nonblocking() || flags.contains(SendFlags::MSG_DONTWAIT);
+                (ret, nonblocking)
+            };
+            match ret {
+                Ok(count) => bytes_sent += count,
+                Err(e) if e.errno() == EAGAIN => {}
+                Err(_) if bytes_sent > 0 => break,
+                Err(e) => return Err(e),
+            }
+            relay.pump();
+            if bytes_sent == data.len() || (nonblocking && bytes_sent > 0) {
+                break;
+            }
+            if nonblocking {
+                return_errno!(EAGAIN, "no space to send to the relay");
+            }
+            let timeout = match deadline.remaining() {
+                Ok(timeout) => timeout,
+                Err(_) if bytes_sent > 0 => break,
+                Err(e) => return Err(e),
+            };
+            if let Err(e) = relay.wait(PollEventFlags::POLLOUT, timeout) {
+                if e.errno() != EINTR {
+                    return Err(e);
+                }
+            }
+            if has_deliverable_signals() {
+                if bytes_sent > 0 {
+                    break;
+                }
+                return_errno!(EINTR, "interrupted by signals");
+            }
+        }
+        Ok(bytes_sent)
+    }
+
+    /// The relay of the peer, if the socket is an end of a socketpair whose other
+    /// end is relayed to the host
+    fn peer_relay(&self) -> Option<Arc<UnixRelay>> {
+        let inner = self.inner.lock().unwrap();
+        let slot = inner.pair_slot.as_ref()?;
+        let relay = slot.lock().unwrap().clone();
+        relay
+    }
+
+    /// The host fd of the bridge, if the socket is connected to a bridge
+    pub fn bridge_host_fd(&self) -> Option<c_int> {
+        let inner = self.inner.lock().unwrap();
+        inner.bridge.as_ref().map(|bridge| bridge.host_fd())
+    }
+
     pub fn socketpair(socket_type: i32, protocol: i32) -> Result<(Self, Self)> {
         let listen_socket = Self::new(socket_type, protocol)?;
         let bound_path = listen_socket.bind_until_success();
-        listen_socket.listen()?;
+        listen_socket.listen(1)?;
 
         let client_socket = Self::new(socket_type, protocol)?;
         client_socket.connect(&bound_path)?;
+        // Like Linux, the sockets created by socketpair are unnamed
+        client_socket.inner.lock().unwrap().peer_name = None;
 
         let accepted_socket = listen_socket.accept()?;
+        let nonblocking = socket_type & libc::SOCK_NONBLOCK != 0;
+        accepted_socket
+            .inner
+            .lock()
+            .unwrap()
+            .set_nonblocking(nonblocking);
+        // Either end may be relayed to the host later, which is found by the other
+        // end through the slot
+        let slot: RelaySlot = Arc::new(Mutex::new(None));
+        client_socket.inner.lock().unwrap().pair_slot = Some(slot.clone());
+        accepted_socket.inner.lock().unwrap().pair_slot = Some(slot);
         Ok((client_socket, accepted_socket))
     }
 
@@ -133,10 +524,11 @@ impl UnixSocketFile {
     }
 
     pub fn is_connected(&self) -> bool {
-        if let Status::Connected(_) = self.inner.lock().unwrap().status {
+        let inner = self.inner.lock().unwrap();
+        if let Status::Connected(_) = inner.status {
             true
         } else {
-            false
+            inner.bridge.is_some()
         }
     }
 }
@@ -147,6 +539,43 @@ impl Debug for UnixSocketFile {
     }
 }
 
+impl Socket for UnixSocketFile {
+    fn bind(&self, addr: &[u8]) -> Result<()> {
+        // The path may be shorter than sun_path, whose remaining part is zeros
+        let mut sockaddr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        let len = min(addr.len(), std::mem::size_of::<libc::sockaddr_un>());
+        unsafe {
+            std::ptr::copy_nonoverlapping(addr.as_ptr(), &mut sockaddr as *mut _ as *mut u8, len);
+        }
+        UnixSocketFile::bind(self, sun_path_of(&sockaddr))
+    }
+
+    fn listen(&self, backlog: c_int) -> Result<()> {
+        UnixSocketFile::listen(self, backlog)
+    }
+
+    fn shutdown(&self, how: c_int) -> Result<()> {
+        UnixSocketFile::shutdown(self, how)
+    }
+
+    fn addr(&self) -> Result<Vec<u8>> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner.addr())
+    }
+
+    fn peer_addr(&self) -> Result<Vec<u8>> {
+        UnixSocketFile::peer_addr(self)
+    }
+
+    fn setsockopt(&self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        UnixSocketFile::setsockopt(self, level, optname, optval)
+    }
+
+    fn getsockopt(&self, level: c_int, optname: c_int, _max_len: usize) -> Result<Vec<u8>> {
+        UnixSocketFile::getsockopt(self, level, optname)
+    }
+}
+
 pub trait AsUnixSocket {
     fn as_unix_socket(&self) -> Result<&UnixSocketFile>;
 }
@@ -160,8 +589,71 @@ impl AsUnixSocket for FileRef {
 }
 
 pub struct UnixSocket {
+    // SOCK_STREAM or SOCK_SEQPACKET, where the boundaries of the messages are
+    // kept by the latter
+    socket_type: c_int,
     obj: Option<Arc<UnixSocketObject>>,
+    // If the object is bound by this socket, rather than by the listening socket
+    // that accepts this socket
+    is_bound: bool,
     status: Status,
+    // If SO_REUSEPORT is set
+    reuse_port: bool,
+    // The sizes given by SO_SNDBUF and SO_RCVBUF
+    sndbuf: usize,
+    rcvbuf: usize,
+    // If SO_PASSCRED is set
+    pass_cred: bool,
+    // The format of the receive timestamps, if SO_TIMESTAMP or SO_TIMESTAMPNS is set
+    timestamp: Option<TimestampFormat>,
+    // The process of the peer, which is recorded when connected
+    peer_pid: Option<pid_t>,
+    // The name of the peer, which is None if the peer is unnamed
+    peer_name: Option<String>,
+    // If O_NONBLOCK is set
+    nonblocking: bool,
+    // The timeouts given by SO_RCVTIMEO and SO_SNDTIMEO, which are infinite if None
+    rcvtimeo: Option<Duration>,
+    sndtimeo: Option<Duration>,
+    // The bridge to the host if connected to an abstract name of a bridge
+    bridge: Option<DbusBridge>,
+    // The slot of the relay shared by the ends, if created by socketpair
+    pair_slot: Option<RelaySlot>,
+    // The path of the relay that the socket is handed over to, if any
+    relay_path: Option<String>,
+}
+
+/// Encode the name of a unix socket in sockaddr_un, where an abstract name is
+/// given with a leading '@'. The length is that of the used part, like Linux.
+fn sockaddr_un_of(name: Option<&str>) -> Vec<u8> {
+    let mut addr = (libc::AF_UNIX as libc::sa_family_t).to_ne_bytes().to_vec();
+    let sun_path_len = std::mem::size_of::<libc::sockaddr_un>() - addr.len();
+    let sun_path = match name {
+        None => return addr,
+        Some(name) if name.starts_with('@') => {
+            let mut sun_path = vec![0];
+            sun_path.extend_from_slice(&name.as_bytes()[1..]);
+            sun_path
+        }
+        Some(name) => {
+            let mut sun_path = name.as_bytes().to_vec();
+            sun_path.push(0);
+            sun_path
+        }
+    };
+    addr.extend_from_slice(&sun_path[..min(sun_path.len(), sun_path_len)]);
+    addr
+}
+
+/// The parts of `bufs` after the first `offset` bytes
+fn skip_bytes<'a>(bufs: &'a mut [&mut [u8]], mut offset: usize) -> IovVec<&'a mut [u8]> {
+    bufs.iter_mut()
+        .map(|buf| {
+            let skipped = min(buf.len(), offset);
+            offset -= skipped;
+            &mut buf[skipped..]
+        })
+        .collect()
 }
 
 enum Status {
@@ -173,10 +665,27 @@ enum Status {
 impl UnixSocket {
     /// C/S 1: Create a new unix socket
     pub fn new(socket_type: c_int, protocol: c_int) -> Result<Self> {
-        if socket_type == libc::SOCK_STREAM && (protocol == 0 || protocol == libc::PF_UNIX) {
+        let is_type_supported =
+            socket_type == libc::SOCK_STREAM || socket_type == libc::SOCK_SEQPACKET;
+        if is_type_supported && (protocol == 0 || protocol == libc::PF_UNIX) {
             Ok(UnixSocket {
+                socket_type,
                 obj: None,
+                is_bound: false,
                 status: Status::None,
+                reuse_port: false,
+                sndbuf: DEFAULT_BUF_SIZE,
+                rcvbuf: DEFAULT_BUF_SIZE,
+                pass_cred: false,
+                timestamp: None,
+                peer_pid: None,
+                peer_name: None,
+                nonblocking: false,
+                rcvtimeo: None,
+                sndtimeo: None,
+                bridge: None,
+                pair_slot: None,
+                relay_path: None,
             })
         } else {
             // Return different error numbers according to input
@@ -190,66 +699,398 @@ impl UnixSocket {
         if self.obj.is_some() {
             return_errno!(EINVAL, "The socket is already bound to an address.");
         }
-        self.obj = Some(UnixSocketObject::create(path)?);
+        self.obj = Some(UnixSocketObject::create(
+            path,
+            self.reuse_port,
+            self.socket_type,
+        )?);
+        self.is_bound = true;
+        Ok(())
+    }
+
+    /// Server 2: Hand the end of a socketpair over to the relay, which listens on
+    /// the host path of the relay, and relays the data of the other end
+    pub fn bind_relay(&mut self, policy: &'static ConfigUnixRelay) -> Result<()> {
+        if self.is_bound || self.relay_path.is_some() {
+            return_errno!(EINVAL, "The socket is already bound to an address.");
+        }
+        let slot = match (&self.status, &self.pair_slot) {
+            (Status::Connected(_), Some(slot)) => slot.clone(),
+            _ => return_errno!(EOPNOTSUPP, "only the ends of socketpairs can be relayed"),
+        };
+        if self.socket_type != libc::SOCK_STREAM {
+            return_errno!(EPROTOTYPE, "the relay only accepts stream sockets");
+        }
+        let mut slot_guard = slot.lock().unwrap();
+        if slot_guard.is_some() {
+            return_errno!(EINVAL, "the peer is relayed already");
+        }
+        let listen_host_fd = UnixRelay::listen_on_host(policy)?;
+        // The socket is left unconnected, with the path of the relay as its name
+        let mut unconnected = UnixSocket::new(self.socket_type, 0)?;
+        unconnected.relay_path = Some(policy.path.clone());
+        let end = std::mem::replace(self, unconnected);
+        *slot_guard = Some(UnixRelay::new(policy, slot.clone(), listen_host_fd, end));
         Ok(())
     }
 
-    /// Server 3: Listen to a socket
-    pub fn listen(&mut self) -> Result<()> {
+    pub fn setsockopt(&mut self, level: c_int, optname: c_int, optval: &[u8]) -> Result<()> {
+        match (level, optname) {
+            (libc::SOL_SOCKET, SO_REUSEPORT) => {
+                if optval.len() < std::mem::size_of::<c_int>() {
+                    return_errno!(EINVAL, "optlen is too small");
+                }
+                // Like Linux, the option only takes effect on bind
+                self.reuse_port = optval.iter().any(|&b| b != 0);
+            }
+            (libc::SOL_SOCKET, SO_SNDBUF) => {
+                self.sndbuf = buf_size_optval(optval, MIN_SNDBUF)?;
+                // The ring to the peer is resized once it is empty, if not now
+                if let Status::Connected(channel) = &mut self.status {
+                    channel.writer.resize(self.sndbuf)?;
+                }
+            }
+            (libc::SOL_SOCKET, SO_RCVBUF) => {
+                // Like Linux, the size is only reported, as the data received is
+                // limited by the send buffer of the peer
+                self.rcvbuf = buf_size_optval(optval, MIN_RCVBUF)?;
+            }
+            (libc::SOL_SOCKET, SO_PASSCRED) => {
+                // The credentials of the sender are received by SCM_CREDENTIALS
+                self.pass_cred = int_optval(optval)? != 0;
+            }
+            (libc::SOL_SOCKET, SO_TIMESTAMP) | (libc::SOL_SOCKET, SO_TIMESTAMPNS) => {
+                // Like Linux, disabling either option disables the timestamps
+                self.timestamp = if int_optval(optval)? != 0 {
+                    TimestampFormat::from_sockopt(optname)
+                } else {
+                    None
+                };
+                if let Status::Connected(channel) = &self.status {
+                    channel.set_stamped(self.timestamp.is_some());
+                }
+            }
+            (libc::SOL_SOCKET, SO_RCVTIMEO) => {
+                self.rcvtimeo = timeout_optval(optval)?;
+                if let Status::Connected(channel) = &self.status {
+                    channel.set_timeouts(self.rcvtimeo, self.sndtimeo);
+                }
+            }
+            (libc::SOL_SOCKET, SO_SNDTIMEO) => {
+                self.sndtimeo = timeout_optval(optval)?;
+                if let Status::Connected(channel) = &self.status {
+                    channel.set_timeouts(self.rcvtimeo, self.sndtimeo);
+                }
+            }
+            _ => warn!("setsockopt for unix socket is unimplemented"),
+        }
+        Ok(())
+    }
+
+    pub fn getsockopt(&self, level: c_int, optname: c_int) -> Result<Vec<u8>> {
+        let val = match (level, optname) {
+            (libc::SOL_SOCKET, SO_TYPE) => self.socket_type,
+            (libc::SOL_SOCKET, SO_DOMAIN) => libc::AF_UNIX,
+            (libc::SOL_SOCKET, SO_PROTOCOL) => 0,
+            (libc::SOL_SOCKET, SO_REUSEPORT) => self.reuse_port as c_int,
+            (libc::SOL_SOCKET, SO_SNDBUF) => self.sndbuf as c_int,
+            (libc::SOL_SOCKET, SO_RCVBUF) => self.rcvbuf as c_int,
+            (libc::SOL_SOCKET, SO_PASSCRED) => self.pass_cred as c_int,
+            (libc::SOL_SOCKET, SO_TIMESTAMP) | (libc::SOL_SOCKET, SO_TIMESTAMPNS) => {
+                (self.timestamp == TimestampFormat::from_sockopt(optname)) as c_int
+            }
+            (libc::SOL_SOCKET, SO_RCVTIMEO) => return Ok(timeout_to_optval(self.rcvtimeo)),
+            (libc::SOL_SOCKET, SO_SNDTIMEO) => return Ok(timeout_to_optval(self.sndtimeo)),
+            (libc::SOL_SOCKET, SO_PEERCRED) => {
+                // Like Linux, the pid is 0 and the ids are -1 if not connected. The
+                // processes in the enclave are all run as root.
+                let cred = match self.peer_pid {
+                    Some(pid) => ucred {
+                        pid,
+                        uid: 0,
+                        gid: 0,
+                    },
+                    None => ucred {
+                        pid: 0,
+                        uid: u32::max_value(),
+                        gid: u32::max_value(),
+                    },
+                };
+                let cred_bytes = unsafe {
+                    std::slice::from_raw_parts(
+                        &cred as *const ucred as *const u8,
+                        std::mem::size_of::<ucred>(),
+                    )
+                };
+                return Ok(cred_bytes.to_vec());
+            }
+            _ => return_errno!(ENOPROTOOPT, "unknown socket option for unix socket"),
+        };
+        Ok(val.to_ne_bytes().to_vec())
+    }
+
+    /// Server 3: Listen to a socket, with the max number of pending connections
+    pub fn listen(&mut self, backlog: c_int) -> Result<()> {
+        let obj = self
+            .obj
+            .as_ref()
+            .ok_or_else(|| errno!(EINVAL, "unix socket is not bound"))?;
+        // Like Linux, a negative or too large backlog is capped by SOMAXCONN, and
+        // listening again only updates the backlog
+        obj.set_backlog(min(backlog as u32 as usize, SOMAXCONN));
         self.status = Status::Listening;
         Ok(())
     }
 
-    /// Server 4: Accept a connection on listening.
-    pub fn accept(&mut self) -> Result<UnixSocket> {
+    /// Server 4: Get the object of the listening socket, from which the connections
+    /// are accepted
+    fn listening_obj(&self) -> Result<Arc<UnixSocketObject>> {
         match self.status {
             Status::Listening => {}
             _ => return_errno!(EINVAL, "unix socket is not listening"),
         };
-        // FIXME: Block. Now spin loop.
-        let socket = loop {
-            if let Some(socket) = self.obj.as_mut().unwrap().pop() {
-                break socket;
+        Ok(self.obj.as_ref().unwrap().clone())
+    }
+
+    /// Server 5: Shut down the socket. A listening socket refuses the new connections
+    /// and releases its path at once, so the path can be bound again even if the
+    /// socket is still open, e.g., in other processes.
+    pub fn shutdown(&mut self, how: c_int) -> Result<()> {
+        if how != libc::SHUT_RD && how != libc::SHUT_WR && how != libc::SHUT_RDWR {
+            return_errno!(EINVAL, "invalid how of shutdown");
+        }
+        if self.bridge.is_some() {
+            return_errno!(EOPNOTSUPP, "the bridge cannot be shut down");
+        }
+        match self.status {
+            Status::Listening => {
+                // Like Linux, only the shutdown of receiving refuses the connections
+                if how != libc::SHUT_WR {
+                    self.release_obj();
+                }
+                Ok(())
             }
-            spin_loop_hint();
-        };
-        Ok(socket)
+            Status::Connected(_) => {
+                return_errno!(EOPNOTSUPP, "shutdown of a connected unix socket")
+            }
+            Status::None => return_errno!(ENOTCONN, "unix socket is not connected"),
+        }
+    }
+
+    /// Release the path bound by this socket, and refuse the new connections
+    fn release_obj(&self) {
+        if !self.is_bound {
+            return;
+        }
+        if let Some(obj) = self.obj.as_ref() {
+            UnixSocketObject::remove(obj);
+            obj.close();
+        }
     }
 
-    /// Client 2: Connect to a path
+    /// Client 2: Connect to a path.
+    ///
+    /// If the backlog of the listening socket is full, it fails with EAGAIN if
+    /// non-blocking, or waits until a pending connection is accepted.
     pub fn connect(&mut self, path: impl AsRef<str>) -> Result<()> {
         if let Status::Listening = self.status {
             return_errno!(EINVAL, "unix socket is listening?");
         }
-        let obj = UnixSocketObject::get(path)
+        let obj = UnixSocketObject::get(path.as_ref())
             .ok_or_else(|| errno!(EINVAL, "unix socket path not found"))?;
+        if obj.socket_type != self.socket_type {
+            return_errno!(EPROTOTYPE, "the type of the listening socket differs");
+        }
         // TODO: Mov the buffer allocation to function new to comply with the bahavior of unix
-        let (channel1, channel2) = Channel::new_pair()?;
+        let is_seqpacket = self.socket_type == libc::SOCK_SEQPACKET;
+        let (channel1, channel2) = Channel::new_pair(self.sndbuf, DEFAULT_BUF_SIZE, is_seqpacket)?;
+        channel1.set_nonblocking(self.nonblocking);
+        channel1.set_timeouts(self.rcvtimeo, self.sndtimeo);
+        channel1.set_stamped(self.timestamp.is_some());
+        obj.push(
+            UnixSocket {
+                socket_type: self.socket_type,
+                obj: Some(obj.clone()),
+                is_bound: false,
+                status: Status::Connected(channel2),
+                reuse_port: false,
+                sndbuf: DEFAULT_BUF_SIZE,
+                rcvbuf: DEFAULT_BUF_SIZE,
+                pass_cred: false,
+                timestamp: None,
+                peer_pid: Some(current!().process().pid()),
+                peer_name: self.obj.as_ref().map(|obj| obj.path.clone()),
+                nonblocking: false,
+                rcvtimeo: None,
+                sndtimeo: None,
+                bridge: None,
+                pair_slot: None,
+                relay_path: None,
+            },
+            self.nonblocking,
+        )?;
         self.status = Status::Connected(channel1);
-        obj.push(UnixSocket {
-            obj: Some(obj.clone()),
-            status: Status::Connected(channel2),
-        });
+        // Like Linux, the peer of the client is the process that creates the server
+        self.peer_pid = Some(obj.pid);
+        self.peer_name = Some(path.as_ref().to_string());
         Ok(())
     }
 
+    /// Client 2: Connect to the unix socket of the host given by the bridge
+    pub fn connect_bridge(&mut self, name: &str, policy: &'static ConfigDbusBridge) -> Result<()> {
+        match self.status {
+            Status::Listening => return_errno!(EINVAL, "unix socket is listening"),
+            Status::Connected(_) => return_errno!(EISCONN, "unix socket is connected"),
+            Status::None => {}
+        }
+        if self.bridge.is_some() {
+            return_errno!(EISCONN, "unix socket is connected");
+        }
+        if self.socket_type != libc::SOCK_STREAM {
+            return_errno!(EPROTOTYPE, "the bridge only accepts stream sockets");
+        }
+        self.bridge = Some(DbusBridge::connect(policy)?);
+        self.peer_name = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Get the address of the socket in the form of sockaddr_un, which is unnamed
+    /// unless the socket binds a path by itself
+    pub fn addr(&self) -> Vec<u8> {
+        let path = match self.obj.as_ref() {
+            Some(obj) if self.is_bound => Some(obj.path.as_str()),
+            _ => self.relay_path.as_deref(),
+        };
+        sockaddr_un_of(path)
+    }
+
+    pub fn peer_addr(&self) -> Result<Vec<u8>> {
+        match self.status {
+            Status::Connected(_) => {}
+            _ if self.bridge.is_some() => {}
+            _ => return_errno!(ENOTCONN, "unix socket is not connected"),
+        }
+        Ok(sockaddr_un_of(self.peer_name.as_deref()))
+    }
+
+    pub fn is_nonblocking(&self) -> bool {
+        self.nonblocking
+    }
+
+    pub fn set_nonblocking(&mut self, nonblocking: bool) {
+        self.nonblocking = nonblocking;
+        if let Status::Connected(channel) = &self.status {
+            channel.set_nonblocking(nonblocking);
+        }
+    }
+
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.channel_mut()?.reader.read_from_buffer(buf)
+        self.readv(&mut [buf])
     }
 
     pub fn readv(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize> {
-        self.channel_mut()?.reader.read_from_vector(bufs)
+        self.readv_with_files(bufs, RecvFlags::empty())
+            .map(|(count, _files, _cred, _timestamp, _msg_flags)| count)
+    }
+
+    pub fn readv_with_files(
+        &mut self,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+    ) -> Result<(
+        usize,
+        Vec<FileRef>,
+        Option<ucred>,
+        Option<(TimestampFormat, Duration)>,
+        MsgHdrFlags,
+    )> {
+        if let Some(bridge) = &mut self.bridge {
+            if flags.contains(RecvFlags::MSG_PEEK) {
+                return_errno!(EOPNOTSUPP, "MSG_PEEK is not supported by the bridge");
+            }
+            return bridge
+                .readv(bufs)
+                .map(|count| (count, Vec::new(), None, None, MsgHdrFlags::empty()));
+        }
+        // Like Linux, receiving from a socket that is not connected is invalid
+        let channel = match &mut self.status {
+            Status::Connected(channel) => channel,
+            _ => return_errno!(EINVAL, "UnixSocket is not connected"),
+        };
+        let (count, files, sender_pid, sent_time, msg_flags) =
+            channel.read_with_files(bufs, flags)?;
+        if count == 0 && bufs.iter().any(|buf| buf.len() > 0) {
+            // The peer is closed
+            channel.check_conn_reset()?;
+        }
+        // The processes in the enclave are all run as root
+        let cred = sender_pid.filter(|_| self.pass_cred).map(|pid| ucred {
+            pid,
+            uid: 0,
+            gid: 0,
+        });
+        // Like Linux, the data sent before the timestamps are enabled is stamped
+        // when it is received
+        let timestamp = match (self.timestamp, sender_pid) {
+            (Some(format), Some(_)) => Some((format, sent_time.unwrap_or_else(realtime_now))),
+            _ => None,
+        };
+        Ok((count, files, cred, timestamp, msg_flags))
+    }
+
+    /// If the next data to read is sent by another process than the data last read,
+    /// which is received separately if SO_PASSCRED is set
+    fn is_sender_changed(&self) -> bool {
+        match &self.status {
+            Status::Connected(channel) if self.pass_cred => channel.is_sender_changed(),
+            _ => false,
+        }
     }
 
     pub fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        self.channel_mut()?.writer.write_to_buffer(buf)
+        self.writev(&[buf])
     }
 
     pub fn writev(&mut self, bufs: &[&[u8]]) -> Result<usize> {
-        self.channel_mut()?.writer.write_to_vector(bufs)
+        self.writev_with_files(bufs, Vec::new())
+    }
+
+    /// Write the data without blocking, regardless of O_NONBLOCK
+    fn writev_with_files_nonblocking(
+        &mut self,
+        bufs: &[&[u8]],
+        files: Vec<FileRef>,
+    ) -> Result<usize> {
+        self.channel()?.set_nonblocking(true);
+        let ret = self.writev_with_files(bufs, files);
+        self.channel()?.set_nonblocking(self.nonblocking);
+        ret
+    }
+
+    pub fn writev_with_files(&mut self, bufs: &[&[u8]], files: Vec<FileRef>) -> Result<usize> {
+        if let Some(bridge) = &mut self.bridge {
+            if !files.is_empty() {
+                return_errno!(EOPNOTSUPP, "the files cannot be passed to the host");
+            }
+            return bridge.writev(bufs);
+        }
+        let sndbuf = self.sndbuf;
+        let channel = self.channel_mut()?;
+        // Apply the SO_SNDBUF that is set while the ring is not empty
+        if channel.writer.capacity() != sndbuf {
+            channel.writer.resize(sndbuf)?;
+        }
+        channel.write_with_files(bufs, files)
     }
 
     fn poll(&self) -> Result<PollEventFlags> {
+        if let Some(bridge) = &self.bridge {
+            let events = PollEventFlags::POLLIN | PollEventFlags::POLLOUT;
+            return poll_host_fd(bridge.host_fd() as FileDesc, events);
+        }
+        if let Status::Listening = self.status {
+            return Ok(self.obj.as_ref().unwrap().poll());
+        }
         let channel_result = self.channel();
         if let Ok(channel) = channel_result {
             let readable = channel.reader.can_read() && !channel.reader.is_peer_closed();
@@ -277,15 +1118,64 @@ impl UnixSocket {
         }
     }
 
+    /// Register the current thread to be notified when the socket may be ready for
+    /// the event
+    pub fn enqueue_event(&self, event: IoEvent) -> Result<()> {
+        match &self.status {
+            Status::Connected(channel) => {
+                channel.reader.enqueue_event(event)?;
+                channel.writer.enqueue_event(event)
+            }
+            Status::Listening => {
+                self.obj.as_ref().unwrap().add_poller(current!().tid());
+                Ok(())
+            }
+            // The unconnected socket is always ready with POLLHUP
+            Status::None => Ok(()),
+        }
+    }
+
+    pub fn dequeue_event(&self) -> Result<()> {
+        match &self.status {
+            Status::Connected(channel) => {
+                channel.reader.dequeue_event()?;
+                channel.writer.dequeue_event()
+            }
+            Status::Listening => {
+                self.obj.as_ref().unwrap().remove_poller(current!().tid());
+                Ok(())
+            }
+            Status::None => Ok(()),
+        }
+    }
+
+    pub fn poll_seq(&self) -> usize {
+        match &self.status {
+            Status::Connected(channel) => channel
+                .reader
+                .poll_seq()
+                .wrapping_add(channel.writer.poll_seq()),
+            Status::Listening => self.obj.as_ref().unwrap().connections(),
+            Status::None => 0,
+        }
+    }
+
     pub fn ioctl(&self, cmd: &mut IoctlCmd) -> Result<i32> {
         match cmd {
             IoctlCmd::FIONREAD(arg) => {
-                let bytes_to_read = self
-                    .channel()?
-                    .reader
-                    .bytes_to_read()
-                    .min(std::i32::MAX as usize) as i32;
-                **arg = bytes_to_read;
+                let bytes_to_read = match &self.status {
+                    Status::Connected(channel) => channel.bytes_to_read(),
+                    Status::Listening => return_errno!(EINVAL, "UnixSocket is listening"),
+                    Status::None => 0,
+                };
+                **arg = bytes_to_read.min(std::i32::MAX as usize) as i32;
+            }
+            IoctlCmd::TIOCOUTQ(arg) => {
+                let bytes_unread = match &self.status {
+                    Status::Connected(channel) => channel.writer.bytes_unread(),
+                    Status::Listening | Status::None => 0,
+                };
+                **arg = bytes_unread.min(std::i32::MAX as usize) as i32;
             }
             _ => return_errno!(EINVAL, "unknown ioctl cmd for unix socket"),
         }
@@ -296,7 +1186,7 @@ impl UnixSocket {
         if let Status::Connected(ref mut channel) = &mut self.status {
             Ok(channel)
         } else {
-            return_errno!(EBADF, "UnixSocket is not connected")
+            return_errno!(ENOTCONN, "UnixSocket is not connected")
         }
     }
 
@@ -304,87 +1194,635 @@ impl UnixSocket {
         if let Status::Connected(channel) = &self.status {
             Ok(channel)
         } else {
-            return_errno!(EBADF, "UnixSocket is not connected")
+            return_errno!(ENOTCONN, "UnixSocket is not connected")
         }
     }
 }
 
 impl Drop for UnixSocket {
     fn drop(&mut self) {
-        if let Status::Listening = self.status {
-            // Only remove the object when there is one
-            if let Some(obj) = self.obj.as_ref() {
-                UnixSocketObject::remove(&obj.path);
-            }
-        }
+        // The path is released even if the socket is not listening
+        self.release_obj();
     }
 }
 
 pub struct UnixSocketObject {
     path: String,
+    reuse_port: bool,
+    // The type of the socket that binds the path, which is the type of the
+    // connections
+    socket_type: c_int,
+    // The process that binds the socket
+    pid: pid_t,
+    // The max number of pending connections, which is given by listen
+    backlog: AtomicUsize,
+    // If the listening socket is closed
+    is_closed: AtomicBool,
     accepted_sockets: Mutex<VecDeque<UnixSocket>>,
+    // The threads blocked in accept, in the order that they start to wait
+    acceptors: Mutex<VecDeque<pid_t>>,
+    // The threads blocked in connect as the backlog is full
+    connectors: Mutex<VecDeque<pid_t>>,
+    // The threads polling the listening socket for the pending connections
+    pollers: Mutex<Vec<pid_t>>,
+    // The number of the connections ever pushed, which only increases
+    connections: AtomicUsize,
 }
 
 impl UnixSocketObject {
-    fn push(&self, unix_socket: UnixSocket) {
-        let mut queue = self.accepted_sockets.lock().unwrap();
-        queue.push_back(unix_socket);
+    fn push(&self, unix_socket: UnixSocket, nonblocking: bool) -> Result<()> {
+        loop {
+            if self.is_closed.load(Ordering::SeqCst) {
+                return_errno!(ECONNREFUSED, "the listening socket is closed");
+            }
+            if self.has_room() {
+                self.accepted_sockets.lock().unwrap().push_back(unix_socket);
+                self.connections.fetch_add(1, Ordering::SeqCst);
+                self.notify_pollers();
+                return wake_one(&self.acceptors);
+            }
+            if nonblocking {
+                return_errno!(EAGAIN, "the backlog of the listening socket is full");
+            }
+            wait_until(&self.connectors, || {
+                self.has_room() || self.is_closed.load(Ordering::SeqCst)
+            })?;
+        }
+    }
+    /// Like Linux, the backlog is full if there are more pending connections than it.
+    ///
+    /// The connections may exceed the backlog slightly if the threads connect at
+    /// the same time, which is harmless.
+    fn has_room(&self) -> bool {
+        self.accepted_sockets.lock().unwrap().len() <= self.backlog.load(Ordering::Relaxed)
+    }
+    fn set_backlog(&self, backlog: usize) {
+        self.backlog.store(backlog, Ordering::Relaxed);
     }
     fn pop(&self) -> Option<UnixSocket> {
         let mut queue = self.accepted_sockets.lock().unwrap();
         queue.pop_front()
     }
+    fn accept(&self, nonblocking: bool) -> Result<UnixSocket> {
+        loop {
+            if let Some(socket) = self.pop() {
+                // Make room for the thread blocked by the full backlog
+                wake_one(&self.connectors)?;
+                return Ok(socket);
+            }
+            if nonblocking {
+                return_errno!(EAGAIN, "no pending connection to accept");
+            }
+            wait_until(&self.acceptors, || {
+                !self.accepted_sockets.lock().unwrap().is_empty()
+            })?;
+        }
+    }
+    fn poll(&self) -> PollEventFlags {
+        if self.accepted_sockets.lock().unwrap().is_empty() {
+            PollEventFlags::empty()
+        } else {
+            PollEventFlags::POLLIN | PollEventFlags::POLLRDNORM
+        }
+    }
+    fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+    fn add_poller(&self, tid: pid_t) {
+        let mut pollers = self.pollers.lock().unwrap();
+        if !pollers.contains(&tid) {
+            pollers.push(tid);
+        }
+    }
+    fn remove_poller(&self, tid: pid_t) {
+        self.pollers.lock().unwrap().retain(|poller| *poller != tid);
+    }
+    /// Notify all the pollers, as the connection may be accepted by any of them
+    fn notify_pollers(&self) {
+        let current_tid = current!().tid();
+        for &tid in self.pollers.lock().unwrap().iter() {
+            if tid != current_tid {
+                notify_thread(tid).ok();
+            }
+        }
+    }
+    /// Refuse the new connections after the listening socket is closed
+    fn close(&self) {
+        self.is_closed.store(true, Ordering::SeqCst);
+        let connectors: Vec<pid_t> = self.connectors.lock().unwrap().drain(..).collect();
+        for tid in connectors {
+            // The thread may have exited
+            notify_thread(tid).ok();
+        }
+    }
+    /// Get the object bound to the path for a new connection.
+    ///
+    /// If multiple sockets are bound to the path with SO_REUSEPORT, the connections
+    /// are dispatched to them in turn.
     fn get(path: impl AsRef<str>) -> Option<Arc<Self>> {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        paths.get(path.as_ref()).map(|obj| obj.clone())
+        let shard = UNIX_SOCKET_OBJS.get_shard(path.as_ref()).read().unwrap();
+        let group = shard.get(path.as_ref())?;
+        let idx = group.next.fetch_add(1, Ordering::Relaxed) % group.objs.len();
+        Some(group.objs[idx].clone())
     }
-    fn create(path: impl AsRef<str>) -> Result<Arc<Self>> {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        if paths.contains_key(path.as_ref()) {
-            return_errno!(EADDRINUSE, "unix socket path already exists");
+    fn create(path: impl AsRef<str>, reuse_port: bool, socket_type: c_int) -> Result<Arc<Self>> {
+        let mut shard = UNIX_SOCKET_OBJS.get_shard(path.as_ref()).write().unwrap();
+        if let Some(group) = shard.get(path.as_ref()) {
+            // The path can be shared only if all of the sockets set SO_REUSEPORT
+            if !reuse_port || !group.objs.iter().all(|obj| obj.reuse_port) {
+                return_errno!(EADDRINUSE, "unix socket path already exists");
+            }
         }
         let obj = Arc::new(UnixSocketObject {
             path: path.as_ref().to_string(),
+            reuse_port,
+            socket_type,
+            pid: current!().process().pid(),
+            backlog: AtomicUsize::new(0),
+            is_closed: AtomicBool::new(false),
             accepted_sockets: Mutex::new(VecDeque::new()),
+            acceptors: Mutex::new(VecDeque::new()),
+            connectors: Mutex::new(VecDeque::new()),
+            pollers: Mutex::new(Vec::new()),
+            connections: AtomicUsize::new(0),
         });
-        paths.insert(path.as_ref().to_string(), obj.clone());
+        shard
+            .entry(path.as_ref().to_string())
+            .or_insert_with(UnixSocketObjectGroup::new)
+            .objs
+            .push(obj.clone());
         Ok(obj)
     }
-    fn remove(path: impl AsRef<str>) {
-        let mut paths = UNIX_SOCKET_OBJS.lock().unwrap();
-        paths.remove(path.as_ref());
+    fn remove(obj: &Arc<Self>) {
+        let mut shard = UNIX_SOCKET_OBJS.get_shard(&obj.path).write().unwrap();
+        // The path may have been bound by another socket after this one is removed
+        let is_empty = match shard.get_mut(&obj.path) {
+            Some(group) => {
+                group
+                    .objs
+                    .retain(|registered_obj| !Arc::ptr_eq(registered_obj, obj));
+                group.objs.is_empty()
+            }
+            None => false,
+        };
+        if is_empty {
+            shard.remove(&obj.path);
+        }
+    }
+}
+
+/// The registry of the unix socket objects, which are sharded by the hash of
+/// their paths. So binding, connecting or closing sockets on different paths
+/// rarely contends for the same lock.
+///
+/// A path is bound by a group of objects if SO_REUSEPORT is set.
+/// Wait in the queue until notified, unless the condition is met.
+fn wait_until(waiters: &Mutex<VecDeque<pid_t>>, is_met: impl Fn() -> bool) -> Result<()> {
+    let tid = current!().tid();
+    // Clear the status of notifier before enqueue
+    clear_notifier_status(tid)?;
+    waiters.lock().unwrap().push_back(tid);
+    // Check again in case the condition is met after the last check, whose
+    // notification would have been missed
+    if is_met() {
+        remove_waiter(waiters, tid);
+        return Ok(());
+    }
+    let ret = wait_for_notification();
+    // The waiter has been removed if it is notified
+    remove_waiter(waiters, tid);
+    if let Err(e) = ret {
+        // Pass the notification on, which may be for the condition
+        if is_met() {
+            wake_one(waiters)?;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Like Linux, only one waiter is woken up for a new connection (or the room for
+/// one), which is the one that has waited for the longest time. So the connections
+/// are distributed fairly among the threads or processes sharing the socket.
+fn wake_one(waiters: &Mutex<VecDeque<pid_t>>) -> Result<()> {
+    let waiter = waiters.lock().unwrap().pop_front();
+    if let Some(tid) = waiter {
+        notify_thread(tid)?;
+    }
+    Ok(())
+}
+
+fn remove_waiter(waiters: &Mutex<VecDeque<pid_t>>, tid: pid_t) {
+    let mut waiters = waiters.lock().unwrap();
+    if let Some(pos) = waiters.iter().position(|t| *t == tid) {
+        waiters.remove(pos);
+    }
+}
+
+struct UnixSocketObjectShards {
+    shards: Vec<RwLock<HashMap<String, UnixSocketObjectGroup>>>,
+}
+
+impl UnixSocketObjectShards {
+    pub fn new(size: usize) -> Self {
+        debug_assert!(size.is_power_of_two());
+        let shards = (0..size).map(|_| RwLock::new(HashMap::new())).collect();
+        Self { shards }
+    }
+
+    pub fn get_shard(&self, path: &str) -> &RwLock<HashMap<String, UnixSocketObjectGroup>> {
+        let idx = {
+            let mut s = DefaultHasher::new();
+            path.hash(&mut s);
+            s.finish() as usize & (self.shards.len() - 1)
+        };
+        &self.shards[idx]
+    }
+}
+
+/// The objects bound to the same path
+struct UnixSocketObjectGroup {
+    objs: Vec<Arc<UnixSocketObject>>,
+    // The sequence number of the next connection, which decides the object
+    next: AtomicUsize,
+}
+
+impl UnixSocketObjectGroup {
+    fn new() -> Self {
+        Self {
+            objs: Vec::new(),
+            next: AtomicUsize::new(0),
+        }
     }
 }
 
 struct Channel {
     reader: RingBufReader,
     writer: RingBufWriter,
+    // If the data are written to the rings as records, one per message
+    is_seqpacket: bool,
+    // If ECONNRESET has been reported to the reader
+    is_reset_reported: bool,
+    // The numbers of bytes read from and written to the channel, which are the
+    // offsets of the next byte to read and write in the stream. The headers of
+    // the records are counted, too.
+    bytes_read: u64,
+    bytes_written: u64,
+    // The ancillary data sent by the peer and to the peer
+    incoming_ancillary: Arc<Mutex<VecDeque<AncillaryData>>>,
+    outgoing_ancillary: Arc<Mutex<VecDeque<AncillaryData>>>,
+    // The process that writes the data last written, and the one that writes the
+    // data last read
+    last_writer_pid: Option<pid_t>,
+    last_sender_pid: Option<pid_t>,
+    // If the data written to this side and to the peer are stamped, i.e., if
+    // SO_TIMESTAMP or SO_TIMESTAMPNS is set by the reader
+    incoming_stamped: Arc<AtomicBool>,
+    outgoing_stamped: Arc<AtomicBool>,
+    // The time when the data last read is sent, if stamped
+    last_sent_time: Option<Duration>,
+}
+
+/// The files passed by SCM_RIGHTS and the process of the sender, which are
+/// received along with the byte at the offset of the stream.
+///
+/// The ancillary data is sent along with the files, and whenever the data is
+/// written by another process. So the data between the offsets of two ancillary
+/// data is always from the same sender, whose credentials are received by
+/// SCM_CREDENTIALS. Such ancillary data is a boundary, where the read stops.
+///
+/// If the reader wants the timestamps, the ancillary data is also sent with every
+/// write to stamp the data written, which is not a boundary unless it is sent for
+/// the reasons above. The data read is stamped by its first byte.
+struct AncillaryData {
+    offset: u64,
+    files: Vec<FileRef>,
+    pid: pid_t,
+    sent_time: Option<Duration>,
+    is_boundary: bool,
 }
 
 unsafe impl Send for Channel {}
 unsafe impl Sync for Channel {}
 
 impl Channel {
-    fn new_pair() -> Result<(Channel, Channel)> {
-        let (reader1, writer1) = ring_buffer(DEFAULT_BUF_SIZE)?;
-        let (reader2, writer2) = ring_buffer(DEFAULT_BUF_SIZE)?;
+    /// Create the channels of both sides, with the sizes of the rings that they
+    /// write to
+    fn new_pair(
+        buf_size1: usize,
+        buf_size2: usize,
+        is_seqpacket: bool,
+    ) -> Result<(Channel, Channel)> {
+        let (reader1, writer1) = ring_buffer(buf_size2)?;
+        let (reader2, writer2) = ring_buffer(buf_size1)?;
+        let ancillary1 = Arc::new(Mutex::new(VecDeque::new()));
+        let ancillary2 = Arc::new(Mutex::new(VecDeque::new()));
+        let stamped1 = Arc::new(AtomicBool::new(false));
+        let stamped2 = Arc::new(AtomicBool::new(false));
         let channel1 = Channel {
             reader: reader1,
             writer: writer2,
+            is_seqpacket,
+            is_reset_reported: false,
+            bytes_read: 0,
+            bytes_written: 0,
+            incoming_ancillary: ancillary1.clone(),
+            outgoing_ancillary: ancillary2.clone(),
+            last_writer_pid: None,
+            last_sender_pid: None,
+            incoming_stamped: stamped1.clone(),
+            outgoing_stamped: stamped2.clone(),
+            last_sent_time: None,
         };
         let channel2 = Channel {
             reader: reader2,
             writer: writer1,
+            is_seqpacket,
+            is_reset_reported: false,
+            bytes_read: 0,
+            bytes_written: 0,
+            incoming_ancillary: ancillary2,
+            outgoing_ancillary: ancillary1,
+            last_writer_pid: None,
+            last_sender_pid: None,
+            incoming_stamped: stamped2,
+            outgoing_stamped: stamped1,
+            last_sent_time: None,
         };
         Ok((channel1, channel2))
     }
+
+    /// Read the data, along with the files sent with the data, the process of the
+    /// sender, which is None if no data is read, and the time when the data is
+    /// sent, if stamped.
+    ///
+    /// Like Linux, the read stops before the data that is sent with other files or
+    /// by another process, so the files are received along with the first byte of
+    /// the data that they are sent with. With MSG_PEEK, the data and the files are
+    /// left to be read again, and the files received are the copies.
+    fn read_with_files(
+        &mut self,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+    ) -> Result<(
+        usize,
+        Vec<FileRef>,
+        Option<pid_t>,
+        Option<Duration>,
+        MsgHdrFlags,
+    )> {
+        if self.is_seqpacket {
+            return self.read_record_with_files(bufs, flags);
+        }
+        let peek = flags.contains(RecvFlags::MSG_PEEK);
+        let nonblocking = flags.contains(RecvFlags::MSG_DONTWAIT);
+        let bytes_read = self.bytes_read;
+        let next_offset = self
+            .incoming_ancillary
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|ancillary| ancillary.is_boundary)
+            .map(|ancillary| ancillary.offset)
+            .find(|&offset| offset > bytes_read);
+        let count = match next_offset {
+            Some(next_offset) => {
+                let mut remaining = (next_offset - bytes_read) as usize;
+                let mut limited_bufs: IovVec<&mut [u8]> = bufs
+                    .iter_mut()
+                    .map(|buf| {
+                        let len = min(buf.len(), remaining);
+                        remaining -= len;
+                        &mut buf[..len]
+                    })
+                    .collect();
+                self.reader
+                    .read_from_vector_with(&mut limited_bufs[..], peek, nonblocking)?
+            }
+            None => self.reader.read_from_vector_with(bufs, peek, nonblocking)?,
+        };
+        if count == 0 {
+            return Ok((0, Vec::new(), None, None, MsgHdrFlags::empty()));
+        }
+        let (files, sender_pid, sent_time) =
+            self.receive_ancillary(bytes_read + count as u64, peek);
+        Ok((count, files, sender_pid, sent_time, MsgHdrFlags::empty()))
+    }
+
+    /// Read one record, i.e., one message, along with the files sent with it. The
+    /// part of the message beyond the buffers is discarded with MSG_TRUNC.
+    fn read_record_with_files(
+        &mut self,
+        bufs: &mut [&mut [u8]],
+        flags: RecvFlags,
+    ) -> Result<(
+        usize,
+        Vec<FileRef>,
+        Option<pid_t>,
+        Option<Duration>,
+        MsgHdrFlags,
+    )> {
+        let peek = flags.contains(RecvFlags::MSG_PEEK);
+        let nonblocking = flags.contains(RecvFlags::MSG_DONTWAIT);
+        let (count, len) = match self.reader.read_record(bufs, peek, nonblocking)? {
+            Some(ret) => ret,
+            // The peer is closed
+            None => return Ok((0, Vec::new(), None, None, MsgHdrFlags::empty())),
+        };
+        let record_end = self.bytes_read + (RECORD_HEADER_LEN + len) as u64;
+        let (files, sender_pid, sent_time) = self.receive_ancillary(record_end, peek);
+        let mut msg_flags = MsgHdrFlags::empty();
+        if count < len {
+            msg_flags |= MsgHdrFlags::MSG_TRUNC;
+        }
+        let count = if flags.contains(RecvFlags::MSG_TRUNC) {
+            len
+        } else {
+            count
+        };
+        Ok((count, files, sender_pid, sent_time, msg_flags))
+    }
+
+    /// Receive the ancillary data sent with the data before the offset `end` of the
+    /// stream, which is where the data read ends, and return the files, the process
+    /// of the sender of the data, and the time when the data is sent. With `peek`,
+    /// the files are left to be received again, and the copies are returned.
+    fn receive_ancillary(
+        &mut self,
+        end: u64,
+        peek: bool,
+    ) -> (Vec<FileRef>, Option<pid_t>, Option<Duration>) {
+        let mut incoming_ancillary = self.incoming_ancillary.lock().unwrap();
+        // The data read is stamped by the ancillary data of its first byte, if any,
+        // or by the one of the data last read
+        let sent_time = match incoming_ancillary.front() {
+            Some(ancillary) if ancillary.offset <= self.bytes_read => ancillary.sent_time,
+            _ => self.last_sent_time,
+        };
+        if peek {
+            let mut sender_pid = self.last_sender_pid;
+            let files = incoming_ancillary
+                .iter()
+                .take_while(|ancillary| ancillary.offset < end)
+                .flat_map(|ancillary| {
+                    sender_pid = Some(ancillary.pid);
+                    ancillary.files.iter().cloned()
+                })
+                .collect();
+            return (files, sender_pid, sent_time);
+        }
+        self.bytes_read = end;
+
+        // The ancillary data is queued before the data is written, so the ancillary
+        // data of the data read is all in the queue, even if the read blocked
+        let mut files = Vec::new();
+        while let Some(ancillary) = incoming_ancillary.front() {
+            if ancillary.offset >= self.bytes_read {
+                break;
+            }
+            let ancillary = incoming_ancillary.pop_front().unwrap();
+            files.extend(ancillary.files);
+            self.last_sender_pid = Some(ancillary.pid);
+            self.last_sent_time = ancillary.sent_time;
+        }
+        (files, self.last_sender_pid, sent_time)
+    }
+
+    /// If the next data to read is sent by another process than the data last read
+    fn is_sender_changed(&self) -> bool {
+        let incoming_ancillary = self.incoming_ancillary.lock().unwrap();
+        match incoming_ancillary.front() {
+            Some(ancillary) => {
+                ancillary.offset == self.bytes_read && Some(ancillary.pid) != self.last_sender_pid
+            }
+            None => false,
+        }
+    }
+
+    /// Write the data, along with the files which are received with its first byte.
+    /// The data is stamped with the time when it is written if the peer wants the
+    /// timestamps.
+    fn write_with_files(&mut self, bufs: &[&[u8]], files: Vec<FileRef>) -> Result<usize> {
+        let pid = current!().process().pid();
+        let is_boundary = !files.is_empty() || self.last_writer_pid != Some(pid);
+        let sent_time = if self.outgoing_stamped.load(Ordering::Relaxed) {
+            Some(realtime_now())
+        } else {
+            None
+        };
+        if !is_boundary && sent_time.is_none() {
+            return self.write(bufs);
+        }
+
+        // Queue the ancillary data before writing the data, so the ancillary data is
+        // there once the data can be read
+        let offset = self.bytes_written;
+        self.outgoing_ancillary
+            .lock()
+            .unwrap()
+            .push_back(AncillaryData {
+                offset,
+                files,
+                pid,
+                sent_time,
+                is_boundary,
+            });
+        match self.write(bufs) {
+            // An empty message is sent as a record, along with the files
+            Ok(count) if count > 0 || self.is_seqpacket => {
+                self.last_writer_pid = Some(pid);
+                Ok(count)
+            }
+            ret => {
+                // Like Linux, the files are not passed if no data is sent. The
+                // ancillary data is the last one in the queue, as the writer is
+                // locked.
+                self.outgoing_ancillary.lock().unwrap().pop_back();
+                ret
+            }
+        }
+    }
+
+    /// Write the data as a record if SOCK_SEQPACKET, or as bytes of the stream
+    fn write(&mut self, bufs: &[&[u8]]) -> Result<usize> {
+        if self.is_seqpacket {
+            let len = self.writer.write_record(bufs)?;
+            self.bytes_written += (RECORD_HEADER_LEN + len) as u64;
+            Ok(len)
+        } else {
+            let count = self.writer.write_to_vector(bufs)?;
+            self.bytes_written += count as u64;
+            Ok(count)
+        }
+    }
+
+    /// The number of bytes that can be read, which is the length of the next
+    /// message if SOCK_SEQPACKET
+    fn bytes_to_read(&self) -> usize {
+        if self.is_seqpacket {
+            self.reader.next_record_len().unwrap_or(0)
+        } else {
+            self.reader.bytes_to_read()
+        }
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) {
+        if nonblocking {
+            self.reader.set_non_blocking();
+            self.writer.set_non_blocking();
+        } else {
+            self.reader.set_blocking();
+            self.writer.set_blocking();
+        }
+    }
+
+    fn set_timeouts(&self, rcvtimeo: Option<Duration>, sndtimeo: Option<Duration>) {
+        self.reader.set_timeout(rcvtimeo);
+        self.writer.set_timeout(sndtimeo);
+    }
+
+    /// Let the peer stamp the data written to this side, or not
+    fn set_stamped(&self, stamped: bool) {
+        self.incoming_stamped.store(stamped, Ordering::Relaxed);
+    }
+
+    /// Like Linux, the connection is reset if the peer is closed with the data
+    /// unread, which is reported once to the reader after all data is read.
+    fn check_conn_reset(&mut self) -> Result<()> {
+        if !self.is_reset_reported && self.writer.is_peer_closed_with_unread_data() {
+            self.is_reset_reported = true;
+            return_errno!(ECONNRESET, "the peer is closed with unread data");
+        }
+        Ok(())
+    }
 }
 
-// TODO: Add SO_SNDBUF and SO_RCVBUF to set/getsockopt to dynamcally change the size.
 // This value is got from /proc/sys/net/core/rmem_max and wmem_max that are same on linux.
 pub const DEFAULT_BUF_SIZE: usize = 208 * 1024;
+// The max backlog of listening sockets, which is the same as Linux
+const SOMAXCONN: usize = 4096;
+// The min sizes of SO_SNDBUF and SO_RCVBUF, which are the same as Linux
+const MIN_SNDBUF: usize = 4608;
+const MIN_RCVBUF: usize = 2304;
+
+/// The time of the realtime clock, which stamps the data of the unix sockets
+fn realtime_now() -> Duration {
+    time::do_clock_gettime(time::ClockID::CLOCK_REALTIME)
+        .unwrap()
+        .as_duration()
+}
+
+/// Get the buffer size of SO_SNDBUF or SO_RCVBUF. Like Linux, the size given by the
+/// user is capped by the max size, and then doubled for the bookkeeping overhead.
+fn buf_size_optval(optval: &[u8], min_size: usize) -> Result<usize> {
+    let size = max(int_optval(optval)?, 0) as usize;
+    Ok(max(min(size, DEFAULT_BUF_SIZE) * 2, min_size))
+}
+
+// The number of shards of the unix socket objects, which must be a power of two
+const UNIX_SOCKET_OBJ_SHARD_COUNT: usize = 64;
 
 lazy_static! {
-    static ref UNIX_SOCKET_OBJS: Mutex<BTreeMap<String, Arc<UnixSocketObject>>> =
-        Mutex::new(BTreeMap::new());
+    static ref UNIX_SOCKET_OBJS: UnixSocketObjectShards =
+        UnixSocketObjectShards::new(UNIX_SOCKET_OBJ_SHARD_COUNT);
 }
@@ -8,9 +8,13 @@ pub use std::cmp::{max, min};
 pub use std::collections::{HashMap, VecDeque};
 pub use std::fmt::{Debug, Display};
 pub use std::prelude::v1::*;
-pub use std::sync::{
-    Arc, SgxMutex, SgxMutexGuard, SgxRwLock, SgxRwLockReadGuard, SgxRwLockWriteGuard,
-};
+pub use std::sync::{Arc, SgxRwLock, SgxRwLockReadGuard, SgxRwLockWriteGuard};
+#[cfg(not(feature = "lock_stat"))]
+pub use std::sync::{SgxMutex, SgxMutexGuard};
+
+// Instrument the mutexes to record contention statistics
+#[cfg(feature = "lock_stat")]
+pub use crate::util::sync::mutex::{SgxMutex, SgxMutexGuard};
 
 // Override prelude::Result with error::Result
 pub use crate::error::Result;
@@ -1,7 +1,5 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
 use std::intrinsics::atomic_load;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
 
 use crate::prelude::*;
 use crate::time::timespec_t;
@@ -82,10 +80,13 @@ pub fn futex_wait(
     // Get and lock the futex bucket
     let futex_key = FutexKey::new(futex_addr);
     let (_, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
-    let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+    let mut futex_bucket = futex_bucket_ref.lock();
 
-    // Check the futex value
+    // Enqueue the item, then check the futex value
+    let futex_item = FutexItem::new(futex_key);
+    futex_bucket.enqueue_item(futex_item.clone());
     if futex_key.load_val() != futex_val {
+        futex_bucket.dequeue_item(&futex_item);
         return_errno!(EAGAIN, "futex value does not match");
     }
     // Why we first lock the bucket then check the futex value?
@@ -119,9 +120,12 @@ pub fn futex_wait(
     // If the waiter on CPU 0 does not lock the bucket before check the futex velue,
     // it cannot find the transition of futex value from val to new_val and enqueue
     // to the bucket, which will cause the waiter to wait forever.
-
-    let futex_item = FutexItem::new(futex_key);
-    futex_bucket.enqueue_item(futex_item.clone());
+    //
+    // And why we enqueue the item before checking the futex value? The waker does
+    // not lock the bucket if it has no waiters (see `FutexBucket::has_waiters`),
+    // so the waiter must be counted before it reads the futex value. Otherwise, the
+    // waker on CPU 1 may find no waiters and return, while the waiter on CPU 0 has
+    // read the old value and is about to enqueue.
 
     // Must make sure that no locks are holded by this thread before wait
     drop(futex_bucket);
@@ -135,10 +139,13 @@ pub fn futex_wake(futex_addr: *const i32, max_count: usize) -> Result<usize> {
         futex_addr as usize, max_count
     );
 
-    // Get and lock the futex bucket
+    // Get the futex bucket, and do not lock it if there are no waiters
     let futex_key = FutexKey::new(futex_addr);
     let (_, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
-    let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+    if !futex_bucket_ref.has_waiters() {
+        return Ok(0);
+    }
+    let mut futex_bucket = futex_bucket_ref.lock();
 
     // Dequeue and wake up the items in the bucket
     let count = futex_bucket.dequeue_and_wake_items(futex_key, max_count);
@@ -159,17 +166,20 @@ pub fn futex_requeue(
     let futex_new_key = FutexKey::new(futex_new_addr);
     let (bucket_idx, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_key);
     let (new_bucket_idx, futex_new_bucket_ref) = FUTEX_BUCKETS.get_bucket(futex_new_key);
+    if !futex_bucket_ref.has_waiters() {
+        return Ok(0);
+    }
     let nwakes = {
         if bucket_idx != new_bucket_idx {
             let (mut futex_bucket, mut futex_new_bucket) = {
                 if bucket_idx < new_bucket_idx {
-                    let mut futex_bucket = futex_bucket_ref.lock().unwrap();
-                    let mut futex_new_bucket = futex_new_bucket_ref.lock().unwrap();
+                    let futex_bucket = futex_bucket_ref.lock();
+                    let futex_new_bucket = futex_new_bucket_ref.lock();
                     (futex_bucket, futex_new_bucket)
                 } else {
                     // bucket_idx > new_bucket_idx
-                    let mut futex_new_bucket = futex_new_bucket_ref.lock().unwrap();
-                    let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+                    let futex_new_bucket = futex_new_bucket_ref.lock();
+                    let futex_bucket = futex_bucket_ref.lock();
                     (futex_bucket, futex_new_bucket)
                 }
             };
@@ -183,7 +193,7 @@ pub fn futex_requeue(
             nwakes
         } else {
             // bucket_idx == new_bucket_idx
-            let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+            let mut futex_bucket = futex_bucket_ref.lock();
             let nwakes = futex_bucket.dequeue_and_wake_items(futex_key, max_nwakes);
             futex_bucket.update_item_keys(futex_key, futex_new_key, max_nrequeues);
             nwakes
@@ -195,10 +205,15 @@ pub fn futex_requeue(
 lazy_static! {
     // Use the same count as linux kernel to keep the same performance
     static ref BUCKET_COUNT: usize = ((1 << 8) * (*crate::sched::NCORES)).next_power_of_two();
-    static ref BUCKET_MASK: usize = *BUCKET_COUNT - 1;
     static ref FUTEX_BUCKETS: FutexBucketVec = { FutexBucketVec::new(*BUCKET_COUNT) };
 }
 
+/// The key of a futex, which is its address.
+///
+/// All the processes share the address space of the enclave, so a futex is told
+/// apart by its address alone, no matter whether it is private (FUTEX_PRIVATE) or
+/// shared between processes. That is, every futex takes the fast path of the
+/// private futexes in Linux, without looking up the memory mapping.
 #[derive(PartialEq, Copy, Clone)]
 struct FutexKey(usize);
 
@@ -214,6 +229,19 @@ impl FutexKey {
     pub fn addr(&self) -> usize {
         self.0
     }
+
+    /// Hash the key into `nbits` bits with the multiplicative hashing, which is
+    /// much cheaper than the default hasher of std
+    pub fn hash(&self, nbits: u32) -> usize {
+        // The addr is the multiples of 4, so we ignore the last 2 bits
+        let addr = (self.addr() >> 2) as u64;
+        // The golden ratio of 2^64, as hash_64 of Linux
+        const GOLDEN_RATIO_64: u64 = 0x61C8_8646_80B5_83EB;
+        if nbits == 0 {
+            return 0;
+        }
+        (addr.wrapping_mul(GOLDEN_RATIO_64) >> (64 - nbits)) as usize
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -235,9 +263,9 @@ impl FutexItem {
     }
 
     pub fn wait(&self, timeout: &Option<timespec_t>) -> Result<()> {
-        if let Err(e) = self.waiter.wait_timeout(&timeout) {
+        if let Err(e) = crate::sched::account_wait(|| self.waiter.wait_timeout(&timeout)) {
             let (_, futex_bucket_ref) = FUTEX_BUCKETS.get_bucket(self.key);
-            let mut futex_bucket = futex_bucket_ref.lock().unwrap();
+            let mut futex_bucket = futex_bucket_ref.lock();
             futex_bucket.dequeue_item(self);
             return_errno!(e.errno(), "futex wait timeout or interrupted");
         }
@@ -254,30 +282,62 @@ impl FutexItem {
     }
 }
 
+/// A bucket of the futexes, whose queue is protected by its own lock.
+///
+/// The buckets are aligned to the cache line, so that the threads working on the
+/// adjacent buckets do not contend for the same cache line.
+#[repr(align(64))]
 struct FutexBucket {
-    queue: VecDeque<FutexItem>,
+    // The number of the items in the queue, which can be read without the lock
+    nwaiters: AtomicUsize,
+    queue: SgxMutex<VecDeque<FutexItem>>,
 }
 
-type FutexBucketRef = Arc<SgxMutex<FutexBucket>>;
-
 impl FutexBucket {
     pub fn new() -> FutexBucket {
         FutexBucket {
-            queue: VecDeque::new(),
+            nwaiters: AtomicUsize::new(0),
+            queue: SgxMutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn lock(&self) -> FutexBucketGuard<'_> {
+        FutexBucketGuard {
+            nwaiters: &self.nwaiters,
+            queue: self.queue.lock().unwrap(),
         }
     }
 
+    /// Whether there may be waiters in the bucket, which is checked by a waker
+    /// without the lock.
+    ///
+    /// A waiter is counted before it checks the futex value, and the full fence
+    /// here pairs with the one of the counting. So either the waker finds the
+    /// waiter, or the waiter finds the futex value updated by the waker.
+    pub fn has_waiters(&self) -> bool {
+        fence(Ordering::SeqCst);
+        self.nwaiters.load(Ordering::SeqCst) > 0
+    }
+}
+
+/// A locked bucket of the futexes
+struct FutexBucketGuard<'a> {
+    nwaiters: &'a AtomicUsize,
+    queue: SgxMutexGuard<'a, VecDeque<FutexItem>>,
+}
+
+impl<'a> FutexBucketGuard<'a> {
     pub fn enqueue_item(&mut self, item: FutexItem) {
         self.queue.push_back(item);
+        // A full fence, which is followed by the check of the futex value
+        self.nwaiters.fetch_add(1, Ordering::SeqCst);
     }
 
     // TODO: this is an O(N) operation. Try to make it more efficient
     pub fn dequeue_item(&mut self, futex_item: &FutexItem) -> Option<FutexItem> {
-        let item_i = self.queue.iter().position(|item| *item == *futex_item);
-        if item_i.is_none() {
-            return None;
-        }
-        self.queue.remove(item_i.unwrap())
+        let item_i = self.queue.iter().position(|item| *item == *futex_item)?;
+        self.nwaiters.fetch_sub(1, Ordering::SeqCst);
+        self.queue.remove(item_i)
     }
 
     // TODO: consider using std::future to improve the readability
@@ -294,6 +354,7 @@ impl FutexBucket {
                 false
             }
         });
+        self.nwaiters.fetch_sub(count, Ordering::SeqCst);
 
         FutexItem::batch_wake(&items_to_wake);
         count
@@ -332,34 +393,29 @@ impl FutexBucket {
                 false
             }
         });
+        self.nwaiters.fetch_sub(count, Ordering::SeqCst);
     }
 }
 
 struct FutexBucketVec {
-    vec: Vec<FutexBucketRef>,
+    vec: Vec<FutexBucket>,
+    // The number of the bits of the bucket indexes
+    nbits: u32,
 }
 
 impl FutexBucketVec {
     pub fn new(size: usize) -> FutexBucketVec {
-        let mut buckets = FutexBucketVec {
-            vec: Vec::with_capacity(size),
-        };
-        for idx in 0..size {
-            let bucket = Arc::new(SgxMutex::new(FutexBucket::new()));
-            buckets.vec.push(bucket);
+        debug_assert!(size.is_power_of_two());
+        let vec = (0..size).map(|_| FutexBucket::new()).collect();
+        FutexBucketVec {
+            vec,
+            nbits: size.trailing_zeros(),
         }
-        buckets
-    }
-
-    pub fn get_bucket(&self, key: FutexKey) -> (usize, FutexBucketRef) {
-        let idx = *BUCKET_MASK & {
-            // The addr is the multiples of 4, so we ignore the last 2 bits
-            let addr = key.addr() >> 2;
-            let mut s = DefaultHasher::new();
-            addr.hash(&mut s);
-            s.finish() as usize
-        };
-        (idx, self.vec[idx].clone())
+    }
+
+    pub fn get_bucket(&self, key: FutexKey) -> (usize, &FutexBucket) {
+        let idx = key.hash(self.nbits);
+        (idx, &self.vec[idx])
     }
 }
 
@@ -0,0 +1,62 @@
+use super::table;
+use crate::fs::FileDesc;
+use crate::prelude::*;
+
+/// Compare the resources of two threads, which returns 0 if the resources are the
+/// same one. Otherwise, it returns 1 or 2 to order the resources, which is stable
+/// but meaningless, like Linux.
+pub fn do_kcmp(pid1: pid_t, pid2: pid_t, kcmp_type: KcmpType) -> Result<isize> {
+    debug!(
+        "kcmp: pid1: {}, pid2: {}, kcmp_type: {:?}",
+        pid1, pid2, kcmp_type
+    );
+    let thread1 = table::get_thread(pid1)?;
+    let thread2 = table::get_thread(pid2)?;
+    let (ptr1, ptr2) = match kcmp_type {
+        KcmpType::KCMP_FILE(fd1, fd2) => {
+            // An open file description is shared by dup and by the inheritance of
+            // files, which is the same FileRef
+            let file1 = thread1.files().lock().unwrap().get(fd1)?;
+            let file2 = thread2.files().lock().unwrap().get(fd2)?;
+            (Arc::as_ptr(&file1) as usize, Arc::as_ptr(&file2) as usize)
+        }
+        KcmpType::KCMP_VM => (
+            Arc::as_ptr(thread1.vm()) as usize,
+            Arc::as_ptr(thread2.vm()) as usize,
+        ),
+        KcmpType::KCMP_FILES => (
+            Arc::as_ptr(thread1.files()) as usize,
+            Arc::as_ptr(thread2.files()) as usize,
+        ),
+    };
+    let ret = if ptr1 == ptr2 {
+        0
+    } else if ptr1 < ptr2 {
+        1
+    } else {
+        2
+    };
+    Ok(ret)
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug)]
+pub enum KcmpType {
+    KCMP_FILE(FileDesc, FileDesc),
+    KCMP_VM,
+    KCMP_FILES,
+}
+
+impl KcmpType {
+    pub fn from_raw(kcmp_type: i32, idx1: u64, idx2: u64) -> Result<KcmpType> {
+        let kcmp_type = match kcmp_type {
+            0 => KcmpType::KCMP_FILE(idx1 as FileDesc, idx2 as FileDesc),
+            1 => KcmpType::KCMP_VM,
+            2 => KcmpType::KCMP_FILES,
+            // KCMP_FS, KCMP_SIGHAND, KCMP_IO, KCMP_SYSVSEM and KCMP_EPOLL_TFD
+            3..=7 => return_errno!(EOPNOTSUPP, "the type of kcmp is not supported"),
+            _ => return_errno!(EINVAL, "unknown type of kcmp"),
+        };
+        Ok(kcmp_type)
+    }
+}
@@ -1,5 +1,8 @@
 use super::ThreadRef;
-use crate::fs::{FileMode, INodeExt};
+use crate::config::LIBOS_CONFIG;
+use crate::fs::{
+    report_security_event, verify_integrity_only_exec, FileMode, INodeExt, SecurityEventKind,
+};
 use crate::prelude::*;
 use rcore_fs::vfs::INode;
 use std::ffi::CString;
@@ -60,10 +63,8 @@ fn parse_script_interpreter(file_buf: &Vec<u8>) -> Result<String> {
 }
 
 pub fn load_file_to_vec(file_path: &str, current_ref: &ThreadRef) -> Result<Vec<u8>> {
-    let inode = current_ref
-        .fs()
-        .lock()
-        .unwrap()
+    let fs = current_ref.fs().lock().unwrap();
+    let inode = fs
         .lookup_inode(file_path)
         .map_err(|e| errno!(e.errno(), "cannot find the file"))?;
     let file_mode = {
@@ -79,7 +80,33 @@ pub fn load_file_to_vec(file_path: &str, current_ref: &ThreadRef) -> Result<Vec<
             file_mode
         );
     }
-    inode
+    let file_buf = inode
         .read_as_vec()
-        .map_err(|e| errno!(e.errno(), "failed to read the file"))
+        .map_err(|e| errno!(e.errno(), "failed to read the file"))?;
+    verify_integrity_only_exec(&fs, file_path, &file_buf)
+        .and_then(|_| check_exec_allowlist(&file_buf))
+        .map_err(|e| {
+            if e.errno() == EACCES {
+                report_security_event(SecurityEventKind::ExecDenied, file_path);
+            }
+            e
+        })?;
+    Ok(file_buf)
+}
+
+/// Check the file to execute against the allowlist of hashes in the config, if it
+/// is enabled. It applies to every file loaded for an exec, i.e., the executable
+/// or the script, the interpreter of the script and ld.so, but not to the shared
+/// libraries that ld.so loads or to any other mmap with PROT_EXEC.
+fn check_exec_allowlist(file_buf: &[u8]) -> Result<()> {
+    let allowlist = match &LIBOS_CONFIG.process.exec_allowlist {
+        Some(allowlist) => allowlist,
+        None => return Ok(()),
+    };
+    let hash = sgx_tcrypto::rsgx_sha256_slice(file_buf)
+        .map_err(|_| errno!(EIO, "failed to calculate the hash of the file"))?;
+    if !allowlist.contains(&hash) {
+        return_errno!(EACCES, "the file is not in the exec allowlist");
+    }
+    Ok(())
 }
@@ -3,6 +3,7 @@ use std::os::raw::c_char;
 use std::{mem, ptr};
 
 use super::aux_vec::{AuxKey, AuxVec};
+use crate::fs::{DevRandom, File};
 use crate::prelude::*;
 
 /*
@@ -16,6 +17,8 @@ use crate::prelude::*;
  *  | by variables below  |
  *  |                     |
  *  +---------------------+
+ *  | 16 random bytes     | <------+ Referenced by AT_RANDOM
+ *  +---------------------+
  *  | AT_NULL             |
  *  +---------------------+
  *  | AT_NULL             |
@@ -58,7 +61,12 @@ pub fn do_init(
     let stack_buf = unsafe { StackBuf::new(stack_top, init_area_size)? };
     let envp_cloned = clone_cstrings_on_stack(&stack_buf, envp)?;
     let argv_cloned = clone_cstrings_on_stack(&stack_buf, argv)?;
-    dump_auxtbl_on_stack(&stack_buf, auxtbl)?;
+    // The bytes are new for every process, so the stack canary and the pointer
+    // guard derived from them by libc are not shared with the parent
+    let random_bytes = stack_buf.put_slice(&new_random_bytes()?)?;
+    let mut auxtbl = auxtbl.clone();
+    auxtbl.set(AuxKey::AT_RANDOM, random_bytes as u64)?;
+    dump_auxtbl_on_stack(&stack_buf, &auxtbl)?;
     dump_cstrptrs_on_stack(&stack_buf, &envp_cloned);
     dump_cstrptrs_on_stack(&stack_buf, &argv_cloned);
     stack_buf.put(argv.len() as u64);
@@ -159,6 +167,12 @@ fn clone_cstrings_on_stack<'a, 'b>(
     Ok(cstrs_cloned)
 }
 
+fn new_random_bytes() -> Result<[u8; 16]> {
+    let mut bytes = [0u8; 16];
+    DevRandom.read(&mut bytes)?;
+    Ok(bytes)
+}
+
 fn dump_auxtbl_on_stack<'a, 'b>(stack: &'a StackBuf, auxtbl: &'b AuxVec) -> Result<()> {
     // For every key-value pair, dump the value first, then the key
     stack.put(0 as u64);
@@ -7,7 +7,7 @@ use super::elf_file::{ElfFile, ElfHeader, ProgramHeader, ProgramHeaderExt};
 use super::process::ProcessBuilder;
 use super::task::Task;
 use super::thread::ThreadName;
-use super::{table, task, ProcessRef, ThreadRef};
+use super::{table, task, EnvVars, ProcessRef, ThreadRef};
 use crate::fs::{
     CreationFlags, File, FileDesc, FileTable, FsView, HostStdioFds, StdinFile, StdoutFile,
     ROOT_INODE,
@@ -29,10 +29,11 @@ pub fn do_spawn(
     current_ref: &ThreadRef,
 ) -> Result<pid_t> {
     let exec_now = true;
+    let env = EnvVars::inherit(&current_ref.process().env().read().unwrap(), envp);
     do_spawn_common(
         elf_path,
         argv,
-        envp,
+        env,
         file_actions,
         None,
         current_ref,
@@ -44,7 +45,7 @@ pub fn do_spawn(
 pub fn do_spawn_without_exec(
     elf_path: &str,
     argv: &[CString],
-    envp: &[CString],
+    env: EnvVars,
     file_actions: &[FileAction],
     host_stdio_fds: &HostStdioFds,
     current_ref: &ThreadRef,
@@ -53,7 +54,7 @@ pub fn do_spawn_without_exec(
     do_spawn_common(
         elf_path,
         argv,
-        envp,
+        env,
         file_actions,
         Some(host_stdio_fds),
         current_ref,
@@ -64,7 +65,7 @@ pub fn do_spawn_without_exec(
 fn do_spawn_common(
     elf_path: &str,
     argv: &[CString],
-    envp: &[CString],
+    env: EnvVars,
     file_actions: &[FileAction],
     host_stdio_fds: Option<&HostStdioFds>,
     current_ref: &ThreadRef,
@@ -73,7 +74,7 @@ fn do_spawn_common(
     let new_process_ref = new_process(
         elf_path,
         argv,
-        envp,
+        env,
         file_actions,
         host_stdio_fds,
         current_ref,
@@ -96,7 +97,7 @@ fn do_spawn_common(
 fn new_process(
     file_path: &str,
     argv: &[CString],
-    envp: &[CString],
+    env: EnvVars,
     file_actions: &[FileAction],
     host_stdio_fds: Option<&HostStdioFds>,
     current_ref: &ThreadRef,
@@ -162,7 +163,7 @@ fn new_process(
             };
             let user_stack_base = vm.get_stack_base();
             let user_stack_limit = vm.get_stack_limit();
-            let user_rsp = init_stack::do_init(user_stack_base, 4096, &argv, envp, &auxvec)?;
+            let user_rsp = init_stack::do_init(user_stack_base, 4096, &argv, &env.envp(), &auxvec)?;
             unsafe {
                 Task::new(
                     ldso_entry,
@@ -190,6 +191,7 @@ fn new_process(
             .vm(vm_ref)
             .exec_path(&elf_path)
             .parent(process_ref)
+            .env(env)
             .task(task)
             .sched(sched_ref)
             .rlimits(rlimit_ref)
@@ -258,6 +260,11 @@ fn init_files(
                     let file = cloned_file_table.get(old_fd)?;
                     if old_fd != new_fd {
                         cloned_file_table.put_at(new_fd, file, false);
+                    } else {
+                        // Like musl and glibc, dup2 to the same fd keeps it open on exec
+                        cloned_file_table
+                            .get_entry_mut(new_fd)?
+                            .set_close_on_spawn(false);
                     }
                 }
                 &FileAction::Close(fd) => {
@@ -32,7 +32,7 @@ pub fn do_wait4(child_filter: &ProcessFilter) -> Result<(pid_t, i32)> {
         .find(|child| child.status() == ProcessStatus::Zombie);
     if let Some(zombie_child) = zombie_child {
         let zombie_pid = zombie_child.pid();
-        let exit_status = free_zombie_child(process_inner, zombie_pid);
+        let exit_status = free_zombie_child(process, process_inner, zombie_pid);
         return Ok((zombie_pid, exit_status));
     }
 
@@ -48,17 +48,22 @@ pub fn do_wait4(child_filter: &ProcessFilter) -> Result<(pid_t, i32)> {
     let zombie_pid = waiter.sleep_until_woken_with_result();
 
     let mut process_inner = process.inner();
-    let exit_status = free_zombie_child(process_inner, zombie_pid);
+    let exit_status = free_zombie_child(process, process_inner, zombie_pid);
     Ok((zombie_pid, exit_status))
 }
 
-fn free_zombie_child(mut parent_inner: SgxMutexGuard<ProcessInner>, zombie_pid: pid_t) -> i32 {
+fn free_zombie_child(
+    parent: &ProcessRef,
+    mut parent_inner: SgxMutexGuard<ProcessInner>,
+    zombie_pid: pid_t,
+) -> i32 {
     // Remove zombie from the process and thread table
     table::del_thread(zombie_pid).expect("tid must be in the table");
     table::del_process(zombie_pid).expect("pid must be in the table");
 
     let zombie = parent_inner.remove_zombie_child(zombie_pid);
     debug_assert!(zombie.status() == ProcessStatus::Zombie);
+    parent.account_waited_child(&zombie);
 
     let zombie_inner = zombie.inner();
     zombie_inner.term_status().unwrap().as_u32() as i32
@@ -0,0 +1,115 @@
+//! The environment variables of processes.
+//!
+//! The environment of a process is kept by the LibOS, in addition to the copy in
+//! the user space, so that it can be inspected via `/proc/[pid]/environ`. Each
+//! variable is tagged with its origin: the variables given by the host (i.e., those
+//! listed in the untrusted env of Occlum.json) are untrusted, while the ones in the
+//! default env of Occlum.json or set by the application itself are trusted. The
+//! origins are exported via `/proc/[pid]/environ_origin`, one line per variable, i.e.,
+//!
+//! ```text
+//! untrusted KEY
+//! ```
+//!
+//! so that audit tools can tell the values injected by the host.
+//!
+//! A spawned process inherits the origins from its parent: a variable passed to the
+//! child is still untrusted if the parent has exactly the same untrusted variable.
+//! The libc mirrors the changes made by `setenv`, `putenv` and `unsetenv` to the
+//! LibOS with the Occlum-specific `putenv` system call.
+use super::*;
+use std::ffi::CString;
+
+#[derive(Debug, Clone, Default)]
+pub struct EnvVars {
+    vars: Vec<EnvVar>,
+}
+
+#[derive(Debug, Clone)]
+struct EnvVar {
+    // In the form of "KEY=VALUE"
+    kv: CString,
+    origin: EnvOrigin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvOrigin {
+    Trusted,
+    Untrusted,
+}
+
+impl EnvVars {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create the environment of a child process from the envp given by its parent
+    pub fn inherit(parent: &EnvVars, envp: &[CString]) -> Self {
+        let mut env = Self::new();
+        for kv in envp {
+            let origin = parent
+                .vars
+                .iter()
+                .find(|var| &var.kv == kv)
+                .map(|var| var.origin)
+                .unwrap_or(EnvOrigin::Trusted);
+            env.set(kv.clone(), origin);
+        }
+        env
+    }
+
+    /// Set a variable in the form of "KEY=VALUE", which replaces the variable of
+    /// the same key.
+    pub fn set(&mut self, kv: CString, origin: EnvOrigin) {
+        let key = key_of(&kv).to_vec();
+        match self.vars.iter_mut().find(|var| key_of(&var.kv) == &key[..]) {
+            Some(var) => {
+                // Setting an untrusted variable to the same value does not make it
+                // trusted
+                if var.kv != kv {
+                    *var = EnvVar { kv, origin };
+                }
+            }
+            None => self.vars.push(EnvVar { kv, origin }),
+        }
+    }
+
+    pub fn unset(&mut self, key: &[u8]) {
+        self.vars.retain(|var| key_of(&var.kv) != key);
+    }
+
+    /// The variables in the form of "KEY=VALUE", which are passed to the user space
+    pub fn envp(&self) -> Vec<CString> {
+        self.vars.iter().map(|var| var.kv.clone()).collect()
+    }
+
+    /// The variables separated by null bytes, as in /proc/[pid]/environ
+    pub fn to_environ(&self) -> Vec<u8> {
+        let mut environ = Vec::new();
+        for var in self.vars.iter() {
+            environ.extend_from_slice(var.kv.as_bytes_with_nul());
+        }
+        environ
+    }
+
+    /// The origins of the variables, as in /proc/[pid]/environ_origin
+    pub fn to_origins(&self) -> String {
+        let mut origins = String::new();
+        for var in self.vars.iter() {
+            let origin = match var.origin {
+                EnvOrigin::Trusted => "trusted",
+                EnvOrigin::Untrusted => "untrusted",
+            };
+            origins += &format!("{} {}\n", origin, String::from_utf8_lossy(key_of(&var.kv)));
+        }
+        origins
+    }
+}
+
+fn key_of(kv: &CString) -> &[u8] {
+    let kv = kv.as_bytes();
+    match kv.iter().position(|&b| b == b'=') {
+        Some(pos) => &kv[..pos],
+        None => kv,
+    }
+}
@@ -23,6 +23,8 @@ use self::wait::{WaitQueue, Waiter};
 pub use self::do_exit::handle_force_exit;
 pub use self::do_futex::{futex_wait, futex_wake};
 pub use self::do_spawn::do_spawn_without_exec;
+pub use self::env::{EnvOrigin, EnvVars};
+pub use self::pidfd::{AsPidFd, PidFd};
 pub use self::process::{Process, ProcessFilter, ProcessStatus, IDLE};
 pub use self::syscalls::*;
 pub use self::task::Task;
@@ -34,9 +36,12 @@ mod do_clone;
 mod do_exit;
 mod do_futex;
 mod do_getpid;
+mod do_kcmp;
 mod do_set_tid_address;
 mod do_spawn;
 mod do_wait4;
+mod env;
+mod pidfd;
 mod prctl;
 mod process;
 mod syscalls;
@@ -0,0 +1,80 @@
+//! Pidfds, i.e., the fds that refer to processes.
+//!
+//! A pidfd holds the process that it refers to, so the signals sent via the pidfd
+//! never hit another process that reuses the pid. The pidfd becomes readable when
+//! the process exits. It is backed by an eventfd that is written on the exit, so that
+//! it can be polled with poll, select and epoll like the other files backed by the
+//! host.
+use super::ProcessRef;
+use crate::fs::{AccessMode, EventCreationFlags, EventFile, File, FileRef, StatusFlags};
+use crate::prelude::*;
+use std::any::Any;
+
+#[derive(Debug)]
+pub struct PidFd {
+    process: ProcessRef,
+    // Written once the process exits
+    exit_event: Arc<EventFile>,
+}
+
+impl PidFd {
+    pub fn new(process: ProcessRef, nonblocking: bool) -> Result<Self> {
+        let flags = if nonblocking {
+            EventCreationFlags::EFD_NONBLOCK
+        } else {
+            EventCreationFlags::empty()
+        };
+        let exit_event = Arc::new(EventFile::new(0, flags)?);
+        process.inner().add_exit_event(&exit_event);
+        Ok(Self {
+            process,
+            exit_event,
+        })
+    }
+
+    pub fn process(&self) -> &ProcessRef {
+        &self.process
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.exit_event.get_host_fd()
+    }
+}
+
+/// Make the event readable, which tells the pidfd that the process has exited.
+pub(super) fn notify_exit_event(event: &EventFile) {
+    if let Err(e) = event.write(&1u64.to_ne_bytes()) {
+        warn!("failed to notify the exit of a process: {:?}", e);
+    }
+}
+
+impl File for PidFd {
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDWR)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        self.exit_event.get_status_flags()
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        self.exit_event
+            .set_status_flags(new_status_flags & StatusFlags::O_NONBLOCK)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub trait AsPidFd {
+    fn as_pidfd(&self) -> Result<&PidFd>;
+}
+
+impl AsPidFd for FileRef {
+    fn as_pidfd(&self) -> Result<&PidFd> {
+        self.as_any()
+            .downcast_ref::<PidFd>()
+            .ok_or_else(|| errno!(EBADF, "not a pidfd"))
+    }
+}
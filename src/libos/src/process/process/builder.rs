@@ -1,12 +1,13 @@
 use super::super::task::Task;
 use super::super::thread::{ThreadBuilder, ThreadId, ThreadName};
 use super::super::{
-    FileTableRef, ForcedExitStatus, FsViewRef, ProcessRef, ProcessVMRef, ResourceLimitsRef,
-    SchedAgentRef,
+    EnvVars, FileTableRef, ForcedExitStatus, FsViewRef, ProcessRef, ProcessVMRef,
+    ResourceLimitsRef, SchedAgentRef,
 };
 use super::{Process, ProcessInner};
 use crate::prelude::*;
 use crate::signal::{SigDispositions, SigQueues};
+use crate::time;
 
 #[derive(Debug)]
 pub struct ProcessBuilder {
@@ -18,6 +19,7 @@ pub struct ProcessBuilder {
     exec_path: Option<String>,
     parent: Option<ProcessRef>,
     no_parent: bool,
+    env: Option<EnvVars>,
 }
 
 impl ProcessBuilder {
@@ -30,6 +32,7 @@ impl ProcessBuilder {
             exec_path: None,
             parent: None,
             no_parent: false,
+            env: None,
         }
     }
 
@@ -53,6 +56,11 @@ impl ProcessBuilder {
         self
     }
 
+    pub fn env(mut self, env: EnvVars) -> Self {
+        self.env = Some(env);
+        self
+    }
+
     pub fn task(mut self, task: Task) -> Self {
         self.thread_builder(|tb| tb.task(task))
     }
@@ -97,19 +105,29 @@ impl ProcessBuilder {
         // Build a new process
         let new_process = {
             let exec_path = self.exec_path.take().unwrap_or_default();
+            let start_time = time::up_time::get();
             let parent = self.parent.take().map(|parent| RwLock::new(parent));
             let inner = SgxMutex::new(ProcessInner::new());
+            let env = RwLock::new(self.env.take().unwrap_or_default());
             let sig_dispositions = RwLock::new(SigDispositions::new());
             let sig_queues = RwLock::new(SigQueues::new());
             let forced_exit_status = ForcedExitStatus::new();
+            let exited_cpu_usage = Default::default();
+            let children_cpu_usage = Default::default();
+            let membarrier_registrations = Default::default();
             Arc::new(Process {
                 pid,
                 exec_path,
+                start_time,
                 parent,
                 inner,
+                env,
                 sig_dispositions,
                 sig_queues,
                 forced_exit_status,
+                exited_cpu_usage,
+                children_cpu_usage,
+                membarrier_registrations,
             })
         };
 
@@ -1,8 +1,14 @@
 use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Weak;
+use std::time::Duration;
 
+use super::pidfd::notify_exit_event;
 use super::wait::WaitQueue;
-use super::{ForcedExitStatus, ProcessRef, TermStatus, ThreadRef};
+use super::{EnvVars, ForcedExitStatus, ProcessRef, TermStatus, ThreadRef};
+use crate::fs::EventFile;
 use crate::prelude::*;
+use crate::sched::CpuUsage;
 use crate::signal::{SigDispositions, SigNum, SigQueues};
 
 pub use self::builder::ProcessBuilder;
@@ -15,13 +21,21 @@ pub struct Process {
     // Immutable info
     pid: pid_t,
     exec_path: String,
+    // The up time when the process is created
+    start_time: Duration,
     // Mutable info
     parent: Option<RwLock<ProcessRef>>,
     inner: SgxMutex<ProcessInner>,
+    env: RwLock<EnvVars>,
     // Signal
     sig_dispositions: RwLock<SigDispositions>,
     sig_queues: RwLock<SigQueues>,
     forced_exit_status: ForcedExitStatus,
+    // CPU usage
+    exited_cpu_usage: SgxMutex<CpuUsage>,
+    children_cpu_usage: SgxMutex<CpuUsage>,
+    // The commands of membarrier that the process has registered for
+    membarrier_registrations: AtomicU32,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -99,6 +113,16 @@ impl Process {
         &self.exec_path
     }
 
+    /// Get the start time, i.e., the up time of the LibOS when the process is created.
+    pub fn start_time(&self) -> Duration {
+        self.start_time
+    }
+
+    /// Get the environment variables.
+    pub fn env(&self) -> &RwLock<EnvVars> {
+        &self.env
+    }
+
     /// Get the signal queues for process-directed signals.
     pub fn sig_queues(&self) -> &RwLock<SigQueues> {
         &self.sig_queues
@@ -129,6 +153,46 @@ impl Process {
         self.forced_exit_status.force_exit(term_status);
     }
 
+    /// Get the CPU usage of all the threads, including the exited ones.
+    pub fn cpu_usage(&self) -> CpuUsage {
+        // Lock the inner first, so that an exiting thread is counted exactly once
+        let inner = self.inner();
+        let mut cpu_usage = *self.exited_cpu_usage.lock().unwrap();
+        for thread in inner.threads().into_iter().flatten() {
+            cpu_usage.add(&thread.sched_stat().cpu_usage());
+        }
+        cpu_usage
+    }
+
+    /// Get the CPU usage of the children (and their descendants) that have been
+    /// waited for.
+    pub fn children_cpu_usage(&self) -> CpuUsage {
+        *self.children_cpu_usage.lock().unwrap()
+    }
+
+    /// Add the CPU usage of an exiting thread, which is called with the inner locked.
+    pub(super) fn account_exited_thread(&self, cpu_usage: &CpuUsage) {
+        self.exited_cpu_usage.lock().unwrap().add(cpu_usage);
+    }
+
+    /// Add the CPU usage of a zombie child that is waited for.
+    pub(super) fn account_waited_child(&self, child: &Process) {
+        let mut children_cpu_usage = self.children_cpu_usage.lock().unwrap();
+        children_cpu_usage.add(&child.cpu_usage());
+        children_cpu_usage.add(&child.children_cpu_usage());
+    }
+
+    /// Get the commands of membarrier that the process has registered for.
+    pub fn membarrier_registrations(&self) -> u32 {
+        self.membarrier_registrations.load(Ordering::Acquire)
+    }
+
+    /// Register the process for the commands of membarrier.
+    pub fn register_membarrier(&self, cmds: u32) {
+        self.membarrier_registrations
+            .fetch_or(cmds, Ordering::Release);
+    }
+
     /// Get the internal representation of the process.
     ///
     /// For the purpose of encapsulation, this method is invisible to other subsystems.
@@ -143,6 +207,8 @@ pub enum ProcessInner {
         children: Vec<ProcessRef>,
         waiting_children: WaitQueue<ProcessFilter, pid_t>,
         threads: Vec<ThreadRef>,
+        // The events of the pidfds to notify on exit
+        exit_events: Vec<Weak<EventFile>>,
     },
     Zombie {
         term_status: TermStatus,
@@ -156,6 +222,7 @@ impl ProcessInner {
             children: Vec::new(),
             waiting_children: WaitQueue::new(),
             threads: Vec::new(),
+            exit_events: Vec::new(),
         }
     }
 
@@ -233,6 +300,18 @@ impl ProcessInner {
         children.swap_remove(zombie_i)
     }
 
+    /// Notify the event when the process exits, or right away if it has exited.
+    pub fn add_exit_event(&mut self, event: &Arc<EventFile>) {
+        match self {
+            Self::Live { exit_events, .. } => {
+                // Forget the events of the closed pidfds
+                exit_events.retain(|event| event.strong_count() > 0);
+                exit_events.push(Arc::downgrade(event));
+            }
+            Self::Zombie { .. } => notify_exit_event(event),
+        }
+    }
+
     /// Exit means two things: 1) transfer all children to a new parent; 2) update the status.
     ///
     /// A lock guard for the new parent process is passed so that the transfer can be done
@@ -256,6 +335,12 @@ impl ProcessInner {
             new_parent_inner.children_mut().unwrap().push(child.clone());
         }
 
+        if let Self::Live { exit_events, .. } = self {
+            for event in exit_events.iter().filter_map(|event| event.upgrade()) {
+                notify_exit_event(&event);
+            }
+        }
+
         *self = Self::Zombie { term_status };
     }
 
@@ -1,7 +1,10 @@
 use super::do_arch_prctl::ArchPrctlCode;
 use super::do_clone::CloneFlags;
 use super::do_futex::{FutexFlags, FutexOp};
+use super::do_kcmp::KcmpType;
 use super::do_spawn::FileAction;
+use super::env::EnvOrigin;
+use super::pidfd::PidFd;
 use super::prctl::PrctlCmd;
 use super::process::ProcessFilter;
 use crate::prelude::*;
@@ -33,6 +36,26 @@ pub fn do_spawn(
     Ok(0)
 }
 
+/// Set an environment variable of the current process if the string is in the form
+/// of "KEY=VALUE", or unset the variable if the string is only "KEY".
+pub fn do_putenv(string: *const i8) -> Result<isize> {
+    let string = clone_cstring_safely(string)?;
+    let bytes = string.as_bytes();
+    if bytes.is_empty() || bytes[0] == b'=' {
+        return_errno!(EINVAL, "the name of the variable is empty");
+    }
+    debug!("putenv: {:?}", string);
+
+    let current = current!();
+    let mut env = current.process().env().write().unwrap();
+    if bytes.contains(&b'=') {
+        env.set(string, EnvOrigin::Trusted);
+    } else {
+        env.unset(bytes);
+    }
+    Ok(0)
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct FdOp {
@@ -232,6 +255,28 @@ pub fn do_wait4(pid: i32, exit_status_ptr: *mut i32) -> Result<isize> {
     }
 }
 
+pub fn do_pidfd_open(pid: i32, flags: u32) -> Result<isize> {
+    if pid <= 0 {
+        return_errno!(EINVAL, "invalid pid");
+    }
+    // PIDFD_NONBLOCK is the only valid flag
+    let nonblocking = match flags {
+        0 => false,
+        flags if flags == libc::O_NONBLOCK as u32 => true,
+        _ => return_errno!(EINVAL, "invalid flags"),
+    };
+    let process = super::table::get_process(pid as pid_t)?;
+    let pidfd: FileRef = Arc::new(Box::new(PidFd::new(process, nonblocking)?));
+    // Like Linux, a pidfd is always closed on exec
+    let fd = current!().add_file(pidfd, true);
+    Ok(fd as isize)
+}
+
+pub fn do_kcmp(pid1: pid_t, pid2: pid_t, kcmp_type: i32, idx1: u64, idx2: u64) -> Result<isize> {
+    let kcmp_type = KcmpType::from_raw(kcmp_type, idx1, idx2)?;
+    super::do_kcmp::do_kcmp(pid1, pid2, kcmp_type)
+}
+
 pub fn do_getpid() -> Result<isize> {
     let pid = super::do_getpid::do_getpid();
     Ok(pid as isize)
@@ -5,6 +5,7 @@ use super::{
     SigQueues, SigSet, Task, Thread, ThreadId, ThreadInner, ThreadName, ThreadRef,
 };
 use crate::prelude::*;
+use crate::sched::SchedStat;
 use crate::time::ThreadProfiler;
 
 #[derive(Debug)]
@@ -105,11 +106,13 @@ impl ThreadBuilder {
         let fs = self.fs.unwrap_or_default();
         let files = self.files.unwrap_or_default();
         let sched = self.sched.unwrap_or_default();
+        let sched_stat = SchedStat::new();
         let rlimits = self.rlimits.unwrap_or_default();
         let name = RwLock::new(self.name.unwrap_or_default());
         let sig_queues = RwLock::new(SigQueues::new());
         let sig_mask = RwLock::new(SigSet::new_empty());
         let sig_tmp_mask = RwLock::new(SigSet::new_empty());
+        let sig_saved_mask = RwLock::new(None);
         let sig_stack = SgxMutex::new(None);
         let profiler = if cfg!(feature = "syscall_timing") {
             SgxMutex::new(Some(ThreadProfiler::new()))
@@ -127,11 +130,13 @@ impl ThreadBuilder {
             fs,
             files,
             sched,
+            sched_stat,
             rlimits,
             name,
             sig_queues,
             sig_mask,
             sig_tmp_mask,
+            sig_saved_mask,
             sig_stack,
             profiler,
         });
@@ -9,8 +9,10 @@ use super::{
 use crate::fs::{EventCreationFlags, EventFile};
 use crate::net::THREAD_NOTIFIERS;
 use crate::prelude::*;
+use crate::sched::SchedStat;
 use crate::signal::{SigQueues, SigSet, SigStack};
 use crate::time::ThreadProfiler;
+use crate::util::sync::LockBackoff;
 
 pub use self::builder::ThreadBuilder;
 pub use self::id::ThreadId;
@@ -36,11 +38,13 @@ pub struct Thread {
     fs: FsViewRef,
     files: FileTableRef,
     sched: SchedAgentRef,
+    sched_stat: SchedStat,
     rlimits: ResourceLimitsRef,
     // Signal
     sig_queues: RwLock<SigQueues>,
     sig_mask: RwLock<SigSet>,
     sig_tmp_mask: RwLock<SigSet>,
+    sig_saved_mask: RwLock<Option<SigSet>>,
     sig_stack: SgxMutex<Option<SigStack>>,
     // System call timing
     profiler: SgxMutex<Option<ThreadProfiler>>,
@@ -82,6 +86,11 @@ impl Thread {
         &self.sched
     }
 
+    /// Get the scheduling statistics.
+    pub fn sched_stat(&self) -> &SchedStat {
+        &self.sched_stat
+    }
+
     /// Get the signal queues for thread-directed signals.
     pub fn sig_queues(&self) -> &RwLock<SigQueues> {
         &self.sig_queues
@@ -100,6 +109,15 @@ impl Thread {
         &self.sig_tmp_mask
     }
 
+    /// Get the per-thread signal mask that is saved when a syscall (e.g.,
+    /// epoll_pwait) replaces the signal mask during its execution.
+    ///
+    /// The saved mask is restored at the end of the execution of the syscall,
+    /// after the signals that are unblocked by the replacement are delivered.
+    pub fn sig_saved_mask(&self) -> &RwLock<Option<SigSet>> {
+        &self.sig_saved_mask
+    }
+
     /// Get the alternate signal stack.
     pub fn sig_stack(&self) -> &SgxMutex<Option<SigStack>> {
         &self.sig_stack
@@ -112,12 +130,15 @@ impl Thread {
 
     /// Get a file from the file table.
     pub fn file(&self, fd: FileDesc) -> Result<FileRef> {
-        self.files().lock().unwrap().get(fd)
+        self.files().lock_with_backoff().unwrap().get(fd)
     }
 
     /// Add a file to the file table.
     pub fn add_file(&self, new_file: FileRef, close_on_spawn: bool) -> FileDesc {
-        self.files().lock().unwrap().put(new_file, close_on_spawn)
+        self.files()
+            .lock_with_backoff()
+            .unwrap()
+            .put(new_file, close_on_spawn)
     }
 
     pub fn fs(&self) -> &FsViewRef {
@@ -146,6 +167,7 @@ impl Thread {
 
     pub(super) fn start(&self, host_tid: pid_t) {
         self.sched().lock().unwrap().attach(host_tid);
+        self.sched_stat().start();
         self.inner().start();
 
         let eventfd = EventFile::new(
@@ -187,9 +209,12 @@ impl Thread {
             .unwrap();
 
         self.sched().lock().unwrap().detach();
+        self.sched_stat().exit();
 
-        // Remove this thread from its owner process
+        // Remove this thread from its owner process, which keeps its CPU usage
         let mut process_inner = self.process.inner();
+        self.process
+            .account_exited_thread(&self.sched_stat().cpu_usage());
         let threads = process_inner.threads_mut().unwrap();
         let thread_i = threads
             .iter()
@@ -117,9 +117,9 @@ where
 fn wait_event(thread: *const c_void) {
     let mut ret: c_int = 0;
     let mut sgx_ret: c_int = 0;
-    unsafe {
-        sgx_ret = sgx_thread_wait_untrusted_event_ocall(&mut ret as *mut c_int, thread);
-    }
+    sgx_ret = crate::sched::account_wait(|| unsafe {
+        sgx_thread_wait_untrusted_event_ocall(&mut ret as *mut c_int, thread)
+    });
     if ret != 0 || sgx_ret != 0 {
         panic!("ERROR: OCall failed!");
     }
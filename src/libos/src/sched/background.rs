@@ -0,0 +1,198 @@
+//! The background works of the LibOS.
+//!
+//! There is no kernel thread in the LibOS, as every thread in the enclave is
+//! created by the host to run a user thread. The works that are done by kernel
+//! threads on Linux, e.g., syncing the file systems or looking for stuck syscalls,
+//! are registered here with their names, priorities and intervals instead, and are
+//! run by the user threads:
+//!
+//! * A work is run by the thread that starts a syscall first after the interval
+//! of the work. At most one work is run per syscall, i.e., the due work of the
+//! highest priority, so that the latency added to a syscall is bounded.
+//! * All works are run once more when a thread leaves the LibOS, so that nothing
+//! is left undone on shutdown.
+//!
+//! The time of the works is ticked by the interrupt thread of the host, i.e., about
+//! every 25 ms, so that no OCall is needed to check the works in syscalls. A work
+//! is never run more often than the ticks.
+//!
+//! The statistics of the works are exported via `/proc/occlum/background_works`,
+//! one line per work after a header line, i.e.,
+//!
+//! ```text
+//! name priority interval_ms runs run_time_ns
+//! ```
+//!
+//! where the interval is "-" if the work is run only on shutdown.
+
+use crate::prelude::*;
+use crate::time::{do_clock_gettime, ClockID};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// The monotonic time of the last tick, in nanoseconds
+static TICK_TIME: AtomicU64 = AtomicU64::new(0);
+/// The earliest time when any of the works is due, which may be earlier than the
+/// actual one but never later
+static NEXT_DUE_TIME: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref WORKS: Vec<BackgroundWork> = {
+        let mut works = Vec::new();
+        works.extend(crate::syscall::watchdog_work());
+        works.push(crate::fs::sync_work());
+        works.extend(crate::net::unix_relay_work());
+        works
+    };
+}
+
+/// The priority of a background work, which decides the work to run when more than
+/// one works are due
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WorkPriority {
+    Low,
+    High,
+}
+
+impl WorkPriority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WorkPriority::Low => "low",
+            WorkPriority::High => "high",
+        }
+    }
+}
+
+pub struct BackgroundWork {
+    name: &'static str,
+    priority: WorkPriority,
+    /// None means that the work is run only on shutdown
+    interval: Option<Duration>,
+    func: fn() -> Result<()>,
+    // The monotonic time when the work is due next, in nanoseconds
+    next_run_time: AtomicU64,
+    nr_runs: AtomicU64,
+    run_time: AtomicU64,
+}
+
+impl BackgroundWork {
+    pub fn new(
+        name: &'static str,
+        priority: WorkPriority,
+        interval: Option<Duration>,
+        func: fn() -> Result<()>,
+    ) -> Self {
+        let next_run_time = match interval {
+            Some(interval) => now_ns().saturating_add(interval.as_nanos() as u64),
+            None => u64::max_value(),
+        };
+        Self {
+            name,
+            priority,
+            interval,
+            func,
+            next_run_time: AtomicU64::new(next_run_time),
+            nr_runs: AtomicU64::new(0),
+            run_time: AtomicU64::new(0),
+        }
+    }
+
+    /// Claim the work if it is due at `now`, so that it is run by one thread only
+    fn claim(&self, now: u64) -> bool {
+        let interval = match self.interval {
+            Some(interval) => interval.as_nanos() as u64,
+            None => return false,
+        };
+        let next_run_time = self.next_run_time.load(Ordering::Relaxed);
+        next_run_time <= now
+            && self
+                .next_run_time
+                .compare_exchange(
+                    next_run_time,
+                    now.saturating_add(interval),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+    }
+
+    fn run(&self) -> Result<()> {
+        let start = now_ns();
+        let ret = (self.func)();
+        let run_time = now_ns().saturating_sub(start);
+        self.nr_runs.fetch_add(1, Ordering::Relaxed);
+        self.run_time.fetch_add(run_time, Ordering::Relaxed);
+        ret
+    }
+}
+
+/// Advance the time of the works, which is called by the interrupt thread of the
+/// host periodically
+pub fn tick_background_works() {
+    TICK_TIME.store(now_ns(), Ordering::Relaxed);
+}
+
+/// Run the due work of the highest priority, if any
+pub fn run_background_works() {
+    let now = TICK_TIME.load(Ordering::Relaxed);
+    if now < NEXT_DUE_TIME.load(Ordering::Relaxed) {
+        return;
+    }
+    let due_work = WORKS
+        .iter()
+        .filter(|work| work.next_run_time.load(Ordering::Relaxed) <= now)
+        .max_by_key(|work| work.priority);
+    if let Some(work) = due_work {
+        if work.claim(now) {
+            if let Err(e) = work.run() {
+                warn!("the background work {} failed: {}", work.name, e);
+            }
+        }
+    }
+    let next_due_time = WORKS
+        .iter()
+        .map(|work| work.next_run_time.load(Ordering::Relaxed))
+        .min()
+        .unwrap_or(u64::max_value());
+    NEXT_DUE_TIME.store(next_due_time, Ordering::Relaxed);
+}
+
+/// Run all works regardless of their intervals, which is done on shutdown
+pub fn run_all_background_works() -> Result<()> {
+    let mut ret = Ok(());
+    for work in WORKS.iter() {
+        if let Err(e) = work.run() {
+            warn!("the background work {} failed: {}", work.name, e);
+            if ret.is_ok() {
+                ret = Err(e);
+            }
+        }
+    }
+    ret
+}
+
+/// Format the statistics of the background works
+pub fn format_background_works() -> String {
+    let mut content = String::from("name priority interval_ms runs run_time_ns\n");
+    for work in WORKS.iter() {
+        let interval = match work.interval {
+            Some(interval) => interval.as_millis().to_string(),
+            None => "-".to_string(),
+        };
+        content += &format!(
+            "{} {} {} {} {}\n",
+            work.name,
+            work.priority.as_str(),
+            interval,
+            work.nr_runs.load(Ordering::Relaxed),
+            work.run_time.load(Ordering::Relaxed)
+        );
+    }
+    content
+}
+
+fn now_ns() -> u64 {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration().as_nanos() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,67 @@
+use super::sched_stat::CpuUsage;
+use crate::prelude::*;
+use crate::time::timeval_t;
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub struct rusage_t {
+    ru_utime: timeval_t,
+    ru_stime: timeval_t,
+    ru_maxrss: i64,
+    ru_ixrss: i64,
+    ru_idrss: i64,
+    ru_isrss: i64,
+    ru_minflt: i64,
+    ru_majflt: i64,
+    ru_nswap: i64,
+    ru_inblock: i64,
+    ru_oublock: i64,
+    ru_msgsnd: i64,
+    ru_msgrcv: i64,
+    ru_nsignals: i64,
+    ru_nvcsw: i64,
+    ru_nivcsw: i64,
+}
+
+impl From<CpuUsage> for rusage_t {
+    // The time in the enclave cannot be told apart as the user and system time,
+    // so all the run time is reported as the user time
+    fn from(cpu_usage: CpuUsage) -> rusage_t {
+        rusage_t {
+            ru_utime: cpu_usage.run_time.into(),
+            ru_minflt: cpu_usage.nr_page_faults as i64,
+            ru_nvcsw: cpu_usage.nr_waits as i64,
+            ru_nivcsw: cpu_usage.nr_interrupts as i64,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RusageWho {
+    RusageSelf,
+    RusageChildren,
+    RusageThread,
+}
+
+impl RusageWho {
+    pub fn from_raw(who: i32) -> Result<RusageWho> {
+        Ok(match who {
+            0 => RusageWho::RusageSelf,
+            -1 => RusageWho::RusageChildren,
+            1 => RusageWho::RusageThread,
+            _ => return_errno!(EINVAL, "invalid who of getrusage"),
+        })
+    }
+}
+
+pub fn do_getrusage(who: RusageWho) -> rusage_t {
+    let thread = current!();
+    let cpu_usage = match who {
+        RusageWho::RusageSelf => thread.process().cpu_usage(),
+        RusageWho::RusageChildren => thread.process().children_cpu_usage(),
+        RusageWho::RusageThread => thread.sched_stat().cpu_usage(),
+    };
+    cpu_usage.into()
+}
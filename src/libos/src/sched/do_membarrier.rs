@@ -0,0 +1,125 @@
+//! The memory barriers on the threads of processes, i.e., membarrier.
+//!
+//! All the threads of the LibOS are the threads of one host process, which run
+//! in the enclave. So a barrier is done by the private expedited membarrier of
+//! the host process, which interrupts the host threads running on the other CPUs.
+//! An interrupted thread exits the enclave asynchronously, which serializes the
+//! CPU like a full memory barrier. The barrier covers the threads of all the
+//! processes in the enclave, which is more than needed for the private commands,
+//! but never less.
+//!
+//! The host process is registered for the expedited membarrier the first time a
+//! barrier is needed. Like Linux, the private expedited barrier of a process fails
+//! with EPERM unless the process has registered for it, while the global expedited
+//! barrier is only needed if any process has registered for it.
+use crate::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const MEMBARRIER_CMD_QUERY: i32 = 0;
+const MEMBARRIER_CMD_GLOBAL: u32 = 1 << 0;
+const MEMBARRIER_CMD_GLOBAL_EXPEDITED: u32 = 1 << 1;
+const MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED: u32 = 1 << 2;
+const MEMBARRIER_CMD_PRIVATE_EXPEDITED: u32 = 1 << 3;
+const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: u32 = 1 << 4;
+
+const SUPPORTED_CMDS: u32 = MEMBARRIER_CMD_GLOBAL
+    | MEMBARRIER_CMD_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+    | MEMBARRIER_CMD_PRIVATE_EXPEDITED
+    | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED;
+
+lazy_static! {
+    /// Whether the host supports the private expedited membarrier
+    static ref IS_HOST_SUPPORTED: bool = {
+        match host_membarrier(MEMBARRIER_CMD_QUERY) {
+            Ok(host_cmds) => {
+                let needed_cmds =
+                    MEMBARRIER_CMD_PRIVATE_EXPEDITED | MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED;
+                host_cmds as u32 & needed_cmds == needed_cmds
+            }
+            Err(_) => false,
+        }
+    };
+}
+
+// Whether any process has registered for the global expedited membarrier
+static IS_GLOBAL_EXPEDITED_REGISTERED: AtomicBool = AtomicBool::new(false);
+static IS_HOST_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+pub fn do_membarrier(cmd: i32, flags: u32) -> Result<isize> {
+    debug!("do_membarrier cmd: {}, flags: {}", cmd, flags);
+    if flags != 0 {
+        return_errno!(EINVAL, "invalid flags of membarrier");
+    }
+    if cmd == MEMBARRIER_CMD_QUERY {
+        let cmds = if *IS_HOST_SUPPORTED {
+            SUPPORTED_CMDS
+        } else {
+            0
+        };
+        return Ok(cmds as isize);
+    }
+    let cmd = cmd as u32;
+    if !cmd.is_power_of_two() || cmd & SUPPORTED_CMDS == 0 {
+        return_errno!(EINVAL, "invalid command of membarrier");
+    }
+    if !*IS_HOST_SUPPORTED {
+        return_errno!(EINVAL, "membarrier is not supported by the host");
+    }
+
+    let process = current!().process().clone();
+    match cmd {
+        MEMBARRIER_CMD_GLOBAL => do_barrier()?,
+        MEMBARRIER_CMD_GLOBAL_EXPEDITED => {
+            if IS_GLOBAL_EXPEDITED_REGISTERED.load(Ordering::Acquire) {
+                do_barrier()?;
+            }
+        }
+        MEMBARRIER_CMD_PRIVATE_EXPEDITED => {
+            if process.membarrier_registrations() & MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED == 0 {
+                return_errno!(EPERM, "the process has not registered for the command");
+            }
+            do_barrier()?;
+        }
+        MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED => {
+            register_host()?;
+            process.register_membarrier(cmd);
+            IS_GLOBAL_EXPEDITED_REGISTERED.store(true, Ordering::Release);
+        }
+        MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED => {
+            register_host()?;
+            process.register_membarrier(cmd);
+        }
+        _ => unreachable!(),
+    }
+    Ok(0)
+}
+
+fn do_barrier() -> Result<()> {
+    register_host()?;
+    host_membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED as i32)?;
+    Ok(())
+}
+
+// Registering the host process more than once is harmless, so no lock is needed
+fn register_host() -> Result<()> {
+    if !IS_HOST_REGISTERED.load(Ordering::Acquire) {
+        host_membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED as i32)?;
+        IS_HOST_REGISTERED.store(true, Ordering::Release);
+    }
+    Ok(())
+}
+
+fn host_membarrier(cmd: i32) -> Result<i32> {
+    extern "C" {
+        fn occlum_ocall_membarrier(ret: *mut i32, cmd: i32) -> sgx_status_t;
+    }
+
+    let ret = try_libc!({
+        let mut retval: i32 = 0;
+        let status = occlum_ocall_membarrier(&mut retval, cmd);
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    Ok(ret)
+}
@@ -21,7 +21,7 @@ pub fn do_sched_setaffinity(tid: pid_t, new_affinity: CpuSet) -> Result<()> {
     Ok(())
 }
 
-fn get_thread_by_tid(tid: pid_t) -> Result<ThreadRef> {
+pub(super) fn get_thread_by_tid(tid: pid_t) -> Result<ThreadRef> {
     if tid == 0 {
         Ok(current!())
     } else {
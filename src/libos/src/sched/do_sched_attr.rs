@@ -0,0 +1,188 @@
+//! The scheduler policies and parameters of threads, i.e., sched_setattr and
+//! sched_getattr.
+//!
+//! The threads of the LibOS are scheduled by the host as normal threads. So only
+//! the normal policies (SCHED_OTHER, SCHED_BATCH and SCHED_IDLE) are accepted,
+//! which are recorded along with the nice values but not applied to the host
+//! threads. Like an unprivileged thread in Linux, a thread cannot switch to the
+//! real-time policies or SCHED_DEADLINE, which fails with EPERM if the parameters
+//! are valid.
+use super::do_sched_affinity::get_thread_by_tid;
+use crate::prelude::*;
+
+/// The `struct sched_attr` of Linux
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub struct sched_attr_t {
+    pub size: u32,
+    pub sched_policy: u32,
+    pub sched_flags: u64,
+    pub sched_nice: i32,
+    pub sched_priority: u32,
+    pub sched_runtime: u64,
+    pub sched_deadline: u64,
+    pub sched_period: u64,
+    pub sched_util_min: u32,
+    pub sched_util_max: u32,
+}
+
+/// The size of the first version of `struct sched_attr`, without the fields of
+/// the utilization clamping
+pub const SCHED_ATTR_SIZE_VER0: usize = 48;
+
+const SCHED_FLAG_RESET_ON_FORK: u64 = 0x01;
+const SCHED_FLAG_KEEP_POLICY: u64 = 0x08;
+const SCHED_FLAG_KEEP_PARAMS: u64 = 0x10;
+const SCHED_FLAG_UTIL_CLAMP: u64 = 0x20 | 0x40;
+const SCHED_FLAG_ALL: u64 = 0x7f;
+
+const MIN_NICE: i32 = -20;
+const MAX_NICE: i32 = 19;
+const MAX_RT_PRIORITY: u32 = 99;
+// The minimal runtime of SCHED_DEADLINE in ns, which is the same as Linux
+const DL_MIN_RUNTIME: u64 = 1 << 10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SchedPolicy {
+    Normal = 0,
+    Fifo = 1,
+    RoundRobin = 2,
+    Batch = 3,
+    Idle = 5,
+    Deadline = 6,
+}
+
+impl SchedPolicy {
+    pub fn from_raw(policy: u32) -> Result<Self> {
+        Ok(match policy {
+            0 => SchedPolicy::Normal,
+            1 => SchedPolicy::Fifo,
+            2 => SchedPolicy::RoundRobin,
+            3 => SchedPolicy::Batch,
+            5 => SchedPolicy::Idle,
+            6 => SchedPolicy::Deadline,
+            _ => return_errno!(EINVAL, "invalid scheduler policy"),
+        })
+    }
+}
+
+/// The scheduler policy and parameters recorded for a thread
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SchedAttr {
+    policy: SchedPolicy,
+    nice: i32,
+    reset_on_fork: bool,
+}
+
+impl SchedAttr {
+    /// The attributes inherited by a child thread. With SCHED_FLAG_RESET_ON_FORK,
+    /// a negative nice value is reset, and the flag is not inherited.
+    pub fn inherit(&self) -> Self {
+        if !self.reset_on_fork {
+            return *self;
+        }
+        Self {
+            policy: self.policy,
+            nice: max(self.nice, 0),
+            reset_on_fork: false,
+        }
+    }
+}
+
+impl Default for SchedAttr {
+    fn default() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            nice: 0,
+            reset_on_fork: false,
+        }
+    }
+}
+
+pub fn do_sched_getattr(tid: pid_t) -> Result<sched_attr_t> {
+    debug!("do_sched_getattr tid: {}", tid);
+    let thread = get_thread_by_tid(tid)?;
+    let attr = *thread.sched().lock().unwrap().attr();
+    let sched_flags = if attr.reset_on_fork {
+        SCHED_FLAG_RESET_ON_FORK
+    } else {
+        0
+    };
+    Ok(sched_attr_t {
+        sched_policy: attr.policy as u32,
+        sched_flags,
+        sched_nice: attr.nice,
+        ..Default::default()
+    })
+}
+
+pub fn do_sched_setattr(tid: pid_t, attr: &sched_attr_t) -> Result<()> {
+    debug!("do_sched_setattr tid: {}, attr: {:?}", tid, attr);
+    let thread = get_thread_by_tid(tid)?;
+    let mut sched = thread.sched().lock().unwrap();
+    let old_attr = *sched.attr();
+
+    let flags = attr.sched_flags;
+    let policy = if flags & SCHED_FLAG_KEEP_POLICY != 0 {
+        old_attr.policy
+    } else {
+        SchedPolicy::from_raw(attr.sched_policy)?
+    };
+    if flags & !SCHED_FLAG_ALL != 0 {
+        return_errno!(EINVAL, "unknown scheduler flags");
+    }
+    match policy {
+        SchedPolicy::Fifo | SchedPolicy::RoundRobin => {
+            if attr.sched_priority == 0 || attr.sched_priority > MAX_RT_PRIORITY {
+                return_errno!(EINVAL, "invalid real-time priority");
+            }
+            return_errno!(EPERM, "the real-time policies are not supported");
+        }
+        SchedPolicy::Deadline => {
+            check_deadline_params(attr)?;
+            return_errno!(EPERM, "SCHED_DEADLINE is not supported");
+        }
+        _ => {
+            if attr.sched_priority != 0 {
+                return_errno!(EINVAL, "the priority must be 0 for the normal policies");
+            }
+        }
+    }
+    if flags & SCHED_FLAG_UTIL_CLAMP != 0 {
+        return_errno!(EOPNOTSUPP, "the utilization clamping is not supported");
+    }
+
+    let nice = if flags & SCHED_FLAG_KEEP_PARAMS != 0 {
+        old_attr.nice
+    } else {
+        // Like Linux, an out-of-range nice value is clamped
+        min(max(attr.sched_nice, MIN_NICE), MAX_NICE)
+    };
+    sched.set_attr(SchedAttr {
+        policy,
+        nice,
+        reset_on_fork: flags & SCHED_FLAG_RESET_ON_FORK != 0,
+    });
+    Ok(())
+}
+
+// Like Linux, the deadline must be nonzero, and runtime <= deadline <= period,
+// where a zero period means the same as the deadline
+fn check_deadline_params(attr: &sched_attr_t) -> Result<()> {
+    let period = if attr.sched_period == 0 {
+        attr.sched_deadline
+    } else {
+        attr.sched_period
+    };
+    let is_valid = attr.sched_deadline != 0
+        && attr.sched_runtime >= DL_MIN_RUNTIME
+        && attr.sched_deadline & (1 << 63) == 0
+        && period & (1 << 63) == 0
+        && attr.sched_runtime <= attr.sched_deadline
+        && attr.sched_deadline <= period;
+    if !is_valid {
+        return_errno!(EINVAL, "invalid parameters of SCHED_DEADLINE");
+    }
+    Ok(())
+}
@@ -1,11 +1,25 @@
 /// CPU scheduling for threads.
+mod background;
 mod cpu_set;
 mod do_getcpu;
+mod do_getrusage;
+mod do_membarrier;
 mod do_sched_affinity;
+mod do_sched_attr;
 mod do_sched_yield;
 mod sched_agent;
+mod sched_stat;
+mod sgx_stat;
 mod syscalls;
 
+pub use background::{
+    format_background_works, run_all_background_works, run_background_works, tick_background_works,
+    BackgroundWork, WorkPriority,
+};
 pub use cpu_set::NCORES;
+pub use do_getrusage::rusage_t;
+pub use do_sched_attr::sched_attr_t;
 pub use sched_agent::SchedAgent;
+pub use sched_stat::{account_wait, format_sched_stat, CpuUsage, SchedStat};
+pub use sgx_stat::format_sgx_stats;
 pub use syscalls::*;
@@ -2,8 +2,10 @@
 //!
 //! # Scheduler Settings
 //!
-//! Currently, the only scheduler setting that SchedAgent can access and update
-//! is the CPU affinity of a thread. Other settings will be added in the future.
+//! Currently, the scheduler settings that SchedAgent can access and update are
+//! the CPU affinity and the scheduler attributes (i.e., the policy and the nice
+//! value) of a thread. The scheduler attributes are only recorded by SchedAgent,
+//! as the host threads are always scheduled as normal threads.
 //!
 //! # The Two Modes: Attached vs Detached
 //!
@@ -22,6 +24,7 @@
 //! host OS thread.
 
 use super::cpu_set::{CpuSet, AVAIL_CPUSET};
+use super::do_sched_attr::SchedAttr;
 use crate::prelude::*;
 use crate::util::dirty::Dirty;
 
@@ -31,6 +34,7 @@ pub struct SchedAgent {
     // the invariant of `inner.is_some() == true`. We use Option so that we can
     // move the Inner out of SchedAgent without upsetting Rust's borrow checker.
     inner: Option<Inner>,
+    attr: SchedAttr,
 }
 
 impl Clone for SchedAgent {
@@ -57,6 +61,7 @@ impl Clone for SchedAgent {
         }
         Self {
             inner: Some(Inner::Detached { affinity }),
+            attr: self.attr.inherit(),
         }
     }
 }
@@ -73,7 +78,10 @@ impl SchedAgent {
             let affinity = Dirty::new(AVAIL_CPUSET.clone());
             Inner::Detached { affinity }
         });
-        Self { inner }
+        Self {
+            inner,
+            attr: SchedAttr::default(),
+        }
     }
 
     pub fn host_tid(&self) -> Option<pid_t> {
@@ -112,6 +120,14 @@ impl SchedAgent {
         Ok(())
     }
 
+    pub fn attr(&self) -> &SchedAttr {
+        &self.attr
+    }
+
+    pub fn set_attr(&mut self, new_attr: SchedAttr) {
+        self.attr = new_attr;
+    }
+
     pub fn attach(&mut self, host_tid: pid_t) {
         self.update_inner(|inner| match inner {
             Inner::Detached { affinity } => {
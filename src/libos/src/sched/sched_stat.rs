@@ -0,0 +1,224 @@
+//! SchedStat records the scheduling statistics of a thread.
+//!
+//! The statistics are exported via `/proc/[pid]/schedstat` and
+//! `/proc/[pid]/task/[tid]/schedstat` in the same format as Linux, i.e.,
+//!
+//! ```text
+//! <run time in ns> <wait time in ns> <number of enclave exits>
+//! ```
+//!
+//! where the wait time is the time that a thread spends on blocking outside the
+//! enclave (e.g., waiting for futexes, I/O events or timers), the run time is the
+//! rest of the time since the thread started, and the enclave exits are those
+//! caused by blocking OCalls, interrupts and exceptions. Note that the third field
+//! is the number of time slices on Linux.
+
+use crate::prelude::*;
+use crate::time::{do_clock_gettime, ClockID};
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use core::time::Duration;
+
+#[derive(Debug, Default)]
+pub struct SchedStat {
+    // The monotonic time when the thread starts, in nanoseconds. Zero means not started.
+    start_time: AtomicU64,
+    // The monotonic time when the thread exits, in nanoseconds. Zero means not exited.
+    exit_time: AtomicU64,
+    wait_time: AtomicU64,
+    nr_waits: AtomicU64,
+    nr_exits: AtomicU64,
+    nr_interrupts: AtomicU64,
+    nr_exceptions: AtomicU64,
+    nr_page_faults: AtomicU64,
+    // The monotonic time since when the thread has been in the syscall without any
+    // expected wait, which is watched by the watchdog. Zero means not watched.
+    watch_start: AtomicU64,
+    watched_syscall_num: AtomicU32,
+    // Whether the watched syscall has been reported as stuck
+    stuck: AtomicBool,
+}
+
+impl SchedStat {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn start(&self) {
+        self.start_time.store(now_ns(), Ordering::Relaxed);
+    }
+
+    pub fn exit(&self) {
+        self.exit_time.store(now_ns(), Ordering::Relaxed);
+    }
+
+    /// Account the time spent in `f` as the wait time.
+    pub fn account_wait<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        // The wait is expected, so it is not watched
+        let watch_start = self.watch_start.swap(0, Ordering::Relaxed);
+        let wait_start = now_ns();
+        let ret = f();
+        let wait_end = now_ns();
+        if watch_start != 0 {
+            self.watch_start.store(wait_end, Ordering::Relaxed);
+        }
+        self.wait_time
+            .fetch_add(wait_end.saturating_sub(wait_start), Ordering::Relaxed);
+        self.nr_waits.fetch_add(1, Ordering::Relaxed);
+        self.nr_exits.fetch_add(1, Ordering::Relaxed);
+        ret
+    }
+
+    /// Account an enclave exit that is not caused by a blocking OCall.
+    pub fn account_exit(&self) {
+        self.nr_exits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account an enclave exit caused by an interrupt.
+    pub fn account_interrupt(&self) {
+        self.account_exit();
+        self.nr_interrupts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Account an enclave exit caused by an exception, which may be a page fault.
+    pub fn account_exception(&self, is_page_fault: bool) {
+        self.account_exit();
+        self.nr_exceptions.fetch_add(1, Ordering::Relaxed);
+        if is_page_fault {
+            self.nr_page_faults.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Start watching the syscall of the number.
+    pub fn watch_syscall(&self, num: u32) {
+        self.watched_syscall_num.store(num, Ordering::Relaxed);
+        self.stuck.store(false, Ordering::Relaxed);
+        self.watch_start.store(now_ns(), Ordering::Relaxed);
+    }
+
+    /// Stop watching the syscall, and return the time since the last expected wait.
+    pub fn unwatch_syscall(&self) -> Duration {
+        match self.watch_start.swap(0, Ordering::Relaxed) {
+            0 => Duration::new(0, 0),
+            watch_start => Duration::from_nanos(now_ns().saturating_sub(watch_start)),
+        }
+    }
+
+    /// The number of the watched syscall and the time since the last expected wait,
+    /// if the thread is in a watched syscall and not waiting.
+    pub fn watched_syscall(&self) -> Option<(u32, Duration)> {
+        match self.watch_start.load(Ordering::Relaxed) {
+            0 => None,
+            watch_start => Some((
+                self.watched_syscall_num.load(Ordering::Relaxed),
+                Duration::from_nanos(now_ns().saturating_sub(watch_start)),
+            )),
+        }
+    }
+
+    /// Mark the watched syscall as stuck, and return whether it was not marked.
+    pub fn mark_stuck(&self) -> bool {
+        !self.stuck.swap(true, Ordering::Relaxed)
+    }
+
+    pub fn is_stuck(&self) -> bool {
+        self.stuck.load(Ordering::Relaxed)
+    }
+
+    pub fn run_time(&self) -> Duration {
+        let start_time = self.start_time.load(Ordering::Relaxed);
+        if start_time == 0 {
+            return Duration::new(0, 0);
+        }
+        let end_time = match self.exit_time.load(Ordering::Relaxed) {
+            0 => now_ns(),
+            exit_time => exit_time,
+        };
+        let total_time = end_time.saturating_sub(start_time);
+        Duration::from_nanos(total_time.saturating_sub(self.wait_time.load(Ordering::Relaxed)))
+    }
+
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_nanos(self.wait_time.load(Ordering::Relaxed))
+    }
+
+    pub fn nr_waits(&self) -> u64 {
+        self.nr_waits.load(Ordering::Relaxed)
+    }
+
+    pub fn nr_exits(&self) -> u64 {
+        self.nr_exits.load(Ordering::Relaxed)
+    }
+
+    pub fn nr_interrupts(&self) -> u64 {
+        self.nr_interrupts.load(Ordering::Relaxed)
+    }
+
+    pub fn nr_exceptions(&self) -> u64 {
+        self.nr_exceptions.load(Ordering::Relaxed)
+    }
+
+    pub fn nr_page_faults(&self) -> u64 {
+        self.nr_page_faults.load(Ordering::Relaxed)
+    }
+
+    pub fn cpu_usage(&self) -> CpuUsage {
+        CpuUsage {
+            run_time: self.run_time(),
+            nr_waits: self.nr_waits(),
+            nr_interrupts: self.nr_interrupts(),
+            nr_page_faults: self.nr_page_faults(),
+        }
+    }
+}
+
+/// The CPU usage of a thread or a group of threads, which is reported by getrusage
+/// and the CPU-time clocks.
+///
+/// The waits are the voluntary context switches, while the interrupts are the
+/// involuntary ones.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CpuUsage {
+    pub run_time: Duration,
+    pub nr_waits: u64,
+    pub nr_interrupts: u64,
+    pub nr_page_faults: u64,
+}
+
+impl CpuUsage {
+    pub fn add(&mut self, other: &CpuUsage) {
+        self.run_time += other.run_time;
+        self.nr_waits += other.nr_waits;
+        self.nr_interrupts += other.nr_interrupts;
+        self.nr_page_faults += other.nr_page_faults;
+    }
+}
+
+/// Account the time spent in `f` as the wait time of the current thread.
+pub fn account_wait<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    current!().sched_stat().account_wait(f)
+}
+
+/// Format the schedstat of a thread or a group of threads.
+pub fn format_sched_stat<'a>(stats: impl Iterator<Item = &'a SchedStat>) -> String {
+    let (run_time, wait_time, nr_exits) =
+        stats.fold((0, 0, 0), |(run_time, wait_time, nr_exits), stat| {
+            (
+                run_time + stat.run_time().as_nanos(),
+                wait_time + stat.wait_time().as_nanos(),
+                nr_exits + stat.nr_exits(),
+            )
+        });
+    format!("{} {} {}\n", run_time, wait_time, nr_exits)
+}
+
+fn now_ns() -> u64 {
+    do_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .map(|ts| ts.as_duration().as_nanos() as u64)
+        .unwrap_or(0)
+}
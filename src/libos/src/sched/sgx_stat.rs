@@ -0,0 +1,83 @@
+//! The statistics of the enclave exits of threads, for performance tuning.
+//!
+//! The statistics are exported via `/proc/occlum/sgxstats`, one line per thread
+//! after a header line, i.e.,
+//!
+//! ```text
+//! tid host_tid exits interrupts exceptions page_faults host_minflt host_majflt host_nivcsw
+//! ```
+//!
+//! where the exits, interrupts, exceptions and page faults (only reported by SGX 2)
+//! are the enclave exits that the LibOS is aware of. Most asynchronous enclave exits
+//! (AEX) are handled by the host transparently, e.g., those caused by EPC paging or
+//! by preemption. They are counted by the host as the page faults and the
+//! involuntary context switches of the host thread, which are given by the host
+//! and thus untrusted. The host fields are "-" if they are unavailable, e.g., when
+//! the thread is not running.
+//!
+//! Many host page faults with a high wait time in schedstat are the sign of EPC
+//! thrashing, rather than the slowness of the application itself.
+
+use crate::prelude::*;
+use crate::process::ThreadRef;
+
+/// Format the sgxstats of the threads
+pub fn format_sgx_stats(threads: &[ThreadRef]) -> String {
+    let mut content = String::from(
+        "tid host_tid exits interrupts exceptions page_faults host_minflt host_majflt host_nivcsw\n",
+    );
+    for thread in threads {
+        let stat = thread.sched_stat();
+        let host_tid = thread.sched().lock().unwrap().host_tid();
+        let (host_tid, host_stats) = match host_tid {
+            Some(host_tid) => match get_host_thread_stats(host_tid) {
+                Ok((minflt, majflt, nivcsw)) => (
+                    host_tid.to_string(),
+                    format!("{} {} {}", minflt, majflt, nivcsw),
+                ),
+                Err(_) => (host_tid.to_string(), "- - -".to_string()),
+            },
+            None => ("-".to_string(), "- - -".to_string()),
+        };
+        content += &format!(
+            "{} {} {} {} {} {} {}\n",
+            thread.tid(),
+            host_tid,
+            stat.nr_exits(),
+            stat.nr_interrupts(),
+            stat.nr_exceptions(),
+            stat.nr_page_faults(),
+            host_stats
+        );
+    }
+    content
+}
+
+/// Get the minor page faults, major page faults and involuntary context switches
+/// of the host thread
+fn get_host_thread_stats(host_tid: pid_t) -> Result<(u64, u64, u64)> {
+    let (mut minflt, mut majflt, mut nivcsw) = (0, 0, 0);
+    try_libc!({
+        let mut retval: i32 = 0;
+        let status = occlum_ocall_get_host_thread_stats(
+            &mut retval,
+            host_tid as i32,
+            &mut minflt,
+            &mut majflt,
+            &mut nivcsw,
+        );
+        assert!(status == sgx_status_t::SGX_SUCCESS);
+        retval
+    });
+    Ok((minflt, majflt, nivcsw))
+}
+
+extern "C" {
+    fn occlum_ocall_get_host_thread_stats(
+        ret: *mut i32,
+        host_tid: i32,
+        minflt: *mut u64,
+        majflt: *mut u64,
+        nivcsw: *mut u64,
+    ) -> sgx_status_t;
+}
@@ -1,6 +1,9 @@
 use super::cpu_set::{CpuSet, AVAIL_CPUSET};
+use super::do_getrusage::{rusage_t, RusageWho};
+use super::do_sched_attr::{sched_attr_t, SCHED_ATTR_SIZE_VER0};
 use crate::prelude::*;
 use crate::util::mem_util::from_user::*;
+use crate::vm::PAGE_SIZE;
 
 pub fn do_sched_yield() -> Result<isize> {
     super::do_sched_yield::do_sched_yield();
@@ -83,3 +86,90 @@ pub fn do_getcpu(cpu_ptr: *mut u32, node_ptr: *mut u32) -> Result<isize> {
     }
     Ok(0)
 }
+
+pub fn do_getrusage(who: i32, buf: *mut rusage_t) -> Result<isize> {
+    let who = RusageWho::from_raw(who)?;
+    check_mut_ptr(buf)?;
+    let rusage = super::do_getrusage::do_getrusage(who);
+    unsafe {
+        buf.write(rusage);
+    }
+    Ok(0)
+}
+
+pub fn do_sched_getattr(
+    pid: pid_t,
+    attr_ptr: *mut sched_attr_t,
+    size: u32,
+    flags: u32,
+) -> Result<isize> {
+    let size = size as usize;
+    if pid < 0 || flags != 0 || size < SCHED_ATTR_SIZE_VER0 || size > PAGE_SIZE {
+        return_errno!(EINVAL, "invalid arguments of sched_getattr");
+    }
+    if attr_ptr.is_null() {
+        return_errno!(EINVAL, "attr ptr must NOT be null");
+    }
+    // Like Linux, only the fields known by both the user and the LibOS are copied,
+    // whose size is reported in the size field
+    let size = min(size, std::mem::size_of::<sched_attr_t>());
+    check_mut_array(attr_ptr as *mut u8, size)?;
+    let mut attr = super::do_sched_attr::do_sched_getattr(pid)?;
+    attr.size = size as u32;
+    unsafe {
+        let src = std::slice::from_raw_parts(&attr as *const _ as *const u8, size);
+        std::slice::from_raw_parts_mut(attr_ptr as *mut u8, size).copy_from_slice(src);
+    }
+    Ok(0)
+}
+
+pub fn do_sched_setattr(pid: pid_t, attr_ptr: *mut sched_attr_t, flags: u32) -> Result<isize> {
+    if pid < 0 || flags != 0 {
+        return_errno!(EINVAL, "invalid arguments of sched_setattr");
+    }
+    if attr_ptr.is_null() {
+        return_errno!(EINVAL, "attr ptr must NOT be null");
+    }
+    // The size field tells the version of the struct given by the user
+    let size_ptr = attr_ptr as *mut u32;
+    check_mut_ptr(size_ptr)?;
+    let size = match unsafe { size_ptr.read() } as usize {
+        0 => SCHED_ATTR_SIZE_VER0,
+        size => size,
+    };
+    let ksize = std::mem::size_of::<sched_attr_t>();
+    let is_too_big = if size < SCHED_ATTR_SIZE_VER0 || size > PAGE_SIZE {
+        true
+    } else if size > ksize {
+        // A larger struct is accepted if the fields unknown to the LibOS are zeros
+        let tail_ptr = unsafe { (attr_ptr as *const u8).add(ksize) };
+        check_array(tail_ptr, size - ksize)?;
+        let tail = unsafe { std::slice::from_raw_parts(tail_ptr, size - ksize) };
+        tail.iter().any(|&byte| byte != 0)
+    } else {
+        false
+    };
+    if is_too_big {
+        // Like Linux, the size of the struct known by the LibOS is reported back
+        unsafe {
+            size_ptr.write(ksize as u32);
+        }
+        return_errno!(E2BIG, "the size of sched_attr is unexpected");
+    }
+
+    let size = min(size, ksize);
+    check_array(attr_ptr as *const u8, size)?;
+    let mut attr = sched_attr_t::default();
+    unsafe {
+        let src = std::slice::from_raw_parts(attr_ptr as *const u8, size);
+        std::slice::from_raw_parts_mut(&mut attr as *mut _ as *mut u8, size).copy_from_slice(src);
+    }
+    super::do_sched_attr::do_sched_setattr(pid, &attr)?;
+    Ok(0)
+}
+
+pub fn do_membarrier(cmd: i32, flags: u32, _cpu_id: i32) -> Result<isize> {
+    // The CPU ID is only used by the commands of restartable sequences, which are
+    // not supported
+    super::do_membarrier::do_membarrier(cmd, flags)
+}
@@ -22,6 +22,30 @@ pub fn do_kill(filter: ProcessFilter, signum: SigNum) -> Result<()> {
     Ok(())
 }
 
+/// Send a signal to the process that a pidfd refers to.
+///
+/// Unlike `do_kill`, it fails if the process has exited. And a signal number of
+/// zero only checks whether the process is still alive.
+pub fn do_kill_process(process: &ProcessRef, signum: Option<SigNum>) -> Result<()> {
+    debug!(
+        "do_kill_process: pid: {:?}, signum: {:?}",
+        process.pid(),
+        &signum
+    );
+
+    if process.status() == ProcessStatus::Zombie {
+        return_errno!(ESRCH, "the process has exited");
+    }
+    if let Some(signum) = signum {
+        let pid = current!().process().pid();
+        let uid = 0;
+        let signal = Box::new(UserSignal::new(signum, UserSignalKind::Kill, pid, uid));
+        let mut sig_queues = process.sig_queues().write().unwrap();
+        sig_queues.enqueue(signal);
+    }
+    Ok(())
+}
+
 /// Send a signal from the outside the enclave.
 ///
 /// Such a call must be performed very carefully. The obvious reason
@@ -101,3 +125,24 @@ pub fn do_tgkill(pid: Option<pid_t>, tid: pid_t, signum: SigNum) -> Result<()> {
     sig_queues.enqueue(signal);
     Ok(())
 }
+
+/// Send SIGPIPE to the current thread, as Linux does when a thread writes to a pipe
+/// or a stream socket whose peer is closed.
+///
+/// The signal is discarded on delivery if it is ignored by the process.
+pub fn send_sigpipe() {
+    let signal = Box::new(KernelSignal::new(SIGPIPE));
+    let thread = current!();
+    let mut sig_queues = thread.sig_queues().write().unwrap();
+    sig_queues.enqueue(signal);
+}
+
+/// Send SIGPIPE to the current thread if the result of a write is EPIPE
+pub fn send_sigpipe_on_epipe<T>(result: Result<T>) -> Result<T> {
+    if let Err(e) = &result {
+        if e.errno() == EPIPE {
+            send_sigpipe();
+        }
+    }
+    result
+}
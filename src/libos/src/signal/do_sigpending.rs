@@ -11,3 +11,17 @@ pub fn do_sigpending() -> Result<SigSet> {
         & *thread.sig_mask().read().unwrap();
     Ok(pending)
 }
+
+/// Check whether the current thread has pending signals that can be delivered, which
+/// interrupt the blocking syscalls
+pub fn has_deliverable_signals() -> bool {
+    let thread = current!();
+    let process = thread.process();
+    if process.is_forced_to_exit() {
+        return true;
+    }
+    let pending = thread.sig_queues().read().unwrap().pending()
+        | process.sig_queues().read().unwrap().pending();
+    let blocked = *thread.sig_mask().read().unwrap() | *thread.sig_tmp_mask().read().unwrap();
+    !(pending & !blocked).empty()
+}
@@ -41,6 +41,34 @@ pub fn do_rt_sigprocmask(
     Ok(())
 }
 
+/// Replace the signal mask of the current thread until the end of the current syscall.
+///
+/// If the syscall is interrupted by a signal that is unblocked by the new mask, the
+/// original mask is restored after the signal is delivered, so that the signal
+/// handler sees the original mask in its ucontext. Otherwise, the syscall should
+/// restore the original mask by itself with `restore_sig_mask`.
+pub fn replace_sig_mask(mut new_mask: SigSet) {
+    new_mask -= SIGKILL;
+    new_mask -= SIGSTOP;
+
+    let thread = current!();
+    let mut sig_mask = thread.sig_mask().write().unwrap();
+    let mut saved_mask = thread.sig_saved_mask().write().unwrap();
+    if saved_mask.is_none() {
+        *saved_mask = Some(*sig_mask);
+    }
+    *sig_mask = new_mask;
+}
+
+/// Restore the signal mask of the current thread that is replaced by `replace_sig_mask`
+pub fn restore_sig_mask() {
+    let thread = current!();
+    let mut sig_mask = thread.sig_mask().write().unwrap();
+    if let Some(saved_mask) = thread.sig_saved_mask().write().unwrap().take() {
+        *sig_mask = saved_mask;
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u32)]
 pub enum MaskOp {
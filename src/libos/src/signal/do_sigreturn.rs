@@ -62,7 +62,8 @@ pub fn do_rt_sigreturn(curr_user_ctxt: &mut CpuContext) -> Result<()> {
 /// **Requirement.** This must be called only once during the execution of a
 /// syscall and at a very late stage.
 ///
-/// **Post-condition.** The temporary signal mask of the current thread is cleared.
+/// **Post-condition.** The temporary signal mask of the current thread is cleared,
+/// and the signal mask saved by the syscall, if any, is restored.
 pub fn deliver_signal(cpu_context: &mut CpuContext) {
     let thread = current!();
     let process = thread.process();
@@ -71,6 +72,10 @@ pub fn deliver_signal(cpu_context: &mut CpuContext) {
         do_deliver_signal(&thread, &process, cpu_context);
     }
 
+    // The saved signal mask is taken over by the ucontext if a signal handler is
+    // set up. Otherwise, it is restored here.
+    super::restore_sig_mask();
+
     // Ensure the tmp signal mask is cleared before sysret
     let mut tmp_sig_mask = thread.sig_tmp_mask().write().unwrap();
     *tmp_sig_mask = SigSet::new_empty();
@@ -205,7 +210,14 @@ fn handle_signals_by_user(
 ) -> Result<()> {
     let old_sigmask = {
         let mut sigmask = thread.sig_mask().write().unwrap();
-        let old_sigmask = *sigmask;
+        // The signal mask to be restored by sigreturn is the one saved by the
+        // syscall that replaces the signal mask, if any
+        let old_sigmask = thread
+            .sig_saved_mask()
+            .write()
+            .unwrap()
+            .take()
+            .unwrap_or(*sigmask);
         *sigmask = new_sigmask;
         if !flags.contains(SigActionFlags::SA_NODEFER) {
             // Block the current signal while executing the signal handler
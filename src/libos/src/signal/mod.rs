@@ -4,9 +4,11 @@ use crate::prelude::*;
 
 use sig_action::{SigAction, SigActionFlags, SigDefaultAction};
 
-pub use self::c_types::{sigaction_t, sigset_t, stack_t};
+pub use self::c_types::{sigaction_t, siginfo_t, sigset_t, stack_t};
 pub use self::constants::*;
-pub use self::do_kill::do_kill_from_outside_enclave;
+pub use self::do_kill::{do_kill_from_outside_enclave, send_sigpipe, send_sigpipe_on_epipe};
+pub use self::do_sigpending::has_deliverable_signals;
+pub use self::do_sigprocmask::{replace_sig_mask, restore_sig_mask};
 pub use self::do_sigreturn::{deliver_signal, force_signal};
 pub use self::sig_dispositions::SigDispositions;
 pub use self::sig_num::SigNum;
@@ -1,9 +1,9 @@
 use super::constants::*;
 use super::do_sigprocmask::MaskOp;
 use super::signals::FaultSignal;
-use super::{sigaction_t, sigset_t, stack_t, SigAction, SigNum, SigSet, SigStack};
+use super::{sigaction_t, siginfo_t, sigset_t, stack_t, SigAction, SigNum, SigSet, SigStack};
 use crate::prelude::*;
-use crate::process::ProcessFilter;
+use crate::process::{AsPidFd, ProcessFilter};
 use crate::syscall::CpuContext;
 use crate::util::mem_util::from_user;
 
@@ -77,6 +77,29 @@ pub fn do_tgkill(pid: i32, tid: pid_t, sig: c_int) -> Result<isize> {
     Ok(0)
 }
 
+pub fn do_pidfd_send_signal(
+    pidfd: FileDesc,
+    sig: c_int,
+    info: *const siginfo_t,
+    flags: u32,
+) -> Result<isize> {
+    if flags != 0 {
+        return_errno!(EINVAL, "invalid flags");
+    }
+    if !info.is_null() {
+        return_errno!(EINVAL, "siginfo is not supported");
+    }
+    let file_ref = current!().file(pidfd)?;
+    let pidfd = file_ref.as_pidfd()?;
+    let signum = if sig == 0 {
+        None
+    } else {
+        Some(SigNum::from_u8(sig as u8)?)
+    };
+    super::do_kill::do_kill_process(pidfd.process(), signum)?;
+    Ok(0)
+}
+
 pub fn do_rt_sigprocmask(
     how: c_int,
     set_ptr: *const sigset_t,
@@ -4,7 +4,7 @@
 //!
 //! 1. Libc calls `__occlum_syscall` (in `syscall_entry_x86_64.S`)
 //! 2. Do user/LibOS switch and then call `occlum_syscall` (in this file)
-//! 3. Preprocess the system call and then call `dispatch_syscall` (in this file)
+//! 3. Preprocess the system call and then call its handler in `SYSCALL_TABLE` (in this file)
 //! 4. Call `do_*` to process the system call (in other modules)
 
 use aligned::{Aligned, A16};
@@ -16,45 +16,73 @@ use std::ffi::{CStr, CString};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::mem::MaybeUninit;
 use std::ptr;
-use time::{clockid_t, timespec_t, timeval_t};
+use time::{clockid_t, timespec_t, timeval_t, timex_t};
 use util::log::{self, LevelFilter};
 use util::mem_util::from_user::*;
 
+use crate::config::LIBOS_CONFIG;
 use crate::exception::do_handle_exception;
 use crate::fs::{
     do_access, do_chdir, do_chmod, do_chown, do_close, do_dup, do_dup2, do_dup3, do_eventfd,
-    do_eventfd2, do_faccessat, do_fchmod, do_fchmodat, do_fchown, do_fchownat, do_fcntl,
-    do_fdatasync, do_fstat, do_fstatat, do_fsync, do_ftruncate, do_getcwd, do_getdents64, do_ioctl,
+    do_eventfd2, do_faccessat, do_fallocate, do_fchmod, do_fchmodat, do_fchown, do_fchownat,
+    do_fcntl, do_fdatasync, do_fstat, do_fstatat, do_fsync, do_ftruncate, do_getcwd, do_getdents64,
+    do_inotify_add_watch, do_inotify_init, do_inotify_init1, do_inotify_rm_watch, do_ioctl,
     do_lchown, do_link, do_linkat, do_lseek, do_lstat, do_mkdir, do_mkdirat, do_open, do_openat,
     do_pipe, do_pipe2, do_pread, do_pwrite, do_read, do_readlink, do_readlinkat, do_readv,
     do_rename, do_renameat, do_rmdir, do_sendfile, do_stat, do_symlink, do_symlinkat, do_sync,
-    do_truncate, do_unlink, do_unlinkat, do_write, do_writev, iovec_t, File, FileDesc, FileRef,
-    HostStdioFds, Stat,
+    do_truncate, do_unlink, do_unlinkat, do_write, do_writev, iovec_t, report_security_event, File,
+    FileDesc, FileRef, HostStdioFds, SecurityEventKind, Stat,
 };
 use crate::interrupt::{do_handle_interrupt, sgx_interrupt_info_t};
 use crate::misc::{resource_t, rlimit_t, sysinfo_t, utsname_t};
 use crate::net::{
-    do_accept, do_accept4, do_bind, do_connect, do_epoll_create, do_epoll_create1, do_epoll_ctl,
-    do_epoll_pwait, do_epoll_wait, do_getpeername, do_getsockname, do_getsockopt, do_listen,
-    do_poll, do_recvfrom, do_recvmsg, do_select, do_sendmsg, do_sendto, do_setsockopt, do_shutdown,
-    do_socket, do_socketpair, msghdr, msghdr_mut, AsSocket, AsUnixSocket, EpollEvent, PollEvent,
-    SocketFile, UnixSocketFile,
+    addrinfo_hints, do_accept, do_accept4, do_bind, do_connect, do_epoll_create, do_epoll_create1,
+    do_epoll_ctl, do_epoll_pwait, do_epoll_pwait2, do_epoll_wait, do_getaddrinfo, do_getpeername,
+    do_getsockname, do_getsockopt, do_listen, do_poll, do_ppoll, do_recvfrom, do_recvmsg,
+    do_select, do_sendmsg, do_sendto, do_setsockopt, do_shutdown, do_socket, do_socketpair,
+    flush_pending_sends, msghdr, msghdr_mut, occlum_addrinfo, AsSocket, AsUnixSocket, EpollEvent,
+    PollEvent, SocketFile, UnixSocketFile,
 };
 use crate::process::{
     do_arch_prctl, do_clone, do_exit, do_exit_group, do_futex, do_getegid, do_geteuid, do_getgid,
-    do_getpgid, do_getpid, do_getppid, do_gettid, do_getuid, do_prctl, do_set_tid_address,
-    do_spawn, do_wait4, pid_t, FdOp, ThreadStatus,
+    do_getpgid, do_getpid, do_getppid, do_gettid, do_getuid, do_kcmp, do_pidfd_open, do_prctl,
+    do_putenv, do_set_tid_address, do_spawn, do_wait4, pid_t, FdOp, ThreadStatus,
+};
+use crate::sched::{
+    do_getcpu, do_getrusage, do_membarrier, do_sched_getaffinity, do_sched_getattr,
+    do_sched_setaffinity, do_sched_setattr, do_sched_yield, rusage_t, sched_attr_t,
 };
-use crate::sched::{do_getcpu, do_sched_getaffinity, do_sched_setaffinity, do_sched_yield};
 use crate::signal::{
-    do_kill, do_rt_sigaction, do_rt_sigpending, do_rt_sigprocmask, do_rt_sigreturn, do_sigaltstack,
-    do_tgkill, do_tkill, sigaction_t, sigset_t, stack_t,
+    do_kill, do_pidfd_send_signal, do_rt_sigaction, do_rt_sigpending, do_rt_sigprocmask,
+    do_rt_sigreturn, do_sigaltstack, do_tgkill, do_tkill, sigaction_t, siginfo_t, sigset_t,
+    stack_t,
 };
-use crate::vm::{MMapFlags, MRemapFlags, MSyncFlags, VMPerms};
+use crate::vm::{MAdvice, MMapFlags, MRemapFlags, MSyncFlags, VMPerms};
 use crate::{fs, process, std, vm};
 
 use super::*;
 
+#[cfg(any(
+    feature = "syscall_trace",
+    feature = "syscall_replay",
+    feature = "libos_test"
+))]
+pub mod trace;
+mod watchdog;
+
+pub use self::watchdog::watchdog_work;
+
+/// Do the OCall on the host, which is called for every OCall at the boundary (see
+/// ocall_boundary.c) if the OCalls are neither recorded nor replayed
+#[cfg(not(any(feature = "syscall_trace", feature = "syscall_replay")))]
+#[no_mangle]
+pub extern "C" fn occlum_trace_ocall(index: u32, ms: *mut u8, _ms_size: usize) -> sgx_status_t {
+    extern "C" {
+        fn occlum_real_ocall(index: u32, ms: *mut u8) -> sgx_status_t;
+    }
+    unsafe { occlum_real_ocall(index, ms) }
+}
+
 /// System call table defined in a macro.
 ///
 /// To keep the info about system calls in a centralized place and avoid redundant code, the system
@@ -112,7 +140,7 @@ macro_rules! process_syscall_table_with_callback {
             (Mremap = 25) => do_mremap(old_addr: usize, old_size: usize, new_size: usize, flags: i32, new_addr: usize),
             (Msync = 26) => do_msync(addr: usize, size: usize, flags: u32),
             (Mincore = 27) => handle_unsupported(),
-            (Madvise = 28) => handle_unsupported(),
+            (Madvise = 28) => do_madvise(addr: usize, len: usize, advice: i32),
             (Shmget = 29) => handle_unsupported(),
             (Shmat = 30) => handle_unsupported(),
             (Shmctl = 31) => handle_unsupported(),
@@ -160,8 +188,8 @@ macro_rules! process_syscall_table_with_callback {
             (Flock = 73) => handle_unsupported(),
             (Fsync = 74) => do_fsync(fd: FileDesc),
             (Fdatasync = 75) => do_fdatasync(fd: FileDesc),
-            (Truncate = 76) => do_truncate(path: *const i8, len: usize),
-            (Ftruncate = 77) => do_ftruncate(fd: FileDesc, len: usize),
+            (Truncate = 76) => do_truncate(path: *const i8, len: off_t),
+            (Ftruncate = 77) => do_ftruncate(fd: FileDesc, len: off_t),
             (Getdents = 78) => handle_unsupported(),
             (Getcwd = 79) => do_getcwd(buf: *mut u8, size: usize),
             (Chdir = 80) => do_chdir(path: *const i8),
@@ -182,7 +210,7 @@ macro_rules! process_syscall_table_with_callback {
             (Umask = 95) => handle_unsupported(),
             (Gettimeofday = 96) => do_gettimeofday(tv_u: *mut timeval_t),
             (Getrlimit = 97) => handle_unsupported(),
-            (Getrusage = 98) => handle_unsupported(),
+            (Getrusage = 98) => do_getrusage(who: i32, buf: *mut rusage_t),
             (SysInfo = 99) => do_sysinfo(info: *mut sysinfo_t),
             (Times = 100) => handle_unsupported(),
             (Ptrace = 101) => handle_unsupported(),
@@ -243,7 +271,7 @@ macro_rules! process_syscall_table_with_callback {
             (SysCtl = 156) => handle_unsupported(),
             (Prctl = 157) => do_prctl(option: i32, arg2: u64, arg3: u64, arg4: u64, arg5: u64),
             (ArchPrctl = 158) => do_arch_prctl(code: u32, addr: *mut usize),
-            (Adjtimex = 159) => handle_unsupported(),
+            (Adjtimex = 159) => do_adjtimex(buf_u: *mut timex_t),
             (Setrlimit = 160) => handle_unsupported(),
             (Chroot = 161) => handle_unsupported(),
             (Sync = 162) => do_sync(),
@@ -337,9 +365,9 @@ macro_rules! process_syscall_table_with_callback {
             (Keyctl = 250) => handle_unsupported(),
             (IoprioSet = 251) => handle_unsupported(),
             (IoprioGet = 252) => handle_unsupported(),
-            (InotifyInit = 253) => handle_unsupported(),
-            (InotifyAddWatch = 254) => handle_unsupported(),
-            (InotifyRmWatch = 255) => handle_unsupported(),
+            (InotifyInit = 253) => do_inotify_init(),
+            (InotifyAddWatch = 254) => do_inotify_add_watch(fd: FileDesc, path: *const i8, mask: u32),
+            (InotifyRmWatch = 255) => do_inotify_rm_watch(fd: FileDesc, wd: i32),
             (MigratePages = 256) => handle_unsupported(),
             (Openat = 257) => do_openat(dirfd: i32, path: *const i8, flags: u32, mode: u32),
             (Mkdirat = 258) => do_mkdirat(dirfd: i32, path: *const i8, mode: usize),
@@ -355,7 +383,7 @@ macro_rules! process_syscall_table_with_callback {
             (Fchmodat = 268) => do_fchmodat(dirfd: i32, path: *const i8, mode: u16, flags: i32),
             (Faccessat = 269) => do_faccessat(dirfd: i32, path: *const i8, mode: u32, flags: u32),
             (Pselect6 = 270) => handle_unsupported(),
-            (Ppoll = 271) => handle_unsupported(),
+            (Ppoll = 271) => do_ppoll(fds: *mut PollEvent, nfds: libc::nfds_t, timeout: *mut timespec_t, sigmask: *const sigset_t, sigsetsize: usize),
             (Unshare = 272) => handle_unsupported(),
             (SetRobustList = 273) => handle_unsupported(),
             (GetRobustList = 274) => handle_unsupported(),
@@ -365,11 +393,11 @@ macro_rules! process_syscall_table_with_callback {
             (Vmsplice = 278) => handle_unsupported(),
             (MovePages = 279) => handle_unsupported(),
             (Utimensat = 280) => handle_unsupported(),
-            (EpollPwait = 281) => do_epoll_pwait(epfd: c_int, events: *mut libc::epoll_event, maxevents: c_int, timeout: c_int, sigmask: *const usize),
+            (EpollPwait = 281) => do_epoll_pwait(epfd: c_int, events: *mut libc::epoll_event, maxevents: c_int, timeout: c_int, sigmask: *const sigset_t, sigsetsize: usize),
             (Signalfd = 282) => handle_unsupported(),
             (TimerfdCreate = 283) => handle_unsupported(),
             (Eventfd = 284) => do_eventfd(init_val: u32),
-            (Fallocate = 285) => handle_unsupported(),
+            (Fallocate = 285) => do_fallocate(fd: FileDesc, mode: u32, offset: off_t, len: off_t),
             (TimerfdSettime = 286) => handle_unsupported(),
             (TimerfdGettime = 287) => handle_unsupported(),
             (Accept4 = 288) => do_accept4(fd: c_int, addr: *mut libc::sockaddr, addr_len: *mut libc::socklen_t, flags: c_int),
@@ -378,7 +406,7 @@ macro_rules! process_syscall_table_with_callback {
             (EpollCreate1 = 291) => do_epoll_create1(flags: c_int),
             (Dup3 = 292) => do_dup3(old_fd: FileDesc, new_fd: FileDesc, flags: u32),
             (Pipe2 = 293) => do_pipe2(fds_u: *mut i32, flags: u32),
-            (InotifyInit1 = 294) => handle_unsupported(),
+            (InotifyInit1 = 294) => do_inotify_init1(flags: i32),
             (Preadv = 295) => handle_unsupported(),
             (Pwritev = 296) => handle_unsupported(),
             (RtTgsigqueueinfo = 297) => handle_unsupported(),
@@ -389,17 +417,17 @@ macro_rules! process_syscall_table_with_callback {
             (Prlimit64 = 302) => do_prlimit(pid: pid_t, resource: u32, new_limit: *const rlimit_t, old_limit: *mut rlimit_t),
             (NameToHandleAt = 303) => handle_unsupported(),
             (OpenByHandleAt = 304) => handle_unsupported(),
-            (ClockAdjtime = 305) => handle_unsupported(),
+            (ClockAdjtime = 305) => do_clock_adjtime(clockid: clockid_t, buf_u: *mut timex_t),
             (Syncfs = 306) => handle_unsupported(),
             (Sendmmsg = 307) => handle_unsupported(),
             (Setns = 308) => handle_unsupported(),
             (Getcpu = 309) => do_getcpu(cpu_ptr: *mut u32, node_ptr: *mut u32),
             (ProcessVmReadv = 310) => handle_unsupported(),
             (ProcessVmWritev = 311) => handle_unsupported(),
-            (Kcmp = 312) => handle_unsupported(),
+            (Kcmp = 312) => do_kcmp(pid1: pid_t, pid2: pid_t, kcmp_type: i32, idx1: u64, idx2: u64),
             (FinitModule = 313) => handle_unsupported(),
-            (SchedSetattr = 314) => handle_unsupported(),
-            (SchedGetattr = 315) => handle_unsupported(),
+            (SchedSetattr = 314) => do_sched_setattr(pid: pid_t, attr: *mut sched_attr_t, flags: u32),
+            (SchedGetattr = 315) => do_sched_getattr(pid: pid_t, attr: *mut sched_attr_t, size: u32, flags: u32),
             (Renameat2 = 316) => handle_unsupported(),
             (Seccomp = 317) => handle_unsupported(),
             (Getrandom = 318) => handle_unsupported(),
@@ -407,14 +435,19 @@ macro_rules! process_syscall_table_with_callback {
             (KexecFileLoad = 320) => handle_unsupported(),
             (Bpf = 321) => handle_unsupported(),
             (Execveat = 322) => handle_unsupported(),
-            (Userfaultfd = 323) => handle_unsupported(),
-            (Membarrier = 324) => handle_unsupported(),
+            (Userfaultfd = 323) => do_userfaultfd(flags: u32),
+            (Membarrier = 324) => do_membarrier(cmd: i32, flags: u32, cpu_id: i32),
             (Mlock2 = 325) => handle_unsupported(),
+            (PidfdSendSignal = 424) => do_pidfd_send_signal(pidfd: FileDesc, sig: c_int, info: *const siginfo_t, flags: u32),
+            (PidfdOpen = 434) => do_pidfd_open(pid: i32, flags: u32),
+            (EpollPwait2 = 441) => do_epoll_pwait2(epfd: c_int, events: *mut libc::epoll_event, maxevents: c_int, timeout: *const timespec_t, sigmask: *const sigset_t, sigsetsize: usize),
 
             // Occlum-specific system calls
             (Spawn = 360) => do_spawn(child_pid_ptr: *mut u32, path: *const i8, argv: *const *const i8, envp: *const *const i8, fdop_list: *const FdOp),
             (HandleException = 361) => do_handle_exception(info: *mut sgx_exception_info_t, fpregs: *mut FpRegs, context: *mut CpuContext),
             (HandleInterrupt = 362) => do_handle_interrupt(info: *mut sgx_interrupt_info_t, fpregs: *mut FpRegs, context: *mut CpuContext),
+            (Putenv = 363) => do_putenv(string: *const i8),
+            (Getaddrinfo = 364) => do_getaddrinfo(node: *const i8, service: *const i8, hints: *const addrinfo_hints, res: *mut occlum_addrinfo, max_res: usize),
         }
     };
 }
@@ -450,6 +483,14 @@ macro_rules! impl_syscall_nums {
                     )*
                 }
             }
+
+            /// Look up the system call by its name in libc, e.g., "rt_sigaction"
+            pub fn from_libc_name(libc_name: &str) -> Option<Self> {
+                [$(Self::$name),*]
+                    .iter()
+                    .copied()
+                    .find(|num| num.libc_name() == libc_name)
+            }
         }
 
         impl TryFrom<u32> for SyscallNum {
@@ -492,10 +533,25 @@ macro_rules! impl_syscall_nums {
 /// Generate system call numbers.
 process_syscall_table_with_callback!(impl_syscall_nums);
 
+impl SyscallNum {
+    /// The name of the system call in libc, i.e., the name in snake case
+    pub fn libc_name(&self) -> String {
+        let mut libc_name = String::new();
+        for (char_i, c) in self.as_str().chars().enumerate() {
+            if c.is_ascii_uppercase() && char_i > 0 {
+                libc_name.push('_');
+            }
+            libc_name.push(c.to_ascii_lowercase());
+        }
+        libc_name
+    }
+}
+
 /// A struct that represents a system call
 struct Syscall {
     num: SyscallNum,
     args: [isize; 6],
+    handler: SyscallHandler,
 }
 
 impl Syscall {
@@ -508,9 +564,16 @@ impl Syscall {
         arg4: isize,
         arg5: isize,
     ) -> Result<Self> {
-        let num = SyscallNum::try_from(num)?;
+        let (num, handler) = SYSCALL_TABLE
+            .get(num as usize)
+            .and_then(|entry| *entry)
+            .ok_or_else(|| errno!(SyscallNumError::new(num)))?;
+        if DENIED_SYSCALLS[num as usize] {
+            report_security_event(SecurityEventKind::SyscallDenied, &num.libc_name());
+            return_errno!(EPERM, "the system call is denied by the config");
+        }
         let args = [arg0, arg1, arg2, arg3, arg4, arg5];
-        Ok(Self { num, args })
+        Ok(Self { num, args, handler })
     }
 }
 
@@ -554,39 +617,87 @@ macro_rules! impl_fmt_syscall {
 }
 process_syscall_table_with_callback!(impl_fmt_syscall);
 
-/// Generate the code that can dispatch any system call to its actual implementation function.
+/// The handler of a system call, which decodes the arguments of the system call
+/// and then calls its implementation function.
+type SyscallHandler = fn(&[isize; 6]) -> Result<isize>;
+
+/// Generate the system call table, which maps system call numbers to their handlers.
+///
+/// The table is a flat array indexed by system call numbers, which is built at
+/// compile time. So looking up a system call is only a bounds check, and then its
+/// handler, in which the decoding of the arguments is inlined, is called directly
+/// instead of matching the system call number again.
 macro_rules! impl_dispatch_syscall {
-    (@do_syscall $fn:ident, $syscall:ident, $arg_i:expr, ($(,)?) -> ($($output:tt)*) ) => {
-        impl_dispatch_syscall!(@as_expr $fn($($output)*));
+    (@do_syscall $fn:ident, $args:ident, $arg_i:expr, ($(,)?) -> ($($output:tt)*) ) => {
+        impl_dispatch_syscall!(@as_expr $fn($($output)*))
     };
-    (@do_syscall $fn:ident, $syscall:ident, $arg_i:expr, ($_arg_name:ident : $arg_type:ty, $($more_args:tt)*) -> ($($output:tt)*)) => {
-        impl_dispatch_syscall!(@do_syscall $fn, $syscall, ($arg_i + 1), ($($more_args)*) -> ($($output)* ($syscall.args[$arg_i] as $arg_type),));
+    (@do_syscall $fn:ident, $args:ident, $arg_i:expr, ($_arg_name:ident : $arg_type:ty, $($more_args:tt)*) -> ($($output:tt)*)) => {
+        impl_dispatch_syscall!(@do_syscall $fn, $args, ($arg_i + 1), ($($more_args)*) -> ($($output)* ($args[$arg_i] as $arg_type),))
     };
     (@as_expr $e:expr) => { $e };
 
     ($( ( $name:ident = $num:expr ) => $fn:ident ( $($args:tt)* ) ),+,) => {
-        fn dispatch_syscall(syscall: Syscall) -> Result<isize> {
-            match syscall.num {
-                #![deny(unreachable_patterns)]
-                $(
-                    // Expands into something like below:
-                    //
-                    // SyscallNum::Read => {
-                    //     let fd = self.args[0] as FileDesc;
-                    //     let buf = self.args[1] as *mut u8;
-                    //     let size = self.args[2] as usize;
-                    //     do_read(fd, buf, size)
-                    // }
-                    SyscallNum::$name => {
-                        impl_dispatch_syscall!(@do_syscall $fn, syscall, 0, ($($args)*,) -> ())
-                    },
-                )*
+        /// The length of the system call table, i.e., the max system call number plus one
+        const SYSCALL_TABLE_LEN: usize = {
+            let nums: &[usize] = &[$(SyscallNum::$name as usize),*];
+            let mut max_num = 0;
+            let mut num_dups = 0;
+            let mut i = 0;
+            while i < nums.len() {
+                if nums[i] > max_num {
+                    max_num = nums[i];
+                }
+                let mut j = i + 1;
+                while j < nums.len() {
+                    if nums[i] == nums[j] {
+                        num_dups += 1;
+                    }
+                    j += 1;
+                }
+                i += 1;
             }
-        }
+            // A duplicate number would silently replace the handler of another system
+            // call in the table. So fail the build by indexing out of bounds, which is
+            // an error in const evaluation.
+            max_num + 1 + [0][num_dups]
+        };
+
+        static SYSCALL_TABLE: [Option<(SyscallNum, SyscallHandler)>; SYSCALL_TABLE_LEN] = {
+            let mut table = [None; SYSCALL_TABLE_LEN];
+            $(
+                // Expands into something like below:
+                //
+                // table[SyscallNum::Read as usize] = Some((SyscallNum::Read, {
+                //     fn handler(args: &[isize; 6]) -> Result<isize> {
+                //         do_read(args[0] as FileDesc, args[1] as *mut u8, args[2] as usize)
+                //     }
+                //     handler
+                // }));
+                table[SyscallNum::$name as usize] = Some((SyscallNum::$name, {
+                    fn handler(args: &[isize; 6]) -> Result<isize> {
+                        impl_dispatch_syscall!(@do_syscall $fn, args, 0, ($($args)*,) -> ())
+                    }
+                    handler as SyscallHandler
+                }));
+            )*
+            table
+        };
     }
 }
 process_syscall_table_with_callback!(impl_dispatch_syscall);
 
+lazy_static! {
+    /// Whether each system call is denied by the syscall denylist in the config,
+    /// which is indexed like SYSCALL_TABLE
+    static ref DENIED_SYSCALLS: [bool; SYSCALL_TABLE_LEN] = {
+        let mut denied_syscalls = [false; SYSCALL_TABLE_LEN];
+        for num in &LIBOS_CONFIG.process.syscall_denylist {
+            denied_syscalls[*num as usize] = true;
+        }
+        denied_syscalls
+    };
+}
+
 #[no_mangle]
 pub extern "C" fn occlum_syscall(user_context: *mut CpuContext) -> ! {
     // Start a new round of log messages for this system call. But we do not
@@ -623,22 +734,26 @@ fn do_syscall(user_context: &mut CpuContext) {
 
         // Pass user_context as an extra argument to two special syscalls that
         // need to modify it
-        if syscall_num == SyscallNum::RtSigreturn {
-            syscall.args[0] = user_context as *mut _ as isize;
-        } else if syscall_num == SyscallNum::HandleException {
-            // syscall.args[0] == info
-            // syscall.args[1] == fpregs
-            syscall.args[2] = user_context as *mut _ as isize;
-        } else if syscall.num == SyscallNum::HandleInterrupt {
-            // syscall.args[0] == info
-            // syscall.args[1] == fpregs
-            syscall.args[2] = user_context as *mut _ as isize;
-        } else if syscall.num == SyscallNum::Sigaltstack {
-            // syscall.args[0] == new_ss
-            // syscall.args[1] == old_ss
-            syscall.args[2] = user_context as *const _ as isize;
+        match syscall_num {
+            SyscallNum::RtSigreturn => {
+                syscall.args[0] = user_context as *mut _ as isize;
+            }
+            SyscallNum::HandleException | SyscallNum::HandleInterrupt => {
+                // syscall.args[0] == info
+                // syscall.args[1] == fpregs
+                syscall.args[2] = user_context as *mut _ as isize;
+            }
+            SyscallNum::Sigaltstack => {
+                // syscall.args[0] == new_ss
+                // syscall.args[1] == old_ss
+                syscall.args[2] = user_context as *const _ as isize;
+            }
+            _ => {}
         }
 
+        #[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+        trace::trace_syscall_enter(num, &syscall.args);
+
         #[cfg(feature = "syscall_timing")]
         current!()
             .profiler()
@@ -649,7 +764,11 @@ fn do_syscall(user_context: &mut CpuContext) {
             .syscall_enter(syscall_num)
             .expect("unexpected error from profiler to enter syscall");
 
-        let ret = dispatch_syscall(syscall);
+        watchdog::syscall_enter(num);
+        flush_pending_sends(syscall_num, syscall.args[0]);
+        crate::sched::run_background_works();
+        let ret = (syscall.handler)(&syscall.args);
+        let ret = watchdog::syscall_exit(&syscall, ret);
 
         #[cfg(feature = "syscall_timing")]
         current!()
@@ -681,7 +800,7 @@ fn do_syscall(user_context: &mut CpuContext) {
                 // TODO: use a smarter, frequency-based strategy to decide whether
                 // to suppress error messages.
                 match errno {
-                    EAGAIN | ETIMEDOUT => false,
+                    EAGAIN | ETIMEDOUT | EINPROGRESS | EALREADY => false,
                     _ => true,
                 }
             };
@@ -696,6 +815,9 @@ fn do_syscall(user_context: &mut CpuContext) {
     };
     trace!("Retval = {:?}", retval);
 
+    #[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+    trace::trace_syscall_exit(num, retval);
+
     // Put the return value into user_context.rax, except for syscalls that may
     // modify user_context directly. Currently, there are three such syscalls:
     // SigReturn, HandleException, and HandleInterrupt.
@@ -793,6 +915,17 @@ fn do_mprotect(addr: usize, len: usize, perms: u32) -> Result<isize> {
     Ok(0)
 }
 
+fn do_madvise(addr: usize, len: usize, advice: i32) -> Result<isize> {
+    let advice = MAdvice::from_i32(advice)?;
+    vm::do_madvise(addr, len, advice)?;
+    Ok(0)
+}
+
+fn do_userfaultfd(flags: u32) -> Result<isize> {
+    let fd = vm::do_userfaultfd(flags)?;
+    Ok(fd as isize)
+}
+
 fn do_brk(new_brk_addr: usize) -> Result<isize> {
     let ret_brk_addr = vm::do_brk(new_brk_addr)?;
     Ok(ret_brk_addr as isize)
@@ -823,8 +956,11 @@ fn do_gettimeofday(tv_u: *mut timeval_t) -> Result<isize> {
 
 fn do_clock_gettime(clockid: clockid_t, ts_u: *mut timespec_t) -> Result<isize> {
     check_mut_ptr(ts_u)?;
-    let clockid = time::ClockID::from_raw(clockid)?;
-    let ts = time::do_clock_gettime(clockid)?;
+    let ts = if clockid < 0 {
+        time::do_cpu_clock_gettime(clockid)?
+    } else {
+        time::do_clock_gettime(time::ClockID::from_raw(clockid)?)?
+    };
     unsafe {
         *ts_u = ts;
     }
@@ -832,18 +968,35 @@ fn do_clock_gettime(clockid: clockid_t, ts_u: *mut timespec_t) -> Result<isize>
 }
 
 fn do_clock_getres(clockid: clockid_t, res_u: *mut timespec_t) -> Result<isize> {
+    // The clock is checked even if the resolution is not wanted, which is how
+    // clock_getcpuclockid checks the process
+    let res = if clockid < 0 {
+        time::do_cpu_clock_getres(clockid)?
+    } else {
+        time::do_clock_getres(time::ClockID::from_raw(clockid)?)?
+    };
     if res_u.is_null() {
         return Ok(0);
     }
     check_mut_ptr(res_u)?;
-    let clockid = time::ClockID::from_raw(clockid)?;
-    let res = time::do_clock_getres(clockid)?;
     unsafe {
         *res_u = res;
     }
     Ok(0)
 }
 
+fn do_adjtimex(buf_u: *mut timex_t) -> Result<isize> {
+    do_clock_adjtime(time::ClockID::CLOCK_REALTIME as clockid_t, buf_u)
+}
+
+fn do_clock_adjtime(clockid: clockid_t, buf_u: *mut timex_t) -> Result<isize> {
+    check_mut_ptr(buf_u)?;
+    let clockid = time::ClockID::from_raw(clockid)?;
+    let buf = unsafe { &mut *buf_u };
+    let state = time::do_clock_adjtime(clockid, buf)?;
+    Ok(state as isize)
+}
+
 // TODO: handle remainder
 fn do_nanosleep(req_u: *const timespec_t, rem_u: *mut timespec_t) -> Result<isize> {
     let req = {
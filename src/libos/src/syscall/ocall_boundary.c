@@ -0,0 +1,52 @@
+#include <stddef.h>
+#include <sgx_error.h>
+
+/*
+ * The boundary of OCalls, where the OCalls are recorded or replayed with the
+ * syscall_trace or syscall_replay feature (see trace.rs).
+ *
+ * The LibOS is linked with --wrap=sgx_ocall and --wrap=sgx_ocalloc, so every
+ * OCall, including the ones of the SDKs, goes through the wrappers here. The
+ * trusted bridge of an OCall allocates one frame on the untrusted stack by
+ * sgx_ocalloc, which holds the marshalled arguments, the return value, the
+ * errno and the buffers of the [out] parameters, and then calls sgx_ocall with
+ * the frame. So the frame is all that the host gives back to the enclave by an
+ * OCall, except the untrusted memory referred to by [user_check] pointers.
+ */
+
+sgx_status_t __real_sgx_ocall(const unsigned int index, void *ms);
+void *__real_sgx_ocalloc(size_t size);
+
+// Defined by trace.rs with the syscall_trace or syscall_replay feature, or else
+// by mod.rs, which does the OCall on the host directly
+sgx_status_t occlum_trace_ocall(unsigned int index, void *ms, size_t ms_size);
+
+// The last frame allocated by the thread
+static __thread void *last_frame = NULL;
+static __thread size_t last_frame_size = 0;
+// The OCalls made while tracing an OCall (e.g., to write the trace) are not traced
+static __thread int is_tracing = 0;
+
+void *__wrap_sgx_ocalloc(size_t size) {
+    void *frame = __real_sgx_ocalloc(size);
+    last_frame = frame;
+    last_frame_size = frame != NULL ? size : 0;
+    return frame;
+}
+
+sgx_status_t __wrap_sgx_ocall(const unsigned int index, void *ms) {
+    if (is_tracing) {
+        return __real_sgx_ocall(index, ms);
+    }
+    // The OCalls without arguments have no frame
+    size_t ms_size = (ms != NULL && ms == last_frame) ? last_frame_size : 0;
+    is_tracing = 1;
+    sgx_status_t status = occlum_trace_ocall(index, ms, ms_size);
+    is_tracing = 0;
+    return status;
+}
+
+// Do the OCall on the host, which is called by occlum_trace_ocall
+sgx_status_t occlum_real_ocall(unsigned int index, void *ms) {
+    return __real_sgx_ocall(index, ms);
+}
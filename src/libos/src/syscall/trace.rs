@@ -0,0 +1,354 @@
+//! Record and replay of system calls and OCalls.
+//!
+//! With the `syscall_trace` feature, the LibOS records every system call (its
+//! number, arguments and return value) as well as every OCall into a trace file.
+//! With the `syscall_replay` feature, the LibOS loads the trace file and, instead
+//! of calling the host, gives back the recorded results of OCalls. So a bug caused
+//! by unexpected results from the host (e.g., an Iago attack) can be reproduced
+//! without the original host.
+//!
+//! The OCalls are intercepted at the boundary of the enclave (see
+//! ocall_boundary.c), so all the OCalls are covered, including the ones of the
+//! SDKs and the raw `occlum_ocall_*` ones, e.g., of time, network, file systems
+//! and scheduling. What is recorded for an OCall is its frame on the untrusted
+//! stack, which holds the return value, the errno and the buffers written by the
+//! host. The untrusted memory referred to by `[user_check]` pointers, e.g., the
+//! buffers shared with the host for networking, is not recorded.
+//!
+//! The trace file is at `<instance_dir>/build/syscall.trace`. It is encrypted
+//! and integrity-protected by the SGX protected file system with a key derived
+//! from the enclave signer, so only the enclaves of the same signer can read it.
+//!
+//! Note that the recorded OCalls are replayed per thread, in the order they are
+//! recorded, which requires the threads to be created with the same TIDs in the
+//! replay. An OCall that diverges from the trace is done on the host.
+
+use super::*;
+use std::io::{Read, Write};
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+use std::sgxfs::SgxFile;
+
+#[cfg(all(feature = "syscall_trace", feature = "syscall_replay"))]
+compile_error!("syscall_trace and syscall_replay cannot be enabled at the same time");
+
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+extern "C" {
+    fn occlum_real_ocall(index: u32, ms: *mut u8) -> sgx_status_t;
+}
+
+/// Record or replay an OCall, which is called for every OCall at the boundary
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+#[no_mangle]
+pub extern "C" fn occlum_trace_ocall(index: u32, ms: *mut u8, ms_size: usize) -> sgx_status_t {
+    // The trace file is not known until the LibOS is initialized
+    if !crate::entry::has_init() {
+        return unsafe { occlum_real_ocall(index, ms) };
+    }
+    let tid = current!().tid();
+    // The lock of the trace is never held during the OCall, which may block
+    #[cfg(feature = "syscall_trace")]
+    {
+        let status = unsafe { occlum_real_ocall(index, ms) };
+        record_ocall(
+            &mut *TRACE_FILE.lock().unwrap(),
+            tid,
+            index,
+            ms,
+            ms_size,
+            status,
+        );
+        status
+    }
+    #[cfg(feature = "syscall_replay")]
+    {
+        let replayed_status = REPLAY_TRACE
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(|replay_trace| replay_ocall(replay_trace, tid, index, ms, ms_size));
+        replayed_status.unwrap_or_else(|| unsafe { occlum_real_ocall(index, ms) })
+    }
+}
+
+/// Record the OCall that is done with the status into the trace
+pub fn record_ocall<W: Write>(
+    trace: &mut Option<W>,
+    tid: pid_t,
+    index: u32,
+    ms: *const u8,
+    ms_size: usize,
+    status: sgx_status_t,
+) {
+    let frame = unsafe { std::slice::from_raw_parts(ms, ms_size) };
+    let header = TraceRecord::new(
+        RecordKind::Ocall,
+        tid,
+        index,
+        &[status as i64, ms_size as i64],
+    );
+    record(trace, &header, frame);
+}
+
+/// Give back the recorded result of the OCall, or None if the OCall diverges from
+/// the trace, which should be done on the host then
+pub fn replay_ocall(
+    trace: &mut ReplayTrace,
+    tid: pid_t,
+    index: u32,
+    ms: *mut u8,
+    ms_size: usize,
+) -> Option<sgx_status_t> {
+    let record = match trace.next_ocall(tid) {
+        Some(record) => record,
+        None => {
+            warn!("no more recorded OCalls for thread {}", tid);
+            return None;
+        }
+    };
+    if record.index != index || record.frame.len() != ms_size {
+        warn!(
+            "replay diverges in thread {}: OCall {} is expected, but got {}",
+            tid, record.index, index
+        );
+        return None;
+    }
+    let frame = unsafe { std::slice::from_raw_parts_mut(ms, ms_size) };
+    frame.copy_from_slice(&record.frame);
+    Some(record.status)
+}
+
+/// Record the start of a system call
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+pub fn trace_syscall_enter(num: u32, args: &[isize]) {
+    let args: Vec<i64> = args.iter().map(|arg| *arg as i64).collect();
+    let tid = current!().tid();
+    #[cfg(feature = "syscall_trace")]
+    record(
+        &mut *TRACE_FILE.lock().unwrap(),
+        &TraceRecord::new(RecordKind::SyscallEnter, tid, num, &args),
+        &[],
+    );
+    #[cfg(feature = "syscall_replay")]
+    {
+        let mut replay_trace = REPLAY_TRACE.lock().unwrap();
+        let replay_trace = match replay_trace.as_mut() {
+            Some(replay_trace) => replay_trace,
+            None => return,
+        };
+        let recorded_num = replay_trace.next_syscall(tid);
+        if recorded_num != Some(num) {
+            warn!(
+                "replay diverges in thread {}: syscall {} is expected, but got {}",
+                tid,
+                recorded_num.map_or("none".to_string(), |num| num.to_string()),
+                num
+            );
+        }
+    }
+}
+
+/// Record the return value of a system call
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+pub fn trace_syscall_exit(num: u32, retval: isize) {
+    #[cfg(feature = "syscall_trace")]
+    record(
+        &mut *TRACE_FILE.lock().unwrap(),
+        &TraceRecord::new(
+            RecordKind::SyscallExit,
+            current!().tid(),
+            num,
+            &[retval as i64],
+        ),
+        &[],
+    );
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u8)]
+enum RecordKind {
+    SyscallEnter = 1,
+    SyscallExit = 2,
+    // Followed by the frame of the OCall, whose size is the second value
+    Ocall = 3,
+}
+
+/// A record of the trace, which is encoded as 64 bytes
+#[derive(Debug, Copy, Clone)]
+struct TraceRecord {
+    kind: RecordKind,
+    tid: pid_t,
+    num: u32,
+    vals: [i64; RECORD_NUM_VALS],
+}
+
+const RECORD_NUM_VALS: usize = 6;
+const RECORD_SIZE: usize = 64;
+
+impl TraceRecord {
+    fn new(kind: RecordKind, tid: pid_t, num: u32, vals: &[i64]) -> Self {
+        let mut record_vals = [0; RECORD_NUM_VALS];
+        let len = min(vals.len(), RECORD_NUM_VALS);
+        record_vals[..len].copy_from_slice(&vals[..len]);
+        Self {
+            kind,
+            tid,
+            num,
+            vals: record_vals,
+        }
+    }
+
+    fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0] = self.kind as u8;
+        bytes[4..8].copy_from_slice(&self.tid.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.num.to_le_bytes());
+        for (val_i, val) in self.vals.iter().enumerate() {
+            let offset = 16 + val_i * 8;
+            bytes[offset..offset + 8].copy_from_slice(&val.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Result<Self> {
+        let kind = match bytes[0] {
+            1 => RecordKind::SyscallEnter,
+            2 => RecordKind::SyscallExit,
+            3 => RecordKind::Ocall,
+            _ => return_errno!(EINVAL, "invalid kind of trace record"),
+        };
+        let read_u32 = |offset: usize| {
+            let mut buf = [0; 4];
+            buf.copy_from_slice(&bytes[offset..offset + 4]);
+            u32::from_le_bytes(buf)
+        };
+        let mut vals = [0; RECORD_NUM_VALS];
+        for (val_i, val) in vals.iter_mut().enumerate() {
+            let offset = 16 + val_i * 8;
+            let mut buf = [0; 8];
+            buf.copy_from_slice(&bytes[offset..offset + 8]);
+            *val = i64::from_le_bytes(buf);
+        }
+        Ok(Self {
+            kind,
+            tid: read_u32(4) as pid_t,
+            num: read_u32(8),
+            vals,
+        })
+    }
+}
+
+/// A recorded OCall
+#[derive(Debug)]
+struct OcallRecord {
+    index: u32,
+    status: sgx_status_t,
+    frame: Vec<u8>,
+}
+
+/// Write the record and its payload into the trace, which is dropped on failure
+fn record<W: Write>(trace: &mut Option<W>, record: &TraceRecord, payload: &[u8]) {
+    if let Some(writer) = trace.as_mut() {
+        let res = writer
+            .write_all(&record.encode())
+            .and_then(|_| writer.write_all(payload))
+            .and_then(|_| {
+                // Make sure the trace is complete even if the enclave crashes later
+                if record.kind == RecordKind::SyscallExit {
+                    writer.flush()
+                } else {
+                    Ok(())
+                }
+            });
+        if let Err(e) = res {
+            error!("failed to write the trace: {}", e);
+            *trace = None;
+        }
+    }
+}
+
+#[cfg(any(feature = "syscall_trace", feature = "syscall_replay"))]
+fn trace_path() -> String {
+    unsafe { format!("{}{}", crate::entry::INSTANCE_DIR, "/build/syscall.trace") }
+}
+
+#[cfg(feature = "syscall_trace")]
+lazy_static! {
+    static ref TRACE_FILE: SgxMutex<Option<SgxFile>> = {
+        let trace_file = SgxFile::create(trace_path())
+            .map_err(|e| error!("failed to create the trace file: {}", e))
+            .ok();
+        SgxMutex::new(trace_file)
+    };
+}
+
+/// The records loaded from the trace, which are grouped by threads
+pub struct ReplayTrace {
+    syscalls: HashMap<pid_t, VecDeque<u32>>,
+    ocalls: HashMap<pid_t, VecDeque<OcallRecord>>,
+}
+
+impl ReplayTrace {
+    pub fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut trace = Self {
+            syscalls: HashMap::new(),
+            ocalls: HashMap::new(),
+        };
+        let mut bytes = [0; RECORD_SIZE];
+        loop {
+            match reader.read_exact(&mut bytes) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(errno!(e)),
+            }
+            let record = TraceRecord::decode(&bytes)?;
+            match record.kind {
+                RecordKind::SyscallEnter => trace
+                    .syscalls
+                    .entry(record.tid)
+                    .or_default()
+                    .push_back(record.num),
+                RecordKind::Ocall => {
+                    let mut frame = vec![0; record.vals[1] as usize];
+                    reader.read_exact(&mut frame).map_err(|e| errno!(e))?;
+                    trace
+                        .ocalls
+                        .entry(record.tid)
+                        .or_default()
+                        .push_back(OcallRecord {
+                            index: record.num,
+                            // The trace is integrity-protected, so the status
+                            // must be one recorded from sgx_status_t
+                            status: unsafe { std::mem::transmute(record.vals[0] as u32) },
+                            frame,
+                        });
+                }
+                RecordKind::SyscallExit => {}
+            }
+        }
+        Ok(trace)
+    }
+
+    #[cfg(feature = "syscall_replay")]
+    fn next_syscall(&mut self, tid: pid_t) -> Option<u32> {
+        self.syscalls.get_mut(&tid)?.pop_front()
+    }
+
+    fn next_ocall(&mut self, tid: pid_t) -> Option<OcallRecord> {
+        self.ocalls.get_mut(&tid)?.pop_front()
+    }
+}
+
+#[cfg(feature = "syscall_replay")]
+lazy_static! {
+    // The replay is turned off if the trace file cannot be loaded
+    static ref REPLAY_TRACE: SgxMutex<Option<ReplayTrace>> = {
+        let trace = SgxFile::open(trace_path())
+            .map_err(|e| errno!(e))
+            .and_then(ReplayTrace::load)
+            .map_err(|e| {
+                error!("failed to load the trace file: {}; replay is off", e.backtrace());
+            })
+            .ok();
+        SgxMutex::new(trace)
+    };
+}
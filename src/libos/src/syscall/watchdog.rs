@@ -0,0 +1,84 @@
+//! The watchdog of the syscalls stuck in the host.
+//!
+//! A malicious or wedged host may never return from an OCall, which starves the
+//! enclave thread silently. The watchdog reports the syscalls that have been
+//! outside the enclave for longer than the threshold in `Occlum.json`. The expected
+//! waits of the syscalls, e.g., for futexes, I/O events or timers, are accounted by
+//! `sched::account_wait` and are not counted.
+//!
+//! The stuck syscalls of the other threads are looked for by a background work
+//! twice per threshold, so that they are reported even if they never return. A
+//! stuck syscall that returns at last may also fail with EIO if configured, though
+//! its side effects are not undone.
+use super::*;
+use crate::config::LIBOS_CONFIG;
+use crate::process::table;
+use crate::sched::{BackgroundWork, WorkPriority};
+use std::time::Duration;
+
+/// The background work that looks for the stuck syscalls, if the watchdog is enabled
+pub fn watchdog_work() -> Option<BackgroundWork> {
+    let threshold = LIBOS_CONFIG.watchdog.ocall_threshold?;
+    Some(BackgroundWork::new(
+        "watchdog",
+        WorkPriority::High,
+        Some(threshold / 2),
+        check_other_threads,
+    ))
+}
+
+/// Start watching the syscall of the current thread
+pub fn syscall_enter(num: u32) {
+    if LIBOS_CONFIG.watchdog.ocall_threshold.is_none() {
+        return;
+    }
+    current!().sched_stat().watch_syscall(num);
+}
+
+/// Stop watching the syscall of the current thread, and fail it if it is stuck and
+/// the watchdog is configured to do so
+pub fn syscall_exit(syscall: &Syscall, ret: Result<isize>) -> Result<isize> {
+    let threshold = match LIBOS_CONFIG.watchdog.ocall_threshold {
+        Some(threshold) => threshold,
+        None => return ret,
+    };
+    let current = current!();
+    let sched_stat = current.sched_stat();
+    let elapsed = sched_stat.unwatch_syscall();
+    if elapsed > threshold && sched_stat.mark_stuck() {
+        report_stuck_syscall(current.tid(), syscall.num.as_str(), elapsed);
+    }
+    if sched_stat.is_stuck() {
+        error!("the stuck syscall returns: {:?}, ret = {:?}", syscall, ret);
+        if LIBOS_CONFIG.watchdog.fail_stuck_ocalls {
+            return_errno!(EIO, "the syscall was stuck in the host");
+        }
+    }
+    ret
+}
+
+fn check_other_threads() -> Result<()> {
+    let threshold = match LIBOS_CONFIG.watchdog.ocall_threshold {
+        Some(threshold) => threshold,
+        None => return Ok(()),
+    };
+    for thread in table::get_all_threads() {
+        let sched_stat = thread.sched_stat();
+        let (num, elapsed) = match sched_stat.watched_syscall() {
+            Some(watched_syscall) => watched_syscall,
+            None => continue,
+        };
+        if elapsed > threshold && sched_stat.mark_stuck() {
+            let name = SyscallNum::try_from(num).map_or("unknown", |num| num.as_str());
+            report_stuck_syscall(thread.tid(), name, elapsed);
+        }
+    }
+    Ok(())
+}
+
+fn report_stuck_syscall(tid: pid_t, name: &str, elapsed: Duration) {
+    error!(
+        "the syscall {} of thread {} has been stuck in the host for {:?}",
+        name, tid, elapsed
+    );
+}
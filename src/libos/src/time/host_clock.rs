@@ -0,0 +1,68 @@
+//! The guard of the realtime clock of the host.
+//!
+//! The time is read from the host, which may step its realtime clock at will, e.g.,
+//! to make the expired certificates appear valid. If a max adjustment is given in
+//! `Occlum.json`, the realtime clock may drift from the monotonic clock by at most
+//! the max adjustment since the LibOS reads it first. A reading beyond that is
+//! clamped, and the clock is reported to be unsynchronized by adjtimex.
+use super::*;
+use crate::config::LIBOS_CONFIG;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+lazy_static! {
+    /// The realtime minus the monotonic time of the first reading, in nanoseconds
+    static ref BASE_OFFSET: i128 = {
+        let realtime = host_clock_gettime(ClockID::CLOCK_REALTIME).as_duration();
+        realtime.as_nanos() as i128 - monotonic_ns()
+    };
+}
+
+/// Whether the last reading of the realtime clock is clamped
+static IS_CLAMPED: AtomicBool = AtomicBool::new(false);
+
+/// Check the realtime read from the host, which is clamped if the clock has been
+/// adjusted by more than the max adjustment
+pub fn guard_realtime(realtime: Duration) -> Duration {
+    let max_adjustment = match LIBOS_CONFIG.time.max_host_clock_adjustment {
+        Some(max_adjustment) => max_adjustment.as_nanos() as i128,
+        None => return realtime,
+    };
+    let base_offset = *BASE_OFFSET;
+    let monotonic = monotonic_ns();
+    let adjustment = adjustment_of(realtime, monotonic);
+    if adjustment.abs() <= max_adjustment {
+        IS_CLAMPED.store(false, Ordering::Relaxed);
+        return realtime;
+    }
+    if !IS_CLAMPED.swap(true, Ordering::Relaxed) {
+        warn!(
+            "the realtime clock of the host is adjusted by {} ns, which is clamped",
+            adjustment
+        );
+    }
+    let clamped = monotonic + base_offset + adjustment.signum() * max_adjustment;
+    Duration::from_nanos(max(clamped, 0) as u64)
+}
+
+/// Whether the realtime clock is within the max adjustment, which is unknown if
+/// there is no max adjustment
+pub fn is_synced() -> bool {
+    LIBOS_CONFIG.time.max_host_clock_adjustment.is_some() && !IS_CLAMPED.load(Ordering::Relaxed)
+}
+
+/// The estimated offset of the realtime clock of the host, i.e., how much it has
+/// been adjusted since the LibOS reads it first, in nanoseconds
+pub fn host_offset_ns() -> i128 {
+    let realtime = host_clock_gettime(ClockID::CLOCK_REALTIME).as_duration();
+    adjustment_of(realtime, monotonic_ns())
+}
+
+fn adjustment_of(realtime: Duration, monotonic: i128) -> i128 {
+    realtime.as_nanos() as i128 - monotonic - *BASE_OFFSET
+}
+
+fn monotonic_ns() -> i128 {
+    host_clock_gettime(ClockID::CLOCK_MONOTONIC)
+        .as_duration()
+        .as_nanos() as i128
+}
@@ -1,5 +1,6 @@
 use self::timer_slack::*;
 use super::*;
+use crate::config::LIBOS_CONFIG;
 use core::convert::TryFrom;
 use process::pid_t;
 use rcore_fs::dev::TimeProvider;
@@ -8,6 +9,7 @@ use std::time::Duration;
 use std::{fmt, u64};
 use syscall::SyscallNum;
 
+mod host_clock;
 mod profiler;
 pub mod timer_slack;
 pub mod up_time;
@@ -52,6 +54,15 @@ impl timeval_t {
     }
 }
 
+impl From<Duration> for timeval_t {
+    fn from(duration: Duration) -> timeval_t {
+        timeval_t {
+            sec: duration.as_secs() as time_t,
+            usec: duration.subsec_micros() as suseconds_t,
+        }
+    }
+}
+
 pub fn do_gettimeofday() -> timeval_t {
     extern "C" {
         fn occlum_ocall_gettimeofday(tv: *mut timeval_t) -> sgx_status_t;
@@ -62,7 +73,7 @@ pub fn do_gettimeofday() -> timeval_t {
         occlum_ocall_gettimeofday(&mut tv as *mut timeval_t);
     }
     tv.validate().expect("ocall returned invalid timeval_t");
-    tv
+    host_clock::guard_realtime(tv.as_duration()).into()
 }
 
 #[repr(C)]
@@ -101,6 +112,15 @@ impl timespec_t {
     }
 }
 
+impl From<Duration> for timespec_t {
+    fn from(duration: Duration) -> timespec_t {
+        timespec_t {
+            sec: duration.as_secs() as time_t,
+            nsec: duration.subsec_nanos() as i64,
+        }
+    }
+}
+
 #[allow(non_camel_case_types)]
 pub type clockid_t = i32;
 
@@ -135,6 +155,69 @@ impl ClockID {
 }
 
 pub fn do_clock_gettime(clockid: ClockID) -> Result<timespec_t> {
+    // The CPU time is the run time of the LibOS threads, rather than that of
+    // the host threads or of the enclave
+    match clockid {
+        ClockID::CLOCK_PROCESS_CPUTIME_ID => {
+            return Ok(current!().process().cpu_usage().run_time.into());
+        }
+        ClockID::CLOCK_THREAD_CPUTIME_ID => {
+            return Ok(current!().sched_stat().run_time().into());
+        }
+        _ => {}
+    }
+    let tv = host_clock_gettime(clockid);
+    match clockid {
+        ClockID::CLOCK_REALTIME | ClockID::CLOCK_REALTIME_COARSE => {
+            Ok(host_clock::guard_realtime(tv.as_duration()).into())
+        }
+        _ => Ok(tv),
+    }
+}
+
+/// Get the CPU time of a process or a thread by a CPU-time clock, whose ID is
+/// negative, e.g., the one given by clock_getcpuclockid or pthread_getcpuclockid
+pub fn do_cpu_clock_gettime(clockid: clockid_t) -> Result<timespec_t> {
+    Ok(cpu_clock_run_time(clockid)?.into())
+}
+
+pub fn do_cpu_clock_getres(clockid: clockid_t) -> Result<timespec_t> {
+    cpu_clock_run_time(clockid)?;
+    Ok(timespec_t { sec: 0, nsec: 1 })
+}
+
+fn cpu_clock_run_time(clockid: clockid_t) -> Result<Duration> {
+    const CPUCLOCK_PERTHREAD_MASK: clockid_t = 4;
+    const CPUCLOCK_CLOCK_MASK: clockid_t = 3;
+    // CPUCLOCK_PROF, CPUCLOCK_VIRT and CPUCLOCK_SCHED are the same in the enclave
+    if clockid >= 0 || clockid & CPUCLOCK_CLOCK_MASK == CPUCLOCK_CLOCK_MASK {
+        return_errno!(EINVAL, "invalid CPU-time clock");
+    }
+    // The ID of the process or the thread, where 0 means the current one
+    let id = !(clockid >> 3);
+    let current = current!();
+    if clockid & CPUCLOCK_PERTHREAD_MASK != 0 {
+        let thread = match id {
+            0 => current.clone(),
+            tid => process::table::get_thread(tid)
+                .map_err(|_| errno!(EINVAL, "the thread of the clock is not found"))?,
+        };
+        // Like Linux, only the threads of the current process can be measured
+        if thread.process().pid() != current.process().pid() {
+            return_errno!(EINVAL, "the thread of the clock is of another process");
+        }
+        Ok(thread.sched_stat().run_time())
+    } else {
+        let process = match id {
+            0 => current.process().clone(),
+            pid => process::table::get_process(pid)
+                .map_err(|_| errno!(EINVAL, "the process of the clock is not found"))?,
+        };
+        Ok(process.cpu_usage().run_time)
+    }
+}
+
+fn host_clock_gettime(clockid: ClockID) -> timespec_t {
     extern "C" {
         fn occlum_ocall_clock_gettime(clockid: clockid_t, tp: *mut timespec_t) -> sgx_status_t;
     }
@@ -144,7 +227,7 @@ pub fn do_clock_gettime(clockid: ClockID) -> Result<timespec_t> {
         occlum_ocall_clock_gettime(clockid as clockid_t, &mut tv as *mut timespec_t);
     }
     tv.validate().expect("ocall returned invalid timespec");
-    Ok(tv)
+    tv
 }
 
 pub fn do_clock_getres(clockid: ClockID) -> Result<timespec_t> {
@@ -169,6 +252,76 @@ pub fn do_clock_getres(clockid: ClockID) -> Result<timespec_t> {
     Ok(res)
 }
 
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub struct timex_t {
+    modes: u32,
+    offset: i64,
+    freq: i64,
+    maxerror: i64,
+    esterror: i64,
+    status: i32,
+    constant: i64,
+    precision: i64,
+    tolerance: i64,
+    time: timeval_t,
+    tick: i64,
+    ppsfreq: i64,
+    jitter: i64,
+    shift: i32,
+    stabil: i64,
+    jitcnt: i64,
+    calcnt: i64,
+    errcnt: i64,
+    stbcnt: i64,
+    tai: i32,
+    padding: [i32; 11],
+}
+
+/// Report the state of the realtime clock, which is read-only in the enclave.
+/// The offset is how much the host has adjusted its clock, and the frequency is
+/// never corrected by the LibOS. Return TIME_OK if the clock is within the max
+/// adjustment, or TIME_ERROR.
+pub fn do_clock_adjtime(clockid: ClockID, buf: &mut timex_t) -> Result<i32> {
+    const ADJ_OFFSET_SS_READ: u32 = 0xa001;
+    const STA_UNSYNC: i32 = 0x0040;
+    const TIME_OK: i32 = 0;
+    const TIME_ERROR: i32 = 5;
+    // The max error when the clock is unsynchronized, which is the same as Linux
+    const NTP_PHASE_LIMIT_US: i64 = 16_000_000;
+
+    match clockid {
+        ClockID::CLOCK_REALTIME => {}
+        _ => return_errno!(EOPNOTSUPP, "the clock cannot be adjusted"),
+    }
+    if buf.modes != 0 && buf.modes != ADJ_OFFSET_SS_READ {
+        return_errno!(EPERM, "the clock cannot be adjusted in the enclave");
+    }
+    let is_synced = host_clock::is_synced();
+    let maxerror = match LIBOS_CONFIG.time.max_host_clock_adjustment {
+        Some(max_adjustment) if is_synced => max_adjustment.as_micros() as i64,
+        _ => NTP_PHASE_LIMIT_US,
+    };
+    // The offset is in microseconds without STA_NANO
+    let offset = host_clock::host_offset_ns() / 1000;
+    *buf = timex_t {
+        modes: buf.modes,
+        offset: max(min(offset, i64::MAX as i128), i64::MIN as i128) as i64,
+        maxerror,
+        esterror: maxerror,
+        status: if is_synced { 0 } else { STA_UNSYNC },
+        precision: 1,
+        // 500 ppm in the fixed point format of 16 bits, which is the same as Linux
+        tolerance: 500 << 16,
+        time: do_gettimeofday(),
+        // The microseconds between the clock ticks of 100 Hz
+        tick: 10_000,
+        ..Default::default()
+    };
+    Ok(if is_synced { TIME_OK } else { TIME_ERROR })
+}
+
 pub fn do_nanosleep(req: &timespec_t, rem: Option<&mut timespec_t>) -> Result<()> {
     extern "C" {
         fn occlum_ocall_nanosleep(
@@ -180,7 +333,8 @@ pub fn do_nanosleep(req: &timespec_t, rem: Option<&mut timespec_t>) -> Result<()
     unsafe {
         let mut ret = 0;
         let mut u_rem: timespec_t = timespec_t { sec: 0, nsec: 0 };
-        let sgx_status = occlum_ocall_nanosleep(&mut ret, req, &mut u_rem);
+        let sgx_status =
+            crate::sched::account_wait(|| occlum_ocall_nanosleep(&mut ret, req, &mut u_rem));
         assert!(sgx_status == sgx_status_t::SGX_SUCCESS);
         assert!(ret == 0 || libc::errno() == Errno::EINTR as i32);
         if ret != 0 {
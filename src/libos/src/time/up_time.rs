@@ -1,19 +1,51 @@
+//! The up time and the boot time of the LibOS.
+//!
+//! The time is given by the host, which is untrusted. The up time is guaranteed to
+//! never go backwards, even if the clock of the host does, so that the start time
+//! of a process never exceeds the up time and the intervals computed from them are
+//! always sane.
 use super::{do_clock_gettime, ClockID};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
+/// The number of clock ticks per second, i.e., `sysconf(_SC_CLK_TCK)`
+pub const USER_HZ: u64 = 100;
+
 lazy_static! {
     static ref BOOT_TIME_STAMP: Duration = do_clock_gettime(ClockID::CLOCK_MONOTONIC_RAW)
         .unwrap()
         .as_duration();
+    // The wall-clock time of the boot, since the Epoch
+    static ref BOOT_TIME: Duration = do_clock_gettime(ClockID::CLOCK_REALTIME)
+        .unwrap()
+        .as_duration();
 }
 
+// The max up time that has been returned, in nanoseconds
+static LAST_UP_TIME: AtomicU64 = AtomicU64::new(0);
+
 pub fn init() {
     *BOOT_TIME_STAMP;
+    *BOOT_TIME;
 }
 
-pub fn get() -> Option<Duration> {
-    do_clock_gettime(ClockID::CLOCK_MONOTONIC_RAW)
+pub fn get() -> Duration {
+    let up_time = do_clock_gettime(ClockID::CLOCK_MONOTONIC_RAW)
         .unwrap()
         .as_duration()
         .checked_sub(*BOOT_TIME_STAMP)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let last_up_time = LAST_UP_TIME.fetch_max(up_time, Ordering::Relaxed);
+    Duration::from_nanos(up_time.max(last_up_time))
+}
+
+/// The time of the boot since the Epoch, as in the btime of /proc/stat
+pub fn boot_time() -> Duration {
+    *BOOT_TIME
+}
+
+/// Convert a duration to clock ticks
+pub fn as_clock_ticks(duration: Duration) -> u64 {
+    duration.as_nanos() as u64 / (1_000_000_000 / USER_HZ)
 }
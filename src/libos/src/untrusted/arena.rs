@@ -0,0 +1,193 @@
+use super::*;
+use std::alloc::{AllocRef, Layout};
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// An arena of untrusted memory that caches freed chunks by size classes.
+///
+/// Allocating untrusted memory takes two OCalls (one for malloc and one for
+/// free), which dominates the cost of small sendmsg/recvmsg. The arena keeps
+/// the freed chunks in a per-thread cache and then in a global cache, so that
+/// most allocations can be served without any OCall.
+///
+/// Requests larger than the biggest size class bypass the arena.
+///
+/// Only the data buffers of sockets are allocated from the arena, i.e., the ones
+/// passed to OCalls as `user_check` pointers. The small buffers of addresses
+/// (e.g., of accept and recvfrom) and of ioctl arguments are declared as `in` or
+/// `out` in the EDL, which the OCall bridge copies via the untrusted stack
+/// without allocating untrusted memory at all.
+pub struct UntrustedArena {
+    global_caches: Vec<SgxMutex<Vec<usize>>>,
+    stats: ArenaStats,
+}
+
+/// The statistics of the arena, which can be used to track leaks of untrusted memory
+#[derive(Debug, Default)]
+pub struct ArenaStats {
+    /// The number of chunks that are being used
+    in_use_chunks: AtomicUsize,
+    /// The total size of chunks that are being used
+    in_use_bytes: AtomicUsize,
+    /// The total size of chunks that are allocated via OCalls and not freed yet
+    total_bytes: AtomicUsize,
+}
+
+/// A chunk of untrusted memory allocated from the arena, which is returned to the
+/// arena when dropped.
+pub struct UntrustedChunk {
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+// The sizes of classes are MIN_CHUNK_SIZE, 2 * MIN_CHUNK_SIZE, ..., MAX_CHUNK_SIZE
+const MIN_CHUNK_SIZE: usize = 64;
+const MAX_CHUNK_SIZE: usize = 64 * 1024;
+const NUM_SIZE_CLASSES: usize = 11;
+// The max number of chunks cached for each size class
+const MAX_LOCAL_CACHED_CHUNKS: usize = 8;
+const MAX_GLOBAL_CACHED_CHUNKS: usize = 64;
+
+lazy_static! {
+    pub static ref UNTRUSTED_ARENA: UntrustedArena = UntrustedArena::new();
+}
+
+thread_local! {
+    static LOCAL_CACHES: RefCell<Vec<Vec<usize>>> =
+        RefCell::new((0..NUM_SIZE_CLASSES).map(|_| Vec::new()).collect());
+}
+
+impl UntrustedArena {
+    fn new() -> Self {
+        debug_assert!(MIN_CHUNK_SIZE << (NUM_SIZE_CLASSES - 1) == MAX_CHUNK_SIZE);
+        let global_caches = (0..NUM_SIZE_CLASSES)
+            .map(|_| SgxMutex::new(Vec::new()))
+            .collect();
+        Self {
+            global_caches,
+            stats: Default::default(),
+        }
+    }
+
+    /// Allocate a chunk of at least `size` bytes
+    pub fn alloc(&self, size: usize) -> Result<UntrustedChunk> {
+        if size == 0 {
+            return_errno!(EINVAL, "cannot allocate an empty chunk");
+        }
+        let chunk = match size_class_of(size) {
+            Some(class) => {
+                let cached_addr = LOCAL_CACHES
+                    .with(|caches| caches.borrow_mut()[class].pop())
+                    .or_else(|| self.global_caches[class].lock().unwrap().pop());
+                match cached_addr {
+                    Some(addr) => UntrustedChunk {
+                        ptr: NonNull::new(addr as *mut u8).unwrap(),
+                        size: size_of_class(class),
+                    },
+                    None => self.alloc_from_host(size_of_class(class))?,
+                }
+            }
+            None => self.alloc_from_host(size)?,
+        };
+        self.stats.in_use_chunks.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .in_use_bytes
+            .fetch_add(chunk.size, Ordering::Relaxed);
+        Ok(chunk)
+    }
+
+    fn dealloc(&self, chunk: &UntrustedChunk) {
+        self.stats.in_use_chunks.fetch_sub(1, Ordering::Relaxed);
+        self.stats
+            .in_use_bytes
+            .fetch_sub(chunk.size, Ordering::Relaxed);
+
+        if let Some(class) = size_class_of(chunk.size) {
+            debug_assert!(size_of_class(class) == chunk.size);
+            let addr = chunk.ptr.as_ptr() as usize;
+            let is_cached_locally = LOCAL_CACHES.with(|caches| {
+                let mut caches = caches.borrow_mut();
+                if caches[class].len() < MAX_LOCAL_CACHED_CHUNKS {
+                    caches[class].push(addr);
+                    true
+                } else {
+                    false
+                }
+            });
+            if is_cached_locally {
+                return;
+            }
+            let mut global_cache = self.global_caches[class].lock().unwrap();
+            if global_cache.len() < MAX_GLOBAL_CACHED_CHUNKS {
+                global_cache.push(addr);
+                return;
+            }
+        }
+        self.dealloc_to_host(chunk);
+    }
+
+    fn alloc_from_host(&self, size: usize) -> Result<UntrustedChunk> {
+        let layout = Layout::from_size_align(size, 1)?;
+        let ptr = unsafe { UNTRUSTED_ALLOC.alloc(layout)?.as_non_null_ptr() };
+        self.stats.total_bytes.fetch_add(size, Ordering::Relaxed);
+        Ok(UntrustedChunk { ptr, size })
+    }
+
+    fn dealloc_to_host(&self, chunk: &UntrustedChunk) {
+        let layout = Layout::from_size_align(chunk.size, 1).unwrap();
+        unsafe {
+            UNTRUSTED_ALLOC.dealloc(chunk.ptr, layout);
+        }
+        self.stats
+            .total_bytes
+            .fetch_sub(chunk.size, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> &ArenaStats {
+        &self.stats
+    }
+}
+
+impl ArenaStats {
+    pub fn in_use_chunks(&self) -> usize {
+        self.in_use_chunks.load(Ordering::Relaxed)
+    }
+
+    pub fn in_use_bytes(&self) -> usize {
+        self.in_use_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+}
+
+fn size_class_of(size: usize) -> Option<usize> {
+    if size > MAX_CHUNK_SIZE {
+        return None;
+    }
+    let size = max(size, MIN_CHUNK_SIZE).next_power_of_two();
+    Some((size / MIN_CHUNK_SIZE).trailing_zeros() as usize)
+}
+
+fn size_of_class(class: usize) -> usize {
+    MIN_CHUNK_SIZE << class
+}
+
+impl UntrustedChunk {
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for UntrustedChunk {
+    fn drop(&mut self) {
+        UNTRUSTED_ARENA.dealloc(self);
+    }
+}
+
+unsafe impl Send for UntrustedChunk {}
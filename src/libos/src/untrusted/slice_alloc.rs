@@ -1,10 +1,11 @@
+use super::arena::{UntrustedChunk, UNTRUSTED_ARENA};
 use super::*;
-use std::alloc::{AllocErr, AllocRef, Layout};
-use std::ptr::NonNull;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// An memory allocator for slices, backed by a fixed-size, untrusted buffer
 pub struct UntrustedSliceAlloc {
+    /// The untrusted buffer, which is None for the dummy case
+    buf: Option<UntrustedChunk>,
     /// The pointer to the untrusted buffer
     buf_ptr: *mut u8,
     /// The size of the untrusted buffer
@@ -19,17 +20,19 @@ impl UntrustedSliceAlloc {
         if buf_size == 0 {
             // Create a dummy object
             return Ok(Self {
+                buf: None,
                 buf_ptr: std::ptr::null_mut(),
                 buf_size: 0,
                 buf_pos: AtomicUsize::new(0),
             });
         }
 
-        let layout = Layout::from_size_align(buf_size, 1)?;
-        let buf_ptr = unsafe { UNTRUSTED_ALLOC.alloc(layout)?.as_mut_ptr() };
+        let buf = UNTRUSTED_ARENA.alloc(buf_size)?;
+        let buf_ptr = buf.as_mut_ptr();
 
         let buf_pos = AtomicUsize::new(0);
         Ok(Self {
+            buf: Some(buf),
             buf_ptr,
             buf_size,
             buf_pos,
@@ -62,17 +65,3 @@ impl UntrustedSliceAlloc {
         Ok(new_slice)
     }
 }
-
-impl Drop for UntrustedSliceAlloc {
-    fn drop(&mut self) {
-        // Do nothing for the dummy case
-        if self.buf_size == 0 {
-            return;
-        }
-
-        let layout = Layout::from_size_align(self.buf_size, 1).unwrap();
-        unsafe {
-            UNTRUSTED_ALLOC.dealloc(NonNull::new(self.buf_ptr).unwrap(), layout);
-        }
-    }
-}
@@ -14,7 +14,7 @@ pub mod from_user {
         if !is_inside_user_space(user_ptr as *const u8, size_of::<T>()) {
             return_errno!(EFAULT, "pointer is not in the user space");
         }
-        Ok(())
+        check_no_missing_pages(user_ptr as *const u8, size_of::<T>())
     }
 
     /// Check the mutable user pointer is within the writable memory of the user process
@@ -26,10 +26,13 @@ pub mod from_user {
 
     /// Check the readonly array is within the readable memory of the user process
     pub fn check_array<T>(user_buf: *const T, count: usize) -> Result<()> {
-        if !is_inside_user_space(user_buf as *const u8, count * size_of::<T>()) {
+        let len = count
+            .checked_mul(size_of::<T>())
+            .ok_or_else(|| errno!(EFAULT, "the buffer is too large"))?;
+        if !is_inside_user_space(user_buf as *const u8, len) {
             return_errno!(EFAULT, "the whole buffer is not in the user space");
         }
-        Ok(())
+        check_no_missing_pages(user_buf as *const u8, len)
     }
 
     /// Check the mutable array is within the writable memory of the user process
@@ -39,6 +42,129 @@ pub mod from_user {
         check_array(user_buf, count)
     }
 
+    /// An immutable pointer to an array of `T` in the user space.
+    ///
+    /// The whole array is checked to be in the user space when the pointer is
+    /// created. So it is safe to access the array as long as the user process
+    /// is alive, e.g., during a system call. The slices of the array borrow the
+    /// pointer, so that they cannot outlive it.
+    #[derive(Debug, Clone, Copy)]
+    pub struct UserPtr<T> {
+        ptr: *const T,
+        count: usize,
+    }
+
+    impl<T> UserPtr<T> {
+        /// Check the array of `count` elements at `ptr`, which must not be NULL
+        pub fn new(ptr: *const T, count: usize) -> Result<Self> {
+            if ptr.is_null() {
+                return_errno!(EFAULT, "NULL address is invalid");
+            }
+            check_array(ptr, count)?;
+            Ok(Self { ptr, count })
+        }
+
+        /// Same as `new`, except that NULL is allowed, which gives None
+        pub fn new_nullable(ptr: *const T, count: usize) -> Result<Option<Self>> {
+            if ptr.is_null() {
+                return Ok(None);
+            }
+            Self::new(ptr, count).map(Some)
+        }
+
+        pub fn as_ptr(&self) -> *const T {
+            self.ptr
+        }
+
+        pub fn len(&self) -> usize {
+            self.count
+        }
+
+        pub fn as_slice(&self) -> &[T] {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.count) }
+        }
+
+        /// Read the first element of the array
+        pub fn read(&self) -> Result<T>
+        where
+            T: Copy,
+        {
+            if self.count == 0 {
+                return_errno!(EFAULT, "the array is empty");
+            }
+            Ok(unsafe { ptr::read_unaligned(self.ptr) })
+        }
+    }
+
+    /// A mutable pointer to an array of `T` in the user space.
+    ///
+    /// See `UserPtr` for more info. The pointer is not copyable, so that there is
+    /// only one mutable slice of the array at a time.
+    #[derive(Debug)]
+    pub struct UserMutPtr<T> {
+        ptr: *mut T,
+        count: usize,
+    }
+
+    impl<T> UserMutPtr<T> {
+        /// Check the array of `count` elements at `ptr`, which must not be NULL
+        pub fn new(ptr: *mut T, count: usize) -> Result<Self> {
+            if ptr.is_null() {
+                return_errno!(EFAULT, "NULL address is invalid");
+            }
+            check_mut_array(ptr, count)?;
+            Ok(Self { ptr, count })
+        }
+
+        /// Same as `new`, except that NULL is allowed, which gives None
+        pub fn new_nullable(ptr: *mut T, count: usize) -> Result<Option<Self>> {
+            if ptr.is_null() {
+                return Ok(None);
+            }
+            Self::new(ptr, count).map(Some)
+        }
+
+        pub fn as_ptr(&self) -> *const T {
+            self.ptr
+        }
+
+        pub fn as_mut_ptr(&self) -> *mut T {
+            self.ptr
+        }
+
+        pub fn len(&self) -> usize {
+            self.count
+        }
+
+        pub fn as_slice(&self) -> &[T] {
+            unsafe { std::slice::from_raw_parts(self.ptr, self.count) }
+        }
+
+        pub fn as_mut_slice(&mut self) -> &mut [T] {
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.count) }
+        }
+
+        /// Read the first element of the array
+        pub fn read(&self) -> Result<T>
+        where
+            T: Copy,
+        {
+            if self.count == 0 {
+                return_errno!(EFAULT, "the array is empty");
+            }
+            Ok(unsafe { ptr::read_unaligned(self.ptr) })
+        }
+
+        /// Write the first element of the array
+        pub fn write(&self, val: T) -> Result<()> {
+            if self.count == 0 {
+                return_errno!(EFAULT, "the array is empty");
+            }
+            unsafe { ptr::write_unaligned(self.ptr, val) };
+            Ok(())
+        }
+    }
+
     /// Clone a C-string from the user process safely
     pub fn clone_cstring_safely(out_ptr: *const c_char) -> Result<CString> {
         if out_ptr.is_null() {
@@ -50,6 +176,7 @@ pub mod from_user {
         if !is_inside_user_space(out_ptr as *const u8, cstring.as_bytes().len()) {
             return_errno!(EFAULT, "the whole buffer is not in the user space");
         }
+        check_no_missing_pages(out_ptr as *const u8, cstring.as_bytes().len())?;
         Ok(cstring)
     }
 
@@ -81,6 +208,17 @@ pub mod from_user {
         Ok(cstrings)
     }
 
+    /// Check the buffer has no page that is missing in the ranges registered to
+    /// userfaultfds, on which the LibOS would fault. The pages mapped from host file
+    /// windows are populated first.
+    fn check_no_missing_pages(addr: *const u8, len: usize) -> Result<()> {
+        vm::populate_window_pages(addr as usize, len)?;
+        if vm::has_missing_user_pages(addr as usize, len) {
+            return_errno!(EFAULT, "the buffer has pages missing for userfaultfd");
+        }
+        Ok(())
+    }
+
     /// Check if the provided buffer is within the current user space
     ///
     /// addr: the start address
@@ -109,10 +247,10 @@ pub mod from_untrusted {
 
     /// Check the untrusted array is outside the enclave
     pub fn check_array<T>(out_ptr: *const T, count: usize) -> Result<()> {
-        if !sgx_trts::trts::rsgx_raw_is_outside_enclave(
-            out_ptr as *const u8,
-            count * size_of::<T>(),
-        ) {
+        let len = count
+            .checked_mul(size_of::<T>())
+            .ok_or_else(|| errno!(EFAULT, "the buffer is too large"))?;
+        if !sgx_trts::trts::rsgx_raw_is_outside_enclave(out_ptr as *const u8, len) {
             return_errno!(EFAULT, "the whole buffer is not outside enclave");
         }
         Ok(())
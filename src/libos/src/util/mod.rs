@@ -6,4 +6,5 @@ pub mod mem_util;
 pub mod mpx_util;
 pub mod ring_buf;
 pub mod sgx;
+pub mod small_vec;
 pub mod sync;
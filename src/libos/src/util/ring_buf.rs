@@ -1,54 +1,272 @@
+//! A lock-free multi-producer, single-consumer ring buffer of bytes.
+//!
+//! The reader and the writers only synchronize through the indices of the ring,
+//! which are placed in separate cache lines to avoid false sharing. Vectored
+//! reads and writes publish the new index only once for all of the buffers.
+//!
+//! The write end of the ring, `RingBufWriter`, may be shared by multiple threads
+//! (e.g., the threads that share a pipe) without any lock. A write reserves its
+//! space in the ring by advancing the reserved index with a CAS, copies its bytes
+//! into the space, and then publishes them by advancing the tail index once the
+//! writes that reserve before it are published. So the bytes of one write are
+//! never interleaved with those of another. The read end, `RingBufReader`, is
+//! owned by one consumer, and multiple consumers must serialize their accesses
+//! to it, e.g., with a mutex.
+//!
+//! The writer may resize the ring while it is empty, which requires exclusive
+//! access to the writer, so no write is in progress. The reader never touches the
+//! storage of an empty ring, and it only sees the new storage along with the bytes
+//! written into it, which are published by the tail index.
+//!
+//! Besides a stream of bytes, the ring can carry records, each of which is the
+//! length of its data followed by the data. A record is published at once, and
+//! is read as a whole, so the boundaries of the messages are kept, e.g., for
+//! SOCK_SEQPACKET. The bytes and the records must not be mixed in one ring.
 use alloc::alloc::{alloc, dealloc, Layout};
 
 use crate::net::{
-    clear_notifier_status, notify_thread, wait_for_notification, IoEvent, PollEventFlags,
+    clear_notifier_status, notify_thread, wait_for_notification_timeout, Deadline, EpollEventFlags,
+    IoEvent, PollEventFlags,
 };
 use std::cmp::{max, min};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{spin_loop_hint, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use super::*;
-use ringbuf::{Consumer, Producer, RingBuffer};
 
-pub fn ring_buffer(capacity: usize) -> Result<(RingBufReader, RingBufWriter)> {
-    let meta = RingBufMeta::new();
-    let buffer = RingBuffer::<u8>::new(capacity);
-    let (producer, consumer) = buffer.split();
-    let meta_ref = Arc::new(meta);
+/// The length of the header of a record, which is the length of its data
+pub const RECORD_HEADER_LEN: usize = 4;
 
+pub fn ring_buffer(capacity: usize) -> Result<(RingBufReader, RingBufWriter)> {
+    let buffer = Arc::new(RingBuf::new(capacity)?);
     let reader = RingBufReader {
-        inner: consumer,
-        buffer: meta_ref.clone(),
-    };
-    let writer = RingBufWriter {
-        inner: producer,
-        buffer: meta_ref,
+        buffer: buffer.clone(),
     };
+    let writer = RingBufWriter { buffer };
     Ok((reader, writer))
 }
 
-struct RingBufMeta {
-    lock: Arc<SgxMutex<bool>>, // lock for the synchronization of reader and writer
-    reader_closed: AtomicBool, // if reader has been dropped
-    writer_closed: AtomicBool, // if writer has been dropped
+// Make sure the value occupies whole cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+struct RingBuf {
+    // Only replaced by the writer when the ring is empty
+    buf: AtomicPtr<u8>,
+    capacity: AtomicUsize,
+    // The indices only increase (and wrap around usize::MAX). The number of bytes
+    // in the ring is `tail - head`, and the space in `[tail, reserved)` is being
+    // written.
+    head: CachePadded<AtomicUsize>,     // updated by the reader only
+    tail: CachePadded<AtomicUsize>,     // updated by the writers in order
+    reserved: CachePadded<AtomicUsize>, // updated by the writers with CAS
+    reader_closed: AtomicBool,          // if reader has been dropped
+    writer_closed: AtomicBool,          // if writer has been dropped
     reader_wait_queue: SgxMutex<HashMap<pid_t, IoEvent>>,
     writer_wait_queue: SgxMutex<HashMap<pid_t, IoEvent>>,
     // TODO: support O_ASYNC and O_DIRECT in ringbuffer
     blocking_read: AtomicBool,  // if the read is blocking
     blocking_write: AtomicBool, // if the write is blocking
+    // The timeouts of the blocking read and write, which are infinite if None
+    read_timeout: SgxMutex<Option<Duration>>,
+    write_timeout: SgxMutex<Option<Duration>>,
 }
 
-impl RingBufMeta {
-    pub fn new() -> RingBufMeta {
-        Self {
-            lock: Arc::new(SgxMutex::new(true)),
+unsafe impl Send for RingBuf {}
+unsafe impl Sync for RingBuf {}
+
+impl RingBuf {
+    pub fn new(capacity: usize) -> Result<RingBuf> {
+        let buf = alloc_storage(capacity)?;
+        Ok(Self {
+            buf: AtomicPtr::new(buf),
+            capacity: AtomicUsize::new(capacity),
+            head: CachePadded(AtomicUsize::new(0)),
+            tail: CachePadded(AtomicUsize::new(0)),
+            reserved: CachePadded(AtomicUsize::new(0)),
             reader_closed: AtomicBool::new(false),
             writer_closed: AtomicBool::new(false),
             reader_wait_queue: SgxMutex::new(HashMap::new()),
             writer_wait_queue: SgxMutex::new(HashMap::new()),
             blocking_read: AtomicBool::new(true),
             blocking_write: AtomicBool::new(true),
+            read_timeout: SgxMutex::new(None),
+            write_timeout: SgxMutex::new(None),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.0.load(Ordering::SeqCst);
+        let head = self.head.0.load(Ordering::SeqCst);
+        tail.wrapping_sub(head)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Acquire)
+    }
+
+    /// The free space that is not reserved by the writers
+    pub fn free_space(&self) -> usize {
+        let reserved = self.reserved.0.load(Ordering::SeqCst);
+        let head = self.head.0.load(Ordering::SeqCst);
+        self.capacity().saturating_sub(reserved.wrapping_sub(head))
+    }
+
+    // Replace the storage with one of the new capacity, which fails if the ring is
+    // not empty. Must be called by the writer with no write in progress.
+    fn resize(&self, capacity: usize) -> Result<bool> {
+        if capacity == self.capacity.load(Ordering::Relaxed) {
+            return Ok(true);
+        }
+        // All bytes in the ring have been copied out by the reader if the ring is
+        // empty, as the reader publishes the head after copying. And the ring stays
+        // empty, as only the caller writes.
+        debug_assert!(
+            self.reserved.0.load(Ordering::Relaxed) == self.tail.0.load(Ordering::Relaxed)
+        );
+        if self.len() > 0 {
+            return Ok(false);
+        }
+        let new_buf = alloc_storage(capacity)?;
+        let old_capacity = self.capacity.swap(capacity, Ordering::Release);
+        let old_buf = self.buf.swap(new_buf, Ordering::Release);
+        unsafe { free_storage(old_buf, old_capacity) };
+        Ok(true)
+    }
+
+    // Copy the bytes in the ring into `bufs`, which are left in the ring if `peek`.
+    // Must be called by the reader only.
+    fn pop_slices(&self, bufs: &mut [&mut [u8]], peek: bool) -> usize {
+        let head = self.head.0.load(Ordering::Relaxed);
+        let tail = self.tail.0.load(Ordering::Acquire);
+        let mut avail = tail.wrapping_sub(head);
+        // The storage may be being replaced if the ring is empty
+        if avail == 0 {
+            return 0;
+        }
+        let mut pos = head;
+        for buf in bufs {
+            let count = min(avail, buf.len());
+            self.copy_from_ring(pos, &mut buf[..count]);
+            pos = pos.wrapping_add(count);
+            avail -= count;
+            if avail == 0 {
+                break;
+            }
+        }
+        // Publish the free space to the writer at once
+        if !peek {
+            self.head.0.store(pos, Ordering::SeqCst);
+        }
+        pos.wrapping_sub(head)
+    }
+
+    // Copy `bufs` into the free space of the ring, which copies nothing unless all
+    // of `bufs` fit if `all_or_nothing`. Can be called by multiple writers.
+    fn push_slices(&self, bufs: &[&[u8]], all_or_nothing: bool) -> usize {
+        let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let capacity = self.capacity();
+        // Reserve the space. The head is loaded after the reserved index, so it is
+        // no older than the head seen by the writer that reserved up to the index,
+        // and the space reserved never exceeds the capacity.
+        let mut start = self.reserved.0.load(Ordering::Acquire);
+        let count = loop {
+            let head = self.head.0.load(Ordering::Acquire);
+            let free = capacity - start.wrapping_sub(head);
+            let count = match (all_or_nothing, free >= len) {
+                (true, true) => len,
+                (true, false) => 0,
+                (false, _) => min(free, len),
+            };
+            if count == 0 {
+                return 0;
+            }
+            match self.reserved.0.compare_exchange_weak(
+                start,
+                start.wrapping_add(count),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break count,
+                Err(reserved) => start = reserved,
+            }
+        };
+
+        let mut pos = start;
+        let mut remaining = count;
+        for buf in bufs {
+            let buf_count = min(remaining, buf.len());
+            self.copy_to_ring(pos, &buf[..buf_count]);
+            pos = pos.wrapping_add(buf_count);
+            remaining -= buf_count;
+            if remaining == 0 {
+                break;
+            }
+        }
+
+        // Publish the new bytes to the reader at once, after the bytes of the writers
+        // that reserve before, which are being copied on other CPUs
+        while self.tail.0.load(Ordering::Acquire) != start {
+            spin_loop_hint();
+        }
+        self.tail.0.store(pos, Ordering::SeqCst);
+        count
+    }
+
+    // Copy the data of the record at the head into `bufs`, which is left in the
+    // ring if `peek`, returning the bytes copied and the length of the data.
+    // Must be called by the reader only.
+    fn pop_record(&self, bufs: &mut [&mut [u8]], peek: bool) -> Option<(usize, usize)> {
+        let head = self.head.0.load(Ordering::Relaxed);
+        let tail = self.tail.0.load(Ordering::Acquire);
+        let avail = tail.wrapping_sub(head);
+        if avail == 0 {
+            return None;
+        }
+        // A record is published as a whole
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        self.copy_from_ring(head, &mut header);
+        let len = u32::from_ne_bytes(header) as usize;
+        debug_assert!(RECORD_HEADER_LEN + len <= avail);
+
+        let mut pos = head.wrapping_add(RECORD_HEADER_LEN);
+        let mut remaining = len;
+        for buf in bufs {
+            let count = min(remaining, buf.len());
+            self.copy_from_ring(pos, &mut buf[..count]);
+            pos = pos.wrapping_add(count);
+            remaining -= count;
+            if remaining == 0 {
+                break;
+            }
+        }
+        // The part of the data that does not fit in the buffers is discarded
+        if !peek {
+            let next_head = head.wrapping_add(RECORD_HEADER_LEN + len);
+            self.head.0.store(next_head, Ordering::SeqCst);
+        }
+        Some((len - remaining, len))
+    }
+
+    fn copy_from_ring(&self, pos: usize, dst: &mut [u8]) {
+        let (buf, capacity) = (self.buf.load(Ordering::Acquire), self.capacity());
+        let offset = pos % capacity;
+        let first_len = min(dst.len(), capacity - offset);
+        unsafe {
+            ptr::copy_nonoverlapping(buf.add(offset), dst.as_mut_ptr(), first_len);
+            ptr::copy_nonoverlapping(buf, dst.as_mut_ptr().add(first_len), dst.len() - first_len);
+        }
+    }
+
+    fn copy_to_ring(&self, pos: usize, src: &[u8]) {
+        let (buf, capacity) = (self.buf.load(Ordering::Acquire), self.capacity());
+        let offset = pos % capacity;
+        let first_len = min(src.len(), capacity - offset);
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), buf.add(offset), first_len);
+            ptr::copy_nonoverlapping(src.as_ptr().add(first_len), buf, src.len() - first_len);
         }
     }
 
@@ -84,12 +302,12 @@ impl RingBufMeta {
         Ok(())
     }
 
+    // The event may have been dequeued, e.g., by epoll on the dup-ed fds of the ring
     pub fn dequeue_reader_event(&self) -> Result<()> {
         self.reader_wait_queue
             .lock()
             .unwrap()
-            .remove(&current!().tid())
-            .unwrap();
+            .remove(&current!().tid());
         Ok(())
     }
 
@@ -105,8 +323,7 @@ impl RingBufMeta {
         self.writer_wait_queue
             .lock()
             .unwrap()
-            .remove(&current!().tid())
-            .unwrap();
+            .remove(&current!().tid());
         Ok(())
     }
 
@@ -133,11 +350,49 @@ impl RingBufMeta {
     pub fn set_blocking_write(&self) {
         self.blocking_write.store(true, Ordering::SeqCst);
     }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        *self.read_timeout.lock().unwrap()
+    }
+
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) {
+        *self.read_timeout.lock().unwrap() = timeout;
+    }
+
+    pub fn write_timeout(&self) -> Option<Duration> {
+        *self.write_timeout.lock().unwrap()
+    }
+
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) {
+        *self.write_timeout.lock().unwrap() = timeout;
+    }
+}
+
+impl Drop for RingBuf {
+    fn drop(&mut self) {
+        unsafe { free_storage(*self.buf.get_mut(), *self.capacity.get_mut()) };
+    }
+}
+
+fn alloc_storage(capacity: usize) -> Result<*mut u8> {
+    if capacity == 0 {
+        return_errno!(EINVAL, "the capacity of ring buffer must not be zero");
+    }
+    let layout = Layout::from_size_align(capacity, 1)
+        .map_err(|_| errno!(EINVAL, "the capacity of ring buffer is too large"))?;
+    let buf = unsafe { alloc(layout) };
+    if buf.is_null() {
+        return_errno!(ENOMEM, "no memory for ring buffer");
+    }
+    Ok(buf)
+}
+
+unsafe fn free_storage(buf: *mut u8, capacity: usize) {
+    dealloc(buf, Layout::from_size_align_unchecked(capacity, 1));
 }
 
 pub struct RingBufReader {
-    inner: Consumer<u8>,
-    buffer: Arc<RingBufMeta>,
+    buffer: Arc<RingBuf>,
 }
 
 impl RingBufReader {
@@ -145,82 +400,114 @@ impl RingBufReader {
         self.bytes_to_read() != 0
     }
 
+    /// The tail index, which changes whenever the writer writes
+    pub fn poll_seq(&self) -> usize {
+        self.buffer.tail.0.load(Ordering::SeqCst)
+    }
+
     pub fn read_from_buffer(&mut self, buffer: &mut [u8]) -> Result<usize> {
-        self.read(Some(buffer), None)
+        self.read_from_vector(&mut [buffer])
     }
 
     pub fn read_from_vector(&mut self, buffers: &mut [&mut [u8]]) -> Result<usize> {
-        self.read(None, Some(buffers))
+        self.read_from_vector_with(buffers, false, false)
     }
 
-    fn read(
+    /// Read like `read_from_vector`, but the bytes read are left in the ring if
+    /// `peek`, and the read never blocks if `nonblocking`
+    pub fn read_from_vector_with(
         &mut self,
-        buffer: Option<&mut [u8]>,
-        buffers: Option<&mut [&mut [u8]]>,
+        buffers: &mut [&mut [u8]],
+        peek: bool,
+        nonblocking: bool,
     ) -> Result<usize> {
-        assert!(buffer.is_some() ^ buffers.is_some());
-        // In case of write after can_read is false
-        let lock_ref = self.buffer.lock.clone();
-        let lock_holder = lock_ref.lock();
-
-        if self.can_read() {
-            let count = if buffer.is_some() {
-                self.inner.pop_slice(buffer.unwrap())
-            } else {
-                self.pop_slices(buffers.unwrap())
-            };
-            assert!(count > 0);
-            self.read_end();
-            Ok(count)
-        } else {
-            if self.is_peer_closed() {
+        if buffers.iter().all(|buf| buf.len() == 0) {
+            return Ok(0);
+        }
+
+        let deadline = Deadline::after(self.buffer.read_timeout());
+        loop {
+            // Check if the writer is closed before reading, so that the bytes written
+            // right before the writer is closed will not be missed
+            let is_peer_closed = self.is_peer_closed();
+            let count = self.buffer.pop_slices(buffers, peek);
+            if count > 0 {
+                if !peek {
+                    self.read_end()?;
+                }
+                return Ok(count);
+            }
+            if is_peer_closed {
                 return Ok(0);
             }
 
-            if !self.buffer.blocking_read() {
+            if nonblocking || !self.buffer.blocking_read() {
                 return_errno!(EAGAIN, "No data to read");
-            } else {
-                // Clear the status of notifier before enqueue
-                clear_notifier_status(current!().tid())?;
-                self.enqueue_event(IoEvent::BlockingRead)?;
-                drop(lock_holder);
-                drop(lock_ref);
-                let ret = wait_for_notification();
-                self.dequeue_event()?;
-                ret?;
-
-                let lock_ref = self.buffer.lock.clone();
-                let lock_holder = lock_ref.lock();
-                let count = if buffer.is_some() {
-                    self.inner.pop_slice(buffer.unwrap())
-                } else {
-                    self.pop_slices(buffers.unwrap())
-                };
-
-                if count > 0 {
+            }
+            self.wait_for_data(&deadline)?;
+        }
+    }
+
+    /// Read the record at the head into `buffers`, returning the bytes read and
+    /// the length of the data of the record. The data beyond the buffers is
+    /// discarded, unless the record is left in the ring if `peek`. None is
+    /// returned if the writer is closed.
+    pub fn read_record(
+        &mut self,
+        buffers: &mut [&mut [u8]],
+        peek: bool,
+        nonblocking: bool,
+    ) -> Result<Option<(usize, usize)>> {
+        let deadline = Deadline::after(self.buffer.read_timeout());
+        loop {
+            let is_peer_closed = self.is_peer_closed();
+            if let Some(ret) = self.buffer.pop_record(buffers, peek) {
+                if !peek {
                     self.read_end()?;
-                } else {
-                    assert!(self.is_peer_closed());
                 }
-                Ok(count)
+                return Ok(Some(ret));
+            }
+            if is_peer_closed {
+                return Ok(None);
+            }
+
+            if nonblocking || !self.buffer.blocking_read() {
+                return_errno!(EAGAIN, "No record to read");
             }
+            self.wait_for_data(&deadline)?;
         }
     }
 
-    fn pop_slices(&mut self, buffers: &mut [&mut [u8]]) -> usize {
-        let mut total = 0;
-        for buf in buffers {
-            let count = self.inner.pop_slice(buf);
-            total += count;
-            if count < buf.len() {
-                break;
-            }
+    /// The length of the data of the record at the head, if any
+    pub fn next_record_len(&self) -> Option<usize> {
+        if !self.can_read() {
+            return None;
+        }
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        let head = self.buffer.head.0.load(Ordering::Relaxed);
+        self.buffer.copy_from_ring(head, &mut header);
+        Some(u32::from_ne_bytes(header) as usize)
+    }
+
+    /// Wait until there is data to read or the writer is closed, which fails with
+    /// EAGAIN once the deadline passes
+    fn wait_for_data(&self, deadline: &Deadline) -> Result<()> {
+        let timeout = deadline.remaining()?;
+        // Clear the status of notifier before enqueue
+        clear_notifier_status(current!().tid())?;
+        self.enqueue_event(IoEvent::BlockingRead)?;
+        // Check again in case of write or close after the last check, whose
+        // notification would have been missed
+        if self.can_read() || self.is_peer_closed() {
+            return self.dequeue_event();
         }
-        total
+        let ret = wait_for_notification_timeout(timeout);
+        self.dequeue_event()?;
+        ret
     }
 
     pub fn bytes_to_read(&self) -> usize {
-        self.inner.len()
+        self.buffer.len()
     }
 
     fn read_end(&self) -> Result<()> {
@@ -234,7 +521,14 @@ impl RingBufReader {
                         notify_thread(*tid)?;
                     }
                 }
-                IoEvent::Epoll(epoll_file) => unimplemented!(),
+                IoEvent::Epoll(epoll_event) => {
+                    if epoll_event
+                        .events()
+                        .intersects(EpollEventFlags::EPOLLOUT | EpollEventFlags::EPOLLWRNORM)
+                    {
+                        notify_thread(*tid)?;
+                    }
+                }
                 IoEvent::BlockingRead => unreachable!(),
                 IoEvent::BlockingWrite => notify_thread(*tid)?,
             }
@@ -262,11 +556,17 @@ impl RingBufReader {
         self.buffer.set_blocking_read()
     }
 
+    /// Set the timeout of the blocking reads, which is infinite if None
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.buffer.set_read_timeout(timeout)
+    }
+
     fn before_drop(&self) {
         for (tid, event) in &*self.buffer.writer_wait_queue().lock().unwrap() {
             match event {
-                IoEvent::Poll(_) | IoEvent::BlockingWrite => notify_thread(*tid).unwrap(),
-                IoEvent::Epoll(epoll_file) => unimplemented!(),
+                IoEvent::Poll(_) | IoEvent::Epoll(_) | IoEvent::BlockingWrite => {
+                    notify_thread(*tid).unwrap()
+                }
                 IoEvent::BlockingRead => unreachable!(),
             }
         }
@@ -284,69 +584,89 @@ impl Drop for RingBufReader {
 }
 
 pub struct RingBufWriter {
-    inner: Producer<u8>,
-    buffer: Arc<RingBufMeta>,
+    buffer: Arc<RingBuf>,
 }
 
 impl RingBufWriter {
-    pub fn write_to_buffer(&mut self, buffer: &[u8]) -> Result<usize> {
-        self.write(Some(buffer), None)
-    }
-
-    pub fn write_to_vector(&mut self, buffers: &[&[u8]]) -> Result<usize> {
-        self.write(None, Some(buffers))
+    pub fn write_to_buffer(&self, buffer: &[u8]) -> Result<usize> {
+        self.write_to_vector(&[buffer])
     }
 
-    fn write(&mut self, buffer: Option<&[u8]>, buffers: Option<&[&[u8]]>) -> Result<usize> {
-        assert!(buffer.is_some() ^ buffers.is_some());
-
-        // TODO: send SIGPIPE to the caller
-        if self.is_peer_closed() {
-            return_errno!(EPIPE, "reader side is closed");
+    pub fn write_to_vector(&self, buffers: &[&[u8]]) -> Result<usize> {
+        if buffers.iter().all(|buf| buf.len() == 0) {
+            return Ok(0);
         }
 
-        // In case of read after can_write is false
-        let lock_ref = self.buffer.lock.clone();
-        let lock_holder = lock_ref.lock();
+        let deadline = Deadline::after(self.buffer.write_timeout());
+        loop {
+            // SIGPIPE is sent by the callers, which know if it is suppressed
+            if self.is_peer_closed() {
+                return_errno!(EPIPE, "reader side is closed");
+            }
+
+            let count = self.buffer.push_slices(buffers, false);
+            if count > 0 {
+                self.write_end()?;
+                return Ok(count);
+            }
 
-        if self.can_write() {
-            let count = if buffer.is_some() {
-                self.inner.push_slice(buffer.unwrap())
-            } else {
-                self.push_slices(buffers.unwrap())
-            };
-            assert!(count > 0);
-            self.write_end();
-            Ok(count)
-        } else {
             if !self.buffer.blocking_write() {
                 return_errno!(EAGAIN, "No space to write");
             }
+            self.wait_for_space(1, &deadline)?;
+        }
+    }
 
-            // Clear the status of notifier before enqueue
-            clear_notifier_status(current!().tid());
-            self.enqueue_event(IoEvent::BlockingWrite)?;
-            drop(lock_holder);
-            drop(lock_ref);
-            let ret = wait_for_notification();
-            self.dequeue_event()?;
-            ret?;
-
-            let lock_ref = self.buffer.lock.clone();
-            let lock_holder = lock_ref.lock();
-            let count = if buffer.is_some() {
-                self.inner.push_slice(buffer.unwrap())
-            } else {
-                self.push_slices(buffers.unwrap())
-            };
+    /// Write `buffers` as one record, which is written only when there is room
+    /// for all of it. Return the length of the data.
+    pub fn write_record(&self, buffers: &[&[u8]]) -> Result<usize> {
+        let len: usize = buffers.iter().map(|buf| buf.len()).sum();
+        let record_len = RECORD_HEADER_LEN + len;
+        if record_len > self.capacity() {
+            return_errno!(EMSGSIZE, "the record is larger than the ring");
+        }
+        let header = (len as u32).to_ne_bytes();
+        let mut slices = Vec::with_capacity(buffers.len() + 1);
+        slices.push(&header[..]);
+        slices.extend_from_slice(buffers);
 
-            if count > 0 {
-                self.write_end();
-                Ok(count)
-            } else {
+        let deadline = Deadline::after(self.buffer.write_timeout());
+        loop {
+            if self.is_peer_closed() {
                 return_errno!(EPIPE, "reader side is closed");
             }
+
+            if self.buffer.push_slices(&slices, true) > 0 {
+                self.write_end()?;
+                return Ok(len);
+            }
+
+            if !self.buffer.blocking_write() {
+                return_errno!(EAGAIN, "No space to write the record");
+            }
+            self.wait_for_space(record_len, &deadline)?;
+        }
+    }
+
+    /// Wait until there is room for `len` bytes or the reader is closed, which
+    /// fails with EAGAIN once the deadline passes
+    fn wait_for_space(&self, len: usize, deadline: &Deadline) -> Result<()> {
+        let timeout = deadline.remaining()?;
+        // Clear the status of notifier before enqueue
+        clear_notifier_status(current!().tid())?;
+        self.enqueue_event(IoEvent::BlockingWrite)?;
+        // Check again in case of read or close after the last check, whose
+        // notification would have been missed
+        if self.free_space() >= len || self.is_peer_closed() {
+            return self.dequeue_event();
         }
+        let ret = wait_for_notification_timeout(timeout);
+        self.dequeue_event()?;
+        ret
+    }
+
+    fn free_space(&self) -> usize {
+        self.buffer.free_space()
     }
 
     fn write_end(&self) -> Result<()> {
@@ -360,7 +680,14 @@ impl RingBufWriter {
                         notify_thread(*tid)?;
                     }
                 }
-                IoEvent::Epoll(epoll_file) => unimplemented!(),
+                IoEvent::Epoll(epoll_event) => {
+                    if epoll_event
+                        .events()
+                        .intersects(EpollEventFlags::EPOLLIN | EpollEventFlags::EPOLLRDNORM)
+                    {
+                        notify_thread(*tid)?;
+                    }
+                }
                 IoEvent::BlockingRead => notify_thread(*tid)?,
                 IoEvent::BlockingWrite => unreachable!(),
             }
@@ -368,26 +695,39 @@ impl RingBufWriter {
         Ok(())
     }
 
-    fn push_slices(&mut self, buffers: &[&[u8]]) -> usize {
-        let mut total = 0;
-        for buf in buffers {
-            let count = self.inner.push_slice(buf);
-            total += count;
-            if count < buf.len() {
-                break;
-            }
-        }
-        total
+    /// The head index, which changes whenever the reader reads
+    pub fn poll_seq(&self) -> usize {
+        self.buffer.head.0.load(Ordering::SeqCst)
     }
 
     pub fn can_write(&self) -> bool {
-        !self.inner.is_full()
+        self.free_space() > 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    /// The number of bytes written but not read by the peer yet
+    pub fn bytes_unread(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Change the capacity of the ring, which only succeeds if the ring is empty.
+    /// No write is in progress, as the writer is borrowed exclusively.
+    pub fn resize(&mut self, capacity: usize) -> Result<bool> {
+        self.buffer.resize(capacity)
     }
 
     pub fn is_peer_closed(&self) -> bool {
         self.buffer.is_reader_closed()
     }
 
+    /// If the reader is closed without reading all the data in the buffer
+    pub fn is_peer_closed_with_unread_data(&self) -> bool {
+        self.is_peer_closed() && self.buffer.len() > 0
+    }
+
     pub fn enqueue_event(&self, event: IoEvent) -> Result<()> {
         self.buffer.enqueue_writer_event(event)
     }
@@ -404,13 +744,17 @@ impl RingBufWriter {
         self.buffer.set_blocking_write()
     }
 
+    /// Set the timeout of the blocking writes, which is infinite if None
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        self.buffer.set_write_timeout(timeout)
+    }
+
     fn before_drop(&self) {
         for (tid, event) in &*self.buffer.reader_wait_queue().lock().unwrap() {
             match event {
-                IoEvent::Poll(_) | IoEvent::BlockingRead => {
+                IoEvent::Poll(_) | IoEvent::Epoll(_) | IoEvent::BlockingRead => {
                     notify_thread(*tid).unwrap();
                 }
-                IoEvent::Epoll(epoll_file) => unimplemented!(),
                 IoEvent::BlockingWrite => unreachable!(),
             }
         }
@@ -0,0 +1,126 @@
+//! A vector that keeps a few elements inline.
+//!
+//! The short-lived vectors in the hot syscall paths, e.g., the iovecs of a
+//! sendmsg, usually have only a few elements. `SmallVec` stores up to `N` elements
+//! in itself (i.e., on the stack), and only moves them to the heap when there are
+//! more, which saves an allocation per syscall in the common case.
+use super::*;
+use std::iter::FromIterator;
+use std::mem::MaybeUninit;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+pub struct SmallVec<T, const N: usize> {
+    // The number of the inline elements, which is zero once spilled to the heap
+    len: usize,
+    inline: [MaybeUninit<T>; N],
+    heap: Option<Vec<T>>,
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            // An array of MaybeUninit needs no initialization
+            inline: unsafe { MaybeUninit::uninit().assume_init() },
+            heap: None,
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if let Some(heap) = self.heap.as_mut() {
+            heap.push(value);
+            return;
+        }
+        if self.len < N {
+            self.inline[self.len] = MaybeUninit::new(value);
+            self.len += 1;
+            return;
+        }
+        self.spill(N * 2).push(value);
+    }
+
+    /// Whether the elements are moved to the heap
+    pub fn spilled(&self) -> bool {
+        self.heap.is_some()
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        match self.heap.as_ref() {
+            Some(heap) => heap.as_slice(),
+            None => unsafe {
+                std::slice::from_raw_parts(self.inline.as_ptr() as *const T, self.len)
+            },
+        }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match self.heap.as_mut() {
+            Some(heap) => heap.as_mut_slice(),
+            None => unsafe {
+                std::slice::from_raw_parts_mut(self.inline.as_mut_ptr() as *mut T, self.len)
+            },
+        }
+    }
+
+    // Move the inline elements to the heap
+    fn spill(&mut self, capacity: usize) -> &mut Vec<T> {
+        debug_assert!(self.heap.is_none());
+        let mut heap = Vec::with_capacity(max(capacity, self.len));
+        for value in self.inline[..self.len].iter() {
+            heap.push(unsafe { ptr::read(value.as_ptr()) });
+        }
+        self.len = 0;
+        self.heap.get_or_insert(heap)
+    }
+}
+
+impl<T, const N: usize> Drop for SmallVec<T, N> {
+    fn drop(&mut self) {
+        if self.heap.is_none() {
+            unsafe { ptr::drop_in_place(self.as_mut_slice()) };
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SmallVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for SmallVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SmallVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut vec = Self::new();
+        // Go to the heap directly if the elements cannot fit inline anyway
+        let (min_len, _) = iter.size_hint();
+        if min_len > N {
+            vec.spill(min_len);
+        }
+        for value in iter {
+            vec.push(value);
+        }
+        vec
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for SmallVec<T, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
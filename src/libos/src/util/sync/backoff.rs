@@ -0,0 +1,115 @@
+//! Contention-aware locking of the LibOS-internal mutexes on the hot paths of
+//! syscalls, e.g., the file tables and the VM managers of processes.
+//!
+//! A thread that finds a `SgxMutex` locked sleeps in the host via an OCall, and is
+//! woken up via another OCall by the thread that unlocks it. As the hot locks are
+//! held briefly, the sleep often costs much more than the wait itself, and a thread
+//! woken up late stalls every thread waiting behind it.
+//!
+//! So `lock_with_backoff` spins on the lock with an exponential backoff before
+//! sleeping. The spinning never leaves the enclave and is bounded by about the
+//! cost of one OCall, after which the thread sleeps in the host as usual, letting
+//! the host schedule the holder if it is preempted. Yielding the CPU to the host
+//! instead would take an OCall per try without any bound on the wait, so the
+//! backoff does not yield.
+//!
+//! With the `lock_stat` feature, a lock that is not available right away is
+//! recorded as a contended one, with the time spent in the backoff as its wait.
+
+use crate::prelude::*;
+#[cfg(feature = "lock_stat")]
+use core::panic::Location;
+use std::sync::atomic::spin_loop_hint;
+use std::sync::{LockResult, TryLockError};
+
+// Spin for up to 2^SPIN_LIMIT pauses in a step, i.e., 2^(SPIN_LIMIT+1)-1 pauses
+// in total, which take about as many cycles as an OCall (~10k cycles) on the CPUs
+// where a pause takes ~100 cycles
+const SPIN_LIMIT: u32 = 6;
+
+pub trait LockBackoff<T: ?Sized> {
+    /// Lock the mutex, trying it with a backoff before sleeping in it.
+    fn lock_with_backoff(&self) -> LockResult<SgxMutexGuard<'_, T>>;
+
+    /// Try the mutex with a backoff, but give up instead of sleeping in it, e.g.,
+    /// where the mutex may be held by the current thread.
+    fn try_lock_with_backoff(&self) -> Option<SgxMutexGuard<'_, T>>;
+}
+
+#[cfg(not(feature = "lock_stat"))]
+impl<T: ?Sized> LockBackoff<T> for SgxMutex<T> {
+    fn lock_with_backoff(&self) -> LockResult<SgxMutexGuard<'_, T>> {
+        lock_inner_with_backoff(self)
+    }
+
+    fn try_lock_with_backoff(&self) -> Option<SgxMutexGuard<'_, T>> {
+        try_lock_inner_with_backoff(self)?.ok()
+    }
+}
+
+#[cfg(feature = "lock_stat")]
+impl<T: ?Sized> LockBackoff<T> for SgxMutex<T> {
+    #[track_caller]
+    fn lock_with_backoff(&self) -> LockResult<SgxMutexGuard<'_, T>> {
+        self.lock_or_else::<()>(Location::caller(), |inner| {
+            Ok(lock_inner_with_backoff(inner))
+        })
+        .unwrap()
+    }
+
+    #[track_caller]
+    fn try_lock_with_backoff(&self) -> Option<SgxMutexGuard<'_, T>> {
+        self.lock_or_else(Location::caller(), |inner| {
+            try_lock_inner_with_backoff(inner).ok_or(())
+        })
+        .ok()?
+        .ok()
+    }
+}
+
+// The mutexes of SGX SDK, which are not instrumented even with `lock_stat`
+type InnerMutex<T> = std::sync::SgxMutex<T>;
+type InnerMutexGuard<'a, T> = std::sync::SgxMutexGuard<'a, T>;
+
+fn lock_inner_with_backoff<T: ?Sized>(mutex: &InnerMutex<T>) -> LockResult<InnerMutexGuard<'_, T>> {
+    try_lock_inner_with_backoff(mutex).unwrap_or_else(|| mutex.lock())
+}
+
+/// Try the mutex with a backoff, or return None if it is time to give up
+fn try_lock_inner_with_backoff<T: ?Sized>(
+    mutex: &InnerMutex<T>,
+) -> Option<LockResult<InnerMutexGuard<'_, T>>> {
+    let mut backoff = Backoff::new();
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(Ok(guard)),
+            Err(TryLockError::Poisoned(e)) => return Some(Err(e)),
+            Err(TryLockError::WouldBlock) => {}
+        }
+        if !backoff.snooze() {
+            return None;
+        }
+    }
+}
+
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    /// Back off for a while, or return false if it is time to give up
+    pub fn snooze(&mut self) -> bool {
+        if self.step > SPIN_LIMIT {
+            return false;
+        }
+        for _ in 0..1 << self.step {
+            spin_loop_hint();
+        }
+        self.step += 1;
+        true
+    }
+}
@@ -0,0 +1,150 @@
+//! Contention statistics of LibOS-internal locks.
+//!
+//! When the `lock_stat` feature is enabled, every acquisition of `SgxMutex` and
+//! `RwLock` is recorded per call site, including whether the lock is contended,
+//! how long it takes to acquire the lock and how long the lock is held. The
+//! statistics are dumped via /proc/occlum/lockstat, where the times are in TSC
+//! cycles.
+//!
+//! The times are read from the TSC by the OCall of rdtsc, rather than by
+//! clock_gettime, which may acquire instrumented locks itself. The instrumentation
+//! slows down locking considerably since it reads the TSC twice per acquisition.
+//! So it is intended for diagnosing scalability problems only. When the feature is
+//! disabled, all of the instrumentation is compiled away.
+
+use super::*;
+use core::panic::Location;
+
+/// Acquire a lock by calling `lock`, recording the statistics at `site`.
+///
+/// `try_lock` is called first to find out whether the lock is contended. If it
+/// succeeds, `lock` is not called.
+#[inline(always)]
+pub fn acquire<G, E>(
+    site: &'static Location<'static>,
+    try_lock: impl FnOnce() -> Option<G>,
+    lock: impl FnOnce() -> core::result::Result<G, E>,
+) -> core::result::Result<(G, HoldTimer), E> {
+    #[cfg(feature = "lock_stat")]
+    {
+        let wait_start = imp::now_cycles();
+        let (guard, contended) = match try_lock() {
+            Some(guard) => (guard, false),
+            None => (lock()?, true),
+        };
+        let acquired_at = imp::now_cycles();
+        imp::record_acquire(site, contended, acquired_at.saturating_sub(wait_start));
+        Ok((guard, HoldTimer { site, acquired_at }))
+    }
+    #[cfg(not(feature = "lock_stat"))]
+    {
+        let _ = (site, try_lock);
+        Ok((lock()?, HoldTimer {}))
+    }
+}
+
+/// Record the hold time of a lock when dropped.
+#[derive(Debug)]
+pub struct HoldTimer {
+    #[cfg(feature = "lock_stat")]
+    site: &'static Location<'static>,
+    #[cfg(feature = "lock_stat")]
+    acquired_at: u64,
+}
+
+impl Drop for HoldTimer {
+    fn drop(&mut self) {
+        #[cfg(feature = "lock_stat")]
+        imp::record_release(
+            self.site,
+            imp::now_cycles().saturating_sub(self.acquired_at),
+        );
+    }
+}
+
+/// Format the lock statistics, sorted by the number of contentions.
+#[cfg(feature = "lock_stat")]
+pub fn format_lock_stats() -> String {
+    imp::format_lock_stats()
+}
+
+#[cfg(feature = "lock_stat")]
+mod imp {
+    use super::*;
+    use crate::time::do_rdtsc;
+    use std::fmt::Write;
+
+    #[derive(Debug, Default)]
+    struct LockStat {
+        acquisitions: u64,
+        contentions: u64,
+        total_wait_cycles: u64,
+        max_wait_cycles: u64,
+        max_hold_cycles: u64,
+    }
+
+    type Site = (&'static str, u32);
+
+    lazy_static! {
+        // Use the mutex of SGX SDK directly, which must not be instrumented
+        static ref LOCK_STATS: std::sync::SgxMutex<HashMap<Site, LockStat>> =
+            std::sync::SgxMutex::new(HashMap::new());
+    }
+
+    fn site_of(location: &'static Location<'static>) -> Site {
+        (location.file(), location.line())
+    }
+
+    pub fn record_acquire(location: &'static Location<'static>, contended: bool, wait_cycles: u64) {
+        let mut stats = LOCK_STATS.lock().unwrap();
+        let stat = stats.entry(site_of(location)).or_default();
+        stat.acquisitions += 1;
+        if contended {
+            stat.contentions += 1;
+            stat.total_wait_cycles += wait_cycles;
+            stat.max_wait_cycles = max(stat.max_wait_cycles, wait_cycles);
+        }
+    }
+
+    pub fn record_release(location: &'static Location<'static>, hold_cycles: u64) {
+        let mut stats = LOCK_STATS.lock().unwrap();
+        let stat = stats.entry(site_of(location)).or_default();
+        stat.max_hold_cycles = max(stat.max_hold_cycles, hold_cycles);
+    }
+
+    pub fn format_lock_stats() -> String {
+        let stats = LOCK_STATS.lock().unwrap();
+        let mut sorted_stats: Vec<(&Site, &LockStat)> = stats.iter().collect();
+        sorted_stats.sort_by(|(_, a), (_, b)| b.contentions.cmp(&a.contentions));
+
+        let mut s = String::new();
+        writeln!(
+            s,
+            "{:<48} {:>12} {:>12} {:>18} {:>18} {:>18}",
+            "call site",
+            "acquisitions",
+            "contentions",
+            "total_wait(cycles)",
+            "max_wait(cycles)",
+            "max_hold(cycles)"
+        );
+        for ((file, line), stat) in sorted_stats {
+            writeln!(
+                s,
+                "{:<48} {:>12} {:>12} {:>18} {:>18} {:>18}",
+                format!("{}:{}", file, line),
+                stat.acquisitions,
+                stat.contentions,
+                stat.total_wait_cycles,
+                stat.max_wait_cycles,
+                stat.max_hold_cycles
+            );
+        }
+        s
+    }
+
+    pub fn now_cycles() -> u64 {
+        let (low, high) = do_rdtsc();
+        ((high as u64) << 32) | low as u64
+    }
+}
@@ -1,5 +1,11 @@
 use super::*;
 
+pub use backoff::LockBackoff;
 pub use rw_lock::RwLock;
+pub use seq_range_set::SeqRangeSet;
 
+pub mod backoff;
+pub mod lock_stat;
+pub mod mutex;
 pub mod rw_lock;
+pub mod seq_range_set;
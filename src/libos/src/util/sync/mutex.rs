@@ -0,0 +1,131 @@
+//! An instrumented wrapper of the mutex of SGX SDK.
+//!
+//! It has the same methods as `std::sync::SgxMutex` and is used in place of the
+//! latter when the `lock_stat` feature is enabled. See `lock_stat` for more info.
+
+use super::lock_stat::{self, HoldTimer};
+use super::*;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::panic::Location;
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+
+pub struct SgxMutex<T: ?Sized> {
+    inner: std::sync::SgxMutex<T>,
+}
+
+pub struct SgxMutexGuard<'a, T: ?Sized + 'a> {
+    inner: std::sync::SgxMutexGuard<'a, T>,
+    _hold_timer: HoldTimer,
+}
+
+impl<T> SgxMutex<T> {
+    pub fn new(t: T) -> SgxMutex<T> {
+        SgxMutex {
+            inner: std::sync::SgxMutex::new(t),
+        }
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> SgxMutex<T> {
+    #[track_caller]
+    pub fn lock(&self) -> LockResult<SgxMutexGuard<'_, T>> {
+        self.lock_or_else::<()>(Location::caller(), |inner| Ok(inner.lock()))
+            .unwrap()
+    }
+
+    /// Lock the mutex by `lock_inner` if it is not available right away, which is
+    /// recorded as a contended acquisition at `site`. The error of `lock_inner`,
+    /// e.g., of giving up, is not recorded.
+    pub fn lock_or_else<'a, E>(
+        &'a self,
+        site: &'static Location<'static>,
+        lock_inner: impl FnOnce(
+            &'a std::sync::SgxMutex<T>,
+        )
+            -> core::result::Result<LockResult<std::sync::SgxMutexGuard<'a, T>>, E>,
+    ) -> core::result::Result<LockResult<SgxMutexGuard<'a, T>>, E> {
+        let (inner, hold_timer) = lock_stat::acquire(
+            site,
+            || match self.inner.try_lock() {
+                Ok(inner) => Some(Ok(inner)),
+                Err(TryLockError::Poisoned(e)) => Some(Err(e)),
+                Err(TryLockError::WouldBlock) => None,
+            },
+            || lock_inner(&self.inner),
+        )?;
+        Ok(match inner {
+            Ok(inner) => Ok(SgxMutexGuard::new(inner, hold_timer)),
+            Err(e) => Err(PoisonError::new(SgxMutexGuard::new(
+                e.into_inner(),
+                hold_timer,
+            ))),
+        })
+    }
+
+    #[track_caller]
+    pub fn try_lock(&self) -> TryLockResult<SgxMutexGuard<'_, T>> {
+        let site = Location::caller();
+        let wrap = |inner| {
+            let ((), hold_timer) =
+                lock_stat::acquire::<_, ()>(site, || Some(()), || Ok(())).unwrap();
+            SgxMutexGuard::new(inner, hold_timer)
+        };
+        match self.inner.try_lock() {
+            Ok(inner) => Ok(wrap(inner)),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(wrap(
+                e.into_inner(),
+            )))),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> Default for SgxMutex<T> {
+    fn default() -> SgxMutex<T> {
+        SgxMutex::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SgxMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<'a, T: ?Sized> SgxMutexGuard<'a, T> {
+    fn new(inner: std::sync::SgxMutexGuard<'a, T>, hold_timer: HoldTimer) -> Self {
+        Self {
+            inner,
+            _hold_timer: hold_timer,
+        }
+    }
+}
+
+impl<T: ?Sized> Deref for SgxMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner.deref()
+    }
+}
+
+impl<T: ?Sized> DerefMut for SgxMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.inner.deref_mut()
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for SgxMutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
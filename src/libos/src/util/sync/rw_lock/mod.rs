@@ -2,8 +2,10 @@ mod inner;
 
 use super::*;
 
+use super::lock_stat::{self, HoldTimer};
 use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
+use core::panic::Location;
 use core::{fmt, mem, ptr};
 use inner::RwLockInner;
 use std::boxed::Box;
@@ -22,6 +24,7 @@ unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
 // The RAII guard for read that can be held by many readers
 pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
     lock: &'a RwLock<T>,
+    _hold_timer: HoldTimer,
 }
 
 // The read guard can be obtained by different threads
@@ -32,6 +35,7 @@ unsafe impl<T: ?Sized + Sync> Sync for RwLockReadGuard<'_, T> {}
 // The RAII gurad for write that can be held by only one writer
 pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
     lock: &'a RwLock<T>,
+    _hold_timer: HoldTimer,
 }
 
 // The write guard can be obtained by different threads
@@ -49,27 +53,43 @@ impl<T> RwLock<T> {
 }
 
 impl<T: ?Sized> RwLock<T> {
+    #[track_caller]
     pub fn read(&self) -> Result<RwLockReadGuard<'_, T>> {
-        self.inner.read()?;
-        RwLockReadGuard::new(self)
+        let ((), hold_timer) = lock_stat::acquire(
+            Location::caller(),
+            || self.inner.try_read().ok(),
+            || self.inner.read(),
+        )?;
+        RwLockReadGuard::new(self, hold_timer)
     }
 
+    #[track_caller]
     pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>> {
         self.inner.try_read()?;
-        RwLockReadGuard::new(self)
+        let ((), hold_timer) =
+            lock_stat::acquire::<_, Error>(Location::caller(), || Some(()), || Ok(()))?;
+        RwLockReadGuard::new(self, hold_timer)
     }
 
+    #[track_caller]
     pub fn write(&self) -> Result<RwLockWriteGuard<'_, T>> {
         unsafe {
-            self.inner.write()?;
-            RwLockWriteGuard::new(self)
+            let ((), hold_timer) = lock_stat::acquire(
+                Location::caller(),
+                || self.inner.try_write().ok(),
+                || self.inner.write(),
+            )?;
+            RwLockWriteGuard::new(self, hold_timer)
         }
     }
 
+    #[track_caller]
     pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>> {
         unsafe {
             self.inner.try_write()?;
-            RwLockWriteGuard::new(self)
+            let ((), hold_timer) =
+                lock_stat::acquire::<_, Error>(Location::caller(), || Some(()), || Ok(()))?;
+            RwLockWriteGuard::new(self, hold_timer)
         }
     }
 
@@ -116,14 +136,20 @@ impl<T: core::fmt::Debug> fmt::Debug for RwLock<T> {
 }
 
 impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
-    pub fn new(lock: &'a RwLock<T>) -> Result<RwLockReadGuard<'a, T>> {
-        Ok(RwLockReadGuard { lock })
+    pub fn new(lock: &'a RwLock<T>, hold_timer: HoldTimer) -> Result<RwLockReadGuard<'a, T>> {
+        Ok(RwLockReadGuard {
+            lock,
+            _hold_timer: hold_timer,
+        })
     }
 }
 
 impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
-    pub fn new(lock: &'a RwLock<T>) -> Result<RwLockWriteGuard<'a, T>> {
-        Ok(RwLockWriteGuard { lock })
+    pub fn new(lock: &'a RwLock<T>, hold_timer: HoldTimer) -> Result<RwLockWriteGuard<'a, T>> {
+        Ok(RwLockWriteGuard {
+            lock,
+            _hold_timer: hold_timer,
+        })
     }
 }
 
@@ -0,0 +1,134 @@
+//! A set of address ranges that can be checked for overlaps without any lock.
+//!
+//! The set is meant for the checks on the hot paths (e.g., of every user buffer
+//! given to a system call) against a few ranges that are rarely updated. The
+//! ranges are kept in a fixed number of slots guarded by a sequence lock: the
+//! updates are serialized by a mutex and bump the sequence number, while a check
+//! reads the slots and retries if the sequence number has changed meanwhile.
+//!
+//! Once there are more ranges than slots, the set overflows and every check is
+//! answered by "overlapped". So the answer of a check is conservative, and the
+//! caller should double-check a positive answer against its own bookkeeping.
+use super::*;
+use std::fmt;
+use std::sync::atomic::{fence, spin_loop_hint, AtomicBool, AtomicUsize, Ordering};
+
+const NUM_SLOTS: usize = 64;
+
+pub struct SeqRangeSet {
+    // Odd while the slots are being updated
+    seq: AtomicUsize,
+    num_ranges: AtomicUsize,
+    // The start and end of each range
+    slots: [(AtomicUsize, AtomicUsize); NUM_SLOTS],
+    // The ranges that do not fit in the slots, which are never checked
+    num_overflowed: AtomicUsize,
+    is_overflowed: AtomicBool,
+    update_lock: SgxMutex<()>,
+}
+
+impl SeqRangeSet {
+    pub fn new() -> Self {
+        Self {
+            seq: AtomicUsize::new(0),
+            num_ranges: AtomicUsize::new(0),
+            slots: unsafe { std::mem::zeroed() },
+            num_overflowed: AtomicUsize::new(0),
+            is_overflowed: AtomicBool::new(false),
+            update_lock: SgxMutex::new(()),
+        }
+    }
+
+    /// Add the range [start, end)
+    pub fn insert(&self, start: usize, end: usize) {
+        let _guard = self.update_lock.lock().unwrap();
+        let num_ranges = self.num_ranges.load(Ordering::Relaxed);
+        if num_ranges == NUM_SLOTS {
+            self.num_overflowed.fetch_add(1, Ordering::Relaxed);
+            self.is_overflowed.store(true, Ordering::Release);
+            return;
+        }
+        self.update(|slots| {
+            slots[num_ranges].0.store(start, Ordering::Relaxed);
+            slots[num_ranges].1.store(end, Ordering::Relaxed);
+            self.num_ranges.store(num_ranges + 1, Ordering::Relaxed);
+        });
+    }
+
+    /// Remove the range [start, end), which must have been added
+    pub fn remove(&self, start: usize, end: usize) {
+        let _guard = self.update_lock.lock().unwrap();
+        let num_ranges = self.num_ranges.load(Ordering::Relaxed);
+        let slot_i = self.slots[..num_ranges]
+            .iter()
+            .position(|(slot_start, slot_end)| {
+                slot_start.load(Ordering::Relaxed) == start
+                    && slot_end.load(Ordering::Relaxed) == end
+            });
+        let slot_i = match slot_i {
+            Some(slot_i) => slot_i,
+            None => {
+                // The range must be one of the overflowed ones
+                if self.num_overflowed.fetch_sub(1, Ordering::Relaxed) == 1 {
+                    self.is_overflowed.store(false, Ordering::Release);
+                }
+                return;
+            }
+        };
+        self.update(|slots| {
+            // Move the last range into the slot
+            let last = &slots[num_ranges - 1];
+            slots[slot_i]
+                .0
+                .store(last.0.load(Ordering::Relaxed), Ordering::Relaxed);
+            slots[slot_i]
+                .1
+                .store(last.1.load(Ordering::Relaxed), Ordering::Relaxed);
+            self.num_ranges.store(num_ranges - 1, Ordering::Relaxed);
+        });
+    }
+
+    /// Whether [start, end) may overlap with any range in the set
+    pub fn overlaps(&self, start: usize, end: usize) -> bool {
+        if self.is_overflowed.load(Ordering::Acquire) {
+            return true;
+        }
+        loop {
+            let seq = self.seq.load(Ordering::Acquire);
+            if seq % 2 == 1 {
+                spin_loop_hint();
+                continue;
+            }
+            let num_ranges = min(self.num_ranges.load(Ordering::Relaxed), NUM_SLOTS);
+            let overlaps = self.slots[..num_ranges]
+                .iter()
+                .any(|(slot_start, slot_end)| {
+                    slot_start.load(Ordering::Relaxed) < end
+                        && start < slot_end.load(Ordering::Relaxed)
+                });
+            fence(Ordering::Acquire);
+            if self.seq.load(Ordering::Relaxed) == seq {
+                return overlaps;
+            }
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&[(AtomicUsize, AtomicUsize); NUM_SLOTS])) {
+        self.seq.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        f(&self.slots);
+        self.seq.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl fmt::Debug for SeqRangeSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeqRangeSet")
+            .field("num_ranges", &self.num_ranges.load(Ordering::Relaxed))
+            .field(
+                "num_overflowed",
+                &self.num_overflowed.load(Ordering::Relaxed),
+            )
+            .finish()
+    }
+}
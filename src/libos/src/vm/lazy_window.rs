@@ -0,0 +1,213 @@
+//! The mappings of host file windows, whose pages are populated on first access.
+//!
+//! A mapping of a host file window (see `fs::HostFileWindow`) is made inaccessible
+//! at mmap. Each page of it is copied from the window and verified against its
+//! trusted hash on the first access, so mapping a large file costs neither the
+//! time of copying nor that of hashing the pages that are never accessed. The
+//! first access by the user faults on the fault path of the LibOS (which requires
+//! SGX 2, or the `sgx1_exception_sim` feature on SGX 1), and the buffers given to
+//! the system calls are populated by `from_user` before the LibOS accesses them.
+//!
+//! A page that fails the verification is never populated: the fault becomes a
+//! signal and the system call fails with EIO. The pending pages of a range are
+//! populated before the range is mprotect-ed or mremap-ed, after which the range
+//! is detached from the mapping, i.e., managed as ordinary memory.
+//!
+//! The ranges of the mappings with pending pages are also kept in a `SeqRangeSet`,
+//! so that checking a buffer outside them takes no lock. The lock of the mappings
+//! is never held while accessing the pages that are not populated, so the fault
+//! path can wait for it.
+use super::vm_manager::VMManager;
+use super::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use util::sync::SeqRangeSet;
+
+lazy_static! {
+    static ref LAZY_WINDOWS: SgxMutex<Vec<LazyWindow>> = SgxMutex::new(Vec::new());
+    static ref PENDING_RANGES: SeqRangeSet = SeqRangeSet::new();
+}
+
+/// The number of the mappings, so that the faults are not looked up if there is none
+static NUM_LAZY_WINDOWS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PageState {
+    Pending,
+    Populated,
+    // Unmapped, or no longer managed as part of the mapping
+    Detached,
+}
+
+struct LazyWindow {
+    range: VMRange,
+    window: Arc<HostFileWindow>,
+    // The file offset of the start of the range
+    file_offset: usize,
+    perms: VMPerms,
+    pages: Vec<PageState>,
+    // Whether the range is in PENDING_RANGES
+    has_pending_range: bool,
+}
+
+impl LazyWindow {
+    fn page_i(&self, page: usize) -> usize {
+        (page - self.range.start()) / PAGE_SIZE
+    }
+
+    fn page_indexes(&self, range: &VMRange) -> Option<std::ops::Range<usize>> {
+        let intersection = self.range.intersect(range)?;
+        let start = (intersection.start() - self.range.start()) / PAGE_SIZE;
+        let end = (intersection.end() - self.range.start()) / PAGE_SIZE;
+        Some(start..end)
+    }
+
+    /// Populate the pending pages in the range from the window
+    fn populate(&mut self, range: &VMRange) -> Result<()> {
+        let page_indexes = match self.page_indexes(range) {
+            Some(page_indexes) => page_indexes,
+            None => return Ok(()),
+        };
+        for page_i in page_indexes {
+            if self.pages[page_i] != PageState::Pending {
+                continue;
+            }
+            // Verify the page before it is accessible, so that the user never sees
+            // the content that is not verified
+            let mut page_buf = [0u8; PAGE_SIZE];
+            self.window
+                .read_verified(self.file_offset + page_i * PAGE_SIZE, &mut page_buf)
+                .map_err(|e| {
+                    warn!("failed to populate the page of host file window: {:?}", e);
+                    errno!(EIO, "the page of host file window fails the verification")
+                })?;
+
+            let page = self.range.start() + page_i * PAGE_SIZE;
+            let page_range = VMRange::new_with_size(page, PAGE_SIZE).unwrap();
+            VMManager::apply_perms(&page_range, VMPerms::default());
+            unsafe { page_range.as_slice_mut() }.copy_from_slice(&page_buf);
+            VMManager::apply_perms(&page_range, self.perms);
+            self.pages[page_i] = PageState::Populated;
+        }
+        Ok(())
+    }
+
+    fn detach(&mut self, range: &VMRange) {
+        if let Some(page_indexes) = self.page_indexes(range) {
+            for state in &mut self.pages[page_indexes] {
+                *state = PageState::Detached;
+            }
+        }
+    }
+}
+
+/// Map the range from the window starting from `file_offset` lazily. The range is
+/// inaccessible until its pages are populated with the permissions `perms`.
+pub(super) fn register(
+    range: &VMRange,
+    window: Arc<HostFileWindow>,
+    file_offset: usize,
+    perms: VMPerms,
+) {
+    VMManager::apply_perms(range, VMPerms::empty());
+    let mut lazy_windows = LAZY_WINDOWS.lock().unwrap();
+    lazy_windows.push(LazyWindow {
+        range: *range,
+        window,
+        file_offset,
+        perms,
+        pages: vec![PageState::Pending; range.size() / PAGE_SIZE],
+        has_pending_range: true,
+    });
+    PENDING_RANGES.insert(range.start(), range.end());
+    NUM_LAZY_WINDOWS.fetch_add(1, Ordering::Release);
+}
+
+/// Populate the pages of the user buffer that are mapped from host file windows
+/// but not populated yet
+pub fn populate_window_pages(addr: usize, len: usize) -> Result<()> {
+    let end = addr.saturating_add(len);
+    if len == 0 || !PENDING_RANGES.overlaps(addr, end) {
+        return Ok(());
+    }
+    let range = VMRange::new(align_down(addr, PAGE_SIZE), align_up(end, PAGE_SIZE))?;
+    let mut lazy_windows = LAZY_WINDOWS.lock().unwrap();
+    let result = lazy_windows
+        .iter_mut()
+        .try_for_each(|lazy_window| lazy_window.populate(&range));
+    update_lazy_windows(&mut lazy_windows);
+    result
+}
+
+/// Populate the pending pages in the range, after which the range is no longer
+/// managed as part of the mappings
+pub(super) fn populate_then_detach(range: &VMRange) -> Result<()> {
+    if !PENDING_RANGES.overlaps(range.start(), range.end()) {
+        return Ok(());
+    }
+    let mut lazy_windows = LAZY_WINDOWS.lock().unwrap();
+    let result = lazy_windows.iter_mut().try_for_each(|lazy_window| {
+        lazy_window.populate(range)?;
+        lazy_window.detach(range);
+        Ok(())
+    });
+    update_lazy_windows(&mut lazy_windows);
+    result
+}
+
+/// Handle the page fault at `addr` if it is in a mapping of a host file window.
+/// Return whether the fault is handled, after which the access can be tried again.
+pub fn handle_window_fault(addr: usize, is_write: bool) -> Result<bool> {
+    if NUM_LAZY_WINDOWS.load(Ordering::Acquire) == 0 {
+        return Ok(false);
+    }
+    let page = align_down(addr, PAGE_SIZE);
+    let page_range = VMRange::new_with_size(page, PAGE_SIZE)?;
+    let mut lazy_windows = LAZY_WINDOWS.lock().unwrap();
+    let lazy_window = match lazy_windows.iter_mut().find(|lazy_window| {
+        lazy_window.range.contains(page)
+            && lazy_window.pages[lazy_window.page_i(page)] != PageState::Detached
+    }) {
+        Some(lazy_window) => lazy_window,
+        None => return Ok(false),
+    };
+    let page_i = lazy_window.page_i(page);
+    let is_handled = match lazy_window.pages[page_i] {
+        PageState::Pending => lazy_window.populate(&page_range).is_ok(),
+        // The page is populated by another thread after the fault, so the access
+        // succeeds when tried again unless it is not permitted
+        _ => lazy_window.perms.can_read() && (!is_write || lazy_window.perms.can_write()),
+    };
+    update_lazy_windows(&mut lazy_windows);
+    Ok(is_handled)
+}
+
+/// Detach the range that is unmapped from the mappings
+pub(super) fn unregister_unmapped(range: &VMRange) {
+    if NUM_LAZY_WINDOWS.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    let mut lazy_windows = LAZY_WINDOWS.lock().unwrap();
+    for lazy_window in lazy_windows.iter_mut() {
+        lazy_window.detach(range);
+    }
+    update_lazy_windows(&mut lazy_windows);
+}
+
+/// Remove the ranges without pending pages from PENDING_RANGES, and the mappings
+/// that are detached entirely
+fn update_lazy_windows(lazy_windows: &mut Vec<LazyWindow>) {
+    for lazy_window in lazy_windows.iter_mut() {
+        if lazy_window.has_pending_range && !lazy_window.pages.contains(&PageState::Pending) {
+            PENDING_RANGES.remove(lazy_window.range.start(), lazy_window.range.end());
+            lazy_window.has_pending_range = false;
+        }
+    }
+    let old_len = lazy_windows.len();
+    lazy_windows.retain(|lazy_window| {
+        lazy_window
+            .pages
+            .iter()
+            .any(|state| *state != PageState::Detached)
+    });
+    NUM_LAZY_WINDOWS.fetch_sub(old_len - lazy_windows.len(), Ordering::Release);
+}
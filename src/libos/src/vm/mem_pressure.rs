@@ -0,0 +1,85 @@
+//! The notifications of the memory pressure of processes.
+//!
+//! The mmap area of a process is fixed in size and is usually small inside an
+//! enclave. So a process is under memory pressure once the free memory of its mmap
+//! area falls below the threshold in the config, which gives the caches of the
+//! application (e.g., the heap of JVM or an in-memory database) a chance to shed
+//! memory before mmap fails with ENOMEM.
+//!
+//! The notifiers are eventfds, which are created by opening /dev/occlum_mem_pressure.
+//! Each time the process comes under pressure, the counters of all its notifiers
+//! are increased by one, so the notifiers are readable until the counters are read.
+//! The process has to recover from the pressure, i.e., the free memory has to grow
+//! back to the threshold, before it can be notified again.
+use super::*;
+use fs::EventFile;
+use std::sync::Weak;
+
+#[derive(Debug, Default)]
+pub struct MemPressure {
+    // The pressure is disabled if the threshold is zero
+    threshold: usize,
+    inner: SgxMutex<MemPressureInner>,
+}
+
+#[derive(Debug, Default)]
+struct MemPressureInner {
+    is_under_pressure: bool,
+    notifiers: Vec<Weak<EventFile>>,
+}
+
+impl MemPressure {
+    pub fn new() -> Self {
+        Self {
+            threshold: config::LIBOS_CONFIG.process.mem_pressure_threshold,
+            inner: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    /// Add a notifier, which is notified at once if the process is already under
+    /// pressure
+    pub fn add_notifier(&self, notifier: &Arc<EventFile>) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .notifiers
+            .retain(|notifier| notifier.upgrade().is_some());
+        inner.notifiers.push(Arc::downgrade(notifier));
+        if inner.is_under_pressure {
+            notify(notifier);
+        }
+    }
+
+    /// Update the pressure with the free memory of the mmap area
+    pub fn update(&self, free_size: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        let is_under_pressure = free_size < self.threshold;
+        if is_under_pressure == inner.is_under_pressure {
+            return;
+        }
+        inner.is_under_pressure = is_under_pressure;
+        if !is_under_pressure {
+            return;
+        }
+        debug!(
+            "memory pressure: free mmap memory {:#x} is below the threshold {:#x}",
+            free_size, self.threshold
+        );
+        inner.notifiers.retain(|notifier| match notifier.upgrade() {
+            Some(notifier) => {
+                notify(&notifier);
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+fn notify(notifier: &EventFile) {
+    if let Err(e) = notifier.write(&1u64.to_ne_bytes()) {
+        warn!("failed to notify the memory pressure: {:?}", e);
+    }
+}
@@ -1,10 +1,13 @@
 use super::*;
-use fs::{File, FileDesc, FileRef};
+use fs::{report_security_event, File, FileDesc, FileRef, HostFileWindow, SecurityEventKind};
 use process::{Process, ProcessRef};
 use std::fmt;
 
+mod lazy_window;
+mod mem_pressure;
 mod process_vm;
 mod user_space_vm;
+mod userfaultfd;
 mod vm_area;
 mod vm_layout;
 mod vm_manager;
@@ -14,8 +17,15 @@ mod vm_range;
 use self::vm_layout::VMLayout;
 use self::vm_manager::{VMManager, VMMapOptionsBuilder};
 
-pub use self::process_vm::{MMapFlags, MRemapFlags, MSyncFlags, ProcessVM, ProcessVMBuilder};
+pub use self::lazy_window::{handle_window_fault, populate_window_pages};
+pub use self::mem_pressure::MemPressure;
+pub use self::process_vm::{
+    MAdvice, MMapFlags, MRemapFlags, MSyncFlags, ProcessVM, ProcessVMBuilder,
+};
 pub use self::user_space_vm::USER_SPACE_VM_MANAGER;
+pub use self::userfaultfd::{
+    handle_user_fault, has_missing_user_pages, AsUserfaultFile, UserfaultFile,
+};
 pub use self::vm_perms::VMPerms;
 pub use self::vm_range::VMRange;
 
@@ -38,6 +48,7 @@ pub fn do_mmap(
             addr, size, perms, flags, fd, offset
         );
     }
+    report_wx_mapping("mmap", addr, size, perms);
 
     current!().vm().mmap(addr, size, perms, flags, fd, offset)
 }
@@ -66,9 +77,42 @@ pub fn do_mprotect(addr: usize, size: usize, perms: VMPerms) -> Result<()> {
         "mprotect: addr: {:#x}, size: {:#x}, perms: {:?}",
         addr, size, perms
     );
+    report_wx_mapping("mprotect", addr, size, perms);
     current!().vm().mprotect(addr, size, perms)
 }
 
+/// Report the mapping that is requested to be both writable and executable, which
+/// breaks W^X, as a security event
+fn report_wx_mapping(syscall: &str, addr: usize, size: usize, perms: VMPerms) {
+    if perms.can_write() && perms.can_execute() {
+        let detail = format!("{}: addr: {:#x}, size: {:#x}", syscall, addr, size);
+        report_security_event(SecurityEventKind::WxMapping, &detail);
+    }
+}
+
+pub fn do_madvise(addr: usize, size: usize, advice: MAdvice) -> Result<()> {
+    debug!(
+        "madvise: addr: {:#x}, size: {:#x}, advice: {:?}",
+        addr, size, advice
+    );
+    if addr % PAGE_SIZE != 0 {
+        return_errno!(EINVAL, "addr must be page aligned");
+    }
+    // Like Linux, the size is rounded up to pages, and nothing is done if zero
+    let size = align_up(size, PAGE_SIZE);
+    if size == 0 {
+        return Ok(());
+    }
+    current!().vm().madvise(addr, size, advice)
+}
+
+pub fn do_userfaultfd(flags: u32) -> Result<FileDesc> {
+    debug!("userfaultfd: flags: {:#x}", flags);
+    let file_ref: FileRef = Arc::new(Box::new(UserfaultFile::new(flags)?));
+    let close_on_spawn = flags & libc::O_CLOEXEC as u32 != 0;
+    Ok(current!().add_file(file_ref, close_on_spawn))
+}
+
 pub fn do_brk(addr: usize) -> Result<usize> {
     debug!("brk: addr: {:#x}", addr);
     current!().vm().brk(addr)
@@ -1,12 +1,15 @@
 use super::*;
 
 use super::config;
+use super::mem_pressure::MemPressure;
 use super::process::elf_file::{ElfFile, ProgramHeaderExt};
 use super::user_space_vm::{UserSpaceVMManager, UserSpaceVMRange, USER_SPACE_VM_MANAGER};
+use super::vm_area::VMForkFlags;
 use super::vm_manager::{
     VMInitializer, VMManager, VMMapAddr, VMMapOptions, VMMapOptionsBuilder, VMRemapOptions,
 };
 use super::vm_perms::VMPerms;
+use crate::util::sync::LockBackoff;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[derive(Debug, Clone)]
@@ -151,6 +154,7 @@ impl<'a, 'b> ProcessVMBuilder<'a, 'b> {
             stack_range,
             brk,
             mmap_manager,
+            mem_pressure: MemPressure::new(),
         })
     }
 
@@ -205,6 +209,7 @@ pub struct ProcessVM {
     heap_range: VMRange,
     stack_range: VMRange,
     brk: AtomicUsize,
+    mem_pressure: MemPressure,
     // Memory safety notes: the process_range field must be the last one.
     //
     // Rust drops fields in the same order as they are declared. So by making
@@ -223,6 +228,7 @@ impl Default for ProcessVM {
             stack_range: Default::default(),
             brk: Default::default(),
             mmap_manager: Default::default(),
+            mem_pressure: Default::default(),
         }
     }
 }
@@ -305,9 +311,22 @@ impl ProcessVM {
                 VMInitializer::FillZeros()
             } else {
                 let file_ref = current!().file(fd)?;
-                VMInitializer::LoadFromFile {
-                    file: file_ref,
-                    offset: offset,
+                // Private mappings of HostFS files with trusted page hashes can
+                // be populated from the host file window directly
+                let window = if !flags.contains(MMapFlags::MAP_SHARED) {
+                    fs::try_map_host_file_window(&file_ref, offset, size).transpose()?
+                } else {
+                    None
+                };
+                match window {
+                    Some(window) => VMInitializer::LoadFromHostWindow {
+                        window: Arc::new(window),
+                        offset: offset,
+                    },
+                    None => VMInitializer::LoadFromFile {
+                        file: file_ref,
+                        offset: offset,
+                    },
                 }
             }
         };
@@ -328,7 +347,9 @@ impl ProcessVM {
             .initializer(initializer)
             .writeback_file(writeback_file)
             .build()?;
-        let mmap_addr = self.mmap_manager.lock().unwrap().mmap(mmap_options)?;
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+        let mmap_addr = mmap_manager.mmap(mmap_options)?;
+        self.update_mem_pressure(&mmap_manager);
         Ok(mmap_addr)
     }
 
@@ -346,11 +367,33 @@ impl ProcessVM {
         }
 
         let mremap_option = VMRemapOptions::new(old_addr, old_size, new_size, flags)?;
-        self.mmap_manager.lock().unwrap().mremap(&mremap_option)
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+        let new_addr = mmap_manager.mremap(&mremap_option)?;
+        self.update_mem_pressure(&mmap_manager);
+        Ok(new_addr)
     }
 
     pub fn munmap(&self, addr: usize, size: usize) -> Result<()> {
-        self.mmap_manager.lock().unwrap().munmap(addr, size)
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+        mmap_manager.munmap(addr, size)?;
+        self.update_mem_pressure(&mmap_manager);
+        Ok(())
+    }
+
+    /// Get the total size and the free size of the mmap area
+    pub fn get_mmap_usage(&self) -> (usize, usize) {
+        let mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+        (mmap_manager.range().size(), mmap_manager.free_size())
+    }
+
+    pub fn mem_pressure(&self) -> &MemPressure {
+        &self.mem_pressure
+    }
+
+    fn update_mem_pressure(&self, mmap_manager: &VMManager) {
+        if self.mem_pressure.is_enabled() {
+            self.mem_pressure.update(mmap_manager.free_size());
+        }
     }
 
     pub fn mprotect(&self, addr: usize, size: usize, perms: VMPerms) -> Result<()> {
@@ -358,7 +401,7 @@ impl ProcessVM {
         if !self.process_range.range().is_superset_of(&protect_range) {
             return_errno!(ENOMEM, "invalid range");
         }
-        let mut mmap_manager = self.mmap_manager.lock().unwrap();
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
 
         // TODO: support mprotect vm regions in addition to mmap
         if !mmap_manager.range().is_superset_of(&protect_range) {
@@ -369,14 +412,48 @@ impl ProcessVM {
         mmap_manager.mprotect(addr, size, perms)
     }
 
+    /// The memory permissions of the range, which must be contained in exact one
+    /// mapping of the mmap region
+    pub fn perms_of(&self, range: &VMRange) -> Result<VMPerms> {
+        let mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+        if !mmap_manager.range().is_superset_of(range) {
+            return_errno!(EINVAL, "the range is not in the mmap region");
+        }
+        mmap_manager.perms_of(range)
+    }
+
+    pub fn madvise(&self, addr: usize, size: usize, advice: MAdvice) -> Result<()> {
+        let (fork_flags, is_added) = match advice {
+            MAdvice::DontFork => (VMForkFlags::DONT_FORK, true),
+            MAdvice::DoFork => (VMForkFlags::DONT_FORK, false),
+            MAdvice::WipeOnFork => (VMForkFlags::WIPE_ON_FORK, true),
+            MAdvice::KeepOnFork => (VMForkFlags::WIPE_ON_FORK, false),
+            // The hints of the access pattern make no difference to the LibOS
+            _ => return Ok(()),
+        };
+        let advise_range = VMRange::new_with_size(addr, size)?;
+        if !self.process_range.range().is_superset_of(&advise_range) {
+            return_errno!(ENOMEM, "invalid range");
+        }
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
+
+        // TODO: support madvise vm regions in addition to mmap
+        if !mmap_manager.range().is_superset_of(&advise_range) {
+            warn!("Do not support madvise memory outside the mmap region yet");
+            return Ok(());
+        }
+
+        mmap_manager.update_fork_flags(addr, size, fork_flags, is_added)
+    }
+
     pub fn msync(&self, addr: usize, size: usize) -> Result<()> {
         let sync_range = VMRange::new_with_size(addr, size)?;
-        let mut mmap_manager = self.mmap_manager.lock().unwrap();
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
         mmap_manager.msync_by_range(&sync_range)
     }
 
     pub fn msync_by_file(&self, sync_file: &FileRef) {
-        let mut mmap_manager = self.mmap_manager.lock().unwrap();
+        let mut mmap_manager = self.mmap_manager.lock_with_backoff().unwrap();
         mmap_manager.msync_by_file(sync_file);
     }
 
@@ -457,6 +534,44 @@ impl Default for MRemapFlags {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MAdvice {
+    Normal,
+    Random,
+    Sequential,
+    WillNeed,
+    DontFork,
+    DoFork,
+    WipeOnFork,
+    KeepOnFork,
+}
+
+impl MAdvice {
+    pub fn from_i32(raw_advice: i32) -> Result<Self> {
+        const MADV_NORMAL: i32 = 0;
+        const MADV_RANDOM: i32 = 1;
+        const MADV_SEQUENTIAL: i32 = 2;
+        const MADV_WILLNEED: i32 = 3;
+        const MADV_DONTFORK: i32 = 10;
+        const MADV_DOFORK: i32 = 11;
+        const MADV_WIPEONFORK: i32 = 18;
+        const MADV_KEEPONFORK: i32 = 19;
+
+        let advice = match raw_advice {
+            MADV_NORMAL => Self::Normal,
+            MADV_RANDOM => Self::Random,
+            MADV_SEQUENTIAL => Self::Sequential,
+            MADV_WILLNEED => Self::WillNeed,
+            MADV_DONTFORK => Self::DontFork,
+            MADV_DOFORK => Self::DoFork,
+            MADV_WIPEONFORK => Self::WipeOnFork,
+            MADV_KEEPONFORK => Self::KeepOnFork,
+            _ => return_errno!(EINVAL, "unsupported advice"),
+        };
+        Ok(advice)
+    }
+}
+
 bitflags! {
     pub struct MSyncFlags : u32 {
         const MS_ASYNC      = 0x1;
@@ -0,0 +1,737 @@
+//! Userfaultfds, i.e., the fds through which the page faults in the registered
+//! ranges are handled by the user.
+//!
+//! Only a subset of userfaultfd of Linux is supported: the missing-page faults in
+//! the ranges of the mmap region, which are resolved by the UFFDIO_COPY or
+//! UFFDIO_ZEROPAGE ioctl, and the UFFDIO_WAKE ioctl. The write-protect mode and the
+//! non-cooperative events (e.g., of fork, mremap or munmap) are not supported.
+//!
+//! As the LibOS commits the memory of a mapping when it is mapped, it cannot tell
+//! the pages that have been touched. So all the pages of a range are missing once
+//! the range is registered. The missing pages are made inaccessible in the page
+//! table of the host, so that the accesses to them fault on the fault path of the
+//! LibOS (which requires SGX 2, or the `sgx1_exception_sim` feature on SGX 1). The
+//! faulting thread queues a message for the userfaultfd and waits until the page is
+//! resolved or it is woken up, after which it tries the access again.
+//!
+//! The missing pages must only be accessed by the user code. The buffers given to
+//! the system calls are checked by `from_user`, which fails with EFAULT if they
+//! have missing pages, as the LibOS must not fault while serving the calls. The
+//! registered ranges with missing pages are also kept in a `SeqRangeSet`, so that
+//! checking a buffer outside them takes no lock. A range is added to the set before
+//! its pages become inaccessible, and removed after they are accessible again, so
+//! the set never misses a page that is inaccessible. The check only tells the pages
+//! missing at the time, though: a range registered by another thread while a
+//! system call is using a buffer in it is a race of the application, and the
+//! system call may fault on it like the user code.
+//!
+//! The fault path never waits for the locks here, since the fault may be taken
+//! while they are held; if they stay busy, the fault is not handled and becomes
+//! SIGSEGV. A registered range should not be remapped, or the registration stays
+//! with the old range.
+use super::vm_manager::VMManager;
+use super::*;
+use crate::fs::{AccessMode, EventCreationFlags, EventFile, IoctlCmd, StatusFlags};
+use crate::fs::{StructuredIoctlArgType, StructuredIoctlNum};
+use crate::net::{clear_notifier_status, notify_thread, wait_for_notification};
+use crate::signal::has_deliverable_signals;
+use std::any::Any;
+use std::mem::size_of;
+use std::sync::Weak;
+use util::mem_util::from_user;
+use util::sync::{LockBackoff, SeqRangeSet};
+
+const UFFD_API: u64 = 0xAA;
+const UFFD_FEATURE_THREAD_ID: u64 = 1 << 8;
+const UFFD_API_FEATURES: u64 = UFFD_FEATURE_THREAD_ID;
+
+const UFFD_USER_MODE_ONLY: u32 = 1;
+
+const UFFDIO_REGISTER_MODE_MISSING: u64 = 1 << 0;
+const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+const UFFDIO_COPY_MODE_DONTWAKE: u64 = 1 << 0;
+const UFFDIO_ZEROPAGE_MODE_DONTWAKE: u64 = 1 << 0;
+
+const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+const UFFD_PAGEFAULT_FLAG_WRITE: u64 = 1 << 0;
+
+// The ids of the ioctls, whose bits tell the ioctls that are supported
+const _UFFDIO_REGISTER: u8 = 0x00;
+const _UFFDIO_UNREGISTER: u8 = 0x01;
+const _UFFDIO_WAKE: u8 = 0x02;
+const _UFFDIO_COPY: u8 = 0x03;
+const _UFFDIO_ZEROPAGE: u8 = 0x04;
+const _UFFDIO_API: u8 = 0x3F;
+const UFFD_API_IOCTLS: u64 = 1 << _UFFDIO_REGISTER | 1 << _UFFDIO_UNREGISTER | 1 << _UFFDIO_API;
+const UFFD_API_RANGE_IOCTLS: u64 = 1 << _UFFDIO_WAKE | 1 << _UFFDIO_COPY | 1 << _UFFDIO_ZEROPAGE;
+
+const UFFDIO: u8 = 0xAA;
+const UFFDIO_API: u32 =
+    StructuredIoctlNum::new::<UffdioApi>(_UFFDIO_API, UFFDIO, StructuredIoctlArgType::InputOutput)
+        .as_u32();
+const UFFDIO_REGISTER: u32 = StructuredIoctlNum::new::<UffdioRegister>(
+    _UFFDIO_REGISTER,
+    UFFDIO,
+    StructuredIoctlArgType::InputOutput,
+)
+.as_u32();
+const UFFDIO_UNREGISTER: u32 = StructuredIoctlNum::new::<UffdioRange>(
+    _UFFDIO_UNREGISTER,
+    UFFDIO,
+    StructuredIoctlArgType::Output,
+)
+.as_u32();
+const UFFDIO_WAKE: u32 =
+    StructuredIoctlNum::new::<UffdioRange>(_UFFDIO_WAKE, UFFDIO, StructuredIoctlArgType::Output)
+        .as_u32();
+const UFFDIO_COPY: u32 = StructuredIoctlNum::new::<UffdioCopy>(
+    _UFFDIO_COPY,
+    UFFDIO,
+    StructuredIoctlArgType::InputOutput,
+)
+.as_u32();
+const UFFDIO_ZEROPAGE: u32 = StructuredIoctlNum::new::<UffdioZeropage>(
+    _UFFDIO_ZEROPAGE,
+    UFFDIO,
+    StructuredIoctlArgType::InputOutput,
+)
+.as_u32();
+
+lazy_static! {
+    static ref USERFAULT_CTXS: SgxMutex<Vec<Weak<UserfaultCtx>>> = SgxMutex::new(Vec::new());
+    // The registered ranges that have missing pages, so that the lookups of the
+    // pages outside them are skipped
+    static ref MISSING_RANGES: SeqRangeSet = SeqRangeSet::new();
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UffdioApi {
+    api: u64,
+    features: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UffdioRange {
+    start: u64,
+    len: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UffdioRegister {
+    range: UffdioRange,
+    mode: u64,
+    ioctls: u64,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UffdioCopy {
+    dst: u64,
+    src: u64,
+    len: u64,
+    mode: u64,
+    // The bytes copied, or the negative errno on failure
+    copy: i64,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct UffdioZeropage {
+    range: UffdioRange,
+    mode: u64,
+    // The bytes zeroed, or the negative errno on failure
+    zeropage: i64,
+}
+
+/// C struct of the message read from a userfaultfd, i.e., struct uffd_msg of Linux
+/// with the argument of a page fault
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+struct UffdMsg {
+    event: u8,
+    reserved1: u8,
+    reserved2: u16,
+    reserved3: u32,
+    flags: u64,
+    address: u64,
+    ptid: u32,
+    reserved4: u32,
+}
+
+/// Handle the page fault at `addr` if it is in a missing page of a registered
+/// range. Return whether the fault is handled, after which the access can be
+/// tried again.
+pub fn handle_user_fault(addr: usize, is_write: bool) -> Result<bool> {
+    let page = align_down(addr, PAGE_SIZE);
+    if !MISSING_RANGES.overlaps(page, page + PAGE_SIZE) {
+        return Ok(false);
+    }
+    let ctx = match find_ctx_of_missing_page(page) {
+        Some(ctx) => ctx,
+        None => return Ok(false),
+    };
+    let tid = current!().tid();
+    debug!("userfault: thread {} faults at {:#x}", tid, addr);
+    ctx.report_fault(tid, page, is_write);
+
+    loop {
+        // Clear the notifier before the check, so that the wakeup since the check is
+        // not missed
+        clear_notifier_status(tid)?;
+        {
+            let mut inner = ctx.inner.lock().unwrap();
+            let fault_i = inner.faults.iter().position(|fault| fault.tid == tid);
+            let is_woken = fault_i.map_or(true, |i| inner.faults[i].is_woken);
+            if is_woken || !inner.is_missing(page) || has_deliverable_signals() {
+                // The signals are delivered before the access is tried again, which
+                // may fault and be reported again
+                if let Some(i) = fault_i {
+                    inner.faults.swap_remove(i);
+                }
+                return Ok(true);
+            }
+        }
+        if let Err(e) = wait_for_notification() {
+            debug!("userfault: the wait is interrupted: {:?}", e);
+        }
+    }
+}
+
+/// Find the userfaultfd that the page is missing in, without waiting for the locks
+/// that the faulting thread may hold
+fn find_ctx_of_missing_page(page: usize) -> Option<Arc<UserfaultCtx>> {
+    let ctxs = match USERFAULT_CTXS.try_lock_with_backoff() {
+        Some(ctxs) => ctxs,
+        None => {
+            warn!("userfault: the fault at {:#x} is not handled as busy", page);
+            return None;
+        }
+    };
+    for ctx in ctxs.iter().filter_map(|ctx| ctx.upgrade()) {
+        let is_missing = match ctx.inner.try_lock_with_backoff() {
+            Some(inner) => inner.is_missing(page),
+            None => {
+                warn!("userfault: the fault at {:#x} is not handled as busy", page);
+                return None;
+            }
+        };
+        if is_missing {
+            return Some(ctx);
+        }
+    }
+    None
+}
+
+/// Whether the user buffer has pages missing in the ranges of any userfaultfd
+pub fn has_missing_user_pages(addr: usize, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    let end = match addr.checked_add(len) {
+        Some(end) => end,
+        None => return true,
+    };
+    if !MISSING_RANGES.overlaps(addr, end) {
+        return false;
+    }
+    // The set may be overflowed or have pages resolved, so the overlap is checked
+    // against the registered ranges
+    let range = match VMRange::new(align_down(addr, PAGE_SIZE), align_up(end, PAGE_SIZE)) {
+        Ok(range) => range,
+        Err(_) => return true,
+    };
+    USERFAULT_CTXS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|ctx| ctx.upgrade())
+        .any(|ctx| ctx.inner.lock().unwrap().has_missing_pages(&range))
+}
+
+/// Remove the registrations of the range that is unmapped
+pub(super) fn unregister_unmapped(range: &VMRange) {
+    for ctx in USERFAULT_CTXS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|ctx| ctx.upgrade())
+    {
+        // The memory permissions have been reset by munmap
+        ctx.inner.lock().unwrap().unregister(range, false);
+    }
+}
+
+/// Keep the missing pages of the range inaccessible after the permissions of the
+/// range are changed to `perms`, which are the ones once the pages are resolved
+pub(super) fn protect_missing_pages(range: &VMRange, perms: VMPerms) {
+    for ctx in USERFAULT_CTXS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|ctx| ctx.upgrade())
+    {
+        ctx.inner.lock().unwrap().update_perms(range, perms);
+    }
+}
+
+#[derive(Debug)]
+pub struct UserfaultFile {
+    ctx: Arc<UserfaultCtx>,
+}
+
+impl UserfaultFile {
+    pub fn new(flags: u32) -> Result<Self> {
+        let valid_flags = libc::O_CLOEXEC as u32 | libc::O_NONBLOCK as u32 | UFFD_USER_MODE_ONLY;
+        if flags & !valid_flags != 0 {
+            return_errno!(EINVAL, "invalid flags");
+        }
+        let mut event_flags = EventCreationFlags::EFD_SEMAPHORE;
+        if flags & libc::O_NONBLOCK as u32 != 0 {
+            event_flags |= EventCreationFlags::EFD_NONBLOCK;
+        }
+        let ctx = Arc::new(UserfaultCtx {
+            event: EventFile::new(0, event_flags)?,
+            inner: Default::default(),
+        });
+        let mut ctxs = USERFAULT_CTXS.lock().unwrap();
+        ctxs.retain(|ctx| ctx.upgrade().is_some());
+        ctxs.push(Arc::downgrade(&ctx));
+        Ok(Self { ctx })
+    }
+
+    pub fn get_host_fd(&self) -> c_int {
+        self.ctx.event.get_host_fd()
+    }
+
+    fn api(&self, arg: &mut UffdioApi) -> Result<()> {
+        let mut inner = self.ctx.inner.lock().unwrap();
+        if inner.features.is_some() {
+            return_errno!(EINVAL, "the API has been handshaked");
+        }
+        if arg.api != UFFD_API || arg.features & !UFFD_API_FEATURES != 0 {
+            arg.features = 0;
+            return_errno!(EINVAL, "unsupported API or features");
+        }
+        inner.features = Some(arg.features);
+        arg.features = UFFD_API_FEATURES;
+        arg.ioctls = UFFD_API_IOCTLS;
+        Ok(())
+    }
+
+    fn register(&self, arg: &mut UffdioRegister) -> Result<()> {
+        let range = to_page_range(&arg.range)?;
+        if arg.mode & UFFDIO_REGISTER_MODE_WP != 0 {
+            return_errno!(EINVAL, "the write-protect mode is not supported");
+        }
+        if arg.mode != UFFDIO_REGISTER_MODE_MISSING {
+            return_errno!(EINVAL, "invalid mode");
+        }
+        let perms = current!().vm().perms_of(&range)?;
+
+        let ctxs = USERFAULT_CTXS.lock().unwrap();
+        let is_registered = ctxs
+            .iter()
+            .filter_map(|ctx| ctx.upgrade())
+            .any(|ctx| ctx.inner.lock().unwrap().is_registered(&range));
+        if is_registered {
+            return_errno!(EBUSY, "the range has been registered");
+        }
+        let mut inner = self.ctx.inner.lock().unwrap();
+        if inner.features.is_none() {
+            return_errno!(EINVAL, "the API has not been handshaked");
+        }
+        // The range is in MISSING_RANGES before its pages are inaccessible
+        let old_missing_ranges = inner.missing_ranges();
+        inner.ranges.push(UserfaultRange {
+            range,
+            perms,
+            missing: vec![true; range.size() / PAGE_SIZE],
+        });
+        inner.sync_missing_ranges(&old_missing_ranges);
+        VMManager::apply_perms(&range, VMPerms::empty());
+        arg.ioctls = UFFD_API_RANGE_IOCTLS;
+        Ok(())
+    }
+
+    fn unregister(&self, arg: &UffdioRange) -> Result<()> {
+        let range = to_page_range(arg)?;
+        self.ctx.inner.lock().unwrap().unregister(&range, true);
+        Ok(())
+    }
+
+    fn wake(&self, arg: &UffdioRange) -> Result<()> {
+        let range = to_page_range(arg)?;
+        self.ctx.inner.lock().unwrap().wake(&range);
+        Ok(())
+    }
+
+    fn copy(&self, arg: &mut UffdioCopy) -> Result<()> {
+        let ret = to_page_range(&UffdioRange {
+            start: arg.dst,
+            len: arg.len,
+        })
+        .and_then(|dst_range| {
+            let src = arg.src as *const u8;
+            // The source with missing pages, which the LibOS must not read, fails
+            from_user::check_array(src, dst_range.size())?;
+            self.resolve(
+                &dst_range,
+                arg.mode & UFFDIO_COPY_MODE_DONTWAKE == 0,
+                |dst| dst.copy_from_slice(unsafe { std::slice::from_raw_parts(src, dst.len()) }),
+            )
+        });
+        arg.copy = match &ret {
+            Ok(()) => arg.len as i64,
+            Err(e) => -(e.errno() as i64),
+        };
+        ret
+    }
+
+    fn zeropage(&self, arg: &mut UffdioZeropage) -> Result<()> {
+        let ret = to_page_range(&arg.range).and_then(|range| {
+            let wake = arg.mode & UFFDIO_ZEROPAGE_MODE_DONTWAKE == 0;
+            self.resolve(&range, wake, |dst| {
+                for byte in dst.iter_mut() {
+                    *byte = 0;
+                }
+            })
+        });
+        arg.zeropage = match &ret {
+            Ok(()) => arg.range.len as i64,
+            Err(e) => -(e.errno() as i64),
+        };
+        ret
+    }
+
+    /// Fill the missing pages of the range by `fill`, after which the pages are
+    /// accessible with the permissions of the mapping
+    fn resolve(&self, range: &VMRange, wake: bool, fill: impl FnOnce(&mut [u8])) -> Result<()> {
+        let mut inner = self.ctx.inner.lock().unwrap();
+        let old_missing_ranges = inner.missing_ranges();
+        let registered_range = inner
+            .ranges
+            .iter_mut()
+            .find(|registered_range| registered_range.range.is_superset_of(range))
+            .ok_or_else(|| errno!(ENOENT, "the range is not registered"))?;
+        let pages = registered_range.pages_of(range);
+        if registered_range.missing[pages.clone()]
+            .iter()
+            .any(|is_missing| !is_missing)
+        {
+            return_errno!(EEXIST, "the range has pages that are not missing");
+        }
+
+        // The pages are made writable to be filled by the LibOS
+        VMManager::apply_perms(range, VMPerms::default());
+        fill(unsafe { std::slice::from_raw_parts_mut(range.start() as *mut u8, range.size()) });
+        VMManager::apply_perms(range, registered_range.perms);
+        for is_missing in &mut registered_range.missing[pages] {
+            *is_missing = false;
+        }
+        inner.sync_missing_ranges(&old_missing_ranges);
+        if wake {
+            inner.wake(range);
+        }
+        Ok(())
+    }
+}
+
+impl File for UserfaultFile {
+    // Read one message of a page fault
+    fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let msg_len = size_of::<UffdMsg>();
+        if buf.len() < msg_len {
+            return_errno!(EINVAL, "the buffer is too small for a message");
+        }
+        if self.ctx.inner.lock().unwrap().features.is_none() {
+            return_errno!(EINVAL, "the API has not been handshaked");
+        }
+        let msg = self.ctx.pop_msg()?;
+        let msg_bytes =
+            unsafe { std::slice::from_raw_parts(&msg as *const _ as *const u8, msg_len) };
+        buf[..msg_len].copy_from_slice(msg_bytes);
+        Ok(msg_len)
+    }
+
+    fn ioctl(&self, cmd: &mut IoctlCmd) -> Result<i32> {
+        let nonbuiltin_cmd = match cmd {
+            IoctlCmd::NonBuiltin(nonbuiltin_cmd) => nonbuiltin_cmd,
+            _ => return_errno!(EINVAL, "unknown ioctl cmd for userfaultfd"),
+        };
+        match nonbuiltin_cmd.cmd_num().as_u32() {
+            UFFDIO_API => self.api(nonbuiltin_cmd.arg_mut::<UffdioApi>()?)?,
+            UFFDIO_REGISTER => self.register(nonbuiltin_cmd.arg_mut::<UffdioRegister>()?)?,
+            // The range is given by the user, though the ioctl is encoded as output
+            UFFDIO_UNREGISTER => self.unregister(nonbuiltin_cmd.arg_mut::<UffdioRange>()?)?,
+            UFFDIO_WAKE => self.wake(nonbuiltin_cmd.arg_mut::<UffdioRange>()?)?,
+            UFFDIO_COPY => self.copy(nonbuiltin_cmd.arg_mut::<UffdioCopy>()?)?,
+            UFFDIO_ZEROPAGE => self.zeropage(nonbuiltin_cmd.arg_mut::<UffdioZeropage>()?)?,
+            _ => return_errno!(EINVAL, "unknown ioctl cmd for userfaultfd"),
+        }
+        Ok(0)
+    }
+
+    fn get_access_mode(&self) -> Result<AccessMode> {
+        Ok(AccessMode::O_RDWR)
+    }
+
+    fn get_status_flags(&self) -> Result<StatusFlags> {
+        self.ctx.event.get_status_flags()
+    }
+
+    fn set_status_flags(&self, new_status_flags: StatusFlags) -> Result<()> {
+        self.ctx
+            .event
+            .set_status_flags(new_status_flags & StatusFlags::O_NONBLOCK)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Drop for UserfaultFile {
+    // Like Linux, the ranges are unregistered once the userfaultfd is closed, and
+    // the faulting threads are woken up
+    fn drop(&mut self) {
+        let mut inner = self.ctx.inner.lock().unwrap();
+        let ranges: Vec<VMRange> = inner.ranges.iter().map(|range| range.range).collect();
+        for range in &ranges {
+            inner.unregister(range, true);
+        }
+    }
+}
+
+pub trait AsUserfaultFile {
+    fn as_userfault_file(&self) -> Result<&UserfaultFile>;
+}
+
+impl AsUserfaultFile for FileRef {
+    fn as_userfault_file(&self) -> Result<&UserfaultFile> {
+        self.as_any()
+            .downcast_ref::<UserfaultFile>()
+            .ok_or_else(|| errno!(EBADF, "not a userfaultfd"))
+    }
+}
+
+#[derive(Debug)]
+struct UserfaultCtx {
+    // The counter is the number of the queued messages
+    event: EventFile,
+    inner: SgxMutex<UserfaultInner>,
+}
+
+impl UserfaultCtx {
+    fn report_fault(&self, tid: pid_t, page: usize, is_write: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut msg = UffdMsg {
+            event: UFFD_EVENT_PAGEFAULT,
+            address: page as u64,
+            ..Default::default()
+        };
+        if is_write {
+            msg.flags |= UFFD_PAGEFAULT_FLAG_WRITE;
+        }
+        if inner.features.unwrap_or(0) & UFFD_FEATURE_THREAD_ID != 0 {
+            msg.ptid = tid as u32;
+        }
+        inner.msgs.push_back(msg);
+        inner.faults.push(PendingFault {
+            tid,
+            page,
+            is_woken: false,
+        });
+        // The lock is not held in the OCall, as the fault path cannot wait for it
+        drop(inner);
+        if let Err(e) = self.event.write(&1u64.to_ne_bytes()) {
+            warn!("failed to notify the page fault: {:?}", e);
+        }
+    }
+
+    fn pop_msg(&self) -> Result<UffdMsg> {
+        // Wait for a message, or fail with EAGAIN if non-blocking
+        let mut counter = [0u8; size_of::<u64>()];
+        self.event.read(&mut counter)?;
+        self.inner
+            .lock()
+            .unwrap()
+            .msgs
+            .pop_front()
+            .ok_or_else(|| errno!(EAGAIN, "no message is available"))
+    }
+}
+
+#[derive(Debug, Default)]
+struct UserfaultInner {
+    // The features enabled by the UFFDIO_API ioctl, which must be done first
+    features: Option<u64>,
+    ranges: Vec<UserfaultRange>,
+    msgs: VecDeque<UffdMsg>,
+    faults: Vec<PendingFault>,
+}
+
+impl UserfaultInner {
+    fn is_missing(&self, page: usize) -> bool {
+        self.ranges.iter().any(|range| {
+            range.range.contains(page) && range.missing[(page - range.range.start()) / PAGE_SIZE]
+        })
+    }
+
+    fn is_registered(&self, range: &VMRange) -> bool {
+        self.ranges
+            .iter()
+            .any(|registered_range| registered_range.range.overlap_with(range))
+    }
+
+    fn has_missing_pages(&self, range: &VMRange) -> bool {
+        self.ranges.iter().any(|registered_range| {
+            registered_range
+                .range
+                .intersect(range)
+                .map_or(false, |intersection| {
+                    registered_range.missing[registered_range.pages_of(&intersection)]
+                        .iter()
+                        .any(|is_missing| *is_missing)
+                })
+        })
+    }
+
+    /// Remove the registrations of the range. The missing pages are made accessible
+    /// again if `restore_perms`.
+    fn unregister(&mut self, range: &VMRange, restore_perms: bool) {
+        let old_missing_ranges = self.missing_ranges();
+        let old_ranges = std::mem::take(&mut self.ranges);
+        for registered_range in old_ranges {
+            let intersection = match registered_range.range.intersect(range) {
+                Some(intersection) if !intersection.empty() => intersection,
+                _ => {
+                    self.ranges.push(registered_range);
+                    continue;
+                }
+            };
+            if restore_perms {
+                VMManager::apply_perms(&intersection, registered_range.perms);
+            }
+            // The parts that are not unregistered are kept
+            for rest in registered_range.range.subtract(&intersection) {
+                self.ranges
+                    .push(registered_range.split(&rest, registered_range.perms));
+            }
+        }
+        self.sync_missing_ranges(&old_missing_ranges);
+        self.wake(range);
+    }
+
+    fn update_perms(&mut self, range: &VMRange, perms: VMPerms) {
+        let old_missing_ranges = self.missing_ranges();
+        let old_ranges = std::mem::take(&mut self.ranges);
+        for registered_range in old_ranges {
+            let intersection = match registered_range.range.intersect(range) {
+                Some(intersection) if !intersection.empty() => intersection,
+                _ => {
+                    self.ranges.push(registered_range);
+                    continue;
+                }
+            };
+            let updated_range = registered_range.split(&intersection, perms);
+            for (page_i, is_missing) in updated_range.missing.iter().enumerate() {
+                if *is_missing {
+                    let page = intersection.start() + page_i * PAGE_SIZE;
+                    let page_range = VMRange::new_with_size(page, PAGE_SIZE).unwrap();
+                    VMManager::apply_perms(&page_range, VMPerms::empty());
+                }
+            }
+            self.ranges.push(updated_range);
+            for rest in registered_range.range.subtract(&intersection) {
+                self.ranges
+                    .push(registered_range.split(&rest, registered_range.perms));
+            }
+        }
+        self.sync_missing_ranges(&old_missing_ranges);
+    }
+
+    /// The registered ranges that have missing pages
+    fn missing_ranges(&self) -> Vec<VMRange> {
+        self.ranges
+            .iter()
+            .filter(|registered_range| registered_range.missing.contains(&true))
+            .map(|registered_range| registered_range.range)
+            .collect()
+    }
+
+    /// Bring MISSING_RANGES up to date with the ranges that had missing pages before
+    /// the update. The new ranges are added first, so that a page split from an old
+    /// range is never out of the set.
+    fn sync_missing_ranges(&self, old_missing_ranges: &[VMRange]) {
+        let missing_ranges = self.missing_ranges();
+        for range in &missing_ranges {
+            if !old_missing_ranges.contains(range) {
+                MISSING_RANGES.insert(range.start(), range.end());
+            }
+        }
+        for range in old_missing_ranges {
+            if !missing_ranges.contains(range) {
+                MISSING_RANGES.remove(range.start(), range.end());
+            }
+        }
+    }
+
+    fn wake(&mut self, range: &VMRange) {
+        for fault in &mut self.faults {
+            if range.contains(fault.page) && !fault.is_woken {
+                fault.is_woken = true;
+                if let Err(e) = notify_thread(fault.tid) {
+                    warn!("failed to wake up thread {}: {:?}", fault.tid, e);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct UserfaultRange {
+    range: VMRange,
+    // The permissions of the pages once they are resolved
+    perms: VMPerms,
+    // Whether each page of the range is missing
+    missing: Vec<bool>,
+}
+
+impl UserfaultRange {
+    /// The registered range of the subrange with the permissions
+    fn split(&self, subrange: &VMRange, perms: VMPerms) -> UserfaultRange {
+        UserfaultRange {
+            range: *subrange,
+            perms,
+            missing: self.missing[self.pages_of(subrange)].to_vec(),
+        }
+    }
+
+    /// The indices of the pages of the subrange
+    fn pages_of(&self, subrange: &VMRange) -> std::ops::Range<usize> {
+        let start = (subrange.start() - self.range.start()) / PAGE_SIZE;
+        start..start + subrange.size() / PAGE_SIZE
+    }
+}
+
+#[derive(Debug)]
+struct PendingFault {
+    tid: pid_t,
+    page: usize,
+    is_woken: bool,
+}
+
+fn to_page_range(range: &UffdioRange) -> Result<VMRange> {
+    let (start, len) = (range.start as usize, range.len as usize);
+    if start % PAGE_SIZE != 0 || len % PAGE_SIZE != 0 || len == 0 {
+        return_errno!(EINVAL, "the range must be page aligned and not empty");
+    }
+    let end = start
+        .checked_add(len)
+        .ok_or_else(|| errno!(EINVAL, "the range overflows"))?;
+    VMRange::new(start, end)
+}
@@ -9,6 +9,23 @@ pub struct VMArea {
     range: VMRange,
     perms: VMPerms,
     writeback_file: Option<(FileRef, usize)>,
+    fork_flags: VMForkFlags,
+}
+
+bitflags! {
+    /// How the memory of a VMA is inherited by a child on fork, which is given by
+    /// madvise.
+    ///
+    /// A child never inherits the memory of its parent as there is no fork in the
+    /// LibOS, so the memory (e.g., the keys of crypto libraries) is never duplicated
+    /// anyway. The flags are kept so that fork honors them once it is supported.
+    #[derive(Default)]
+    pub struct VMForkFlags : u32 {
+        /// MADV_DONTFORK: not mapped in the child
+        const DONT_FORK    = 0x1;
+        /// MADV_WIPEONFORK: mapped in the child, but filled with zeros
+        const WIPE_ON_FORK = 0x2;
+    }
 }
 
 impl VMArea {
@@ -17,11 +34,12 @@ impl VMArea {
             range,
             perms,
             writeback_file,
+            fork_flags: VMForkFlags::empty(),
         }
     }
 
-    /// Create a new VMArea object that inherits the write-back file (if any), but has
-    /// a new range and permissions.
+    /// Create a new VMArea object that inherits the write-back file (if any) and the
+    /// fork flags, but has a new range and permissions.
     pub fn inherits_file_from(vma: &VMArea, new_range: VMRange, new_perms: VMPerms) -> Self {
         let new_writeback_file = vma.writeback_file.as_ref().map(|(file, file_offset)| {
             let new_file = file.clone();
@@ -36,7 +54,9 @@ impl VMArea {
             };
             (new_file, new_file_offset)
         });
-        Self::new(new_range, new_perms, new_writeback_file)
+        let mut new_vma = Self::new(new_range, new_perms, new_writeback_file);
+        new_vma.fork_flags = vma.fork_flags;
+        new_vma
     }
 
     pub fn perms(&self) -> VMPerms {
@@ -55,6 +75,14 @@ impl VMArea {
         self.perms = new_perms;
     }
 
+    pub fn fork_flags(&self) -> VMForkFlags {
+        self.fork_flags
+    }
+
+    pub fn set_fork_flags(&mut self, new_fork_flags: VMForkFlags) {
+        self.fork_flags = new_fork_flags;
+    }
+
     pub fn subtract(&self, other: &VMRange) -> Vec<VMArea> {
         self.deref()
             .subtract(other)
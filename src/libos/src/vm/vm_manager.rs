@@ -1,14 +1,25 @@
 use super::*;
 
-use super::vm_area::VMArea;
+use super::lazy_window;
+use super::userfaultfd;
+use super::vm_area::{VMArea, VMForkFlags};
 use super::vm_perms::VMPerms;
 
 #[derive(Clone, Debug)]
 pub enum VMInitializer {
     DoNothing(),
     FillZeros(),
-    CopyFrom { range: VMRange },
-    LoadFromFile { file: FileRef, offset: usize },
+    CopyFrom {
+        range: VMRange,
+    },
+    LoadFromFile {
+        file: FileRef,
+        offset: usize,
+    },
+    LoadFromHostWindow {
+        window: Arc<HostFileWindow>,
+        offset: usize,
+    },
 }
 
 impl Default for VMInitializer {
@@ -45,6 +56,9 @@ impl VMInitializer {
                     *b = 0;
                 }
             }
+            VMInitializer::LoadFromHostWindow { .. } => {
+                // The pages are populated on first access (see `lazy_window`)
+            }
         }
         Ok(())
     }
@@ -282,6 +296,12 @@ impl VMManager {
         &self.range
     }
 
+    /// The size of the memory that is not mapped
+    pub fn free_size(&self) -> usize {
+        let used_size: usize = self.vmas.iter().map(|vma| vma.size()).sum();
+        self.range.size() - used_size
+    }
+
     pub fn mmap(&mut self, mut options: VMMapOptions) -> Result<usize> {
         // TODO: respect options.align when mmap
         let addr = *options.addr();
@@ -305,6 +325,9 @@ impl VMManager {
         }
         // Set memory permissions
         Self::apply_perms(&new_vma, new_vma.perms());
+        if let VMInitializer::LoadFromHostWindow { window, offset } = options.initializer() {
+            lazy_window::register(&new_vma, window.clone(), *offset, new_vma.perms());
+        }
 
         // After initializing, we can safely insert the new VMA
         self.insert_new_vma(insert_idx, new_vma);
@@ -362,6 +385,8 @@ impl VMManager {
             })
             .collect();
         self.vmas = new_vmas;
+        userfaultfd::unregister_unmapped(&munmap_range);
+        lazy_window::unregister_unmapped(&munmap_range);
         Ok(())
     }
 
@@ -386,6 +411,9 @@ impl VMManager {
             SizeType::Growing
         };
 
+        // The old range is copied or resized as ordinary memory
+        lazy_window::populate_then_detach(&old_range)?;
+
         // Get the memory permissions of the old range
         let perms = {
             // The old range must be contained in one VMA
@@ -509,6 +537,7 @@ impl VMManager {
         if new_perms == old_perms {
             return Ok(());
         }
+        lazy_window::populate_then_detach(&protect_range)?;
 
         let same_start = protect_range.start() == containing_vma.start();
         let same_end = protect_range.end() == containing_vma.end();
@@ -560,7 +589,61 @@ impl VMManager {
                 self.insert_new_vma(containing_idx + 2, new_vma2);
             }
         }
+        userfaultfd::protect_missing_pages(&protect_range, new_perms);
+
+        Ok(())
+    }
+
+    /// The memory permissions of the range, which must be contained in exact one VMA
+    pub fn perms_of(&self, range: &VMRange) -> Result<VMPerms> {
+        let containing_idx = self
+            .find_containing_vma_idx(range)
+            .ok_or_else(|| errno!(EINVAL, "the range is not in exact one VMA"))?;
+        Ok(self.vmas[containing_idx].perms())
+    }
+
+    /// Add or remove the fork flags of the range, which is given by madvise.
+    ///
+    /// Like mprotect, the range must be contained in exact one VMA.
+    pub fn update_fork_flags(
+        &mut self,
+        addr: usize,
+        size: usize,
+        flags: VMForkFlags,
+        is_added: bool,
+    ) -> Result<()> {
+        let advise_range = VMRange::new_with_size(addr, size)?;
+        let containing_idx = self
+            .find_containing_vma_idx(&advise_range)
+            .ok_or_else(|| errno!(ENOMEM, "invalid range"))?;
+        let containing_vma = &self.vmas[containing_idx];
+
+        // Like Linux, only the private mappings can be wiped on fork
+        if is_added
+            && flags.contains(VMForkFlags::WIPE_ON_FORK)
+            && containing_vma.writeback_file().is_some()
+        {
+            return_errno!(EINVAL, "a shared mapping cannot be wiped on fork");
+        }
+        let old_flags = containing_vma.fork_flags();
+        let new_flags = if is_added {
+            old_flags | flags
+        } else {
+            old_flags - flags
+        };
+        if new_flags == old_flags {
+            return Ok(());
+        }
 
+        // The containing VMA is divided into the VMA of the range, and the VMAs
+        // before and after the range (if any), which keep the old flags
+        let mut new_vma = containing_vma.intersect(&advise_range).unwrap();
+        new_vma.set_fork_flags(new_flags);
+        let mut new_vmas = containing_vma.subtract(&advise_range);
+        new_vmas.push(new_vma);
+        new_vmas.sort_by_key(|vma| vma.start());
+        self.vmas
+            .splice(containing_idx..containing_idx + 1, new_vmas);
         Ok(())
     }
 
@@ -783,6 +866,10 @@ impl VMManager {
         if left.perms() != right.perms() {
             return false;
         }
+        // The two VMAs must be inherited in the same way on fork
+        if left.fork_flags() != right.fork_flags() {
+            return false;
+        }
 
         // If the two VMAs have write-back files, the files must be the same and
         // the two file regions must be continuous.
@@ -800,7 +887,7 @@ impl VMManager {
         }
     }
 
-    fn apply_perms(protect_range: &VMRange, perms: VMPerms) {
+    pub(super) fn apply_perms(protect_range: &VMRange, perms: VMPerms) {
         extern "C" {
             pub fn occlum_ocall_mprotect(
                 retval: *mut i32,
@@ -830,5 +917,6 @@ impl Drop for VMManager {
             }
             Self::apply_perms(vma, VMPerms::default());
         }
+        lazy_window::unregister_unmapped(&self.range);
     }
 }
@@ -1,18 +1,125 @@
 #include "ocalls.h"
+#include <dirent.h>
 #include <errno.h>
+#include <fcntl.h>
 #include <net/if.h>
+#include <string.h>
 #include <unistd.h>
+#include <sys/stat.h>
 #include <sys/eventfd.h>
+#include <sys/inotify.h>
 #include <sys/ioctl.h>
 
 void occlum_ocall_sync(void) {
     sync();
 }
 
+// The layout of a directory entry returned by occlum_ocall_read_dir_entries.
+// It must be kept in sync with the Rust definition in libos/src/fs/hostfs.rs.
+struct occlum_dir_entry {
+    uint32_t rec_len;       // The length of this record, including the name
+    uint32_t name_len;      // The length of the name, excluding the NUL
+    uint64_t dev;
+    uint64_t ino;
+    uint64_t size;
+    uint64_t blksize;
+    uint64_t blocks;
+    int64_t atime_sec;
+    int64_t atime_nsec;
+    int64_t mtime_sec;
+    int64_t mtime_nsec;
+    int64_t ctime_sec;
+    int64_t ctime_nsec;
+    uint32_t mode;
+    uint32_t nlink;
+    uint32_t uid;
+    uint32_t gid;
+    uint64_t rdev;
+    char name[];
+};
+
+#define DIR_ENTRY_ALIGN         8
+#define DIR_ENTRY_REC_LEN(name_len) \
+    ((sizeof(struct occlum_dir_entry) + (name_len) + 1 + DIR_ENTRY_ALIGN - 1) \
+     & ~(DIR_ENTRY_ALIGN - 1))
+
+// Read all the entries of a directory, together with their stat data, into buf.
+//
+// On success, return the total length of the entries. If buf is too small to
+// hold all the entries, nothing useful is written to buf and the required
+// length is returned, so the caller can retry with a larger buffer.
+ssize_t occlum_ocall_read_dir_entries(const char *path, void *buf, size_t buf_len) {
+    DIR *dir = opendir(path);
+    if (dir == NULL) {
+        return -1;
+    }
+    int dir_fd = dirfd(dir);
+
+    size_t total_len = 0;
+    struct dirent *dirent;
+    errno = 0;
+    while ((dirent = readdir(dir)) != NULL) {
+        size_t name_len = strlen(dirent->d_name);
+        size_t rec_len = DIR_ENTRY_REC_LEN(name_len);
+
+        if (total_len + rec_len <= buf_len) {
+            // Follow symlinks, as stat of the LibOS does for HostFS. If the entry
+            // is a dangling symlink or is removed after readdir, it is still
+            // listed, but with a zero mode, which means no stat data.
+            struct stat stat_buf;
+            if (fstatat(dir_fd, dirent->d_name, &stat_buf, 0) < 0) {
+                memset(&stat_buf, 0, sizeof(stat_buf));
+                errno = 0;
+            }
+
+            struct occlum_dir_entry *entry = (struct occlum_dir_entry *)((char *)buf + total_len);
+            entry->rec_len = rec_len;
+            entry->name_len = name_len;
+            entry->dev = stat_buf.st_dev;
+            entry->ino = stat_buf.st_ino;
+            entry->size = stat_buf.st_size;
+            entry->blksize = stat_buf.st_blksize;
+            entry->blocks = stat_buf.st_blocks;
+            entry->atime_sec = stat_buf.st_atim.tv_sec;
+            entry->atime_nsec = stat_buf.st_atim.tv_nsec;
+            entry->mtime_sec = stat_buf.st_mtim.tv_sec;
+            entry->mtime_nsec = stat_buf.st_mtim.tv_nsec;
+            entry->ctime_sec = stat_buf.st_ctim.tv_sec;
+            entry->ctime_nsec = stat_buf.st_ctim.tv_nsec;
+            entry->mode = stat_buf.st_mode;
+            entry->nlink = stat_buf.st_nlink;
+            entry->uid = stat_buf.st_uid;
+            entry->gid = stat_buf.st_gid;
+            entry->rdev = stat_buf.st_rdev;
+            memcpy(entry->name, dirent->d_name, name_len + 1);
+        }
+        total_len += rec_len;
+    }
+    int saved_errno = errno;
+    closedir(dir);
+    if (saved_errno != 0) {
+        errno = saved_errno;
+        return -1;
+    }
+    return total_len;
+}
+
 int occlum_ocall_eventfd(unsigned int initval, int flags) {
     return eventfd(initval, flags);
 }
 
+int occlum_ocall_inotify_init1(int flags) {
+    return inotify_init1(flags);
+}
+
+int occlum_ocall_inotify_add_watch(int fd, const char *path, uint32_t mask) {
+    return inotify_add_watch(fd, path, mask);
+}
+
+int occlum_ocall_inotify_rm_watch(int fd, int wd) {
+    return inotify_rm_watch(fd, wd);
+}
+
 int occlum_ocall_ioctl_repack(int fd, int request, char *buf, int len, int *recv_len) {
     int ret = 0;
 
@@ -1,5 +1,8 @@
+#include <fcntl.h>
 #include <stdlib.h>
+#include <unistd.h>
 #include <sys/mman.h>
+#include <sys/stat.h>
 #include "ocalls.h"
 
 void *occlum_ocall_posix_memalign(size_t alignment, size_t size) {
@@ -30,3 +33,31 @@ void occlum_ocall_free(void *ptr) {
 int occlum_ocall_mprotect(void *addr, size_t len, int prot) {
     return mprotect(addr, len, prot);
 }
+
+void *occlum_ocall_mmap_host_file(const char *path, size_t offset, size_t len) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return NULL;
+    }
+    // Accessing the part of the mapping beyond the end of file triggers SIGBUS,
+    // so a file shorter than the enclave expects is an I/O error
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0 || (size_t)stat_buf.st_size < offset + len) {
+        close(fd);
+        errno = EIO;
+        return NULL;
+    }
+
+    void *addr = mmap(NULL, len, PROT_READ, MAP_PRIVATE, fd, offset);
+    int saved_errno = errno;
+    close(fd);
+    if (addr == MAP_FAILED) {
+        errno = saved_errno;
+        return NULL;
+    }
+    return addr;
+}
+
+int occlum_ocall_munmap(void *addr, size_t len) {
+    return munmap(addr, len);
+}
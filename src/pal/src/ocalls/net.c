@@ -1,11 +1,20 @@
+#define _GNU_SOURCE
+#include <sys/ioctl.h>
 #include <sys/time.h>
 #include <sys/types.h>
 #include <sys/select.h>
 #include <sys/socket.h>
 #include <errno.h>
+#include <fcntl.h>
+#include <linux/if_tun.h>
+#include <net/if.h>
+#include <netdb.h>
 #include <stdio.h>
 #include <stddef.h>
+#include <string.h>
+#include <unistd.h>
 #include "ocalls.h"
+#include "../pal_async_io.h"
 
 ssize_t occlum_ocall_sendmsg(int sockfd,
                              const void *msg_name,
@@ -88,3 +97,188 @@ int occlum_ocall_poll(struct pollfd *fds,
     errno = saved_errno;
     return ret;
 }
+
+// The statistics of a network interface returned by occlum_ocall_get_net_dev_stats.
+// It must be kept in sync with the Rust definition in libos/src/net/net_dev.rs.
+struct occlum_net_dev_stats {
+    char name[IFNAMSIZ];
+    uint64_t rx_bytes;
+    uint64_t rx_packets;
+    uint64_t rx_errors;
+    uint64_t rx_dropped;
+    uint64_t tx_bytes;
+    uint64_t tx_packets;
+    uint64_t tx_errors;
+    uint64_t tx_dropped;
+};
+
+// Read the statistics of the network interfaces from /proc/net/dev into buf.
+//
+// On success, return the number of the interfaces. If buf is too small to hold
+// all the interfaces, only the first ones are written to buf, so the caller can
+// retry with a larger buffer.
+int occlum_ocall_get_net_dev_stats(void *buf, size_t buf_len) {
+    FILE *fp = fopen("/proc/net/dev", "r");
+    if (fp == NULL) {
+        return -1;
+    }
+
+    size_t max_count = buf_len / sizeof(struct occlum_net_dev_stats);
+    int count = 0;
+    char line[512];
+    while (fgets(line, sizeof(line), fp) != NULL) {
+        // The header lines have no colon
+        char *colon = strchr(line, ':');
+        if (colon == NULL) {
+            continue;
+        }
+        *colon = '\0';
+        char *name = line;
+        while (*name == ' ') {
+            name++;
+        }
+
+        unsigned long long rx_bytes, rx_packets, rx_errors, rx_dropped;
+        unsigned long long tx_bytes, tx_packets, tx_errors, tx_dropped;
+        unsigned long long unused;
+        int num_fields = sscanf(colon + 1,
+                                "%llu %llu %llu %llu %llu %llu %llu %llu "
+                                "%llu %llu %llu %llu",
+                                &rx_bytes, &rx_packets, &rx_errors, &rx_dropped,
+                                &unused, &unused, &unused, &unused,
+                                &tx_bytes, &tx_packets, &tx_errors, &tx_dropped);
+        if (num_fields != 12 || strlen(name) >= IFNAMSIZ) {
+            continue;
+        }
+
+        if (count < max_count) {
+            struct occlum_net_dev_stats *stats = (struct occlum_net_dev_stats *)buf + count;
+            memset(stats, 0, sizeof(*stats));
+            strcpy(stats->name, name);
+            stats->rx_bytes = rx_bytes;
+            stats->rx_packets = rx_packets;
+            stats->rx_errors = rx_errors;
+            stats->rx_dropped = rx_dropped;
+            stats->tx_bytes = tx_bytes;
+            stats->tx_packets = tx_packets;
+            stats->tx_errors = tx_errors;
+            stats->tx_dropped = tx_dropped;
+        }
+        count++;
+    }
+    fclose(fp);
+    return count;
+}
+
+// An address resolved by occlum_ocall_getaddrinfo.
+// It must be kept in sync with the Rust definition in libos/src/net/host_dns.rs.
+struct occlum_addrinfo {
+    int family;
+    int socktype;
+    int protocol;
+    uint32_t addr_len;
+    uint8_t addr[sizeof(struct sockaddr_in6)];
+};
+
+static int eai_to_errno(int eai) {
+    switch (eai) {
+        case EAI_NONAME:
+        case EAI_NODATA:
+        case EAI_ADDRFAMILY:
+            return ENOENT;
+        case EAI_AGAIN:
+            return EAGAIN;
+        case EAI_MEMORY:
+            return ENOMEM;
+        case EAI_SYSTEM:
+            return errno;
+        case EAI_FAMILY:
+        case EAI_SOCKTYPE:
+        case EAI_SERVICE:
+        case EAI_BADFLAGS:
+            return EINVAL;
+        default:
+            return EIO;
+    }
+}
+
+// Resolve the node and the service by getaddrinfo into buf.
+//
+// On success, return the number of the addresses. If buf is too small to hold
+// all the addresses, only the first ones are written to buf. The addresses
+// that do not fit in struct occlum_addrinfo are skipped.
+int occlum_ocall_getaddrinfo(const char *node, const char *service, int flags,
+                             int family, int socktype, int protocol,
+                             void *buf, size_t buf_len) {
+    struct addrinfo hints;
+    memset(&hints, 0, sizeof(hints));
+    hints.ai_flags = flags;
+    hints.ai_family = family;
+    hints.ai_socktype = socktype;
+    hints.ai_protocol = protocol;
+
+    struct addrinfo *res = NULL;
+    int eai = getaddrinfo(node, service, &hints, &res);
+    if (eai != 0) {
+        errno = eai_to_errno(eai);
+        return -1;
+    }
+
+    size_t max_count = buf_len / sizeof(struct occlum_addrinfo);
+    int count = 0;
+    for (struct addrinfo *ai = res; ai != NULL; ai = ai->ai_next) {
+        if (ai->ai_addrlen > sizeof(struct sockaddr_in6)) {
+            continue;
+        }
+        if (count < max_count) {
+            struct occlum_addrinfo *info = (struct occlum_addrinfo *)buf + count;
+            memset(info, 0, sizeof(*info));
+            info->family = ai->ai_family;
+            info->socktype = ai->ai_socktype;
+            info->protocol = ai->ai_protocol;
+            info->addr_len = ai->ai_addrlen;
+            memcpy(info->addr, ai->ai_addr, ai->ai_addrlen);
+        }
+        count++;
+    }
+    freeaddrinfo(res);
+    return count;
+}
+
+int occlum_ocall_open_tap(const char *name) {
+    if (strlen(name) >= IFNAMSIZ) {
+        errno = EINVAL;
+        return -1;
+    }
+    int fd = open("/dev/net/tun", O_RDWR | O_NONBLOCK | O_CLOEXEC);
+    if (fd < 0) {
+        return -1;
+    }
+
+    // The frames are exchanged without the extra packet information
+    struct ifreq ifr;
+    memset(&ifr, 0, sizeof(ifr));
+    ifr.ifr_flags = IFF_TAP | IFF_NO_PI;
+    strcpy(ifr.ifr_name, name);
+    if (ioctl(fd, TUNSETIFF, &ifr) < 0) {
+        int saved_errno = errno;
+        close(fd);
+        errno = saved_errno;
+        return -1;
+    }
+    return fd;
+}
+
+int occlum_ocall_async_io_start(void *queue, int num_threads) {
+    return pal_async_io_start((struct async_io_queue *)queue, num_threads);
+}
+
+void occlum_ocall_async_io_wake(void *queue) {
+    (void)futex_wake(&((struct async_io_queue *)queue)->tail);
+}
+
+void occlum_ocall_async_io_wait(uint32_t *state, uint32_t expected) {
+    // Return on a spurious wakeup or a signal as well, where the LibOS checks the
+    // state and waits again
+    (void)futex_wait(state, expected, NULL);
+}
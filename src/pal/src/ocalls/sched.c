@@ -2,6 +2,8 @@
 #include <sched.h>
 #include <dirent.h>
 #include <unistd.h>
+#include <errno.h>
+#include <stdio.h>
 #include "ocalls.h"
 
 int occlum_ocall_sched_getaffinity(size_t cpusize, unsigned char *buf) {
@@ -28,6 +30,51 @@ int occlum_ocall_ncores(void) {
     return sysconf(_SC_NPROCESSORS_CONF);
 }
 
+int occlum_ocall_membarrier(int cmd) {
+    return syscall(__NR_membarrier, cmd, 0, 0);
+}
+
+int occlum_ocall_get_host_thread_stats(int host_tid, uint64_t *minflt, uint64_t *majflt,
+                                       uint64_t *nivcsw) {
+    char path[64];
+    char line[512];
+
+    // The page faults are the 10th and 12th fields of /proc/[pid]/task/[tid]/stat,
+    // which follow the command name in parentheses
+    snprintf(path, sizeof(path), "/proc/self/task/%d/stat", host_tid);
+    FILE *fp = fopen(path, "r");
+    if (fp == NULL) {
+        return -1;
+    }
+    char *fields = fgets(line, sizeof(line), fp) ? strrchr(line, ')') : NULL;
+    fclose(fp);
+    unsigned long long minflt_val, majflt_val;
+    if (fields == NULL ||
+            sscanf(fields + 1, " %*c %*d %*d %*d %*d %*d %*u %llu %*u %llu",
+                   &minflt_val, &majflt_val) != 2) {
+        errno = EINVAL;
+        return -1;
+    }
+
+    snprintf(path, sizeof(path), "/proc/self/task/%d/status", host_tid);
+    fp = fopen(path, "r");
+    if (fp == NULL) {
+        return -1;
+    }
+    unsigned long long nivcsw_val = 0;
+    while (fgets(line, sizeof(line), fp) != NULL) {
+        if (sscanf(line, "nonvoluntary_ctxt_switches: %llu", &nivcsw_val) == 1) {
+            break;
+        }
+    }
+    fclose(fp);
+
+    *minflt = minflt_val;
+    *majflt = majflt_val;
+    *nivcsw = nivcsw_val;
+    return 0;
+}
+
 static int is_number(const char *str) {
     size_t len = strlen(str);
     for (size_t i = 0; i < len; i++) {
@@ -1,5 +1,6 @@
 #include <occlum_pal_api.h>
 #include "Enclave_u.h"
+#include "pal_async_io.h"
 #include "pal_enclave.h"
 #include "pal_error.h"
 #include "pal_interrupt_thread.h"
@@ -186,6 +187,12 @@ int occlum_pal_destroy(void) {
     }
 #endif
 
+    // The threads are started by the LibOS only if the async I/O is configured
+    if (pal_async_io_stop() < 0 && errno != ENOENT) {
+        ret = -1;
+        PAL_WARN("Cannot stop the async I/O threads: %s", errno2str(errno));
+    }
+
     if (pal_destroy_enclave() < 0) {
         ret = -1;
         PAL_WARN("Cannot destroy the enclave");
@@ -0,0 +1,144 @@
+#include <sys/socket.h>
+#include <errno.h>
+#include <limits.h>
+#include <pthread.h>
+#include <time.h>
+#include "pal_async_io.h"
+#include "pal_log.h"
+#include "pal_syscall.h"
+#include "errno2str.h"
+
+#define MAX_NUM_THREADS     16
+// The rounds to spin on an empty queue before a worker sleeps
+#define SPIN_ROUNDS         4096
+#define MS                  (1000*1000L) // 1ms = 1,000,000ns
+
+static pthread_t threads[MAX_NUM_THREADS];
+static int num_running = 0;
+static struct async_io_queue *running_queue = NULL;
+
+static void do_request(struct async_io_queue *queue, uint32_t idx) {
+    // Nothing in the queue is trusted by the PAL either
+    if (idx >= ASYNC_IO_QUEUE_DEPTH) {
+        return;
+    }
+    struct async_io_request *req = &queue->requests[idx];
+    if (__atomic_load_n(&req->state, __ATOMIC_SEQ_CST) != ASYNC_IO_STATE_SUBMITTED) {
+        return;
+    }
+
+    // The workers never block, so that a few of them serve all the sockets
+    void *buf = (void *)req->buf;
+    size_t len = req->len;
+    ssize_t ret;
+    switch (req->op) {
+        case ASYNC_IO_OP_RECV:
+            ret = recv(req->fd, buf, len, req->flags | MSG_DONTWAIT);
+            break;
+        case ASYNC_IO_OP_SEND:
+            ret = send(req->fd, buf, len, req->flags | MSG_DONTWAIT | MSG_NOSIGNAL);
+            break;
+        default:
+            ret = -1;
+            errno = EINVAL;
+            break;
+    }
+    req->result = ret < 0 ? -errno : ret;
+
+    __atomic_store_n(&req->state, ASYNC_IO_STATE_DONE, __ATOMIC_SEQ_CST);
+    // The submitter that is parked after the store is sure to see the new state
+    if (__atomic_load_n(&req->is_parked, __ATOMIC_SEQ_CST)) {
+        (void)futex_wake(&req->state);
+    }
+}
+
+static void *worker_func(void *data) {
+    struct async_io_queue *queue = (struct async_io_queue *)data;
+
+    int idle_rounds = 0;
+    while (!__atomic_load_n(&queue->stop, __ATOMIC_SEQ_CST)) {
+        uint32_t head = __atomic_load_n(&queue->head, __ATOMIC_SEQ_CST);
+        uint32_t tail = __atomic_load_n(&queue->tail, __ATOMIC_SEQ_CST);
+        if (head != tail) {
+            idle_rounds = 0;
+            if (!__atomic_compare_exchange_n(&queue->head, &head, head + 1, 0,
+                                             __ATOMIC_SEQ_CST, __ATOMIC_SEQ_CST)) {
+                continue;
+            }
+            uint32_t idx = __atomic_load_n(&queue->submissions[head % ASYNC_IO_QUEUE_DEPTH],
+                                           __ATOMIC_SEQ_CST);
+            do_request(queue, idx);
+            continue;
+        }
+
+        if (++idle_rounds < SPIN_ROUNDS) {
+            __builtin_ia32_pause();
+            continue;
+        }
+        idle_rounds = 0;
+
+        // The LibOS wakes up a worker if it sees any worker sleeping after it
+        // updates the tail. The timeout bounds the time to notice the stop.
+        __atomic_add_fetch(&queue->num_sleeping, 1, __ATOMIC_SEQ_CST);
+        if (__atomic_load_n(&queue->tail, __ATOMIC_SEQ_CST) == tail) {
+            struct timespec timeout = { .tv_sec = 0, .tv_nsec = 100 * MS };
+            (void)futex_wait(&queue->tail, tail, &timeout);
+        }
+        __atomic_sub_fetch(&queue->num_sleeping, 1, __ATOMIC_SEQ_CST);
+    }
+    return NULL;
+}
+
+int pal_async_io_start(struct async_io_queue *queue, int num_threads) {
+    if (num_running > 0) {
+        errno = EEXIST;
+        PAL_ERROR("The async I/O threads are already running: %s", errno2str(errno));
+        return -1;
+    }
+    if (queue == NULL || num_threads <= 0 || num_threads > MAX_NUM_THREADS) {
+        errno = EINVAL;
+        return -1;
+    }
+
+    __atomic_store_n(&queue->stop, 0, __ATOMIC_SEQ_CST);
+    running_queue = queue;
+    for (int i = 0; i < num_threads; i++) {
+        int ret = 0;
+        if ((ret = pthread_create(&threads[i], NULL, worker_func, queue))) {
+            pal_async_io_stop();
+
+            errno = ret;
+            PAL_ERROR("Failed to start the async I/O threads: %s", errno2str(errno));
+            return -1;
+        }
+        num_running++;
+    }
+    return 0;
+}
+
+int pal_async_io_stop(void) {
+    if (running_queue == NULL) {
+        errno = ENOENT;
+        return -1;
+    }
+
+    __atomic_store_n(&running_queue->stop, 1, __ATOMIC_SEQ_CST);
+    (void)syscall(__NR_futex, &running_queue->tail, FUTEX_WAKE, INT_MAX);
+
+    int err = 0;
+    for (int i = 0; i < num_running; i++) {
+        int ret = 0;
+        if ((ret = pthread_join(threads[i], NULL))) {
+            err = ret;
+        }
+    }
+    num_running = 0;
+    running_queue = NULL;
+
+    if (err) {
+        errno = err;
+        PAL_ERROR("Failed to free the async I/O threads: %s", errno2str(errno));
+        return -1;
+    }
+    return 0;
+}
@@ -0,0 +1,45 @@
+#ifndef __PAL_ASYNC_IO_H__
+#define __PAL_ASYNC_IO_H__
+
+#include <stdint.h>
+
+/*
+ * The queue of the asynchronous OCalls of the sends and receives of sockets,
+ * which is in the untrusted memory allocated by the LibOS. The layout must be
+ * kept in sync with src/libos/src/net/async_io.rs.
+ */
+#define ASYNC_IO_QUEUE_DEPTH        256
+
+#define ASYNC_IO_STATE_IDLE         0
+#define ASYNC_IO_STATE_SUBMITTED    1
+#define ASYNC_IO_STATE_DONE         2
+
+#define ASYNC_IO_OP_RECV            1
+#define ASYNC_IO_OP_SEND            2
+
+struct async_io_request {
+    uint32_t state;
+    uint32_t is_parked;
+    uint32_t op;
+    int32_t fd;
+    int32_t flags;
+    uint32_t _padding;
+    uint64_t buf;
+    uint64_t len;
+    int64_t result;
+};
+
+struct async_io_queue {
+    uint32_t tail;
+    uint32_t head;
+    uint32_t num_sleeping;
+    uint32_t stop;
+    uint32_t submissions[ASYNC_IO_QUEUE_DEPTH];
+    struct async_io_request requests[ASYNC_IO_QUEUE_DEPTH];
+};
+
+int pal_async_io_start(struct async_io_queue *queue, int num_threads);
+
+int pal_async_io_stop(void);
+
+#endif /* __PAL_ASYNC_IO_H__ */
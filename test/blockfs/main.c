@@ -0,0 +1,284 @@
+#include <sys/stat.h>
+#include <dirent.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define BLOCKFS_DIR     "/blockfs"
+#define BLOCK_SIZE      4096
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static void fill_pattern(char *buf, size_t len, off_t offset) {
+    for (size_t i = 0; i < len; i++) {
+        buf[i] = (char)((offset + i) * 7 + 3);
+    }
+}
+
+static int check_pattern(const char *buf, size_t len, off_t offset) {
+    for (size_t i = 0; i < len; i++) {
+        if (buf[i] != (char)((offset + i) * 7 + 3)) {
+            return -1;
+        }
+    }
+    return 0;
+}
+
+static int create_file(const char *path, const char *content) {
+    int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create %s", path);
+    }
+    ssize_t len = write(fd, content, strlen(content));
+    close(fd);
+    if (len != (ssize_t)strlen(content)) {
+        THROW_ERROR("failed to write %s", path);
+    }
+    return 0;
+}
+
+static int check_file(const char *path, const char *content) {
+    char buf[128] = { 0 };
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open %s", path);
+    }
+    ssize_t len = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (len != (ssize_t)strlen(content) || strcmp(buf, content) != 0) {
+        THROW_ERROR("unexpected content of %s", path);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for BlockFS
+// ============================================================================
+
+int test_random_write_read(void) {
+    const char *path = BLOCKFS_DIR "/random_rw";
+    // Cover the direct, the indirect and the double indirect blocks
+    const off_t offsets[] = { 100, 5 * BLOCK_SIZE - 10, 20 * BLOCK_SIZE + 1,
+                              1200 * BLOCK_SIZE, 300, 11 * BLOCK_SIZE
+                            };
+    const size_t len = 3 * BLOCK_SIZE;
+    char *buf = malloc(len);
+    if (buf == NULL) {
+        THROW_ERROR("failed to allocate the buffer");
+    }
+
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        free(buf);
+        THROW_ERROR("failed to create the file");
+    }
+    int ret = -1;
+    for (int i = 0; i < ARRAY_SIZE(offsets); i++) {
+        fill_pattern(buf, len, offsets[i]);
+        if (pwrite(fd, buf, len, offsets[i]) != len) {
+            goto out;
+        }
+    }
+    for (int i = 0; i < ARRAY_SIZE(offsets); i++) {
+        memset(buf, 0, len);
+        if (pread(fd, buf, len, offsets[i]) != len ||
+                check_pattern(buf, len, offsets[i]) < 0) {
+            goto out;
+        }
+    }
+    // The hole between the written ranges is read as zeros
+    if (pread(fd, buf, BLOCK_SIZE, 600 * BLOCK_SIZE) != BLOCK_SIZE) {
+        goto out;
+    }
+    for (int i = 0; i < BLOCK_SIZE; i++) {
+        if (buf[i] != 0) {
+            goto out;
+        }
+    }
+    struct stat stat_buf;
+    if (fstat(fd, &stat_buf) < 0 || stat_buf.st_size != 1200 * BLOCK_SIZE + len) {
+        goto out;
+    }
+    ret = 0;
+out:
+    close(fd);
+    free(buf);
+    unlink(path);
+    if (ret < 0) {
+        THROW_ERROR("failed to write and read the file at random");
+    }
+    return 0;
+}
+
+int test_truncate(void) {
+    const char *path = BLOCKFS_DIR "/truncate";
+    char buf[2 * BLOCK_SIZE];
+    fill_pattern(buf, sizeof(buf), 0);
+
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    int ret = -1;
+    if (write(fd, buf, sizeof(buf)) != sizeof(buf)) {
+        goto out;
+    }
+    // The data beyond the end is not read again after the file is extended
+    if (ftruncate(fd, 100) < 0 || ftruncate(fd, sizeof(buf)) < 0) {
+        goto out;
+    }
+    memset(buf, 0xff, sizeof(buf));
+    if (pread(fd, buf, sizeof(buf), 0) != sizeof(buf) || check_pattern(buf, 100, 0) < 0) {
+        goto out;
+    }
+    for (int i = 100; i < sizeof(buf); i++) {
+        if (buf[i] != 0) {
+            goto out;
+        }
+    }
+    ret = 0;
+out:
+    close(fd);
+    unlink(path);
+    if (ret < 0) {
+        THROW_ERROR("failed to truncate the file");
+    }
+    return 0;
+}
+
+int test_dir_ops(void) {
+    const char *dir_path = BLOCKFS_DIR "/dir";
+    const char *file_path = BLOCKFS_DIR "/dir/file";
+    const char *moved_path = BLOCKFS_DIR "/moved_file";
+
+    if (mkdir(dir_path, 0755) < 0) {
+        THROW_ERROR("failed to create the dir");
+    }
+    if (create_file(file_path, "hello") < 0) {
+        return -1;
+    }
+    if (rmdir(dir_path) == 0 || errno != ENOTEMPTY) {
+        THROW_ERROR("removing a non-empty dir should fail with ENOTEMPTY");
+    }
+
+    DIR *dirp = opendir(dir_path);
+    if (dirp == NULL) {
+        THROW_ERROR("failed to open the dir");
+    }
+    int num_entries = 0;
+    int found = 0;
+    struct dirent *dp;
+    while ((dp = readdir(dirp)) != NULL) {
+        num_entries++;
+        if (strcmp(dp->d_name, "file") == 0) {
+            found = 1;
+        }
+    }
+    closedir(dirp);
+    if (num_entries != 3 || !found) {
+        THROW_ERROR("unexpected entries of the dir");
+    }
+
+    if (rename(file_path, moved_path) < 0) {
+        THROW_ERROR("failed to move the file");
+    }
+    if (access(file_path, F_OK) == 0 || check_file(moved_path, "hello") < 0) {
+        THROW_ERROR("the file is not moved");
+    }
+    if (rmdir(dir_path) < 0) {
+        THROW_ERROR("failed to remove the dir");
+    }
+    if (unlink(moved_path) < 0) {
+        THROW_ERROR("failed to unlink the file");
+    }
+    return 0;
+}
+
+int test_rename_replace(void) {
+    const char *old_path = BLOCKFS_DIR "/rename_old";
+    const char *new_path = BLOCKFS_DIR "/rename_new";
+
+    if (create_file(old_path, "old") < 0 || create_file(new_path, "new") < 0) {
+        return -1;
+    }
+    if (rename(old_path, new_path) < 0) {
+        THROW_ERROR("failed to rename over the existing file");
+    }
+    if (access(old_path, F_OK) == 0 || check_file(new_path, "old") < 0) {
+        THROW_ERROR("the existing file is not replaced");
+    }
+    unlink(new_path);
+    return 0;
+}
+
+int test_unlink_opened_file(void) {
+    const char *path = BLOCKFS_DIR "/unlinked";
+    const char *content = "still readable";
+    char buf[64] = { 0 };
+
+    if (create_file(path, content) < 0) {
+        return -1;
+    }
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the file");
+    }
+    if (unlink(path) < 0) {
+        close(fd);
+        THROW_ERROR("failed to unlink the file");
+    }
+    // The data of an unlinked file is kept until the file is closed
+    ssize_t len = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (len != (ssize_t)strlen(content) || strcmp(buf, content) != 0) {
+        THROW_ERROR("failed to read the unlinked file");
+    }
+    if (access(path, F_OK) == 0) {
+        THROW_ERROR("the unlinked file should not be found");
+    }
+    return 0;
+}
+
+int test_hard_link(void) {
+    const char *path = BLOCKFS_DIR "/link_src";
+    const char *link_path = BLOCKFS_DIR "/link_dst";
+    struct stat stat_buf;
+
+    if (create_file(path, "linked") < 0) {
+        return -1;
+    }
+    if (link(path, link_path) < 0) {
+        THROW_ERROR("failed to link the file");
+    }
+    if (stat(path, &stat_buf) < 0 || stat_buf.st_nlink != 2) {
+        THROW_ERROR("the number of links should be 2");
+    }
+    if (unlink(path) < 0 || check_file(link_path, "linked") < 0) {
+        THROW_ERROR("the file should be kept by the other link");
+    }
+    unlink(link_path);
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_random_write_read),
+    TEST_CASE(test_truncate),
+    TEST_CASE(test_dir_ops),
+    TEST_CASE(test_rename_replace),
+    TEST_CASE(test_unlink_opened_file),
+    TEST_CASE(test_hard_link),
+};
+
+int main(void) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
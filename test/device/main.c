@@ -1,9 +1,14 @@
+#include <sys/mman.h>
 #include <sys/stat.h>
+#include <sys/syscall.h>
 #include <sys/types.h>
+#include <errno.h>
 #include <fcntl.h>
 #include <poll.h>
+#include <stdint.h>
 #include <unistd.h>
 #include <stdio.h>
+#include <string.h>
 #include "test.h"
 
 // ============================================================================
@@ -115,6 +120,171 @@ int test_dev_arandom() {
     return 0;
 }
 
+int test_dev_mem_denied() {
+    // The paths that are not normalized, or are relative to the cwd (i.e., "/")
+    const char *paths[] = {"/dev/mem", "/dev/../dev/mem", "//dev/./mem", "../dev/mem"};
+    for (int i = 0; i < sizeof(paths) / sizeof(paths[0]); i++) {
+        if (open(paths[i], O_RDONLY) >= 0 || errno != EPERM) {
+            THROW_ERROR("opening %s should fail with EPERM", paths[i]);
+        }
+    }
+
+    // The denial is recorded
+    char buf[4096] = {0};
+    int fd = open("/proc/occlum/denied_devices", O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /proc/occlum/denied_devices");
+    }
+    int len = read(fd, buf, sizeof(buf) - 1);
+    close(fd);
+    if (len <= 0 || strstr(buf, "/dev/mem") == NULL) {
+        THROW_ERROR("the denial of /dev/mem is not recorded");
+    }
+    return 0;
+}
+
+int test_dev_kvm_emulated() {
+    // /dev/kvm is emulated by /dev/null in Occlum.json
+    char buf[16];
+    int fd = open("/dev/kvm", O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open the emulated /dev/kvm");
+    }
+    if (read(fd, buf, sizeof(buf)) != 0) {
+        close(fd);
+        THROW_ERROR("the emulated /dev/kvm should behave like /dev/null");
+    }
+    close(fd);
+    return 0;
+}
+
+// The threshold of the memory pressure is 4MB in Occlum.json, which is less than
+// the mmap area of 32MB
+#define MEM_CHUNK_SIZE (1024 * 1024)
+#define MAX_MEM_CHUNKS 32
+
+int test_dev_mem_pressure() {
+    int fd = open("/dev/occlum_mem_pressure", O_RDONLY | O_NONBLOCK);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/occlum_mem_pressure");
+    }
+    uint64_t count = 0;
+    if (read(fd, &count, sizeof(count)) >= 0 || errno != EAGAIN) {
+        close(fd);
+        THROW_ERROR("there should be no memory pressure at first");
+    }
+
+    // Map memory until the process comes under pressure
+    void *chunks[MAX_MEM_CHUNKS];
+    int nchunks = 0;
+    struct pollfd pollfd = { .fd = fd, .events = POLLIN };
+    while (poll(&pollfd, 1, 0) == 0 && nchunks < MAX_MEM_CHUNKS) {
+        void *chunk = mmap(NULL, MEM_CHUNK_SIZE, PROT_READ | PROT_WRITE,
+                           MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+        if (chunk == MAP_FAILED) {
+            break;
+        }
+        chunks[nchunks++] = chunk;
+    }
+    int notified = pollfd.revents & POLLIN;
+    if (notified && read(fd, &count, sizeof(count)) != sizeof(count)) {
+        count = 0;
+    }
+    for (int i = 0; i < nchunks; i++) {
+        munmap(chunks[i], MEM_CHUNK_SIZE);
+    }
+    close(fd);
+
+    if (!notified) {
+        THROW_ERROR("the memory pressure is not notified");
+    }
+    if (count != 1) {
+        THROW_ERROR("the memory pressure should be notified once");
+    }
+    return 0;
+}
+
+// Keep in sync with SecurityEventRecord in src/libos/src/fs/dev_fs/dev_security_events.rs
+struct security_event {
+    uint32_t kind;
+    uint32_t pid;
+    uint32_t lost;
+    uint32_t detail_len;
+    char detail[240];
+};
+
+#define SECURITY_EVENT_WX_MAPPING 2
+#define SECURITY_EVENT_SYSCALL_DENIED 4
+
+int test_dev_security_events() {
+    int fd = open("/dev/occlum_security_events", O_RDONLY | O_NONBLOCK);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/occlum_security_events");
+    }
+    struct security_event event;
+    if (read(fd, &event, sizeof(event)) >= 0 || errno != EAGAIN) {
+        close(fd);
+        THROW_ERROR("there should be no security event at first");
+    }
+
+    // The event is reported even if the mapping fails
+    void *addr = mmap(NULL, MEM_CHUNK_SIZE, PROT_READ | PROT_WRITE | PROT_EXEC,
+                      MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (addr != MAP_FAILED) {
+        munmap(addr, MEM_CHUNK_SIZE);
+    }
+
+    struct pollfd pollfd = { .fd = fd, .events = POLLIN };
+    if (poll(&pollfd, 1, 0) != 1 || !(pollfd.revents & POLLIN)) {
+        close(fd);
+        THROW_ERROR("the security event device should be readable");
+    }
+    if (read(fd, &event, sizeof(event)) != sizeof(event)) {
+        close(fd);
+        THROW_ERROR("failed to read the security event");
+    }
+    if (event.kind != SECURITY_EVENT_WX_MAPPING || event.pid != getpid() ||
+            event.lost != 0 || event.detail_len > sizeof(event.detail)) {
+        close(fd);
+        THROW_ERROR("the security event is unexpected");
+    }
+    if (read(fd, &event, sizeof(event)) >= 0 || errno != EAGAIN) {
+        close(fd);
+        THROW_ERROR("the security event should be read only once");
+    }
+    close(fd);
+    return 0;
+}
+
+// getpgid is in the syscall denylist of Occlum.json
+int test_denied_syscall_event() {
+    int fd = open("/dev/occlum_security_events", O_RDONLY | O_NONBLOCK);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/occlum_security_events");
+    }
+    if (syscall(SYS_getpgid, 0) >= 0 || errno != EPERM) {
+        close(fd);
+        THROW_ERROR("the denied syscall should fail with EPERM");
+    }
+
+    struct security_event event;
+    if (read(fd, &event, sizeof(event)) != sizeof(event)) {
+        close(fd);
+        THROW_ERROR("failed to read the security event");
+    }
+    close(fd);
+    if (event.kind != SECURITY_EVENT_SYSCALL_DENIED || event.pid != getpid() ||
+            event.detail_len != strlen("getpgid") ||
+            strncmp(event.detail, "getpgid", event.detail_len) != 0) {
+        THROW_ERROR("the security event is unexpected");
+    }
+    // The syscalls that are not denied are not affected
+    if (getpid() <= 0) {
+        THROW_ERROR("getpid should not be denied");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -127,6 +297,11 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_dev_urandom_fstat),
     TEST_CASE(test_dev_urandom_poll),
     TEST_CASE(test_dev_arandom),
+    TEST_CASE(test_dev_mem_denied),
+    TEST_CASE(test_dev_kvm_emulated),
+    TEST_CASE(test_dev_mem_pressure),
+    TEST_CASE(test_dev_security_events),
+    TEST_CASE(test_denied_syscall_event),
 };
 
 int main() {
@@ -0,0 +1,121 @@
+#include <sys/socket.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <string.h>
+#include <time.h>
+#include <unistd.h>
+#include "test.h"
+
+// The egress of this executable is limited to 64KB per second, with a burst of
+// 16KB, in Occlum.json
+#define EGRESS_RATE (64 * 1024)
+#define EGRESS_BURST (16 * 1024)
+
+#define DATAGRAM_SIZE (8 * 1024)
+#define DATAGRAM_NUM 20
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int create_udp_pair(int *sender, int *receiver, struct sockaddr_in *addr) {
+    socklen_t addr_len = sizeof(*addr);
+    memset(addr, 0, sizeof(*addr));
+    addr->sin_family = AF_INET;
+    addr->sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr->sin_port = 0;
+
+    *receiver = socket(AF_INET, SOCK_DGRAM, 0);
+    if (*receiver < 0) {
+        THROW_ERROR("failed to create the receiver");
+    }
+    if (bind(*receiver, (struct sockaddr *)addr, addr_len) < 0 ||
+            getsockname(*receiver, (struct sockaddr *)addr, &addr_len) < 0) {
+        close(*receiver);
+        THROW_ERROR("failed to bind the receiver");
+    }
+    *sender = socket(AF_INET, SOCK_DGRAM, 0);
+    if (*sender < 0) {
+        close(*receiver);
+        THROW_ERROR("failed to create the sender");
+    }
+    return 0;
+}
+
+static double now_in_secs(void) {
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return ts.tv_sec + ts.tv_nsec / 1e9;
+}
+
+// ============================================================================
+// Test cases
+// ============================================================================
+
+int test_blocking_send_is_throttled() {
+    int sender, receiver;
+    struct sockaddr_in addr;
+    if (create_udp_pair(&sender, &receiver, &addr) < 0) {
+        return -1;
+    }
+
+    // The datagrams are not read by the receiver, which may drop some of them.
+    // It does not matter as only the egress of the sender is limited.
+    static char buf[DATAGRAM_SIZE];
+    double start = now_in_secs();
+    for (int i = 0; i < DATAGRAM_NUM; i++) {
+        if (sendto(sender, buf, sizeof(buf), 0, (struct sockaddr *)&addr,
+                   sizeof(addr)) != sizeof(buf)) {
+            close_files(2, sender, receiver);
+            THROW_ERROR("failed to send a datagram");
+        }
+    }
+    double elapsed = now_in_secs() - start;
+    close_files(2, sender, receiver);
+
+    // All the data but the burst is sent at the rate, with some slack
+    double min_elapsed = (double)(DATAGRAM_SIZE * DATAGRAM_NUM - EGRESS_BURST -
+                                  DATAGRAM_SIZE) / EGRESS_RATE;
+    if (elapsed < min_elapsed * 0.8) {
+        THROW_ERROR("the egress rate limit is not enforced");
+    }
+    return 0;
+}
+
+int test_nonblocking_send_fails_with_eagain() {
+    int sender, receiver;
+    struct sockaddr_in addr;
+    if (create_udp_pair(&sender, &receiver, &addr) < 0) {
+        return -1;
+    }
+
+    // Exhaust the tokens, after which the send must fail instead of waiting
+    static char buf[DATAGRAM_SIZE];
+    int ret = 0;
+    for (int i = 0; i < DATAGRAM_NUM; i++) {
+        ret = sendto(sender, buf, sizeof(buf), MSG_DONTWAIT, (struct sockaddr *)&addr,
+                     sizeof(addr));
+        if (ret < 0) {
+            break;
+        }
+    }
+    close_files(2, sender, receiver);
+    if (ret >= 0 || errno != EAGAIN) {
+        THROW_ERROR("the non-blocking send should fail with EAGAIN");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_blocking_send_is_throttled),
+    TEST_CASE(test_nonblocking_send_fails_with_eagain),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -5,7 +5,9 @@
 #include <spawn.h>
 #include <sys/auxv.h>
 #include <stdlib.h>
+#include <sys/syscall.h>
 #include <sys/wait.h>
+#include <fcntl.h>
 #include <unistd.h>
 #include "test.h"
 
@@ -33,12 +35,17 @@ const char *child_argv[3] = {
     NULL
 };
 
-// Expected child environment variables
+// Expected child environment variables, where OVERRIDE is inherited from the
+// parent and thus is still untrusted
 const char *child_envp[] = {
     "ENV_CHILD=ok",
+    "OVERRIDE=Y",
     NULL
 };
 
+// The Occlum-specific system call that mirrors the changes of the environment
+#define SYS_occlum_putenv 363
+
 static int test_argv_val(const char **expect_argv) {
     for (int arg_i = 0; arg_i < g_argc; arg_i++) {
         const char *actual_arg = *(g_argv + arg_i);
@@ -66,6 +73,51 @@ static int test_env_val(const char *expect_env_key, const char *expect_env_val)
     return 0;
 }
 
+#define ENV_SIZE (256)
+#define PROC_BUF_SIZE (4096)
+static char proc_buf[PROC_BUF_SIZE];
+
+// Read the whole file, whose content is terminated by a null byte in proc_buf
+static ssize_t read_proc_file(const char *path) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        return -1;
+    }
+    ssize_t len = read(fd, proc_buf, sizeof(proc_buf) - 1);
+    close(fd);
+    if (len >= 0) {
+        proc_buf[len] = '\0';
+    }
+    return len;
+}
+
+// Check whether the null-separated list of /proc/self/environ has the variable
+static int environ_has(const char *kv, ssize_t len) {
+    for (char *entry = proc_buf; entry < proc_buf + len; entry += strlen(entry) + 1) {
+        if (strcmp(entry, kv) == 0) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+static int test_env_origin(const char *origin, const char *key) {
+    char expected_line[ENV_SIZE];
+    snprintf(expected_line, sizeof(expected_line), "%s %s", origin, key);
+    if (read_proc_file("/proc/self/environ_origin") < 0) {
+        THROW_ERROR("failed to read /proc/self/environ_origin");
+    }
+    char *saveptr = NULL;
+    for (char *line = strtok_r(proc_buf, "\n", &saveptr); line != NULL;
+            line = strtok_r(NULL, "\n", &saveptr)) {
+        if (strcmp(line, expected_line) == 0) {
+            return 0;
+        }
+    }
+    printf("ERROR: %s is expected to be %s\n", key, origin);
+    return -1;
+}
+
 // ============================================================================
 // Test cases for argv
 // ============================================================================
@@ -96,6 +148,23 @@ static int test_env_getauxval() {
     return 0;
 }
 
+static int test_env_getauxval_random() {
+    const unsigned char *random_bytes = (const unsigned char *)getauxval(AT_RANDOM);
+    if (random_bytes == NULL) {
+        THROW_ERROR("AT_RANDOM should be given");
+    }
+    int all_zeros = 1;
+    for (int i = 0; i < 16; i++) {
+        if (random_bytes[i] != 0) {
+            all_zeros = 0;
+        }
+    }
+    if (all_zeros) {
+        THROW_ERROR("the bytes of AT_RANDOM should be random");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test cases for env
 // ============================================================================
@@ -130,6 +199,52 @@ static int test_env_getenv() {
     return 0;
 }
 
+static int test_env_proc_environ() {
+    ssize_t len = read_proc_file("/proc/self/environ");
+    if (len < 0) {
+        THROW_ERROR("failed to read /proc/self/environ");
+    }
+    if (!environ_has("OCCLUM=yes", len) || !environ_has("OVERRIDE=Y", len)) {
+        THROW_ERROR("/proc/self/environ does not match the environment");
+    }
+
+    if (test_env_origin("trusted", "OCCLUM") < 0 ||
+            test_env_origin("trusted", "STABLE") < 0) {
+        THROW_ERROR("the default env should be trusted");
+    }
+    if (test_env_origin("untrusted", "TEST") < 0 ||
+            test_env_origin("untrusted", "OVERRIDE") < 0) {
+        THROW_ERROR("the env from the host should be untrusted");
+    }
+    return 0;
+}
+
+static int test_env_putenv() {
+    if (syscall(SYS_occlum_putenv, "PUTENV=1") < 0) {
+        THROW_ERROR("failed to set the variable");
+    }
+    ssize_t len = read_proc_file("/proc/self/environ");
+    if (len < 0 || !environ_has("PUTENV=1", len)) {
+        THROW_ERROR("the variable set is not in /proc/self/environ");
+    }
+    if (test_env_origin("trusted", "PUTENV") < 0) {
+        THROW_ERROR("the variable set by the application should be trusted");
+    }
+
+    if (syscall(SYS_occlum_putenv, "PUTENV") < 0) {
+        THROW_ERROR("failed to unset the variable");
+    }
+    len = read_proc_file("/proc/self/environ");
+    if (len < 0 || environ_has("PUTENV=1", len)) {
+        THROW_ERROR("the variable unset is still in /proc/self/environ");
+    }
+
+    if (syscall(SYS_occlum_putenv, "=1") == 0 || errno != EINVAL) {
+        THROW_ERROR("a variable with an empty name should be rejected");
+    }
+    return 0;
+}
+
 static int test_env_set_child_env_and_argv() {
     int status, child_pid;
     int ret = posix_spawn(&child_pid,
@@ -171,7 +286,6 @@ static int test_env_child_getargv() {
 // Child Test cases for env
 // ============================================================================
 
-#define ENV_SIZE (256)
 static int test_env_child_getenv() {
     char env_key[ENV_SIZE];
     char env_val[ENV_SIZE];
@@ -187,6 +301,16 @@ static int test_env_child_getenv() {
     return 0;
 }
 
+static int test_env_child_origin() {
+    if (test_env_origin("trusted", "ENV_CHILD") < 0) {
+        THROW_ERROR("the env given by the parent should be trusted");
+    }
+    if (test_env_origin("untrusted", "OVERRIDE") < 0) {
+        THROW_ERROR("the untrusted env of the parent should stay untrusted");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -194,14 +318,19 @@ static int test_env_child_getenv() {
 static test_case_t test_cases[] = {
     TEST_CASE(test_env_getargv),
     TEST_CASE(test_env_getauxval),
+    TEST_CASE(test_env_getauxval_random),
     TEST_CASE(test_env_getenv),
+    TEST_CASE(test_env_proc_environ),
+    TEST_CASE(test_env_putenv),
     TEST_CASE(test_env_set_child_env_and_argv),
 };
 
 static test_case_t child_test_cases[] = {
     TEST_CASE(test_env_getauxval),
+    TEST_CASE(test_env_getauxval_random),
     TEST_CASE(test_env_child_getargv),
     TEST_CASE(test_env_child_getenv),
+    TEST_CASE(test_env_child_origin),
 };
 
 int main(int argc, const char *argv[]) {
@@ -4,6 +4,7 @@
 #include <sys/socket.h>
 #include <sys/syscall.h>
 #include <sys/wait.h>
+#include <errno.h>
 #include <fcntl.h>
 #include <unistd.h>
 #include <poll.h>
@@ -12,6 +13,7 @@
 #include <stdio.h>
 #include <spawn.h>
 #include <string.h>
+#include <time.h>
 
 #include "test.h"
 
@@ -242,6 +244,93 @@ int test_epoll_with_socket() {
 
     return 0;
 }
+
+#ifndef SYS_epoll_pwait2
+#define SYS_epoll_pwait2 441
+#endif
+
+#define TIMEOUT_MS 100
+
+static long elapsed_ms_since(struct timespec *start) {
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    return (now.tv_sec - start->tv_sec) * 1000 + (now.tv_nsec - start->tv_nsec) / 1000000;
+}
+
+int test_poll_timeout() {
+    int event_fd = eventfd(0, 0);
+    if (event_fd < 0) {
+        THROW_ERROR("failed to create an eventfd");
+    }
+
+    struct pollfd pollfds[] = {
+        { .fd = event_fd, .events = POLLIN, .revents = 0, },
+    };
+    struct timespec start;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    int ret = poll(pollfds, 1, TIMEOUT_MS);
+    long elapsed_ms = elapsed_ms_since(&start);
+    close(event_fd);
+    if (ret != 0) {
+        THROW_ERROR("poll should time out");
+    }
+    if (elapsed_ms < TIMEOUT_MS) {
+        THROW_ERROR("poll returns before the timeout");
+    }
+    return 0;
+}
+
+int test_epoll_pwait2_timeout() {
+    int event_fd = eventfd(0, EFD_NONBLOCK);
+    int epfd = epoll_create1(0);
+    if (event_fd < 0 || epfd < 0) {
+        THROW_ERROR("failed to create files");
+    }
+
+    struct epoll_event ctl_event = { .events = EPOLLIN, .data.fd = event_fd };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, event_fd, &ctl_event) == -1) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("epoll_ctl");
+    }
+
+    // The timeout is not a multiple of milliseconds
+    struct timespec timeout = { .tv_sec = 0, .tv_nsec = TIMEOUT_MS * 1000000 + 500000 };
+    struct epoll_event events[MAXEVENTS] = {0};
+    struct timespec start;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    int ret = syscall(SYS_epoll_pwait2, epfd, events, MAXEVENTS, &timeout, NULL, 0);
+    long elapsed_ms = elapsed_ms_since(&start);
+    if (ret != 0) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("epoll_pwait2 should time out");
+    }
+    if (elapsed_ms < TIMEOUT_MS) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("epoll_pwait2 returns before the timeout");
+    }
+
+    // The ready file is returned without waiting for the timeout
+    uint64_t data = TEST_DATA;
+    if (write(event_fd, &data, sizeof(data)) != sizeof(data)) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("failed to write the eventfd");
+    }
+    ret = syscall(SYS_epoll_pwait2, epfd, events, MAXEVENTS, &timeout, NULL, 0);
+    if (ret != 1 || events[0].data.fd != event_fd) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("epoll_pwait2 should return the ready eventfd");
+    }
+
+    // An invalid timeout is rejected
+    timeout.tv_nsec = 1000000000;
+    ret = syscall(SYS_epoll_pwait2, epfd, events, MAXEVENTS, &timeout, NULL, 0);
+    close_files(2, event_fd, epfd);
+    if (ret != -1 || errno != EINVAL) {
+        THROW_ERROR("epoll_pwait2 should reject the invalid timeout");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -254,6 +343,8 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_epoll_with_socket),
     TEST_CASE(test_poll_with_socket),
     TEST_CASE(test_select_with_socket),
+    TEST_CASE(test_poll_timeout),
+    TEST_CASE(test_epoll_pwait2_timeout),
 };
 
 int main(int argc, const char *argv[]) {
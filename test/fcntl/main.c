@@ -1,5 +1,8 @@
 #include <errno.h>
 #include <fcntl.h>
+#include <poll.h>
+#include <string.h>
+#include <unistd.h>
 #include "test_fs.h"
 
 // ============================================================================
@@ -115,6 +118,32 @@ static int test_fcntl_dupfd() {
     return test_fcntl_framework(__fcntl_dupfd);
 }
 
+static int test_fcntl_stdout_nonblock() {
+    int flags = fcntl(STDOUT_FILENO, F_GETFL);
+    if (flags < 0 || (flags & O_ACCMODE) != O_WRONLY) {
+        THROW_ERROR("failed to get the flags of stdout");
+    }
+    if (fcntl(STDOUT_FILENO, F_SETFL, flags | O_NONBLOCK) < 0) {
+        THROW_ERROR("failed to set stdout non-blocking");
+    }
+    if ((fcntl(STDOUT_FILENO, F_GETFL) & O_NONBLOCK) == 0) {
+        fcntl(STDOUT_FILENO, F_SETFL, flags);
+        THROW_ERROR("failed to check getfl after setfl on stdout");
+    }
+
+    // The host stdout is pollable, and writable without blocking
+    struct pollfd pollfd = { .fd = STDOUT_FILENO, .events = POLLOUT, .revents = 0 };
+    const char *msg = "Write to the non-blocking stdout\n";
+    if (poll(&pollfd, 1, 0) != 1 || (pollfd.revents & POLLOUT) == 0 ||
+            write(STDOUT_FILENO, msg, strlen(msg)) != strlen(msg)) {
+        fcntl(STDOUT_FILENO, F_SETFL, flags);
+        THROW_ERROR("failed to poll and write the non-blocking stdout");
+    }
+
+    fcntl(STDOUT_FILENO, F_SETFL, flags);
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -124,6 +153,7 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_fcntl_setfl),
     TEST_CASE(test_getlk_and_setlk),
     TEST_CASE(test_fcntl_dupfd),
+    TEST_CASE(test_fcntl_stdout_nonblock),
 };
 
 int main() {
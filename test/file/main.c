@@ -1,7 +1,9 @@
+#define _GNU_SOURCE
 #include <sys/stat.h>
 #include <sys/uio.h>
 #include <errno.h>
 #include <fcntl.h>
+#include <limits.h>
 #include "test_fs.h"
 
 // ============================================================================
@@ -127,6 +129,54 @@ static int __test_writev_readv(const char *file_path) {
     return 0;
 }
 
+static int __test_writev_readv_limits(const char *file_path) {
+    static struct iovec iov[IOV_MAX + 1];
+    char buf[16] = { 0 };
+    int fd;
+
+    fd = open(file_path, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file");
+    }
+    for (int i = 0; i < IOV_MAX + 1; ++i) {
+        iov[i].iov_base = buf;
+        iov[i].iov_len = 1;
+    }
+    if (writev(fd, iov, IOV_MAX + 1) >= 0 || errno != EINVAL) {
+        THROW_ERROR("check writev with too many iovecs fail");
+    }
+    if (readv(fd, iov, IOV_MAX + 1) >= 0 || errno != EINVAL) {
+        THROW_ERROR("check readv with too many iovecs fail");
+    }
+    if (writev(fd, iov, -1) >= 0 || errno != EINVAL) {
+        THROW_ERROR("check writev with negative count fail");
+    }
+
+    // The total length exceeds SSIZE_MAX
+    iov[0].iov_len = SSIZE_MAX;
+    iov[1].iov_len = 1;
+    if (writev(fd, iov, 2) >= 0 || errno != EINVAL) {
+        THROW_ERROR("check writev with too long iovecs fail");
+    }
+    if (readv(fd, iov, 2) >= 0 || errno != EINVAL) {
+        THROW_ERROR("check readv with too long iovecs fail");
+    }
+
+    // Empty iovecs can have any base
+    iov[0].iov_base = NULL;
+    iov[0].iov_len = 0;
+    iov[1].iov_base = buf;
+    iov[1].iov_len = sizeof(buf);
+    if (writev(fd, iov, 2) != sizeof(buf)) {
+        THROW_ERROR("failed to writev with an empty iovec");
+    }
+    if (writev(fd, iov, IOV_MAX) != IOV_MAX - 2 + sizeof(buf)) {
+        THROW_ERROR("failed to writev with IOV_MAX iovecs");
+    }
+    close(fd);
+    return 0;
+}
+
 static int __test_lseek(const char *file_path) {
     char *write_str = "Hello World\n";
     char read_buf[128] = { 0 };
@@ -162,6 +212,74 @@ static int __test_lseek(const char *file_path) {
     return 0;
 }
 
+static int __test_large_offset(const char *file_path) {
+    const off_t large_offset = 5LL * 1024 * 1024 * 1024;
+    char *write_str = "Hello World\n";
+    int fd, ret;
+
+    fd = open(file_path, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file to read/write");
+    }
+    if (lseek(fd, large_offset, SEEK_SET) != large_offset) {
+        THROW_ERROR("failed to lseek beyond 4GB");
+    }
+    if (lseek(fd, 0, SEEK_CUR) != large_offset) {
+        THROW_ERROR("the offset beyond 4GB is truncated");
+    }
+    ret = lseek(fd, LLONG_MAX, SEEK_CUR);
+    if (ret >= 0 || errno != EOVERFLOW) {
+        THROW_ERROR("check lseek with overflowed offset fail");
+    }
+    ret = pwrite(fd, write_str, strlen(write_str), LLONG_MAX - 1);
+    if (ret >= 0 || errno != EINVAL) {
+        THROW_ERROR("check pwrite with overflowed offset fail");
+    }
+    ret = pread(fd, write_str, strlen(write_str), LLONG_MAX - 1);
+    if (ret >= 0 || errno != EINVAL) {
+        THROW_ERROR("check pread with overflowed offset fail");
+    }
+    ret = ftruncate(fd, -1);
+    if (ret >= 0 || errno != EINVAL) {
+        THROW_ERROR("check ftruncate with negative length fail");
+    }
+    close(fd);
+    return 0;
+}
+
+static int __test_fallocate(const char *file_path) {
+    const off_t alloc_len = 8192;
+    struct stat stat_buf;
+    int fd, ret;
+
+    fd = open(file_path, O_RDWR);
+    if (fd < 0) {
+        THROW_ERROR("failed to open a file to read/write");
+    }
+    if (fallocate(fd, 0, 0, alloc_len) < 0) {
+        THROW_ERROR("failed to fallocate");
+    }
+    if (fstat(fd, &stat_buf) < 0 || stat_buf.st_size != alloc_len) {
+        THROW_ERROR("the file size is not extended by fallocate");
+    }
+    if (fallocate(fd, FALLOC_FL_KEEP_SIZE, 0, alloc_len * 2) < 0) {
+        THROW_ERROR("failed to fallocate with FALLOC_FL_KEEP_SIZE");
+    }
+    if (fstat(fd, &stat_buf) < 0 || stat_buf.st_size != alloc_len) {
+        THROW_ERROR("the file size is changed with FALLOC_FL_KEEP_SIZE");
+    }
+    ret = fallocate(fd, 0, 0, -1);
+    if (ret >= 0 || errno != EINVAL) {
+        THROW_ERROR("check fallocate with negative length fail");
+    }
+    ret = fallocate(fd, 0, LLONG_MAX, 1);
+    if (ret >= 0 || errno != EFBIG) {
+        THROW_ERROR("check fallocate with overflowed range fail");
+    }
+    close(fd);
+    return 0;
+}
+
 typedef int(*test_file_func_t)(const char *);
 
 static int test_file_framework(test_file_func_t fn) {
@@ -191,10 +309,36 @@ static int test_writev_readv() {
     return test_file_framework(__test_writev_readv);
 }
 
+static int test_writev_readv_limits() {
+    return test_file_framework(__test_writev_readv_limits);
+}
+
 static int test_lseek() {
     return test_file_framework(__test_lseek);
 }
 
+static int test_large_offset() {
+    return test_file_framework(__test_large_offset);
+}
+
+static int test_fallocate() {
+    return test_file_framework(__test_fallocate);
+}
+
+static int test_lseek_pipe() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    int ret = lseek(pipe_fds[0], 0, SEEK_SET);
+    if (ret >= 0 || errno != ESPIPE) {
+        THROW_ERROR("check lseek on pipe fail");
+    }
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -203,7 +347,11 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_write_read),
     TEST_CASE(test_pwrite_pread),
     TEST_CASE(test_writev_readv),
+    TEST_CASE(test_writev_readv_limits),
     TEST_CASE(test_lseek),
+    TEST_CASE(test_large_offset),
+    TEST_CASE(test_fallocate),
+    TEST_CASE(test_lseek_pipe),
 };
 
 int main(int argc, const char *argv[]) {
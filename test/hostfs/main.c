@@ -1,7 +1,9 @@
+#include <sys/inotify.h>
 #include <sys/stat.h>
 #include <errno.h>
 #include <fcntl.h>
 #include <dirent.h>
+#include <poll.h>
 #include <stdbool.h>
 #include "test_fs.h"
 
@@ -159,6 +161,178 @@ static int test_mkdir_then_rmdir() {
     return 0;
 }
 
+// Prepared by the host, since symlinks cannot be created on HostFS
+#define STAT_DIR_PATH "/host/hostfs_stat_dir"
+#define STAT_FILE_SIZE 6
+
+static const char *stat_dir_entries[] = { "file", "dir", "link" };
+
+static int check_stat_of_entry(const char *name, const struct stat *stat_buf) {
+    if (strcmp(name, "dir") == 0) {
+        if (!S_ISDIR(stat_buf->st_mode)) {
+            THROW_ERROR("failed to check if it is dir");
+        }
+        return 0;
+    }
+    // The symlink is followed
+    if (!S_ISREG(stat_buf->st_mode) || stat_buf->st_size != STAT_FILE_SIZE) {
+        THROW_ERROR("failed to check the stat of the file");
+    }
+    return 0;
+}
+
+static int find_stat_dir_entry(const char *name) {
+    for (int i = 0; i < ARRAY_SIZE(stat_dir_entries); i++) {
+        if (strcmp(name, stat_dir_entries[i]) == 0) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+static int read_dir_then_stat(DIR *dirp, const struct stat *expected_stat_bufs) {
+    struct dirent *dp;
+    struct stat stat_buf;
+    int found = 0;
+
+    rewinddir(dirp);
+    while (1) {
+        errno = 0;
+        dp = readdir(dirp);
+        if (dp == NULL) {
+            if (errno != 0) {
+                THROW_ERROR("failed to call readdir");
+            }
+            break;
+        }
+        if (strcmp(dp->d_name, ".") == 0 || strcmp(dp->d_name, "..") == 0) {
+            continue;
+        }
+        int i = find_stat_dir_entry(dp->d_name);
+        if (i < 0) {
+            THROW_ERROR("found an unexpected entry");
+        }
+        // The lookup relative to the scanned dir reuses the metadata of the scan
+        if (fstatat(dirfd(dirp), dp->d_name, &stat_buf, 0) < 0) {
+            THROW_ERROR("failed to stat the entry");
+        }
+        if (check_stat_of_entry(dp->d_name, &stat_buf) < 0) {
+            return -1;
+        }
+        if (stat_buf.st_ino != expected_stat_bufs[i].st_ino ||
+                stat_buf.st_mode != expected_stat_bufs[i].st_mode ||
+                stat_buf.st_nlink != expected_stat_bufs[i].st_nlink) {
+            THROW_ERROR("the stat after readdir differs from the one before");
+        }
+        found++;
+    }
+    if (found != ARRAY_SIZE(stat_dir_entries)) {
+        THROW_ERROR("failed to read all the entries");
+    }
+    return 0;
+}
+
+static int test_readdir_then_stat() {
+    struct stat expected_stat_bufs[ARRAY_SIZE(stat_dir_entries)];
+    struct stat stat_buf;
+    char path[PATH_MAX];
+    int ret = -1;
+
+    for (int i = 0; i < ARRAY_SIZE(stat_dir_entries); i++) {
+        snprintf(path, sizeof(path), "%s/%s", STAT_DIR_PATH, stat_dir_entries[i]);
+        if (stat(path, &expected_stat_bufs[i]) < 0) {
+            THROW_ERROR("failed to stat the entry without readdir");
+        }
+        if (check_stat_of_entry(stat_dir_entries[i], &expected_stat_bufs[i]) < 0) {
+            return -1;
+        }
+    }
+
+    DIR *dirp = opendir(STAT_DIR_PATH);
+    if (dirp == NULL) {
+        THROW_ERROR("failed to open the dir");
+    }
+    if (read_dir_then_stat(dirp, expected_stat_bufs) < 0) {
+        goto out;
+    }
+
+    // Scan the dir again, but the metadata of the scan is not used after a file
+    // is changed
+    rewinddir(dirp);
+    if (readdir(dirp) == NULL) {
+        printf("failed to scan the dir again\n");
+        goto out;
+    }
+    if (link(STAT_DIR_PATH "/file", STAT_DIR_PATH "/file_link") < 0) {
+        printf("failed to link the file\n");
+        goto out;
+    }
+    if (fstatat(dirfd(dirp), "file", &stat_buf, 0) < 0 ||
+            stat_buf.st_nlink != expected_stat_bufs[0].st_nlink + 1) {
+        printf("got the stale stat of the file after it is linked\n");
+        unlink(STAT_DIR_PATH "/file_link");
+        goto out;
+    }
+    if (unlink(STAT_DIR_PATH "/file_link") < 0) {
+        printf("failed to unlink the file\n");
+        goto out;
+    }
+    ret = 0;
+out:
+    closedir(dirp);
+    return ret;
+}
+
+static int test_inotify() {
+    const char *file_path = "/host/hostfs_inotify.txt";
+    char buf[sizeof(struct inotify_event) + NAME_MAX + 1]
+    __attribute__((aligned(__alignof__(struct inotify_event))));
+
+    int fd = inotify_init1(IN_NONBLOCK | IN_CLOEXEC);
+    if (fd < 0) {
+        THROW_ERROR("failed to create an inotify file");
+    }
+    // Only the files on HostFS can be watched
+    if (inotify_add_watch(fd, "/tmp", IN_CREATE) >= 0 || errno != EOPNOTSUPP) {
+        close(fd);
+        THROW_ERROR("watching the files not on HostFS should fail");
+    }
+    int wd = inotify_add_watch(fd, "/host", IN_CREATE);
+    if (wd < 0) {
+        close(fd);
+        THROW_ERROR("failed to watch the dir on HostFS");
+    }
+    if (read(fd, buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        close(fd);
+        THROW_ERROR("reading no events should fail with EAGAIN");
+    }
+
+    if (create_file(file_path) < 0) {
+        close(fd);
+        return -1;
+    }
+    struct pollfd pollfd = { .fd = fd, .events = POLLIN };
+    ssize_t len = -1;
+    if (poll(&pollfd, 1, 1000) == 1) {
+        len = read(fd, buf, sizeof(buf));
+    }
+    remove_file(file_path);
+    struct inotify_event *event = (struct inotify_event *)buf;
+    if (len < (ssize_t)sizeof(struct inotify_event) || event->wd != wd ||
+            !(event->mask & IN_CREATE) ||
+            strcmp(event->name, "hostfs_inotify.txt") != 0) {
+        close(fd);
+        THROW_ERROR("failed to read the event of the created file");
+    }
+
+    if (inotify_rm_watch(fd, wd) < 0) {
+        close(fd);
+        THROW_ERROR("failed to remove the watch");
+    }
+    close(fd);
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -168,6 +342,8 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_rename),
     TEST_CASE(test_readdir),
     TEST_CASE(test_mkdir_then_rmdir),
+    TEST_CASE(test_readdir_then_stat),
+    TEST_CASE(test_inotify),
 };
 
 int main(int argc, const char *argv[]) {
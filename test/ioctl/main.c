@@ -274,6 +274,125 @@ int test_ioctl_SIOCGIFCONF(void) {
     return 0;
 }
 
+// ============================================================================
+// Test cases for socket ioctls
+// ============================================================================
+
+int test_ioctl_FIONBIO(void) {
+    int socks[3];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a unix socket pair");
+    }
+    socks[2] = socket(AF_INET, SOCK_STREAM, 0);
+    if (socks[2] < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to create an inet socket");
+    }
+
+    for (int i = 0; i < 3; i++) {
+        int on = 1, off = 0;
+        if (ioctl(socks[i], FIONBIO, &on) < 0 ||
+                !(fcntl(socks[i], F_GETFL) & O_NONBLOCK)) {
+            close_files(3, socks[0], socks[1], socks[2]);
+            THROW_ERROR("failed to set the non-blocking mode with FIONBIO");
+        }
+        if (ioctl(socks[i], FIONBIO, &off) < 0 ||
+                fcntl(socks[i], F_GETFL) & O_NONBLOCK) {
+            close_files(3, socks[0], socks[1], socks[2]);
+            THROW_ERROR("failed to clear the non-blocking mode with FIONBIO");
+        }
+    }
+
+    int on = 1;
+    char buf[16];
+    if (ioctl(socks[0], FIONBIO, &on) < 0 ||
+            read(socks[0], buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        close_files(3, socks[0], socks[1], socks[2]);
+        THROW_ERROR("read should fail with EAGAIN after FIONBIO");
+    }
+    close_files(3, socks[0], socks[1], socks[2]);
+    return 0;
+}
+
+int test_ioctl_FIOCLEX_FIONCLEX(void) {
+    int socks[3];
+    if (socketpair(AF_UNIX, SOCK_STREAM | SOCK_CLOEXEC, 0, socks) < 0) {
+        THROW_ERROR("failed to create a unix socket pair");
+    }
+    socks[2] = socket(AF_INET, SOCK_STREAM | SOCK_CLOEXEC, 0);
+    if (socks[2] < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to create an inet socket");
+    }
+
+    for (int i = 0; i < 3; i++) {
+        if (!(fcntl(socks[i], F_GETFD) & FD_CLOEXEC)) {
+            close_files(3, socks[0], socks[1], socks[2]);
+            THROW_ERROR("SOCK_CLOEXEC is not set");
+        }
+        if (ioctl(socks[i], FIONCLEX) < 0 || fcntl(socks[i], F_GETFD) & FD_CLOEXEC) {
+            close_files(3, socks[0], socks[1], socks[2]);
+            THROW_ERROR("failed to clear the close-on-exec flag with FIONCLEX");
+        }
+        if (ioctl(socks[i], FIOCLEX) < 0 || !(fcntl(socks[i], F_GETFD) & FD_CLOEXEC)) {
+            close_files(3, socks[0], socks[1], socks[2]);
+            THROW_ERROR("failed to set the close-on-exec flag with FIOCLEX");
+        }
+    }
+
+    // The flag belongs to the fd, not to the file shared with the duplicated fd
+    int fd = dup(socks[0]);
+    if (fd < 0 || fcntl(fd, F_GETFD) & FD_CLOEXEC) {
+        close_files(3, socks[0], socks[1], socks[2]);
+        THROW_ERROR("the close-on-exec flag should not be duplicated");
+    }
+    close_files(4, socks[0], socks[1], socks[2], fd);
+    return 0;
+}
+
+int test_ioctl_SIOCINQ_SIOCOUTQ(void) {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a unix socket pair");
+    }
+
+    const char msg[] = "Hello";
+    if (write(socks[0], msg, sizeof(msg)) != sizeof(msg)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write to the socket");
+    }
+    int inq = -1, outq = -1;
+    if (ioctl(socks[0], TIOCOUTQ, &outq) < 0 || outq != sizeof(msg) ||
+            ioctl(socks[1], FIONREAD, &inq) < 0 || inq != sizeof(msg)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong queue lengths after write");
+    }
+
+    char buf[sizeof(msg)];
+    if (read(socks[1], buf, sizeof(buf)) != sizeof(buf)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to read from the socket");
+    }
+    if (ioctl(socks[0], TIOCOUTQ, &outq) < 0 || outq != 0 ||
+            ioctl(socks[1], FIONREAD, &inq) < 0 || inq != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong queue lengths after read");
+    }
+    close_files(2, socks[0], socks[1]);
+
+    // The queue lengths of host sockets are passed through
+    int sock = socket(AF_INET, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create an inet socket");
+    }
+    if (ioctl(sock, TIOCOUTQ, &outq) < 0 || outq != 0) {
+        close(sock);
+        THROW_ERROR("wrong output queue length of an inet socket");
+    }
+    close(sock);
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -286,6 +405,9 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_sgx_ioctl_SGXIOC_SELF_TARGET),
     TEST_CASE(test_sgx_ioctl_SGXIOC_CREATE_AND_VERIFY_REPORT),
     TEST_CASE(test_ioctl_SIOCGIFCONF),
+    TEST_CASE(test_ioctl_FIONBIO),
+    TEST_CASE(test_ioctl_FIOCLEX_FIONCLEX),
+    TEST_CASE(test_ioctl_SIOCINQ_SIOCOUTQ),
 };
 
 int main() {
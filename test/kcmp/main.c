@@ -0,0 +1,141 @@
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <spawn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define KCMP_FILE       0
+#define KCMP_VM         1
+#define KCMP_FILES      2
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int kcmp(pid_t pid1, pid_t pid2, int type, unsigned long idx1,
+                unsigned long idx2) {
+    return syscall(SYS_kcmp, pid1, pid2, type, idx1, idx2);
+}
+
+// Wait until the pipe is closed by the parent
+static int waiting_child(const char *fd_str) {
+    int fd = atoi(fd_str);
+    char buf;
+    while (read(fd, &buf, 1) > 0) {
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for kcmp
+// ============================================================================
+
+int test_kcmp_file(void) {
+    pid_t pid = getpid();
+    int fd = open("/dev/null", O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/null");
+    }
+    int dup_fd = dup(fd);
+    int other_fd = open("/dev/null", O_RDONLY);
+    if (dup_fd < 0 || other_fd < 0) {
+        close_files(3, fd, dup_fd, other_fd);
+        THROW_ERROR("failed to dup or open files");
+    }
+
+    if (kcmp(pid, pid, KCMP_FILE, fd, dup_fd) != 0) {
+        close_files(3, fd, dup_fd, other_fd);
+        THROW_ERROR("the duplicated fd should refer to the same file");
+    }
+    int ret = kcmp(pid, pid, KCMP_FILE, fd, other_fd);
+    int reversed_ret = kcmp(pid, pid, KCMP_FILE, other_fd, fd);
+    if ((ret != 1 && ret != 2) || ret + reversed_ret != 3) {
+        close_files(3, fd, dup_fd, other_fd);
+        THROW_ERROR("the files opened separately should be ordered");
+    }
+    close_files(3, fd, dup_fd, other_fd);
+    return 0;
+}
+
+int test_kcmp_across_processes(void) {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    // The child sees EOF once the write end is closed by the parent
+    if (fcntl(pipe_fds[1], F_SETFD, FD_CLOEXEC) < 0) {
+        close_files(2, pipe_fds[0], pipe_fds[1]);
+        THROW_ERROR("failed to set FD_CLOEXEC");
+    }
+    char fd_str[16];
+    snprintf(fd_str, sizeof(fd_str), "%d", pipe_fds[0]);
+    char *child_argv[] = { "/bin/kcmp", "waiting_child", fd_str, NULL };
+    pid_t child_pid;
+    if (posix_spawn(&child_pid, "/bin/kcmp", NULL, NULL, child_argv, NULL) != 0) {
+        close_files(2, pipe_fds[0], pipe_fds[1]);
+        THROW_ERROR("failed to spawn a child process");
+    }
+
+    pid_t pid = getpid();
+    int ret = 0;
+    // The inherited fd refers to the same open file description
+    if (kcmp(pid, child_pid, KCMP_FILE, pipe_fds[0], pipe_fds[0]) != 0) {
+        printf("ERROR: the inherited fd should refer to the same file\n");
+        ret = -1;
+    }
+    if (kcmp(pid, child_pid, KCMP_FILES, 0, 0) == 0) {
+        printf("ERROR: the processes should not share the file table\n");
+        ret = -1;
+    }
+    if (kcmp(pid, child_pid, KCMP_VM, 0, 0) == 0) {
+        printf("ERROR: the processes should not share the address space\n");
+        ret = -1;
+    }
+    if (kcmp(pid, pid, KCMP_FILES, 0, 0) != 0 || kcmp(pid, pid, KCMP_VM, 0, 0) != 0) {
+        printf("ERROR: a process should share the resources with itself\n");
+        ret = -1;
+    }
+
+    close_files(2, pipe_fds[0], pipe_fds[1]);
+    if (wait4(child_pid, NULL, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    return ret;
+}
+
+int test_kcmp_invalid(void) {
+    pid_t pid = getpid();
+    if (kcmp(pid, pid, 100, 0, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("kcmp with an invalid type should fail with EINVAL");
+    }
+    if (kcmp(pid, pid, KCMP_FILE, 0, 12345) >= 0 || errno != EBADF) {
+        THROW_ERROR("kcmp with a closed fd should fail with EBADF");
+    }
+    if (kcmp(pid, 0x7ffffff, KCMP_VM, 0, 0) >= 0 || errno != ESRCH) {
+        THROW_ERROR("kcmp with a nonexistent pid should fail with ESRCH");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_kcmp_file),
+    TEST_CASE(test_kcmp_across_processes),
+    TEST_CASE(test_kcmp_invalid),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 2 && strcmp(argv[1], "waiting_child") == 0) {
+        return waiting_child(argv[2]);
+    }
+
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,62 @@
+#include <sys/types.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define LIBOS_TEST_DEV      "/dev/libos_test"
+#define REPORT_MAX_LEN      (64 * 1024)
+
+// ============================================================================
+// Test cases for the in-enclave test runner
+// ============================================================================
+
+int test_run_libos_tests() {
+    static char report[REPORT_MAX_LEN];
+    int fd = open(LIBOS_TEST_DEV, O_RDWR);
+    if (fd < 0) {
+        if (errno == ENOENT) {
+            printf("\t\tSkipped: the LibOS is built without the libos_test feature\n");
+            return 0;
+        }
+        THROW_ERROR("failed to open %s", LIBOS_TEST_DEV);
+    }
+
+    // An empty filter runs all tests
+    const char *filter = "\n";
+    if (write(fd, filter, strlen(filter)) != strlen(filter)) {
+        close(fd);
+        THROW_ERROR("failed to run the LibOS tests");
+    }
+
+    size_t report_len = 0;
+    ssize_t len;
+    while ((len = read(fd, report + report_len, sizeof(report) - 1 - report_len)) > 0) {
+        report_len += len;
+    }
+    close(fd);
+    if (len < 0) {
+        THROW_ERROR("failed to read the report of the LibOS tests");
+    }
+    report[report_len] = '\0';
+    printf("%s", report);
+
+    if (strstr(report, " 0 failed") == NULL) {
+        THROW_ERROR("some LibOS tests failed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_run_libos_tests),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,80 @@
+#include <ctype.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#define MACHINE_ID_PATH     "/etc/machine-id"
+#define MACHINE_ID_LEN      32
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int read_machine_id(char *buf, size_t buf_len) {
+    int fd = open(MACHINE_ID_PATH, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open %s", MACHINE_ID_PATH);
+    }
+    ssize_t len = read(fd, buf, buf_len - 1);
+    close(fd);
+    if (len < 0) {
+        THROW_ERROR("failed to read %s", MACHINE_ID_PATH);
+    }
+    buf[len] = '\0';
+    return 0;
+}
+
+// ============================================================================
+// Test cases for machine-id
+// ============================================================================
+
+int test_machine_id_format(void) {
+    char id[64];
+    if (read_machine_id(id, sizeof(id)) < 0) {
+        THROW_ERROR("failed to read the machine ID");
+    }
+    if (strlen(id) != MACHINE_ID_LEN + 1 || id[MACHINE_ID_LEN] != '\n') {
+        THROW_ERROR("the machine ID should be 32 hex digits and a newline");
+    }
+    int all_zeros = 1;
+    for (int i = 0; i < MACHINE_ID_LEN; i++) {
+        if (!isxdigit(id[i]) || isupper(id[i])) {
+            THROW_ERROR("the machine ID should be lowercase hex digits");
+        }
+        if (id[i] != '0') {
+            all_zeros = 0;
+        }
+    }
+    if (all_zeros) {
+        THROW_ERROR("the machine ID should not be all zeros");
+    }
+    return 0;
+}
+
+int test_machine_id_stable(void) {
+    char id[64];
+    char id_again[64];
+    if (read_machine_id(id, sizeof(id)) < 0 ||
+            read_machine_id(id_again, sizeof(id_again)) < 0) {
+        THROW_ERROR("failed to read the machine ID");
+    }
+    if (strcmp(id, id_again) != 0) {
+        THROW_ERROR("the machine ID should not change");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_machine_id_format),
+    TEST_CASE(test_machine_id_stable),
+};
+
+int main(void) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -2,6 +2,9 @@
 #include <sys/types.h>
 #include <sys/stat.h>
 #include <sys/mman.h>
+#include <sys/ioctl.h>
+#include <sys/syscall.h>
+#include <linux/userfaultfd.h>
 #include <unistd.h>
 #include <stdio.h>
 #include <stdlib.h>
@@ -1061,6 +1064,303 @@ int test_mprotect_with_invalid_prot() {
     return 0;
 }
 
+// ============================================================================
+// Test cases for madvise
+// ============================================================================
+
+#ifndef MADV_WIPEONFORK
+#define MADV_WIPEONFORK 18
+#define MADV_KEEPONFORK 19
+#endif
+
+int test_madvise_fork_flags() {
+    size_t len = 3 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    char *addr = mmap(NULL, len, prot, flags, -1, 0);
+    if (addr == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    memset(addr, 0xab, len);
+
+    // The flags of a part of the mapping are changed
+    if (madvise(addr + PAGE_SIZE, PAGE_SIZE, MADV_WIPEONFORK) < 0 ||
+            madvise(addr, PAGE_SIZE, MADV_DONTFORK) < 0 ||
+            madvise(addr + PAGE_SIZE, PAGE_SIZE, MADV_KEEPONFORK) < 0 ||
+            madvise(addr, PAGE_SIZE, MADV_DOFORK) < 0) {
+        munmap(addr, len);
+        THROW_ERROR("madvise failed");
+    }
+    // The memory is kept intact
+    for (size_t i = 0; i < len; i++) {
+        if (addr[i] != (char)0xab) {
+            munmap(addr, len);
+            THROW_ERROR("the memory is changed by madvise");
+        }
+    }
+    if (munmap(addr, len) < 0) {
+        THROW_ERROR("munmap failed");
+    }
+    return 0;
+}
+
+int test_madvise_with_invalid_args() {
+    int prot = PROT_READ | PROT_WRITE;
+    int flags = MAP_PRIVATE | MAP_ANONYMOUS;
+    char *addr = mmap(NULL, PAGE_SIZE, prot, flags, -1, 0);
+    if (addr == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    int ret = madvise(addr + 1, PAGE_SIZE - 1, MADV_WIPEONFORK);
+    if (ret == 0 || errno != EINVAL) {
+        munmap(addr, PAGE_SIZE);
+        THROW_ERROR("using non-page-aligned addr should have failed");
+    }
+    ret = madvise(addr, PAGE_SIZE, 0x1234);
+    munmap(addr, PAGE_SIZE);
+    if (ret == 0 || errno != EINVAL) {
+        THROW_ERROR("using invalid advice should have failed");
+    }
+    ret = madvise(NULL, PAGE_SIZE, MADV_WIPEONFORK);
+    if (ret == 0 || errno != ENOMEM) {
+        THROW_ERROR("using invalid addr should have failed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for userfaultfd
+// ============================================================================
+
+static int userfaultfd_open(int flags) {
+    int uffd = syscall(__NR_userfaultfd, flags);
+    if (uffd < 0) {
+        return -1;
+    }
+    struct uffdio_api api = { .api = UFFD_API, .features = 0 };
+    if (ioctl(uffd, UFFDIO_API, &api) < 0 ||
+            !(api.ioctls & (1ULL << _UFFDIO_REGISTER))) {
+        close(uffd);
+        return -1;
+    }
+    return uffd;
+}
+
+int test_userfaultfd_copy_and_zeropage() {
+    int ret = 0;
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    char *addr = mmap(NULL, len, prot, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (addr == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    int uffd = userfaultfd_open(O_CLOEXEC | O_NONBLOCK);
+    if (uffd < 0) {
+        munmap(addr, len);
+        THROW_ERROR("failed to open a userfaultfd");
+    }
+
+    struct uffdio_register reg = {
+        .range = { .start = (unsigned long)addr, .len = len },
+        .mode = UFFDIO_REGISTER_MODE_MISSING,
+    };
+    if (ioctl(uffd, UFFDIO_REGISTER, &reg) < 0 ||
+            !(reg.ioctls & (1ULL << _UFFDIO_COPY))) {
+        ret = -1;
+        printf("failed to register the range\n");
+        goto out;
+    }
+    // No page fault has happened
+    struct uffd_msg msg;
+    if (read(uffd, &msg, sizeof(msg)) >= 0 || errno != EAGAIN) {
+        ret = -1;
+        printf("no message should be read\n");
+        goto out;
+    }
+
+    // The missing pages are resolved without accessing them before
+    char src[PAGE_SIZE];
+    memset(src, 0xab, sizeof(src));
+    struct uffdio_copy copy = {
+        .dst = (unsigned long)addr,
+        .src = (unsigned long)src,
+        .len = PAGE_SIZE,
+    };
+    if (ioctl(uffd, UFFDIO_COPY, &copy) < 0 || copy.copy != PAGE_SIZE) {
+        ret = -1;
+        printf("failed to copy the page\n");
+        goto out;
+    }
+    struct uffdio_zeropage zeropage = {
+        .range = { .start = (unsigned long)addr + PAGE_SIZE, .len = PAGE_SIZE },
+    };
+    if (ioctl(uffd, UFFDIO_ZEROPAGE, &zeropage) < 0 || zeropage.zeropage != PAGE_SIZE) {
+        ret = -1;
+        printf("failed to zero the page\n");
+        goto out;
+    }
+    for (size_t i = 0; i < len; i++) {
+        if (addr[i] != (i < PAGE_SIZE ? (char)0xab : 0)) {
+            ret = -1;
+            printf("the pages are not resolved as expected\n");
+            goto out;
+        }
+    }
+    // A page can be resolved only once
+    if (ioctl(uffd, UFFDIO_COPY, &copy) == 0 || errno != EEXIST ||
+            copy.copy != -EEXIST) {
+        ret = -1;
+        printf("copying to a resolved page should fail with EEXIST\n");
+        goto out;
+    }
+
+    struct uffdio_range range = { .start = (unsigned long)addr, .len = len };
+    if (ioctl(uffd, UFFDIO_UNREGISTER, &range) < 0) {
+        ret = -1;
+        printf("failed to unregister the range\n");
+        goto out;
+    }
+    if (ioctl(uffd, UFFDIO_ZEROPAGE, &zeropage) == 0 || errno != ENOENT) {
+        ret = -1;
+        printf("zeroing an unregistered page should fail with ENOENT\n");
+        goto out;
+    }
+
+out:
+    close(uffd);
+    munmap(addr, len);
+    return ret;
+}
+
+// The LibOS must not fault on the missing pages while serving a system call, so
+// the buffers with any missing page are refused
+int test_userfaultfd_syscall_on_missing_pages() {
+    int ret = 0;
+    int pipe_fds[2] = { -1, -1 };
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    char *addr = mmap(NULL, len, prot, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (addr == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    int uffd = userfaultfd_open(O_CLOEXEC | O_NONBLOCK);
+    if (uffd < 0) {
+        munmap(addr, len);
+        THROW_ERROR("failed to open a userfaultfd");
+    }
+    struct uffdio_register reg = {
+        .range = { .start = (unsigned long)addr, .len = len },
+        .mode = UFFDIO_REGISTER_MODE_MISSING,
+    };
+    if (pipe(pipe_fds) < 0 || ioctl(uffd, UFFDIO_REGISTER, &reg) < 0) {
+        ret = -1;
+        printf("failed to create the pipe or to register the range\n");
+        goto out;
+    }
+
+    // Neither read from nor written to by the LibOS
+    if (write(pipe_fds[1], addr, 1) >= 0 || errno != EFAULT) {
+        ret = -1;
+        printf("writing from a missing page should fail with EFAULT\n");
+        goto out;
+    }
+    char byte = 'a';
+    if (write(pipe_fds[1], &byte, 1) != 1 ||
+            read(pipe_fds[0], addr + PAGE_SIZE, 1) >= 0 || errno != EFAULT) {
+        ret = -1;
+        printf("reading into a missing page should fail with EFAULT\n");
+        goto out;
+    }
+    struct uffdio_copy copy = {
+        .dst = (unsigned long)addr,
+        .src = (unsigned long)addr + PAGE_SIZE,
+        .len = PAGE_SIZE,
+    };
+    if (ioctl(uffd, UFFDIO_COPY, &copy) == 0 || errno != EFAULT ||
+            copy.copy != -EFAULT) {
+        ret = -1;
+        printf("copying from a missing page should fail with EFAULT\n");
+        goto out;
+    }
+
+    // Only the buffers that have no missing page left are accepted
+    struct uffdio_zeropage zeropage = {
+        .range = { .start = (unsigned long)addr, .len = PAGE_SIZE },
+    };
+    if (ioctl(uffd, UFFDIO_ZEROPAGE, &zeropage) < 0) {
+        ret = -1;
+        printf("failed to zero the page\n");
+        goto out;
+    }
+    if (read(pipe_fds[0], addr, 1) != 1 || addr[0] != 'a') {
+        ret = -1;
+        printf("failed to read into a resolved page\n");
+        goto out;
+    }
+    if (write(pipe_fds[1], addr, len) >= 0 || errno != EFAULT) {
+        ret = -1;
+        printf("writing from a partly missing buffer should fail with EFAULT\n");
+        goto out;
+    }
+
+out:
+    close(pipe_fds[0]);
+    close(pipe_fds[1]);
+    close(uffd);
+    munmap(addr, len);
+    return ret;
+}
+
+int test_userfaultfd_with_invalid_args() {
+    if (syscall(__NR_userfaultfd, 0x1234) >= 0 || errno != EINVAL) {
+        THROW_ERROR("using invalid flags should have failed");
+    }
+    int uffd = syscall(__NR_userfaultfd, O_CLOEXEC);
+    if (uffd < 0) {
+        THROW_ERROR("failed to open a userfaultfd");
+    }
+    size_t len = 2 * PAGE_SIZE;
+    int prot = PROT_READ | PROT_WRITE;
+    char *addr = mmap(NULL, len, prot, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (addr == MAP_FAILED) {
+        close(uffd);
+        THROW_ERROR("mmap failed");
+    }
+    struct uffdio_register reg = {
+        .range = { .start = (unsigned long)addr, .len = len },
+        .mode = UFFDIO_REGISTER_MODE_MISSING,
+    };
+    // The API must be handshaked first
+    if (ioctl(uffd, UFFDIO_REGISTER, &reg) == 0 || errno != EINVAL) {
+        close(uffd);
+        munmap(addr, len);
+        THROW_ERROR("registering before the handshake should have failed");
+    }
+    close(uffd);
+
+    uffd = userfaultfd_open(O_CLOEXEC);
+    if (uffd < 0) {
+        munmap(addr, len);
+        THROW_ERROR("failed to open a userfaultfd");
+    }
+    reg.mode = UFFDIO_REGISTER_MODE_WP;
+    int wp_ret = ioctl(uffd, UFFDIO_REGISTER, &reg);
+    int wp_errno = errno;
+    reg.mode = UFFDIO_REGISTER_MODE_MISSING;
+    reg.range.start += 1;
+    int unaligned_ret = ioctl(uffd, UFFDIO_REGISTER, &reg);
+    int unaligned_errno = errno;
+    close(uffd);
+    munmap(addr, len);
+    if (wp_ret == 0 || wp_errno != EINVAL) {
+        THROW_ERROR("the write-protect mode should not be supported");
+    }
+    if (unaligned_ret == 0 || unaligned_errno != EINVAL) {
+        THROW_ERROR("registering a non-page-aligned range should have failed");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -1100,6 +1400,11 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_mprotect_with_zero_len),
     TEST_CASE(test_mprotect_with_invalid_addr),
     TEST_CASE(test_mprotect_with_invalid_prot),
+    TEST_CASE(test_madvise_fork_flags),
+    TEST_CASE(test_madvise_with_invalid_args),
+    TEST_CASE(test_userfaultfd_copy_and_zeropage),
+    TEST_CASE(test_userfaultfd_syscall_on_missing_pages),
+    TEST_CASE(test_userfaultfd_with_invalid_args),
 };
 
 int main() {
@@ -0,0 +1,108 @@
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+#include "test.h"
+
+// The address of the in-enclave stack, which must match the one in Occlum.json
+// (see run_net_stack_test.sh)
+#define STACK_ADDR      "10.0.3.2"
+#define STACK_PORT      8808
+#define CLIENT_MSG      "msg from client to server"
+#define SERVER_MSG      "msg from server to client"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int send_and_recv(int send_fd, int recv_fd, const char *msg) {
+    size_t len = strlen(msg);
+    if (send(send_fd, msg, len, 0) != len) {
+        THROW_ERROR("failed to send the message");
+    }
+
+    char buf[64] = {0};
+    size_t recv_len = 0;
+    while (recv_len < len) {
+        ssize_t ret = recv(recv_fd, buf + recv_len, sizeof(buf) - 1 - recv_len, 0);
+        if (ret <= 0) {
+            THROW_ERROR("failed to recv the message");
+        }
+        recv_len += ret;
+    }
+    if (recv_len != len || strcmp(buf, msg) != 0) {
+        THROW_ERROR("the message received is not the one sent");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases
+// ============================================================================
+
+// The frames between the sockets on the stack are looped back in the enclave
+// instead of going through the tap device
+int test_loopback_connect_send_recv() {
+    int ret = -1;
+    int listen_fd = -1, client_fd = -1, server_fd = -1;
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_port = htons(STACK_PORT);
+    if (inet_pton(AF_INET, STACK_ADDR, &addr.sin_addr) != 1) {
+        THROW_ERROR("invalid address of the stack");
+    }
+
+    listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0 || client_fd < 0) {
+        printf("failed to create the sockets\n");
+        goto out;
+    }
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        printf("failed to listen on the stack\n");
+        goto out;
+    }
+
+    // The connection is established by the stack without waiting for accept
+    if (connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        printf("failed to connect to the stack\n");
+        goto out;
+    }
+    server_fd = accept(listen_fd, NULL, NULL);
+    if (server_fd < 0) {
+        printf("failed to accept the connection\n");
+        goto out;
+    }
+
+    if (send_and_recv(client_fd, server_fd, CLIENT_MSG) < 0 ||
+            send_and_recv(server_fd, client_fd, SERVER_MSG) < 0) {
+        goto out;
+    }
+    ret = 0;
+out:
+    if (server_fd >= 0) { close(server_fd); }
+    if (client_fd >= 0) { close(client_fd); }
+    if (listen_fd >= 0) { close(listen_fd); }
+    if (ret < 0) {
+        THROW_ERROR("the loopback test of the stack failed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_loopback_connect_send_recv),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
@@ -0,0 +1,27 @@
+#!/bin/bash
+# The TCP sockets are served by the in-enclave stack only in an instance of its
+# own, as the stack requires a tap device on the host, which can be created only
+# with CAP_NET_ADMIN. So the test is not run by `make test`, but by this script
+# after the tests are built.
+set -e
+
+script_dir="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+build_dir="${script_dir}/../../build"
+tap=occlum_test_tap
+# Must match STACK_ADDR in main.c
+stack_addr="10.0.3.2/24"
+
+ip tuntap add dev ${tap} mode tap
+trap "ip tuntap del dev ${tap} mode tap" EXIT
+ip link set ${tap} up
+
+rm -rf "${build_dir}/test_net_stack" && mkdir -p "${build_dir}/test_net_stack"
+cd "${build_dir}/test_net_stack"
+occlum init
+cp "${build_dir}/test/image/bin/net_stack" image/bin
+new_json="$(jq --arg tap "${tap}" --arg addr "${stack_addr}" \
+    '.network.stack = {"tap": $tap, "addr": $addr}' Occlum.json)" && \
+echo "${new_json}" > Occlum.json
+
+occlum build
+occlum run /bin/net_stack
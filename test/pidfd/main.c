@@ -0,0 +1,216 @@
+#include <sys/epoll.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <spawn.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+#ifndef SYS_pidfd_send_signal
+#define SYS_pidfd_send_signal 424
+#endif
+#ifndef SYS_pidfd_open
+#define SYS_pidfd_open 434
+#endif
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static int pidfd_open(pid_t pid, unsigned int flags) {
+    return syscall(SYS_pidfd_open, pid, flags);
+}
+
+static int pidfd_send_signal(int pidfd, int sig, siginfo_t *info, unsigned int flags) {
+    return syscall(SYS_pidfd_send_signal, pidfd, sig, info, flags);
+}
+
+static int spawn_child(const char *cmd, pid_t *child_pid) {
+    char *child_argv[] = { "/bin/pidfd", (char *)cmd, NULL };
+    if (posix_spawn(child_pid, "/bin/pidfd", NULL, NULL, child_argv, NULL) != 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    return 0;
+}
+
+static int sleeping_child(void) {
+    usleep(500 * 1000);
+    return 0;
+}
+
+// Signals are delivered to the child when it makes system calls
+static int looping_child(void) {
+    while (1) {
+        getpid();
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for pidfd
+// ============================================================================
+
+int test_pidfd_open_invalid(void) {
+    if (pidfd_open(-1, 0) >= 0 || errno != EINVAL) {
+        THROW_ERROR("pidfd_open with an invalid pid should fail with EINVAL");
+    }
+    if (pidfd_open(getpid(), O_CLOEXEC) >= 0 || errno != EINVAL) {
+        THROW_ERROR("pidfd_open with invalid flags should fail with EINVAL");
+    }
+    if (pidfd_open(0x7ffffff, 0) >= 0 || errno != ESRCH) {
+        THROW_ERROR("pidfd_open with a nonexistent pid should fail with ESRCH");
+    }
+    int fd = open("/dev/null", O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open /dev/null");
+    }
+    if (pidfd_send_signal(fd, SIGKILL, NULL, 0) >= 0 || errno != EBADF) {
+        close(fd);
+        THROW_ERROR("pidfd_send_signal to a non-pidfd should fail with EBADF");
+    }
+    close(fd);
+    return 0;
+}
+
+int test_pidfd_poll(void) {
+    pid_t child_pid;
+    if (spawn_child("sleeping_child", &child_pid) < 0) {
+        return -1;
+    }
+    int pidfd = pidfd_open(child_pid, 0);
+    if (pidfd < 0) {
+        THROW_ERROR("failed to open a pidfd");
+    }
+    if (!(fcntl(pidfd, F_GETFD) & FD_CLOEXEC)) {
+        close(pidfd);
+        THROW_ERROR("a pidfd should be closed on exec");
+    }
+
+    struct pollfd pfd = { .fd = pidfd, .events = POLLIN };
+    if (poll(&pfd, 1, 0) != 0) {
+        close(pidfd);
+        THROW_ERROR("the pidfd should not be readable before the child exits");
+    }
+    if (poll(&pfd, 1, -1) != 1 || !(pfd.revents & POLLIN)) {
+        close(pidfd);
+        THROW_ERROR("the pidfd should be readable after the child exits");
+    }
+
+    int status = 0;
+    if (wait4(child_pid, &status, 0, NULL) < 0 || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0) {
+        close(pidfd);
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    close(pidfd);
+    return 0;
+}
+
+int test_pidfd_epoll(void) {
+    pid_t child_pid;
+    if (spawn_child("sleeping_child", &child_pid) < 0) {
+        return -1;
+    }
+    int pidfd = pidfd_open(child_pid, 0);
+    if (pidfd < 0) {
+        THROW_ERROR("failed to open a pidfd");
+    }
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        close(pidfd);
+        THROW_ERROR("failed to create an epoll file");
+    }
+    struct epoll_event event = { .events = EPOLLIN, .data.fd = pidfd };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, pidfd, &event) < 0) {
+        close_files(2, pidfd, epfd);
+        THROW_ERROR("failed to add the pidfd to the epoll file");
+    }
+    if (epoll_wait(epfd, &event, 1, -1) != 1 || event.data.fd != pidfd ||
+            !(event.events & EPOLLIN)) {
+        close_files(2, pidfd, epfd);
+        THROW_ERROR("the pidfd should be readable after the child exits");
+    }
+    if (wait4(child_pid, NULL, 0, NULL) < 0) {
+        close_files(2, pidfd, epfd);
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    close_files(2, pidfd, epfd);
+    return 0;
+}
+
+int test_pidfd_send_signal(void) {
+    pid_t child_pid;
+    if (spawn_child("looping_child", &child_pid) < 0) {
+        return -1;
+    }
+    int pidfd = pidfd_open(child_pid, O_NONBLOCK);
+    if (pidfd < 0) {
+        THROW_ERROR("failed to open a pidfd");
+    }
+    if (pidfd_send_signal(pidfd, SIGKILL, NULL, 1) >= 0 || errno != EINVAL) {
+        close(pidfd);
+        THROW_ERROR("pidfd_send_signal with invalid flags should fail with EINVAL");
+    }
+    // Signal 0 checks whether the process is alive
+    if (pidfd_send_signal(pidfd, 0, NULL, 0) < 0) {
+        close(pidfd);
+        THROW_ERROR("the child should be alive");
+    }
+    if (pidfd_send_signal(pidfd, SIGKILL, NULL, 0) < 0) {
+        close(pidfd);
+        THROW_ERROR("failed to send SIGKILL via the pidfd");
+    }
+
+    struct pollfd pfd = { .fd = pidfd, .events = POLLIN };
+    if (poll(&pfd, 1, -1) != 1 || !(pfd.revents & POLLIN)) {
+        close(pidfd);
+        THROW_ERROR("the pidfd should be readable after the child is killed");
+    }
+    int status = 0;
+    if (wait4(child_pid, &status, 0, NULL) < 0 || !WIFSIGNALED(status) ||
+            WTERMSIG(status) != SIGKILL) {
+        close(pidfd);
+        THROW_ERROR("the child should be killed by SIGKILL");
+    }
+
+    // The pidfd still refers to the reaped child, even if the pid is reused
+    if (pidfd_send_signal(pidfd, SIGKILL, NULL, 0) >= 0 || errno != ESRCH) {
+        close(pidfd);
+        THROW_ERROR("pidfd_send_signal to an exited process should fail with ESRCH");
+    }
+    close(pidfd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_pidfd_open_invalid),
+    TEST_CASE(test_pidfd_poll),
+    TEST_CASE(test_pidfd_epoll),
+    TEST_CASE(test_pidfd_send_signal),
+};
+
+int main(int argc, const char *argv[]) {
+    if (argc > 1) {
+        const char *cmd = argv[1];
+        if (strcmp(cmd, "sleeping_child") == 0) {
+            return sleeping_child();
+        } else if (strcmp(cmd, "looping_child") == 0) {
+            return looping_child();
+        } else {
+            fprintf(stderr, "ERROR: unknown command: %s\n", cmd);
+            return EXIT_FAILURE;
+        }
+    }
+
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
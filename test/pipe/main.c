@@ -3,6 +3,7 @@
 #include <sys/wait.h>
 #include <fcntl.h>
 #include <poll.h>
+#include <pthread.h>
 #include <unistd.h>
 #include <stdlib.h>
 #include <stdio.h>
@@ -220,6 +221,81 @@ int test_select_read_write() {
     return 0;
 }
 
+#define NUM_WRITERS     4
+#define NUM_MSGS        1000
+#define MSG_LEN         64
+
+struct writer_arg {
+    int fd;
+    int id;
+};
+
+// Each message is filled with the id of the writer, except the sequence number
+static void *write_msgs(void *_arg) {
+    struct writer_arg *arg = (struct writer_arg *)_arg;
+    char msg[MSG_LEN];
+    memset(msg, arg->id, sizeof(msg));
+    for (int seq = 0; seq < NUM_MSGS; seq++) {
+        memcpy(msg + 1, &seq, sizeof(seq));
+        if (write(arg->fd, msg, sizeof(msg)) != sizeof(msg)) {
+            return (void *) -1;
+        }
+    }
+    return NULL;
+}
+
+// The messages written by the threads sharing the write end must not interleave
+int test_concurrent_writers() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+
+    pthread_t threads[NUM_WRITERS];
+    struct writer_arg args[NUM_WRITERS];
+    for (int i = 0; i < NUM_WRITERS; i++) {
+        args[i].fd = pipe_fds[1];
+        args[i].id = i + 1;
+        if (pthread_create(&threads[i], NULL, write_msgs, &args[i]) != 0) {
+            THROW_ERROR("failed to create a writer thread");
+        }
+    }
+
+    int next_seqs[NUM_WRITERS] = {0};
+    char msg[MSG_LEN];
+    for (int msg_i = 0; msg_i < NUM_WRITERS * NUM_MSGS; msg_i++) {
+        size_t len = 0;
+        while (len < sizeof(msg)) {
+            ssize_t ret = read(pipe_fds[0], msg + len, sizeof(msg) - len);
+            if (ret <= 0) {
+                THROW_ERROR("failed to read the pipe");
+            }
+            len += ret;
+        }
+
+        int id = msg[0], seq;
+        memcpy(&seq, msg + 1, sizeof(seq));
+        if (id < 1 || id > NUM_WRITERS || seq != next_seqs[id - 1]) {
+            THROW_ERROR("the messages are interleaved or out of order");
+        }
+        for (int i = 1 + sizeof(seq); i < sizeof(msg); i++) {
+            if (msg[i] != id) {
+                THROW_ERROR("the messages are interleaved");
+            }
+        }
+        next_seqs[id - 1]++;
+    }
+
+    for (int i = 0; i < NUM_WRITERS; i++) {
+        void *ret;
+        if (pthread_join(threads[i], &ret) != 0 || ret != NULL) {
+            THROW_ERROR("the writer thread failed");
+        }
+    }
+    free_pipe(pipe_fds);
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -232,6 +308,7 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_select_no_timeout),
     TEST_CASE(test_poll_no_timeout),
     TEST_CASE(test_select_read_write),
+    TEST_CASE(test_concurrent_writers),
 };
 
 int main(int argc, const char *argv[]) {
@@ -0,0 +1,329 @@
+#include <sys/syscall.h>
+#include <sys/stat.h>
+#include <dirent.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/sysinfo.h>
+#include <time.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Helper function
+// ============================================================================
+
+static int read_file(const char *path, char *buf, size_t buf_size) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open %s", path);
+    }
+    int len = read(fd, buf, buf_size - 1);
+    close(fd);
+    if (len < 0) {
+        THROW_ERROR("failed to read %s", path);
+    }
+    buf[len] = '\0';
+    return len;
+}
+
+// Find the entry of the name in the directory, which returns 1 if found, 0 if not
+static int find_dirent(const char *dir_path, const char *name, unsigned char *type,
+                       ino_t *ino) {
+    DIR *dir = opendir(dir_path);
+    if (dir == NULL) {
+        THROW_ERROR("failed to open the directory %s", dir_path);
+    }
+    int found = 0;
+    struct dirent *entry;
+    while ((entry = readdir(dir)) != NULL) {
+        if (strcmp(entry->d_name, name) == 0) {
+            *type = entry->d_type;
+            *ino = entry->d_ino;
+            found = 1;
+            break;
+        }
+    }
+    closedir(dir);
+    return found;
+}
+
+static int check_schedstat(const char *path) {
+    char buf[128];
+    unsigned long long run_time, wait_time, nr_exits;
+
+    if (read_file(path, buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (sscanf(buf, "%llu %llu %llu", &run_time, &wait_time, &nr_exits) != 3) {
+        THROW_ERROR("invalid schedstat format: %s", buf);
+    }
+    if (run_time == 0) {
+        THROW_ERROR("the run time must not be zero");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for procfs
+// ============================================================================
+
+static int test_self_schedstat() {
+    // Sleep a while to make the wait time non-zero
+    struct timespec ts = { .tv_sec = 0, .tv_nsec = 10 * 1000 * 1000 };
+    nanosleep(&ts, NULL);
+    return check_schedstat("/proc/self/schedstat");
+}
+
+static int test_pid_schedstat() {
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%d/schedstat", getpid());
+    return check_schedstat(path);
+}
+
+static int test_task_schedstat() {
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/self/task/%ld/schedstat", syscall(SYS_gettid));
+    return check_schedstat(path);
+}
+
+static int test_nonexistent_pid() {
+    if (open("/proc/65535/schedstat", O_RDONLY) >= 0) {
+        THROW_ERROR("the file of a nonexistent process should not be opened");
+    }
+    return 0;
+}
+
+static int test_net_dev() {
+    char buf[4096];
+    if (read_file("/proc/net/dev", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strncmp(buf, "Inter-|", strlen("Inter-|")) != 0) {
+        THROW_ERROR("invalid header of /proc/net/dev: %s", buf);
+    }
+
+    // Skip the two header lines
+    char *line = strchr(buf, '\n');
+    line = line == NULL ? NULL : strchr(line + 1, '\n');
+    while (line != NULL && *(++line) != '\0') {
+        char name[16];
+        unsigned long long rx_bytes, rx_packets;
+        if (sscanf(line, " %15[^:]: %llu %llu", name, &rx_bytes, &rx_packets) != 3) {
+            THROW_ERROR("invalid line of /proc/net/dev: %s", line);
+        }
+        line = strchr(line, '\n');
+    }
+    return 0;
+}
+
+static int test_sgxstats() {
+    char buf[4096];
+    if (read_file("/proc/occlum/sgxstats", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strncmp(buf, "tid host_tid exits", strlen("tid host_tid exits")) != 0) {
+        THROW_ERROR("invalid header of /proc/occlum/sgxstats: %s", buf);
+    }
+
+    // The current thread is running, so its host fields are available
+    long tid = syscall(SYS_gettid);
+    char *line = strchr(buf, '\n');
+    while (line != NULL && *(++line) != '\0') {
+        long line_tid, host_tid;
+        unsigned long long exits, interrupts, exceptions, page_faults;
+        unsigned long long host_minflt, host_majflt, host_nivcsw;
+        if (sscanf(line, "%ld", &line_tid) == 1 && line_tid == tid) {
+            if (sscanf(line, "%ld %ld %llu %llu %llu %llu %llu %llu %llu", &line_tid,
+                       &host_tid, &exits, &interrupts, &exceptions, &page_faults,
+                       &host_minflt, &host_majflt, &host_nivcsw) != 9) {
+                THROW_ERROR("invalid line of /proc/occlum/sgxstats: %s", line);
+            }
+            return 0;
+        }
+        line = strchr(line, '\n');
+    }
+    THROW_ERROR("the current thread is not found in /proc/occlum/sgxstats");
+}
+
+static int test_background_works() {
+    // The file systems are synced every second in the test config
+    usleep(1200 * 1000);
+    getppid();
+
+    char buf[1024];
+    if (read_file("/proc/occlum/background_works", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    const char *header = "name priority interval_ms runs run_time_ns\n";
+    if (strncmp(buf, header, strlen(header)) != 0) {
+        THROW_ERROR("invalid header of /proc/occlum/background_works: %s", buf);
+    }
+    char *line = strstr(buf, "\nfs_sync ");
+    unsigned long long interval_ms, runs, run_time_ns;
+    char priority[16];
+    if (line == NULL || sscanf(line + 1, "fs_sync %15s %llu %llu %llu", priority,
+                               &interval_ms, &runs, &run_time_ns) != 4) {
+        THROW_ERROR("fs_sync is not found in /proc/occlum/background_works: %s", buf);
+    }
+    if (strcmp(priority, "low") != 0 || interval_ms != 1000 || runs == 0) {
+        THROW_ERROR("unexpected stats of fs_sync: %s", line + 1);
+    }
+    return 0;
+}
+
+static int test_host_fd_exhaustion() {
+    char buf[256];
+    if (read_file("/proc/occlum/host_fd_exhaustion", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    unsigned long long exhausted, recovered;
+    if (sscanf(buf, "exhausted %llu\nrecovered %llu\n", &exhausted, &recovered) != 2) {
+        THROW_ERROR("invalid /proc/occlum/host_fd_exhaustion: %s", buf);
+    }
+    if (recovered > exhausted) {
+        THROW_ERROR("more exhaustions are recovered than happened: %s", buf);
+    }
+    return 0;
+}
+
+static int test_stat_btime() {
+    char buf[256];
+    long long btime;
+    if (read_file("/proc/stat", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    char *line = strstr(buf, "btime ");
+    if (line == NULL || sscanf(line, "btime %lld", &btime) != 1) {
+        THROW_ERROR("btime is not found in /proc/stat: %s", buf);
+    }
+
+    struct sysinfo info;
+    if (sysinfo(&info) < 0) {
+        THROW_ERROR("failed to call sysinfo");
+    }
+    // The boot time plus the up time should be (roughly) the current time
+    long long now = time(NULL);
+    if (btime <= 0 || btime + info.uptime > now + 2 || btime + info.uptime < now - 2) {
+        THROW_ERROR("btime %lld and uptime %ld are inconsistent with now %lld",
+                    btime, info.uptime, now);
+    }
+    return 0;
+}
+
+static int test_self_stat_starttime() {
+    char buf[1024];
+    if (read_file("/proc/self/stat", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    // Skip the pid and the comm, which may contain spaces
+    char *fields = strrchr(buf, ')');
+    if (fields == NULL) {
+        THROW_ERROR("invalid /proc/self/stat: %s", buf);
+    }
+    // The starttime is the 22nd field, i.e., the 20th field after the comm
+    char *field = fields + 1;
+    for (int i = 0; i < 19 && field != NULL; i++) {
+        field = strchr(field + 1, ' ');
+    }
+    unsigned long long start_time;
+    if (field == NULL || sscanf(field, " %llu", &start_time) != 1) {
+        THROW_ERROR("starttime is not found in /proc/self/stat: %s", buf);
+    }
+
+    struct sysinfo info;
+    if (sysinfo(&info) < 0) {
+        THROW_ERROR("failed to call sysinfo");
+    }
+    long clock_ticks = sysconf(_SC_CLK_TCK);
+    if (start_time > (unsigned long long)(info.uptime + 1) * clock_ticks) {
+        THROW_ERROR("starttime %llu is later than uptime %ld", start_time, info.uptime);
+    }
+    return 0;
+}
+
+static int test_readdir_proc() {
+    char pid_str[16];
+    snprintf(pid_str, sizeof(pid_str), "%d", getpid());
+    unsigned char type;
+    ino_t ino, ino_again;
+    if (find_dirent("/proc", pid_str, &type, &ino) != 1 || type != DT_DIR || ino == 0) {
+        THROW_ERROR("the directory of the process is not found in /proc");
+    }
+    // The inode numbers are stable, and the same as the ones given by stat
+    if (find_dirent("/proc", pid_str, &type, &ino_again) != 1 || ino_again != ino) {
+        THROW_ERROR("the inode number of the process is not stable");
+    }
+    char path[64];
+    snprintf(path, sizeof(path), "/proc/%s", pid_str);
+    struct stat stat_buf;
+    if (stat(path, &stat_buf) < 0 || !S_ISDIR(stat_buf.st_mode) ||
+            stat_buf.st_ino != ino) {
+        THROW_ERROR("wrong stat of %s", path);
+    }
+
+    if (find_dirent("/proc", "self", &type, &ino) != 1 || type != DT_LNK) {
+        THROW_ERROR("/proc/self is not found");
+    }
+    char link[16] = {0};
+    if (readlink("/proc/self", link, sizeof(link) - 1) < 0 ||
+            strcmp(link, pid_str) != 0) {
+        THROW_ERROR("wrong link of /proc/self");
+    }
+    if (find_dirent(path, "stat", &type, &ino) != 1 || type != DT_REG) {
+        THROW_ERROR("stat is not found in %s", path);
+    }
+    return 0;
+}
+
+static int test_readdir_self_fd() {
+    // The fd is not the lowest one, which is taken by the directory when listed
+    int fd = dup2(STDOUT_FILENO, 100);
+    if (fd < 0) {
+        THROW_ERROR("failed to dup the fd");
+    }
+    unsigned char type;
+    ino_t ino;
+    if (find_dirent("/proc/self/fd", "100", &type, &ino) != 1 || type != DT_LNK) {
+        close(fd);
+        THROW_ERROR("the opened fd is not found in /proc/self/fd");
+    }
+    close(fd);
+    if (find_dirent("/proc/self/fd", "100", &type, &ino) != 0) {
+        THROW_ERROR("the closed fd is still found in /proc/self/fd");
+    }
+    return 0;
+}
+
+static int test_readdir_dev() {
+    unsigned char type;
+    ino_t ino;
+    if (find_dirent("/dev", "null", &type, &ino) != 1 || type != DT_CHR || ino == 0) {
+        THROW_ERROR("/dev/null is not found in /dev");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_self_schedstat),
+    TEST_CASE(test_pid_schedstat),
+    TEST_CASE(test_task_schedstat),
+    TEST_CASE(test_nonexistent_pid),
+    TEST_CASE(test_net_dev),
+    TEST_CASE(test_sgxstats),
+    TEST_CASE(test_background_works),
+    TEST_CASE(test_host_fd_exhaustion),
+    TEST_CASE(test_stat_btime),
+    TEST_CASE(test_self_stat_starttime),
+    TEST_CASE(test_readdir_proc),
+    TEST_CASE(test_readdir_self_fd),
+    TEST_CASE(test_readdir_dev),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
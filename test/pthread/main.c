@@ -2,6 +2,10 @@
 #include <pthread.h>
 #include <stdio.h>
 #include <errno.h>
+#include <unistd.h>
+#include <time.h>
+#include <linux/futex.h>
+#include <sys/syscall.h>
 #include "test.h"
 
 // ============================================================================
@@ -183,6 +187,69 @@ static int test_mutex_timedlock() {
     return 0;
 }
 
+// ============================================================================
+// The test cases of futex
+// ============================================================================
+
+#define PING_PONG_ROUND     (10000)
+
+static long futex(volatile int *addr, int op, int val, const struct timespec *timeout) {
+    return syscall(__NR_futex, addr, op, val, timeout, NULL, 0);
+}
+
+static int test_futex_wait_and_wake_without_waiters() {
+    volatile int val = 0;
+    struct timespec timeout = { .tv_sec = 0, .tv_nsec = 10 * 1000 * 1000 };
+
+    if (futex(&val, FUTEX_WAIT_PRIVATE, 1, NULL) == 0 || errno != EAGAIN) {
+        THROW_ERROR("futex wait with a wrong value should fail with EAGAIN");
+    }
+    if (futex(&val, FUTEX_WAIT_PRIVATE, 0, &timeout) == 0 || errno != ETIMEDOUT) {
+        THROW_ERROR("futex wait should time out");
+    }
+    // The waiters that fail or time out must have been removed
+    if (futex(&val, FUTEX_WAKE_PRIVATE, 1, NULL) != 0 ||
+            futex(&val, FUTEX_WAKE, 1, NULL) != 0) {
+        THROW_ERROR("futex wake without waiters should wake none");
+    }
+    return 0;
+}
+
+static volatile int ping;
+static volatile int pong;
+
+static void *thread_ping_pong(void *_arg) {
+    for (int i = 1; i <= PING_PONG_ROUND; i++) {
+        while (ping != i) {
+            futex(&ping, FUTEX_WAIT_PRIVATE, i - 1, NULL);
+        }
+        pong = i;
+        futex(&pong, FUTEX_WAKE_PRIVATE, 1, NULL);
+    }
+    return NULL;
+}
+
+static int test_futex_ping_pong() {
+    pthread_t thread;
+    ping = 0;
+    pong = 0;
+    if (pthread_create(&thread, NULL, thread_ping_pong, NULL) < 0) {
+        THROW_ERROR("pthread_create failed");
+    }
+    // A lost wakeup would hang either thread
+    for (int i = 1; i <= PING_PONG_ROUND; i++) {
+        ping = i;
+        futex(&ping, FUTEX_WAKE_PRIVATE, 1, NULL);
+        while (pong != i) {
+            futex(&pong, FUTEX_WAIT_PRIVATE, i - 1, NULL);
+        }
+    }
+    if (pthread_join(thread, NULL) < 0) {
+        THROW_ERROR("pthread_join failed");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -191,6 +258,8 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_mutex_with_concurrent_counter),
     TEST_CASE(test_mutex_with_cond_wait),
     TEST_CASE(test_mutex_timedlock),
+    TEST_CASE(test_futex_wait_and_wake_without_waiters),
+    TEST_CASE(test_futex_ping_pong),
 };
 
 int main() {
@@ -4,10 +4,12 @@
 #include <string.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <stdint.h>
 #include <sched.h>
 #include <errno.h>
 #include <spawn.h>
 #include <sys/syscall.h>
+#include <linux/membarrier.h>
 #include <sys/wait.h>
 #include "test.h"
 
@@ -303,6 +305,173 @@ static int test_getcpu_after_setaffinity() {
     return 0;
 }
 
+// ============================================================================
+// Test cases for sched_getattr and sched_setattr
+// ============================================================================
+
+// The struct is not defined by the libc
+struct sched_attr {
+    uint32_t size;
+    uint32_t sched_policy;
+    uint64_t sched_flags;
+    int32_t sched_nice;
+    uint32_t sched_priority;
+    uint64_t sched_runtime;
+    uint64_t sched_deadline;
+    uint64_t sched_period;
+    uint32_t sched_util_min;
+    uint32_t sched_util_max;
+};
+
+#ifndef SCHED_DEADLINE
+#define SCHED_DEADLINE 6
+#endif
+
+static int get_sched_attr(struct sched_attr *attr, unsigned int size) {
+    memset(attr, 0, sizeof(*attr));
+    return syscall(__NR_sched_getattr, 0, attr, size, 0);
+}
+
+static int set_sched_attr(struct sched_attr *attr) {
+    return syscall(__NR_sched_setattr, 0, attr, 0);
+}
+
+static int test_sched_getattr() {
+    struct sched_attr attr;
+    if (get_sched_attr(&attr, sizeof(attr)) < 0) {
+        THROW_ERROR("failed to call sched_getattr");
+    }
+    if (attr.size != sizeof(attr) || attr.sched_policy != SCHED_OTHER ||
+            attr.sched_priority != 0) {
+        THROW_ERROR("the attributes are unexpected");
+    }
+    // The size of the first version of the struct, without the utilization clamps
+    if (get_sched_attr(&attr, 48) < 0 || attr.size != 48) {
+        THROW_ERROR("failed to get the attributes of the first version");
+    }
+    if (get_sched_attr(&attr, 8) == 0 || errno != EINVAL) {
+        THROW_ERROR("sched_getattr with a too small size should fail with EINVAL");
+    }
+    return 0;
+}
+
+static int test_sched_setattr() {
+    struct sched_attr attr;
+    memset(&attr, 0, sizeof(attr));
+    attr.size = sizeof(attr);
+    attr.sched_policy = SCHED_BATCH;
+    attr.sched_nice = 5;
+    if (set_sched_attr(&attr) < 0) {
+        THROW_ERROR("failed to set SCHED_BATCH");
+    }
+    if (get_sched_attr(&attr, sizeof(attr)) < 0) {
+        THROW_ERROR("failed to call sched_getattr");
+    }
+    if (attr.sched_policy != SCHED_BATCH || attr.sched_nice != 5) {
+        THROW_ERROR("the attributes are not set");
+    }
+
+    memset(&attr, 0, sizeof(attr));
+    attr.size = sizeof(attr);
+    attr.sched_policy = SCHED_OTHER;
+    if (set_sched_attr(&attr) < 0) {
+        THROW_ERROR("failed to restore SCHED_OTHER");
+    }
+    return 0;
+}
+
+static int test_sched_setattr_with_unsupported_policies() {
+    struct sched_attr attr;
+    memset(&attr, 0, sizeof(attr));
+    attr.size = sizeof(attr);
+    attr.sched_policy = SCHED_FIFO;
+    attr.sched_priority = 10;
+    if (set_sched_attr(&attr) == 0 || errno != EPERM) {
+        THROW_ERROR("setting SCHED_FIFO should fail with EPERM");
+    }
+
+    memset(&attr, 0, sizeof(attr));
+    attr.size = sizeof(attr);
+    attr.sched_policy = SCHED_DEADLINE;
+    attr.sched_runtime = 10 * 1000 * 1000;
+    attr.sched_deadline = 30 * 1000 * 1000;
+    attr.sched_period = 30 * 1000 * 1000;
+    if (set_sched_attr(&attr) == 0 || errno != EPERM) {
+        THROW_ERROR("setting SCHED_DEADLINE should fail with EPERM");
+    }
+    // The runtime must not exceed the deadline
+    attr.sched_runtime = 2 * attr.sched_deadline;
+    if (set_sched_attr(&attr) == 0 || errno != EINVAL) {
+        THROW_ERROR("setting invalid SCHED_DEADLINE params should fail with EINVAL");
+    }
+
+    memset(&attr, 0, sizeof(attr));
+    attr.size = sizeof(attr);
+    attr.sched_policy = 4;
+    if (set_sched_attr(&attr) == 0 || errno != EINVAL) {
+        THROW_ERROR("setting an invalid policy should fail with EINVAL");
+    }
+
+    // The size of the struct known by the kernel is reported for a wrong size
+    memset(&attr, 0, sizeof(attr));
+    attr.size = 8;
+    if (set_sched_attr(&attr) == 0 || errno != E2BIG || attr.size != sizeof(attr)) {
+        THROW_ERROR("setting with a wrong size should fail with E2BIG");
+    }
+
+    struct sched_attr cur_attr;
+    if (get_sched_attr(&cur_attr, sizeof(cur_attr)) < 0 ||
+            cur_attr.sched_policy != SCHED_OTHER) {
+        THROW_ERROR("the policy should not be changed");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test membarrier
+// ============================================================================
+
+static int membarrier(int cmd, unsigned int flags) {
+    return syscall(__NR_membarrier, cmd, flags, 0);
+}
+
+static int test_membarrier() {
+    int cmds = membarrier(MEMBARRIER_CMD_QUERY, 0);
+    if (cmds < 0) {
+        THROW_ERROR("failed to query the commands of membarrier");
+    }
+    if (cmds == 0) {
+        printf("Warning: membarrier is not supported by the host\n");
+        return 0;
+    }
+    int expected_cmds = MEMBARRIER_CMD_GLOBAL | MEMBARRIER_CMD_PRIVATE_EXPEDITED |
+                        MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED;
+    if ((cmds & expected_cmds) != expected_cmds) {
+        THROW_ERROR("the commands of membarrier are not fully supported");
+    }
+
+    if (membarrier(MEMBARRIER_CMD_GLOBAL, 0) < 0) {
+        THROW_ERROR("failed to do MEMBARRIER_CMD_GLOBAL");
+    }
+    if (membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0) == 0 || errno != EPERM) {
+        THROW_ERROR("the expedited barrier without registration should fail with EPERM");
+    }
+    if (membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED, 0) < 0) {
+        THROW_ERROR("failed to register for MEMBARRIER_CMD_PRIVATE_EXPEDITED");
+    }
+    if (membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED, 0) < 0) {
+        THROW_ERROR("failed to do MEMBARRIER_CMD_PRIVATE_EXPEDITED");
+    }
+
+    if (membarrier(MEMBARRIER_CMD_GLOBAL, 1) == 0 || errno != EINVAL) {
+        THROW_ERROR("membarrier with invalid flags should fail with EINVAL");
+    }
+    if (membarrier(1 << 20, 0) == 0 || errno != EINVAL) {
+        THROW_ERROR("membarrier with an invalid command should fail with EINVAL");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -321,6 +490,10 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_sched_xetaffinity_children_inheritance),
     TEST_CASE(test_getcpu),
     TEST_CASE(test_getcpu_after_setaffinity),
+    TEST_CASE(test_sched_getattr),
+    TEST_CASE(test_sched_setattr),
+    TEST_CASE(test_sched_setattr_with_unsupported_policies),
+    TEST_CASE(test_membarrier),
 };
 
 int main() {
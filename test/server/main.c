@@ -1,14 +1,20 @@
 #include <errno.h>
 #include <fcntl.h>
 #include <poll.h>
+#include <pthread.h>
 #include <spawn.h>
 #include <stdlib.h>
 #include <stdio.h>
 #include <string.h>
 #include <unistd.h>
 #include <arpa/inet.h>
+#include <net/if.h>
+#include <netdb.h>
 #include <netinet/in.h>
+#include <netinet/tcp.h>
 #include <sys/types.h>
+#include <sys/ioctl.h>
+#include <sys/sendfile.h>
 #include <sys/socket.h>
 #include <sys/wait.h>
 
@@ -367,14 +373,901 @@ int test_poll() {
     return 0;
 }
 
+static void *poll_thread_func(void *arg) {
+    struct pollfd *pollfd = (struct pollfd *)arg;
+    // Time out rather than hang if the thread is not woken up
+    return (void *)(long)poll(pollfd, 1, 5000);
+}
+
+int test_poll_wakeup_on_shutdown() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("create socket error");
+    }
+    int reuse = 1;
+    if (setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("setsockopt port to reuse failed");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(8806);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the server");
+    }
+    int server_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (server_fd < 0) {
+        close(client_fd);
+        THROW_ERROR("accept socket error");
+    }
+
+    // Block another thread in poll on the socket, then shut it down
+    struct pollfd pollfd = { .fd = server_fd, .events = POLLIN };
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, poll_thread_func, &pollfd) != 0) {
+        close(client_fd);
+        close(server_fd);
+        THROW_ERROR("failed to create the thread");
+    }
+    sleep(1);
+    if (shutdown(server_fd, SHUT_RD) < 0) {
+        THROW_ERROR("failed to shut down the socket");
+    }
+
+    void *poll_ret = NULL;
+    pthread_join(thread, &poll_ret);
+    close(client_fd);
+    close(server_fd);
+    if ((long)poll_ret != 1 || !(pollfd.revents & POLLIN)) {
+        THROW_ERROR("the thread blocked in poll is not woken up by shutdown");
+    }
+    return 0;
+}
+
+// Wait for a non-blocking connect to complete, and return its result
+static int wait_for_connect(int sock) {
+    struct pollfd pollfd = { .fd = sock, .events = POLLOUT };
+    if (poll(&pollfd, 1, 5000) != 1 || !(pollfd.revents & (POLLOUT | POLLERR))) {
+        THROW_ERROR("the connect is not completed in time");
+    }
+    int err = -1;
+    socklen_t err_len = sizeof(err);
+    if (getsockopt(sock, SOL_SOCKET, SO_ERROR, &err, &err_len) < 0) {
+        THROW_ERROR("failed to get SO_ERROR");
+    }
+    return err;
+}
+
+int test_nonblocking_connect() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+
+    int client_fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (client_fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create the socket");
+    }
+    int ret = connect(client_fd, (struct sockaddr *)&addr, sizeof(addr));
+    if (ret < 0 && errno == EINPROGRESS) {
+        ret = wait_for_connect(client_fd);
+    }
+    if (ret != 0) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("failed to connect without blocking");
+    }
+    // Connecting again tells that the socket has been connected
+    ret = connect(client_fd, (struct sockaddr *)&addr, sizeof(addr));
+    if (ret == 0 || errno != EISCONN) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("the socket is not reported as connected");
+    }
+    close(client_fd);
+
+    // The port is no longer listened, so the connect is refused
+    close(listen_fd);
+    client_fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (client_fd < 0) {
+        THROW_ERROR("failed to create the socket");
+    }
+    ret = connect(client_fd, (struct sockaddr *)&addr, sizeof(addr));
+    if (ret < 0) {
+        ret = errno == EINPROGRESS ? wait_for_connect(client_fd) : errno;
+    }
+    if (ret != ECONNREFUSED) {
+        close(client_fd);
+        THROW_ERROR("the refused connect is not reported");
+    }
+    close(client_fd);
+    return 0;
+}
+
+int test_recvfrom_truncated_datagram() {
+    int recv_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    int send_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    struct sockaddr_in recv_addr, send_addr;
+    memset(&recv_addr, 0, sizeof(recv_addr));
+    recv_addr.sin_family = AF_INET;
+    recv_addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    send_addr = recv_addr;
+    socklen_t addr_len = sizeof(recv_addr);
+    if (recv_fd < 0 || send_fd < 0 ||
+            bind(recv_fd, (struct sockaddr *)&recv_addr, sizeof(recv_addr)) < 0 ||
+            getsockname(recv_fd, (struct sockaddr *)&recv_addr, &addr_len) < 0 ||
+            bind(send_fd, (struct sockaddr *)&send_addr, sizeof(send_addr)) < 0 ||
+            getsockname(send_fd, (struct sockaddr *)&send_addr, &addr_len) < 0) {
+        close_files(2, recv_fd, send_fd);
+        THROW_ERROR("failed to bind the sockets");
+    }
+
+    char msg[64];
+    memset(msg, 'a', sizeof(msg));
+    for (int i = 0; i < 2; i++) {
+        if (sendto(send_fd, msg, sizeof(msg), 0, (struct sockaddr *)&recv_addr,
+                   sizeof(recv_addr)) != sizeof(msg)) {
+            close_files(2, recv_fd, send_fd);
+            THROW_ERROR("failed to send the datagram");
+        }
+    }
+
+    // With MSG_TRUNC, the full length of the datagram is returned
+    char buf[8];
+    struct sockaddr_in src_addr;
+    addr_len = sizeof(src_addr);
+    ssize_t ret = recvfrom(recv_fd, buf, sizeof(buf), MSG_TRUNC,
+                           (struct sockaddr *)&src_addr, &addr_len);
+    if (ret != sizeof(msg) || addr_len != sizeof(src_addr) ||
+            src_addr.sin_family != AF_INET || src_addr.sin_port != send_addr.sin_port) {
+        close_files(2, recv_fd, send_fd);
+        THROW_ERROR("unexpected datagram or source address with MSG_TRUNC");
+    }
+
+    // The source address is truncated to the buffer, while its full length is returned
+    unsigned char short_addr[4];
+    addr_len = sizeof(short_addr);
+    ret = recvfrom(recv_fd, buf, sizeof(buf), 0, (struct sockaddr *)short_addr,
+                   &addr_len);
+    if (ret != sizeof(buf) || addr_len != sizeof(src_addr)) {
+        close_files(2, recv_fd, send_fd);
+        THROW_ERROR("unexpected datagram or source address length without MSG_TRUNC");
+    }
+    close_files(2, recv_fd, send_fd);
+    return 0;
+}
+
+static int check_socket_identity(int sock, int domain, int type, int protocol) {
+    int val = -1;
+    socklen_t len = sizeof(val);
+    if (getsockopt(sock, SOL_SOCKET, SO_DOMAIN, &val, &len) < 0 || val != domain) {
+        THROW_ERROR("failed to get SO_DOMAIN");
+    }
+    len = sizeof(val);
+    if (getsockopt(sock, SOL_SOCKET, SO_TYPE, &val, &len) < 0 || val != type) {
+        THROW_ERROR("failed to get SO_TYPE");
+    }
+    len = sizeof(val);
+    if (getsockopt(sock, SOL_SOCKET, SO_PROTOCOL, &val, &len) < 0 || val != protocol) {
+        THROW_ERROR("failed to get SO_PROTOCOL");
+    }
+    return 0;
+}
+
+int test_socket_identity_sockopts() {
+    int ret = 0;
+    int dgram_fd = socket(AF_INET, SOCK_DGRAM | SOCK_CLOEXEC, 0);
+    if (dgram_fd < 0) {
+        THROW_ERROR("create socket error");
+    }
+    ret = check_socket_identity(dgram_fd, AF_INET, SOCK_DGRAM, IPPROTO_UDP);
+    close(dgram_fd);
+    if (ret < 0) {
+        return -1;
+    }
+
+    int listen_fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("create socket error");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(8807);
+    int reuse = 1;
+    if (setsockopt(listen_fd, SOL_SOCKET, SO_REUSEADDR, &reuse, sizeof(reuse)) < 0 ||
+            bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the server");
+    }
+    // The accepted socket inherits the identity of the listener
+    int accepted_fd = accept(listen_fd, NULL, NULL);
+    if (accepted_fd < 0) {
+        close(listen_fd);
+        close(client_fd);
+        THROW_ERROR("accept socket error");
+    }
+    ret = check_socket_identity(listen_fd, AF_INET, SOCK_STREAM, IPPROTO_TCP);
+    if (ret == 0) {
+        ret = check_socket_identity(accepted_fd, AF_INET, SOCK_STREAM, IPPROTO_TCP);
+    }
+    close(accepted_fd);
+    close(client_fd);
+    close(listen_fd);
+    return ret;
+}
+
+int test_ip_mtu_sockopts() {
+    int sock = socket(AF_INET, SOCK_DGRAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("create socket error");
+    }
+
+    int val = IP_PMTUDISC_DO;
+    if (setsockopt(sock, IPPROTO_IP, IP_MTU_DISCOVER, &val, sizeof(val)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to set IP_MTU_DISCOVER");
+    }
+    val = 100;
+    if (setsockopt(sock, IPPROTO_IP, IP_MTU_DISCOVER, &val, sizeof(val)) == 0 ||
+            errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("setting an invalid IP_MTU_DISCOVER should fail with EINVAL");
+    }
+    socklen_t len = sizeof(val);
+    if (getsockopt(sock, IPPROTO_IP, IP_MTU_DISCOVER, &val, &len) < 0 ||
+            len != sizeof(val) || val != IP_PMTUDISC_DO) {
+        close(sock);
+        THROW_ERROR("failed to get IP_MTU_DISCOVER");
+    }
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(8806);
+    if (connect(sock, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(sock);
+        THROW_ERROR("connect failed");
+    }
+    len = sizeof(val);
+    if (getsockopt(sock, IPPROTO_IP, IP_MTU, &val, &len) < 0 ||
+            len != sizeof(val) || val < 68 || val > 65535) {
+        close(sock);
+        THROW_ERROR("failed to get IP_MTU");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_so_bindtodevice() {
+    int sock = socket(AF_INET, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("create socket error");
+    }
+
+    // Only the interfaces listed in network.bind_devices of Occlum.json are allowed
+    const char *not_allowed_dev = "not_allowed0";
+    if (setsockopt(sock, SOL_SOCKET, SO_BINDTODEVICE, not_allowed_dev,
+                   strlen(not_allowed_dev) + 1) == 0 || errno != EPERM) {
+        close(sock);
+        THROW_ERROR("binding to an interface that is not allowed should fail with EPERM");
+    }
+    // An empty name removes the binding
+    if (setsockopt(sock, SOL_SOCKET, SO_BINDTODEVICE, "", 0) < 0) {
+        close(sock);
+        THROW_ERROR("failed to unbind the socket from interfaces");
+    }
+    char dev[IFNAMSIZ];
+    socklen_t len = sizeof(dev);
+    if (getsockopt(sock, SOL_SOCKET, SO_BINDTODEVICE, dev, &len) < 0 || len != 0) {
+        close(sock);
+        THROW_ERROR("the socket should not be bound to any interface");
+    }
+
+    int val = 1;
+    if (setsockopt(sock, IPPROTO_IP, IP_FREEBIND, &val, sizeof(val)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to set IP_FREEBIND");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_multicast_sockopts() {
+    int sock = socket(AF_INET, SOCK_DGRAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("create socket error");
+    }
+
+    struct ip_mreq mreq;
+    mreq.imr_multiaddr.s_addr = htonl(INADDR_LOOPBACK);
+    mreq.imr_interface.s_addr = htonl(INADDR_ANY);
+    if (setsockopt(sock, IPPROTO_IP, IP_ADD_MEMBERSHIP, &mreq, sizeof(mreq)) == 0 ||
+            errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("joining a non-multicast group should fail with EINVAL");
+    }
+
+    int ttl = 256;
+    if (setsockopt(sock, IPPROTO_IP, IP_MULTICAST_TTL, &ttl, sizeof(ttl)) == 0 ||
+            errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("setting an invalid IP_MULTICAST_TTL should fail with EINVAL");
+    }
+    ttl = 8;
+    if (setsockopt(sock, IPPROTO_IP, IP_MULTICAST_TTL, &ttl, sizeof(ttl)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to set IP_MULTICAST_TTL");
+    }
+    socklen_t len = sizeof(ttl);
+    ttl = 0;
+    if (getsockopt(sock, IPPROTO_IP, IP_MULTICAST_TTL, &ttl, &len) < 0 || ttl != 8) {
+        close(sock);
+        THROW_ERROR("failed to get IP_MULTICAST_TTL");
+    }
+
+    unsigned char loop = 0;
+    if (setsockopt(sock, IPPROTO_IP, IP_MULTICAST_LOOP, &loop, sizeof(loop)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to set IP_MULTICAST_LOOP");
+    }
+    int loop_val = 1;
+    len = sizeof(loop_val);
+    if (getsockopt(sock, IPPROTO_IP, IP_MULTICAST_LOOP, &loop_val, &len) < 0 ||
+            loop_val != 0) {
+        close(sock);
+        THROW_ERROR("failed to get IP_MULTICAST_LOOP");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_ping_socket() {
+    int sock = socket(AF_INET, SOCK_DGRAM, IPPROTO_ICMP);
+    if (sock < 0) {
+        if (errno == EACCES || errno == EPERM) {
+            // The host does not allow ping sockets by net.ipv4.ping_group_range
+            printf("\t\tSkipped: ping sockets are not allowed by the host\n");
+            return 0;
+        }
+        THROW_ERROR("create ping socket error");
+    }
+
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+    // Type, code, checksum, identifier and sequence number, followed by the payload.
+    // The checksum and identifier are filled by the host.
+    unsigned char msg[16] = { 0 };
+    msg[0] = 13; // ICMP_TIMESTAMP
+    if (sendto(sock, msg, sizeof(msg), 0, (struct sockaddr *)&addr, sizeof(addr)) >= 0 ||
+            errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("sending a non-echo ICMP message should fail with EINVAL");
+    }
+    if (sendto(sock, msg, 4, 0, (struct sockaddr *)&addr, sizeof(addr)) >= 0 ||
+            errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("sending a truncated ICMP header should fail with EINVAL");
+    }
+
+    msg[0] = 8; // ICMP_ECHO
+    msg[7] = 1;
+    if (sendto(sock, msg, sizeof(msg), 0, (struct sockaddr *)&addr,
+               sizeof(addr)) != sizeof(msg)) {
+        close(sock);
+        THROW_ERROR("failed to send an ICMP echo request");
+    }
+
+    unsigned char reply[64];
+    ssize_t len = recvfrom(sock, reply, sizeof(reply), 0, NULL, NULL);
+    if (len != sizeof(msg) || reply[0] != 0 /* ICMP_ECHOREPLY */ || reply[7] != msg[7]) {
+        close(sock);
+        THROW_ERROR("failed to receive the ICMP echo reply");
+    }
+
+    close(sock);
+    return 0;
+}
+
+int test_sendfile() {
+    const char *file_path = "/root/test_server_sendfile.txt";
+    const char *content = "0123456789abcdefghij";
+    size_t content_len = strlen(content);
+    int file_fd = open(file_path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    if (file_fd < 0) {
+        THROW_ERROR("failed to create the file");
+    }
+    if (write(file_fd, content, content_len) != content_len) {
+        close(file_fd);
+        THROW_ERROR("failed to write the file");
+    }
+
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close_files(2, file_fd, listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close_files(3, file_fd, listen_fd, client_fd);
+        THROW_ERROR("failed to connect");
+    }
+    int server_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (server_fd < 0) {
+        close_files(2, file_fd, client_fd);
+        THROW_ERROR("failed to accept");
+    }
+
+    // Send from the offset given, which is updated and the file offset is not
+    off_t offset = 10;
+    char buf[32] = { 0 };
+    if (sendfile(client_fd, file_fd, &offset, content_len) != content_len - 10 ||
+            offset != content_len || lseek(file_fd, 0, SEEK_CUR) != content_len) {
+        close_files(3, file_fd, client_fd, server_fd);
+        THROW_ERROR("failed to sendfile from the offset given");
+    }
+    if (recv(server_fd, buf, content_len - 10, MSG_WAITALL) != content_len - 10 ||
+            strncmp(buf, content + 10, content_len - 10) != 0) {
+        close_files(3, file_fd, client_fd, server_fd);
+        THROW_ERROR("the data sent from the offset given mismatch");
+    }
+
+    // Send from the file offset, which is updated
+    if (lseek(file_fd, 0, SEEK_SET) != 0 ||
+            sendfile(client_fd, file_fd, NULL, 5) != 5 ||
+            lseek(file_fd, 0, SEEK_CUR) != 5) {
+        close_files(3, file_fd, client_fd, server_fd);
+        THROW_ERROR("failed to sendfile from the file offset");
+    }
+    memset(buf, 0, sizeof(buf));
+    if (recv(server_fd, buf, 5, MSG_WAITALL) != 5 || strncmp(buf, content, 5) != 0) {
+        close_files(3, file_fd, client_fd, server_fd);
+        THROW_ERROR("the data sent from the file offset mismatch");
+    }
+
+    close_files(3, file_fd, client_fd, server_fd);
+    if (unlink(file_path) < 0) {
+        THROW_ERROR("failed to unlink the file");
+    }
+    return 0;
+}
+
+// The Occlum-specific socket option to turn off the buffering of a socket
+#define SOL_OCCLUM 0x4f43
+#define SO_OCCLUM_BUFFERING 2
+
+#define NUM_SMALL_MSGS 100
+
+int test_buffering() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("failed to connect");
+    }
+    int server_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (server_fd < 0) {
+        close(client_fd);
+        THROW_ERROR("failed to accept");
+    }
+
+    // The small messages arrive in order, no matter whether they are buffered
+    char expected[NUM_SMALL_MSGS * 2 + 1];
+    char buf[NUM_SMALL_MSGS * 2 + 1] = { 0 };
+    for (int i = 0; i < NUM_SMALL_MSGS; i++) {
+        snprintf(expected + i * 2, 3, "%02d", i);
+        if (write(client_fd, expected + i * 2, 2) != 2) {
+            close_files(2, client_fd, server_fd);
+            THROW_ERROR("failed to write a small message");
+        }
+    }
+    int len = NUM_SMALL_MSGS * 2;
+    if (read(server_fd, buf, 1) != 1) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("failed to read a byte");
+    }
+    // The rest of the data is readable, even if it has been prefetched
+    struct pollfd pollfds[] = { { .fd = server_fd, .events = POLLIN } };
+    int nread = 0;
+    if (poll(pollfds, 1, 1000) != 1 || ioctl(server_fd, FIONREAD, &nread) < 0 ||
+            nread <= 0) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("the rest of the data should be readable");
+    }
+    if (recv(server_fd, buf + 1, len - 1, MSG_WAITALL) != len - 1 ||
+            strncmp(buf, expected, len) != 0) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("the small messages mismatch");
+    }
+
+    // The buffering can be turned off
+    int val = 0;
+    socklen_t val_len = sizeof(val);
+    if (getsockopt(client_fd, SOL_OCCLUM, SO_OCCLUM_BUFFERING, &val, &val_len) < 0 ||
+            val_len != sizeof(val) || val != 1) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("the buffering should be on by default");
+    }
+    val = 0;
+    if (setsockopt(client_fd, SOL_OCCLUM, SO_OCCLUM_BUFFERING, &val, sizeof(val)) < 0 ||
+            getsockopt(client_fd, SOL_OCCLUM, SO_OCCLUM_BUFFERING, &val, &val_len) < 0 ||
+            val != 0) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("failed to turn off the buffering");
+    }
+    memset(buf, 0, sizeof(buf));
+    int msg_len = strlen(DEFAULT_MSG);
+    if (send(client_fd, DEFAULT_MSG, msg_len, 0) != msg_len ||
+            recv(server_fd, buf, msg_len, MSG_WAITALL) != msg_len ||
+            strcmp(buf, DEFAULT_MSG) != 0) {
+        close_files(2, client_fd, server_fd);
+        THROW_ERROR("failed to send without the buffering");
+    }
+    close_files(2, client_fd, server_fd);
+    return 0;
+}
+
+int test_accept_addr_truncated() {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 2) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+    int client_fds[2] = { -1, -1 };
+    for (int i = 0; i < 2; i++) {
+        client_fds[i] = socket(AF_INET, SOCK_STREAM, 0);
+        if (client_fds[i] < 0 ||
+                connect(client_fds[i], (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+            close_files(3, listen_fd, client_fds[0], client_fds[1]);
+            THROW_ERROR("failed to connect");
+        }
+    }
+
+    // The address is truncated to the buffer, while the full length is returned
+    struct sockaddr_in peer_addr;
+    memset(&peer_addr, 0xff, sizeof(peer_addr));
+    socklen_t peer_addr_len = sizeof(sa_family_t);
+    int server_fd = accept(listen_fd, (struct sockaddr *)&peer_addr, &peer_addr_len);
+    if (server_fd < 0) {
+        close_files(3, listen_fd, client_fds[0], client_fds[1]);
+        THROW_ERROR("failed to accept");
+    }
+    close(server_fd);
+    int ret = 0;
+    if (peer_addr_len != sizeof(struct sockaddr_in) || peer_addr.sin_family != AF_INET ||
+            peer_addr.sin_port != 0xffff) {
+        ret = -1;
+    }
+
+    // The whole address is returned if the buffer is large enough
+    struct sockaddr_storage peer_storage;
+    peer_addr_len = sizeof(peer_storage);
+    server_fd = accept(listen_fd, (struct sockaddr *)&peer_storage, &peer_addr_len);
+    close_files(3, listen_fd, client_fds[0], client_fds[1]);
+    if (server_fd < 0) {
+        THROW_ERROR("failed to accept");
+    }
+    close(server_fd);
+    if (ret < 0) {
+        THROW_ERROR("the truncated address is unexpected");
+    }
+    struct sockaddr_in *peer_in = (struct sockaddr_in *)&peer_storage;
+    if (peer_addr_len != sizeof(struct sockaddr_in) || peer_in->sin_family != AF_INET ||
+            peer_in->sin_addr.s_addr != htonl(INADDR_LOOPBACK)) {
+        THROW_ERROR("the address of the peer is unexpected");
+    }
+    return 0;
+}
+
+static int check_v4_mapped_loopback(struct sockaddr_in6 *addr, socklen_t addr_len) {
+    if (addr_len != sizeof(struct sockaddr_in6) || addr->sin6_family != AF_INET6 ||
+            !IN6_IS_ADDR_V4MAPPED(&addr->sin6_addr) || addr->sin6_scope_id != 0 ||
+            addr->sin6_flowinfo != 0 ||
+            memcmp(&addr->sin6_addr.s6_addr[12], "\x7f\x00\x00\x01", 4) != 0) {
+        return -1;
+    }
+    return 0;
+}
+
+int test_dual_stack_addrs() {
+    int listen_fd = socket(AF_INET6, SOCK_STREAM, 0);
+    if (listen_fd < 0 && errno == EAFNOSUPPORT) {
+        printf("\t\tSkipped: IPv6 is not supported by the host\n");
+        return 0;
+    }
+    int off = 0;
+    struct sockaddr_in6 addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin6_family = AF_INET6;
+    addr.sin6_addr = in6addr_any;
+    socklen_t addr_len = sizeof(addr);
+    if (listen_fd < 0 ||
+            setsockopt(listen_fd, IPPROTO_IPV6, IPV6_V6ONLY, &off, sizeof(off)) < 0 ||
+            bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+    if (addr_len != sizeof(addr) || addr.sin6_family != AF_INET6 ||
+            addr.sin6_scope_id != 0) {
+        close(listen_fd);
+        THROW_ERROR("the local address is unexpected");
+    }
+
+    // The IPv4 peer of a dual-stack socket is given as an IPv4-mapped address
+    struct sockaddr_in addr4;
+    memset(&addr4, 0, sizeof(addr4));
+    addr4.sin_family = AF_INET;
+    addr4.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr4.sin_port = addr.sin6_port;
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 ||
+            connect(client_fd, (struct sockaddr *)&addr4, sizeof(addr4)) < 0) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("failed to connect");
+    }
+    struct sockaddr_in6 peer_addr;
+    socklen_t peer_addr_len = sizeof(peer_addr);
+    int server_fd = accept(listen_fd, (struct sockaddr *)&peer_addr, &peer_addr_len);
+    if (server_fd < 0) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("failed to accept");
+    }
+    int ret = check_v4_mapped_loopback(&peer_addr, peer_addr_len);
+    peer_addr_len = sizeof(peer_addr);
+    memset(&peer_addr, 0xff, sizeof(peer_addr));
+    if (getpeername(server_fd, (struct sockaddr *)&peer_addr, &peer_addr_len) < 0 ||
+            check_v4_mapped_loopback(&peer_addr, peer_addr_len) < 0) {
+        ret = -1;
+    }
+    close_files(3, listen_fd, client_fd, server_fd);
+    if (ret < 0) {
+        THROW_ERROR("the address of the IPv4 peer is unexpected");
+    }
+    return 0;
+}
+
+// The firewall in Occlum.json denies the outbound addresses of 127.0.0.1:9
+#define DENIED_PORT 9
+
+int test_firewall() {
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    addr.sin_port = htons(DENIED_PORT);
+
+    int stream_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (stream_fd < 0) {
+        THROW_ERROR("failed to create the stream socket");
+    }
+    int ret = connect(stream_fd, (struct sockaddr *)&addr, sizeof(addr));
+    close(stream_fd);
+    if (ret == 0 || errno != EACCES) {
+        THROW_ERROR("connecting to the denied address did not fail with EACCES");
+    }
+
+    int dgram_fd = socket(AF_INET, SOCK_DGRAM, 0);
+    if (dgram_fd < 0) {
+        THROW_ERROR("failed to create the datagram socket");
+    }
+    ret = sendto(dgram_fd, "x", 1, 0, (struct sockaddr *)&addr, sizeof(addr));
+    if (ret >= 0 || errno != EACCES) {
+        close(dgram_fd);
+        THROW_ERROR("sending to the denied address did not fail with EACCES");
+    }
+    // The other ports of the same address are allowed
+    addr.sin_port = htons(DENIED_PORT + 1);
+    ret = sendto(dgram_fd, "x", 1, 0, (struct sockaddr *)&addr, sizeof(addr));
+    close(dgram_fd);
+    if (ret != 1) {
+        THROW_ERROR("failed to send to an allowed address");
+    }
+
+    // An IPv4-mapped address is matched as the IPv4 address that it carries
+    int dgram6_fd = socket(AF_INET6, SOCK_DGRAM, 0);
+    if (dgram6_fd < 0 && errno == EAFNOSUPPORT) {
+        return 0;
+    }
+    if (dgram6_fd < 0) {
+        THROW_ERROR("failed to create the IPv6 datagram socket");
+    }
+    struct sockaddr_in6 addr6;
+    memset(&addr6, 0, sizeof(addr6));
+    addr6.sin6_family = AF_INET6;
+    addr6.sin6_port = htons(DENIED_PORT);
+    addr6.sin6_addr.s6_addr[10] = 0xff;
+    addr6.sin6_addr.s6_addr[11] = 0xff;
+    memcpy(&addr6.sin6_addr.s6_addr[12], &addr.sin_addr, 4);
+    ret = sendto(dgram6_fd, "x", 1, 0, (struct sockaddr *)&addr6, sizeof(addr6));
+    close(dgram6_fd);
+    if (ret >= 0 || errno != EACCES) {
+        THROW_ERROR("sending to the mapped denied address did not fail with EACCES");
+    }
+    return 0;
+}
+
+// The keepalive in Occlum.json
+#define KEEPALIVE_IDLE_SECS 120
+#define KEEPALIVE_INTERVAL_SECS 15
+#define KEEPALIVE_COUNT 4
+
+static int get_int_sockopt(int fd, int level, int optname) {
+    int val = -1;
+    socklen_t len = sizeof(val);
+    if (getsockopt(fd, level, optname, &val, &len) < 0 || len != sizeof(val)) {
+        return -1;
+    }
+    return val;
+}
+
+int test_keepalive() {
+    int fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (fd < 0) {
+        THROW_ERROR("failed to create the stream socket");
+    }
+    if (get_int_sockopt(fd, SOL_SOCKET, SO_KEEPALIVE) != 1 ||
+            get_int_sockopt(fd, IPPROTO_TCP, TCP_KEEPIDLE) != KEEPALIVE_IDLE_SECS ||
+            get_int_sockopt(fd, IPPROTO_TCP, TCP_KEEPINTVL) != KEEPALIVE_INTERVAL_SECS ||
+            get_int_sockopt(fd, IPPROTO_TCP, TCP_KEEPCNT) != KEEPALIVE_COUNT) {
+        close(fd);
+        THROW_ERROR("the keepalive of the config is not enabled");
+    }
+
+    // The application can change the keepalive within the valid ranges
+    int val = 1;
+    if (setsockopt(fd, IPPROTO_TCP, TCP_KEEPIDLE, &val, sizeof(val)) < 0 ||
+            get_int_sockopt(fd, IPPROTO_TCP, TCP_KEEPIDLE) != 1) {
+        close(fd);
+        THROW_ERROR("failed to set TCP_KEEPIDLE");
+    }
+    int invalid_vals[][2] = {
+        { TCP_KEEPIDLE, 0 },
+        { TCP_KEEPINTVL, 32768 },
+        { TCP_KEEPCNT, 0 },
+        { TCP_KEEPCNT, 128 },
+    };
+    for (int i = 0; i < ARRAY_SIZE(invalid_vals); i++) {
+        int ret = setsockopt(fd, IPPROTO_TCP, invalid_vals[i][0], &invalid_vals[i][1],
+                             sizeof(int));
+        if (ret == 0 || errno != EINVAL) {
+            close(fd);
+            THROW_ERROR("setting an invalid keepalive option did not fail with EINVAL");
+        }
+    }
+    close(fd);
+    return 0;
+}
+
+#define SYS_occlum_getaddrinfo 364
+
+// The same as struct occlum_addrinfo in the LibOS
+struct occlum_addrinfo {
+    int family;
+    int socktype;
+    int protocol;
+    uint32_t addr_len;
+    struct sockaddr_in6 addr;
+};
+
+// The host DNS in Occlum.json pins 127.0.0.3 to 127.0.0.4, which never matches
+#define MISPINNED_HOST "127.0.0.3"
+
+int test_host_dns() {
+    struct addrinfo hints;
+    memset(&hints, 0, sizeof(hints));
+    hints.ai_family = AF_INET;
+    hints.ai_socktype = SOCK_STREAM;
+    struct occlum_addrinfo res[4];
+    int count = syscall(SYS_occlum_getaddrinfo, "localhost", "80", &hints, res, 4);
+    if (count <= 0) {
+        THROW_ERROR("failed to resolve localhost");
+    }
+    for (int i = 0; i < count; i++) {
+        struct sockaddr_in *addr = (struct sockaddr_in *)&res[i].addr;
+        if (res[i].family != AF_INET || res[i].socktype != SOCK_STREAM ||
+                res[i].addr_len != sizeof(*addr) || addr->sin_family != AF_INET ||
+                addr->sin_port != htons(80) ||
+                addr->sin_addr.s_addr != htonl(INADDR_LOOPBACK)) {
+            THROW_ERROR("unexpected address of localhost");
+        }
+    }
+
+    // The addresses of a pinned name must be the expected ones
+    count = syscall(SYS_occlum_getaddrinfo, MISPINNED_HOST, NULL, &hints, res, 4);
+    if (count >= 0 || errno != EACCES) {
+        THROW_ERROR("resolving to an unexpected address did not fail with EACCES");
+    }
+
+    // Only the addresses are resolved, not the canonical names
+    hints.ai_flags = AI_CANONNAME;
+    count = syscall(SYS_occlum_getaddrinfo, "localhost", NULL, &hints, res, 4);
+    if (count >= 0 || errno != EINVAL) {
+        THROW_ERROR("AI_CANONNAME did not fail with EINVAL");
+    }
+    return 0;
+}
+
 static test_case_t test_cases[] = {
     TEST_CASE(test_read_write),
     TEST_CASE(test_send_recv),
     TEST_CASE(test_sendmsg_recvmsg),
     TEST_CASE(test_sendmsg_recvmsg_connectionless),
+    TEST_CASE(test_recvfrom_truncated_datagram),
     TEST_CASE(test_fcntl_setfl_and_getfl),
     TEST_CASE(test_poll),
     TEST_CASE(test_poll_events_unchanged),
+    TEST_CASE(test_poll_wakeup_on_shutdown),
+    TEST_CASE(test_nonblocking_connect),
+    TEST_CASE(test_socket_identity_sockopts),
+    TEST_CASE(test_accept_addr_truncated),
+    TEST_CASE(test_dual_stack_addrs),
+    TEST_CASE(test_firewall),
+    TEST_CASE(test_host_dns),
+    TEST_CASE(test_keepalive),
+    TEST_CASE(test_ip_mtu_sockopts),
+    TEST_CASE(test_so_bindtodevice),
+    TEST_CASE(test_multicast_sockopts),
+    TEST_CASE(test_ping_socket),
+    TEST_CASE(test_sendfile),
+    TEST_CASE(test_buffering),
 };
 
 int main(int argc, const char *argv[]) {
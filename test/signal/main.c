@@ -3,6 +3,8 @@
 #include <sys/stat.h>
 #include <sys/mman.h>
 #include <sys/wait.h>
+#include <sys/epoll.h>
+#include <sys/eventfd.h>
 #include <unistd.h>
 #include <ucontext.h>
 #include <stdio.h>
@@ -12,6 +14,7 @@
 #include <assert.h>
 #include <string.h>
 #include <fcntl.h>
+#include <errno.h>
 #include <signal.h>
 #include "test.h"
 
@@ -424,6 +427,67 @@ int test_sigchld() {
     return 0;
 }
 
+// ============================================================================
+// Test epoll_pwait with a signal mask
+// ============================================================================
+static volatile int sigusr1_count = 0;
+
+static void handle_sigusr1(int num) {
+    sigusr1_count++;
+}
+
+static int is_sigusr1_blocked() {
+    sigset_t curr_mask;
+    sigprocmask(0, NULL, &curr_mask);
+    return sigismember(&curr_mask, SIGUSR1);
+}
+
+int test_epoll_pwait_sigmask() {
+    int event_fd = eventfd(0, 0);
+    int epfd = epoll_create1(0);
+    if (event_fd < 0 || epfd < 0) {
+        THROW_ERROR("failed to create files");
+    }
+    struct epoll_event event = { .events = EPOLLIN, .data.fd = event_fd };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, event_fd, &event) < 0) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("epoll_ctl failed");
+    }
+
+    signal(SIGUSR1, handle_sigusr1);
+    sigset_t block_mask, old_mask, wait_mask;
+    sigemptyset(&block_mask);
+    sigaddset(&block_mask, SIGUSR1);
+    sigprocmask(SIG_BLOCK, &block_mask, &old_mask);
+    sigusr1_count = 0;
+    raise(SIGUSR1);
+
+    // The pending signal stays blocked if it is blocked by the given mask
+    int ret = epoll_pwait(epfd, &event, 1, 10, &block_mask);
+    if (ret != 0 || sigusr1_count != 0) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("the blocked signal should not interrupt epoll_pwait");
+    }
+
+    // The pending signal interrupts the wait once it is unblocked by the given mask
+    sigemptyset(&wait_mask);
+    ret = epoll_pwait(epfd, &event, 1, -1, &wait_mask);
+    if (ret != -1 || errno != EINTR || sigusr1_count != 1) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("the unblocked signal should interrupt epoll_pwait");
+    }
+    // The original signal mask is restored after the signal is handled
+    if (!is_sigusr1_blocked()) {
+        close_files(2, event_fd, epfd);
+        THROW_ERROR("the signal mask is not restored");
+    }
+
+    sigprocmask(SIG_SETMASK, &old_mask, NULL);
+    signal(SIGUSR1, SIG_DFL);
+    close_files(2, event_fd, epfd);
+    return 0;
+}
+
 // ============================================================================
 // Test suite main
 // ============================================================================
@@ -437,6 +501,7 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_handle_sigsegv),
     TEST_CASE(test_sigaltstack),
     TEST_CASE(test_sigchld),
+    TEST_CASE(test_epoll_pwait_sigmask),
 };
 
 int main(int argc, const char *argv[]) {
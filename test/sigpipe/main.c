@@ -0,0 +1,321 @@
+#include <sys/socket.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <errno.h>
+#include <signal.h>
+#include <string.h>
+#include <unistd.h>
+#include <sys/uio.h>
+#include "test.h"
+
+// ============================================================================
+// Helper functions
+// ============================================================================
+
+static volatile int sigpipe_count = 0;
+
+static void sigpipe_handler(int signum) {
+    sigpipe_count++;
+}
+
+static int install_sigpipe_handler(void) {
+    struct sigaction action;
+    memset(&action, 0, sizeof(action));
+    action.sa_handler = sigpipe_handler;
+    if (sigaction(SIGPIPE, &action, NULL) < 0) {
+        THROW_ERROR("failed to install the handler of SIGPIPE");
+    }
+    sigpipe_count = 0;
+    return 0;
+}
+
+// Write to the fd whose peer is closed, which must fail with EPIPE and raise SIGPIPE
+static int check_write_epipe(int fd) {
+    if (install_sigpipe_handler() < 0) {
+        return -1;
+    }
+    const char *msg = "msg";
+    if (write(fd, msg, strlen(msg)) != -1 || errno != EPIPE) {
+        THROW_ERROR("write to a closed peer should fail with EPIPE");
+    }
+    if (sigpipe_count != 1) {
+        THROW_ERROR("SIGPIPE is not raised on EPIPE");
+    }
+    return 0;
+}
+
+// Send to the fd that can no longer be written with MSG_NOSIGNAL, which must fail with
+// EPIPE without raising SIGPIPE
+static int check_send_nosignal(int fd) {
+    if (install_sigpipe_handler() < 0) {
+        return -1;
+    }
+    char msg[] = "msg";
+    if (send(fd, msg, strlen(msg), MSG_NOSIGNAL) != -1 || errno != EPIPE) {
+        THROW_ERROR("send to a closed peer should fail with EPIPE");
+    }
+    struct iovec iov = { .iov_base = msg, .iov_len = strlen(msg) };
+    struct msghdr hdr;
+    memset(&hdr, 0, sizeof(hdr));
+    hdr.msg_iov = &iov;
+    hdr.msg_iovlen = 1;
+    if (sendmsg(fd, &hdr, MSG_NOSIGNAL) != -1 || errno != EPIPE) {
+        THROW_ERROR("sendmsg to a closed peer should fail with EPIPE");
+    }
+    if (sigpipe_count != 0) {
+        THROW_ERROR("SIGPIPE is raised with MSG_NOSIGNAL");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for pipes
+// ============================================================================
+
+int test_pipe_epipe() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    close(pipe_fds[0]);
+    int ret = check_write_epipe(pipe_fds[1]);
+    close(pipe_fds[1]);
+    return ret;
+}
+
+int test_pipe_epipe_with_sigpipe_ignored() {
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        THROW_ERROR("failed to create a pipe");
+    }
+    close(pipe_fds[0]);
+    signal(SIGPIPE, SIG_IGN);
+    const char *msg = "msg";
+    ssize_t ret = write(pipe_fds[1], msg, strlen(msg));
+    int saved_errno = errno;
+    signal(SIGPIPE, SIG_DFL);
+    close(pipe_fds[1]);
+    if (ret != -1 || saved_errno != EPIPE) {
+        THROW_ERROR("write to a closed peer should fail with EPIPE");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for unix sockets
+// ============================================================================
+
+int test_unix_socket_epipe() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    close(socks[1]);
+    int ret = check_write_epipe(socks[0]);
+    close(socks[0]);
+    return ret;
+}
+
+int test_unix_socket_eof() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    const char *msg = "msg";
+    if (write(socks[1], msg, strlen(msg)) != strlen(msg)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    close(socks[1]);
+
+    // The data written before the peer is closed can still be read
+    char buf[16];
+    if (read(socks[0], buf, sizeof(buf)) != strlen(msg)) {
+        close(socks[0]);
+        THROW_ERROR("failed to read the data written before close");
+    }
+    if (read(socks[0], buf, sizeof(buf)) != 0) {
+        close(socks[0]);
+        THROW_ERROR("read from a closed peer should return EOF");
+    }
+    close(socks[0]);
+    return 0;
+}
+
+int test_unix_socket_econnreset() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    // The peer is closed with the data unread
+    const char *msg = "msg";
+    if (write(socks[0], msg, strlen(msg)) != strlen(msg)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    close(socks[1]);
+
+    char buf[16];
+    if (read(socks[0], buf, sizeof(buf)) != -1 || errno != ECONNRESET) {
+        close(socks[0]);
+        THROW_ERROR("read should fail with ECONNRESET");
+    }
+    // The error is reported only once
+    if (read(socks[0], buf, sizeof(buf)) != 0) {
+        close(socks[0]);
+        THROW_ERROR("read after ECONNRESET should return EOF");
+    }
+    close(socks[0]);
+    return 0;
+}
+
+int test_unix_socket_not_connected() {
+    int sock = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    char buf[16] = "msg";
+    if (write(sock, buf, strlen(buf)) != -1 || errno != ENOTCONN) {
+        close(sock);
+        THROW_ERROR("write to an unconnected socket should fail with ENOTCONN");
+    }
+    if (read(sock, buf, sizeof(buf)) != -1 || errno != EINVAL) {
+        close(sock);
+        THROW_ERROR("read from an unconnected socket should fail with EINVAL");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_unix_socket_msg_nosignal() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("failed to create a socket pair");
+    }
+    close(socks[1]);
+    int ret = check_send_nosignal(socks[0]);
+    close(socks[0]);
+    return ret;
+}
+
+// ============================================================================
+// Test cases for host sockets
+// ============================================================================
+
+// Create a connected host socket that is shut down for writing, where sending fails
+// with EPIPE. Return the fd of the socket, or -1 on error.
+static int create_shut_down_host_socket(void) {
+    int listen_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a socket");
+    }
+    struct sockaddr_in addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sin_family = AF_INET;
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (bind(listen_fd, (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(listen_fd, 1) < 0 ||
+            getsockname(listen_fd, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen on the socket");
+    }
+    int client_fd = socket(AF_INET, SOCK_STREAM, 0);
+    if (client_fd < 0 || connect(client_fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to connect to the server");
+    }
+    int accepted_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (accepted_fd < 0) {
+        close(client_fd);
+        THROW_ERROR("failed to accept");
+    }
+    shutdown(client_fd, SHUT_WR);
+    close(accepted_fd);
+    return client_fd;
+}
+
+int test_host_socket_epipe() {
+    int fd = create_shut_down_host_socket();
+    if (fd < 0) {
+        return -1;
+    }
+    int ret = check_write_epipe(fd);
+    if (ret == 0) {
+        // Only one SIGPIPE is raised by a writev of many buffers
+        char msg[] = "msg";
+        struct iovec iov[2] = {
+            { .iov_base = msg, .iov_len = strlen(msg) },
+            { .iov_base = msg, .iov_len = strlen(msg) },
+        };
+        sigpipe_count = 0;
+        if (writev(fd, iov, 2) != -1 || errno != EPIPE || sigpipe_count != 1) {
+            ret = -1;
+        }
+    }
+    close(fd);
+    if (ret < 0) {
+        THROW_ERROR("writev to a closed peer should fail with EPIPE and one SIGPIPE");
+    }
+    return 0;
+}
+
+int test_host_socket_epipe_with_sigpipe_blocked() {
+    int fd = create_shut_down_host_socket();
+    if (fd < 0) {
+        return -1;
+    }
+    if (install_sigpipe_handler() < 0) {
+        close(fd);
+        return -1;
+    }
+    sigset_t mask, pending;
+    sigemptyset(&mask);
+    sigaddset(&mask, SIGPIPE);
+    sigprocmask(SIG_BLOCK, &mask, NULL);
+    const char *msg = "msg";
+    int ret = (write(fd, msg, strlen(msg)) == -1 && errno == EPIPE) ? 0 : -1;
+    close(fd);
+    // The blocked SIGPIPE is pending, until it is unblocked
+    sigpending(&pending);
+    if (!sigismember(&pending, SIGPIPE) || sigpipe_count != 0) {
+        ret = -1;
+    }
+    sigprocmask(SIG_UNBLOCK, &mask, NULL);
+    if (ret < 0 || sigpipe_count != 1) {
+        THROW_ERROR("the blocked SIGPIPE should be delivered after it is unblocked");
+    }
+    return 0;
+}
+
+int test_host_socket_msg_nosignal() {
+    int fd = create_shut_down_host_socket();
+    if (fd < 0) {
+        return -1;
+    }
+    int ret = check_send_nosignal(fd);
+    close(fd);
+    return ret;
+}
+
+// ============================================================================
+// Test suite
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_pipe_epipe),
+    TEST_CASE(test_pipe_epipe_with_sigpipe_ignored),
+    TEST_CASE(test_unix_socket_epipe),
+    TEST_CASE(test_unix_socket_eof),
+    TEST_CASE(test_unix_socket_econnreset),
+    TEST_CASE(test_unix_socket_not_connected),
+    TEST_CASE(test_unix_socket_msg_nosignal),
+    TEST_CASE(test_host_socket_epipe),
+    TEST_CASE(test_host_socket_epipe_with_sigpipe_blocked),
+    TEST_CASE(test_host_socket_msg_nosignal),
+};
+
+int main() {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
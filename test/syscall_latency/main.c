@@ -0,0 +1,60 @@
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <fcntl.h>
+#include <unistd.h>
+#include <stdio.h>
+
+#define NREPEATS 1000000
+
+static suseconds_t elapsed_us(struct timeval *tv_start, struct timeval *tv_end) {
+    return (tv_end->tv_sec - tv_start->tv_sec) * 1000000UL +
+           (tv_end->tv_usec - tv_start->tv_usec);
+}
+
+int main(int argc, const char *argv[]) {
+    struct timeval tv_start, tv_end;
+    char buf[1] = {0};
+
+    int zero_fd = open("/dev/zero", O_RDONLY);
+    int null_fd = open("/dev/null", O_WRONLY);
+    if (zero_fd < 0 || null_fd < 0) {
+        printf("ERROR: failed to open /dev/zero or /dev/null\n");
+        return -1;
+    }
+
+    // The overhead of a system call that does nearly nothing
+    gettimeofday(&tv_start, NULL);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        syscall(SYS_getppid);
+    }
+    gettimeofday(&tv_end, NULL);
+    printf("Latency of getppid = %lu ns\n",
+           elapsed_us(&tv_start, &tv_end) * 1000 / NREPEATS);
+
+    // The overhead of small reads and writes
+    gettimeofday(&tv_start, NULL);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        if (read(zero_fd, buf, sizeof(buf)) != sizeof(buf)) {
+            printf("ERROR: failed to read (# of repeats = %lu)\n", i);
+            return -1;
+        }
+    }
+    gettimeofday(&tv_end, NULL);
+    printf("Latency of 1-byte read = %lu ns\n",
+           elapsed_us(&tv_start, &tv_end) * 1000 / NREPEATS);
+
+    gettimeofday(&tv_start, NULL);
+    for (unsigned long i = 0; i < NREPEATS; i++) {
+        if (write(null_fd, buf, sizeof(buf)) != sizeof(buf)) {
+            printf("ERROR: failed to write (# of repeats = %lu)\n", i);
+            return -1;
+        }
+    }
+    gettimeofday(&tv_end, NULL);
+    printf("Latency of 1-byte write = %lu ns\n",
+           elapsed_us(&tv_start, &tv_end) * 1000 / NREPEATS);
+
+    close(zero_fd);
+    close(null_fd);
+    return 0;
+}
@@ -0,0 +1,141 @@
+#include <sys/mman.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Helper function
+// ============================================================================
+
+static int read_file(const char *path, char *buf, size_t buf_size) {
+    int fd = open(path, O_RDONLY);
+    if (fd < 0) {
+        THROW_ERROR("failed to open %s", path);
+    }
+    int len = read(fd, buf, buf_size - 1);
+    close(fd);
+    if (len < 0) {
+        THROW_ERROR("failed to read %s", path);
+    }
+    buf[len] = '\0';
+    return len;
+}
+
+static int read_number(const char *path, long long *number) {
+    char buf[64];
+    if (read_file(path, buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (sscanf(buf, "%lld", number) != 1) {
+        THROW_ERROR("invalid number in %s: %s", path, buf);
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for sysfs
+// ============================================================================
+
+static int test_cpu_online() {
+    char buf[64];
+    if (read_file("/sys/devices/system/cpu/online", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    // The CPUs are listed as "0" or "0-N"
+    long ncpus = sysconf(_SC_NPROCESSORS_ONLN);
+    int last_cpu = 0;
+    if (sscanf(buf, "0-%d", &last_cpu) != 1 && strcmp(buf, "0\n") != 0) {
+        THROW_ERROR("invalid cpu list: %s", buf);
+    }
+    if (ncpus > 0 && last_cpu + 1 != ncpus) {
+        THROW_ERROR("the cpu list %s does not match the %ld online cpus", buf, ncpus);
+    }
+    return 0;
+}
+
+#define MEMORY_LIMIT_PATH "/sys/fs/cgroup/memory/memory.limit_in_bytes"
+#define MEMORY_USAGE_PATH "/sys/fs/cgroup/memory/memory.usage_in_bytes"
+
+static int test_cgroup_memory() {
+    long long limit, usage_before, usage_after;
+    if (read_number(MEMORY_LIMIT_PATH, &limit) < 0 ||
+            read_number(MEMORY_USAGE_PATH, &usage_before) < 0) {
+        return -1;
+    }
+    if (limit <= 0 || usage_before > limit) {
+        THROW_ERROR("invalid memory limit %lld or usage %lld", limit, usage_before);
+    }
+
+    // The usage grows with mmap
+    size_t len = 1024 * 1024;
+    void *addr = mmap(NULL, len, PROT_READ | PROT_WRITE,
+                      MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+    if (addr == MAP_FAILED) {
+        THROW_ERROR("mmap failed");
+    }
+    int ret = read_number(MEMORY_USAGE_PATH, &usage_after);
+    munmap(addr, len);
+    if (ret < 0) {
+        return -1;
+    }
+    if (usage_after < usage_before + (long long)len) {
+        THROW_ERROR("the memory usage does not grow with mmap");
+    }
+    return 0;
+}
+
+static int test_cgroup_cpu() {
+    long long quota, period;
+    if (read_number("/sys/fs/cgroup/cpu/cpu.cfs_quota_us", &quota) < 0 ||
+            read_number("/sys/fs/cgroup/cpu/cpu.cfs_period_us", &period) < 0) {
+        return -1;
+    }
+    if (quota != -1 || period <= 0) {
+        THROW_ERROR("the cpu time should not be limited");
+    }
+    return 0;
+}
+
+static int test_hugepages() {
+    char buf[64];
+    if (read_file("/sys/kernel/mm/transparent_hugepage/enabled", buf, sizeof(buf)) < 0) {
+        return -1;
+    }
+    if (strstr(buf, "[never]") == NULL) {
+        THROW_ERROR("transparent huge pages should be disabled");
+    }
+    long long nr_hugepages;
+    if (read_number("/sys/kernel/mm/hugepages/hugepages-2048kB/nr_hugepages",
+                    &nr_hugepages) < 0) {
+        return -1;
+    }
+    if (nr_hugepages != 0) {
+        THROW_ERROR("huge pages should not be available");
+    }
+    return 0;
+}
+
+static int test_nonexistent_file() {
+    if (open("/sys/kernel/nonexistent", O_RDONLY) >= 0) {
+        THROW_ERROR("a nonexistent file under /sys should not be opened");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_cpu_online),
+    TEST_CASE(test_cgroup_memory),
+    TEST_CASE(test_cgroup_cpu),
+    TEST_CASE(test_hugepages),
+    TEST_CASE(test_nonexistent_file),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
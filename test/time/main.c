@@ -1,5 +1,12 @@
+#define _GNU_SOURCE
+#include <sys/resource.h>
 #include <sys/time.h>
+#include <sys/timex.h>
+#include <errno.h>
+#include <pthread.h>
 #include <time.h>
+#include <string.h>
+#include <unistd.h>
 #include "test.h"
 
 // ============================================================================
@@ -50,6 +57,146 @@ int test_clock_getres() {
     return 0;
 }
 
+// ============================================================================
+// Test cases for adjtimex
+// ============================================================================
+
+int test_adjtimex() {
+    struct timex buf;
+    memset(&buf, 0, sizeof(buf));
+    int state = adjtimex(&buf);
+    if (state != TIME_OK && state != TIME_ERROR) {
+        THROW_ERROR("adjtimex failed");
+    }
+    struct timeval tv;
+    if (gettimeofday(&tv, NULL) || tv.tv_sec - buf.time.tv_sec > 1) {
+        THROW_ERROR("adjtimex should report the realtime");
+    }
+    if ((state == TIME_ERROR) != ((buf.status & STA_UNSYNC) != 0)) {
+        THROW_ERROR("adjtimex should report STA_UNSYNC along with TIME_ERROR");
+    }
+
+    // The offset can be read without adjusting the clock
+    memset(&buf, 0, sizeof(buf));
+    buf.modes = ADJ_OFFSET_SS_READ;
+    if (adjtimex(&buf) < 0 || buf.freq != 0) {
+        THROW_ERROR("adjtimex should report the offset without any frequency correction");
+    }
+
+    // The clock is read-only in the enclave
+    memset(&buf, 0, sizeof(buf));
+    buf.modes = ADJ_FREQUENCY;
+    if (adjtimex(&buf) != -1 || errno != EPERM) {
+        THROW_ERROR("adjusting the clock should fail with EPERM");
+    }
+    memset(&buf, 0, sizeof(buf));
+    if (clock_adjtime(CLOCK_MONOTONIC, &buf) != -1 || errno != EOPNOTSUPP) {
+        THROW_ERROR("clock_adjtime(CLOCK_MONOTONIC, ...) should fail with EOPNOTSUPP");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for CPU time
+// ============================================================================
+
+#define SPIN_MS 50
+#define SLEEP_MS 100
+// The margin of the CPU time measured, e.g., for the truncation to milliseconds
+#define MARGIN_MS 10
+
+static long ts_to_ms(const struct timespec *ts) {
+    return ts->tv_sec * 1000 + ts->tv_nsec / 1000000;
+}
+
+static long tv_to_ms(const struct timeval *tv) {
+    return tv->tv_sec * 1000 + tv->tv_usec / 1000;
+}
+
+static long clock_ms(clockid_t clock) {
+    struct timespec ts;
+    if (clock_gettime(clock, &ts) < 0) {
+        return -1;
+    }
+    return ts_to_ms(&ts);
+}
+
+static void *spin(void *arg) {
+    long end = clock_ms(CLOCK_MONOTONIC) + SPIN_MS;
+    while (clock_ms(CLOCK_MONOTONIC) < end) {
+    }
+    return NULL;
+}
+
+int test_cpu_time_clocks() {
+    long thread_start = clock_ms(CLOCK_THREAD_CPUTIME_ID);
+    spin(NULL);
+    long thread_spun = clock_ms(CLOCK_THREAD_CPUTIME_ID);
+    if (thread_start < 0 || thread_spun - thread_start < SPIN_MS - MARGIN_MS) {
+        THROW_ERROR("the thread CPU time should include the spin");
+    }
+    if (clock_ms(CLOCK_PROCESS_CPUTIME_ID) < thread_spun) {
+        THROW_ERROR("the process CPU time should include the thread");
+    }
+
+    // The time blocked in the host is not CPU time
+    struct timespec sleep_ts = { .tv_sec = 0, .tv_nsec = SLEEP_MS * 1000000 };
+    nanosleep(&sleep_ts, NULL);
+    if (clock_ms(CLOCK_THREAD_CPUTIME_ID) - thread_spun >= SLEEP_MS - MARGIN_MS) {
+        THROW_ERROR("the thread CPU time should not include the sleep");
+    }
+
+    // The clocks of the current thread and process given by their IDs
+    clockid_t thread_clock, process_clock;
+    if (pthread_getcpuclockid(pthread_self(), &thread_clock) != 0 ||
+            clock_getcpuclockid(getpid(), &process_clock) != 0) {
+        THROW_ERROR("failed to get the CPU-time clocks");
+    }
+    struct timespec res;
+    if (clock_ms(thread_clock) < thread_spun || clock_ms(process_clock) < thread_spun ||
+            clock_getres(thread_clock, &res) < 0 || res.tv_sec != 0 || res.tv_nsec != 1) {
+        THROW_ERROR("the CPU-time clocks are unexpected");
+    }
+    if (clock_getcpuclockid(0x7fffffff, &process_clock) != ESRCH) {
+        THROW_ERROR("getting the clock of a non-existent process should fail");
+    }
+    return 0;
+}
+
+int test_getrusage() {
+    struct rusage self_before, thread_usage, self_after, children;
+    if (getrusage(RUSAGE_SELF, &self_before) < 0) {
+        THROW_ERROR("getrusage(RUSAGE_SELF, ...) failed");
+    }
+
+    // The CPU time of a thread is kept by the process after the thread exits
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, spin, NULL) != 0 ||
+            pthread_join(thread, NULL) != 0) {
+        THROW_ERROR("failed to run a thread");
+    }
+    spin(NULL);
+    if (getrusage(RUSAGE_THREAD, &thread_usage) < 0 ||
+            getrusage(RUSAGE_SELF, &self_after) < 0 ||
+            getrusage(RUSAGE_CHILDREN, &children) < 0) {
+        THROW_ERROR("getrusage failed");
+    }
+    long self_spun = tv_to_ms(&self_after.ru_utime) - tv_to_ms(&self_before.ru_utime);
+    if (tv_to_ms(&thread_usage.ru_utime) < SPIN_MS - MARGIN_MS ||
+            self_spun < (SPIN_MS - MARGIN_MS) * 2) {
+        THROW_ERROR("the CPU time of the spins is not accounted");
+    }
+    if (tv_to_ms(&self_after.ru_utime) < tv_to_ms(&thread_usage.ru_utime) ||
+            self_after.ru_nvcsw < thread_usage.ru_nvcsw) {
+        THROW_ERROR("the usage of the process should include the current thread");
+    }
+
+    if (getrusage(2, &thread_usage) != -1 || errno != EINVAL) {
+        THROW_ERROR("getrusage with an invalid who should fail with EINVAL");
+    }
+    return 0;
+}
+
 // ============================================================================
 // Test suite
 // ============================================================================
@@ -58,6 +205,9 @@ static test_case_t test_cases[] = {
     TEST_CASE(test_gettimeofday),
     TEST_CASE(test_clock_gettime),
     TEST_CASE(test_clock_getres),
+    TEST_CASE(test_adjtimex),
+    TEST_CASE(test_cpu_time_clocks),
+    TEST_CASE(test_getrusage),
 };
 
 int main() {
@@ -0,0 +1,160 @@
+#include <sys/epoll.h>
+#include <sys/socket.h>
+#include <linux/netlink.h>
+#include <errno.h>
+#include <poll.h>
+#include <string.h>
+#include <unistd.h>
+#include "test.h"
+
+// ============================================================================
+// Helper function
+// ============================================================================
+
+static int create_uevent_socket(int flags) {
+    int sock = socket(AF_NETLINK, SOCK_RAW | flags, NETLINK_KOBJECT_UEVENT);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a uevent socket");
+    }
+    return sock;
+}
+
+static int bind_uevent_socket(int sock) {
+    struct sockaddr_nl addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.nl_family = AF_NETLINK;
+    addr.nl_groups = 1;
+    if (bind(sock, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        THROW_ERROR("failed to bind the uevent socket");
+    }
+    return 0;
+}
+
+// ============================================================================
+// Test cases for the stub of uevent sockets
+// ============================================================================
+
+static int test_bind_and_getsockname() {
+    int sock = create_uevent_socket(SOCK_CLOEXEC);
+    if (sock < 0) {
+        return -1;
+    }
+    int bufsize = 128 * 1024 * 1024;
+    if (setsockopt(sock, SOL_SOCKET, SO_RCVBUFFORCE, &bufsize, sizeof(bufsize)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to set SO_RCVBUFFORCE");
+    }
+    if (bind_uevent_socket(sock) < 0) {
+        close(sock);
+        return -1;
+    }
+
+    struct sockaddr_nl addr;
+    socklen_t addr_len = sizeof(addr);
+    if (getsockname(sock, (struct sockaddr *)&addr, &addr_len) < 0) {
+        close(sock);
+        THROW_ERROR("getsockname failed");
+    }
+    if (addr_len != sizeof(addr) || addr.nl_family != AF_NETLINK ||
+            addr.nl_groups != 1 || addr.nl_pid == 0) {
+        close(sock);
+        THROW_ERROR("wrong address of the uevent socket");
+    }
+
+    int protocol = 0;
+    socklen_t len = sizeof(protocol);
+    if (getsockopt(sock, SOL_SOCKET, SO_PROTOCOL, &protocol, &len) < 0 ||
+            protocol != NETLINK_KOBJECT_UEVENT) {
+        close(sock);
+        THROW_ERROR("wrong SO_PROTOCOL");
+    }
+    close(sock);
+    return 0;
+}
+
+static int test_recv_nothing() {
+    int sock = create_uevent_socket(SOCK_NONBLOCK);
+    if (sock < 0) {
+        return -1;
+    }
+    if (bind_uevent_socket(sock) < 0) {
+        close(sock);
+        return -1;
+    }
+
+    char buf[4096];
+    if (recv(sock, buf, sizeof(buf), 0) >= 0 || errno != EAGAIN) {
+        close(sock);
+        THROW_ERROR("recv should fail with EAGAIN");
+    }
+    if (read(sock, buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        close(sock);
+        THROW_ERROR("read should fail with EAGAIN");
+    }
+    close(sock);
+    return 0;
+}
+
+static int test_poll_never_readable() {
+    int sock = create_uevent_socket(0);
+    if (sock < 0) {
+        return -1;
+    }
+    if (bind_uevent_socket(sock) < 0) {
+        close(sock);
+        return -1;
+    }
+
+    struct pollfd pollfd = { .fd = sock, .events = POLLIN };
+    int ret = poll(&pollfd, 1, 100);
+    if (ret != 0) {
+        close(sock);
+        THROW_ERROR("the uevent socket should not be readable");
+    }
+    // The socket blocks by default, so receive with MSG_DONTWAIT
+    char buf[4096];
+    if (recv(sock, buf, sizeof(buf), MSG_DONTWAIT) >= 0 || errno != EAGAIN) {
+        close(sock);
+        THROW_ERROR("recv with MSG_DONTWAIT should fail with EAGAIN");
+    }
+    close(sock);
+    return 0;
+}
+
+static int test_epoll_never_readable() {
+    int sock = create_uevent_socket(SOCK_NONBLOCK);
+    if (sock < 0) {
+        return -1;
+    }
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        close(sock);
+        THROW_ERROR("epoll_create1 failed");
+    }
+    struct epoll_event event = { .events = EPOLLIN, .data.fd = sock };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, sock, &event) < 0) {
+        close_files(2, sock, epfd);
+        THROW_ERROR("failed to add the uevent socket to epoll");
+    }
+    if (epoll_wait(epfd, &event, 1, 100) != 0) {
+        close_files(2, sock, epfd);
+        THROW_ERROR("the uevent socket should not be readable");
+    }
+    close_files(2, sock, epfd);
+    return 0;
+}
+
+// ============================================================================
+// Test suite main
+// ============================================================================
+
+static test_case_t test_cases[] = {
+    TEST_CASE(test_bind_and_getsockname),
+    TEST_CASE(test_recv_nothing),
+    TEST_CASE(test_poll_never_readable),
+    TEST_CASE(test_epoll_never_readable),
+};
+
+int main(int argc, const char *argv[]) {
+    return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
+}
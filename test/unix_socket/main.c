@@ -1,14 +1,23 @@
+#define _GNU_SOURCE
 #include <sys/syscall.h>
 #include <sys/wait.h>
+#include <sys/epoll.h>
 #include <sys/socket.h>
 #include <sys/un.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
 #include <poll.h>
+#include <fcntl.h>
 #include <unistd.h>
 #include <stdlib.h>
 #include <stdio.h>
 #include <spawn.h>
 #include <string.h>
+#include <errno.h>
+#include <stdint.h>
 #include <spawn.h>
+#include <time.h>
+#include <signal.h>
 
 #include "test.h"
 
@@ -203,13 +212,1594 @@ int test_poll() {
     return 0;
 }
 
+// Poll a host socket and a unix socket, which is a file of LibOS, at the same time
+int test_poll_mixed_files() {
+    int ret = 0;
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int udp_sock = socket(AF_INET, SOCK_DGRAM, 0);
+    struct sockaddr_in addr = { .sin_family = AF_INET };
+    addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+    socklen_t addr_len = sizeof(addr);
+    if (udp_sock < 0 || bind(udp_sock, (struct sockaddr *)&addr, addr_len) < 0 ||
+            getsockname(udp_sock, (struct sockaddr *)&addr, &addr_len) < 0) {
+        ret = -1;
+        printf("failed to bind the UDP socket\n");
+        goto out;
+    }
+
+    struct pollfd polls[] = {
+        { .fd = udp_sock, .events = POLLIN },
+        { .fd = socks[1], .events = POLLIN },
+    };
+    // The child process writes to the unix socket while the parent is waiting
+    const char *child_prog = "/bin/hello_world";
+    const char *child_argv[3] = { child_prog, ECHO_MSG, NULL };
+    int child_pid;
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, socks[0], STDOUT_FILENO);
+    if (posix_spawn(&child_pid, child_prog, &file_actions,
+                    NULL, (char *const *)child_argv, NULL) < 0) {
+        ret = -1;
+        printf("failed to spawn a child process\n");
+        goto out;
+    }
+    int nfds = poll(polls, 2, 5000);
+    wait4(child_pid, NULL, 0, NULL);
+    if (nfds != 1 || polls[0].revents != 0 || !(polls[1].revents & POLLIN)) {
+        ret = -1;
+        printf("only the unix socket should be readable\n");
+        goto out;
+    }
+
+    // The host socket wakes up ppoll with the signal mask replaced
+    if (sendto(udp_sock, "ping", 4, 0, (struct sockaddr *)&addr, addr_len) != 4) {
+        ret = -1;
+        printf("failed to send to the UDP socket\n");
+        goto out;
+    }
+    char buf[sizeof(ECHO_MSG)] = {0};
+    if (read(socks[1], buf, sizeof(buf)) <= 0) {
+        ret = -1;
+        printf("failed to read the unix socket\n");
+        goto out;
+    }
+    struct timespec timeout = { .tv_sec = 5, .tv_nsec = 0 };
+    sigset_t sigmask;
+    sigemptyset(&sigmask);
+    nfds = ppoll(polls, 2, &timeout, &sigmask);
+    if (nfds != 1 || !(polls[0].revents & POLLIN) || polls[1].revents != 0) {
+        ret = -1;
+        printf("only the UDP socket should be readable\n");
+        goto out;
+    }
+
+    // The remaining time is written back on timeout
+    if (recv(udp_sock, buf, sizeof(buf), 0) != 4) {
+        ret = -1;
+        printf("failed to receive from the UDP socket\n");
+        goto out;
+    }
+    timeout.tv_sec = 0;
+    timeout.tv_nsec = 10 * 1000 * 1000;
+    if (ppoll(polls, 2, &timeout, NULL) != 0 || timeout.tv_sec != 0 ||
+            timeout.tv_nsec != 0) {
+        ret = -1;
+        printf("ppoll should time out with no time left\n");
+        goto out;
+    }
+
+out:
+    if (udp_sock >= 0) {
+        close(udp_sock);
+    }
+    close(socks[0]);
+    close(socks[1]);
+    return ret;
+}
+
+#define ACCEPTOR_NUM 3
+#define ACCEPTOR_LISTEN_FD 10
+#define ACCEPTOR_SOCK_PATH "unix_socket_acceptors_path"
+
+// The child process accepts one connection on the inherited listening socket,
+// then sends its pid through the connection
+int run_acceptor() {
+    int fd = accept(ACCEPTOR_LISTEN_FD, NULL, NULL);
+    if (fd < 0) {
+        THROW_ERROR("failed to accept");
+    }
+    pid_t pid = getpid();
+    if (write(fd, &pid, sizeof(pid)) != sizeof(pid)) {
+        close(fd);
+        THROW_ERROR("failed to write pid");
+    }
+    close(fd);
+    return 0;
+}
+
+int test_accept_shared_across_processes() {
+    int ret = 0;
+    int listen_fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, ACCEPTOR_SOCK_PATH);
+    socklen_t addr_len = strlen(addr.sun_path) + sizeof(addr.sun_family);
+    if (bind(listen_fd, (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(listen_fd, 5) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+
+    // All the child processes accept on the same listening socket
+    int child_pids[ACCEPTOR_NUM];
+    const char *child_argv[] = { "/bin/unix_socket", "acceptor", NULL };
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, listen_fd, ACCEPTOR_LISTEN_FD);
+    for (int i = 0; i < ACCEPTOR_NUM; i++) {
+        if (posix_spawn(&child_pids[i], child_argv[0], &file_actions, NULL,
+                        (char *const *)child_argv, NULL) != 0) {
+            close(listen_fd);
+            THROW_ERROR("failed to spawn a child process");
+        }
+    }
+    posix_spawn_file_actions_destroy(&file_actions);
+
+    // Each connection is accepted by exactly one child
+    pid_t accepted_pids[ACCEPTOR_NUM];
+    for (int i = 0; i < ACCEPTOR_NUM; i++) {
+        int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+        if (fd < 0 || connect(fd, (struct sockaddr *)&addr, addr_len) < 0) {
+            close(listen_fd);
+            THROW_ERROR("failed to connect");
+        }
+        ssize_t len = read(fd, &accepted_pids[i], sizeof(pid_t));
+        close(fd);
+        if (len != sizeof(pid_t)) {
+            close(listen_fd);
+            THROW_ERROR("failed to read the pid of the acceptor");
+        }
+        for (int j = 0; j < i; j++) {
+            if (accepted_pids[j] == accepted_pids[i]) {
+                ret = -1;
+            }
+        }
+    }
+    close(listen_fd);
+
+    for (int i = 0; i < ACCEPTOR_NUM; i++) {
+        int status = 0;
+        if (wait4(child_pids[i], &status, 0, NULL) < 0) {
+            THROW_ERROR("failed to wait4 the child process");
+        }
+    }
+    if (ret < 0) {
+        THROW_ERROR("the connections are not distributed among the children");
+    }
+    return 0;
+}
+
+#define REUSEPORT_WORKER_NUM 2
+#define REUSEPORT_MAX_CONNECTIONS 64
+#define REUSEPORT_SOCK_PATH "unix_socket_reuseport_path"
+
+// The worker process serves the connections on its own listening socket until it
+// is told to quit. It sends its pid back through each connection.
+int run_reuseport_worker() {
+    for (;;) {
+        int fd = accept(ACCEPTOR_LISTEN_FD, NULL, NULL);
+        if (fd < 0) {
+            THROW_ERROR("failed to accept");
+        }
+        char cmd = 0;
+        pid_t pid = getpid();
+        if (read(fd, &cmd, 1) != 1 || write(fd, &pid, sizeof(pid)) != sizeof(pid)) {
+            close(fd);
+            THROW_ERROR("failed to serve the connection");
+        }
+        close(fd);
+        if (cmd == 'q') {
+            return 0;
+        }
+    }
+}
+
+// Send the command through a new connection and get the pid of the worker
+static pid_t request_reuseport_worker(struct sockaddr_un *addr, socklen_t addr_len,
+                                      char cmd) {
+    pid_t pid = -1;
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0) {
+        return -1;
+    }
+    if (connect(fd, (struct sockaddr *)addr, addr_len) < 0 ||
+            write(fd, &cmd, 1) != 1 ||
+            read(fd, &pid, sizeof(pid)) != sizeof(pid)) {
+        pid = -1;
+    }
+    close(fd);
+    return pid;
+}
+
+static void close_fds(int *fds, int num) {
+    for (int i = 0; i < num; i++) {
+        close(fds[i]);
+    }
+}
+
+static int find_pid(pid_t *pids, int num, pid_t pid) {
+    for (int i = 0; i < num; i++) {
+        if (pids[i] == pid) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+int test_reuseport() {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, REUSEPORT_SOCK_PATH);
+    socklen_t addr_len = strlen(addr.sun_path) + sizeof(addr.sun_family);
+
+    // Each worker has its own listening socket bound to the same path
+    int listen_fds[REUSEPORT_WORKER_NUM];
+    for (int i = 0; i < REUSEPORT_WORKER_NUM; i++) {
+        int optval = 1;
+        listen_fds[i] = socket(AF_UNIX, SOCK_STREAM, 0);
+        if (listen_fds[i] < 0 ||
+                setsockopt(listen_fds[i], SOL_SOCKET, SO_REUSEPORT, &optval,
+                           sizeof(optval)) < 0 ||
+                bind(listen_fds[i], (struct sockaddr *)&addr, addr_len) < 0 ||
+                listen(listen_fds[i], 5) < 0) {
+            close_fds(listen_fds, i + 1);
+            THROW_ERROR("failed to listen with SO_REUSEPORT");
+        }
+    }
+
+    // The path cannot be shared by a socket without SO_REUSEPORT
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0) {
+        close_fds(listen_fds, REUSEPORT_WORKER_NUM);
+        THROW_ERROR("failed to create a unix socket");
+    }
+    int ret = bind(fd, (struct sockaddr *)&addr, addr_len);
+    close(fd);
+    if (ret == 0 || errno != EADDRINUSE) {
+        close_fds(listen_fds, REUSEPORT_WORKER_NUM);
+        THROW_ERROR("bind without SO_REUSEPORT should fail with EADDRINUSE");
+    }
+
+    pid_t child_pids[REUSEPORT_WORKER_NUM];
+    const char *child_argv[] = { "/bin/unix_socket", "reuseport_worker", NULL };
+    for (int i = 0; i < REUSEPORT_WORKER_NUM; i++) {
+        posix_spawn_file_actions_t file_actions;
+        posix_spawn_file_actions_init(&file_actions);
+        posix_spawn_file_actions_adddup2(&file_actions, listen_fds[i],
+                                         ACCEPTOR_LISTEN_FD);
+        ret = posix_spawn(&child_pids[i], child_argv[0], &file_actions, NULL,
+                          (char *const *)child_argv, NULL);
+        posix_spawn_file_actions_destroy(&file_actions);
+        if (ret != 0) {
+            close_fds(listen_fds, REUSEPORT_WORKER_NUM);
+            THROW_ERROR("failed to spawn a child process");
+        }
+    }
+    // The listening sockets are owned by the workers from now on
+    close_fds(listen_fds, REUSEPORT_WORKER_NUM);
+
+    // The connections are distributed among the workers
+    int served[REUSEPORT_WORKER_NUM] = { 0 };
+    int served_num = 0;
+    for (int i = 0; i < REUSEPORT_MAX_CONNECTIONS && served_num < REUSEPORT_WORKER_NUM;
+            i++) {
+        int idx = find_pid(child_pids, REUSEPORT_WORKER_NUM,
+                           request_reuseport_worker(&addr, addr_len, 'p'));
+        if (idx < 0) {
+            THROW_ERROR("failed to request the workers");
+        }
+        if (!served[idx]) {
+            served[idx] = 1;
+            served_num++;
+        }
+    }
+    ret = served_num == REUSEPORT_WORKER_NUM ? 0 : -1;
+
+    // Tell the workers to quit. The path is released after all of them exit.
+    for (int i = 0; i < REUSEPORT_WORKER_NUM; i++) {
+        pid_t pid = request_reuseport_worker(&addr, addr_len, 'q');
+        if (find_pid(child_pids, REUSEPORT_WORKER_NUM, pid) < 0) {
+            THROW_ERROR("failed to stop the workers");
+        }
+        int status = 0;
+        if (wait4(pid, &status, 0, NULL) < 0) {
+            THROW_ERROR("failed to wait4 the child process");
+        }
+    }
+    if (ret < 0) {
+        THROW_ERROR("the connections are not distributed among the workers");
+    }
+    return 0;
+}
+
+#define REBIND_SOCK_PATH "unix_socket_rebind_path"
+
+int test_rebind() {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, REBIND_SOCK_PATH);
+    socklen_t addr_len = strlen(addr.sun_path) + sizeof(addr.sun_family);
+
+    // The path is released once the socket bound to it is closed, even if the
+    // socket is not listening
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0 || bind(fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        THROW_ERROR("failed to bind");
+    }
+    close(fd);
+
+    int listen_fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(listen_fd, 5) < 0) {
+        THROW_ERROR("failed to bind the path released");
+    }
+    // The path is released once the listening socket is shut down
+    if (shutdown(listen_fd, SHUT_RDWR) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to shut down the listening socket");
+    }
+    fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (fd < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to create a unix socket");
+    }
+    if (connect(fd, (struct sockaddr *)&addr, addr_len) == 0) {
+        close_files(2, fd, listen_fd);
+        THROW_ERROR("the listening socket shut down should refuse connections");
+    }
+    if (bind(fd, (struct sockaddr *)&addr, addr_len) < 0) {
+        close_files(2, fd, listen_fd);
+        THROW_ERROR("failed to bind the path of the socket shut down");
+    }
+    close_files(2, fd, listen_fd);
+    return 0;
+}
+
+#define PROC_NET_SOCK_PATH "unix_socket_proc_net_path"
+
+int test_proc_net_unix() {
+    struct sockaddr_un addr;
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, PROC_NET_SOCK_PATH);
+    socklen_t addr_len = strlen(addr.sun_path) + sizeof(addr.sun_family);
+
+    int listen_fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (listen_fd < 0 || bind(listen_fd, (struct sockaddr *)&addr, addr_len) < 0 ||
+            listen(listen_fd, 5) < 0) {
+        THROW_ERROR("failed to listen");
+    }
+
+    // The listening socket is listed with SO_ACCEPTCON and its path
+    char line[256];
+    FILE *fp = fopen("/proc/net/unix", "r");
+    if (fp == NULL) {
+        close(listen_fd);
+        THROW_ERROR("failed to open /proc/net/unix");
+    }
+    if (fgets(line, sizeof(line), fp) == NULL || strncmp(line, "Num", 3) != 0) {
+        fclose(fp);
+        close(listen_fd);
+        THROW_ERROR("invalid header of /proc/net/unix");
+    }
+    int found = 0;
+    while (fgets(line, sizeof(line), fp) != NULL) {
+        if (strstr(line, PROC_NET_SOCK_PATH) != NULL &&
+                strstr(line, " 00010000 ") != NULL) {
+            found = 1;
+        }
+    }
+    fclose(fp);
+    close(listen_fd);
+    if (!found) {
+        THROW_ERROR("the listening socket is not listed in /proc/net/unix");
+    }
+    return 0;
+}
+
+// The Occlum-specific socket option to get the traffic statistics of a socket
+#define SOL_OCCLUM 0x4f43
+#define SO_OCCLUM_STATS 1
+
+struct occlum_sock_stats {
+    uint64_t rx_bytes;
+    uint64_t rx_packets;
+    uint64_t tx_bytes;
+    uint64_t tx_packets;
+    uint64_t lifetime_ns;
+};
+
+int test_socket_stats() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    char buf[sizeof(ECHO_MSG) * 2];
+    if (write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            read(socks[1], buf, sizeof(buf)) <= 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to transfer data");
+    }
+
+    struct occlum_sock_stats stats;
+    socklen_t len = sizeof(stats);
+    if (getsockopt(socks[0], SOL_OCCLUM, SO_OCCLUM_STATS, &stats, &len) < 0 ||
+            len != sizeof(stats)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to get the statistics of the sender");
+    }
+    if (stats.tx_bytes != sizeof(ECHO_MSG) * 2 || stats.tx_packets != 2 ||
+            stats.rx_bytes != 0 || stats.rx_packets != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong statistics of the sender");
+    }
+    len = sizeof(stats);
+    if (getsockopt(socks[1], SOL_OCCLUM, SO_OCCLUM_STATS, &stats, &len) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to get the statistics of the receiver");
+    }
+    if (stats.rx_bytes == 0 || stats.rx_packets != 1 || stats.tx_bytes != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong statistics of the receiver");
+    }
+
+    // The buffer must be large enough for the statistics
+    len = sizeof(stats) - 1;
+    int ret = getsockopt(socks[0], SOL_OCCLUM, SO_OCCLUM_STATS, &stats, &len);
+    if (ret == 0 || errno != EINVAL) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("getsockopt with a small buffer should fail with EINVAL");
+    }
+
+    // The sockets are listed in /proc/net/occlum_sockets
+    char line[256];
+    char expected[64];
+    snprintf(expected, sizeof(expected), "%d %d ", getpid(), socks[0]);
+    FILE *fp = fopen("/proc/net/occlum_sockets", "r");
+    if (fp == NULL) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to open /proc/net/occlum_sockets");
+    }
+    int found = 0;
+    while (fgets(line, sizeof(line), fp) != NULL) {
+        if (strncmp(line, expected, strlen(expected)) == 0) {
+            found = 1;
+        }
+    }
+    fclose(fp);
+    close_files(2, socks[0], socks[1]);
+    if (!found) {
+        THROW_ERROR("the socket is not listed in /proc/net/occlum_sockets");
+    }
+    return 0;
+}
+
+#define SCM_RIGHTS_SOCK_FD 11
+
+int test_sockopts() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+
+    // The send buffer is resized even if the socket is connected
+    int bufsize = 8192;
+    int val = 0;
+    socklen_t len = sizeof(val);
+    if (setsockopt(socks[0], SOL_SOCKET, SO_SNDBUF, &bufsize, sizeof(bufsize)) < 0 ||
+            setsockopt(socks[0], SOL_SOCKET, SO_RCVBUF, &bufsize, sizeof(bufsize)) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to set the buffer sizes");
+    }
+    if (getsockopt(socks[0], SOL_SOCKET, SO_SNDBUF, &val, &len) < 0 ||
+            len != sizeof(val) || val < bufsize) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_SNDBUF");
+    }
+    len = sizeof(val);
+    if (getsockopt(socks[0], SOL_SOCKET, SO_RCVBUF, &val, &len) < 0 ||
+            len != sizeof(val) || val < bufsize) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_RCVBUF");
+    }
+    char buf[sizeof(ECHO_MSG)];
+    if (write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            read(socks[1], buf, sizeof(buf)) != sizeof(ECHO_MSG) ||
+            strcmp(buf, ECHO_MSG) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to transfer data after resizing the buffer");
+    }
+
+    int on = 1;
+    len = sizeof(val);
+    if (setsockopt(socks[1], SOL_SOCKET, SO_PASSCRED, &on, sizeof(on)) < 0 ||
+            getsockopt(socks[1], SOL_SOCKET, SO_PASSCRED, &val, &len) < 0 || val != 1) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_PASSCRED");
+    }
+
+    // The peer of both sockets of a socketpair is the process that creates them
+    for (int i = 0; i < 2; i++) {
+        struct ucred cred;
+        len = sizeof(cred);
+        if (getsockopt(socks[i], SOL_SOCKET, SO_PEERCRED, &cred, &len) < 0 ||
+                len != sizeof(cred)) {
+            close_files(2, socks[0], socks[1]);
+            THROW_ERROR("failed to get SO_PEERCRED");
+        }
+        if (cred.pid != getpid() || cred.uid != getuid() || cred.gid != getgid()) {
+            close_files(2, socks[0], socks[1]);
+            THROW_ERROR("wrong SO_PEERCRED");
+        }
+    }
+
+    len = sizeof(val);
+    if (getsockopt(socks[0], SOL_SOCKET, SO_TYPE, &val, &len) < 0 || val != SOCK_STREAM) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_TYPE");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
+static int send_fd(int sock, int fd) {
+    char data = 'x';
+    struct iovec iov = { .iov_base = &data, .iov_len = sizeof(data) };
+    char control[CMSG_SPACE(sizeof(int))];
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = sizeof(control),
+    };
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    cmsg->cmsg_level = SOL_SOCKET;
+    cmsg->cmsg_type = SCM_RIGHTS;
+    cmsg->cmsg_len = CMSG_LEN(sizeof(int));
+    memcpy(CMSG_DATA(cmsg), &fd, sizeof(int));
+    return sendmsg(sock, &msg, 0) == sizeof(data) ? 0 : -1;
+}
+
+// Receive a fd, or return -1 with errno set
+static int recv_fd(int sock, size_t control_len, int *msg_flags) {
+    char data;
+    struct iovec iov = { .iov_base = &data, .iov_len = sizeof(data) };
+    char control[CMSG_SPACE(sizeof(int))];
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = control_len,
+    };
+    if (recvmsg(sock, &msg, 0) != sizeof(data)) {
+        return -1;
+    }
+    *msg_flags = msg.msg_flags;
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    if (cmsg == NULL || cmsg->cmsg_level != SOL_SOCKET || cmsg->cmsg_type != SCM_RIGHTS ||
+            cmsg->cmsg_len != CMSG_LEN(sizeof(int))) {
+        errno = ENOMSG;
+        return -1;
+    }
+    int fd;
+    memcpy(&fd, CMSG_DATA(cmsg), sizeof(int));
+    return fd;
+}
+
+// The receiver gets the read end of a pipe from the socket, and echoes the
+// message read from the pipe back through the socket
+int run_scm_rights_receiver() {
+    int msg_flags;
+    int pipe_fd = recv_fd(SCM_RIGHTS_SOCK_FD, CMSG_SPACE(sizeof(int)), &msg_flags);
+    if (pipe_fd < 0) {
+        THROW_ERROR("failed to receive the fd");
+    }
+    char buf[sizeof(ECHO_MSG)];
+    if (read(pipe_fd, buf, sizeof(buf)) != sizeof(buf) ||
+            write(SCM_RIGHTS_SOCK_FD, buf, sizeof(buf)) != sizeof(buf)) {
+        close(pipe_fd);
+        THROW_ERROR("failed to echo the message from the pipe");
+    }
+    close(pipe_fd);
+    return 0;
+}
+
+int test_scm_rights_inter_process() {
+    int socks[2], pipe_fds[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    if (pipe(pipe_fds) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to create a pipe");
+    }
+
+    int child_pid;
+    const char *child_argv[] = { "/bin/unix_socket", "scm_rights_receiver", NULL };
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, socks[1], SCM_RIGHTS_SOCK_FD);
+    posix_spawn_file_actions_addclose(&file_actions, pipe_fds[0]);
+    posix_spawn_file_actions_addclose(&file_actions, pipe_fds[1]);
+    int ret = posix_spawn(&child_pid, child_argv[0], &file_actions, NULL,
+                          (char *const *)child_argv, NULL);
+    posix_spawn_file_actions_destroy(&file_actions);
+    if (ret != 0) {
+        close_files(4, socks[0], socks[1], pipe_fds[0], pipe_fds[1]);
+        THROW_ERROR("failed to spawn a child process");
+    }
+
+    // The child can only read the pipe with the fd passed through the socket
+    char buf[sizeof(ECHO_MSG)] = {0};
+    if (send_fd(socks[0], pipe_fds[0]) < 0) {
+        close_files(4, socks[0], socks[1], pipe_fds[0], pipe_fds[1]);
+        THROW_ERROR("failed to send the fd");
+    }
+    close(pipe_fds[0]);
+    if (write(pipe_fds[1], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            read(socks[0], buf, sizeof(buf)) != sizeof(buf)) {
+        close_files(3, socks[0], socks[1], pipe_fds[1]);
+        THROW_ERROR("failed to transfer the message");
+    }
+    close_files(3, socks[0], socks[1], pipe_fds[1]);
+
+    int status = 0;
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+        THROW_ERROR("the child failed to receive the fd");
+    }
+    if (strcmp(buf, ECHO_MSG) != 0) {
+        THROW_ERROR("the message echoed is not as expected");
+    }
+    return 0;
+}
+
+int test_scm_rights_truncated() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    if (send_fd(socks[0], STDOUT_FILENO) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to send the fd");
+    }
+    // The fd is discarded if the control buffer is too small
+    int msg_flags;
+    int fd = recv_fd(socks[1], sizeof(struct cmsghdr), &msg_flags);
+    close_files(2, socks[0], socks[1]);
+    if (fd >= 0 || errno != ENOMSG || !(msg_flags & MSG_CTRUNC)) {
+        THROW_ERROR("the control message should be truncated");
+    }
+    return 0;
+}
+
+#define SCM_CREDENTIALS_SOCK_FD 12
+
+static int send_cred(int sock, pid_t pid) {
+    char data = 'x';
+    struct iovec iov = { .iov_base = &data, .iov_len = sizeof(data) };
+    char control[CMSG_SPACE(sizeof(struct ucred))];
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = sizeof(control),
+    };
+    struct ucred cred = { .pid = pid, .uid = getuid(), .gid = getgid() };
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    cmsg->cmsg_level = SOL_SOCKET;
+    cmsg->cmsg_type = SCM_CREDENTIALS;
+    cmsg->cmsg_len = CMSG_LEN(sizeof(cred));
+    memcpy(CMSG_DATA(cmsg), &cred, sizeof(cred));
+    return sendmsg(sock, &msg, 0) == sizeof(data) ? 0 : -1;
+}
+
+// Receive the data along with the credentials of the sender, or return -1 with
+// errno set
+static ssize_t recv_cred(int sock, char *buf, size_t len, struct ucred *cred) {
+    struct iovec iov = { .iov_base = buf, .iov_len = len };
+    char control[CMSG_SPACE(sizeof(struct ucred))];
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = sizeof(control),
+    };
+    ssize_t ret = recvmsg(sock, &msg, 0);
+    if (ret < 0) {
+        return -1;
+    }
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    if (cmsg == NULL || cmsg->cmsg_level != SOL_SOCKET ||
+            cmsg->cmsg_type != SCM_CREDENTIALS ||
+            cmsg->cmsg_len != CMSG_LEN(sizeof(struct ucred))) {
+        errno = ENOMSG;
+        return -1;
+    }
+    memcpy(cred, CMSG_DATA(cmsg), sizeof(struct ucred));
+    return ret;
+}
+
+int run_scm_credentials_sender() {
+    if (write(SCM_CREDENTIALS_SOCK_FD, ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG)) {
+        THROW_ERROR("failed to send the message");
+    }
+    return 0;
+}
+
+int test_scm_credentials() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int on = 1;
+    if (setsockopt(socks[0], SOL_SOCKET, SO_PASSCRED, &on, sizeof(on)) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to set SO_PASSCRED");
+    }
+    // The credentials of other processes cannot be forged
+    if (send_cred(socks[1], getpid() + 1) == 0 || errno != EPERM) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the forged credentials should be rejected");
+    }
+    if (send_cred(socks[1], getpid()) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to send the credentials");
+    }
+
+    int child_pid;
+    const char *child_argv[] = { "/bin/unix_socket", "scm_credentials_sender", NULL };
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, socks[1], SCM_CREDENTIALS_SOCK_FD);
+    int ret = posix_spawn(&child_pid, child_argv[0], &file_actions, NULL,
+                          (char *const *)child_argv, NULL);
+    posix_spawn_file_actions_destroy(&file_actions);
+    if (ret != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to spawn a child process");
+    }
+    int status = 0;
+    if (wait4(child_pid, &status, 0, NULL) < 0 || !WIFEXITED(status) ||
+            WEXITSTATUS(status) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the child failed to send the message");
+    }
+
+    // The data of different senders is never received at once
+    char buf[sizeof(ECHO_MSG) + 1] = {0};
+    struct ucred cred;
+    if (recv_cred(socks[0], buf, sizeof(buf), &cred) != 1 || cred.pid != getpid() ||
+            cred.uid != getuid() || cred.gid != getgid()) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong credentials of the current process");
+    }
+    if (recv_cred(socks[0], buf, sizeof(buf), &cred) != sizeof(ECHO_MSG) ||
+            cred.pid != child_pid || strcmp(buf, ECHO_MSG) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong credentials of the child process");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
+// The child checks that the sockets created with SOCK_CLOEXEC are not inherited
+int run_cloexec_checker(const char *fd_str) {
+    int fd = atoi(fd_str);
+    if (fcntl(fd, F_GETFD) >= 0 || errno != EBADF) {
+        THROW_ERROR("the socket with SOCK_CLOEXEC should be closed on spawn");
+    }
+    return 0;
+}
+
+int test_socket_flags() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK | SOCK_CLOEXEC, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    for (int i = 0; i < 2; i++) {
+        if (!(fcntl(socks[i], F_GETFD) & FD_CLOEXEC) ||
+                !(fcntl(socks[i], F_GETFL) & O_NONBLOCK)) {
+            close_files(2, socks[0], socks[1]);
+            THROW_ERROR("the flags of the socket are not set");
+        }
+    }
+    char buf[sizeof(ECHO_MSG)];
+    if (read(socks[0], buf, sizeof(buf)) >= 0 || errno != EAGAIN) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("read from an empty non-blocking socket should fail with EAGAIN");
+    }
+
+    int child_pid;
+    char fd_str[16];
+    snprintf(fd_str, sizeof(fd_str), "%d", socks[1]);
+    const char *child_argv[] = { "/bin/unix_socket", "cloexec_checker", fd_str, NULL };
+    int ret = posix_spawn(&child_pid, child_argv[0], NULL, NULL,
+                          (char *const *)child_argv, NULL);
+    close_files(2, socks[0], socks[1]);
+    if (ret != 0) {
+        THROW_ERROR("failed to spawn a child process");
+    }
+    int status = 0;
+    if (wait4(child_pid, &status, 0, NULL) < 0) {
+        THROW_ERROR("failed to wait4 the child process");
+    }
+    if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+        THROW_ERROR("the socket is inherited by the child");
+    }
+
+    // The status flags can be changed by fcntl
+    int sock = socket(AF_UNIX, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    if (fcntl(sock, F_GETFD) & FD_CLOEXEC || fcntl(sock, F_SETFL, 0) < 0 ||
+            fcntl(sock, F_GETFL) & O_NONBLOCK) {
+        close(sock);
+        THROW_ERROR("failed to clear O_NONBLOCK");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_epoll() {
+    int ret = 0;
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        close(socks[0]);
+        close(socks[1]);
+        THROW_ERROR("pipe failed");
+    }
+    int epfd = epoll_create1(0);
+    if (epfd < 0) {
+        ret = -1;
+        printf("failed to create the epoll file\n");
+        goto out;
+    }
+
+    struct epoll_event event = { .events = EPOLLIN, .data.u64 = 1 };
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, socks[1], &event) < 0) {
+        ret = -1;
+        printf("failed to add the socket to epoll\n");
+        goto out;
+    }
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, socks[1], &event) == 0 || errno != EEXIST) {
+        ret = -1;
+        printf("adding the socket twice should fail with EEXIST\n");
+        goto out;
+    }
+    event.data.u64 = 2;
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, pipe_fds[0], &event) < 0) {
+        ret = -1;
+        printf("failed to add the pipe to epoll\n");
+        goto out;
+    }
+
+    struct epoll_event events[2];
+    if (epoll_wait(epfd, events, 2, 0) != 0) {
+        ret = -1;
+        printf("no file should be ready\n");
+        goto out;
+    }
+
+    // The child process writes to the socket while the parent is waiting
+    const char *child_prog = "/bin/hello_world";
+    const char *child_argv[3] = { child_prog, ECHO_MSG, NULL };
+    int child_pid;
+    posix_spawn_file_actions_t file_actions;
+    posix_spawn_file_actions_init(&file_actions);
+    posix_spawn_file_actions_adddup2(&file_actions, socks[0], STDOUT_FILENO);
+    if (posix_spawn(&child_pid, child_prog, &file_actions,
+                    NULL, (char *const *)child_argv, NULL) < 0) {
+        ret = -1;
+        printf("failed to spawn a child process\n");
+        goto out;
+    }
+    int nfds = epoll_wait(epfd, events, 2, 5000);
+    wait4(child_pid, NULL, 0, NULL);
+    if (nfds != 1 || events[0].data.u64 != 1 || !(events[0].events & EPOLLIN)) {
+        ret = -1;
+        printf("the socket should be readable\n");
+        goto out;
+    }
+
+    char buf[sizeof(ECHO_MSG)] = {0};
+    if (read(socks[1], buf, sizeof(buf)) <= 0 || epoll_wait(epfd, events, 2, 0) != 0) {
+        ret = -1;
+        printf("the socket should not be readable after read\n");
+        goto out;
+    }
+
+    // The closed pipe is removed from the interest list
+    close(pipe_fds[1]);
+    pipe_fds[1] = -1;
+    nfds = epoll_wait(epfd, events, 2, 0);
+    if (nfds != 1 || events[0].data.u64 != 2 || !(events[0].events & EPOLLHUP)) {
+        ret = -1;
+        printf("the pipe should be hung up\n");
+        goto out;
+    }
+    close(pipe_fds[0]);
+    pipe_fds[0] = -1;
+    if (epoll_wait(epfd, events, 2, 0) != 0) {
+        ret = -1;
+        printf("the closed pipe should not be reported\n");
+        goto out;
+    }
+
+out:
+    if (epfd >= 0) {
+        close(epfd);
+    }
+    close_fds(pipe_fds, 2);
+    close(socks[0]);
+    close(socks[1]);
+    return ret;
+}
+
+static int epoll_wait_nonblocking(int epfd) {
+    struct epoll_event events[2];
+    return epoll_wait(epfd, events, 2, 0);
+}
+
+int test_epoll_edge_triggered_and_oneshot() {
+    int ret = 0;
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int pipe_fds[2];
+    if (pipe(pipe_fds) < 0) {
+        close(socks[0]);
+        close(socks[1]);
+        THROW_ERROR("pipe failed");
+    }
+    int epfd = epoll_create1(0);
+    struct epoll_event event = { .events = EPOLLIN | EPOLLET };
+    if (epfd < 0 || epoll_ctl(epfd, EPOLL_CTL_ADD, socks[1], &event) < 0) {
+        ret = -1;
+        printf("failed to add the socket to epoll\n");
+        goto out;
+    }
+
+    // The edge-triggered socket is reported once for each write
+    write(socks[0], ECHO_MSG, sizeof(ECHO_MSG));
+    if (epoll_wait_nonblocking(epfd) != 1 || epoll_wait_nonblocking(epfd) != 0) {
+        ret = -1;
+        printf("the socket should be reported once\n");
+        goto out;
+    }
+    write(socks[0], ECHO_MSG, sizeof(ECHO_MSG));
+    if (epoll_wait_nonblocking(epfd) != 1) {
+        ret = -1;
+        printf("the socket should be reported again after the write\n");
+        goto out;
+    }
+    if (epoll_ctl(epfd, EPOLL_CTL_DEL, socks[1], NULL) < 0) {
+        ret = -1;
+        printf("failed to delete the socket from epoll\n");
+        goto out;
+    }
+
+    // The oneshot pipe is disabled after reported until re-armed
+    event.events = EPOLLIN | EPOLLONESHOT;
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, pipe_fds[0], &event) < 0) {
+        ret = -1;
+        printf("failed to add the pipe to epoll\n");
+        goto out;
+    }
+    write(pipe_fds[1], ECHO_MSG, sizeof(ECHO_MSG));
+    if (epoll_wait_nonblocking(epfd) != 1 || epoll_wait_nonblocking(epfd) != 0) {
+        ret = -1;
+        printf("the pipe should be reported once\n");
+        goto out;
+    }
+    write(pipe_fds[1], ECHO_MSG, sizeof(ECHO_MSG));
+    if (epoll_wait_nonblocking(epfd) != 0) {
+        ret = -1;
+        printf("the pipe should be disabled\n");
+        goto out;
+    }
+    if (epoll_ctl(epfd, EPOLL_CTL_MOD, pipe_fds[0], &event) < 0 ||
+            epoll_wait_nonblocking(epfd) != 1) {
+        ret = -1;
+        printf("the pipe should be reported after re-armed\n");
+        goto out;
+    }
+
+out:
+    if (epfd >= 0) {
+        close(epfd);
+    }
+    close_fds(pipe_fds, 2);
+    close(socks[0]);
+    close(socks[1]);
+    return ret;
+}
+
+int test_getpeername() {
+    int socks[2];
+    struct sockaddr_un addr;
+    socklen_t addr_len;
+    const char *sock_path = "unix_socket_getpeername_path";
+    if (create_connected_sockets(socks, (char *)sock_path) < 0) {
+        THROW_ERROR("failed to create connected sockets");
+    }
+    // The peer of the client is the path bound by the server
+    addr_len = sizeof(addr);
+    memset(&addr, 0, sizeof(addr));
+    if (getpeername(socks[0], (struct sockaddr *)&addr, &addr_len) < 0 ||
+            addr.sun_family != AF_UNIX || strcmp(addr.sun_path, sock_path) != 0 ||
+            addr_len != sizeof(addr.sun_family) + strlen(sock_path) + 1) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the peer of the client is not the bound path");
+    }
+    // The peer of the accepted socket is unnamed
+    addr_len = sizeof(addr);
+    if (getpeername(socks[1], (struct sockaddr *)&addr, &addr_len) < 0 ||
+            addr.sun_family != AF_UNIX || addr_len != sizeof(addr.sun_family)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the peer of the accepted socket should be unnamed");
+    }
+    close_files(2, socks[0], socks[1]);
+
+    int sock = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    addr_len = sizeof(addr);
+    if (getpeername(sock, (struct sockaddr *)&addr, &addr_len) == 0 ||
+            errno != ENOTCONN) {
+        close(sock);
+        THROW_ERROR("getpeername on an unconnected socket should fail with ENOTCONN");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_getsockname() {
+    struct sockaddr_un addr;
+    socklen_t addr_len;
+    const char *sock_path = "unix_socket_getsockname_path";
+    int sock = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    // The socket is unnamed before bound
+    addr_len = sizeof(addr);
+    if (getsockname(sock, (struct sockaddr *)&addr, &addr_len) < 0 ||
+            addr.sun_family != AF_UNIX || addr_len != sizeof(addr.sun_family)) {
+        close(sock);
+        THROW_ERROR("the unbound socket should be unnamed");
+    }
+
+    memset(&addr, 0, sizeof(addr));
+    addr.sun_family = AF_UNIX;
+    strcpy(addr.sun_path, sock_path);
+    if (bind(sock, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close(sock);
+        THROW_ERROR("failed to bind");
+    }
+    addr_len = sizeof(addr);
+    memset(&addr, 0, sizeof(addr));
+    if (getsockname(sock, (struct sockaddr *)&addr, &addr_len) < 0 ||
+            addr.sun_family != AF_UNIX || strcmp(addr.sun_path, sock_path) != 0 ||
+            addr_len != sizeof(addr.sun_family) + strlen(sock_path) + 1) {
+        close(sock);
+        THROW_ERROR("the name of the socket is not the bound path");
+    }
+    close(sock);
+    return 0;
+}
+
+int test_recv_flags() {
+    int socks[2];
+    char buf[32];
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    if (recv(socks[1], buf, sizeof(buf), MSG_DONTWAIT) != -1 || errno != EAGAIN) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("recv with MSG_DONTWAIT should fail with EAGAIN");
+    }
+    if (write(socks[0], "abc", 3) != 3 || write(socks[0], "def", 3) != 3) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    // The data peeked is still there to be received
+    for (int i = 0; i < 2; i++) {
+        memset(buf, 0, sizeof(buf));
+        if (recv(socks[1], buf, 2, MSG_PEEK) != 2 || strcmp(buf, "ab") != 0) {
+            close_files(2, socks[0], socks[1]);
+            THROW_ERROR("failed to peek the data");
+        }
+    }
+    // MSG_WAITALL returns the data received before the peer is closed
+    close(socks[0]);
+    memset(buf, 0, sizeof(buf));
+    if (recv(socks[1], buf, sizeof(buf), MSG_WAITALL) != 6 ||
+            strcmp(buf, "abcdef") != 0) {
+        close(socks[1]);
+        THROW_ERROR("failed to receive all of the data");
+    }
+    if (recv(socks[1], buf, sizeof(buf), MSG_WAITALL) != 0) {
+        close(socks[1]);
+        THROW_ERROR("recv should return 0 after the peer is closed");
+    }
+    close(socks[1]);
+    return 0;
+}
+
+static long elapsed_ms(const struct timespec *start) {
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    return (now.tv_sec - start->tv_sec) * 1000 + (now.tv_nsec - start->tv_nsec) / 1000000;
+}
+
+int test_socket_timeouts() {
+    int socks[2];
+    char buf[4096] = {0};
+    if (socketpair(AF_UNIX, SOCK_STREAM, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    struct timeval timeout = { .tv_sec = 0, .tv_usec = 200 * 1000 };
+    socklen_t len = sizeof(timeout);
+    if (setsockopt(socks[1], SOL_SOCKET, SO_RCVTIMEO, &timeout, len) < 0 ||
+            setsockopt(socks[0], SOL_SOCKET, SO_SNDTIMEO, &timeout, len) < 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to set the timeouts");
+    }
+    struct timeval got = {0};
+    socklen_t optlen = sizeof(got);
+    if (getsockopt(socks[1], SOL_SOCKET, SO_RCVTIMEO, &got, &optlen) < 0 ||
+            optlen != sizeof(got) || got.tv_sec != 0 || got.tv_usec != 200 * 1000) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to get SO_RCVTIMEO");
+    }
+    struct timeval invalid = { .tv_sec = 1, .tv_usec = 1000000 };
+    if (setsockopt(socks[1], SOL_SOCKET, SO_RCVTIMEO, &invalid, sizeof(invalid)) != -1 ||
+            errno != EDOM) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("setting an invalid timeout should fail with EDOM");
+    }
+
+    // A blocking receive fails with EAGAIN once the timeout expires
+    struct timespec start;
+    clock_gettime(CLOCK_MONOTONIC, &start);
+    if (recv(socks[1], buf, sizeof(buf), 0) != -1 || errno != EAGAIN) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("recv should fail with EAGAIN on timeout");
+    }
+    if (elapsed_ms(&start) < 150) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("recv returns before the timeout expires");
+    }
+
+    // A blocking send fails with EAGAIN once the buffer stays full until the timeout
+    ssize_t ret;
+    while ((ret = write(socks[0], buf, sizeof(buf))) > 0) {
+        clock_gettime(CLOCK_MONOTONIC, &start);
+    }
+    if (ret != -1 || errno != EAGAIN || elapsed_ms(&start) < 150) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("write should fail with EAGAIN on timeout");
+    }
+
+    // A zero timeout is infinite
+    struct timeval zero = {0};
+    optlen = sizeof(got);
+    if (setsockopt(socks[1], SOL_SOCKET, SO_RCVTIMEO, &zero, sizeof(zero)) < 0 ||
+            getsockopt(socks[1], SOL_SOCKET, SO_RCVTIMEO, &got, &optlen) < 0 ||
+            got.tv_sec != 0 || got.tv_usec != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to clear SO_RCVTIMEO");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
+int test_seqpacket() {
+    int socks[2];
+    char buf[32];
+    if (socketpair(AF_UNIX, SOCK_SEQPACKET, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int type = 0;
+    socklen_t optlen = sizeof(type);
+    if (getsockopt(socks[0], SOL_SOCKET, SO_TYPE, &type, &optlen) < 0 ||
+            type != SOCK_SEQPACKET) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the type of the socket is not SOCK_SEQPACKET");
+    }
+    // The boundaries of the messages are kept
+    if (write(socks[0], "abc", 3) != 3 || write(socks[0], "defgh", 5) != 5 ||
+            write(socks[0], "ijklmn", 6) != 6) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    memset(buf, 0, sizeof(buf));
+    if (recv(socks[1], buf, sizeof(buf), MSG_WAITALL) != 3 || strcmp(buf, "abc") != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to receive the first message");
+    }
+    // The message is truncated to the buffer, and the rest is discarded
+    struct iovec iov = { .iov_base = buf, .iov_len = 2 };
+    struct msghdr msg = { .msg_iov = &iov, .msg_iovlen = 1 };
+    memset(buf, 0, sizeof(buf));
+    if (recvmsg(socks[1], &msg, 0) != 2 || strcmp(buf, "de") != 0 ||
+            !(msg.msg_flags & MSG_TRUNC)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the second message is not truncated");
+    }
+    // The real length is returned with MSG_TRUNC
+    if (recv(socks[1], buf, 2, MSG_TRUNC) != 6) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the real length of the third message is not returned");
+    }
+    close(socks[0]);
+    if (recv(socks[1], buf, sizeof(buf), 0) != 0) {
+        close(socks[1]);
+        THROW_ERROR("recv should return 0 after the peer is closed");
+    }
+    close(socks[1]);
+    return 0;
+}
+
+// Receive a message along with its timestamp of the type, whose time is returned in ns
+static ssize_t recv_timestamp(int sock, int type, int64_t *time_ns, int *msg_flags,
+                              size_t control_len) {
+    char buf[32];
+    char control[CMSG_SPACE(sizeof(struct timespec))] = {0};
+    struct iovec iov = { .iov_base = buf, .iov_len = sizeof(buf) };
+    struct msghdr msg = {
+        .msg_iov = &iov,
+        .msg_iovlen = 1,
+        .msg_control = control,
+        .msg_controllen = control_len,
+    };
+    ssize_t ret = recvmsg(sock, &msg, 0);
+    if (ret < 0) {
+        return -1;
+    }
+    *msg_flags = msg.msg_flags;
+    *time_ns = -1;
+    struct cmsghdr *cmsg = CMSG_FIRSTHDR(&msg);
+    if (cmsg == NULL || cmsg->cmsg_level != SOL_SOCKET || cmsg->cmsg_type != type) {
+        return ret;
+    }
+    if (type == SCM_TIMESTAMP) {
+        struct timeval tv;
+        memcpy(&tv, CMSG_DATA(cmsg), sizeof(tv));
+        *time_ns = tv.tv_sec * 1000000000L + tv.tv_usec * 1000L;
+    } else {
+        struct timespec ts;
+        memcpy(&ts, CMSG_DATA(cmsg), sizeof(ts));
+        *time_ns = ts.tv_sec * 1000000000L + ts.tv_nsec;
+    }
+    return ret;
+}
+
+static int64_t realtime_ns(void) {
+    struct timespec ts;
+    clock_gettime(CLOCK_REALTIME, &ts);
+    return ts.tv_sec * 1000000000L + ts.tv_nsec;
+}
+
+int test_so_timestamp() {
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_SEQPACKET, 0, socks) < 0) {
+        THROW_ERROR("socketpair failed");
+    }
+    int on = 1, val = 0;
+    socklen_t len = sizeof(val);
+    if (setsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMPNS, &on, sizeof(on)) < 0 ||
+            getsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMPNS, &val, &len) < 0 ||
+            val != 1 ||
+            getsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMP, &val, &len) < 0 || val != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_TIMESTAMPNS");
+    }
+
+    // The message is stamped when it is sent
+    int64_t start_ns = realtime_ns();
+    if (write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    int64_t end_ns = realtime_ns();
+    int64_t time_ns;
+    int msg_flags;
+    size_t control_len = CMSG_SPACE(sizeof(struct timespec));
+    if (recv_timestamp(socks[1], SCM_TIMESTAMPNS, &time_ns, &msg_flags,
+                       control_len) < 0 ||
+            time_ns < start_ns || time_ns > end_ns || (msg_flags & MSG_CTRUNC)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SCM_TIMESTAMPNS");
+    }
+
+    if (setsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMP, &on, sizeof(on)) < 0 ||
+            getsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMP, &val, &len) < 0 || val != 1) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SO_TIMESTAMP");
+    }
+    start_ns = realtime_ns() / 1000 * 1000;
+    if (write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to write");
+    }
+    end_ns = realtime_ns();
+    control_len = CMSG_SPACE(sizeof(struct timeval));
+    if (recv_timestamp(socks[1], SCM_TIMESTAMP, &time_ns, &msg_flags, control_len) < 0 ||
+            time_ns < start_ns || time_ns > end_ns || (msg_flags & MSG_CTRUNC)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("wrong SCM_TIMESTAMP");
+    }
+
+    // The timestamp that does not fit in the control buffer is truncated
+    if (write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            recv_timestamp(socks[1], SCM_TIMESTAMP, &time_ns, &msg_flags,
+                           sizeof(struct cmsghdr) - 1) < 0 ||
+            time_ns != -1 || !(msg_flags & MSG_CTRUNC)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the timestamp should be truncated");
+    }
+
+    // No timestamp is received after the timestamps are disabled
+    int off = 0;
+    if (setsockopt(socks[1], SOL_SOCKET, SO_TIMESTAMP, &off, sizeof(off)) < 0 ||
+            write(socks[0], ECHO_MSG, sizeof(ECHO_MSG)) != sizeof(ECHO_MSG) ||
+            recv_timestamp(socks[1], SCM_TIMESTAMP, &time_ns, &msg_flags,
+                           control_len) < 0 ||
+            time_ns != -1 || (msg_flags & MSG_CTRUNC)) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the timestamp should be disabled");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
+int test_seqpacket_connect() {
+    const char *path = "seqpacket_test_path";
+    int listen_fd = socket(AF_UNIX, SOCK_SEQPACKET, 0);
+    if (listen_fd < 0) {
+        THROW_ERROR("failed to create a seqpacket socket");
+    }
+    struct sockaddr_un addr = { .sun_family = AF_UNIX };
+    strcpy(addr.sun_path, path);
+    if (bind(listen_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0 ||
+            listen(listen_fd, 5) < 0) {
+        close(listen_fd);
+        THROW_ERROR("failed to listen");
+    }
+    // A stream socket cannot connect to a seqpacket one
+    int stream_fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    int ret = connect(stream_fd, (struct sockaddr *)&addr, sizeof(addr));
+    close(stream_fd);
+    if (ret != -1 || errno != EPROTOTYPE) {
+        close(listen_fd);
+        THROW_ERROR("connect of a stream socket should fail with EPROTOTYPE");
+    }
+
+    int client_fd = socket(AF_UNIX, SOCK_SEQPACKET, 0);
+    if (connect(client_fd, (struct sockaddr *)&addr, sizeof(addr)) < 0) {
+        close_files(2, listen_fd, client_fd);
+        THROW_ERROR("failed to connect");
+    }
+    int accepted_fd = accept(listen_fd, NULL, NULL);
+    close(listen_fd);
+    if (accepted_fd < 0) {
+        close(client_fd);
+        THROW_ERROR("failed to accept");
+    }
+    char buf[32] = {0};
+    if (send(accepted_fd, "hello", 5, 0) != 5 || send(accepted_fd, "", 0, 0) != 0 ||
+            send(accepted_fd, "world", 5, 0) != 5) {
+        close_files(2, client_fd, accepted_fd);
+        THROW_ERROR("failed to send");
+    }
+    // The empty message is received as is
+    if (recv(client_fd, buf, sizeof(buf), 0) != 5 || strcmp(buf, "hello") != 0 ||
+            recv(client_fd, buf, sizeof(buf), 0) != 0 ||
+            recv(client_fd, buf, sizeof(buf), 0) != 5) {
+        close_files(2, client_fd, accepted_fd);
+        THROW_ERROR("failed to receive the messages");
+    }
+    close_files(2, client_fd, accepted_fd);
+    return 0;
+}
+
+// Send "hello world" in two iovecs, with the address if it is given
+static ssize_t sendmsg_in_two_iovs(int fd, struct sockaddr_un *addr) {
+    struct iovec iovs[2] = {
+        { .iov_base = "hello ", .iov_len = 6 },
+        { .iov_base = "world", .iov_len = 5 },
+    };
+    struct msghdr msg = {
+        .msg_name = addr,
+        .msg_namelen = addr == NULL ? 0 : sizeof(*addr),
+        .msg_iov = iovs,
+        .msg_iovlen = 2,
+    };
+    return sendmsg(fd, &msg, 0);
+}
+
+// Receive "hello world" in two iovecs, and return the length of the address
+static int recvmsg_in_two_iovs(int fd, struct sockaddr_un *addr) {
+    char buf1[6] = {0};
+    char buf2[8] = {0};
+    struct iovec iovs[2] = {
+        { .iov_base = buf1, .iov_len = sizeof(buf1) },
+        { .iov_base = buf2, .iov_len = sizeof(buf2) },
+    };
+    memset(addr, 0, sizeof(*addr));
+    struct msghdr msg = {
+        .msg_name = addr,
+        .msg_namelen = sizeof(*addr),
+        .msg_iov = iovs,
+        .msg_iovlen = 2,
+    };
+    if (recvmsg(fd, &msg, 0) != 11 || memcmp(buf1, "hello ", 6) != 0 ||
+            strcmp(buf2, "world") != 0) {
+        return -1;
+    }
+    return msg.msg_namelen;
+}
+
+int test_msghdr_name() {
+    int socks[2];
+    struct sockaddr_un addr;
+    const char *sock_path = "unix_socket_msghdr_name_path";
+    if (create_connected_sockets(socks, (char *)sock_path) < 0) {
+        THROW_ERROR("failed to create connected sockets");
+    }
+    // The sender of the client is the path bound by the server
+    if (sendmsg_in_two_iovs(socks[1], NULL) != 11) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to send to the client");
+    }
+    int name_len = recvmsg_in_two_iovs(socks[0], &addr);
+    if (name_len != sizeof(addr.sun_family) + strlen(sock_path) + 1 ||
+            addr.sun_family != AF_UNIX || strcmp(addr.sun_path, sock_path) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the sender of the client is not the bound path");
+    }
+    // The sender of the accepted socket is unnamed, so no address is returned
+    if (sendmsg_in_two_iovs(socks[0], NULL) != 11 ||
+            recvmsg_in_two_iovs(socks[1], &addr) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the sender of the accepted socket should be unnamed");
+    }
+    // A stream socket rejects the address even if connected
+    struct sockaddr_un dest_addr = { .sun_family = AF_UNIX };
+    strcpy(dest_addr.sun_path, sock_path);
+    int ret = sendmsg_in_two_iovs(socks[0], &dest_addr);
+    close_files(2, socks[0], socks[1]);
+    if (ret != -1 || errno != EISCONN) {
+        THROW_ERROR("sendmsg with an address should fail with EISCONN");
+    }
+
+    // A seqpacket socket ignores the address
+    if (socketpair(AF_UNIX, SOCK_SEQPACKET, 0, socks) < 0) {
+        THROW_ERROR("failed to create a seqpacket socketpair");
+    }
+    if (sendmsg_in_two_iovs(socks[0], &dest_addr) != 11 ||
+            recvmsg_in_two_iovs(socks[1], &addr) != 0) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("failed to sendmsg with an address on a seqpacket socket");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
+// The path that is relayed to the host by Occlum.json
+#define RELAY_PATH "unix_relay_test_path"
+
+int test_relay_rejected() {
+    struct sockaddr_un addr = { .sun_family = AF_UNIX };
+    strcpy(addr.sun_path, RELAY_PATH);
+
+    // Only an end of a socketpair can be relayed to the host
+    int sock = socket(AF_UNIX, SOCK_STREAM, 0);
+    if (sock < 0) {
+        THROW_ERROR("failed to create a unix socket");
+    }
+    int ret = bind(sock, (struct sockaddr *)&addr, sizeof(addr));
+    close(sock);
+    if (ret != -1 || errno != EOPNOTSUPP) {
+        THROW_ERROR("bind of a socket to the relay should fail with EOPNOTSUPP");
+    }
+
+    // The host sockets of the relays are stream sockets
+    int socks[2];
+    if (socketpair(AF_UNIX, SOCK_SEQPACKET, 0, socks) < 0) {
+        THROW_ERROR("failed to create a seqpacket socketpair");
+    }
+    ret = bind(socks[0], (struct sockaddr *)&addr, sizeof(addr));
+    if (ret != -1 || errno != EPROTOTYPE) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("bind of a seqpacket socket should fail with EPROTOTYPE");
+    }
+    // The socketpair is still connected
+    char buf[8] = {0};
+    if (send(socks[0], "hello", 5, 0) != 5 || recv(socks[1], buf, sizeof(buf), 0) != 5) {
+        close_files(2, socks[0], socks[1]);
+        THROW_ERROR("the socketpair is not connected any more");
+    }
+    close_files(2, socks[0], socks[1]);
+    return 0;
+}
+
 static test_case_t test_cases[] = {
     TEST_CASE(test_unix_socket_inter_process),
     TEST_CASE(test_socketpair_inter_process),
     TEST_CASE(test_multiple_socketpairs),
     TEST_CASE(test_poll),
+    TEST_CASE(test_poll_mixed_files),
+    TEST_CASE(test_accept_shared_across_processes),
+    TEST_CASE(test_reuseport),
+    TEST_CASE(test_rebind),
+    TEST_CASE(test_proc_net_unix),
+    TEST_CASE(test_socket_stats),
+    TEST_CASE(test_sockopts),
+    TEST_CASE(test_scm_rights_inter_process),
+    TEST_CASE(test_scm_rights_truncated),
+    TEST_CASE(test_scm_credentials),
+    TEST_CASE(test_socket_flags),
+    TEST_CASE(test_getpeername),
+    TEST_CASE(test_getsockname),
+    TEST_CASE(test_msghdr_name),
+    TEST_CASE(test_epoll),
+    TEST_CASE(test_epoll_edge_triggered_and_oneshot),
+    TEST_CASE(test_recv_flags),
+    TEST_CASE(test_socket_timeouts),
+    TEST_CASE(test_seqpacket),
+    TEST_CASE(test_seqpacket_connect),
+    TEST_CASE(test_so_timestamp),
+    TEST_CASE(test_relay_rejected),
 };
 
 int main(int argc, const char *argv[]) {
+    if (argc == 2 && strcmp(argv[1], "acceptor") == 0) {
+        return run_acceptor();
+    }
+    if (argc == 2 && strcmp(argv[1], "reuseport_worker") == 0) {
+        return run_reuseport_worker();
+    }
+    if (argc == 2 && strcmp(argv[1], "scm_rights_receiver") == 0) {
+        return run_scm_rights_receiver();
+    }
+    if (argc == 2 && strcmp(argv[1], "scm_credentials_sender") == 0) {
+        return run_scm_credentials_sender();
+    }
+    if (argc == 3 && strcmp(argv[1], "cloexec_checker") == 0) {
+        return run_cloexec_checker(argv[2]);
+    }
     return test_suite_run(test_cases, ARRAY_SIZE(test_cases));
 }
@@ -43,6 +43,28 @@ define get_conf_default_mmap_size
 		python -c "import sys, json; print json.load(sys.stdin)['process']['default_mmap_size']" ['resource_limits']['user_space_size']
 endef
 
+define get_conf_mem_pressure_threshold
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.load(sys.stdin)['process'].get('mem_pressure_threshold', '0B')"
+endef
+
+define get_conf_exec_allowlist
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin)['process'].get('exec_allowlist'))"
+endef
+
+define get_conf_syscall_denylist
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin)['process'].get('syscall_denylist', []))"
+endef
+
+# The mount points other than the root, whose layers are generated from the image,
+# separated by commas. A SEFS without a source is put under run/mount.
+define get_conf_mount
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; mounts = [m for m in json.load(sys.stdin)['mount'] if m['target'] != '/']; [m.setdefault('source', './run/mount' + m['target']) for m in mounts if m['type'] == 'sefs']; print ', '.join(json.dumps(m) for m in mounts)"
+endef
+
 define get_conf_user_space_size
 	cat "$(JSON_CONF)" | \
 		python -c "import sys, json; print json.load(sys.stdin)['resource_limits']['user_space_size']"
@@ -58,6 +80,31 @@ define get_conf_entry_points
 		python -c "import sys, json; print json.dumps(json.load(sys.stdin)['entry_points'])"
 endef
 
+define get_conf_network
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin).get('network', {}))"
+endef
+
+define get_conf_devices
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin).get('devices', {}))"
+endef
+
+define get_conf_watchdog
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin).get('watchdog', {}))"
+endef
+
+define get_conf_time
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin).get('time', {}))"
+endef
+
+define get_conf_sync
+	cat "$(JSON_CONF)" | \
+		python -c "import sys, json; print json.dumps(json.load(sys.stdin).get('sync', {}))"
+endef
+
 define get_occlum_conf_file_mac
 	LD_LIBRARY_PATH="$(SGX_SDK)/sdk_libs" \
 		"$(occlum_dir)/build/bin/occlum-protect-integrity" show-mac "$(instance_dir)/build/Occlum.json.protected"
@@ -107,8 +154,17 @@ $(instance_dir)/build/Occlum.json: $(SECURE_IMAGE) $(JSON_CONF) | $(instance_dir
 		export OCCLUM_CONF_DEFAULT_STACK_SIZE=`$(get_conf_default_stack_size)` ; \
 		export OCCLUM_CONF_DEFAULT_HEAP_SIZE=`$(get_conf_default_heap_size)` ; \
 		export OCCLUM_CONF_DEFAULT_MMAP_SIZE=`$(get_conf_default_mmap_size)` ; \
+		export OCCLUM_CONF_MEM_PRESSURE_THRESHOLD=`$(get_conf_mem_pressure_threshold)` ; \
+		export OCCLUM_CONF_EXEC_ALLOWLIST="`$(get_conf_exec_allowlist)`" ; \
+		export OCCLUM_CONF_SYSCALL_DENYLIST="`$(get_conf_syscall_denylist)`" ; \
+		export OCCLUM_CONF_MOUNT="`$(get_conf_mount)`" ; \
 		export OCCLUM_CONF_ENV="`$(get_conf_env)`" ; \
 		export OCCLUM_CONF_ENTRY_POINTS=`$(get_conf_entry_points)` ; \
+		export OCCLUM_CONF_NETWORK="`$(get_conf_network)`" ; \
+		export OCCLUM_CONF_DEVICES="`$(get_conf_devices)`" ; \
+		export OCCLUM_CONF_WATCHDOG="`$(get_conf_watchdog)`" ; \
+		export OCCLUM_CONF_TIME="`$(get_conf_time)`" ; \
+		export OCCLUM_CONF_SYNC="`$(get_conf_sync)`" ; \
 		cd "$(instance_dir)/build" ; \
 		"$(occlum_dir)/build/bin/occlum-gen-default-occlum-json" > "Occlum.json"
 